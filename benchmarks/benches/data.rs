@@ -7,7 +7,8 @@ use maplibre::{
     io::apc::{Context, IntoMessage, SendError},
     style::source::TileAddressingScheme,
     vector::{
-        process_vector_tile, DefaultVectorTransferables, ProcessVectorContext, VectorTileRequest,
+        process_vector_tile, DefaultVectorTransferables, ExtentScale, ProcessVectorContext,
+        VectorTileRequest,
     },
 };
 
@@ -42,11 +43,15 @@ fn bench_process_vector_tile(c: &mut Criterion) {
                     coords: MUNICH_COORDS
                         .into_world_tile(TileAddressingScheme::XYZ)
                         .unwrap(),
+                    source: None,
                     layers: HashSet::from([
                         "transportation".to_owned(),
                         "water".to_owned(),
                         "building".to_owned(),
                     ]),
+                    tessellators: Default::default(),
+                    index: true,
+                    deadline: None,
                 },
                 &mut ProcessVectorContext::<DefaultVectorTransferables, _>::new(DummyContext),
             );
@@ -54,5 +59,30 @@ fn bench_process_vector_tile(c: &mut Criterion) {
     });
 }
 
-criterion_group!(benches, bench_process_vector_tile);
+/// Scales the coordinates of a dense tile with an extent of 8192, which has a vertex at every
+/// fourth coordinate, with the integer path and the floating point path.
+fn bench_extent_scale(c: &mut Criterion) {
+    const EXTENT: u32 = 8192;
+
+    let points: Vec<(i64, i64)> = (0..EXTENT as i64)
+        .step_by(4)
+        .flat_map(|x| (0..EXTENT as i64).step_by(4).map(move |y| (x, y)))
+        .collect();
+
+    for (name, scale) in [
+        ("extent_scale_integer", ExtentScale::new(EXTENT).unwrap()),
+        ("extent_scale_float", ExtentScale::float(EXTENT)),
+    ] {
+        c.bench_function(name, |b| {
+            b.iter(|| {
+                points
+                    .iter()
+                    .map(|point| scale.apply(*point))
+                    .fold(0, |sum, (x, y)| sum ^ x ^ y)
+            })
+        });
+    }
+}
+
+criterion_group!(benches, bench_process_vector_tile, bench_extent_scale);
 criterion_main!(benches);
@@ -24,11 +24,11 @@ fn headless_render(c: &mut Criterion) {
             let map = HeadlessMap::new(style, renderer, kernel, plugins).unwrap();
 
             let tile = map
-                .fetch_tile(WorldTileCoords::from((0, 0, ZoomLevel::default())))
+                .fetch_tile(None, WorldTileCoords::from((0, 0, ZoomLevel::default())))
                 .await
                 .expect("Failed to fetch!");
 
-            let tile = map.process_tile(tile, &["water"]).await;
+            let tile = map.process_tile(None, tile, &["water"]).await;
 
             (map, tile)
         });
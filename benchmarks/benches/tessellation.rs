@@ -0,0 +1,53 @@
+use benchmarks::{
+    fixtures::{tile_fixtures, TileFixture},
+    recording::{RecordingContext, TessellationCounts},
+};
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use maplibre::vector::{
+    process_vector_tile, DefaultVectorTransferables, ProcessVectorContext, VectorTileRequest,
+};
+
+/// Processes the tile of the `fixture` like a fetched tile, from decoding it to tessellating and
+/// indexing all of its layers.
+fn process(fixture: &TileFixture) -> TessellationCounts {
+    let mut context =
+        ProcessVectorContext::<DefaultVectorTransferables, _>::new(RecordingContext::default());
+
+    process_vector_tile(
+        &fixture.data,
+        VectorTileRequest {
+            coords: fixture.coords,
+            source: None,
+            layers: fixture.layers.clone(),
+            tessellators: Default::default(),
+            index: true,
+            deadline: None,
+        },
+        &mut context,
+    )
+    .expect("processing the fixture failed");
+
+    context.take_context().counts()
+}
+
+/// Measures the time per tile of every fixture. The throughput is reported in vertices, such that
+/// changes which reduce the amount of vertices are visible as well.
+fn bench_tessellation(c: &mut Criterion) {
+    let mut group = c.benchmark_group("tessellation");
+
+    for fixture in tile_fixtures() {
+        let counts = process(&fixture);
+        println!(
+            "{}: {} layers, {} vertices, {} indices",
+            fixture.name, counts.layers, counts.vertices, counts.indices
+        );
+
+        group.throughput(Throughput::Elements(counts.vertices as u64));
+        group.bench_function(fixture.name, |b| b.iter(|| process(&fixture)));
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_tessellation);
+criterion_main!(benches);
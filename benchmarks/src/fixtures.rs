@@ -0,0 +1,172 @@
+//! Vector tiles which are generated for the benchmarks, such that they are available without
+//! downloading any data. The tiles resemble the layers of OpenMapTiles at a low and a high zoom
+//! level.
+
+use std::{collections::HashSet, f64::consts::TAU};
+
+use geozero::mvt::{tile, Message, Tile};
+use maplibre::coords::{WorldTileCoords, ZoomLevel};
+
+const EXTENT: u32 = 4096;
+
+/// An encoded vector tile together with the layers which are processed.
+pub struct TileFixture {
+    pub name: &'static str,
+    pub coords: WorldTileCoords,
+    pub data: Vec<u8>,
+    pub layers: HashSet<String>,
+}
+
+impl TileFixture {
+    fn new(name: &'static str, coords: WorldTileCoords, layers: Vec<tile::Layer>) -> Self {
+        Self {
+            name,
+            coords,
+            layers: layers.iter().map(|layer| layer.name.clone()).collect(),
+            data: Tile { layers }.encode_to_vec(),
+        }
+    }
+}
+
+/// All fixtures, ordered from the sparsest to the densest tile.
+pub fn tile_fixtures() -> Vec<TileFixture> {
+    vec![sparse_tile(), dense_tile()]
+}
+
+/// A tile at a low zoom level with a few large polygons and long lines.
+pub fn sparse_tile() -> TileFixture {
+    let water = (0..2)
+        .map(|i| polygon(i + 1, circle((1024 + 2048 * i as i32, 2048), 900.0, 48)))
+        .collect();
+
+    let transportation = (0..5)
+        .map(|i| {
+            let y = 400 + 800 * i as i32;
+            line(
+                i + 1,
+                (0..10).map(|x| (x * 455, y + (x % 2) * 60)).collect(),
+            )
+        })
+        .collect();
+
+    TileFixture::new(
+        "sparse",
+        WorldTileCoords::from((2, 1, ZoomLevel::new(2))),
+        vec![
+            layer("water", water),
+            layer("transportation", transportation),
+        ],
+    )
+}
+
+/// A tile at a high zoom level within a city, with thousands of buildings, many streets and a
+/// detailed river bank.
+pub fn dense_tile() -> TileFixture {
+    const BUILDINGS_PER_ROW: u32 = 48;
+    const BUILDING_SPACING: u32 = EXTENT / BUILDINGS_PER_ROW;
+
+    let building = (0..BUILDINGS_PER_ROW * BUILDINGS_PER_ROW)
+        .map(|i| {
+            let x = (i % BUILDINGS_PER_ROW * BUILDING_SPACING) as i32;
+            let y = (i / BUILDINGS_PER_ROW * BUILDING_SPACING) as i32;
+            let size = BUILDING_SPACING as i32 * 2 / 3;
+            polygon(
+                i as u64 + 1,
+                vec![(x, y), (x + size, y), (x + size, y + size), (x, y + size)],
+            )
+        })
+        .collect();
+
+    let transportation = (0..128)
+        .map(|i| {
+            let offset = 16 + 32 * i as i32;
+            let points = (0..64).map(|j| (j * 64, offset + (j % 3) * 8)).collect();
+            line(i + 1, points)
+        })
+        .collect();
+
+    let water = vec![polygon(1, circle((2048, 2048), 1500.0, 512))];
+
+    TileFixture::new(
+        "dense",
+        WorldTileCoords::from((17425, 11365, ZoomLevel::new(15))),
+        vec![
+            layer("water", water),
+            layer("transportation", transportation),
+            layer("building", building),
+        ],
+    )
+}
+
+fn layer(name: &str, features: Vec<tile::Feature>) -> tile::Layer {
+    tile::Layer {
+        version: 2,
+        name: name.to_string(),
+        features,
+        keys: vec![],
+        values: vec![],
+        extent: Some(EXTENT),
+    }
+}
+
+/// A polygon with a single exterior `ring`, which is closed implicitly.
+fn polygon(id: u64, ring: Vec<(i32, i32)>) -> tile::Feature {
+    tile::Feature {
+        id: Some(id),
+        tags: vec![],
+        r#type: Some(tile::GeomType::Polygon as i32),
+        geometry: encode_path(&ring, true),
+    }
+}
+
+fn line(id: u64, points: Vec<(i32, i32)>) -> tile::Feature {
+    tile::Feature {
+        id: Some(id),
+        tags: vec![],
+        r#type: Some(tile::GeomType::Linestring as i32),
+        geometry: encode_path(&points, false),
+    }
+}
+
+/// The points of a ring around `center`. The points are ordered clockwise on the screen, which
+/// makes the ring an exterior ring.
+fn circle(center: (i32, i32), radius: f64, points: usize) -> Vec<(i32, i32)> {
+    (0..points)
+        .map(|i| {
+            let angle = TAU * i as f64 / points as f64;
+            (
+                center.0 + (radius * angle.cos()) as i32,
+                center.1 + (radius * angle.sin()) as i32,
+            )
+        })
+        .collect()
+}
+
+/// Encodes the commands of a path through `points`, see
+/// <https://github.com/mapbox/vector-tile-spec/tree/master/2.1#43-geometry-encoding>.
+fn encode_path(points: &[(i32, i32)], close: bool) -> Vec<u32> {
+    const MOVE_TO: u32 = 1;
+    const LINE_TO: u32 = 2;
+    const CLOSE_PATH: u32 = 7;
+
+    let command = |id: u32, count: usize| id | ((count as u32) << 3);
+    let zigzag = |value: i32| ((value << 1) ^ (value >> 31)) as u32;
+
+    let mut geometry = Vec::with_capacity(points.len() * 2 + 3);
+    let mut cursor = (0, 0);
+    for (i, (x, y)) in points.iter().copied().enumerate() {
+        match i {
+            0 => geometry.push(command(MOVE_TO, 1)),
+            1 => geometry.push(command(LINE_TO, points.len() - 1)),
+            _ => {}
+        }
+        geometry.push(zigzag(x - cursor.0));
+        geometry.push(zigzag(y - cursor.1));
+        cursor = (x, y);
+    }
+
+    if close {
+        geometry.push(command(CLOSE_PATH, 1));
+    }
+    geometry
+}
@@ -1 +1,5 @@
 #![deny(unused_imports)]
+//! Fixtures and utilities which are shared by the benchmarks.
+
+pub mod fixtures;
+pub mod recording;
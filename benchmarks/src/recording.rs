@@ -0,0 +1,50 @@
+//! A [`Context`] for [`process_vector_tile`](maplibre::vector::process_vector_tile) which records
+//! what has been tessellated instead of passing it to a map.
+
+use std::cell::Cell;
+
+use maplibre::{
+    io::apc::{Context, IntoMessage, SendError},
+    vector::{DefaultVectorTransferables, LayerTessellated, VectorTransferables},
+};
+
+type Tessellated = <DefaultVectorTransferables as VectorTransferables>::LayerTessellated;
+
+/// The amount of geometry which has been tessellated for a tile.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TessellationCounts {
+    pub layers: usize,
+    pub vertices: usize,
+    pub indices: usize,
+}
+
+/// Counts the geometry of the tessellated layers. All other messages are dropped.
+#[derive(Default)]
+pub struct RecordingContext {
+    counts: Cell<TessellationCounts>,
+}
+
+impl RecordingContext {
+    pub fn counts(&self) -> TessellationCounts {
+        self.counts.get()
+    }
+}
+
+impl Context for RecordingContext {
+    fn send<T: IntoMessage>(&self, message: T) -> Result<(), SendError> {
+        let message = IntoMessage::into(message);
+        if !message.has_tag(Tessellated::message_tag()) {
+            return Ok(());
+        }
+
+        let layer = message.into_transferable::<Tessellated>().to_layer();
+
+        let mut counts = self.counts.get();
+        counts.layers += 1;
+        counts.vertices += layer.buffer.buffer.vertices.len();
+        counts.indices += layer.buffer.usable_indices as usize;
+        self.counts.set(counts);
+
+        Ok(())
+    }
+}
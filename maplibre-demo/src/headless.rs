@@ -44,10 +44,11 @@ pub async fn run_headless(tile_size: u32, min: LatLon, max: LatLon) {
         let coords = WorldTileCoords::from((x as i32, y as i32, z.into()));
         println!("Rendering {coords}");
 
-        let tile = map.fetch_tile(coords).await.expect("Failed to fetch!");
+        let tile = map.fetch_tile(None, coords).await.expect("Failed to fetch!");
 
         let layers = map
             .process_tile(
+                None,
                 tile,
                 &requested_layers
                     .iter()
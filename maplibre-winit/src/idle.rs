@@ -0,0 +1,111 @@
+//! Decides when the event loop stops rendering, see [`WinitMapWindowConfig::with_idle_timeout`].
+//!
+//! [`WinitMapWindowConfig::with_idle_timeout`]: crate::WinitMapWindowConfig::with_idle_timeout
+
+use std::time::Duration;
+
+use instant::Instant;
+
+/// Tracks the last activity of the map. The map idles once nothing happened for the timeout.
+pub(crate) struct IdleTimer {
+    timeout: Option<Duration>,
+    last_activity: Instant,
+    idle: bool,
+}
+
+impl IdleTimer {
+    pub fn new(timeout: Option<Duration>, now: Instant) -> Self {
+        Self {
+            timeout,
+            last_activity: now,
+            idle: false,
+        }
+    }
+
+    /// Records an event at `now` which wakes the map up. Returns whether the map was idling, such
+    /// that rendering has to be resumed.
+    pub fn wake(&mut self, now: Instant) -> bool {
+        self.last_activity = now;
+        std::mem::replace(&mut self.idle, false)
+    }
+
+    /// Records that the map changed in the frame rendered at `now`, e.g. because the camera moved
+    /// or tiles are still loading.
+    pub fn keep_active(&mut self, now: Instant) {
+        self.last_activity = now;
+    }
+
+    /// Whether the map stops rendering at `now` until the next event.
+    pub fn should_idle(&mut self, now: Instant) -> bool {
+        let Some(timeout) = self.timeout else { return false; };
+
+        if now.saturating_duration_since(self.last_activity) < timeout {
+            return false;
+        }
+
+        if !self.idle {
+            log::info!("Idling until the next event");
+            self.idle = true;
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use instant::Instant;
+
+    use super::IdleTimer;
+
+    /// The count of frames which are rendered within 10 seconds at 60 frames per second after the
+    /// map has been opened, if tiles are loading until `loading_until`
+    fn rendered_frames(loading_until: Option<Duration>) -> usize {
+        let start = Instant::now();
+        let mut timer = IdleTimer::new(Some(Duration::from_secs(1)), start);
+
+        let mut rendered = 0;
+        for frame in 0..600 {
+            let now = start + Duration::from_secs(frame) / 60;
+            // Without an event, nothing is rendered anymore
+            if timer.should_idle(now) {
+                break;
+            }
+
+            rendered += 1;
+            if loading_until.map_or(true, |until| now - start < until) {
+                timer.keep_active(now);
+            }
+        }
+        rendered
+    }
+
+    #[test]
+    fn test_idle_after_tiles_finished() {
+        // Tiles which never finish loading, e.g. because their fetch failed, keep the map
+        // rendering
+        assert_eq!(rendered_frames(None), 600);
+
+        // Once failed tiles are finished, the map idles a second later
+        assert_eq!(rendered_frames(Some(Duration::from_millis(500))), 89);
+    }
+
+    #[test]
+    fn test_wake() {
+        let start = Instant::now();
+        let mut timer = IdleTimer::new(Some(Duration::from_secs(1)), start);
+
+        assert!(!timer.should_idle(start + Duration::from_millis(999)));
+        assert!(timer.should_idle(start + Duration::from_secs(1)));
+
+        let now = start + Duration::from_secs(2);
+        assert!(timer.wake(now));
+        assert!(!timer.wake(now));
+        assert!(!timer.should_idle(now));
+
+        // Without a timeout, the map never idles
+        let mut timer = IdleTimer::new(None, start);
+        assert!(!timer.should_idle(start + Duration::from_secs(60)));
+    }
+}
@@ -3,17 +3,19 @@
 use std::time::Duration;
 
 use cgmath::Vector2;
-use maplibre::context::MapContext;
+use maplibre::{context::MapContext, render::viewport::Viewport};
 use winit::event::{DeviceEvent, KeyboardInput, TouchPhase, WindowEvent};
 
 use crate::input::{
     pan_handler::PanHandler, pinch_handler::PinchHandler, query_handler::QueryHandler,
-    shift_handler::ShiftHandler, tilt_handler::TiltHandler, zoom_handler::ZoomHandler,
+    rotate_handler::RotateHandler, shift_handler::ShiftHandler, tilt_handler::TiltHandler,
+    zoom_handler::ZoomHandler,
 };
 
 mod pan_handler;
 mod pinch_handler;
 mod query_handler;
+mod rotate_handler;
 mod shift_handler;
 mod tilt_handler;
 mod zoom_handler;
@@ -23,8 +25,11 @@ pub struct InputController {
     pan_handler: PanHandler,
     zoom_handler: ZoomHandler,
     tilt_handler: TiltHandler,
+    rotate_handler: RotateHandler,
     shift_handler: ShiftHandler,
     query_handler: QueryHandler,
+    /// The viewport of the renderer, which window positions are mapped into
+    viewport: Option<Viewport>,
 }
 
 impl InputController {
@@ -44,27 +49,41 @@ impl InputController {
             pan_handler: PanHandler::new(),
             zoom_handler: ZoomHandler::new(zoom_sensitivity),
             tilt_handler: TiltHandler::new(speed, sensitivity),
+            rotate_handler: RotateHandler::new(speed, sensitivity),
             shift_handler: ShiftHandler::new(speed, sensitivity),
             query_handler: QueryHandler::new(),
+            viewport: None,
         }
     }
 
+    /// Sets the duration over which discrete zoom changes, like wheel clicks or key presses, are
+    /// animated. A duration of zero changes the zoom instantly.
+    pub fn set_zoom_animation_duration(&mut self, duration: Duration) {
+        self.zoom_handler.set_animation_duration(duration);
+    }
+
     pub fn device_input(&mut self, _event: &DeviceEvent) -> bool {
         false
     }
 
+    /// Maps a position within the window to a position within the viewport of the map.
+    fn to_viewport(&self, position: (f64, f64)) -> Vector2<f64> {
+        let (x, y) = match &self.viewport {
+            Some(viewport) => viewport.window_to_viewport(position.0, position.1),
+            None => position,
+        };
+        Vector2::new(x, y)
+    }
+
     /// Process the given winit `[winit::event::WindowEvent]`.
     /// Returns true if the event has been processed and false otherwise.
     pub fn window_input(&mut self, event: &WindowEvent) -> bool {
         match event {
             WindowEvent::CursorMoved { position, .. } => {
-                let position: (f64, f64) = position.to_owned().into();
-                self.pan_handler
-                    .process_window_position(&Vector2::from(position), false);
-                self.query_handler
-                    .process_window_position(&Vector2::from(position), false);
-                self.zoom_handler
-                    .process_window_position(&Vector2::from(position), false);
+                let position = self.to_viewport(position.to_owned().into());
+                self.pan_handler.process_window_position(&position, false);
+                self.query_handler.process_window_position(&position, false);
+                self.zoom_handler.process_window_position(&position, false);
                 true
             }
             WindowEvent::KeyboardInput {
@@ -78,7 +97,11 @@ impl InputController {
             } => {
                 if !self.shift_handler.process_key_press(*key, *state) {
                     if !self.tilt_handler.process_key_press(*key, *state) {
-                        self.zoom_handler.process_key_press(*key, *state)
+                        if !self.rotate_handler.process_key_press(*key, *state) {
+                            self.zoom_handler.process_key_press(*key, *state)
+                        } else {
+                            false
+                        }
                     } else {
                         false
                     }
@@ -88,9 +111,8 @@ impl InputController {
             }
             WindowEvent::Touch(touch) => match touch.phase {
                 TouchPhase::Started => {
-                    let position: (f64, f64) = touch.location.to_owned().into();
-                    self.pan_handler
-                        .process_touch_start(&Vector2::from(position));
+                    let position = self.to_viewport(touch.location.to_owned().into());
+                    self.pan_handler.process_touch_start(&position);
                     self.query_handler.process_touch_start();
                     true
                 }
@@ -100,13 +122,10 @@ impl InputController {
                     true
                 }
                 TouchPhase::Moved => {
-                    let position: (f64, f64) = touch.location.to_owned().into();
-                    self.pan_handler
-                        .process_window_position(&Vector2::from(position), true);
-                    self.query_handler
-                        .process_window_position(&Vector2::from(position), true);
-                    self.zoom_handler
-                        .process_window_position(&Vector2::from(position), true);
+                    let position = self.to_viewport(touch.location.to_owned().into());
+                    self.pan_handler.process_window_position(&position, true);
+                    self.query_handler.process_window_position(&position, true);
+                    self.zoom_handler.process_window_position(&position, true);
                     true
                 }
                 TouchPhase::Cancelled => false,
@@ -131,10 +150,17 @@ pub trait UpdateState {
 
 impl UpdateState for InputController {
     fn update_state(&mut self, map_context: &mut MapContext, dt: Duration) {
+        self.viewport = map_context.renderer.resources.clamped_viewport();
+
+        if !map_context.world.is_frozen() {
+            map_context.view_state.advance_animation(dt);
+        }
+
         self.pan_handler.update_state(map_context, dt);
         self.pinch_handler.update_state(map_context, dt);
         self.zoom_handler.update_state(map_context, dt);
         self.tilt_handler.update_state(map_context, dt);
+        self.rotate_handler.update_state(map_context, dt);
         self.shift_handler.update_state(map_context, dt);
         self.query_handler.update_state(map_context, dt);
     }
@@ -0,0 +1,57 @@
+use std::time::Duration;
+
+use cgmath::{Deg, Zero};
+use maplibre::context::MapContext;
+
+use super::UpdateState;
+
+/// Changes the bearing of the map with the Q and E keys.
+pub struct RotateHandler {
+    delta_bearing: Deg<f64>,
+
+    speed: f64,
+    sensitivity: f64,
+}
+
+impl UpdateState for RotateHandler {
+    fn update_state(&mut self, MapContext { view_state, .. }: &mut MapContext, dt: Duration) {
+        let dt = dt.as_secs_f64() * (1.0 / self.speed);
+
+        let delta = self.delta_bearing * dt;
+        view_state.camera_mut().rotate_bearing(delta);
+        self.delta_bearing -= delta;
+    }
+}
+
+impl RotateHandler {
+    pub fn new(speed: f64, sensitivity: f64) -> Self {
+        Self {
+            delta_bearing: Deg::zero(),
+            speed,
+            sensitivity,
+        }
+    }
+
+    pub fn process_key_press(
+        &mut self,
+        key: winit::event::VirtualKeyCode,
+        state: winit::event::ElementState,
+    ) -> bool {
+        let amount = if state == winit::event::ElementState::Pressed {
+            Deg(self.sensitivity)
+        } else {
+            Deg::zero()
+        };
+        match key {
+            winit::event::VirtualKeyCode::Q => {
+                self.delta_bearing -= amount;
+                true
+            }
+            winit::event::VirtualKeyCode::E => {
+                self.delta_bearing += amount;
+                true
+            }
+            _ => false,
+        }
+    }
+}
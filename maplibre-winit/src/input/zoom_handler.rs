@@ -1,45 +1,97 @@
 use std::time::Duration;
 
 use cgmath::{Vector2, Vector3};
-use maplibre::{context::MapContext, coords::Zoom};
+use maplibre::{context::MapContext, coords::Zoom, view_state::ViewState};
 
 use super::UpdateState;
 
+/// The default duration of the animation of discrete zoom changes.
+const DEFAULT_ANIMATION_DURATION: Duration = Duration::from_millis(150);
+
+/// Eases the zoom from `from` to `to` over the `duration`.
+struct ZoomAnimation {
+    from: Zoom,
+    to: Zoom,
+    duration: Duration,
+    elapsed: Duration,
+}
+
+impl ZoomAnimation {
+    /// Advances the animation by `dt` and returns the current zoom.
+    fn advance(&mut self, dt: Duration) -> Zoom {
+        self.elapsed += dt;
+
+        if self.is_finished() {
+            return self.to;
+        }
+
+        // Ease-out: The zoom changes quickly at first and slows down towards the target
+        let t = self.elapsed.as_secs_f64() / self.duration.as_secs_f64();
+        self.from.lerp(&self.to, 1.0 - (1.0 - t).powi(3))
+    }
+
+    fn is_finished(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+}
+
 pub struct ZoomHandler {
     window_position: Option<Vector2<f64>>,
+    /// Discrete zoom changes, like wheel clicks or key presses, which are animated
     zoom_delta: Option<Zoom>,
+    /// Continuous zoom changes, like trackpad scrolling, which follow the input directly
+    continuous_zoom_delta: Option<Zoom>,
     sensitivity: f64,
+    animation_duration: Duration,
+    animation: Option<ZoomAnimation>,
 }
 
 impl UpdateState for ZoomHandler {
-    fn update_state(&mut self, MapContext { view_state, .. }: &mut MapContext, _dt: Duration) {
-        if let Some(zoom_delta) = self.zoom_delta {
-            if let Some(window_position) = self.window_position {
-                let current_zoom = view_state.zoom();
-                let next_zoom = current_zoom + zoom_delta;
-
-                view_state.update_zoom(next_zoom);
-                self.zoom_delta = None;
-
-                let view_proj = view_state.view_projection();
-                let inverted_view_proj = view_proj.invert();
-
-                if let Some(cursor_position) = view_state.camera().window_to_world_at_ground(
-                    &window_position,
-                    &inverted_view_proj,
-                    false,
-                ) {
-                    let scale = current_zoom.scale_delta(&next_zoom);
-
-                    let delta = Vector3::new(
-                        cursor_position.x * scale,
-                        cursor_position.y * scale,
-                        cursor_position.z,
-                    ) - cursor_position;
-
-                    view_state.camera_mut().move_relative(delta);
-                }
+    fn update_state(&mut self, MapContext { view_state, .. }: &mut MapContext, dt: Duration) {
+        let Some(window_position) = self.window_position else { return; };
+
+        if let Some(zoom_delta) = self.continuous_zoom_delta.take() {
+            // A running animation is shifted, such that it does not undo the change
+            if let Some(animation) = &mut self.animation {
+                animation.from = animation.from + zoom_delta;
+                animation.to = animation.to + zoom_delta;
             }
+
+            let current_zoom = view_state.zoom();
+            Self::zoom_at(view_state, &window_position, current_zoom + zoom_delta);
+        }
+
+        if let Some(zoom_delta) = self.zoom_delta.take() {
+            let current_zoom = view_state.zoom();
+
+            if self.animation_duration.is_zero() || view_state.reduced_motion() {
+                Self::zoom_at(view_state, &window_position, current_zoom + zoom_delta);
+            } else {
+                // Further input while animating continues from the current target
+                let target = self
+                    .animation
+                    .as_ref()
+                    .map_or(current_zoom, |animation| animation.to);
+
+                self.animation = Some(ZoomAnimation {
+                    from: current_zoom,
+                    to: target + zoom_delta,
+                    duration: self.animation_duration,
+                    elapsed: Duration::ZERO,
+                });
+                // The animation starts with this frame
+                return;
+            }
+        }
+
+        if let Some(animation) = &mut self.animation {
+            let next_zoom = animation.advance(dt);
+
+            if animation.is_finished() {
+                self.animation = None;
+            }
+
+            Self::zoom_at(view_state, &window_position, next_zoom);
         }
     }
 }
@@ -49,7 +101,41 @@ impl ZoomHandler {
         Self {
             window_position: None,
             zoom_delta: None,
+            continuous_zoom_delta: None,
             sensitivity,
+            animation_duration: DEFAULT_ANIMATION_DURATION,
+            animation: None,
+        }
+    }
+
+    /// Sets the duration over which discrete zoom changes are animated. A duration of zero
+    /// changes the zoom instantly.
+    pub fn set_animation_duration(&mut self, duration: Duration) {
+        self.animation_duration = duration;
+    }
+
+    /// Changes the zoom to `next_zoom` while keeping the position below the cursor in place.
+    fn zoom_at(view_state: &mut ViewState, window_position: &Vector2<f64>, next_zoom: Zoom) {
+        let current_zoom = view_state.zoom();
+        view_state.update_zoom(next_zoom);
+
+        let view_proj = view_state.view_projection();
+        let inverted_view_proj = view_proj.invert();
+
+        if let Some(cursor_position) = view_state.camera().window_to_world_at_ground(
+            window_position,
+            &inverted_view_proj,
+            false,
+        ) {
+            let scale = current_zoom.scale_delta(&next_zoom);
+
+            let delta = Vector3::new(
+                cursor_position.x * scale,
+                cursor_position.y * scale,
+                cursor_position.z,
+            ) - cursor_position;
+
+            view_state.camera_mut().move_relative(delta);
         }
     }
 
@@ -66,18 +152,22 @@ impl ZoomHandler {
         self.zoom_delta = Some(self.zoom_delta.unwrap_or_default() + Zoom::new(delta));
     }
 
+    /// Wheels scroll by lines, which are animated like other discrete zoom changes. Trackpads
+    /// scroll by pixels in many small steps, which are applied directly.
     pub fn process_scroll(&mut self, delta: &winit::event::MouseScrollDelta) {
-        self.update_zoom(
-            match delta {
-                winit::event::MouseScrollDelta::LineDelta(_horizontal, vertical) => {
-                    *vertical as f64
-                }
-                winit::event::MouseScrollDelta::PixelDelta(winit::dpi::PhysicalPosition {
-                    y: scroll,
-                    ..
-                }) => *scroll / 100.0,
-            } * self.sensitivity,
-        );
+        match delta {
+            winit::event::MouseScrollDelta::LineDelta(_horizontal, vertical) => {
+                self.update_zoom(*vertical as f64 * self.sensitivity)
+            }
+            winit::event::MouseScrollDelta::PixelDelta(winit::dpi::PhysicalPosition {
+                y: scroll,
+                ..
+            }) => {
+                let delta = Zoom::new(*scroll / 100.0 * self.sensitivity);
+                self.continuous_zoom_delta =
+                    Some(self.continuous_zoom_delta.unwrap_or_default() + delta);
+            }
+        }
     }
 
     pub fn process_key_press(
@@ -104,3 +194,60 @@ impl ZoomHandler {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use maplibre::coords::Zoom;
+
+    use super::{ZoomAnimation, ZoomHandler};
+
+    #[test]
+    fn test_zoom_animation() {
+        let mut animation = ZoomAnimation {
+            from: Zoom::new(10.0),
+            to: Zoom::new(11.3),
+            duration: Duration::from_millis(150),
+            elapsed: Duration::ZERO,
+        };
+
+        let mut previous = Zoom::new(10.0);
+        for step in 1..15 {
+            let zoom = animation.advance(Duration::from_millis(10));
+            assert!(previous.scale_delta(&zoom) > 1.0);
+            assert!(!animation.is_finished());
+
+            // Ease-out: more than half of the distance is covered after half of the duration
+            if step == 8 {
+                assert!(Zoom::new(10.65).scale_delta(&zoom) > 1.0);
+            }
+            previous = zoom;
+        }
+
+        assert_eq!(
+            animation.advance(Duration::from_millis(10)),
+            Zoom::new(11.3)
+        );
+        assert!(animation.is_finished());
+    }
+
+    #[test]
+    fn test_scroll() {
+        let mut handler = ZoomHandler::new(2.0);
+
+        // Wheel clicks are animated
+        handler.process_scroll(&winit::event::MouseScrollDelta::LineDelta(0.0, 1.0));
+        assert_eq!(handler.zoom_delta, Some(Zoom::new(2.0)));
+        assert_eq!(handler.continuous_zoom_delta, None);
+
+        // Trackpad scrolling is accumulated until the next frame and applied directly
+        for _ in 0..2 {
+            handler.process_scroll(&winit::event::MouseScrollDelta::PixelDelta(
+                winit::dpi::PhysicalPosition::new(0.0, 25.0),
+            ));
+        }
+        assert_eq!(handler.zoom_delta, Some(Zoom::new(2.0)));
+        assert_eq!(handler.continuous_zoom_delta, Some(Zoom::new(1.0)));
+    }
+}
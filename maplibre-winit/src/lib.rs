@@ -1,13 +1,18 @@
 #![deny(unused_imports)]
 
-use std::{fmt::Debug, marker::PhantomData};
+use std::{fmt::Debug, marker::PhantomData, time::Duration};
 
 use instant::Instant;
 use maplibre::{
+    context::MapContext,
     environment::{Environment, OffscreenKernelEnvironment},
     event_loop::{EventLoop, EventLoopProxy, SendEventError},
-    io::{apc::AsyncProcedureCall, scheduler::Scheduler, source_client::HttpClient},
-    map::Map,
+    io::{
+        apc::AsyncProcedureCall, request_settings::RequestSettings, scheduler::Scheduler,
+        source_client::HttpClient,
+    },
+    map::{Map, MapError},
+    render::device_lost::DeviceLostReason,
     window::{HeadedMapWindow, MapWindowConfig},
 };
 use winit::{
@@ -15,8 +20,12 @@ use winit::{
     event_loop::ControlFlow,
 };
 
-use crate::input::{InputController, UpdateState};
+use crate::{
+    idle::IdleTimer,
+    input::{InputController, UpdateState},
+};
 
+mod idle;
 pub mod input;
 
 pub type RawWinitWindow = winit::window::Window;
@@ -63,6 +72,10 @@ impl<ET> HeadedMapWindow for WinitMapWindow<ET> {
 
 pub struct WinitEventLoop<ET: 'static> {
     event_loop: RawWinitEventLoop<ET>,
+    /// See [`WinitMapWindowConfig::with_idle_timeout`]
+    idle_timeout: Option<Duration>,
+    /// The scale factor of the window when it has been created
+    scale_factor: f64,
 }
 
 impl<ET: 'static + PartialEq + Debug> EventLoop<ET> for WinitEventLoop<ET> {
@@ -78,6 +91,13 @@ impl<ET: 'static + PartialEq + Debug> EventLoop<ET> for WinitEventLoop<ET> {
 
         let mut input_controller = InputController::new(0.2, 100.0, 0.1);
 
+        let mut idle_timer = IdleTimer::new(self.idle_timeout, Instant::now());
+
+        if let Ok(map_context) = map.context_mut() {
+            set_pixel_ratio(map_context, self.scale_factor);
+        }
+        let mut scale_factor = self.scale_factor;
+
         self.event_loop
             .run(move |event, _window_target, control_flow| {
                 #[cfg(target_os = "android")]
@@ -89,9 +109,23 @@ impl<ET: 'static + PartialEq + Debug> EventLoop<ET> for WinitEventLoop<ET> {
                             map.initialize_renderer().await.unwrap();
                         })
                     });
+                    if let Ok(map_context) = map.context_mut() {
+                        set_pixel_ratio(map_context, scale_factor);
+                    }
                     return;
                 }
 
+                // Input, resizes and events sent through the proxy wake the loop up from idling
+                if matches!(
+                    event,
+                    Event::WindowEvent { .. } | Event::UserEvent(_) | Event::Resumed
+                ) {
+                    if idle_timer.wake(Instant::now()) {
+                        log::info!("Resuming rendering");
+                        *control_flow = ControlFlow::Poll;
+                    }
+                }
+
                 match event {
                     Event::DeviceEvent {
                         ref event,
@@ -121,9 +155,11 @@ impl<ET: 'static + PartialEq + Debug> EventLoop<ET> for WinitEventLoop<ET> {
                                         map_context.resize(physical_size.width, physical_size.height);
                                     }
                                 }
-                                WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
+                                WindowEvent::ScaleFactorChanged { scale_factor: new_scale_factor, new_inner_size } => {
+                                    scale_factor = *new_scale_factor;
                                     if let Ok(map_context) =  map.context_mut() {
                                         map_context.resize(new_inner_size.width, new_inner_size.height);
+                                        set_pixel_ratio(map_context, scale_factor);
                                     }
                                 }
                                 _ => {}
@@ -137,10 +173,28 @@ impl<ET: 'static + PartialEq + Debug> EventLoop<ET> for WinitEventLoop<ET> {
 
                         if let Ok(map_context) =  map.context_mut() {
                             input_controller.update_state(map_context, dt);
+
+                            // The camera can keep moving without input, e.g. while a key is held
+                            // or an animation is running
+                            let view_state = &map_context.view_state;
+                            if view_state.did_camera_change()
+                                || view_state.did_zoom_change()
+                                || view_state.is_animating()
+                                || map_context.world.tiles.has_loading_tiles()
+                            {
+                                idle_timer.keep_active(now);
+                            }
                         }
 
-                        // TODO: Maybe handle gracefully
-                        map.run_schedule().expect("Failed to run schedule!");
+                        match map.run_schedule() {
+                            Err(MapError::DeviceLost(reason)) => {
+                                if !recover_device(&mut map, reason) {
+                                    *control_flow = ControlFlow::Exit;
+                                }
+                            }
+                            // TODO: Maybe handle gracefully
+                            result => result.expect("Failed to run schedule!"),
+                        }
 
                         if let Some(max_frames) = max_frames {
                             if current_frame >= max_frames {
@@ -158,9 +212,13 @@ impl<ET: 'static + PartialEq + Debug> EventLoop<ET> for WinitEventLoop<ET> {
                         // FIXME unimplemented!()
                     }
                     Event::MainEventsCleared => {
-                        // RedrawRequested will only trigger once, unless we manually
-                        // request it.
-                        map.window().request_redraw();
+                        if idle_timer.should_idle(Instant::now()) {
+                            *control_flow = ControlFlow::Wait;
+                        } else {
+                            // RedrawRequested will only trigger once, unless we manually
+                            // request it.
+                            map.window().request_redraw();
+                        }
                     }
                     _ => {}
                 }
@@ -173,6 +231,40 @@ impl<ET: 'static + PartialEq + Debug> EventLoop<ET> for WinitEventLoop<ET> {
         }
     }
 }
+/// Requests raster tiles for the `scale_factor` of the window, e.g. `@2x` tiles on high-DPI
+/// displays, see [`RequestSettings::pixel_ratio`].
+fn set_pixel_ratio(map_context: &mut MapContext, scale_factor: f64) {
+    map_context
+        .world
+        .resources
+        .get_or_init_mut::<RequestSettings>()
+        .pixel_ratio = scale_factor;
+}
+
+/// Tries once to create the device again after it has been lost. Returns whether the map can
+/// continue to render.
+fn recover_device<E>(map: &mut Map<E>, reason: DeviceLostReason) -> bool
+where
+    E: Environment,
+    <E::MapWindowConfig as MapWindowConfig>::MapWindow: HeadedMapWindow,
+{
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        use tokio::{runtime::Handle, task};
+
+        task::block_in_place(|| Handle::current().block_on(map.recover_device(reason))).is_ok()
+    }
+
+    // The event loop can not wait for a new device in the browser
+    #[cfg(target_arch = "wasm32")]
+    {
+        if let Ok(map_context) = map.context() {
+            map_context.renderer.device_lost.notify(reason);
+        }
+        false
+    }
+}
+
 pub struct WinitEventLoopProxy<ET: 'static> {
     proxy: RawEventLoopProxy<ET>,
 }
@@ -202,7 +294,7 @@ pub struct WinitEnvironment<
 impl<
         S: Scheduler,
         HC: HttpClient,
-        K: OffscreenKernelEnvironment,
+        K: OffscreenKernelEnvironment<HttpClient = HC>,
         APC: AsyncProcedureCall<K>,
         ET: 'static,
     > Environment for WinitEnvironment<S, HC, K, APC, ET>
@@ -3,7 +3,7 @@
 //! * Platform Events like suspend/resume
 //! * Render a new frame
 
-use std::marker::PhantomData;
+use std::{marker::PhantomData, time::Duration};
 
 use maplibre::{
     debug::DebugPlugin,
@@ -11,6 +11,7 @@ use maplibre::{
     io::apc::SchedulerAsyncProcedureCall,
     kernel::{Kernel, KernelBuilder},
     map::Map,
+    overlay::OverlayPlugin,
     platform::{
         http_client::ReqwestHttpClient, run_multithreaded, scheduler::TokioScheduler,
         ReqwestOffscreenKernelEnvironment,
@@ -27,6 +28,7 @@ use crate::{WinitEnvironment, WinitEventLoop};
 
 pub struct WinitMapWindowConfig<ET> {
     title: String,
+    idle_timeout: Option<Duration>,
 
     phantom_et: PhantomData<ET>,
 }
@@ -35,9 +37,18 @@ impl<ET> WinitMapWindowConfig<ET> {
     pub fn new(title: String) -> Self {
         Self {
             title,
+            idle_timeout: None,
             phantom_et: Default::default(),
         }
     }
+
+    /// Stops rendering once the map has been idle for `timeout`, i.e. no input arrived, the
+    /// camera did not move and no tiles were loading. The event loop then waits for the next
+    /// event, which saves power while the map is not interacted with. Disabled by default.
+    pub fn with_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = Some(timeout);
+        self
+    }
 }
 
 impl<ET> MapWindow for WinitMapWindow<ET> {
@@ -67,10 +78,12 @@ impl<ET: 'static> MapWindowConfig for WinitMapWindowConfig<ET> {
             .unwrap();
 
         Self::MapWindow {
-            window,
             event_loop: Some(WinitEventLoop {
                 event_loop: raw_event_loop,
+                idle_timeout: self.idle_timeout,
+                scale_factor: window.scale_factor(),
             }),
+            window,
         }
     }
 }
@@ -103,6 +116,7 @@ pub fn run_headed_map(cache_path: Option<String>) {
                 //Box::new(VectorPlugin::<DefaultVectorTransferables>::default()),
                 Box::new(RasterPlugin::<DefaultRasterTransferables>::default()),
                 Box::new(DebugPlugin::default()),
+                Box::new(OverlayPlugin::default()),
             ],
         )
         .unwrap();
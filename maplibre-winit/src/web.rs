@@ -1,4 +1,4 @@
-use std::marker::PhantomData;
+use std::{marker::PhantomData, time::Duration};
 
 use maplibre::window::{MapWindow, MapWindowConfig, WindowSize};
 use winit::{platform::web::WindowBuilderExtWebSys, window::WindowBuilder};
@@ -8,6 +8,7 @@ use crate::WinitEventLoop;
 
 pub struct WinitMapWindowConfig<ET> {
     canvas_id: String,
+    idle_timeout: Option<Duration>,
     phantom_et: PhantomData<ET>,
 }
 
@@ -15,9 +16,18 @@ impl<ET: 'static> WinitMapWindowConfig<ET> {
     pub fn new(canvas_id: String) -> Self {
         Self {
             canvas_id,
+            idle_timeout: None,
             phantom_et: Default::default(),
         }
     }
+
+    /// Stops rendering once the map has been idle for `timeout`, i.e. no input arrived, the
+    /// camera did not move and no tiles were loading. The event loop then waits for the next
+    /// event, which saves power while the map is not interacted with. Disabled by default.
+    pub fn with_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = Some(timeout);
+        self
+    }
 }
 
 impl<ET: 'static> MapWindowConfig for WinitMapWindowConfig<ET> {
@@ -34,10 +44,12 @@ impl<ET: 'static> MapWindowConfig for WinitMapWindowConfig<ET> {
         let size = get_body_size().unwrap();
         window.set_inner_size(size);
         Self::MapWindow {
-            window,
             event_loop: Some(WinitEventLoop {
                 event_loop: raw_event_loop,
+                idle_timeout: self.idle_timeout,
+                scale_factor: window.scale_factor(),
             }),
+            window,
         }
     }
 }
@@ -1,4 +1,9 @@
-use crate::{render::Renderer, style::Style, tcs::world::World, view_state::ViewState};
+use crate::{
+    render::{viewport::Viewport, Renderer},
+    style::Style,
+    tcs::world::World,
+    view_state::ViewState,
+};
 
 /// Stores the context of the map.
 ///
@@ -13,7 +18,19 @@ pub struct MapContext {
 
 impl MapContext {
     pub fn resize(&mut self, width: u32, height: u32) {
-        self.view_state.resize(width, height);
-        self.renderer.resize_surface(width, height)
+        self.renderer.resize_surface(width, height);
+        self.resize_camera();
+    }
+
+    /// Renders the map into the `viewport` of the surface and adjusts the camera to the size of
+    /// the viewport. See [`Renderer::set_viewport`].
+    pub fn set_viewport(&mut self, viewport: Option<Viewport>) {
+        self.renderer.set_viewport(viewport);
+        self.resize_camera();
+    }
+
+    fn resize_camera(&mut self) {
+        let size = self.renderer.resources.render_area_size();
+        self.view_state.resize(size.width(), size.height());
     }
 }
@@ -1,7 +1,6 @@
 //! Provides utilities related to coordinates.
 
 use std::{
-    f64::consts::PI,
     fmt,
     fmt::{Display, Formatter},
 };
@@ -11,6 +10,7 @@ use cgmath::{AbsDiffEq, Matrix4, Point3, Vector3};
 use serde::{Deserialize, Serialize};
 
 use crate::{
+    projection::{Projection, WebMercator},
     style::source::TileAddressingScheme,
     util::{
         math::{div_floor, Aabb2},
@@ -69,6 +69,17 @@ impl fmt::Debug for Quadkey {
     }
 }
 
+/// Formats the quad key as digits from the lowest to the highest zoom level, as used by Bing Maps.
+impl fmt::Display for Quadkey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let ZoomLevel(len) = self.0[0];
+        for ZoomLevel(digit) in self.0[1..=len as usize].iter().rev() {
+            write!(f, "{digit}")?;
+        }
+        Ok(())
+    }
+}
+
 // FIXME: does Pod and Zeroable make sense?
 #[derive(
     Ord,
@@ -165,7 +176,7 @@ impl Display for LatLon {
 
 /// `Zoom` is an exponential scale that defines the zoom of the camera on the map.
 /// We can derive the `ZoomLevel` from `Zoom` by using the `[crate::coords::ZOOM_BOUNDS]`.
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct Zoom(f64);
 
 impl Zoom {
@@ -221,6 +232,12 @@ impl Zoom {
         2.0_f64.powf(zoom.0 - self.0)
     }
 
+    /// Linearly interpolates between this zoom and `to`. A `t` of `0.0` yields this zoom and a `t`
+    /// of `1.0` yields `to`.
+    pub fn lerp(&self, to: &Zoom, t: f64) -> Zoom {
+        Zoom(self.0 + (to.0 - self.0) * t)
+    }
+
     pub fn level(&self) -> ZoomLevel {
         ZoomLevel::from(self.0.floor() as u8)
     }
@@ -535,19 +552,14 @@ pub struct WorldCoords {
 }
 
 impl WorldCoords {
+    /// Projects `lat_lon` using the [`WebMercator`] projection.
     pub fn from_lat_lon(lat_lon: LatLon, zoom: Zoom) -> WorldCoords {
-        let tile_size = TILE_SIZE * 2.0_f64.powf(zoom.0);
-        // Get x value
-        let x = (lat_lon.longitude + 180.0) * (tile_size / 360.0);
-
-        // Convert from degrees to radians
-        let lat_rad = (lat_lon.latitude * PI) / 180.0;
-
-        // get y value
-        let merc_n = f64::ln(f64::tan((PI / 4.0) + (lat_rad / 2.0)));
-        let y = (tile_size / 2.0) - (tile_size * merc_n / (2.0 * PI));
+        WebMercator.project(lat_lon, zoom)
+    }
 
-        WorldCoords { x, y }
+    /// Inverse of [`WorldCoords::from_lat_lon`].
+    pub fn to_lat_lon(&self, zoom: Zoom) -> LatLon {
+        WebMercator.unproject(*self, zoom)
     }
 
     pub fn at_ground(x: f64, y: f64) -> Self {
@@ -595,7 +607,7 @@ impl From<Point3<f64>> for WorldCoords {
 }
 
 /// Defines a bounding box on a tiled map with a [`ZoomLevel`] and a padding.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct ViewRegion {
     min_tile: WorldTileCoords,
     max_tile: WorldTileCoords,
@@ -605,6 +617,9 @@ pub struct ViewRegion {
     padding: i32,
     /// The maximum amount of tiles this view region contains
     max_n_tiles: usize,
+    /// The center of the hemisphere which is visible on the globe
+    #[cfg(feature = "globe")]
+    near_hemisphere: Option<LatLon>,
 }
 
 impl ViewRegion {
@@ -626,9 +641,31 @@ impl ViewRegion {
             zoom_level: z,
             max_n_tiles,
             padding,
+            #[cfg(feature = "globe")]
+            near_hemisphere: None,
         }
     }
 
+    /// Restricts this view region to the tiles which are on the hemisphere around `center` when
+    /// rendering the globe.
+    #[cfg(feature = "globe")]
+    pub fn on_globe(mut self, center: LatLon) -> Self {
+        self.near_hemisphere = Some(center);
+        self
+    }
+
+    #[cfg(feature = "globe")]
+    fn is_on_near_hemisphere(&self, coords: &WorldTileCoords) -> bool {
+        self.near_hemisphere.map_or(true, |center| {
+            crate::globe::is_tile_on_near_hemisphere(*coords, center)
+        })
+    }
+
+    #[cfg(not(feature = "globe"))]
+    fn is_on_near_hemisphere(&self, _coords: &WorldTileCoords) -> bool {
+        true
+    }
+
     pub fn zoom_level(&self) -> ZoomLevel {
         self.zoom_level
     }
@@ -639,6 +676,7 @@ impl ViewRegion {
             && world_coords.x >= self.min_tile.x - self.padding
             && world_coords.y >= self.min_tile.y - self.padding
             && world_coords.z == self.zoom_level
+            && self.is_on_near_hemisphere(&world_coords)
     }
 
     pub fn iter(&self) -> impl Iterator<Item = WorldTileCoords> + '_ {
@@ -649,8 +687,42 @@ impl ViewRegion {
                     tile_coord
                 })
             })
+            .filter(|coords| self.is_on_near_hemisphere(coords))
             .take(self.max_n_tiles)
     }
+
+    /// The tiles of this view region ordered by their distance to the center of the region, such
+    /// that the tiles in the center are requested first.
+    pub fn iter_center_first(&self) -> impl Iterator<Item = WorldTileCoords> {
+        self.sort_center_first(self.iter().collect())
+    }
+
+    /// The tiles of this view region which are not in view of the `previous` region, ordered like
+    /// [`ViewRegion::iter_center_first`]. After panning, these are the newly exposed tiles at the
+    /// edges of the view. All tiles are exposed if the zoom level differs.
+    pub fn iter_exposed(&self, previous: &ViewRegion) -> impl Iterator<Item = WorldTileCoords> {
+        self.sort_center_first(
+            self.iter()
+                .filter(|coords| !previous.is_in_view(coords))
+                .collect(),
+        )
+    }
+
+    fn sort_center_first(
+        &self,
+        mut tiles: Vec<WorldTileCoords>,
+    ) -> std::vec::IntoIter<WorldTileCoords> {
+        // Twice the center, such that the distances are computed without fractions
+        let center_x = i64::from(self.min_tile.x) + i64::from(self.max_tile.x);
+        let center_y = i64::from(self.min_tile.y) + i64::from(self.max_tile.y);
+
+        tiles.sort_by_key(|coords| {
+            let dx = 2 * i64::from(coords.x) - center_x;
+            let dy = 2 * i64::from(coords.y) - center_y;
+            dx * dx + dy * dy
+        });
+        tiles.into_iter()
+    }
 }
 
 impl Display for TileCoords {
@@ -688,7 +760,8 @@ mod tests {
 
     use crate::{
         coords::{
-            Quadkey, TileCoords, ViewRegion, WorldCoords, WorldTileCoords, Zoom, ZoomLevel, EXTENT,
+            LatLon, Quadkey, TileCoords, ViewRegion, WorldCoords, WorldTileCoords, Zoom, ZoomLevel,
+            EXTENT,
         },
         style::source::TileAddressingScheme,
         util::math::Aabb2,
@@ -778,4 +851,50 @@ mod tests {
             println!("{tile_coords}");
         }
     }
+
+    #[test]
+    fn test_view_region_exposed_after_pan() {
+        let view_region = |min_x: f64| {
+            ViewRegion::new(
+                Aabb2::new(
+                    Point2::new(min_x, 512.0),
+                    Point2::new(min_x + 988.0, 1500.0),
+                ),
+                0,
+                32,
+                Zoom::new(4.0),
+                ZoomLevel::from(4),
+            )
+        };
+
+        // Panning by one tile to the right only exposes the column at the right edge
+        let previous = view_region(512.0);
+        let current = view_region(1024.0);
+        assert_eq!(
+            current.iter_exposed(&previous).collect::<Vec<_>>(),
+            vec![
+                WorldTileCoords::from((3, 1, ZoomLevel::from(4))),
+                WorldTileCoords::from((3, 2, ZoomLevel::from(4))),
+            ]
+        );
+
+        // Small pans within the same tiles expose nothing
+        assert_eq!(view_region(540.0).iter_exposed(&previous).count(), 0);
+    }
+
+    #[test]
+    fn test_lat_lon_round_trip() {
+        for zoom in [Zoom::new(0.0), Zoom::new(4.5), Zoom::new(15.0)] {
+            for lat_lon in [
+                LatLon::new(0.0, 0.0),
+                LatLon::new(48.137154, 11.576124),
+                LatLon::new(-33.865143, 151.209900),
+            ] {
+                let round_trip = WorldCoords::from_lat_lon(lat_lon, zoom).to_lat_lon(zoom);
+
+                assert!((round_trip.latitude - lat_lon.latitude).abs() < 1e-9);
+                assert!((round_trip.longitude - lat_lon.longitude).abs() < 1e-9);
+            }
+        }
+    }
 }
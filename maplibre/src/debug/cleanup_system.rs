@@ -1,11 +1,17 @@
-use crate::{context::MapContext, debug::TileDebugItem, render::render_phase::RenderPhase};
+use crate::{
+    context::MapContext,
+    debug::{TileDebugItem, TileStatusItem},
+    render::render_phase::RenderPhase,
+};
 
 pub fn cleanup_system(MapContext { world, .. }: &mut MapContext) {
-    let Some(debug_tile_phase) = world
+    let Some((debug_tile_phase, tile_status_phase)) = world
         .resources
-        .query_mut::<
+        .query_mut::<(
             &mut RenderPhase<TileDebugItem>,
-        >() else { return; };
+            &mut RenderPhase<TileStatusItem>,
+        )>() else { return; };
 
     debug_tile_phase.clear();
+    tile_status_phase.clear();
 }
@@ -1,7 +1,7 @@
 use std::ops::Deref;
 
 use crate::{
-    debug::TileDebugItem,
+    debug::{TileDebugItem, TileStatusItem},
     render::{
         eventually::Eventually::Initialized,
         graph::{Node, NodeRunError, RenderContext, RenderGraphContext, SlotInfo},
@@ -60,6 +60,13 @@ impl Node for DebugPassNode {
 
         let mut tracked_pass = TrackedRenderPass::new(render_pass);
 
+        // The fills of the tiles are drawn below the outlines
+        if let Some(status_items) = world.resources.get::<RenderPhase<TileStatusItem>>() {
+            for item in status_items {
+                item.draw_function.draw(&mut tracked_pass, world, item);
+            }
+        }
+
         if let Some(debug_items) = world.resources.get::<RenderPhase<TileDebugItem>>() {
             log::trace!(
                 "RenderPhase<TileDebugItem>::size() = {}",
@@ -70,6 +77,8 @@ impl Node for DebugPassNode {
             }
         }
 
+        resources.record_stats(tracked_pass.stats());
+
         Ok(())
     }
 }
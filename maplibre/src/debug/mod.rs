@@ -2,8 +2,11 @@ use std::{ops::Deref, rc::Rc};
 
 use crate::{
     debug::{
-        cleanup_system::cleanup_system, debug_pass::DebugPassNode, queue_system::queue_system,
+        cleanup_system::cleanup_system,
+        debug_pass::DebugPassNode,
+        queue_system::queue_system,
         resource_system::resource_system,
+        tile_status::{tile_status_system, TileStatusResources},
     },
     environment::Environment,
     kernel::Kernel,
@@ -24,6 +27,9 @@ mod debug_pass;
 mod queue_system;
 mod render_commands;
 mod resource_system;
+mod tile_status;
+
+pub use tile_status::{DebugTileStatus, TileStatus};
 
 /// Labels for the "draw" graph
 mod draw_graph {
@@ -63,6 +69,23 @@ impl PhaseItem for TileDebugItem {
     }
 }
 
+/// Draws the fills of all tiles whose data is not available, see [`DebugTileStatus`].
+struct TileStatusItem {
+    pub draw_function: Box<dyn Draw<TileStatusItem>>,
+}
+
+impl PhaseItem for TileStatusItem {
+    type SortKey = u32;
+
+    fn sort_key(&self) -> Self::SortKey {
+        0
+    }
+
+    fn draw_function(&self) -> &dyn Draw<TileStatusItem> {
+        self.draw_function.as_ref()
+    }
+}
+
 #[derive(Default)]
 pub struct DebugPlugin;
 
@@ -85,9 +108,13 @@ impl<E: Environment> Plugin<E> for DebugPlugin {
 
         resources.init::<RenderPhase<TileDebugItem>>();
         resources.insert(Eventually::<DebugPipeline>::Uninitialized);
+        resources.init::<RenderPhase<TileStatusItem>>();
+        resources.insert(Eventually::<TileStatusResources>::Uninitialized);
+        resources.get_or_init_mut::<DebugTileStatus>();
 
         schedule.add_system_to_stage(RenderStageLabel::Prepare, resource_system);
         schedule.add_system_to_stage(RenderStageLabel::Queue, queue_system);
+        schedule.add_system_to_stage(RenderStageLabel::Queue, tile_status_system);
         schedule.add_system_to_stage(RenderStageLabel::Cleanup, cleanup_system);
     }
 }
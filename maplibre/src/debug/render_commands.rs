@@ -1,7 +1,7 @@
 //! Specifies the instructions which are going to be sent to the GPU. Render commands can be concatenated
 //! into a new render command which executes multiple instruction sets.
 use crate::{
-    debug::{DebugPipeline, TileDebugItem},
+    debug::{tile_status::TileStatusResources, DebugPipeline, TileDebugItem},
     render::{
         eventually::{Eventually, Eventually::Initialized},
         render_phase::{PhaseItem, RenderCommand, RenderCommandResult},
@@ -43,10 +43,7 @@ impl RenderCommand<TileDebugItem> for DrawDebugOutline {
         let tile_view_pattern_buffer = source_shape
             .buffer_range()
             .expect("tile_view_pattern needs to be uploaded first"); // FIXME tcs
-        pass.set_vertex_buffer(
-            0,
-            tile_view_pattern.buffer().slice(tile_view_pattern_buffer),
-        );
+        pass.set_vertex_buffer(0, tile_view_pattern.buffer(), tile_view_pattern_buffer);
 
         const TILE_MASK_SHADER_VERTICES: u32 = 24;
         pass.draw(0..TILE_MASK_SHADER_VERTICES, 0..1);
@@ -55,4 +52,44 @@ impl RenderCommand<TileDebugItem> for DrawDebugOutline {
     }
 }
 
+pub struct SetTileStatusPipeline;
+impl<P: PhaseItem> RenderCommand<P> for SetTileStatusPipeline {
+    fn render<'w>(
+        world: &'w World,
+        _item: &P,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        let Some(Initialized(tile_status_resources)) = world
+            .resources
+            .get::<Eventually<TileStatusResources>>() else { return RenderCommandResult::Failure; };
+
+        pass.set_render_pipeline(tile_status_resources.pipeline());
+        RenderCommandResult::Success
+    }
+}
+
+pub struct DrawTileStatus;
+impl<P: PhaseItem> RenderCommand<P> for DrawTileStatus {
+    fn render<'w>(
+        world: &'w World,
+        _item: &P,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        let Some(Initialized(tile_status_resources)) = world
+            .resources
+            .get::<Eventually<TileStatusResources>>() else { return RenderCommandResult::Failure; };
+
+        let Some(buffer) = &tile_status_resources.buffer else { return RenderCommandResult::Failure; };
+
+        pass.set_vertex_buffer(0, &buffer.instances, ..);
+
+        const TILE_STATUS_VERTICES: u32 = 6;
+        pass.draw(0..TILE_STATUS_VERTICES, 0..buffer.count);
+
+        RenderCommandResult::Success
+    }
+}
+
 pub type DrawDebugOutlines = (SetDebugPipeline, DrawDebugOutline);
+
+pub type DrawTileStatuses = (SetTileStatusPipeline, DrawTileStatus);
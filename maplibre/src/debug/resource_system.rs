@@ -1,7 +1,7 @@
 //! Prepares GPU-owned resources by initializing them if they are uninitialized or out-of-date.
 use crate::{
     context::MapContext,
-    debug::DebugPipeline,
+    debug::{tile_status::TileStatusResources, DebugPipeline},
     render::{
         eventually::Eventually,
         resource::{RenderPipeline, TilePipeline},
@@ -24,11 +24,13 @@ pub fn resource_system(
         ..
     }: &mut MapContext,
 ) {
-    let Some(
-        debug_pipeline
-    ) = world.resources.query_mut::<
+    let Some((
+        debug_pipeline,
+        tile_status_resources,
+    )) = world.resources.query_mut::<(
         &mut Eventually<DebugPipeline>,
-    >() else { return; };
+        &mut Eventually<TileStatusResources>,
+    )>() else { return; };
 
     debug_pipeline.initialize(|| {
         let mask_shader = shaders::TileMaskShader {
@@ -53,4 +55,26 @@ pub fn resource_system(
         .initialize(device);
         DebugPipeline(pipeline)
     });
+
+    tile_status_resources.initialize(|| {
+        let tile_status_shader = shaders::TileStatusShader {
+            format: surface.surface_format(),
+        };
+
+        let pipeline = TilePipeline::new(
+            "tile_status_pipeline".into(),
+            *settings,
+            tile_status_shader.describe_vertex(),
+            tile_status_shader.describe_fragment(),
+            false,
+            false,
+            false,
+            false,
+            false,
+            false,
+        )
+        .describe_render_pipeline()
+        .initialize(device);
+        TileStatusResources::new(pipeline)
+    });
 }
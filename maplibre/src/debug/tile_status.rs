@@ -0,0 +1,264 @@
+//! Fills tiles whose data is not available with a color, such that tiles which are still loading,
+//! failed to load or have no data can be told apart.
+
+use std::mem::size_of;
+
+use csscolorparser::Color;
+
+use crate::{
+    context::MapContext,
+    coords::WorldTileCoords,
+    debug::{render_commands::DrawTileStatuses, TileStatusItem},
+    render::{
+        color_space::ColorSpace,
+        eventually::{Eventually, Eventually::Initialized},
+        render_phase::{DrawState, RenderPhase},
+        shaders::ShaderTileStatus,
+        tile_view_pattern::WgpuTileViewPattern,
+        RenderResources, Renderer,
+    },
+    tcs::{
+        tiles::{TileState, Tiles},
+        world::World,
+    },
+    view_state::ViewState,
+};
+
+/// The reason why the data of a tile is not available.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum TileStatus {
+    /// The tile has not been requested yet or its data has not arrived yet.
+    Loading,
+    /// Loading some of the data of the tile failed.
+    Error,
+    /// Some of the data of the tile does not exist.
+    Missing,
+}
+
+impl TileStatus {
+    /// The status of the tile at `coords`, or `None` if all of its data is available.
+    pub fn of(tiles: &Tiles, coords: &WorldTileCoords) -> Option<TileStatus> {
+        match tiles.tile_state(coords) {
+            None | Some(TileState::Loading) => Some(TileStatus::Loading),
+            Some(TileState::Loaded) => None,
+            Some(TileState::Partial | TileState::Unavailable) => {
+                Some(if tiles.has_errors(coords) {
+                    TileStatus::Error
+                } else {
+                    TileStatus::Missing
+                })
+            }
+        }
+    }
+}
+
+/// Configures the fills of tiles whose data is not available. The fills are drawn on top of the
+/// map and should therefore be semi-transparent.
+#[derive(Clone, Debug)]
+pub struct DebugTileStatus {
+    /// Whether the fills are drawn
+    pub enabled: bool,
+    pub loading: Color,
+    pub error: Color,
+    pub missing: Color,
+}
+
+impl Default for DebugTileStatus {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            loading: Color::new(0.5, 0.5, 0.5, 0.5),
+            error: Color::new(1.0, 0.0, 0.0, 0.5),
+            missing: Color::new(1.0, 1.0, 0.0, 0.5),
+        }
+    }
+}
+
+impl DebugTileStatus {
+    pub fn color(&self, status: TileStatus) -> &Color {
+        match status {
+            TileStatus::Loading => &self.loading,
+            TileStatus::Error => &self.error,
+            TileStatus::Missing => &self.missing,
+        }
+    }
+}
+
+impl World {
+    /// Enables or disables the fills of tiles whose data is not available. Requires the
+    /// [`DebugPlugin`](crate::debug::DebugPlugin).
+    pub fn set_debug_tile_status(&mut self, enabled: bool) {
+        self.resources.get_or_init_mut::<DebugTileStatus>().enabled = enabled;
+    }
+}
+
+/// Holds the pipeline and the instance buffer of the tile fills.
+pub struct TileStatusResources {
+    pipeline: wgpu::RenderPipeline,
+    pub buffer: Option<TileStatusBuffer>,
+}
+
+impl TileStatusResources {
+    pub fn new(pipeline: wgpu::RenderPipeline) -> Self {
+        Self {
+            pipeline,
+            buffer: None,
+        }
+    }
+
+    pub fn pipeline(&self) -> &wgpu::RenderPipeline {
+        &self.pipeline
+    }
+}
+
+/// The instance buffer of the tile fills.
+pub struct TileStatusBuffer {
+    pub instances: wgpu::Buffer,
+    /// The amount of fills which fit into the buffer
+    pub capacity: usize,
+    /// The amount of fills which have been uploaded
+    pub count: u32,
+}
+
+impl TileStatusBuffer {
+    pub fn new(device: &wgpu::Device, capacity: usize) -> Self {
+        Self {
+            instances: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("tile status instance buffer"),
+                size: (capacity * size_of::<ShaderTileStatus>()) as u64,
+                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }),
+            capacity,
+            count: 0,
+        }
+    }
+}
+
+/// Computes the fills of the tiles at `coords` whose data is not available.
+fn instances(
+    settings: &DebugTileStatus,
+    coords: impl Iterator<Item = WorldTileCoords>,
+    tiles: &Tiles,
+    view_state: &ViewState,
+    color_space: ColorSpace,
+) -> Vec<ShaderTileStatus> {
+    let view_proj = view_state.view_projection();
+
+    coords
+        .filter_map(|coords| {
+            let status = TileStatus::of(tiles, &coords)?;
+
+            Some(ShaderTileStatus::new(
+                view_proj
+                    .to_model_view_projection(view_state.tile_transform(coords))
+                    .downcast()
+                    .into(),
+                color_space.shader_color(settings.color(status)),
+            ))
+        })
+        .collect()
+}
+
+/// Uploads the fills of the tiles in view and queues them for rendering.
+pub fn tile_status_system(
+    MapContext {
+        world,
+        view_state,
+        renderer:
+            Renderer {
+                device,
+                queue,
+                resources: RenderResources { surface, .. },
+                ..
+            },
+        ..
+    }: &mut MapContext,
+) {
+    let Some((
+        settings,
+        Initialized(tile_view_pattern),
+        Initialized(tile_status_resources),
+        tile_status_phase,
+    )) = world.resources.query_mut::<(
+        &DebugTileStatus,
+        &Eventually<WgpuTileViewPattern>,
+        &mut Eventually<TileStatusResources>,
+        &mut RenderPhase<TileStatusItem>,
+    )>() else { return; };
+
+    if !settings.enabled {
+        return;
+    }
+
+    let instances = instances(
+        settings,
+        tile_view_pattern.iter().map(|view_tile| view_tile.coords()),
+        &world.tiles,
+        view_state,
+        surface.color_space(),
+    );
+
+    if instances.is_empty() {
+        return;
+    }
+
+    let buffer = match &mut tile_status_resources.buffer {
+        Some(buffer) if buffer.capacity >= instances.len() => buffer,
+        buffer => buffer.insert(TileStatusBuffer::new(
+            device,
+            instances.len().next_power_of_two(),
+        )),
+    };
+
+    queue.write_buffer(&buffer.instances, 0, bytemuck::cast_slice(&instances));
+    buffer.count = instances.len() as u32;
+
+    tile_status_phase.add(TileStatusItem {
+        draw_function: Box::new(DrawState::<TileStatusItem, DrawTileStatuses>::new()),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TileStatus;
+    use crate::{
+        coords::{WorldTileCoords, ZoomLevel},
+        tcs::tiles::Tiles,
+        vector::{
+            LayerMissingReason, MissingVectorLayerData, VectorLayerData, VectorLayersDataComponent,
+        },
+    };
+
+    fn missing_tile(tiles: &mut Tiles, coords: WorldTileCoords, reason: LayerMissingReason) {
+        tiles
+            .spawn_mut(coords)
+            .unwrap()
+            .insert(VectorLayersDataComponent {
+                done: true,
+                layers: vec![VectorLayerData::Missing(MissingVectorLayerData {
+                    coords,
+                    source: None,
+                    source_layer: "water".to_string(),
+                    reason,
+                })],
+                ..VectorLayersDataComponent::default()
+            });
+    }
+
+    #[test]
+    fn test_tile_status() {
+        let mut tiles = Tiles::default();
+
+        let error = WorldTileCoords::from((0, 0, ZoomLevel::new(1)));
+        let missing = WorldTileCoords::from((1, 0, ZoomLevel::new(1)));
+        let absent = WorldTileCoords::from((0, 1, ZoomLevel::new(1)));
+
+        missing_tile(&mut tiles, error, LayerMissingReason::FetchFailed);
+        missing_tile(&mut tiles, missing, LayerMissingReason::Missing);
+
+        assert_eq!(TileStatus::of(&tiles, &error), Some(TileStatus::Error));
+        assert_eq!(TileStatus::of(&tiles, &missing), Some(TileStatus::Missing));
+        assert_eq!(TileStatus::of(&tiles, &absent), Some(TileStatus::Loading));
+    }
+}
@@ -4,6 +4,7 @@ use crate::{
         scheduler::Scheduler,
         source_client::{HttpClient, SourceClient},
     },
+    tessellation::tessellator::Tessellators,
     window::MapWindowConfig,
 };
 
@@ -24,12 +25,22 @@ pub trait Environment: 'static {
 
     type HttpClient: HttpClient;
 
-    type OffscreenKernelEnvironment: OffscreenKernelEnvironment;
+    type OffscreenKernelEnvironment: OffscreenKernelEnvironment<HttpClient = Self::HttpClient>;
 }
 
 pub trait OffscreenKernelEnvironment: Send + Sync + 'static {
     type HttpClient: HttpClient;
-    fn create() -> Self;
+
+    /// Creates the environment of a procedure. The `source_client` is the client of the
+    /// [`Kernel`](crate::kernel::Kernel), which has been configured by the
+    /// [`KernelBuilder`](crate::kernel::KernelBuilder). It is `None` if the client can not be
+    /// shared with the procedure, e.g. with a web worker, in which case a default client is used.
+    fn create(source_client: Option<SourceClient<Self::HttpClient>>) -> Self;
 
     fn source_client(&self) -> SourceClient<Self::HttpClient>;
+
+    /// The tessellators which are used for the source-layers of vector tiles.
+    fn tessellators(&self) -> Tessellators {
+        Tessellators::default()
+    }
 }
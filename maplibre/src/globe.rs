@@ -0,0 +1,180 @@
+//! Experimental globe mode which renders the world on a sphere at low zoom levels.
+//!
+//! Each tile is rendered as a flat facet which touches the sphere at the center of the tile. The
+//! globe transitions to the flat [`WebMercator`] map between [`GLOBE_TRANSITION_ZOOM`] and
+//! `GLOBE_TRANSITION_ZOOM + 1`. Seams between tiles and the antimeridian are not handled yet.
+
+use std::f64::consts::PI;
+
+use cgmath::{Matrix4, Vector3, Vector4};
+
+use crate::{
+    coords::{LatLon, WorldCoords, WorldTileCoords, Zoom, EXTENT, TILE_SIZE},
+    projection::{Projection, WebMercator},
+};
+
+/// The zoom at which the globe starts to transition to the flat map.
+pub const GLOBE_TRANSITION_ZOOM: f64 = 5.0;
+
+/// How much the globe is visible at the `zoom`. `1.0` means that the world is rendered on the
+/// sphere and `0.0` means that the world is rendered flat.
+pub fn globe_factor(zoom: Zoom) -> f64 {
+    let delta = Zoom::new(GLOBE_TRANSITION_ZOOM).scale_delta(&zoom).log2();
+    (1.0 - delta).clamp(0.0, 1.0)
+}
+
+/// The unit vector of `lat_lon` in earth-centered, earth-fixed coordinates.
+fn to_ecef(lat_lon: LatLon) -> Vector3<f64> {
+    let (lat, lon) = (
+        lat_lon.latitude.to_radians(),
+        lat_lon.longitude.to_radians(),
+    );
+    Vector3::new(lat.cos() * lon.cos(), lat.cos() * lon.sin(), lat.sin())
+}
+
+/// The east, north and up axes at `lat_lon` in earth-centered, earth-fixed coordinates.
+fn local_axes(lat_lon: LatLon) -> [Vector3<f64>; 3] {
+    let (lat, lon) = (
+        lat_lon.latitude.to_radians(),
+        lat_lon.longitude.to_radians(),
+    );
+    [
+        Vector3::new(-lon.sin(), lon.cos(), 0.0),
+        Vector3::new(-lat.sin() * lon.cos(), -lat.sin() * lon.sin(), lat.cos()),
+        to_ecef(lat_lon),
+    ]
+}
+
+/// Converts the earth-centered, earth-fixed vector `v` to world axes at `center`. The x-axis of
+/// the world points east, the y-axis south and the z-axis up.
+fn to_world_axes(v: Vector3<f64>, center: LatLon) -> Vector3<f64> {
+    let [east, north, up] = local_axes(center);
+    Vector3::new(
+        cgmath::dot(v, east),
+        -cgmath::dot(v, north),
+        cgmath::dot(v, up),
+    )
+}
+
+/// Whether `lat_lon` is on the hemisphere which faces the camera above `center`.
+pub fn is_on_near_hemisphere(lat_lon: LatLon, center: LatLon) -> bool {
+    to_world_axes(to_ecef(lat_lon), center).z > 0.0
+}
+
+/// Whether any part of the tile at `coords` is on the hemisphere which faces the camera above
+/// `center`.
+pub fn is_tile_on_near_hemisphere(coords: WorldTileCoords, center: LatLon) -> bool {
+    let zoom = Zoom::from(coords.z);
+
+    [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0), (1.0, 1.0), (0.5, 0.5)]
+        .into_iter()
+        .map(|(dx, dy)| {
+            WorldCoords::at_ground(
+                (coords.x as f64 + dx) * TILE_SIZE,
+                (coords.y as f64 + dy) * TILE_SIZE,
+            )
+        })
+        .any(|world| is_on_near_hemisphere(WebMercator.unproject(world, zoom), center))
+}
+
+/// Places the point `lat_lon` on the sphere which touches the ground at `center`.
+fn to_sphere(lat_lon: LatLon, center: WorldCoords, radius: f64, zoom: Zoom) -> Vector3<f64> {
+    let position = to_world_axes(to_ecef(lat_lon), WebMercator.unproject(center, zoom));
+    Vector3::new(center.x, center.y, -radius) + position * radius
+}
+
+/// The transform of the tile at `coords` on the globe. The tile is rotated so it touches the
+/// sphere at its center and is scaled according to the latitude.
+fn globe_transform(coords: WorldTileCoords, zoom: Zoom, center: WorldCoords) -> Matrix4<f64> {
+    let flat = coords.transform_for_zoom(zoom);
+    let world_size = WebMercator.world_size(zoom);
+    let radius = world_size / (2.0 * PI);
+
+    let tile_center = flat * Vector4::new(EXTENT / 2.0, EXTENT / 2.0, 0.0, 1.0);
+    let tile_center = WorldCoords::at_ground(tile_center.x, tile_center.y);
+    let lat_lon = WebMercator.unproject(tile_center, zoom);
+    let center_lat_lon = WebMercator.unproject(center, zoom);
+
+    let [east, north, up] = local_axes(lat_lon).map(|axis| to_world_axes(axis, center_lat_lon));
+
+    // Web Mercator is conformal, which means that both axes are scaled by the same factor
+    let scale = lat_lon.latitude.to_radians().cos();
+    let rotate_and_scale = Matrix4::from_cols(
+        (east * scale).extend(0.0),
+        (-north * scale).extend(0.0),
+        up.extend(0.0),
+        Vector4::unit_w(),
+    );
+
+    Matrix4::from_translation(to_sphere(lat_lon, center, radius, zoom))
+        * rotate_and_scale
+        * Matrix4::from_translation(Vector3::new(-tile_center.x, -tile_center.y, 0.0))
+        * flat
+}
+
+/// The transform of the tile at `coords` which blends between the globe and the flat map
+/// according to [`globe_factor`]. `center` is the point which the camera looks at.
+pub fn tile_transform(coords: WorldTileCoords, zoom: Zoom, center: WorldCoords) -> Matrix4<f64> {
+    let flat = coords.transform_for_zoom(zoom);
+    let factor = globe_factor(zoom);
+
+    if factor <= 0.0 {
+        return flat;
+    }
+
+    flat * (1.0 - factor) + globe_transform(coords, zoom, center) * factor
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::Vector4;
+
+    use super::{globe_factor, is_tile_on_near_hemisphere, tile_transform};
+    use crate::coords::{LatLon, WorldCoords, WorldTileCoords, Zoom, EXTENT};
+
+    #[test]
+    fn test_globe_factor() {
+        assert_eq!(globe_factor(Zoom::new(2.0)), 1.0);
+        assert_eq!(globe_factor(Zoom::new(5.0)), 1.0);
+        assert!((globe_factor(Zoom::new(5.5)) - 0.5).abs() < 1e-9);
+        assert_eq!(globe_factor(Zoom::new(8.0)), 0.0);
+    }
+
+    #[test]
+    fn test_tile_transform_is_flat_when_zoomed_in() {
+        let zoom = Zoom::new(10.0);
+        let coords = WorldTileCoords::from((550, 350, 10.into()));
+
+        assert_eq!(
+            tile_transform(coords, zoom, WorldCoords::default()),
+            coords.transform_for_zoom(zoom)
+        );
+    }
+
+    #[test]
+    fn test_tile_at_center_touches_ground() {
+        let zoom = Zoom::new(2.0);
+        let coords = WorldTileCoords::from((1, 1, 2.into()));
+        let flat = coords.transform_for_zoom(zoom);
+
+        let tile_center = flat * Vector4::new(EXTENT / 2.0, EXTENT / 2.0, 0.0, 1.0);
+        let center = WorldCoords::at_ground(tile_center.x, tile_center.y);
+
+        let on_globe = tile_transform(coords, zoom, center)
+            * Vector4::new(EXTENT / 2.0, EXTENT / 2.0, 0.0, 1.0);
+
+        assert!((on_globe.x - tile_center.x).abs() < 1e-6);
+        assert!((on_globe.y - tile_center.y).abs() < 1e-6);
+        assert!(on_globe.z.abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_is_tile_on_near_hemisphere() {
+        let center = LatLon::new(0.0, 0.0);
+
+        assert!(is_tile_on_near_hemisphere((1, 1, 2.into()).into(), center));
+        assert!(is_tile_on_near_hemisphere((2, 2, 2.into()).into(), center));
+        // The tile next to the antimeridian on the far side of the globe
+        assert!(!is_tile_on_near_hemisphere((0, 7, 4.into()).into(), center));
+    }
+}
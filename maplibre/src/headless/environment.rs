@@ -1,20 +1,47 @@
+use std::marker::PhantomData;
+
 use crate::{
-    environment::Environment,
+    environment::{Environment, OffscreenKernelEnvironment},
     headless::window::HeadlessMapWindowConfig,
-    io::apc::SchedulerAsyncProcedureCall,
-    platform::{
-        http_client::ReqwestHttpClient, scheduler::TokioScheduler,
-        ReqwestOffscreenKernelEnvironment,
+    io::{
+        apc::SchedulerAsyncProcedureCall,
+        source_client::{HttpClient, SourceClient},
     },
+    platform::{http_client::ReqwestHttpClient, scheduler::TokioScheduler},
 };
 
-pub struct HeadlessEnvironment;
+/// The environment of a [`HeadlessMap`](crate::headless::map::HeadlessMap), whose tiles are
+/// fetched with the HTTP client `HC`.
+pub struct HeadlessEnvironment<HC = ReqwestHttpClient> {
+    _http_client: PhantomData<HC>,
+}
 
-impl Environment for HeadlessEnvironment {
+impl<HC: HttpClient> Environment for HeadlessEnvironment<HC> {
     type MapWindowConfig = HeadlessMapWindowConfig;
     type AsyncProcedureCall =
         SchedulerAsyncProcedureCall<Self::OffscreenKernelEnvironment, Self::Scheduler>;
     type Scheduler = TokioScheduler;
-    type HttpClient = ReqwestHttpClient;
-    type OffscreenKernelEnvironment = ReqwestOffscreenKernelEnvironment;
+    type HttpClient = HC;
+    type OffscreenKernelEnvironment = HeadlessOffscreenKernelEnvironment<HC>;
+}
+
+/// The environment of the procedures of a [`HeadlessEnvironment`]. Procedures run on the tokio
+/// runtime and share the source client of the kernel.
+pub struct HeadlessOffscreenKernelEnvironment<HC: HttpClient> {
+    source_client: SourceClient<HC>,
+}
+
+impl<HC: HttpClient> OffscreenKernelEnvironment for HeadlessOffscreenKernelEnvironment<HC> {
+    type HttpClient = HC;
+
+    fn create(source_client: Option<SourceClient<Self::HttpClient>>) -> Self {
+        Self {
+            source_client: source_client
+                .expect("the source client is shared with procedures by the kernel"),
+        }
+    }
+
+    fn source_client(&self) -> SourceClient<Self::HttpClient> {
+        self.source_client.clone()
+    }
 }
@@ -31,7 +31,7 @@ impl Node for CopySurfaceBufferNode {
     ) -> Result<(), NodeRunError> {
         let surface = state.surface();
         match surface.head() {
-            Head::Headed(_) => {}
+            Head::Headed(_) | Head::External(_) => {}
             Head::Headless(buffered_texture) => {
                 let size = surface.size();
                 command_encoder.copy_texture_to_buffer(
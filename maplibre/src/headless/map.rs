@@ -1,41 +1,63 @@
-use std::{cell::RefCell, ops::Deref, rc::Rc};
+use std::{cell::RefCell, collections::HashSet, ops::Deref, rc::Rc};
+
+use cgmath::Matrix4;
+use image::RgbaImage;
 
 use crate::{
     context::MapContext,
-    coords::{WorldCoords, WorldTileCoords, Zoom, ZoomLevel, TILE_SIZE},
-    headless::environment::HeadlessEnvironment,
+    coords::{LatLon, WorldCoords, WorldTileCoords, Zoom, ZoomLevel, TILE_SIZE},
+    headless::{environment::HeadlessEnvironment, StaticMapError},
     io::{
         apc::{Context, IntoMessage, Message, SendError},
-        source_client::SourceFetchError,
-        source_type::{SourceType, TessellateSource},
+        source_client::{HttpClient, SourceClient, SourceFetchError},
+        source_type::SourceType,
     },
     kernel::Kernel,
     map::MapError,
+    platform::http_client::ReqwestHttpClient,
     plugin::Plugin,
-    render::{eventually::Eventually, Renderer},
+    projection::SourceCrs,
+    render::{
+        device_lost::DeviceLostReason, eventually::Eventually, resource::Head, stats::RenderStats,
+        Renderer,
+    },
     schedule::{Schedule, Stage},
     style::Style,
-    tcs::world::World,
+    tcs::world::{InsertTileError, World},
+    tessellation::tessellator::Tessellators,
     vector::{
-        process_vector_tile, AvailableVectorLayerData, DefaultVectorTransferables,
-        LayerTessellated, ProcessVectorContext, VectorBufferPool, VectorLayerData,
-        VectorLayersDataComponent, VectorTileRequest, VectorTransferables,
+        fetch_covering_tiles, process_vector_tiles, requested_source_layers, source_crs,
+        vector_source_type, DefaultVectorTransferables, LayerMissingReason, LayerTessellated,
+        MissingVectorLayerData, ProcessVectorContext, ProcessVectorError, VectorBufferPool,
+        VectorLayerData, VectorLayersDataComponent, VectorTileRequest, VectorTransferables,
     },
     view_state::ViewState,
+    window::MapWindowConfig,
 };
 
-pub struct HeadlessMap {
-    kernel: Rc<Kernel<HeadlessEnvironment>>,
+type TessellatedLayers =
+    Vec<Box<<DefaultVectorTransferables as VectorTransferables>::LayerTessellated>>;
+
+/// A map which renders into images instead of a window. Tiles which are requested through the
+/// kernel are fetched with the HTTP client `HC`.
+pub struct HeadlessMap<HC: HttpClient = ReqwestHttpClient> {
+    kernel: Rc<Kernel<HeadlessEnvironment<HC>>>,
     schedule: Schedule,
     map_context: MapContext,
+    /// Kept to build the world again after the device has been lost
+    plugins: Vec<Box<dyn Plugin<HeadlessEnvironment<HC>>>>,
+    /// Replaces the view projection of the camera in [`HeadlessMap::render_view`]
+    view_projection: Option<Matrix4<f64>>,
+    /// Whether images are read at the render scale instead of the surface resolution
+    full_resolution: bool,
 }
 
-impl HeadlessMap {
+impl<HC: HttpClient> HeadlessMap<HC> {
     pub fn new(
         style: Style,
         mut renderer: Renderer,
-        kernel: Kernel<HeadlessEnvironment>,
-        plugins: Vec<Box<dyn Plugin<HeadlessEnvironment>>>,
+        kernel: Kernel<HeadlessEnvironment<HC>>,
+        plugins: Vec<Box<dyn Plugin<HeadlessEnvironment<HC>>>>,
     ) -> Result<Self, MapError> {
         let window_size = renderer.state().surface().size();
 
@@ -69,38 +91,267 @@ impl HeadlessMap {
                 renderer,
             },
             schedule,
+            plugins,
+            view_projection: None,
+            full_resolution: false,
         })
     }
 
-    pub fn render_tile(
+    /// The world of the map, e.g. to configure plugins.
+    pub fn world_mut(&mut self) -> &mut World {
+        &mut self.map_context.world
+    }
+
+    /// The view of the map, e.g. to move the camera.
+    pub fn view_state_mut(&mut self) -> &mut ViewState {
+        &mut self.map_context.view_state
+    }
+
+    /// Inserts the tile `data` of the source `source_id` of the style of the map, see
+    /// [`World::insert_tile`].
+    pub fn insert_tile(
+        &mut self,
+        source_id: Option<&str>,
+        coords: WorldTileCoords,
+        source: &SourceType,
+        data: &[u8],
+    ) -> Result<(), InsertTileError> {
+        let MapContext { style, world, .. } = &mut self.map_context;
+        world.insert_tile(style, source_id, coords, source, data)
+    }
+
+    /// The renderer of the map, e.g. to set a device lost callback.
+    pub fn renderer_mut(&mut self) -> &mut Renderer {
+        &mut self.map_context.renderer
+    }
+
+    /// Returns the rendered images at the render scale of the renderer instead of downsampling them
+    /// to the surface resolution, see [`Renderer::set_render_scale`]. For example a render scale
+    /// of `2.0` results in images which are twice as large as the surface.
+    pub fn set_full_resolution(&mut self, full_resolution: bool) {
+        self.full_resolution = full_resolution;
+    }
+
+    /// The statistics of the last rendered frame.
+    pub fn render_stats(&self) -> RenderStats {
+        self.map_context.renderer.stats()
+    }
+
+    /// Renders the tiles which are already in the world, e.g. inserted with
+    /// [`World::insert_tile`], without fetching any tiles. The tiles are kept.
+    pub fn render(&mut self) -> Result<RgbaImage, StaticMapError> {
+        self.run_schedule();
+        self.read_image().ok_or(StaticMapError::ReadImage)
+    }
+
+    pub fn render_tile(&mut self, layers: TessellatedLayers) {
+        self.insert_layers(
+            (0, 0, ZoomLevel::default()).into(),
+            available_layers(layers).collect(),
+        );
+
+        self.run_schedule();
+
+        self.clear_tiles();
+    }
+
+    /// Moves the camera to `center` and `zoom` and renders once the request systems have loaded
+    /// all tiles in view through the kernel, see [`HeadlessPlugin::with_tile_requests`]. Like in
+    /// an interactive [`Map`](crate::map::Map), the requests are limited by the
+    /// [`RequestSettings`](crate::io::request_settings::RequestSettings) of the world. The layers
+    /// of tiles which fail to load are missing. The tiles are kept for the next view.
+    ///
+    /// The procedures which load the tiles only run on the tokio runtime with the feature
+    /// "thread-safe-futures", see [`TokioScheduler`](crate::platform::scheduler::TokioScheduler).
+    ///
+    /// If the device has been lost while rendering, it is created again once and
+    /// [`MapError::DeviceLost`] is returned, such that the view can be rendered again.
+    ///
+    /// [`HeadlessPlugin::with_tile_requests`]: crate::headless::HeadlessPlugin::with_tile_requests
+    #[cfg(feature = "thread-safe-futures")]
+    pub async fn render_loaded_view(
+        &mut self,
+        center: LatLon,
+        zoom: Zoom,
+    ) -> Result<RgbaImage, StaticMapError> {
+        self.move_camera(center, zoom);
+
+        loop {
+            let tile_count = self.map_context.world.tiles.loaded_coords().len();
+            self.run_schedule();
+
+            if let Some(reason) = self.map_context.renderer.device_lost.take() {
+                self.recover_device(reason).await?;
+                return Err(MapError::DeviceLost(reason).into());
+            }
+
+            // The view is loaded once no tiles are requested anymore and all of them finished
+            let MapContext {
+                view_state, world, ..
+            } = &self.map_context;
+            let is_requesting = view_state.did_camera_change()
+                || view_state.did_zoom_change()
+                || world.tiles.loaded_coords().len() != tile_count;
+            if !is_requesting && !world.tiles.has_loading_tiles() {
+                break;
+            }
+
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+
+        self.read_image().ok_or(StaticMapError::ReadImage)
+    }
+
+    /// Moves the camera to `center` and `zoom`, fetches and tessellates all tiles in view with the
+    /// `source_client` and renders them once all of them are loaded. The tiles are fetched from
+    /// the URLs of the sources of the style, but not through the kernel, such that requests are
+    /// neither limited nor recorded. The layers of tiles which fail to load are missing.
+    ///
+    /// If the device has been lost while rendering, it is created again once and
+    /// [`MapError::DeviceLost`] is returned, such that the view can be rendered again.
+    pub async fn render_view<C: HttpClient>(
+        &mut self,
+        source_client: &SourceClient<C>,
+        center: LatLon,
+        zoom: Zoom,
+    ) -> Result<RgbaImage, StaticMapError> {
+        self.move_camera(center, zoom);
+
+        if let Some(view_region) = self.map_context.view_state.create_view_region() {
+            let sources =
+                requested_source_layers(&self.map_context.style, view_region.zoom_level());
+            let tessellators = Tessellators::default()
+                .with_line_layouts(&self.map_context.style)
+                .with_source_settings(&self.map_context.style);
+            for coords in view_region.iter() {
+                // The layers of all sources are inserted together
+                let mut layers = Vec::new();
+
+                for (vector_source, source_layers) in &sources {
+                    let source =
+                        vector_source_type(&self.map_context.style, vector_source.as_deref());
+                    let crs = source_crs(&self.map_context.style, vector_source.as_deref());
+                    let tessellated =
+                        match fetch_covering_tiles(source_client, &source, coords, crs).await {
+                            Ok(tiles) => tessellate(
+                                coords,
+                                &tiles,
+                                vector_source.clone(),
+                                source_layers.clone(),
+                                tessellators.clone(),
+                                crs,
+                            ),
+                            Err(e) => {
+                                log::warn!("tile at {coords} could not be fetched: {e:?}");
+                                layers.extend(missing_layers(coords, vector_source, source_layers));
+                                continue;
+                            }
+                        };
+
+                    match tessellated {
+                        Ok(tessellated) => layers.extend(available_layers(tessellated)),
+                        Err(e) => {
+                            log::warn!("tile at {coords} could not be processed: {e:?}");
+                            layers.extend(missing_layers(coords, vector_source, source_layers));
+                        }
+                    }
+                }
+
+                self.insert_layers(coords, layers);
+            }
+        }
+
+        self.run_schedule();
+
+        if let Some(reason) = self.map_context.renderer.device_lost.take() {
+            self.recover_device(reason).await?;
+            return Err(MapError::DeviceLost(reason).into());
+        }
+
+        let image = self.read_image();
+        self.clear_tiles();
+        image.ok_or(StaticMapError::ReadImage)
+    }
+
+    /// Like [`HeadlessMap::render_view`], but renders with the `view_projection` instead of the
+    /// camera, see [`ViewState::set_view_projection`]. The camera still determines which tiles are
+    /// fetched.
+    pub async fn render_view_with_view_projection<C: HttpClient>(
         &mut self,
-        layers: Vec<Box<<DefaultVectorTransferables as VectorTransferables>::LayerTessellated>>,
-    ) {
-        let context = &mut self.map_context;
-        let tiles = &mut context.world.tiles;
+        source_client: &SourceClient<C>,
+        center: LatLon,
+        zoom: Zoom,
+        view_projection: Matrix4<f64>,
+    ) -> Result<RgbaImage, StaticMapError> {
+        self.view_projection = Some(view_projection);
+        let image = self.render_view(source_client, center, zoom).await;
+        self.view_projection = None;
+        image
+    }
+
+    /// Tries once to create the device again after it has been lost. Only the resources of the
+    /// lost device are created again, see [`Map::recover_device`](crate::map::Map::recover_device).
+    /// If the device can not be created, the device lost callback of the renderer is invoked.
+    pub async fn recover_device(&mut self, reason: DeviceLostReason) -> Result<(), MapError> {
+        log::warn!("render device lost ({reason:?}), creating it again");
+
+        let window = self.kernel.map_window_config().create();
+        let renderer = &mut self.map_context.renderer;
+        if let Err(e) = renderer.recreate_headless(&window).await {
+            renderer.device_lost.notify(reason);
+            return Err(MapError::DeviceInit(e));
+        }
+
+        self.schedule = Schedule::default();
+
+        for plugin in &self.plugins {
+            plugin.build(
+                &mut self.schedule,
+                self.kernel.clone(),
+                &mut self.map_context.world,
+                &mut self.map_context.renderer.render_graph,
+            );
+        }
+
+        Ok(())
+    }
 
-        tiles
-            .spawn_mut((0, 0, ZoomLevel::default()).into())
+    /// Replaces the camera with one at `center` and `zoom`.
+    fn move_camera(&mut self, center: LatLon, zoom: Zoom) {
+        let window_size = self.map_context.renderer.state().surface().size();
+        self.map_context.view_state = ViewState::new(
+            window_size,
+            WorldCoords::from_lat_lon(center, zoom),
+            zoom,
+            cgmath::Deg(0.0),
+            cgmath::Deg(110.0),
+        );
+        self.map_context
+            .view_state
+            .set_view_projection(self.view_projection);
+    }
+
+    fn run_schedule(&mut self) {
+        self.schedule.run(&mut self.map_context);
+        self.map_context.view_state.notify_bearing_changed();
+    }
+
+    fn insert_layers(&mut self, coords: WorldTileCoords, layers: Vec<VectorLayerData>) {
+        self.map_context
+            .world
+            .tiles
+            .spawn_mut(coords)
             .expect("unable to spawn tile")
             .insert(VectorLayersDataComponent {
                 done: true,
-                layers: layers
-                    .into_iter()
-                    .map(|layer| {
-                        VectorLayerData::Available(AvailableVectorLayerData {
-                            coords: layer.coords,
-                            source_layer: layer.layer_data.name,
-                            buffer: layer.buffer,
-                            feature_indices: layer.feature_indices,
-                        })
-                    })
-                    .collect::<Vec<_>>(),
+                layers,
+                ..VectorLayersDataComponent::default()
             });
+    }
 
-        self.schedule.run(context);
-
-        let resources = &mut context.world.resources;
-        let tiles = &mut context.world.tiles;
+    fn clear_tiles(&mut self) {
+        let resources = &mut self.map_context.world.resources;
+        let tiles = &mut self.map_context.world.tiles;
 
         tiles.clear();
 
@@ -112,49 +363,141 @@ impl HeadlessMap {
         pool.clear();
     }
 
-    pub async fn fetch_tile(&self, coords: WorldTileCoords) -> Result<Box<[u8]>, SourceFetchError> {
+    /// Reads the image which has been rendered last from the headless surface, or from the
+    /// offscreen target of the render scale if the full resolution is requested.
+    fn read_image(&self) -> Option<RgbaImage> {
+        let renderer = &self.map_context.renderer;
+
+        if self.full_resolution && renderer.render_scale() != 1.0 {
+            let state = renderer.state();
+            if let Eventually::Initialized(Some(texture)) = &state.supersampling_texture {
+                return texture.read_image(renderer.device(), renderer.queue());
+            }
+        }
+
+        let surface = renderer.state().surface();
+        let Head::Headless(buffered_texture) = surface.head() else { return None; };
+
+        let buffer_slice = buffered_texture.map_async(renderer.device());
+        let padded_buffer = buffer_slice.get_mapped_range();
+        let image = buffered_texture.to_image(&padded_buffer);
+
+        // All mapped views must be dropped before the buffer is unmapped
+        drop(padded_buffer);
+        buffered_texture.unmap();
+
+        image
+    }
+
+    /// Fetches the tile at `coords` of the vector `source` of the style with the source client of
+    /// the kernel. `None` is the default source.
+    pub async fn fetch_tile(
+        &self,
+        source: Option<&str>,
+        coords: WorldTileCoords,
+    ) -> Result<Box<[u8]>, SourceFetchError> {
         let source_client = self.kernel.source_client();
         let data = source_client
             .fetch(
                 &coords,
-                &SourceType::Tessellate(TessellateSource::default()),
+                &vector_source_type(&self.map_context.style, source),
             )
             .await?
             .into_boxed_slice();
         Ok(data)
     }
 
+    /// Tessellates the `source_layers` of a tile of the vector `source`, see
+    /// [`HeadlessMap::fetch_tile`].
     pub async fn process_tile(
         &self,
+        source: Option<&str>,
         tile_data: Box<[u8]>,
         source_layers: &[&str],
-    ) -> Vec<Box<<DefaultVectorTransferables as VectorTransferables>::LayerTessellated>> {
-        let context = HeadlessContext::default();
-        let mut processor =
-            ProcessVectorContext::<DefaultVectorTransferables, HeadlessContext>::new(context);
-
+    ) -> TessellatedLayers {
         let target_coords = WorldTileCoords::default(); // load to 0,0,0
-        process_vector_tile(
-            &tile_data,
-            VectorTileRequest {
-                coords: target_coords,
-                layers: source_layers
-                    .iter()
-                    .map(|layer| layer.to_string())
-                    .collect(),
-            },
-            &mut processor,
+        tessellate(
+            target_coords,
+            &[(target_coords, tile_data.into_vec())],
+            source.map(str::to_string),
+            source_layers
+                .iter()
+                .map(|layer| layer.to_string())
+                .collect(),
+            Tessellators::default()
+                .with_line_layouts(&self.map_context.style)
+                .with_source_settings(&self.map_context.style),
+            source_crs(&self.map_context.style, source),
         )
-        .expect("Failed to process!");
+        .expect("Failed to process!")
+    }
+}
 
-        let messages = processor.take_context().messages.deref().take();
-        let layers = messages.into_iter()
-            .filter(|message| message.tag() == <DefaultVectorTransferables as VectorTransferables>::LayerTessellated::message_tag())
-            .map(|message| message.into_transferable::<<DefaultVectorTransferables as VectorTransferables>::LayerTessellated>())
-            .collect::<Vec<_>>();
+/// Tessellates the `source_layers` of the `tiles` of the `source` in the `crs`, which cover the
+/// tile at `coords`, see [`process_vector_tiles`].
+fn tessellate(
+    coords: WorldTileCoords,
+    tiles: &[(WorldTileCoords, Vec<u8>)],
+    source: Option<String>,
+    source_layers: HashSet<String>,
+    tessellators: Tessellators,
+    crs: SourceCrs,
+) -> Result<TessellatedLayers, ProcessVectorError> {
+    let context = HeadlessContext::default();
+    let mut processor =
+        ProcessVectorContext::<DefaultVectorTransferables, HeadlessContext>::new(context);
 
-        layers
-    }
+    let tiles = tiles
+        .iter()
+        .map(|(coords, data)| (*coords, data.as_slice()))
+        .collect::<Vec<_>>();
+
+    process_vector_tiles(
+        &tiles,
+        crs,
+        VectorTileRequest {
+            coords,
+            source,
+            layers: source_layers,
+            tessellators,
+            // Only the tessellated layers are kept
+            index: false,
+            deadline: None,
+        },
+        &mut processor,
+    )?;
+
+    let messages = processor.take_context().messages.deref().take();
+    let layers = messages.into_iter()
+        .filter(|message| message.tag() == <DefaultVectorTransferables as VectorTransferables>::LayerTessellated::message_tag())
+        .map(|message| message.into_transferable::<<DefaultVectorTransferables as VectorTransferables>::LayerTessellated>())
+        .collect::<Vec<_>>();
+
+    Ok(layers)
+}
+
+/// The tessellated `layers` as available layers of a tile.
+fn available_layers(layers: TessellatedLayers) -> impl Iterator<Item = VectorLayerData> {
+    layers
+        .into_iter()
+        .map(|layer| VectorLayerData::Available(layer.to_layer()))
+}
+
+/// The `source_layers` of the `source` in the tile at `coords`, which are missing because the
+/// tile failed to load.
+fn missing_layers<'a>(
+    coords: WorldTileCoords,
+    source: &'a Option<String>,
+    source_layers: &'a HashSet<String>,
+) -> impl Iterator<Item = VectorLayerData> + 'a {
+    source_layers.iter().map(move |source_layer| {
+        VectorLayerData::Missing(MissingVectorLayerData {
+            coords,
+            source: source.clone(),
+            source_layer: source_layer.clone(),
+            reason: LayerMissingReason::FetchFailed,
+        })
+    })
 }
 
 #[derive(Default, Clone)]
@@ -1,5 +1,17 @@
 use std::rc::Rc;
 
+#[cfg(feature = "thread-safe-futures")]
+use image::RgbaImage;
+use thiserror::Error;
+
+#[cfg(feature = "thread-safe-futures")]
+use crate::{
+    coords::{LatLon, Zoom},
+    headless::map::HeadlessMap,
+    render::RenderPlugin,
+    style::Style,
+    vector::{DefaultVectorTransferables, VectorPlugin},
+};
 use crate::{
     headless::{
         environment::HeadlessEnvironment,
@@ -7,13 +19,14 @@ use crate::{
         system::WriteSurfaceBufferSystem,
         window::{HeadlessMapWindow, HeadlessMapWindowConfig},
     },
-    io::apc::SchedulerAsyncProcedureCall,
+    io::{apc::SchedulerAsyncProcedureCall, source_client::HttpClient},
     kernel::{Kernel, KernelBuilder},
+    map::MapError,
     platform::{http_client::ReqwestHttpClient, scheduler::TokioScheduler},
     plugin::Plugin,
     render::{
-        builder::RendererBuilder, graph::RenderGraph, tile_view_pattern::ViewTileSources,
-        RenderStageLabel, Renderer,
+        builder::RendererBuilder, error::RenderError, graph::RenderGraph,
+        tile_view_pattern::ViewTileSources, RenderStageLabel, Renderer,
     },
     schedule::Schedule,
     tcs::{system::SystemContainer, world::World},
@@ -53,6 +66,51 @@ pub async fn create_headless_renderer(
     (kernel, renderer)
 }
 
+#[derive(Error, Debug)]
+pub enum StaticMapError {
+    #[error("initializing the renderer failed")]
+    Render(#[from] RenderError),
+    #[error("creating the map failed")]
+    Map(#[from] MapError),
+    #[error("reading the rendered image failed")]
+    ReadImage,
+}
+
+/// Renders the vector layers of the `style` at `center` and `zoom` into an image of the given
+/// `size`, e.g. for a static map server. The tiles are requested from the sources of the `style`
+/// through a kernel, which fetches them with the `client`. All tiles in view are loaded before the
+/// map is rendered, such that no event loop is required, see [`HeadlessMap::render_loaded_view`].
+#[cfg(feature = "thread-safe-futures")]
+pub async fn render_static_map<HC: HttpClient>(
+    style: Style,
+    center: LatLon,
+    zoom: Zoom,
+    size: WindowSize,
+    client: HC,
+) -> Result<RgbaImage, StaticMapError> {
+    let kernel = KernelBuilder::new()
+        .with_map_window_config(HeadlessMapWindowConfig::new(size))
+        .with_http_client(client)
+        .with_apc(SchedulerAsyncProcedureCall::new(TokioScheduler::new()))
+        .with_scheduler(TokioScheduler::new())
+        .build();
+
+    let window: HeadlessMapWindow = kernel.map_window_config().create();
+    let renderer = RendererBuilder::new()
+        .build()
+        .initialize_headless::<HeadlessMapWindowConfig>(&window)
+        .await?;
+
+    let plugins: Vec<Box<dyn Plugin<HeadlessEnvironment<HC>>>> = vec![
+        Box::new(RenderPlugin::default()),
+        Box::new(VectorPlugin::<DefaultVectorTransferables>::default()),
+        Box::new(HeadlessPlugin::new(false).with_tile_requests()),
+    ];
+    let mut map = HeadlessMap::new(style, renderer, kernel, plugins)?;
+
+    map.render_loaded_view(center, zoom).await
+}
+
 /// Labels for the "draw" graph
 mod draw_graph {
     pub const NAME: &str = "draw";
@@ -61,25 +119,40 @@ mod draw_graph {
     // Labels for non-input nodes
     pub mod node {
         pub const MAIN_PASS: &str = "main_pass";
+        pub const DEBUG_PASS: &str = "debug_pass";
         pub const COPY: &str = "copy_pass";
     }
 }
 
 pub struct HeadlessPlugin {
     write_to_disk: bool,
+    request_tiles: bool,
 }
 
 impl HeadlessPlugin {
     pub fn new(write_to_disk: bool) -> Self {
-        Self { write_to_disk }
+        Self {
+            write_to_disk,
+            request_tiles: false,
+        }
+    }
+
+    /// Keeps the systems which request the tiles in view through the [`Kernel`], like in an
+    /// interactive [`Map`](crate::map::Map), e.g. for
+    /// [`HeadlessMap::render_loaded_view`](map::HeadlessMap::render_loaded_view). By default,
+    /// tiles are only fetched by [`HeadlessMap::render_view`](map::HeadlessMap::render_view) or
+    /// inserted into the world.
+    pub fn with_tile_requests(mut self) -> Self {
+        self.request_tiles = true;
+        self
     }
 }
 
-impl Plugin<HeadlessEnvironment> for HeadlessPlugin {
+impl<HC: HttpClient> Plugin<HeadlessEnvironment<HC>> for HeadlessPlugin {
     fn build(
         &self,
         schedule: &mut Schedule,
-        _kernel: Rc<Kernel<HeadlessEnvironment>>,
+        _kernel: Rc<Kernel<HeadlessEnvironment<HC>>>,
         world: &mut World,
         graph: &mut RenderGraph,
     ) {
@@ -93,13 +166,1189 @@ impl Plugin<HeadlessEnvironment> for HeadlessPlugin {
             .add_node_edge(draw_graph::node::MAIN_PASS, draw_graph::node::COPY)
             .unwrap(); // TODO: remove unwrap
 
+        // Debug information is part of the copied image if the debug plugin is built before
+        if draw_graph.get_node_id(draw_graph::node::DEBUG_PASS).is_ok() {
+            draw_graph
+                .add_node_edge(draw_graph::node::DEBUG_PASS, draw_graph::node::COPY)
+                .unwrap();
+        }
+
         schedule.add_system_to_stage(
             RenderStageLabel::Cleanup,
             SystemContainer::new(WriteSurfaceBufferSystem::new(self.write_to_disk)),
         );
 
         // FIXME tcs: Is this good style?
-        schedule.remove_stage(RenderStageLabel::Extract);
+        if !self.request_tiles {
+            schedule.remove_stage(RenderStageLabel::Extract);
+        }
         resources.get_mut::<ViewTileSources>().unwrap().clear();
     }
 }
+
+#[cfg(test)]
+pub mod tests {
+    use std::{cell::Cell, rc::Rc, str::FromStr, time::Duration};
+
+    use cgmath::Matrix4;
+    use csscolorparser::Color;
+    use geo_types::{LineString, Polygon};
+    use geozero::mvt::{tile, Message, Tile};
+    use image::RgbaImage;
+
+    use super::{create_headless_renderer, HeadlessPlugin, StaticMapError};
+    use crate::{
+        coords::{LatLon, WorldCoords, WorldTileCoords, Zoom, ZoomLevel},
+        debug::DebugPlugin,
+        headless::{
+            environment::HeadlessEnvironment,
+            map::HeadlessMap,
+            window::{HeadlessMapWindow, HeadlessMapWindowConfig},
+        },
+        io::{
+            apc::SchedulerAsyncProcedureCall,
+            source_client::{tests::MockHttpClient, HttpSourceClient, SourceClient},
+            source_type::{SourceType, TessellateSource},
+        },
+        kernel::KernelBuilder,
+        map::MapError,
+        overlay::{
+            arrow::LineArrows,
+            line_label::{LineLabel, PlacedGlyph},
+            OverlayPaint, OverlayPlugin,
+        },
+        platform::{http_client::ReqwestHttpClient, scheduler::TokioScheduler},
+        plugin::Plugin,
+        render::{
+            builder::RendererBuilder,
+            color_space::ColorSpace,
+            compositing::OffscreenPass,
+            device_lost::DeviceLostReason,
+            settings::{Backend, RendererSettings, TextureFormat},
+            RenderPlugin,
+        },
+        style::{
+            glyph::{GlyphAtlas, GlyphBitmap, GLYPH_BORDER, GLYPH_SIZE},
+            layer::{BlendMode, FillPaint, LayerLayout, LayerPaint, LinePaint, StyleLayer},
+            sprite::SpriteAtlas,
+            Style,
+        },
+        tcs::world::InsertTileError,
+        vector::{
+            DefaultVectorTransferables, FeatureData, VectorLayersDataComponent, VectorPlugin,
+        },
+        view_state::ViewState,
+        window::{MapWindowConfig, WindowSize},
+    };
+
+    /// A tile which is covered by a single water polygon
+    pub fn water_tile() -> Vec<u8> {
+        let layer = tile::Layer {
+            version: 2,
+            name: "water".to_string(),
+            features: vec![tile::Feature {
+                id: Some(1),
+                tags: vec![],
+                r#type: Some(tile::GeomType::Polygon as i32),
+                // A square covering the whole extent of 4096
+                geometry: vec![9, 0, 0, 26, 8192, 0, 0, 8192, 8191, 0, 15],
+            }],
+            keys: vec![],
+            values: vec![],
+            extent: Some(4096),
+        };
+        Tile {
+            layers: vec![layer],
+        }
+        .encode_to_vec()
+    }
+
+    pub fn water_style() -> Style {
+        Style {
+            layers: vec![StyleLayer {
+                id: "water".to_string(),
+                paint: Some(LayerPaint::Fill(FillPaint {
+                    fill_color: Some(Color::from_str("#ff0000").unwrap()),
+                    fill_pattern: None,
+                })),
+                source_layer: Some("water".to_string()),
+                ..StyleLayer::default()
+            }],
+            ..Style::default()
+        }
+    }
+
+    /// A yellow water layer below a cyan water layer which is blended with `blend_mode`
+    fn overlapping_water_style(blend_mode: BlendMode) -> Style {
+        let layer = |id: &str, index: u32, color: &str, blend_mode: Option<BlendMode>| StyleLayer {
+            id: id.to_string(),
+            index,
+            paint: Some(LayerPaint::Fill(FillPaint {
+                fill_color: Some(Color::from_str(color).unwrap()),
+                fill_pattern: None,
+            })),
+            source_layer: Some("water".to_string()),
+            blend_mode,
+            ..StyleLayer::default()
+        };
+
+        Style {
+            layers: vec![
+                layer("bottom", 0, "#ffff00", None),
+                layer("top", 1, "#00ffff", Some(blend_mode)),
+            ],
+            ..Style::default()
+        }
+    }
+
+    /// Serves the [`water_tile`] for all coordinates
+    fn water_http_client() -> MockHttpClient {
+        MockHttpClient::serving(water_tile())
+    }
+
+    /// A kernel for a window of `size` pixels, whose procedures run on the tokio runtime. Its HTTP
+    /// client is not used if the tiles are produced by a tile generator.
+    pub fn kernel_builder(size: u32) -> KernelBuilder<HeadlessEnvironment> {
+        KernelBuilder::new()
+            .with_map_window_config(HeadlessMapWindowConfig::new(
+                WindowSize::new(size, size).unwrap(),
+            ))
+            .with_http_client(ReqwestHttpClient::new(None))
+            .with_apc(SchedulerAsyncProcedureCall::new(TokioScheduler::new()))
+            .with_scheduler(TokioScheduler::new())
+    }
+
+    /// A map of the `style` whose tiles are requested through the kernel of the `kernel_builder`
+    /// by the request system of the `plugin`, see [`HeadlessPlugin::with_tile_requests`]
+    pub async fn requesting_map(
+        kernel_builder: KernelBuilder<HeadlessEnvironment>,
+        style: Style,
+        plugin: Box<dyn Plugin<HeadlessEnvironment>>,
+    ) -> HeadlessMap {
+        let kernel = kernel_builder.build();
+        let window: HeadlessMapWindow = kernel.map_window_config().create();
+        let renderer = RendererBuilder::new()
+            .build()
+            .initialize_headless::<HeadlessMapWindowConfig>(&window)
+            .await
+            .unwrap();
+        let plugins: Vec<Box<dyn Plugin<HeadlessEnvironment>>> = vec![
+            Box::new(RenderPlugin::default()),
+            plugin,
+            Box::new(HeadlessPlugin::new(false).with_tile_requests()),
+        ];
+        HeadlessMap::new(style, renderer, kernel, plugins).unwrap()
+    }
+
+    /// A map of the [`water_style`] whose vector tiles are requested through the kernel of the
+    /// `kernel_builder`
+    pub async fn water_map(kernel_builder: KernelBuilder<HeadlessEnvironment>) -> HeadlessMap {
+        requesting_map(
+            kernel_builder,
+            water_style(),
+            Box::new(VectorPlugin::<DefaultVectorTransferables>::default()),
+        )
+        .await
+    }
+
+    #[cfg(feature = "thread-safe-futures")]
+    #[tokio::test]
+    async fn test_render_static_map() {
+        use std::sync::{Arc, Mutex};
+
+        use super::render_static_map;
+        use crate::style::source::{Source, VectorSource};
+
+        let mut style = water_style();
+        style.sources.insert(
+            "water".to_string(),
+            Source::Vector(VectorSource {
+                tiles: Some("https://example.com/water/{z}/{x}/{y}.mvt".to_string()),
+                ..VectorSource::default()
+            }),
+        );
+        style.layers[0].source = Some("water".to_string());
+
+        // The tiles are requested from the source of the style with the given client
+        let urls = Arc::new(Mutex::new(Vec::new()));
+        let requested = urls.clone();
+        let client = MockHttpClient::new(move |url, _key| {
+            requested.lock().unwrap().push(url.to_string());
+            Ok(water_tile())
+        });
+
+        let image = render_static_map(
+            style,
+            LatLon::new(48.137154, 11.576124),
+            Zoom::new(10.0),
+            WindowSize::new(64, 48).unwrap(),
+            client,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(image.dimensions(), (64, 48));
+
+        let [red, green, blue, _] = image.get_pixel(32, 24).0;
+        assert!(red > 200 && green < 50 && blue < 50, "{red} {green} {blue}");
+
+        let urls = urls.lock().unwrap();
+        assert!(!urls.is_empty());
+        assert!(
+            urls.iter()
+                .all(|url| url.starts_with("https://example.com/water/10/")),
+            "{urls:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_offscreen_pass() {
+        /// Swaps the red and the green channel of the offscreen texture
+        const SWAP_EFFECT: &str = r#"
+            @group(0) @binding(0)
+            var t_diffuse: texture_2d<f32>;
+            @group(0) @binding(1)
+            var s_diffuse: sampler;
+
+            @fragment
+            fn main(@location(0) tex_coords: vec2<f32>) -> @location(0) vec4<f32> {
+                let color = textureSample(t_diffuse, s_diffuse, tex_coords);
+                return vec4<f32>(color.g, color.r, color.b, color.a);
+            }
+        "#;
+
+        async fn center_pixel(offscreen_pass: OffscreenPass) -> [u8; 3] {
+            let (kernel, renderer) = create_headless_renderer(64, None).await;
+            let plugins: Vec<Box<dyn Plugin<HeadlessEnvironment>>> = vec![
+                Box::new(RenderPlugin::default()),
+                Box::new(VectorPlugin::<DefaultVectorTransferables>::default()),
+                Box::new(HeadlessPlugin::new(false)),
+            ];
+            let mut map = HeadlessMap::new(water_style(), renderer, kernel, plugins).unwrap();
+            map.renderer_mut().set_offscreen_pass(Some(offscreen_pass));
+
+            map.insert_tile(
+                None,
+                WorldTileCoords::from((0, 0, ZoomLevel::default())),
+                &SourceType::Tessellate(TessellateSource::default()),
+                &water_tile(),
+            )
+            .unwrap();
+
+            let image = map.render().unwrap();
+            let [red, green, blue, _] = image.get_pixel(32, 32).0;
+            [red, green, blue]
+        }
+
+        // The red water layer is composited unchanged over the white background
+        let [red, green, blue] = center_pixel(OffscreenPass::new(["water"])).await;
+        assert!(red > 200 && green < 50 && blue < 50, "{red} {green} {blue}");
+
+        // The effect is only applied to the offscreen layer
+        let [red, green, blue] =
+            center_pixel(OffscreenPass::new(["water"]).with_effect(SWAP_EFFECT)).await;
+        assert!(red < 50 && green > 200 && blue < 50, "{red} {green} {blue}");
+    }
+
+    #[tokio::test]
+    async fn test_adapter_info() {
+        let (_, renderer) = create_headless_renderer(64, None).await;
+
+        let info = renderer.adapter_info().unwrap();
+        assert!(!info.name.is_empty());
+        assert_ne!(info.backend, Backend::Empty);
+
+        let limits = renderer.device_limits();
+        assert!(limits.max_texture_dimension_2d >= 64);
+        assert!(limits.max_buffer_size > 0);
+    }
+
+    #[tokio::test]
+    async fn test_render_stats() {
+        let (kernel, renderer) = create_headless_renderer(64, None).await;
+        let plugins: Vec<Box<dyn Plugin<HeadlessEnvironment>>> = vec![
+            Box::new(RenderPlugin::default()),
+            Box::new(VectorPlugin::<DefaultVectorTransferables>::default()),
+            Box::new(HeadlessPlugin::new(false)),
+        ];
+        let mut map = HeadlessMap::new(water_style(), renderer, kernel, plugins).unwrap();
+
+        let layers = map
+            .process_tile(None, water_tile().into(), &["water"])
+            .await;
+        let triangles = layers
+            .iter()
+            .map(|layer| layer.buffer.usable_indices as u64 / 3)
+            .sum::<u64>();
+        assert!(triangles > 0);
+
+        // The masks of the tiles are drawn regardless of the loaded layers
+        map.render_tile(Vec::new());
+        let empty = map.render_stats();
+
+        map.render_tile(layers);
+        let water = map.render_stats();
+
+        assert_eq!(water.draw_calls, empty.draw_calls + 1);
+        assert_eq!(water.triangles, empty.triangles + triangles);
+        assert!(water.buffer_bytes > empty.buffer_bytes);
+    }
+
+    #[tokio::test]
+    async fn test_gpu_time() {
+        let (kernel, mut renderer) = create_headless_renderer(64, None).await;
+        renderer.settings.gpu_timing = true;
+        let supported = renderer
+            .device()
+            .features()
+            .contains(wgpu::Features::TIMESTAMP_QUERY);
+
+        let plugins: Vec<Box<dyn Plugin<HeadlessEnvironment>>> = vec![
+            Box::new(RenderPlugin::default()),
+            Box::new(VectorPlugin::<DefaultVectorTransferables>::default()),
+            Box::new(HeadlessPlugin::new(false)),
+        ];
+        let mut map = HeadlessMap::new(water_style(), renderer, kernel, plugins).unwrap();
+
+        let layers = map
+            .process_tile(None, water_tile().into(), &["water"])
+            .await;
+        map.render_tile(layers);
+
+        let gpu_time = map.render_stats().gpu_time;
+        if supported {
+            assert!(gpu_time.unwrap() > Duration::ZERO);
+        } else {
+            assert_eq!(gpu_time, None);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_debug_tile_status() {
+        let (kernel, renderer) = create_headless_renderer(64, None).await;
+        let plugins: Vec<Box<dyn Plugin<HeadlessEnvironment>>> = vec![
+            Box::new(RenderPlugin::default()),
+            Box::new(VectorPlugin::<DefaultVectorTransferables>::default()),
+            Box::new(DebugPlugin::default()),
+            Box::new(HeadlessPlugin::new(false)),
+        ];
+        let mut map = HeadlessMap::new(water_style(), renderer, kernel, plugins).unwrap();
+        map.world_mut().set_debug_tile_status(true);
+
+        let source_client = SourceClient::new(HttpSourceClient::new(MockHttpClient::failing()));
+        let image = map
+            .render_view(
+                &source_client,
+                LatLon::new(48.137154, 11.576124),
+                Zoom::new(10.0),
+            )
+            .await
+            .unwrap();
+
+        // The semi-transparent red error color is blended with the white background
+        let [red, green, blue, _] = image.get_pixel(32, 32).0;
+        assert!(
+            red > 200 && green < red - 40 && green.abs_diff(blue) < 10,
+            "{red} {green} {blue}"
+        );
+    }
+
+    #[cfg(feature = "thread-safe-futures")]
+    #[tokio::test]
+    async fn test_blend_modes() {
+        use super::render_static_map;
+
+        async fn center_pixel(blend_mode: BlendMode) -> [u8; 3] {
+            let image = render_static_map(
+                overlapping_water_style(blend_mode),
+                LatLon::new(48.137154, 11.576124),
+                Zoom::new(10.0),
+                WindowSize::new(64, 48).unwrap(),
+                water_http_client(),
+            )
+            .await
+            .unwrap();
+
+            let [red, green, blue, _] = image.get_pixel(32, 24).0;
+            [red, green, blue]
+        }
+
+        // Cyan covers yellow
+        let [red, green, blue] = center_pixel(BlendMode::Normal).await;
+        assert!(
+            red < 50 && green > 200 && blue > 200,
+            "{red} {green} {blue}"
+        );
+
+        // Cyan multiplied with yellow results in green
+        let [red, green, blue] = center_pixel(BlendMode::Multiply).await;
+        assert!(red < 50 && green > 200 && blue < 50, "{red} {green} {blue}");
+    }
+
+    #[tokio::test]
+    async fn test_fill_pattern() {
+        let style = Style {
+            layers: vec![StyleLayer {
+                id: "water".to_string(),
+                paint: Some(LayerPaint::Fill(FillPaint {
+                    fill_color: None,
+                    fill_pattern: Some("stripes".to_string()),
+                })),
+                source_layer: Some("water".to_string()),
+                ..StyleLayer::default()
+            }],
+            ..Style::default()
+        };
+
+        let (kernel, renderer) = create_headless_renderer(64, None).await;
+        let plugins: Vec<Box<dyn Plugin<HeadlessEnvironment>>> = vec![
+            Box::new(RenderPlugin::default()),
+            Box::new(VectorPlugin::<DefaultVectorTransferables>::default()),
+            Box::new(HeadlessPlugin::new(false)),
+        ];
+        let mut map = HeadlessMap::new(style, renderer, kernel, plugins).unwrap();
+
+        // Vertical stripes which are 4 pixels wide, red on the left and blue on the right
+        let pixels = (0..8 * 8)
+            .flat_map(|i| {
+                if i % 8 < 4 {
+                    [255, 0, 0, 255]
+                } else {
+                    [0, 0, 255, 255]
+                }
+            })
+            .collect();
+        let index = r#"{"stripes": {"x": 0, "y": 0, "width": 8, "height": 8, "pixelRatio": 1}}"#;
+        map.world_mut()
+            .set_sprite_atlas(SpriteAtlas::new(8, 8, pixels, index).unwrap());
+
+        let source_client = SourceClient::new(HttpSourceClient::new(water_http_client()));
+        let image = map
+            .render_view(
+                &source_client,
+                LatLon::new(48.137154, 11.576124),
+                Zoom::new(10.0),
+            )
+            .await
+            .unwrap();
+
+        // A flat color would result in a single color along the row
+        let row = (0..64)
+            .map(|x| image.get_pixel(x, 32).0)
+            .collect::<Vec<_>>();
+        assert!(row
+            .iter()
+            .any(|[red, green, blue, _]| *red > 200 && *green < 50 && *blue < 50));
+        assert!(row
+            .iter()
+            .any(|[red, green, blue, _]| *red < 50 && *green < 50 && *blue > 200));
+    }
+
+    #[tokio::test]
+    async fn test_focus_region() {
+        let (kernel, renderer) = create_headless_renderer(64, None).await;
+        let plugins: Vec<Box<dyn Plugin<HeadlessEnvironment>>> = vec![
+            Box::new(RenderPlugin::default()),
+            Box::new(VectorPlugin::<DefaultVectorTransferables>::default()),
+            Box::new(OverlayPlugin::default()),
+            Box::new(HeadlessPlugin::new(false)),
+        ];
+        let mut map = HeadlessMap::new(water_style(), renderer, kernel, plugins).unwrap();
+
+        // A square of roughly 15 pixels around the center
+        let (latitude, longitude) = (48.137154, 11.576124);
+        let region = Polygon::new(
+            LineString::from(vec![
+                (longitude - 0.005, latitude - 0.005),
+                (longitude + 0.005, latitude - 0.005),
+                (longitude + 0.005, latitude + 0.005),
+                (longitude - 0.005, latitude + 0.005),
+            ]),
+            vec![],
+        );
+        map.world_mut().set_focus_region(Some(region)).unwrap();
+
+        let source_client = SourceClient::new(HttpSourceClient::new(water_http_client()));
+        let image = map
+            .render_view(
+                &source_client,
+                LatLon::new(latitude, longitude),
+                Zoom::new(10.0),
+            )
+            .await
+            .unwrap();
+
+        // The red water is not dimmed within the region
+        let [red, green, blue, _] = image.get_pixel(32, 32).0;
+        assert!(red > 200 && green < 50 && blue < 50, "{red} {green} {blue}");
+
+        // Outside of the region the water is dimmed by the semi-transparent black
+        for (x, y) in [(2, 2), (61, 32), (32, 61)] {
+            let [dimmed, green, blue, _] = image.get_pixel(x, y).0;
+            assert!(
+                dimmed < red - 50 && dimmed > 50 && green < 50 && blue < 50,
+                "{dimmed} {green} {blue}"
+            );
+        }
+    }
+
+    /// The centers of the arrows along a horizontal line through the center of a view at `zoom`
+    async fn line_arrow_centers(zoom: f64, half_length: f64) -> Vec<f32> {
+        let (kernel, renderer) = create_headless_renderer(64, None).await;
+        let plugins: Vec<Box<dyn Plugin<HeadlessEnvironment>>> = vec![
+            Box::new(RenderPlugin::default()),
+            Box::new(VectorPlugin::<DefaultVectorTransferables>::default()),
+            Box::new(OverlayPlugin::default()),
+            Box::new(HeadlessPlugin::new(false)),
+        ];
+        let mut map = HeadlessMap::new(water_style(), renderer, kernel, plugins).unwrap();
+
+        // The line is longer than the view is wide
+        let (latitude, longitude) = (48.137154, 11.576124);
+        let line = format!(
+            r#"{{"type": "LineString", "coordinates": [[{}, {latitude}], [{}, {latitude}]]}}"#,
+            longitude - half_length,
+            longitude + half_length
+        );
+        map.world_mut()
+            .add_geojson_overlay(
+                "route",
+                &line,
+                OverlayPaint {
+                    color: Color::new(0.0, 1.0, 0.0, 1.0),
+                    width: 4.0,
+                    arrows: Some(LineArrows {
+                        color: Color::new(0.0, 0.0, 1.0, 1.0),
+                        spacing: 16.0,
+                        size: 8.0,
+                    }),
+                },
+            )
+            .unwrap();
+
+        let source_client = SourceClient::new(HttpSourceClient::new(water_http_client()));
+        let image = map
+            .render_view(
+                &source_client,
+                LatLon::new(latitude, longitude),
+                Zoom::new(zoom),
+            )
+            .await
+            .unwrap();
+
+        let mut arrows = Vec::new();
+        let mut start = None;
+        for x in 0..64 {
+            let [red, green, blue, _] = image.get_pixel(x, 32).0;
+            let is_arrow = red < 50 && green < 50 && blue > 200;
+            match (is_arrow, start) {
+                (true, None) => start = Some(x),
+                (false, Some(first)) => {
+                    arrows.push((first + x - 1) as f32 / 2.0);
+                    start = None;
+                }
+                _ => {}
+            }
+        }
+
+        // Arrows which are cut off at the border of the view are not considered
+        if image.get_pixel(0, 32).0[2] > 200 {
+            arrows.remove(0);
+        }
+
+        // The line is visible between the arrows
+        if let Some(first) = arrows.first() {
+            let [red, green, blue, _] = image.get_pixel((first + 8.0) as u32, 32).0;
+            assert!(red < 50 && green > 200 && blue < 50, "{red} {green} {blue}");
+        }
+
+        arrows
+    }
+
+    #[tokio::test]
+    async fn test_line_arrows() {
+        // Arrows keep their shape at high zoom levels
+        for (zoom, half_length) in [(10.0, 0.05), (18.0, 0.0005)] {
+            let arrows = line_arrow_centers(zoom, half_length).await;
+
+            assert!(arrows.len() >= 3, "{zoom}: {arrows:?}");
+            for pair in arrows.windows(2) {
+                let spacing = pair[1] - pair[0];
+                assert!((spacing - 16.0).abs() <= 1.5, "{zoom}: {arrows:?}");
+            }
+        }
+    }
+
+    /// An atlas in which every character of the `text` is a filled rectangle of 6 by 10 pixels
+    /// with an advance of 8 at the size of the distance fields. Its baseline is at the bottom.
+    fn rectangle_glyph_atlas(text: &str) -> GlyphAtlas {
+        let (width, height) = (6, 10);
+        GlyphAtlas::new(text.chars().map(|character| {
+            let bitmap = (0..height + 2 * GLYPH_BORDER)
+                .flat_map(|y| {
+                    (0..width + 2 * GLYPH_BORDER).map(move |x| {
+                        let inside = (GLYPH_BORDER..width + GLYPH_BORDER).contains(&x)
+                            && (GLYPH_BORDER..height + GLYPH_BORDER).contains(&y);
+                        if inside {
+                            255
+                        } else {
+                            0
+                        }
+                    })
+                })
+                .collect();
+
+            GlyphBitmap {
+                id: character as u32,
+                bitmap,
+                width,
+                height,
+                left: 1,
+                top: 10,
+                advance: 8,
+            }
+        }))
+    }
+
+    /// Asserts that the glyphs of "Isar" are centered on a horizontal line through the center of
+    /// the `image` and are drawn in black over the red water.
+    fn assert_isar_label(glyphs: &[PlacedGlyph], image: &RgbaImage) {
+        let characters = glyphs
+            .iter()
+            .map(|glyph| glyph.character)
+            .collect::<String>();
+        assert_eq!(characters, "Isar");
+
+        // The glyphs are centered on the line and read from the left to the right
+        for (i, glyph) in glyphs.iter().enumerate() {
+            let x = 32.0 - 16.0 + 8.0 * i as f64 + 4.0;
+            assert!((glyph.x - x).abs() <= 1.0, "{glyphs:?}");
+            assert!((glyph.y - 32.0).abs() <= 1.0, "{glyphs:?}");
+            assert!(glyph.angle.abs() < 1e-3, "{glyphs:?}");
+
+            let [red, green, blue, _] = image.get_pixel(x as u32, 36).0;
+            assert!(red < 50 && green < 50 && blue < 50, "{red} {green} {blue}");
+        }
+
+        // The water is visible above the label and next to it
+        for (x, y) in [(20, 20), (4, 36), (60, 36)] {
+            let [red, green, blue, _] = image.get_pixel(x, y).0;
+            assert!(red > 200 && green < 50 && blue < 50, "{red} {green} {blue}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_line_label() {
+        let (kernel, renderer) = create_headless_renderer(64, None).await;
+        let plugins: Vec<Box<dyn Plugin<HeadlessEnvironment>>> = vec![
+            Box::new(RenderPlugin::default()),
+            Box::new(VectorPlugin::<DefaultVectorTransferables>::default()),
+            Box::new(OverlayPlugin::default()),
+            Box::new(HeadlessPlugin::new(false)),
+        ];
+        let mut map = HeadlessMap::new(water_style(), renderer, kernel, plugins).unwrap();
+
+        // A horizontal line through the center which runs from the east to the west
+        let (latitude, longitude) = (48.137154, 11.576124);
+        let label = LineLabel {
+            id: "river".to_string(),
+            text: "Isar".to_string(),
+            line: vec![
+                LatLon::new(latitude, longitude + 0.05),
+                LatLon::new(latitude, longitude - 0.05),
+            ],
+            size: GLYPH_SIZE,
+            ..LineLabel::default()
+        };
+        map.world_mut().add_line_label(label);
+
+        let source_client = SourceClient::new(HttpSourceClient::new(water_http_client()));
+        let center = LatLon::new(latitude, longitude);
+
+        // Nothing is placed without glyphs
+        map.render_view(&source_client, center, Zoom::new(10.0))
+            .await
+            .unwrap();
+        assert!(map.world_mut().placed_line_label("river").is_none());
+
+        map.world_mut()
+            .set_glyph_atlas(rectangle_glyph_atlas("Isar"));
+        let image = map
+            .render_view(&source_client, center, Zoom::new(10.0))
+            .await
+            .unwrap();
+
+        let glyphs = map.world_mut().placed_line_label("river").unwrap().to_vec();
+        assert_isar_label(&glyphs, &image);
+
+        assert!(map.world_mut().remove_line_label("river"));
+        assert!(map.world_mut().placed_line_label("river").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_line_label_from_style() {
+        let (kernel, renderer) = create_headless_renderer(64, None).await;
+        let plugins: Vec<Box<dyn Plugin<HeadlessEnvironment>>> = vec![
+            Box::new(RenderPlugin::default()),
+            Box::new(VectorPlugin::<DefaultVectorTransferables>::default()),
+            Box::new(OverlayPlugin::default()),
+            Box::new(HeadlessPlugin::new(false)),
+        ];
+
+        // The river is labeled with its name
+        let mut style = water_style();
+        style.layers.push(StyleLayer {
+            id: "river".to_string(),
+            index: 1,
+            paint: Some(LayerPaint::Line(LinePaint {
+                line_color: Some(Color::from_str("#0000ff").unwrap()),
+            })),
+            layout: Some(LayerLayout {
+                text_field: Some("{name}".to_string()),
+                text_size: Some(GLYPH_SIZE),
+                ..LayerLayout::default()
+            }),
+            source_layer: Some("rivers".to_string()),
+            ..StyleLayer::default()
+        });
+        let mut map = HeadlessMap::new(style, renderer, kernel, plugins).unwrap();
+        map.world_mut()
+            .set_glyph_atlas(rectangle_glyph_atlas("Isar"));
+
+        // A horizontal line through the center of the tile, which is in the center of the view
+        let rivers = tile::Layer {
+            version: 2,
+            name: "rivers".to_string(),
+            features: vec![tile::Feature {
+                id: Some(7),
+                tags: vec![0, 0],
+                r#type: Some(tile::GeomType::Linestring as i32),
+                geometry: vec![9, 2048, 4096, 10, 4096, 0],
+            }],
+            keys: vec!["name".to_string()],
+            values: vec![tile::Value {
+                string_value: Some("Isar".to_string()),
+                ..tile::Value::default()
+            }],
+            extent: Some(4096),
+        };
+        let mut data = Tile::decode(water_tile().as_slice()).unwrap();
+        data.layers.push(rivers);
+
+        map.insert_tile(
+            None,
+            WorldTileCoords::from((0, 0, ZoomLevel::default())),
+            &SourceType::Tessellate(TessellateSource::default()),
+            &data.encode_to_vec(),
+        )
+        .unwrap();
+        let image = map.render().unwrap();
+
+        let glyphs = map
+            .world_mut()
+            .placed_line_label("river/7")
+            .unwrap()
+            .to_vec();
+        assert_isar_label(&glyphs, &image);
+    }
+
+    #[tokio::test]
+    async fn test_device_lost() {
+        let (kernel, renderer) = create_headless_renderer(64, None).await;
+        let plugins: Vec<Box<dyn Plugin<HeadlessEnvironment>>> = vec![
+            Box::new(RenderPlugin::default()),
+            Box::new(VectorPlugin::<DefaultVectorTransferables>::default()),
+            Box::new(HeadlessPlugin::new(false)),
+        ];
+        let mut map = HeadlessMap::new(water_style(), renderer, kernel, plugins).unwrap();
+
+        let lost = Rc::new(Cell::new(None));
+        let callback_lost = lost.clone();
+        map.renderer_mut()
+            .set_device_lost_callback(Box::new(move |reason| callback_lost.set(Some(reason))));
+
+        let source_client = SourceClient::new(HttpSourceClient::new(water_http_client()));
+        let (center, zoom) = (LatLon::new(48.137154, 11.576124), Zoom::new(10.0));
+
+        // The data of another source does not change the rendered view
+        map.world_mut().set_feature_data("other", 1, 1.0);
+
+        // The device is created again, so the callback is not invoked and the view can be rendered
+        // again
+        map.renderer_mut()
+            .device_lost
+            .report(DeviceLostReason::Lost);
+        assert!(matches!(
+            map.render_view(&source_client, center, zoom).await,
+            Err(StaticMapError::Map(MapError::DeviceLost(
+                DeviceLostReason::Lost
+            )))
+        ));
+        assert_eq!(lost.get(), None);
+
+        // Only the resources of the lost device are created again
+        let feature_data = map.world_mut().resources.get::<FeatureData>().unwrap();
+        assert_eq!(feature_data.get("other", 1), Some(1.0));
+
+        let image = map.render_view(&source_client, center, zoom).await.unwrap();
+        let [red, green, blue, _] = image.get_pixel(32, 32).0;
+        assert!(red > 200 && green < 50 && blue < 50, "{red} {green} {blue}");
+
+        // Without an instance the device can not be created again, so the callback is invoked
+        map.renderer_mut().instance = None;
+        map.renderer_mut()
+            .device_lost
+            .report(DeviceLostReason::OutOfMemory);
+        assert!(matches!(
+            map.render_view(&source_client, center, zoom).await,
+            Err(StaticMapError::Map(MapError::DeviceInit(_)))
+        ));
+        assert_eq!(lost.get(), Some(DeviceLostReason::OutOfMemory));
+    }
+
+    #[tokio::test]
+    async fn test_custom_view_projection() {
+        let (kernel, renderer) = create_headless_renderer(64, None).await;
+        let plugins: Vec<Box<dyn Plugin<HeadlessEnvironment>>> = vec![
+            Box::new(RenderPlugin::default()),
+            Box::new(VectorPlugin::<DefaultVectorTransferables>::default()),
+            Box::new(HeadlessPlugin::new(false)),
+        ];
+        let mut map = HeadlessMap::new(water_style(), renderer, kernel, plugins).unwrap();
+
+        let source_client = SourceClient::new(HttpSourceClient::new(water_http_client()));
+        let (center, zoom) = (LatLon::new(48.137154, 11.576124), Zoom::new(10.0));
+
+        let camera = map.render_view(&source_client, center, zoom).await.unwrap();
+
+        // The same view projection as the camera, but the map is shrunk to half of the view
+        let view_projection = ViewState::new(
+            WindowSize::new(64, 64).unwrap(),
+            WorldCoords::from_lat_lon(center, zoom),
+            zoom,
+            cgmath::Deg(0.0),
+            cgmath::Deg(110.0),
+        )
+        .view_projection()
+        .matrix();
+        let custom = map
+            .render_view_with_view_projection(
+                &source_client,
+                center,
+                zoom,
+                Matrix4::from_nonuniform_scale(0.5, 0.5, 1.0) * view_projection,
+            )
+            .await
+            .unwrap();
+
+        assert_ne!(camera, custom);
+
+        // The water covers the whole view of the camera, but only the center of the custom view
+        let is_water = |[red, green, blue, _]: [u8; 4]| red > 200 && green < 50 && blue < 50;
+        assert!(is_water(camera.get_pixel(32, 32).0));
+        assert!(is_water(camera.get_pixel(2, 2).0));
+        assert!(is_water(custom.get_pixel(32, 32).0));
+        assert!(!is_water(custom.get_pixel(2, 2).0));
+
+        // Rendering with the camera is not affected
+        let again = map.render_view(&source_client, center, zoom).await.unwrap();
+        assert_eq!(camera, again);
+    }
+
+    #[tokio::test]
+    async fn test_insert_tile() {
+        let (kernel, renderer) = create_headless_renderer(64, None).await;
+        let plugins: Vec<Box<dyn Plugin<HeadlessEnvironment>>> = vec![
+            Box::new(RenderPlugin::default()),
+            Box::new(VectorPlugin::<DefaultVectorTransferables>::default()),
+            Box::new(HeadlessPlugin::new(false)),
+        ];
+        let mut map = HeadlessMap::new(water_style(), renderer, kernel, plugins).unwrap();
+
+        let coords = WorldTileCoords::from((0, 0, ZoomLevel::default()));
+        map.insert_tile(
+            None,
+            coords,
+            &SourceType::Tessellate(TessellateSource::default()),
+            &water_tile(),
+        )
+        .unwrap();
+        let world = map.world_mut();
+
+        // The tile is loaded and its features can be queried without fetching it
+        let component = world
+            .tiles
+            .query::<&VectorLayersDataComponent>(coords)
+            .unwrap();
+        assert!(component.done);
+        assert_eq!(component.layers.len(), 1);
+        assert_eq!(
+            world.tiles.geometry_index.layer_features(&coords).count(),
+            1
+        );
+
+        let image = map.render().unwrap();
+        let [red, green, blue, _] = image.get_pixel(32, 32).0;
+        assert!(red > 200 && green < 50 && blue < 50, "{red} {green} {blue}");
+
+        // Malformed input is rejected without touching the world
+        let source = SourceType::Tessellate(TessellateSource::default());
+        let outside = WorldTileCoords::from((1, 0, ZoomLevel::default()));
+        assert!(matches!(
+            map.insert_tile(None, outside, &source, &water_tile()),
+            Err(InsertTileError::OutOfBounds(_))
+        ));
+        assert!(matches!(
+            map.insert_tile(None, coords, &source, &[0xff; 4]),
+            Err(InsertTileError::Vector(_))
+        ));
+        assert!(map
+            .world_mut()
+            .tiles
+            .query::<&VectorLayersDataComponent>(outside)
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_full_resolution() {
+        let (kernel, mut renderer) = create_headless_renderer(64, None).await;
+        renderer.set_render_scale(2.0).unwrap();
+        let plugins: Vec<Box<dyn Plugin<HeadlessEnvironment>>> = vec![
+            Box::new(RenderPlugin::default()),
+            Box::new(VectorPlugin::<DefaultVectorTransferables>::default()),
+            Box::new(HeadlessPlugin::new(false)),
+        ];
+        let mut map = HeadlessMap::new(water_style(), renderer, kernel, plugins).unwrap();
+        map.insert_tile(
+            None,
+            WorldTileCoords::from((0, 0, ZoomLevel::default())),
+            &SourceType::Tessellate(TessellateSource::default()),
+            &water_tile(),
+        )
+        .unwrap();
+
+        // By default the image is downsampled to the surface
+        let image = map.render().unwrap();
+        assert_eq!(image.dimensions(), (64, 64));
+        let [red, green, blue, _] = image.get_pixel(32, 32).0;
+        assert!(red > 200 && green < 50 && blue < 50, "{red} {green} {blue}");
+
+        map.set_full_resolution(true);
+        let image = map.render().unwrap();
+        assert_eq!(image.dimensions(), (128, 128));
+        let [red, green, blue, _] = image.get_pixel(64, 64).0;
+        assert!(red > 200 && green < 50 && blue < 50, "{red} {green} {blue}");
+    }
+
+    #[tokio::test]
+    async fn test_color_space() {
+        for (format, color_space) in [
+            (TextureFormat::Rgba8Unorm, ColorSpace::Srgb),
+            (TextureFormat::Rgba8UnormSrgb, ColorSpace::Linear),
+        ] {
+            let (kernel, _) = create_headless_renderer(64, None).await;
+            let window: HeadlessMapWindow = kernel.map_window_config().create();
+            let renderer = RendererBuilder::new()
+                .with_renderer_settings(RendererSettings {
+                    texture_format: Some(format),
+                    ..RendererSettings::default()
+                })
+                .build()
+                .initialize_headless::<HeadlessMapWindowConfig>(&window)
+                .await
+                .unwrap();
+            assert_eq!(renderer.color_space(), color_space);
+
+            let mut style = water_style();
+            style.layers[0].paint = Some(LayerPaint::Fill(FillPaint {
+                fill_color: Some(Color::from_str("#804020").unwrap()),
+                fill_pattern: None,
+            }));
+
+            let plugins: Vec<Box<dyn Plugin<HeadlessEnvironment>>> = vec![
+                Box::new(RenderPlugin::default()),
+                Box::new(VectorPlugin::<DefaultVectorTransferables>::default()),
+                Box::new(HeadlessPlugin::new(false)),
+            ];
+            let mut map = HeadlessMap::new(style, renderer, kernel, plugins).unwrap();
+            map.insert_tile(
+                None,
+                WorldTileCoords::from((0, 0, ZoomLevel::default())),
+                &SourceType::Tessellate(TessellateSource::default()),
+                &water_tile(),
+            )
+            .unwrap();
+
+            // The stored pixel has the color of the style regardless of the color space
+            let image = map.render().unwrap();
+            let pixel = image.get_pixel(32, 32).0;
+            for (actual, expected) in pixel.iter().zip([128u8, 64, 32, 255]) {
+                assert!(actual.abs_diff(expected) <= 1, "{format:?}: {pixel:?}");
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_single_blend() {
+        // Two squares which overlap in the center of the tile
+        let square = |id: u64, min: u32| tile::Feature {
+            id: Some(id),
+            tags: vec![],
+            r#type: Some(tile::GeomType::Polygon as i32),
+            geometry: vec![9, min * 2, min * 2, 26, 6144, 0, 0, 6144, 6143, 0, 15],
+        };
+        let data = Tile {
+            layers: vec![tile::Layer {
+                version: 2,
+                name: "water".to_string(),
+                features: vec![square(1, 0), square(2, 1024)],
+                keys: vec![],
+                values: vec![],
+                extent: Some(4096),
+            }],
+        }
+        .encode_to_vec();
+
+        // Renders the squares and returns the pixels of the first square, of the overlap and of
+        // the second square
+        let render = |single_blend: Option<bool>| {
+            let data = data.clone();
+            async move {
+                let mut style = water_style();
+                style.layers[0].paint = Some(LayerPaint::Fill(FillPaint {
+                    fill_color: Some(Color::from_str("rgba(255, 0, 0, 0.5)").unwrap()),
+                    fill_pattern: None,
+                }));
+                style.layers[0].blend_mode = Some(BlendMode::Normal);
+                style.layers[0].single_blend = single_blend;
+
+                let (kernel, renderer) = create_headless_renderer(64, None).await;
+                let plugins: Vec<Box<dyn Plugin<HeadlessEnvironment>>> = vec![
+                    Box::new(RenderPlugin::default()),
+                    Box::new(VectorPlugin::<DefaultVectorTransferables>::default()),
+                    Box::new(HeadlessPlugin::new(false)),
+                ];
+                let mut map = HeadlessMap::new(style, renderer, kernel, plugins).unwrap();
+
+                map.insert_tile(
+                    None,
+                    WorldTileCoords::from((0, 0, ZoomLevel::default())),
+                    &SourceType::Tessellate(TessellateSource::default()),
+                    &data,
+                )
+                .unwrap();
+
+                let image = map.render().unwrap();
+                [(8, 8), (32, 32), (56, 56)].map(|(x, y)| image.get_pixel(x, y).0)
+            }
+        };
+
+        // Without the property, the semi-transparent fill is blended twice where the squares
+        // overlap
+        let [single, overlap, other] = render(None).await;
+        assert_ne!(overlap, single);
+        assert_eq!(other, single);
+
+        // The fill does not darken where the squares overlap
+        let [single, overlap, other] = render(Some(true)).await;
+        assert!(single[0] > 100 && single[0] < 250, "{single:?}");
+        assert_eq!(overlap, single);
+        assert_eq!(other, single);
+    }
+
+    #[tokio::test]
+    async fn test_bring_feature_to_front() {
+        // The red layer of the land source is covered by the blue layer of the water source
+        let layer = |id: &str, index: u32, color: &str| StyleLayer {
+            id: id.to_string(),
+            index,
+            paint: Some(LayerPaint::Fill(FillPaint {
+                fill_color: Some(Color::from_str(color).unwrap()),
+                fill_pattern: None,
+            })),
+            source: Some(id.to_string()),
+            source_layer: Some("water".to_string()),
+            ..StyleLayer::default()
+        };
+        let style = Style {
+            layers: vec![layer("land", 0, "#ff0000"), layer("water", 1, "#0000ff")],
+            ..Style::default()
+        };
+
+        let (kernel, renderer) = create_headless_renderer(64, None).await;
+        let plugins: Vec<Box<dyn Plugin<HeadlessEnvironment>>> = vec![
+            Box::new(RenderPlugin::default()),
+            Box::new(VectorPlugin::<DefaultVectorTransferables>::default()),
+            Box::new(HeadlessPlugin::new(false)),
+        ];
+        let mut map = HeadlessMap::new(style, renderer, kernel, plugins).unwrap();
+
+        // Both sources serve the water tile
+        let source_client = SourceClient::new(HttpSourceClient::new(water_http_client()));
+        async fn render_pixel(
+            map: &mut HeadlessMap,
+            source_client: &SourceClient<MockHttpClient>,
+        ) -> [u8; 4] {
+            map.render_view(
+                source_client,
+                LatLon::new(48.137154, 11.576124),
+                Zoom::new(10.0),
+            )
+            .await
+            .unwrap()
+            .get_pixel(32, 32)
+            .0
+        }
+
+        let [red, _, blue, _] = render_pixel(&mut map, &source_client).await;
+        assert!(red < 50 && blue > 200, "{red} {blue}");
+
+        // The feature of the land source is drawn in the final pass on top of the water layer,
+        // although its own layer is below
+        map.world_mut().bring_feature_to_front("land", 1);
+        let [red, _, blue, _] = render_pixel(&mut map, &source_client).await;
+        assert!(red > 200 && blue < 50, "{red} {blue}");
+
+        assert!(map.world_mut().restore_feature_order("land", 1));
+        let [red, _, blue, _] = render_pixel(&mut map, &source_client).await;
+        assert!(red < 50 && blue > 200, "{red} {blue}");
+    }
+
+    #[tokio::test]
+    async fn test_feature_data_shades_feature() {
+        let mut style = water_style();
+        style.layers[0].source = Some("regions".to_string());
+
+        let (kernel, renderer) = create_headless_renderer(64, None).await;
+        let plugins: Vec<Box<dyn Plugin<HeadlessEnvironment>>> = vec![
+            Box::new(RenderPlugin::default()),
+            Box::new(VectorPlugin::<DefaultVectorTransferables>::default()),
+            Box::new(HeadlessPlugin::new(false)),
+        ];
+        let mut map = HeadlessMap::new(style, renderer, kernel, plugins).unwrap();
+        let source_client = SourceClient::new(HttpSourceClient::new(water_http_client()));
+        let center = LatLon::new(48.137154, 11.576124);
+
+        // The value darkens the red water feature by half
+        map.world_mut().set_feature_data("regions", 1, 0.5);
+        let [red, green, blue, _] = map
+            .render_view(&source_client, center, Zoom::new(10.0))
+            .await
+            .unwrap()
+            .get_pixel(32, 32)
+            .0;
+        assert!(red > 50 && red < 200, "{red}");
+        assert!(green < 50 && blue < 50, "{green} {blue}");
+
+        // Features of other sources are not affected
+        map.world_mut().set_feature_data("regions", 1, 0.0);
+        map.world_mut().set_feature_data("other", 1, 1.0);
+        let [red, ..] = map
+            .render_view(&source_client, center, Zoom::new(10.0))
+            .await
+            .unwrap()
+            .get_pixel(32, 32)
+            .0;
+        assert!(red > 200, "{red}");
+    }
+}
@@ -44,7 +44,7 @@ impl System for WriteSurfaceBufferSystem {
     ) {
         let surface = state.surface();
         match surface.head() {
-            Head::Headed(_) => {}
+            Head::Headed(_) | Head::External(_) => {}
             Head::Headless(buffered_texture) => {
                 let buffered_texture: Arc<BufferedTextureHead> = buffered_texture.clone();
 
@@ -1,6 +1,7 @@
 use std::{
     any::Any,
     cell::RefCell,
+    collections::HashSet,
     fmt::Debug,
     future::Future,
     marker::PhantomData,
@@ -16,8 +17,12 @@ use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 use crate::{
-    coords::WorldTileCoords, define_label, environment::OffscreenKernelEnvironment,
-    io::scheduler::Scheduler, style::Style,
+    coords::WorldTileCoords,
+    define_label,
+    environment::OffscreenKernelEnvironment,
+    io::{request_settings::Deadline, scheduler::Scheduler, source_client::SourceClient},
+    raster::FrameRequest,
+    style::Style,
 };
 
 define_label!(MessageTag);
@@ -72,8 +77,31 @@ pub enum Input {
     TileRequest {
         coords: WorldTileCoords,
         style: Style, // TODO
+        /// The id of the requested source in the `style`, `None` for the default vector source
+        source: Option<String>,
+        /// The requested source-layers of a vector `source`. All of its source-layers which are
+        /// visible in the `style` are requested if `None`.
+        layers: Option<HashSet<String>>,
+        /// See [`RequestSettings::pixel_ratio`](crate::io::request_settings::RequestSettings::pixel_ratio)
+        pixel_ratio: f64,
+        /// Whether the geometries of the tile are indexed, see [`World::is_interactive`](crate::tcs::world::World::is_interactive)
+        index: bool,
+        /// See [`RequestSettings::request_deadline`](crate::io::request_settings::RequestSettings::request_deadline)
+        deadline: Option<Deadline>,
+        /// The frame of raster sources with a time dimension, see
+        /// [`RasterTimeline`](crate::raster::RasterTimeline)
+        frame: Option<FrameRequest>,
+    },
+    /// Requests several adjacent vector tiles which are fetched with one request, see
+    /// [`VectorSource::batch_size`](crate::style::source::VectorSource::batch_size)
+    TileBatchRequest {
+        coords: Vec<WorldTileCoords>,
+        style: Style,
+        /// See the `source` of [`Input::TileRequest`]
+        source: Option<String>,
+        index: bool,
+        deadline: Option<Deadline>,
     },
-    NotYetImplemented, // TODO: Placeholder, should be removed when second input is added
 }
 
 #[derive(Error, Debug)]
@@ -94,9 +122,9 @@ pub enum ProcedureError {
     #[error("provided input is not compatible with procedure")]
     IncompatibleInput,
     #[error("execution of procedure failed")]
-    Execution(Box<dyn std::error::Error>),
+    Execution(#[source] Box<dyn std::error::Error>),
     #[error("sending data failed")]
-    Send(SendError),
+    Send(#[source] SendError),
 }
 
 #[cfg(feature = "thread-safe-futures")]
@@ -111,11 +139,11 @@ pub enum CallError {
     #[error("scheduling work failed")]
     Schedule,
     #[error("serializing data failed")]
-    Serialize(Box<dyn std::error::Error>),
+    Serialize(#[source] Box<dyn std::error::Error>),
     #[error("deserializing failed")]
-    Deserialize(Box<dyn std::error::Error>),
+    Deserialize(#[source] Box<dyn std::error::Error>),
     #[error("deserializing input failed")]
-    DeserializeInput(Box<dyn std::error::Error>),
+    DeserializeInput(#[source] Box<dyn std::error::Error>),
 }
 
 /// Type definitions for asynchronous procedure calls. These functions can be called in an
@@ -179,33 +207,113 @@ pub trait AsyncProcedureCall<K: OffscreenKernelEnvironment>: 'static {
         input: Input,
         procedure: AsyncProcedure<K, Self::Context>,
     ) -> Result<(), CallError>;
+
+    /// Passes the `source_client` of the [`Kernel`](crate::kernel::Kernel) to the procedures, see
+    /// [`OffscreenKernelEnvironment::create`]. Returns whether the procedures use the client.
+    /// Implementations which can not share the client with their procedures, e.g. because the
+    /// procedures run in web workers, ignore it.
+    fn set_source_client(&mut self, _source_client: SourceClient<K::HttpClient>) -> bool {
+        false
+    }
+
+    /// Applies backpressure to the procedures. If `bound` messages are waiting to be received,
+    /// procedures wait until the caller catches up before passing on further messages, and no
+    /// further procedures are started. This keeps the memory bounded if the procedures produce
+    /// results faster than the caller consumes them. Implementations which can not bound their
+    /// messages ignore it.
+    fn set_message_bound(&mut self, _bound: usize) {}
+
+    /// Cancels the procedures which are still running, see [`Scheduler::shutdown`].
+    fn shutdown(&self) {}
+}
+
+#[derive(Clone)]
+enum MessageSender {
+    Unbounded(Sender<Message>),
+    /// Forwards the messages of a single procedure to the bounded channel of the caller, see
+    /// [`AsyncProcedureCall::set_message_bound`]
+    #[cfg(not(target_arch = "wasm32"))]
+    Forwarding(tokio::sync::mpsc::UnboundedSender<Message>),
 }
 
 #[derive(Clone)]
 pub struct SchedulerContext {
-    sender: Sender<Message>,
+    sender: MessageSender,
 }
 
 impl Context for SchedulerContext {
     fn send<T: IntoMessage>(&self, message: T) -> Result<(), SendError> {
-        self.sender
-            .send(message.into())
-            .map_err(|_e| SendError::Transmission)
+        match &self.sender {
+            MessageSender::Unbounded(sender) => sender
+                .send(message.into())
+                .map_err(|_e| SendError::Transmission),
+            #[cfg(not(target_arch = "wasm32"))]
+            MessageSender::Forwarding(sender) => sender
+                .send(message.into())
+                .map_err(|_e| SendError::Transmission),
+        }
+    }
+}
+
+/// Keeps the sent messages, such that they can be received on the same thread, e.g. for tiles
+/// which are processed without an [`AsyncProcedureCall`].
+#[derive(Default)]
+pub struct BufferedContext {
+    messages: RefCell<Vec<Message>>,
+}
+
+impl BufferedContext {
+    pub fn into_messages(self) -> Vec<Message> {
+        self.messages.into_inner()
+    }
+}
+
+impl Context for BufferedContext {
+    fn send<T: IntoMessage>(&self, message: T) -> Result<(), SendError> {
+        self.messages.borrow_mut().push(message.into());
+        Ok(())
+    }
+}
+
+enum MessageChannel {
+    Unbounded(Sender<Message>, Receiver<Message>),
+    /// Procedures wait asynchronously while the channel is full
+    #[cfg(not(target_arch = "wasm32"))]
+    Bounded(
+        tokio::sync::mpsc::Sender<Message>,
+        RefCell<tokio::sync::mpsc::Receiver<Message>>,
+    ),
+}
+
+impl MessageChannel {
+    fn try_recv(&self) -> Option<Message> {
+        match self {
+            MessageChannel::Unbounded(_, receiver) => receiver.try_recv().ok(),
+            #[cfg(not(target_arch = "wasm32"))]
+            MessageChannel::Bounded(_, receiver) => receiver.borrow_mut().try_recv().ok(),
+        }
     }
 }
 
 pub struct SchedulerAsyncProcedureCall<K: OffscreenKernelEnvironment, S: Scheduler> {
-    channel: (Sender<Message>, Receiver<Message>),
+    channel: MessageChannel,
+    /// The maximum amount of messages which are buffered by the channel and by the caller
+    bound: Option<usize>,
     buffer: RefCell<Vec<Message>>,
     scheduler: S,
+    /// The client which is shared with the procedures, see [`AsyncProcedureCall::set_source_client`]
+    source_client: Option<SourceClient<K::HttpClient>>,
     phantom_k: PhantomData<K>,
 }
 
 impl<K: OffscreenKernelEnvironment, S: Scheduler> SchedulerAsyncProcedureCall<K, S> {
     pub fn new(scheduler: S) -> Self {
+        let (sender, receiver) = mpsc::channel();
         Self {
-            channel: mpsc::channel(),
+            channel: MessageChannel::Unbounded(sender, receiver),
+            bound: None,
             buffer: RefCell::new(Vec::new()),
+            source_client: None,
             phantom_k: PhantomData::default(),
             scheduler,
         }
@@ -235,7 +343,11 @@ impl<K: OffscreenKernelEnvironment, S: Scheduler> AsyncProcedureCall<K>
 
         // TODO: (optimize) Using while instead of if means that we are processing all that is
         // TODO: available this might cause frame drops.
-        while let Ok(message) = self.channel.1.try_recv() {
+        // Messages which are not received stay in the channel if the buffer is full, such that
+        // the procedures wait.
+        while self.bound.map_or(true, |bound| buffer.len() < bound) {
+            let Some(message) = self.channel.try_recv() else { break; };
+
             tracing::debug!("Data reached main thread: {message:?}");
             log::debug!("Data reached main thread: {message:?}");
 
@@ -254,23 +366,96 @@ impl<K: OffscreenKernelEnvironment, S: Scheduler> AsyncProcedureCall<K>
         input: Input,
         procedure: AsyncProcedure<K, Self::Context>,
     ) -> Result<(), CallError> {
-        let sender = self.channel.0.clone();
+        let source_client = self.source_client.clone();
+
+        match &self.channel {
+            MessageChannel::Unbounded(sender, _) => {
+                let context = SchedulerContext {
+                    sender: MessageSender::Unbounded(sender.clone()),
+                };
+
+                self.scheduler.schedule(move || async move {
+                    log::info!("Processing on thread: {:?}", std::thread::current().name());
+
+                    procedure(input, context, K::create(source_client))
+                        .await
+                        .unwrap();
+                })
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            MessageChannel::Bounded(sender, _) => {
+                let sender = sender.clone();
+
+                self.scheduler.schedule(move || async move {
+                    // No further work is started while the caller is behind
+                    if sender.reserve().await.is_err() {
+                        return;
+                    }
+
+                    log::info!("Processing on thread: {:?}", std::thread::current().name());
+
+                    // The procedure sends its messages without blocking. They are passed on to
+                    // the caller once the channel has room.
+                    let (forward_sender, mut forward_receiver) =
+                        tokio::sync::mpsc::unbounded_channel();
+                    let context = SchedulerContext {
+                        sender: MessageSender::Forwarding(forward_sender),
+                    };
+                    let forward = async move {
+                        while let Some(message) = forward_receiver.recv().await {
+                            if sender.send(message).await.is_err() {
+                                break;
+                            }
+                        }
+                    };
+
+                    let (result, ()) =
+                        tokio::join!(procedure(input, context, K::create(source_client)), forward);
+                    result.unwrap();
+                })
+            }
+        }
+        .map_err(|_e| CallError::Schedule)
+    }
+
+    fn shutdown(&self) {
+        self.scheduler.shutdown();
+    }
 
-        self.scheduler
-            .schedule(move || async move {
-                log::info!("Processing on thread: {:?}", std::thread::current().name());
+    #[cfg(not(target_arch = "wasm32"))]
+    fn set_message_bound(&mut self, bound: usize) {
+        // A channel without capacity can not pass on any message
+        let bound = bound.max(1);
+        let (sender, receiver) = tokio::sync::mpsc::channel(bound);
+        self.channel = MessageChannel::Bounded(sender, RefCell::new(receiver));
+        self.bound = Some(bound);
+    }
 
-                procedure(input, SchedulerContext { sender }, K::create())
-                    .await
-                    .unwrap();
-            })
-            .map_err(|_e| CallError::Schedule)
+    fn set_source_client(&mut self, source_client: SourceClient<K::HttpClient>) -> bool {
+        self.source_client = Some(source_client);
+        true
     }
 }
 
 #[cfg(test)]
 pub mod tests {
-    use crate::io::apc::{Context, IntoMessage, SendError};
+    use std::{
+        future::Future,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    use crate::{
+        io::{
+            apc::{
+                AsyncProcedureCall, AsyncProcedureFuture, Context, Input, IntoMessage, Message,
+                MessageChannel, ProcedureError, SchedulerAsyncProcedureCall, SchedulerContext,
+                SendError,
+            },
+            scheduler::{ScheduleError, Scheduler},
+        },
+        platform::ReqwestOffscreenKernelEnvironment,
+        style::Style,
+    };
 
     pub struct DummyContext;
 
@@ -279,4 +464,110 @@ pub mod tests {
             Ok(())
         }
     }
+
+    struct TestMessage(usize);
+
+    impl IntoMessage for TestMessage {
+        fn into(self) -> Message {
+            Message::new(&0u32, Box::new(self.0))
+        }
+    }
+
+    /// Runs the scheduled work on the [`LocalSet`](tokio::task::LocalSet) of the test
+    struct LocalScheduler;
+
+    impl Scheduler for LocalScheduler {
+        fn schedule<T>(
+            &self,
+            future_factory: impl FnOnce() -> T + Send + 'static,
+        ) -> Result<(), ScheduleError>
+        where
+            T: Future<Output = ()> + 'static,
+        {
+            tokio::task::spawn_local(future_factory());
+            Ok(())
+        }
+    }
+
+    type TestApc = SchedulerAsyncProcedureCall<ReqwestOffscreenKernelEnvironment, LocalScheduler>;
+
+    static STARTED: AtomicUsize = AtomicUsize::new(0);
+
+    /// A fast producer which sends 10 messages at once
+    fn produce(
+        _input: Input,
+        context: SchedulerContext,
+        _kernel: ReqwestOffscreenKernelEnvironment,
+    ) -> AsyncProcedureFuture {
+        Box::pin(async move {
+            let first = STARTED.fetch_add(1, Ordering::SeqCst) * 10;
+            for i in first..first + 10 {
+                context.send(TestMessage(i)).map_err(ProcedureError::Send)?;
+            }
+            Ok(())
+        })
+    }
+
+    /// The count of messages which wait in the channel of the `apc`
+    fn queued(apc: &TestApc) -> usize {
+        let MessageChannel::Bounded(sender, _) = &apc.channel else { unreachable!(); };
+        sender.max_capacity() - sender.capacity()
+    }
+
+    /// Lets the scheduled work run until it waits for the caller. Nothing else runs on the
+    /// current thread runtime of the test.
+    async fn settle() {
+        for _ in 0..100 {
+            tokio::task::yield_now().await;
+        }
+    }
+
+    #[test]
+    fn test_backpressure() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+
+        tokio::task::LocalSet::new().block_on(&runtime, async {
+            let mut apc = TestApc::new(LocalScheduler);
+            apc.set_message_bound(2);
+
+            for _ in 0..2 {
+                let input = Input::TileBatchRequest {
+                    coords: vec![],
+                    style: Style::default(),
+                    source: None,
+                    index: false,
+                    deadline: None,
+                };
+                apc.call(input, produce).unwrap();
+            }
+
+            // The messages of the first procedure wait for the caller, and the second procedure
+            // is not started
+            settle().await;
+            assert_eq!(queued(&apc), 2);
+            assert_eq!(STARTED.load(Ordering::SeqCst), 1);
+
+            // Messages which are not received are buffered up to the bound
+            assert_eq!(apc.receive(|_| false).count(), 0);
+            settle().await;
+            assert_eq!(apc.buffer.borrow().len(), 2);
+            assert_eq!(queued(&apc), 2);
+
+            // A slow consumer
+            let mut received = Vec::new();
+            while received.len() < 20 {
+                assert!(queued(&apc) <= 2);
+                received.extend(
+                    apc.receive(|_| true)
+                        .map(|message| *message.into_transferable::<usize>()),
+                );
+                settle().await;
+            }
+
+            received.sort();
+            assert_eq!(received, (0..20).collect::<Vec<_>>());
+        });
+    }
 }
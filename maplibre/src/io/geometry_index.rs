@@ -20,8 +20,12 @@ use crate::{
 };
 
 /// A quad tree storing the currently loaded tiles.
+///
+/// The index of a tile is built incrementally, layer by layer, such that the features of a layer
+/// can be queried as soon as the layer is indexed, even if other layers of the tile are still
+/// being processed.
 pub struct GeometryIndex {
-    index: BTreeMap<Quadkey, TileIndex>,
+    index: BTreeMap<Quadkey, Vec<(String, TileIndex)>>,
 }
 
 impl GeometryIndex {
@@ -31,10 +35,61 @@ impl GeometryIndex {
         }
     }
 
-    pub fn index_tile(&mut self, coords: &WorldTileCoords, tile_index: TileIndex) {
+    /// Adds the index of the layer `layer_name` to the tile at `coords`. A previous index of the
+    /// same layer is replaced, e.g. if the tile is reloaded.
+    pub fn index_layer(
+        &mut self,
+        coords: &WorldTileCoords,
+        layer_name: String,
+        layer_index: TileIndex,
+    ) {
+        let Some(key) = coords.build_quad_key() else { return; };
+        let layers = self.index.entry(key).or_default();
+
+        if let Some((_, index)) = layers.iter_mut().find(|(name, _)| *name == layer_name) {
+            *index = layer_index;
+        } else {
+            layers.push((layer_name, layer_index));
+        }
+    }
+
+    /// The indexed features of the loaded tile at `coords` along with the name of the layer which
+    /// contains them. See [`GeometryIndex::features`].
+    pub fn layer_features(
+        &self,
+        coords: &WorldTileCoords,
+    ) -> impl Iterator<Item = (&str, &IndexedGeometry<f64>)> + '_ {
+        coords
+            .build_quad_key()
+            .and_then(|key| self.index.get(&key))
+            .into_iter()
+            .flatten()
+            .flat_map(|(layer_name, index)| {
+                index
+                    .geometries()
+                    .map(move |geometry| (layer_name.as_str(), geometry))
+            })
+    }
+
+    /// The indexed features of the loaded tile at `coords`. The geometries are in coordinates
+    /// local to the tile, which range from 0 to [`EXTENT`]. Only polygons and line strings are
+    /// indexed.
+    pub fn features(
+        &self,
+        coords: &WorldTileCoords,
+    ) -> impl Iterator<Item = &IndexedGeometry<f64>> + '_ {
         coords
             .build_quad_key()
-            .and_then(|key| self.index.insert(key, tile_index));
+            .and_then(|key| self.index.get(&key))
+            .into_iter()
+            .flatten()
+            .flat_map(|(_, index)| index.geometries())
+    }
+
+    pub fn remove_tile(&mut self, coords: &WorldTileCoords) {
+        coords
+            .build_quad_key()
+            .and_then(|key| self.index.remove(&key));
     }
 
     pub fn query_point(
@@ -45,7 +100,7 @@ impl GeometryIndex {
     ) -> Option<Vec<&IndexedGeometry<f64>>> {
         let world_tile_coords = world_coords.into_world_tile(z, zoom);
 
-        if let Some(index) = world_tile_coords
+        if let Some(layers) = world_tile_coords
             .build_quad_key()
             .and_then(|key| self.index.get(&key))
         {
@@ -56,7 +111,12 @@ impl GeometryIndex {
 
             let x = delta_x * EXTENT;
             let y = delta_y * EXTENT;
-            Some(index.point_query(InnerCoords { x, y }))
+            Some(
+                layers
+                    .iter()
+                    .flat_map(|(_, index)| index.point_query(InnerCoords { x, y }))
+                    .collect(),
+            )
         } else {
             None
         }
@@ -80,6 +140,13 @@ pub enum TileIndex {
 }
 
 impl TileIndex {
+    pub fn geometries(&self) -> Box<dyn Iterator<Item = &IndexedGeometry<f64>> + '_> {
+        match self {
+            TileIndex::Spatial { tree } => Box::new(tree.iter()),
+            TileIndex::Linear { list } => Box::new(list.iter()),
+        }
+    }
+
     pub fn point_query(&self, inner_coords: InnerCoords) -> Vec<&IndexedGeometry<f64>> {
         let point = Point::new(inner_coords.x, inner_coords.y);
         let coordinate: Coord<_> = point.into();
@@ -114,6 +181,8 @@ where
     pub bounds: AABB<Point<T>>,
     pub exact: ExactGeometry<T>,
     pub properties: HashMap<String, String>,
+    /// The id of the feature within the vector tile. Features without an id have the id `0`.
+    pub feature_id: u64,
 }
 
 /// Contains either a polygon or line vector.
@@ -130,18 +199,24 @@ impl<T> IndexedGeometry<T>
 where
     T: CoordFloat + Bounded + Signed + PartialOrd,
 {
-    fn from_polygon(polygon: Polygon<T>, properties: HashMap<String, String>) -> Option<Self> {
+    fn from_polygon(
+        polygon: Polygon<T>,
+        properties: HashMap<String, String>,
+        feature_id: u64,
+    ) -> Option<Self> {
         let (min, max) = bounds_from_points(polygon.exterior().points())?;
 
         Some(Self {
             exact: ExactGeometry::Polygon(polygon),
             bounds: AABB::from_corners(Point::from(min), Point::from(max)),
             properties,
+            feature_id,
         })
     }
     fn from_linestring(
         linestring: LineString<T>,
         properties: HashMap<String, String>,
+        feature_id: u64,
     ) -> Option<Self> {
         let bounds = linestring.envelope();
 
@@ -149,10 +224,38 @@ where
             exact: ExactGeometry::LineString(linestring),
             bounds,
             properties,
+            feature_id,
         })
     }
 }
 
+impl IndexedGeometry<f64> {
+    /// Whether the exact geometry intersects the `polygon`, which is in the same coordinates. The
+    /// polygon may be concave. Geometries whose bounds do not overlap the bounds of the polygon
+    /// are rejected before the exact test.
+    pub fn intersects_polygon(&self, polygon: &Polygon<f64>) -> bool {
+        let Some(rect) = polygon.bounding_rect() else { return false; };
+        let polygon_bounds = AABB::from_corners(Point::from(rect.min()), Point::from(rect.max()));
+        if !self.bounds.intersects(&polygon_bounds) {
+            return false;
+        }
+
+        match &self.exact {
+            ExactGeometry::Polygon(exact) => exact.intersects(polygon),
+            ExactGeometry::LineString(exact) => exact.intersects(polygon),
+        }
+    }
+
+    /// The distance between the `point` and the exact geometry, which are in the same
+    /// coordinates. The distance is `0` if the point is inside of a polygon.
+    pub fn distance_to_point(&self, point: &Point<f64>) -> f64 {
+        match &self.exact {
+            ExactGeometry::Polygon(exact) => point.euclidean_distance(exact),
+            ExactGeometry::LineString(exact) => point.euclidean_distance(exact),
+        }
+    }
+}
+
 impl<T> RTreeObject for IndexedGeometry<T>
 where
     T: CoordFloat + Bounded + Signed + PartialOrd,
@@ -185,6 +288,10 @@ pub struct IndexProcessor {
     geo_writer: GeoWriter,
     geometries: Vec<IndexedGeometry<f64>>,
     properties: Option<HashMap<String, String>>,
+    /// The ids of the features of the processed layer in order
+    feature_ids: Vec<u64>,
+    /// The position of the current feature within the processed layer
+    feature_index: usize,
 }
 
 impl IndexProcessor {
@@ -193,9 +300,18 @@ impl IndexProcessor {
             geo_writer: GeoWriter::new(),
             geometries: Vec::new(),
             properties: None,
+            feature_ids: Vec::new(),
+            feature_index: 0,
         }
     }
 
+    /// Assigns the `feature_ids` to the indexed geometries, in the order of the features of the
+    /// processed layer.
+    pub fn with_feature_ids(mut self, feature_ids: Vec<u64>) -> Self {
+        self.feature_ids = feature_ids;
+        self
+    }
+
     pub fn build_tree(self) -> RTree<IndexedGeometry<f64>> {
         RTree::bulk_load(self.geometries)
     }
@@ -280,7 +396,8 @@ impl FeatureProcessor for IndexProcessor {
         Ok(())
     }
     /// Begin of feature processing.
-    fn feature_begin(&mut self, _idx: u64) -> Result<(), GeozeroError> {
+    fn feature_begin(&mut self, idx: u64) -> Result<(), GeozeroError> {
+        self.feature_index = idx as usize;
         Ok(())
     }
     /// End of feature processing.
@@ -303,14 +420,24 @@ impl FeatureProcessor for IndexProcessor {
     /// End of feature geometry processing.
     fn geometry_end(&mut self) -> Result<(), GeozeroError> {
         let geometry = self.geo_writer.take_geometry();
+        let feature_id = self
+            .feature_ids
+            .get(self.feature_index)
+            .copied()
+            .unwrap_or_default();
 
         match geometry {
             Some(Geometry::Polygon(polygon)) => self.geometries.push(
-                IndexedGeometry::from_polygon(polygon, self.properties.take().unwrap()).unwrap(),
+                IndexedGeometry::from_polygon(polygon, self.properties.take().unwrap(), feature_id)
+                    .unwrap(),
             ),
             Some(Geometry::LineString(linestring)) => self.geometries.push(
-                IndexedGeometry::from_linestring(linestring, self.properties.take().unwrap())
-                    .unwrap(),
+                IndexedGeometry::from_linestring(
+                    linestring,
+                    self.properties.take().unwrap(),
+                    feature_id,
+                )
+                .unwrap(),
             ),
             Some(Geometry::Point(_)) => debug!("Unsupported Point geometry in index"),
             Some(Geometry::Line(_)) => debug!("Unsupported Line geometry in index"),
@@ -330,3 +457,71 @@ impl FeatureProcessor for IndexProcessor {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use geozero::{
+        mvt::{tile, tile::Value},
+        GeozeroDatasource,
+    };
+
+    use super::{ExactGeometry, GeometryIndex, IndexProcessor, TileIndex};
+    use crate::coords::{WorldTileCoords, ZoomLevel};
+
+    #[test]
+    fn test_features() {
+        let mut layer = tile::Layer {
+            version: 2,
+            name: "park".to_string(),
+            features: vec![tile::Feature {
+                id: Some(1),
+                tags: vec![0, 0],
+                r#type: Some(tile::GeomType::Polygon as i32),
+                geometry: vec![9, 0, 0, 26, 20, 0, 0, 20, 19, 0, 15],
+            }],
+            keys: vec!["name".to_string()],
+            values: vec![Value {
+                string_value: Some("Englischer Garten".to_string()),
+                ..Value::default()
+            }],
+            extent: Some(4096),
+        };
+
+        let mut processor = IndexProcessor::new().with_feature_ids(vec![1]);
+        layer.process(&mut processor).unwrap();
+
+        let coords = WorldTileCoords::from((1, 2, ZoomLevel::new(3)));
+        let mut index = GeometryIndex::new();
+        index.index_layer(
+            &coords,
+            "park".to_string(),
+            TileIndex::Linear {
+                list: processor.get_geometries(),
+            },
+        );
+
+        let features = index.features(&coords).collect::<Vec<_>>();
+        assert_eq!(features.len(), 1);
+        assert_eq!(features[0].properties["name"], "Englischer Garten");
+        assert_eq!(features[0].feature_id, 1);
+
+        let ExactGeometry::Polygon(polygon) = &features[0].exact else { panic!() };
+        assert_eq!(
+            polygon
+                .exterior()
+                .points()
+                .map(|point| point.x_y())
+                .collect::<Vec<_>>(),
+            vec![
+                (0.0, 0.0),
+                (10.0, 0.0),
+                (10.0, 10.0),
+                (0.0, 10.0),
+                (0.0, 0.0)
+            ]
+        );
+
+        let other = WorldTileCoords::from((2, 2, ZoomLevel::new(3)));
+        assert_eq!(index.features(&other).count(), 0);
+    }
+}
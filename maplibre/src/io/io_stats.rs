@@ -0,0 +1,211 @@
+//! Statistics about the requested tiles, e.g. for telemetry or to find out why a map loads slowly.
+
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+use instant::Instant;
+
+/// The tiles which have been requested through a
+/// [`SourceClient`](crate::io::source_client::SourceClient) since it has been created.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct IoStats {
+    /// The tiles which have been requested, including failed requests
+    pub requested: u64,
+    /// The tiles which have been served from a cache, as reported by the
+    /// [`HttpClient`](crate::io::source_client::HttpClient)
+    pub cache_hits: u64,
+    /// The tiles which have not been found in a cache
+    pub cache_misses: u64,
+    /// The size of all fetched tiles before they are transformed
+    pub bytes: u64,
+    /// The requests which failed in the HTTP client
+    pub fetch_errors: u64,
+    /// The tiles which exceeded the maximum tile size
+    pub too_large_errors: u64,
+    /// The tiles which could not be transformed
+    pub transform_errors: u64,
+}
+
+/// Called with the current statistics, see [`IoCounters::with_callback`].
+pub type IoStatsCallback = Box<dyn Fn(IoStats) + Send + Sync>;
+
+/// The atomic counters behind [`IoStats`], which are shared by all clones of a source client.
+#[derive(Default)]
+pub struct IoCounters {
+    requested: AtomicU64,
+    cache_hits: AtomicU64,
+    cache_misses: AtomicU64,
+    bytes: AtomicU64,
+    fetch_errors: AtomicU64,
+    too_large_errors: AtomicU64,
+    transform_errors: AtomicU64,
+    callback: Option<PeriodicCallback>,
+}
+
+struct PeriodicCallback {
+    interval: Duration,
+    last_call: Mutex<Option<Instant>>,
+    callback: IoStatsCallback,
+}
+
+impl IoCounters {
+    /// Calls the `callback` with the current statistics after a request has finished, at most
+    /// once per `interval`.
+    pub fn with_callback(interval: Duration, callback: IoStatsCallback) -> Self {
+        Self {
+            callback: Some(PeriodicCallback {
+                interval,
+                last_call: Mutex::new(None),
+                callback,
+            }),
+            ..Self::default()
+        }
+    }
+
+    pub fn stats(&self) -> IoStats {
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+
+        IoStats {
+            requested: load(&self.requested),
+            cache_hits: load(&self.cache_hits),
+            cache_misses: load(&self.cache_misses),
+            bytes: load(&self.bytes),
+            fetch_errors: load(&self.fetch_errors),
+            too_large_errors: load(&self.too_large_errors),
+            transform_errors: load(&self.transform_errors),
+        }
+    }
+
+    pub(crate) fn record_request(&self) {
+        self.requested.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records a fetched tile of `bytes` and whether it has been served from a cache, if known.
+    pub(crate) fn record_fetched(&self, bytes: usize, cache_hit: Option<bool>) {
+        self.bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+        match cache_hit {
+            Some(true) => self.cache_hits.fetch_add(1, Ordering::Relaxed),
+            Some(false) => self.cache_misses.fetch_add(1, Ordering::Relaxed),
+            None => 0,
+        };
+    }
+
+    pub(crate) fn record_fetch_error(&self) {
+        self.fetch_errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_too_large(&self) {
+        self.too_large_errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_transform_error(&self) {
+        self.transform_errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Calls the callback if its interval has passed since the last call.
+    pub(crate) fn notify(&self) {
+        let Some(periodic) = &self.callback else { return; };
+
+        let now = Instant::now();
+        {
+            let mut last_call = periodic
+                .last_call
+                .lock()
+                .expect("last call of the IO stats callback is poisoned");
+            if matches!(*last_call, Some(last_call) if now - last_call < periodic.interval) {
+                return;
+            }
+            *last_call = Some(now);
+        }
+
+        (periodic.callback)(self.stats());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use async_trait::async_trait;
+
+    use super::IoStats;
+    use crate::{
+        coords::{WorldTileCoords, ZoomLevel},
+        io::{
+            source_client::{HttpClient, HttpSourceClient, SourceClient, SourceFetchError},
+            source_type::{SourceType, TessellateSource},
+        },
+    };
+
+    /// Serves tiles of the column `x` with a size of `x * 100` bytes. Even columns are cached and
+    /// the column 0 fails.
+    #[derive(Clone)]
+    struct ColumnHttpClient;
+
+    #[cfg_attr(not(feature = "thread-safe-futures"), async_trait(?Send))]
+    #[cfg_attr(feature = "thread-safe-futures", async_trait)]
+    impl HttpClient for ColumnHttpClient {
+        async fn fetch(&self, _url: &str) -> Result<Vec<u8>, SourceFetchError> {
+            unreachable!()
+        }
+
+        async fn fetch_cached_tile(
+            &self,
+            url: &str,
+            _key: &str,
+            _max_size: usize,
+        ) -> Result<(Vec<u8>, Option<bool>), SourceFetchError> {
+            let x: usize = url.split('/').nth(2).unwrap().parse().unwrap();
+            if x == 0 {
+                return Err(SourceFetchError("unavailable".into()));
+            }
+            Ok((vec![0; x * 100], Some(x % 2 == 0)))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_io_stats() {
+        let reported = Arc::new(Mutex::new(Vec::new()));
+        let callback_reported = reported.clone();
+        let client = SourceClient::new(
+            HttpSourceClient::new(ColumnHttpClient)
+                .with_max_tile_size(350)
+                .with_io_stats_callback(
+                    Duration::ZERO,
+                    Box::new(move |stats| callback_reported.lock().unwrap().push(stats)),
+                ),
+        );
+        let source = SourceType::Tessellate(TessellateSource::new("", "pbf"));
+
+        // Clones share the statistics
+        let clone = client.clone();
+        for x in 0..5 {
+            let coords = WorldTileCoords::from((x, 0, ZoomLevel::from(3)));
+            let _ = clone.fetch(&coords, &source).await;
+        }
+
+        let expected = IoStats {
+            requested: 5,
+            cache_hits: 2,
+            cache_misses: 2,
+            bytes: 100 + 200 + 300 + 400,
+            fetch_errors: 1,
+            too_large_errors: 1,
+            transform_errors: 0,
+        };
+        assert_eq!(client.io_stats(), expected);
+
+        // The callback is called after every request because the interval is zero
+        let reported = reported.lock().unwrap();
+        assert_eq!(reported.len(), 5);
+        assert_eq!(reported.last(), Some(&expected));
+    }
+}
@@ -4,8 +4,18 @@ pub use geozero::mvt::tile::Layer as RawLayer;
 
 pub mod apc;
 pub mod geometry_index;
+pub mod io_stats;
+pub mod preload;
+pub mod redirect;
+pub mod request_log;
+pub mod request_observer;
+pub mod request_settings;
 pub mod scheduler;
 pub mod source_client;
 pub mod source_type;
 #[cfg(feature = "embed-static-tiles")]
 pub mod static_tile_fetcher;
+pub mod tile_format;
+pub mod tile_generator;
+pub mod tile_key;
+pub mod tile_transform;
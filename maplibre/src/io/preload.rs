@@ -0,0 +1,296 @@
+//! Requests the tiles of a region independently of the current view, e.g. to make the region
+//! available offline through the cache of the HTTP client.
+
+use std::ops::RangeInclusive;
+
+use thiserror::Error;
+
+use crate::{
+    coords::{LatLon, WorldTileCoords, ZoomLevel, MAX_ZOOM, ZOOM_BOUNDS},
+    projection::{Projection, WebMercator},
+    tcs::{tiles::Tiles, world::World},
+};
+
+/// The maximum amount of tiles which can be preloaded at once.
+pub const MAX_PRELOAD_TILES: usize = 10_000;
+
+/// The maximum amount of preload requests which are issued per frame. Tiles in view are requested
+/// first, such that preloading does not delay them.
+pub const PRELOAD_REQUESTS_PER_FRAME: usize = 4;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum PreloadError {
+    /// The region contains more tiles than [`MAX_PRELOAD_TILES`]
+    #[error("region contains {count} tiles, but at most {max} tiles can be preloaded")]
+    TooManyTiles { count: usize, max: usize },
+    /// The zoom range is empty or exceeds the maximum zoom
+    #[error("invalid zoom range")]
+    InvalidZoomRange,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PreloadProgress {
+    pub completed: usize,
+    pub total: usize,
+}
+
+impl PreloadProgress {
+    pub fn is_finished(&self) -> bool {
+        self.completed >= self.total
+    }
+}
+
+struct PreloadRegion {
+    /// Tiles which have not been requested yet, in reverse order
+    pending: Vec<WorldTileCoords>,
+    /// Tiles which have been requested, but are not finished yet
+    in_flight: Vec<WorldTileCoords>,
+    progress: PreloadProgress,
+    on_progress: Box<dyn FnMut(PreloadProgress)>,
+}
+
+/// The regions which are currently preloaded.
+#[derive(Default)]
+pub struct PreloadRegions {
+    regions: Vec<PreloadRegion>,
+}
+
+impl PreloadRegions {
+    /// Takes up to `max` tiles which should be requested next.
+    pub fn next_requests(&mut self, max: usize) -> Vec<WorldTileCoords> {
+        let mut requests = Vec::new();
+
+        for region in &mut self.regions {
+            while requests.len() < max {
+                let Some(coords) = region.pending.pop() else { break; };
+                region.in_flight.push(coords);
+                requests.push(coords);
+            }
+        }
+
+        requests
+    }
+
+    /// Marks the requested tiles for which `is_finished` returns true as completed and reports the
+    /// progress. Finished regions are removed. Returns the tiles which have been completed.
+    pub fn update(
+        &mut self,
+        is_finished: impl Fn(WorldTileCoords) -> bool,
+    ) -> Vec<WorldTileCoords> {
+        let mut finished = Vec::new();
+
+        for region in &mut self.regions {
+            let in_flight = region.in_flight.len();
+            region.in_flight.retain(|coords| {
+                let done = is_finished(*coords);
+                if done {
+                    finished.push(*coords);
+                }
+                !done
+            });
+
+            let completed = in_flight - region.in_flight.len();
+            if completed > 0 {
+                region.progress.completed += completed;
+                (region.on_progress)(region.progress);
+            }
+        }
+
+        self.regions.retain(|region| !region.progress.is_finished());
+        finished
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.regions.is_empty()
+    }
+}
+
+/// Evicts the `finished` tiles of preloaded regions which have not been used in the meantime, e.g.
+/// because they came into view. Their data has been loaded into the cache of the HTTP client, so
+/// they do not need to occupy memory until they are in view. Pinned tiles are kept.
+pub(crate) fn evict_finished(tiles: &mut Tiles, finished: &[WorldTileCoords]) {
+    for coords in finished {
+        if !tiles.is_used(coords) && !tiles.is_pinned(coords) {
+            tiles.evict(coords);
+        }
+    }
+}
+
+/// Enumerates the tiles which cover the `bounds` at each zoom level within the `zoom_range`. The
+/// bounds are given by two opposite corners.
+pub fn region_tiles(
+    bounds: (LatLon, LatLon),
+    zoom_range: RangeInclusive<u8>,
+) -> Result<Vec<WorldTileCoords>, PreloadError> {
+    if zoom_range.is_empty() || *zoom_range.end() as usize >= MAX_ZOOM {
+        return Err(PreloadError::InvalidZoomRange);
+    }
+
+    let (x1, y1) = WebMercator.project_unit(bounds.0);
+    let (x2, y2) = WebMercator.project_unit(bounds.1);
+    let (min_x, max_x) = (x1.min(x2), x1.max(x2));
+    let (min_y, max_y) = (y1.min(y2), y1.max(y2));
+
+    let tile_range = |z: u8| {
+        let tiles = ZOOM_BOUNDS[z as usize] as f64;
+        let to_tile = |unit: f64| (unit * tiles).floor().clamp(0.0, tiles - 1.0) as i32;
+        (
+            to_tile(min_x)..=to_tile(max_x),
+            to_tile(min_y)..=to_tile(max_y),
+        )
+    };
+
+    let count = zoom_range
+        .clone()
+        .map(|z| {
+            let (xs, ys) = tile_range(z);
+            xs.count() * ys.count()
+        })
+        .sum::<usize>();
+
+    if count > MAX_PRELOAD_TILES {
+        return Err(PreloadError::TooManyTiles {
+            count,
+            max: MAX_PRELOAD_TILES,
+        });
+    }
+
+    let mut tiles = Vec::with_capacity(count);
+    for z in zoom_range {
+        let (xs, ys) = tile_range(z);
+        for x in xs {
+            for y in ys.clone() {
+                tiles.push(WorldTileCoords::from((x, y, ZoomLevel::new(z))));
+            }
+        }
+    }
+
+    Ok(tiles)
+}
+
+impl World {
+    /// Requests all tiles which cover the `bounds` within the `zoom_range`, regardless of the
+    /// current view. The tiles are requested with a lower priority than the tiles in view. The
+    /// `on_progress` callback is called whenever tiles have finished loading.
+    ///
+    /// Returns the amount of tiles which are requested.
+    pub fn preload_region(
+        &mut self,
+        bounds: (LatLon, LatLon),
+        zoom_range: RangeInclusive<u8>,
+        on_progress: impl FnMut(PreloadProgress) + 'static,
+    ) -> Result<usize, PreloadError> {
+        let mut pending = region_tiles(bounds, zoom_range)?;
+        let total = pending.len();
+
+        if total == 0 {
+            return Ok(0);
+        }
+
+        // Tiles are taken from the back
+        pending.reverse();
+
+        self.resources
+            .get_or_init_mut::<PreloadRegions>()
+            .regions
+            .push(PreloadRegion {
+                pending,
+                in_flight: Vec::new(),
+                progress: PreloadProgress {
+                    completed: 0,
+                    total,
+                },
+                on_progress: Box::new(on_progress),
+            });
+
+        Ok(total)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use super::{evict_finished, region_tiles, PreloadError, PreloadRegions, MAX_PRELOAD_TILES};
+    use crate::{
+        coords::{LatLon, WorldTileCoords, ZoomLevel},
+        tcs::{tiles::Tiles, world::World},
+    };
+
+    const MUNICH: (LatLon, LatLon) = (
+        LatLon {
+            latitude: 48.2,
+            longitude: 11.5,
+        },
+        LatLon {
+            latitude: 48.1,
+            longitude: 11.6,
+        },
+    );
+
+    #[test]
+    fn test_region_tiles() {
+        // The region is covered by a single tile up to zoom 10
+        assert_eq!(region_tiles(MUNICH, 0..=10).unwrap().len(), 11);
+        assert_eq!(region_tiles(MUNICH, 12..=12).unwrap().len(), 6);
+        assert_eq!(region_tiles(MUNICH, 12..=13).unwrap().len(), 21);
+
+        assert_eq!(
+            region_tiles(MUNICH, 10..=9),
+            Err(PreloadError::InvalidZoomRange)
+        );
+        assert!(matches!(
+            region_tiles(MUNICH, 0..=20),
+            Err(PreloadError::TooManyTiles {
+                max: MAX_PRELOAD_TILES,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_preload_progress() {
+        let mut world = World::default();
+        let progress = Rc::new(RefCell::new(Vec::new()));
+
+        let reported = progress.clone();
+        let total = world
+            .preload_region(MUNICH, 12..=12, move |progress| {
+                reported.borrow_mut().push(progress.completed)
+            })
+            .unwrap();
+        assert_eq!(total, 6);
+
+        let regions = world.resources.get_mut::<PreloadRegions>().unwrap();
+        let requests = regions.next_requests(4);
+        assert_eq!(requests.len(), 4);
+
+        let finished = regions.update(|coords| coords == requests[0] || coords == requests[1]);
+        assert_eq!(finished, requests[..2]);
+        let requests = regions.next_requests(4);
+        assert_eq!(requests.len(), 2);
+
+        assert_eq!(regions.update(|_| true).len(), 4);
+        assert!(regions.is_empty());
+        assert_eq!(*progress.borrow(), vec![2, 6]);
+    }
+
+    #[test]
+    fn test_evict_finished() {
+        let mut tiles = Tiles::default();
+        let tile = |x| WorldTileCoords::from((x, 0, ZoomLevel::new(2)));
+        for x in 0..3 {
+            tiles.spawn_mut(tile(x)).unwrap();
+        }
+
+        // The first tile came into view while it was preloaded, the second one is pinned
+        tiles.mark_used(tile(0));
+        tiles.pin(&tile(1));
+
+        evict_finished(&mut tiles, &[tile(0), tile(1), tile(2)]);
+        assert!(tiles.exists(tile(0)));
+        assert!(tiles.exists(tile(1)));
+        assert!(!tiles.exists(tile(2)));
+        assert_eq!(tiles.take_evicted(), vec![tile(2)]);
+    }
+}
@@ -0,0 +1,192 @@
+//! Follows HTTP redirects of tile requests explicitly, e.g. if a provider redirects to a CDN.
+
+use std::future::Future;
+
+use thiserror::Error;
+
+use crate::io::source_client::SourceFetchError;
+
+/// Controls how redirects are followed.
+#[derive(Clone, Copy, Debug)]
+pub struct RedirectPolicy {
+    /// The maximum amount of redirects which are followed for a single request
+    pub max_redirects: usize,
+}
+
+impl Default for RedirectPolicy {
+    fn default() -> Self {
+        Self { max_redirects: 10 }
+    }
+}
+
+/// The response to a single request.
+#[derive(Debug)]
+pub enum FetchResponse<T = Vec<u8>> {
+    Data(T),
+    /// The requested resource is located at the contained URL
+    Redirect(String),
+}
+
+#[derive(Error, Debug)]
+pub enum RedirectError {
+    #[error("exceeded the maximum of {0} redirects")]
+    TooManyRedirects(usize),
+    #[error("redirected to {0}, which is no http(s) URL")]
+    UnsupportedScheme(String),
+}
+
+/// Requests the `url` with `fetch_once` and follows the returned redirects according to the
+/// `policy`. Returns the data of the final response.
+pub async fn follow_redirects<T, F, Fut>(
+    url: &str,
+    policy: RedirectPolicy,
+    fetch_once: F,
+) -> Result<T, SourceFetchError>
+where
+    F: Fn(String) -> Fut,
+    Fut: Future<Output = Result<FetchResponse<T>, SourceFetchError>>,
+{
+    let mut chain = vec![url.to_string()];
+
+    loop {
+        let current = chain.last().unwrap().clone();
+
+        match fetch_once(current.clone()).await? {
+            FetchResponse::Data(data) => {
+                if chain.len() > 1 {
+                    log::debug!("followed redirects {}", chain.join(" -> "));
+                }
+                return Ok(data);
+            }
+            FetchResponse::Redirect(location) => {
+                if chain.len() > policy.max_redirects {
+                    return Err(SourceFetchError(Box::new(RedirectError::TooManyRedirects(
+                        policy.max_redirects,
+                    ))));
+                }
+
+                let next = resolve_location(&current, &location);
+                if !next.starts_with("http://") && !next.starts_with("https://") {
+                    return Err(SourceFetchError(Box::new(
+                        RedirectError::UnsupportedScheme(next),
+                    )));
+                }
+
+                chain.push(next);
+            }
+        }
+    }
+}
+
+/// Resolves the `location` of a redirect relative to the `url` which has been requested.
+fn resolve_location(url: &str, location: &str) -> String {
+    if location.contains("://") {
+        return location.to_string();
+    }
+
+    let (scheme, rest) = url.split_once("://").unwrap_or(("", url));
+
+    if let Some(location) = location.strip_prefix("//") {
+        format!("{scheme}://{location}")
+    } else if location.starts_with('/') {
+        let host = rest.split('/').next().unwrap_or(rest);
+        format!("{scheme}://{host}{location}")
+    } else {
+        let path = rest.split(['?', '#']).next().unwrap_or(rest);
+        let directory = path
+            .rsplit_once('/')
+            .map_or(path, |(directory, _)| directory);
+        format!("{scheme}://{directory}/{location}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, collections::HashMap};
+
+    use super::{follow_redirects, resolve_location, FetchResponse, RedirectPolicy};
+    use crate::io::source_client::SourceFetchError;
+
+    /// Serves the responses for URLs and records the requested URLs
+    struct MockClient {
+        responses: HashMap<&'static str, &'static str>,
+        requested: RefCell<Vec<String>>,
+    }
+
+    impl MockClient {
+        async fn fetch_once(&self, url: String) -> Result<FetchResponse, SourceFetchError> {
+            self.requested.borrow_mut().push(url.clone());
+            let response = self.responses[url.as_str()];
+            Ok(match response.strip_prefix("redirect:") {
+                Some(location) => FetchResponse::Redirect(location.to_string()),
+                None => FetchResponse::Data(response.as_bytes().to_vec()),
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_follow_redirects() {
+        let client = MockClient {
+            responses: HashMap::from([
+                ("https://tiles.example/1/2/3.pbf", "redirect:/v2/1/2/3.pbf"),
+                (
+                    "https://tiles.example/v2/1/2/3.pbf",
+                    "redirect:https://cdn.example/1/2/3.pbf",
+                ),
+                ("https://cdn.example/1/2/3.pbf", "tile"),
+                ("https://tiles.example/loop", "redirect:loop"),
+                ("https://tiles.example/file", "redirect:file:///etc/passwd"),
+            ]),
+            requested: RefCell::default(),
+        };
+
+        let data = follow_redirects(
+            "https://tiles.example/1/2/3.pbf",
+            RedirectPolicy::default(),
+            |url| client.fetch_once(url),
+        )
+        .await
+        .unwrap();
+        assert_eq!(data, b"tile");
+        assert_eq!(client.requested.borrow().len(), 3);
+
+        client.requested.borrow_mut().clear();
+        assert!(follow_redirects(
+            "https://tiles.example/loop",
+            RedirectPolicy { max_redirects: 2 },
+            |url| client.fetch_once(url),
+        )
+        .await
+        .is_err());
+        assert_eq!(client.requested.borrow().len(), 3);
+
+        assert!(follow_redirects(
+            "https://tiles.example/file",
+            RedirectPolicy::default(),
+            |url| client.fetch_once(url),
+        )
+        .await
+        .is_err());
+    }
+
+    #[test]
+    fn test_resolve_location() {
+        let url = "https://tiles.example/a/b.pbf?key=1";
+        assert_eq!(
+            resolve_location(url, "https://cdn.example/b.pbf"),
+            "https://cdn.example/b.pbf"
+        );
+        assert_eq!(
+            resolve_location(url, "//cdn.example/b.pbf"),
+            "https://cdn.example/b.pbf"
+        );
+        assert_eq!(
+            resolve_location(url, "/c/b.pbf"),
+            "https://tiles.example/c/b.pbf"
+        );
+        assert_eq!(
+            resolve_location(url, "c.pbf"),
+            "https://tiles.example/a/c.pbf"
+        );
+    }
+}
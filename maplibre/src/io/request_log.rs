@@ -0,0 +1,339 @@
+//! Records the tile requests of a session, such that they can be inspected or replayed later,
+//! e.g. to reproduce a bug report or to compare the loading behaviour of two versions.
+
+use std::time::Duration;
+
+use instant::Instant;
+use serde::{Deserialize, Serialize};
+
+use crate::{coords::WorldTileCoords, tcs::world::World};
+
+/// Why a tile has been requested. Tiles in view are requested first, all others are requested
+/// afterwards in this order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RequestPriority {
+    /// The tile is in view
+    View,
+    /// The tile is in view and its data is outdated
+    Refresh,
+    /// The tile is pinned, see [`Tiles::pin`](crate::tcs::tiles::Tiles::pin)
+    Pinned,
+    /// The tile belongs to a preloaded region, see [`World::preload_region`]
+    Preload,
+}
+
+/// Which request system has issued a tile request.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RequestKind {
+    #[default]
+    Vector,
+    Raster,
+}
+
+/// A tile request which has been issued while the [`RequestLog`] was recording.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RecordedRequest {
+    /// Recordings which predate raster requests only contain vector requests
+    #[serde(default)]
+    pub kind: RequestKind,
+    pub coords: WorldTileCoords,
+    /// The requested source layers, sorted by name
+    pub layers: Vec<String>,
+    /// The source of the requested layers. `None` stands for the default source.
+    pub source: Option<String>,
+    /// The time since the recording started
+    pub elapsed: Duration,
+    pub priority: RequestPriority,
+}
+
+/// Records the tile requests which are issued by the vector and raster request systems while
+/// recording, see [`World::start_request_log`].
+///
+/// The recorded requests can be serialized, e.g. with `serde_json`, to write them to a file.
+pub struct RequestLog {
+    recording: bool,
+    started_at: Instant,
+    requests: Vec<RecordedRequest>,
+}
+
+impl Default for RequestLog {
+    fn default() -> Self {
+        Self {
+            recording: false,
+            started_at: Instant::now(),
+            requests: Vec::new(),
+        }
+    }
+}
+
+impl RequestLog {
+    pub fn is_recording(&self) -> bool {
+        self.recording
+    }
+
+    /// Records the request of the `layers` of the tile at `coords`, if the log is recording.
+    pub fn record(
+        &mut self,
+        kind: RequestKind,
+        coords: WorldTileCoords,
+        layers: impl IntoIterator<Item = String>,
+        source: Option<String>,
+        priority: RequestPriority,
+    ) {
+        if !self.recording {
+            return;
+        }
+
+        let mut layers: Vec<String> = layers.into_iter().collect();
+        layers.sort();
+
+        self.requests.push(RecordedRequest {
+            kind,
+            coords,
+            layers,
+            source,
+            elapsed: self.started_at.elapsed(),
+            priority,
+        });
+    }
+
+    /// The requests which have been recorded so far, in the order in which they were issued.
+    pub fn requests(&self) -> &[RecordedRequest] {
+        &self.requests
+    }
+}
+
+/// Recorded requests which are issued again with their original timing, see
+/// [`World::replay_requests`].
+#[derive(Default)]
+pub struct RequestReplay {
+    /// Requests which have not been issued yet, in the order in which they are due
+    pending: Vec<RecordedRequest>,
+    /// The time at which the first requests have been issued
+    started_at: Option<Instant>,
+}
+
+impl RequestReplay {
+    fn new(mut requests: Vec<RecordedRequest>) -> Self {
+        requests.sort_by_key(|request| request.elapsed);
+        Self {
+            pending: requests,
+            started_at: None,
+        }
+    }
+
+    /// Takes the requests of the `kind` which are due at `now`. The replay starts with the first
+    /// call of any request system.
+    pub fn take_due(&mut self, kind: RequestKind, now: Instant) -> Vec<RecordedRequest> {
+        let started_at = *self.started_at.get_or_insert(now);
+        let elapsed = now.saturating_duration_since(started_at);
+
+        let (due, pending): (Vec<_>, Vec<_>) = std::mem::take(&mut self.pending)
+            .into_iter()
+            .partition(|request| request.kind == kind && request.elapsed <= elapsed);
+        self.pending = pending;
+        due
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.pending.is_empty()
+    }
+}
+
+impl World {
+    /// Starts recording the tile requests. A previous recording is discarded.
+    pub fn start_request_log(&mut self) {
+        *self.resources.get_or_init_mut::<RequestLog>() = RequestLog {
+            recording: true,
+            ..RequestLog::default()
+        };
+    }
+
+    /// Stops recording the tile requests and returns the recorded requests.
+    pub fn stop_request_log(&mut self) -> Vec<RecordedRequest> {
+        let Some(log) = self.resources.get_mut::<RequestLog>() else { return Vec::new(); };
+        log.recording = false;
+        std::mem::take(&mut log.requests)
+    }
+
+    /// Issues the `requests` again, with the same delays in between them as when they were
+    /// recorded. Each tile is requested from the recorded sources and source layers, regardless of
+    /// the current style. Tiles which are already loaded or in flight are skipped, so replays
+    /// should start without tiles, e.g. after [`Tiles::clear`](crate::tcs::tiles::Tiles::clear).
+    /// A previous replay which has not finished yet is cancelled.
+    pub fn replay_requests(&mut self, requests: Vec<RecordedRequest>) {
+        *self.resources.get_or_init_mut::<RequestReplay>() = RequestReplay::new(requests);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use instant::Instant;
+
+    use super::{RecordedRequest, RequestKind, RequestLog, RequestPriority, RequestReplay};
+    use crate::{
+        coords::{WorldTileCoords, ZoomLevel},
+        tcs::world::World,
+    };
+
+    #[test]
+    fn test_replay_requests() {
+        let mut world = World::default();
+        let tile = |x, y| WorldTileCoords::from((x, y, ZoomLevel::from(3)));
+        let layers = || vec!["water".to_string(), "building".to_string()];
+        let streets = || Some("streets".to_string());
+
+        // Requests are not recorded before the log is started
+        world.resources.get_or_init_mut::<RequestLog>().record(
+            RequestKind::Vector,
+            tile(0, 0),
+            layers(),
+            None,
+            RequestPriority::View,
+        );
+
+        world.start_request_log();
+        let log = world.resources.get_mut::<RequestLog>().unwrap();
+        log.record(
+            RequestKind::Vector,
+            tile(1, 1),
+            layers(),
+            None,
+            RequestPriority::View,
+        );
+        log.record(
+            RequestKind::Vector,
+            tile(1, 1),
+            vec!["roads".to_string()],
+            streets(),
+            RequestPriority::View,
+        );
+        log.record(
+            RequestKind::Raster,
+            tile(2, 1),
+            vec!["raster".to_string()],
+            None,
+            RequestPriority::View,
+        );
+        log.record(
+            RequestKind::Vector,
+            tile(5, 5),
+            layers(),
+            None,
+            RequestPriority::Preload,
+        );
+
+        let mut recorded = world.stop_request_log();
+        assert_eq!(recorded.len(), 4);
+        assert_eq!(recorded[0].layers, vec!["building", "water"]);
+        assert_eq!(recorded[1].source, streets());
+        assert!(world.stop_request_log().is_empty());
+
+        // The recording survives a round trip through a file
+        let json = serde_json::to_string(&recorded).unwrap();
+        assert_eq!(
+            serde_json::from_str::<Vec<RecordedRequest>>(&json).unwrap(),
+            recorded
+        );
+
+        // The preload has been issued half a second after the requests of the view
+        recorded[3].elapsed = Duration::from_millis(500);
+
+        world.replay_requests(recorded.clone());
+        let replay = world.resources.get_mut::<RequestReplay>().unwrap();
+
+        // Each request system takes the requests which it has issued
+        let start = Instant::now();
+        let mut replayed = replay.take_due(RequestKind::Vector, start);
+        assert_eq!(replayed, recorded[..2]);
+        assert!(replay
+            .take_due(RequestKind::Vector, start + Duration::from_millis(499))
+            .is_empty());
+        assert_eq!(replay.take_due(RequestKind::Raster, start), recorded[2..3]);
+        replayed.extend(replay.take_due(RequestKind::Vector, start + Duration::from_millis(500)));
+        assert!(replay.is_finished());
+        assert_eq!(replayed[2], recorded[3]);
+    }
+
+    #[cfg(all(feature = "headless", feature = "thread-safe-futures"))]
+    #[tokio::test]
+    async fn test_replay_requests_through_request_system() {
+        use std::collections::HashSet;
+
+        use crate::{
+            headless::{
+                map::HeadlessMap,
+                tests::{kernel_builder, water_map, water_tile},
+            },
+            io::source_client::SourceFetchError,
+            vector::{VectorLayerData, VectorLayersDataComponent},
+        };
+
+        let mut map = water_map(kernel_builder(64).with_tile_generator(
+            |_coords: WorldTileCoords| async { Ok::<_, SourceFetchError>(water_tile()) },
+        ))
+        .await;
+
+        /// Renders until all requests of a replay have been issued and no tile is loading anymore
+        async fn render_until_loaded(map: &mut HeadlessMap) {
+            let is_loading = |map: &mut HeadlessMap| {
+                let world = map.world_mut();
+                world.tiles.has_loading_tiles()
+                    || !world
+                        .resources
+                        .get::<RequestReplay>()
+                        .map_or(true, RequestReplay::is_finished)
+            };
+
+            map.render().unwrap();
+            for _ in 0..100 {
+                if !is_loading(map) {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                map.render().unwrap();
+            }
+            assert!(!is_loading(map));
+        }
+
+        // The request system records the tiles in view
+        map.world_mut().start_request_log();
+        render_until_loaded(&mut map).await;
+        let mut recorded = map.world_mut().stop_request_log();
+
+        assert!(!recorded.is_empty());
+        assert!(recorded
+            .iter()
+            .all(|request| request.kind == RequestKind::Vector
+                && request.source.is_none()
+                && request.layers == vec!["water"]));
+        let coords: HashSet<WorldTileCoords> =
+            recorded.iter().map(|request| request.coords).collect();
+
+        // The replay requests the recorded layers, even if the style does not draw them. The
+        // camera has not moved, so the tiles are only requested again by the replay.
+        for request in &mut recorded {
+            request.layers.push("rivers".to_string());
+        }
+        map.world_mut().tiles.clear();
+        map.world_mut().replay_requests(recorded);
+        render_until_loaded(&mut map).await;
+
+        let tiles = &map.world_mut().tiles;
+        assert_eq!(
+            tiles.loaded_coords().into_iter().collect::<HashSet<_>>(),
+            coords
+        );
+        for coords in coords {
+            let component = tiles.query::<&VectorLayersDataComponent>(coords).unwrap();
+            assert!(component.layers.iter().any(|layer| matches!(
+                layer,
+                VectorLayerData::Missing(data) if data.source_layer == "rivers"
+            )));
+        }
+    }
+}
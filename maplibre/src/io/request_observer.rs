@@ -0,0 +1,50 @@
+//! Observes the URLs of requested tiles, e.g. to debug misconfigured sources.
+
+use crate::coords::WorldTileCoords;
+
+/// Is notified about every tile which is requested from a source. The `url` is the fully
+/// expanded URL which is passed to the [`HttpClient`](crate::io::source_client::HttpClient).
+pub trait RequestObserver: Send + Sync + 'static {
+    fn on_request(&self, url: &str, coords: &WorldTileCoords);
+}
+
+impl<F> RequestObserver for F
+where
+    F: Fn(&str, &WorldTileCoords) + Send + Sync + 'static,
+{
+    fn on_request(&self, url: &str, coords: &WorldTileCoords) {
+        self(url, coords)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use crate::{
+        coords::{WorldTileCoords, ZoomLevel},
+        io::{
+            source_client::{tests::MockHttpClient, HttpSourceClient},
+            source_type::{SourceType, TessellateSource},
+        },
+    };
+
+    #[tokio::test]
+    async fn test_observe_request() {
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let observed = requests.clone();
+        let client = HttpSourceClient::new(MockHttpClient::serving(Vec::new()))
+            .with_request_observer(move |url: &str, coords: &WorldTileCoords| {
+                observed.lock().unwrap().push((url.to_string(), *coords));
+            });
+
+        let coords = WorldTileCoords::from((1, 2, ZoomLevel::from(3)));
+        let source = SourceType::Tessellate(TessellateSource::new("https://example.com", "pbf"));
+        client.fetch(&coords, &source).await.unwrap();
+
+        assert_eq!(
+            *requests.lock().unwrap(),
+            vec![("https://example.com/3/1/2.pbf".to_string(), coords)]
+        );
+    }
+}
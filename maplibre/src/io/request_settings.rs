@@ -0,0 +1,276 @@
+//! Settings which control when and which tiles are requested.
+
+use std::time::Duration;
+
+use instant::{Instant, SystemTime};
+use serde::{Deserialize, Serialize};
+
+use crate::{tcs::world::World, view_state::ViewState};
+
+/// Controls when the request systems request tiles. This is stored as a resource in the
+/// [`World`](crate::tcs::world::World). If it is absent, the default settings are used.
+#[derive(Clone, Copy, Debug)]
+pub struct RequestSettings {
+    /// Whether tiles are requested for the intermediate frames of a camera animation. If disabled,
+    /// tiles are only requested once the animation has finished.
+    pub request_during_animation: bool,
+    /// The minimum time between two requests for a changed view. Changes of the camera within
+    /// this window are coalesced into a single request for the view at the end of the window.
+    /// A zero window, the default, requests tiles in every frame in which the camera changed.
+    pub coalesce_window: Duration,
+    /// The ratio between physical and logical pixels of the display. Raster tiles for high-DPI
+    /// displays, like `@2x` tiles, are requested if the ratio is above 1.
+    pub pixel_ratio: f64,
+    /// The maximum amount of tiles which are requested in a single frame. If more tiles enter the
+    /// view, the remaining tiles are requested in the following frames, nearest to the center of
+    /// the view first. Tiles which left the view in the meantime are not requested anymore.
+    pub max_requests_per_frame: Option<usize>,
+    /// The time for which a tile which is requested for the current view is useful. Tiles whose
+    /// deadline has passed before they are tessellated are dropped, as the view most likely moved
+    /// on, e.g. during an animation. Dropped tiles are requested again once they are in view.
+    pub request_deadline: Option<Duration>,
+}
+
+impl Default for RequestSettings {
+    fn default() -> Self {
+        Self {
+            request_during_animation: true,
+            coalesce_window: Duration::ZERO,
+            pixel_ratio: 1.0,
+            max_requests_per_frame: None,
+            request_deadline: None,
+        }
+    }
+}
+
+impl RequestSettings {
+    /// The settings of the `world` if the tiles of the current view should be requested, see
+    /// [`should_request`](Self::should_request). Nothing is requested while the `world` is
+    /// [frozen](World::freeze).
+    pub fn for_view_request(
+        world: &World,
+        view_state: &ViewState,
+        last_request: Option<Instant>,
+        now: Instant,
+    ) -> Option<Self> {
+        if world.is_frozen() {
+            return None;
+        }
+
+        let settings = world
+            .resources
+            .get::<RequestSettings>()
+            .copied()
+            .unwrap_or_default();
+
+        settings
+            .should_request(view_state, last_request, now)
+            .then_some(settings)
+    }
+
+    /// Whether the tiles of the current view should be requested. The `last_request` is the time
+    /// at which the tiles of a changed view have been requested the last time.
+    pub fn should_request(
+        &self,
+        view_state: &ViewState,
+        last_request: Option<Instant>,
+        now: Instant,
+    ) -> bool {
+        if !self.request_during_animation && view_state.is_animating() {
+            return false;
+        }
+
+        let did_view_change = view_state.did_camera_change() || view_state.did_zoom_change();
+
+        !did_view_change
+            || last_request.map_or(true, |last_request| {
+                now.saturating_duration_since(last_request) >= self.coalesce_window
+            })
+    }
+}
+
+/// The point in time after which a requested tile is not useful anymore, see
+/// [`RequestSettings::request_deadline`]. The deadline is stored as wall-clock time, such that it
+/// can be passed to other threads and workers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Deadline {
+    /// Milliseconds since the UNIX epoch
+    unix_millis: u64,
+}
+
+impl Deadline {
+    /// A deadline which passes once the `timeout` has elapsed from now.
+    pub fn after(timeout: Duration) -> Self {
+        Self {
+            unix_millis: unix_millis() + timeout.as_millis() as u64,
+        }
+    }
+
+    pub fn has_passed(&self) -> bool {
+        unix_millis() >= self.unix_millis
+    }
+}
+
+fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_millis() as u64)
+}
+
+/// Counts the tile requests of a single frame against
+/// [`RequestSettings::max_requests_per_frame`].
+pub struct RequestBudget {
+    remaining: Option<usize>,
+    deferred: bool,
+}
+
+impl RequestBudget {
+    pub fn new(settings: &RequestSettings) -> Self {
+        Self {
+            remaining: settings.max_requests_per_frame,
+            deferred: false,
+        }
+    }
+
+    /// Takes a request from the budget. Returns `false` if the budget is exhausted, in which case
+    /// the request has to be deferred to the next frame.
+    pub fn take(&mut self) -> bool {
+        match &mut self.remaining {
+            Some(0) => {
+                self.deferred = true;
+                false
+            }
+            Some(remaining) => {
+                *remaining -= 1;
+                true
+            }
+            None => true,
+        }
+    }
+
+    /// Whether any requests have been deferred to the next frame.
+    pub fn has_deferred(&self) -> bool {
+        self.deferred
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use cgmath::{Deg, Vector3};
+    use instant::Instant;
+
+    use super::RequestSettings;
+    use crate::{
+        coords::{LatLon, WorldCoords, Zoom},
+        tcs::world::World,
+        view_state::ViewState,
+        window::WindowSize,
+    };
+
+    /// Steps an animation from Munich to Berlin and counts the frames in which tiles are
+    /// requested.
+    fn count_requests(settings: RequestSettings) -> usize {
+        let zoom = Zoom::new(10.0);
+        let mut view_state = ViewState::new(
+            WindowSize::new(800, 600).unwrap(),
+            WorldCoords::from_lat_lon(LatLon::new(48.137154, 11.576124), zoom),
+            zoom,
+            Deg(0.0),
+            Deg(110.0),
+        );
+        view_state.update_references();
+
+        let target_zoom = Zoom::new(12.0);
+        view_state.animate_to(
+            WorldCoords::from_lat_lon(LatLon::new(52.520008, 13.404954), target_zoom),
+            target_zoom,
+            Duration::from_secs(1),
+        );
+
+        let start = Instant::now();
+        let mut last_request = None;
+
+        let mut requests = 0;
+        for frame in 1..=20 {
+            view_state.advance_animation(Duration::from_millis(100));
+            let now = start + Duration::from_millis(100 * frame);
+
+            if !settings.should_request(&view_state, last_request, now) {
+                continue;
+            }
+
+            if view_state.did_camera_change() || view_state.did_zoom_change() {
+                last_request = Some(now);
+                requests += 1;
+            }
+            view_state.update_references();
+        }
+
+        assert!(!view_state.is_animating());
+        requests
+    }
+
+    #[test]
+    fn test_request_during_animation() {
+        assert_eq!(
+            count_requests(RequestSettings {
+                request_during_animation: true,
+                ..RequestSettings::default()
+            }),
+            10
+        );
+        assert_eq!(
+            count_requests(RequestSettings {
+                request_during_animation: false,
+                ..RequestSettings::default()
+            }),
+            1
+        );
+    }
+
+    #[test]
+    fn test_coalesce_window() {
+        // Without a window, tiles are requested in every frame of the animation
+        assert_eq!(count_requests(RequestSettings::default()), 10);
+
+        // Requests at 100ms, 400ms, 700ms and for the final view at 1s
+        assert_eq!(
+            count_requests(RequestSettings {
+                request_during_animation: true,
+                coalesce_window: Duration::from_millis(250),
+                ..RequestSettings::default()
+            }),
+            4
+        );
+    }
+
+    #[test]
+    fn test_frozen_world() {
+        let zoom = Zoom::new(10.0);
+        let mut view_state = ViewState::new(
+            WindowSize::new(800, 600).unwrap(),
+            WorldCoords::from_lat_lon(LatLon::new(48.137154, 11.576124), zoom),
+            zoom,
+            Deg(0.0),
+            Deg(110.0),
+        );
+        view_state.update_references();
+
+        let mut world = World::default();
+        world.freeze();
+
+        view_state
+            .camera_mut()
+            .move_relative(Vector3::new(100.0, 0.0, 0.0));
+        assert!(view_state.did_camera_change());
+
+        let now = Instant::now();
+        assert!(RequestSettings::for_view_request(&world, &view_state, None, now).is_none());
+
+        // The change of the camera is requested once the world is unfrozen
+        world.unfreeze();
+        assert!(RequestSettings::for_view_request(&world, &view_state, None, now).is_some());
+    }
+}
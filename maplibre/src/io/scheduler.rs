@@ -10,6 +10,8 @@ pub enum ScheduleError {
     Scheduling(Box<dyn std::error::Error>),
     #[error("scheduler is not implemented on this platform")]
     NotImplemented,
+    #[error("scheduler has been shut down")]
+    ShutDown,
 }
 
 /// Async/await scheduler.
@@ -30,6 +32,12 @@ pub trait Scheduler: 'static {
     ) -> Result<(), ScheduleError>
     where
         T: Future<Output = ()> + 'static;
+
+    /// Stops accepting new work. Work which is still running is cancelled, such that the
+    /// resources which it holds, like HTTP connections, are released. Work which is scheduled
+    /// afterwards fails with [`ScheduleError::ShutDown`]. Called when the
+    /// [`Kernel`](crate::kernel::Kernel) is dropped.
+    fn shutdown(&self) {}
 }
 
 pub struct NopScheduler;
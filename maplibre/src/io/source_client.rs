@@ -1,9 +1,25 @@
 //! HTTP client.
 
+use std::{sync::Arc, time::Duration};
+
 use async_trait::async_trait;
 use thiserror::Error;
 
-use crate::{coords::WorldTileCoords, io::source_type::SourceType};
+use crate::{
+    coords::WorldTileCoords,
+    io::{
+        io_stats::{IoCounters, IoStats, IoStatsCallback},
+        request_observer::RequestObserver,
+        source_type::SourceType,
+        tile_generator::TileGenerator,
+        tile_key::{QuadKeyTileKey, TileKey},
+        tile_transform::TileTransform,
+    },
+};
+
+/// The default maximum size of a fetched tile in bytes, see
+/// [`HttpSourceClient::with_max_tile_size`].
+pub const DEFAULT_MAX_TILE_SIZE: usize = 50 * 1024 * 1024;
 
 /// A closure that returns a HTTP client.
 pub type HTTPClientFactory<HC> = dyn Fn() -> HC;
@@ -18,6 +34,59 @@ pub type HTTPClientFactory<HC> = dyn Fn() -> HC;
 #[cfg_attr(feature = "thread-safe-futures", async_trait)]
 pub trait HttpClient: Clone + Sync + Send + 'static {
     async fn fetch(&self, url: &str) -> Result<Vec<u8>, SourceFetchError>;
+
+    /// Fetches a tile. Clients which cache tiles identify them by the `key`, see [`TileKey`]. By
+    /// default the key is ignored.
+    async fn fetch_tile(&self, url: &str, _key: &str) -> Result<Vec<u8>, SourceFetchError> {
+        self.fetch(url).await
+    }
+
+    /// Fetches a tile like [`HttpClient::fetch_tile`] and reports whether it has been served from
+    /// a cache, see [`IoStats`]. Clients which do not know about a cache report `None`.
+    ///
+    /// Clients should stop reading the response once it exceeds `max_size` bytes and return a
+    /// [`TileTooLargeError`]. Larger tiles which are returned anyway are rejected afterwards.
+    async fn fetch_cached_tile(
+        &self,
+        url: &str,
+        key: &str,
+        _max_size: usize,
+    ) -> Result<(Vec<u8>, Option<bool>), SourceFetchError> {
+        Ok((self.fetch_tile(url, key).await?, None))
+    }
+
+    /// Fetches several tiles with a single request to a batch endpoint. `tiles` contains the url
+    /// and the key of each tile, see [`HttpClient::fetch_tile`]. Returns the raw response, which
+    /// contains the tiles in the order of `tiles`, each of them prefixed by its length as a
+    /// big-endian `u32`. The response is split into the tiles by the [`HttpSourceClient`].
+    ///
+    /// This is only used for sources which declare batch support. Clients without a batch
+    /// endpoint return `None`, in which case the tiles are fetched with
+    /// [`HttpClient::fetch_tiles`].
+    async fn fetch_batch_response(
+        &self,
+        _tiles: &[(String, String)],
+    ) -> Option<Result<Vec<u8>, SourceFetchError>> {
+        None
+    }
+
+    /// Fetches several tiles at once, e.g. with a single range request which covers all of them.
+    /// `tiles` contains the url and the key of each tile, see [`HttpClient::fetch_tile`]. Returns
+    /// the result of each tile in the order of `tiles`.
+    ///
+    /// This is only used for sources which declare batch support, if the client has no batch
+    /// endpoint, see [`HttpClient::fetch_batch_response`]. By default each tile is fetched
+    /// separately.
+    async fn fetch_tiles(
+        &self,
+        tiles: &[(String, String)],
+    ) -> Vec<Result<Vec<u8>, SourceFetchError>> {
+        let mut results = Vec::with_capacity(tiles.len());
+        for (url, key) in tiles {
+            results.push(self.fetch_tile(url, key).await);
+        }
+        results
+    }
 }
 
 /// Gives access to the HTTP client which can be of multiple types,
@@ -28,12 +97,43 @@ where
     HC: HttpClient,
 {
     inner_client: HC,
+    tile_key: Arc<dyn TileKey>,
+    transform: Option<Arc<dyn TileTransform>>,
+    /// Generates the tiles instead of the HTTP client if set
+    generator: Option<Arc<dyn TileGenerator>>,
+    request_observer: Option<Arc<dyn RequestObserver>>,
+    max_tile_size: usize,
+    /// Shared by all clones of the client
+    counters: Arc<IoCounters>,
 }
 
 #[derive(Error, Debug)]
 #[error("failed to fetch from source")]
 pub struct SourceFetchError(#[source] pub Box<dyn std::error::Error>);
 
+impl SourceFetchError {
+    /// Whether the fetched tile has been rejected because it exceeds the maximum tile size.
+    pub fn is_too_large(&self) -> bool {
+        self.0.is::<TileTooLargeError>()
+    }
+}
+
+/// A fetched tile exceeds the maximum tile size, see [`HttpSourceClient::with_max_tile_size`].
+#[derive(Error, Debug)]
+#[error("tile of {size} bytes exceeds the maximum tile size of {max_size} bytes")]
+pub struct TileTooLargeError {
+    /// The size of the tile. Clients which stop reading large tiles report the bytes which have
+    /// been read so far.
+    pub size: usize,
+    pub max_size: usize,
+}
+
+impl From<TileTooLargeError> for SourceFetchError {
+    fn from(error: TileTooLargeError) -> Self {
+        SourceFetchError(Box::new(error))
+    }
+}
+
 /// Defines the different types of HTTP clients such as basic HTTP and Mbtiles.
 /// More types might be coming such as S3 and other cloud http clients.
 #[derive(Clone)]
@@ -59,6 +159,20 @@ where
     ) -> Result<Vec<u8>, SourceFetchError> {
         self.http.fetch(coords, source_type).await
     }
+
+    /// Fetches the tiles at `coords` with a single batch request, see [`HttpClient::fetch_tiles`].
+    pub async fn fetch_batch(
+        &self,
+        coords: &[WorldTileCoords],
+        source_type: &SourceType,
+    ) -> Vec<Result<Vec<u8>, SourceFetchError>> {
+        self.http.fetch_batch(coords, source_type).await
+    }
+
+    /// The statistics of all tiles which have been requested by this client and its clones.
+    pub fn io_stats(&self) -> IoStats {
+        self.http.counters.stats()
+    }
 }
 
 impl<HC> HttpSourceClient<HC>
@@ -68,16 +182,514 @@ where
     pub fn new(http_client: HC) -> Self {
         Self {
             inner_client: http_client,
+            tile_key: Arc::new(QuadKeyTileKey),
+            transform: None,
+            generator: None,
+            request_observer: None,
+            max_tile_size: DEFAULT_MAX_TILE_SIZE,
+            counters: Default::default(),
         }
     }
 
+    /// Uses the `tile_key` to derive the cache keys of tiles instead of their quad keys.
+    pub fn with_tile_key(self, tile_key: impl TileKey) -> Self {
+        self.with_shared_tile_key(Arc::new(tile_key))
+    }
+
+    pub(crate) fn with_shared_tile_key(mut self, tile_key: Arc<dyn TileKey>) -> Self {
+        self.tile_key = tile_key;
+        self
+    }
+
+    /// Applies the `transform` to fetched tiles before they are processed, see [`TileTransform`].
+    pub fn with_tile_transform(self, transform: impl TileTransform) -> Self {
+        self.with_shared_tile_transform(Arc::new(transform))
+    }
+
+    pub(crate) fn with_shared_tile_transform(mut self, transform: Arc<dyn TileTransform>) -> Self {
+        self.transform = Some(transform);
+        self
+    }
+
+    /// Produces tiles with the `generator` instead of fetching them with the HTTP client, see
+    /// [`TileGenerator`].
+    pub fn with_tile_generator(self, generator: impl TileGenerator) -> Self {
+        self.with_shared_tile_generator(Arc::new(generator))
+    }
+
+    pub(crate) fn with_shared_tile_generator(mut self, generator: Arc<dyn TileGenerator>) -> Self {
+        self.generator = Some(generator);
+        self
+    }
+
+    /// Notifies the `request_observer` about every requested tile, see [`RequestObserver`].
+    pub fn with_request_observer(self, request_observer: impl RequestObserver) -> Self {
+        self.with_shared_request_observer(Arc::new(request_observer))
+    }
+
+    pub(crate) fn with_shared_request_observer(
+        mut self,
+        request_observer: Arc<dyn RequestObserver>,
+    ) -> Self {
+        self.request_observer = Some(request_observer);
+        self
+    }
+
+    /// Rejects fetched tiles which are larger than `max_tile_size` bytes before they are decoded.
+    /// The limit applies to tiles before and after they are transformed. Defaults to
+    /// [`DEFAULT_MAX_TILE_SIZE`].
+    pub fn with_max_tile_size(mut self, max_tile_size: usize) -> Self {
+        self.max_tile_size = max_tile_size;
+        self
+    }
+
+    /// Calls the `callback` with the statistics of the requested tiles at most once per `interval`
+    /// after a request has finished. Replaces the statistics which have been collected so far.
+    pub fn with_io_stats_callback(self, interval: Duration, callback: IoStatsCallback) -> Self {
+        self.with_shared_io_counters(Arc::new(IoCounters::with_callback(interval, callback)))
+    }
+
+    pub(crate) fn with_shared_io_counters(mut self, counters: Arc<IoCounters>) -> Self {
+        self.counters = counters;
+        self
+    }
+
+    fn check_tile_size(&self, data: Vec<u8>) -> Result<Vec<u8>, SourceFetchError> {
+        if data.len() > self.max_tile_size {
+            self.counters.record_too_large();
+            return Err(SourceFetchError(Box::new(TileTooLargeError {
+                size: data.len(),
+                max_size: self.max_tile_size,
+            })));
+        }
+
+        Ok(data)
+    }
+
     pub async fn fetch(
         &self,
         coords: &WorldTileCoords,
         source_type: &SourceType,
     ) -> Result<Vec<u8>, SourceFetchError> {
-        self.inner_client
-            .fetch(source_type.format(coords).as_str())
+        let url = source_type.format(coords);
+        log::debug!("requesting tile {coords} from {url}");
+        if let Some(request_observer) = &self.request_observer {
+            request_observer.on_request(&url, coords);
+        }
+
+        self.counters.record_request();
+        let key = self.tile_key.key(coords, source_type);
+        let result = self.fetch_url(coords, source_type, &url, &key).await;
+        self.counters.notify();
+        result
+    }
+
+    pub async fn fetch_batch(
+        &self,
+        coords: &[WorldTileCoords],
+        source_type: &SourceType,
+    ) -> Vec<Result<Vec<u8>, SourceFetchError>> {
+        let tiles: Vec<(String, String)> = coords
+            .iter()
+            .map(|coords| {
+                let url = source_type.format(coords);
+                log::debug!("requesting tile {coords} from {url} in a batch");
+                if let Some(request_observer) = &self.request_observer {
+                    request_observer.on_request(&url, coords);
+                }
+                self.counters.record_request();
+                (url, self.tile_key.key(coords, source_type))
+            })
+            .collect();
+
+        let mut fetched = match &self.generator {
+            Some(generator) => {
+                let mut results = Vec::with_capacity(coords.len());
+                for coords in coords {
+                    results.push(generator.generate(*coords, source_type).await);
+                }
+                results
+            }
+            None => match self.inner_client.fetch_batch_response(&tiles).await {
+                Some(Ok(response)) => split_batch_response(&response),
+                Some(Err(e)) => {
+                    log::warn!("batch of {} tiles could not be fetched: {e:?}", tiles.len());
+                    tiles
+                        .iter()
+                        .map(|_| Err(SourceFetchError("the batch request failed".into())))
+                        .collect()
+                }
+                None => self.inner_client.fetch_tiles(&tiles).await,
+            },
+        };
+        // Tiles which are missing from the response of the client failed to fetch
+        fetched.resize_with(tiles.len(), || {
+            Err(SourceFetchError(
+                "tile is missing from the batch response".into(),
+            ))
+        });
+
+        let results = fetched
+            .into_iter()
+            .zip(&tiles)
+            .map(|(result, (_url, key))| {
+                let data = result.map_err(|e| {
+                    if e.is_too_large() {
+                        self.counters.record_too_large();
+                    } else {
+                        self.counters.record_fetch_error();
+                    }
+                    e
+                })?;
+                self.counters.record_fetched(data.len(), None);
+                self.check_and_transform(source_type, key, data)
+            })
+            .collect();
+        self.counters.notify();
+        results
+    }
+
+    async fn fetch_url(
+        &self,
+        coords: &WorldTileCoords,
+        source_type: &SourceType,
+        url: &str,
+        key: &str,
+    ) -> Result<Vec<u8>, SourceFetchError> {
+        let result = match &self.generator {
+            Some(generator) => generator
+                .generate(*coords, source_type)
+                .await
+                .map(|data| (data, None)),
+            None => {
+                self.inner_client
+                    .fetch_cached_tile(url, key, self.max_tile_size)
+                    .await
+            }
+        };
+        let (data, cache_hit) = result.map_err(|e| {
+            if e.is_too_large() {
+                self.counters.record_too_large();
+            } else {
+                self.counters.record_fetch_error();
+            }
+            e
+        })?;
+        self.counters.record_fetched(data.len(), cache_hit);
+
+        self.check_and_transform(source_type, key, data)
+    }
+
+    fn check_and_transform(
+        &self,
+        source_type: &SourceType,
+        key: &str,
+        data: Vec<u8>,
+    ) -> Result<Vec<u8>, SourceFetchError> {
+        let data = self.check_tile_size(data)?;
+
+        match &self.transform {
+            Some(transform) => {
+                let data = transform.transform(source_type, key, data).map_err(|e| {
+                    self.counters.record_transform_error();
+                    e
+                })?;
+                self.check_tile_size(data)
+            }
+            None => Ok(data),
+        }
+    }
+}
+
+/// Splits the `response` of a batch endpoint into its tiles, each of which is prefixed by its
+/// length as a big-endian `u32`, see [`HttpClient::fetch_batch_response`]. A truncated tile fails
+/// to fetch, as do all tiles which follow it.
+fn split_batch_response(mut response: &[u8]) -> Vec<Result<Vec<u8>, SourceFetchError>> {
+    let mut tiles = Vec::new();
+    while !response.is_empty() {
+        if response.len() < 4 {
+            tiles.push(Err(SourceFetchError(
+                "tile length is truncated in the batch response".into(),
+            )));
+            break;
+        }
+        let (length, rest) = response.split_at(4);
+        let length = u32::from_be_bytes([length[0], length[1], length[2], length[3]]) as usize;
+        if rest.len() < length {
+            tiles.push(Err(SourceFetchError(
+                "tile is truncated in the batch response".into(),
+            )));
+            break;
+        }
+        let (tile, rest) = rest.split_at(length);
+        tiles.push(Ok(tile.to_vec()));
+        response = rest;
+    }
+    tiles
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use async_trait::async_trait;
+
+    use super::{
+        split_batch_response, HttpClient, HttpSourceClient, SourceClient, SourceFetchError,
+        TileTooLargeError,
+    };
+    use crate::{
+        coords::{WorldTileCoords, ZoomLevel},
+        io::source_type::{SourceType, TessellateSource},
+    };
+
+    /// Responds to every request with the result of a closure, which receives the URL and the key
+    /// of the requested tile. Requests without a key receive an empty key.
+    #[derive(Clone)]
+    pub struct MockHttpClient {
+        respond: Arc<dyn Fn(&str, &str) -> Result<Vec<u8>, SourceFetchError> + Send + Sync>,
+    }
+
+    impl MockHttpClient {
+        pub fn new(
+            respond: impl Fn(&str, &str) -> Result<Vec<u8>, SourceFetchError> + Send + Sync + 'static,
+        ) -> Self {
+            Self {
+                respond: Arc::new(respond),
+            }
+        }
+
+        /// Serves `data` for all requests
+        pub fn serving(data: Vec<u8>) -> Self {
+            Self::new(move |_url, _key| Ok(data.clone()))
+        }
+
+        /// Fails to fetch anything
+        pub fn failing() -> Self {
+            Self::new(|url, _key| Err(SourceFetchError(format!("{url} is not available").into())))
+        }
+    }
+
+    #[cfg_attr(not(feature = "thread-safe-futures"), async_trait(?Send))]
+    #[cfg_attr(feature = "thread-safe-futures", async_trait)]
+    impl HttpClient for MockHttpClient {
+        async fn fetch(&self, url: &str) -> Result<Vec<u8>, SourceFetchError> {
+            (self.respond)(url, "")
+        }
+
+        async fn fetch_tile(&self, url: &str, key: &str) -> Result<Vec<u8>, SourceFetchError> {
+            (self.respond)(url, key)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reject_oversized_tile() {
+        let coords = WorldTileCoords::from((0, 0, ZoomLevel::default()));
+        let source = SourceType::Tessellate(TessellateSource::default());
+
+        let client =
+            HttpSourceClient::new(MockHttpClient::serving(vec![0; 1024])).with_max_tile_size(1024);
+        assert_eq!(client.fetch(&coords, &source).await.unwrap().len(), 1024);
+
+        let client =
+            HttpSourceClient::new(MockHttpClient::serving(vec![0; 1025])).with_max_tile_size(1024);
+        let error = client.fetch(&coords, &source).await.unwrap_err();
+        assert!(error.is_too_large());
+    }
+
+    /// Stops reading tiles of `self.0` bytes once they exceed the maximum size
+    #[derive(Clone)]
+    struct StreamingHttpClient(usize);
+
+    #[cfg_attr(not(feature = "thread-safe-futures"), async_trait(?Send))]
+    #[cfg_attr(feature = "thread-safe-futures", async_trait)]
+    impl HttpClient for StreamingHttpClient {
+        async fn fetch(&self, _url: &str) -> Result<Vec<u8>, SourceFetchError> {
+            unreachable!("tiles are fetched with a limit")
+        }
+
+        async fn fetch_cached_tile(
+            &self,
+            _url: &str,
+            _key: &str,
+            max_size: usize,
+        ) -> Result<(Vec<u8>, Option<bool>), SourceFetchError> {
+            if self.0 > max_size {
+                return Err(TileTooLargeError {
+                    size: max_size + 1,
+                    max_size,
+                }
+                .into());
+            }
+            Ok((vec![0; self.0], None))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stop_reading_oversized_tile() {
+        let coords = WorldTileCoords::from((0, 0, ZoomLevel::default()));
+        let source = SourceType::Tessellate(TessellateSource::default());
+
+        let client = SourceClient::new(
+            HttpSourceClient::new(StreamingHttpClient(4096)).with_max_tile_size(1024),
+        );
+        let error = client.fetch(&coords, &source).await.unwrap_err();
+        assert!(error.is_too_large());
+
+        // The rejected tile is no failed request
+        let stats = client.io_stats();
+        assert_eq!(stats.too_large_errors, 1);
+        assert_eq!(stats.fetch_errors, 0);
+        assert_eq!(stats.bytes, 0);
+    }
+
+    /// Serves batches of tiles from a batch endpoint, which responds with the URL of each tile as
+    /// its data. The last `self.cut` bytes of each response are lost.
+    #[derive(Clone, Default)]
+    struct BatchHttpClient {
+        requests: Arc<AtomicUsize>,
+        cut: usize,
+    }
+
+    #[cfg_attr(not(feature = "thread-safe-futures"), async_trait(?Send))]
+    #[cfg_attr(feature = "thread-safe-futures", async_trait)]
+    impl HttpClient for BatchHttpClient {
+        async fn fetch(&self, _url: &str) -> Result<Vec<u8>, SourceFetchError> {
+            unreachable!("tiles are fetched in batches")
+        }
+
+        async fn fetch_batch_response(
+            &self,
+            tiles: &[(String, String)],
+        ) -> Option<Result<Vec<u8>, SourceFetchError>> {
+            self.requests.fetch_add(1, Ordering::Relaxed);
+            let mut response = Vec::new();
+            for (url, _) in tiles {
+                response.extend_from_slice(&(url.len() as u32).to_be_bytes());
+                response.extend_from_slice(url.as_bytes());
+            }
+            response.truncate(response.len() - self.cut);
+            Some(Ok(response))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fetch_batch() {
+        let source = SourceType::Tessellate(TessellateSource::default());
+        let coords = [
+            WorldTileCoords::from((0, 0, ZoomLevel::from(1))),
+            WorldTileCoords::from((1, 0, ZoomLevel::from(1))),
+        ];
+
+        let http_client = BatchHttpClient::default();
+        let client = HttpSourceClient::new(http_client.clone());
+        let tiles = client
+            .fetch_batch(&coords, &source)
             .await
+            .into_iter()
+            .map(|tile| String::from_utf8(tile.unwrap()).unwrap())
+            .collect::<Vec<_>>();
+
+        // Both tiles are split from the response of a single request
+        assert_eq!(http_client.requests.load(Ordering::Relaxed), 1);
+        assert_eq!(
+            tiles,
+            vec![source.format(&coords[0]), source.format(&coords[1])]
+        );
+        assert_eq!(client.counters.stats().requested, 2);
+
+        // The tiles from the truncated tile onwards fail
+        let coords = [coords[0], coords[1], coords[0]];
+        let client = HttpSourceClient::new(BatchHttpClient {
+            cut: 1,
+            ..BatchHttpClient::default()
+        });
+        let tiles = client.fetch_batch(&coords, &source).await;
+        assert_eq!(
+            tiles[0].as_ref().unwrap(),
+            source.format(&coords[0]).as_bytes()
+        );
+        assert!(tiles[1].is_ok());
+        assert!(tiles[2].is_err());
+        assert_eq!(client.counters.stats().fetch_errors, 1);
+    }
+
+    /// Fetches batches of tiles of `self.0` bytes, which are rejected once they exceed the
+    /// maximum size
+    #[derive(Clone)]
+    struct LargeBatchHttpClient(usize);
+
+    #[cfg_attr(not(feature = "thread-safe-futures"), async_trait(?Send))]
+    #[cfg_attr(feature = "thread-safe-futures", async_trait)]
+    impl HttpClient for LargeBatchHttpClient {
+        async fn fetch(&self, _url: &str) -> Result<Vec<u8>, SourceFetchError> {
+            unreachable!("tiles are fetched in batches")
+        }
+
+        async fn fetch_tiles(
+            &self,
+            tiles: &[(String, String)],
+        ) -> Vec<Result<Vec<u8>, SourceFetchError>> {
+            tiles
+                .iter()
+                .map(|_| {
+                    Err(TileTooLargeError {
+                        size: self.0,
+                        max_size: 1024,
+                    }
+                    .into())
+                })
+                .collect()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reject_oversized_batch_tile() {
+        let source = SourceType::Tessellate(TessellateSource::default());
+        let coords = [
+            WorldTileCoords::from((0, 0, ZoomLevel::from(1))),
+            WorldTileCoords::from((1, 0, ZoomLevel::from(1))),
+        ];
+
+        let client = SourceClient::new(
+            HttpSourceClient::new(LargeBatchHttpClient(4096)).with_max_tile_size(1024),
+        );
+        let tiles = client.fetch_batch(&coords, &source).await;
+        assert!(tiles
+            .iter()
+            .all(|tile| tile.as_ref().unwrap_err().is_too_large()));
+
+        // Rejected tiles of a batch are no failed requests either
+        let stats = client.io_stats();
+        assert_eq!(stats.too_large_errors, 2);
+        assert_eq!(stats.fetch_errors, 0);
+    }
+
+    #[test]
+    fn test_split_batch_response() {
+        // Tiles of two, zero and one bytes
+        let response: &[u8] = &[0, 0, 0, 2, 1, 2, 0, 0, 0, 0, 0, 0, 0, 1, 3];
+        let tiles = split_batch_response(response)
+            .into_iter()
+            .map(|tile| tile.unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(tiles, vec![vec![1, 2], vec![], vec![3]]);
+
+        // A tile which is longer than the rest of the response
+        let tiles = split_batch_response(&response[..14]);
+        assert_eq!(tiles.len(), 3);
+        assert_eq!(tiles[0].as_ref().unwrap(), &[1, 2]);
+        assert!(tiles[1].is_ok());
+        assert!(tiles[2].is_err());
+
+        // A length which is cut off
+        let tiles = split_batch_response(&response[..8]);
+        assert_eq!(tiles.len(), 2);
+        assert!(tiles[1].is_err());
+
+        assert!(split_batch_response(&[]).is_empty());
     }
 }
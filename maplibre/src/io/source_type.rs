@@ -1,5 +1,17 @@
 use crate::{coords::WorldTileCoords, style::source::TileAddressingScheme};
 
+/// The size of tiles in pixels, which are requested at the visible zoom level.
+pub const DEFAULT_TILE_SIZE: u32 = 256;
+
+/// The amount of zoom levels by which tiles of `tile_size` pixels are requested below the visible
+/// zoom level. Each doubling of the tile size covers the area of the next lower zoom level.
+pub fn tile_zoom_offset(tile_size: u32) -> u8 {
+    (tile_size as f64 / DEFAULT_TILE_SIZE as f64)
+        .log2()
+        .round()
+        .max(0.0) as u8
+}
+
 /// Represents a source from which the vector tile are fetched.
 #[derive(Clone)]
 pub struct TessellateSource {
@@ -15,8 +27,25 @@ impl TessellateSource {
         }
     }
 
+    /// Creates a source whose tiles are requested from the URL `template` of a style source, see
+    /// [`VectorSource::tiles`](crate::style::source::VectorSource::tiles). `{z}`, `{x}` and `{y}`
+    /// are replaced by the coordinates of a tile.
+    pub fn from_template(template: &str) -> Self {
+        Self::new(template, "")
+    }
+
     pub fn format(&self, coords: &WorldTileCoords) -> String {
         let tile_coords = coords.into_tile(TileAddressingScheme::XYZ).unwrap();
+
+        // Templates of style sources contain all parts of the URL
+        if self.url.contains("{z}") {
+            return self
+                .url
+                .replace("{z}", &tile_coords.z.to_string())
+                .replace("{x}", &tile_coords.x.to_string())
+                .replace("{y}", &tile_coords.y.to_string());
+        }
+
         format!(
             "{url}/{z}/{x}/{y}.{filetype}",
             url = self.url,
@@ -40,6 +69,14 @@ pub struct RasterSource {
     pub url: String,
     pub filetype: String,
     pub key: String,
+    /// The size of the tiles in pixels at a pixel ratio of 1
+    pub tile_size: u32,
+    /// Tiles for a pixel ratio above 1 are requested with a suffix like `@2x` and are
+    /// `pixel_ratio` times larger
+    pub pixel_ratio: u32,
+    /// The timestamp of the frame which replaces `{time}` in the `url`, see
+    /// [`RasterTimeline`](crate::raster::RasterTimeline)
+    pub time: Option<String>,
 }
 
 impl RasterSource {
@@ -48,14 +85,61 @@ impl RasterSource {
             url: url.to_string(),
             filetype: filetype.to_string(),
             key: key.to_string(),
+            tile_size: DEFAULT_TILE_SIZE,
+            pixel_ratio: 1,
+            time: None,
         }
     }
 
+    /// Creates a source whose tiles are requested from the URL `template` of a style source, see
+    /// [`VectorSource::tiles`](crate::style::source::VectorSource::tiles). `{z}`, `{x}` and `{y}`
+    /// are replaced by the coordinates of a tile, `{ratio}` by the suffix of the pixel ratio and
+    /// `{time}` by the timestamp of the frame.
+    pub fn from_template(template: &str) -> Self {
+        Self::new(template, "", "")
+    }
+
+    pub fn with_tile_size(mut self, tile_size: u32) -> Self {
+        self.tile_size = tile_size;
+        self
+    }
+
+    /// Sets the pixel ratio of the display. The ratio is rounded, as tiles are only available for
+    /// whole ratios.
+    pub fn with_pixel_ratio(mut self, pixel_ratio: f64) -> Self {
+        self.pixel_ratio = pixel_ratio.round().max(1.0) as u32;
+        self
+    }
+
+    pub fn with_time(mut self, time: impl Into<String>) -> Self {
+        self.time = Some(time.into());
+        self
+    }
+
     pub fn format(&self, coords: &WorldTileCoords) -> String {
         let tile_coords = coords.into_tile(TileAddressingScheme::XYZ).unwrap();
+        let url = match &self.time {
+            Some(time) => self.url.replace("{time}", time),
+            None => self.url.clone(),
+        };
+        let suffix = if self.pixel_ratio > 1 {
+            format!("@{}x", self.pixel_ratio)
+        } else {
+            String::new()
+        };
+
+        // Templates of style sources contain all parts of the URL
+        if url.contains("{z}") {
+            return url
+                .replace("{z}", &tile_coords.z.to_string())
+                .replace("{x}", &tile_coords.x.to_string())
+                .replace("{y}", &tile_coords.y.to_string())
+                .replace("{ratio}", &suffix);
+        }
+
         format!(
-            "{url}/{z}/{x}/{y}.{filetype}?key={key}",
-            url = self.url,
+            "{url}/{z}/{x}/{y}{suffix}.{filetype}?key={key}",
+            url = url,
             z = tile_coords.z,
             x = tile_coords.x,
             y = tile_coords.y,
@@ -89,4 +173,101 @@ impl SourceType {
             SourceType::Tessellate(tessellate_source) => tessellate_source.format(coords),
         }
     }
+
+    /// The size of the tiles in pixels, which determines the zoom level of the requested tiles.
+    /// See [`tile_zoom_offset`].
+    pub fn tile_size(&self) -> u32 {
+        match self {
+            SourceType::Raster(raster_source) => raster_source.tile_size,
+            SourceType::Tessellate(_) => DEFAULT_TILE_SIZE,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::Deg;
+
+    use super::{tile_zoom_offset, RasterSource, SourceType, TessellateSource};
+    use crate::{
+        coords::{LatLon, WorldCoords, WorldTileCoords, Zoom, ZoomLevel},
+        view_state::ViewState,
+        window::WindowSize,
+    };
+
+    #[test]
+    fn test_tile_size_zoom_offset() {
+        assert_eq!(tile_zoom_offset(256), 0);
+        assert_eq!(tile_zoom_offset(512), 1);
+        assert_eq!(tile_zoom_offset(128), 0);
+
+        let zoom = Zoom::new(10.0);
+        let view_state = ViewState::new(
+            WindowSize::new(800, 600).unwrap(),
+            WorldCoords::from_lat_lon(LatLon::new(48.137154, 11.576124), zoom),
+            zoom,
+            Deg(0.0),
+            Deg(110.0),
+        );
+
+        let source = SourceType::Raster(RasterSource::default().with_tile_size(512));
+        let view_region = view_state
+            .create_view_region_for_tile_size(source.tile_size())
+            .unwrap();
+        assert_eq!(view_region.zoom_level(), ZoomLevel::from(9));
+
+        let source = SourceType::Raster(RasterSource::default());
+        let view_region = view_state
+            .create_view_region_for_tile_size(source.tile_size())
+            .unwrap();
+        assert_eq!(view_region.zoom_level(), ZoomLevel::from(10));
+    }
+
+    #[test]
+    fn test_pixel_ratio_suffix() {
+        let coords = WorldTileCoords::from((1, 2, ZoomLevel::from(3)));
+        let source = RasterSource::new("https://example.com", "png", "key");
+        assert_eq!(
+            source.clone().with_pixel_ratio(2.0).format(&coords),
+            "https://example.com/3/1/2@2x.png?key=key"
+        );
+        assert_eq!(
+            source.with_pixel_ratio(1.25).format(&coords),
+            "https://example.com/3/1/2.png?key=key"
+        );
+    }
+
+    #[test]
+    fn test_time_placeholder() {
+        let coords = WorldTileCoords::from((1, 2, ZoomLevel::from(3)));
+        let source = RasterSource::new("https://example.com/radar/{time}", "png", "key");
+        assert_eq!(
+            source.with_time("2024-06-01T12:00").format(&coords),
+            "https://example.com/radar/2024-06-01T12:00/3/1/2.png?key=key"
+        );
+    }
+
+    #[test]
+    fn test_url_template() {
+        let coords = WorldTileCoords::from((1, 2, ZoomLevel::from(3)));
+        let source =
+            RasterSource::from_template("https://example.com/{time}/{z}/{x}/{y}{ratio}.png");
+        assert_eq!(
+            source
+                .with_pixel_ratio(2.0)
+                .with_time("0910")
+                .format(&coords),
+            "https://example.com/0910/3/1/2@2x.png"
+        );
+    }
+
+    #[test]
+    fn test_vector_url_template() {
+        let coords = WorldTileCoords::from((1, 2, ZoomLevel::from(3)));
+        let source = TessellateSource::from_template("https://example.com/tiles/{z}/{x}/{y}.mvt");
+        assert_eq!(
+            source.format(&coords),
+            "https://example.com/tiles/3/1/2.mvt"
+        );
+    }
 }
@@ -0,0 +1,57 @@
+//! Detects whether fetched tile data is a vector or a raster tile, such that it is processed by
+//! the matching pipeline even if the source is misconfigured.
+
+/// The pipeline which is able to process a tile.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TileFormat {
+    /// Mapbox vector tiles encoded as protobuf
+    Vector,
+    /// PNG, JPEG or WebP images
+    Raster,
+}
+
+impl TileFormat {
+    /// Determines the format from the first bytes of the `data`.
+    pub fn sniff(data: &[u8]) -> Option<Self> {
+        const PNG: &[u8] = &[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+        const JPEG: &[u8] = &[0xFF, 0xD8, 0xFF];
+        const GZIP: &[u8] = &[0x1F, 0x8B];
+        // Field 3 (layers) of a tile with the wire type length-delimited
+        const MVT_LAYER: u8 = 0x1A;
+
+        if data.starts_with(PNG)
+            || data.starts_with(JPEG)
+            || (data.len() >= 12 && &data[0..4] == b"RIFF" && &data[8..12] == b"WEBP")
+        {
+            Some(TileFormat::Raster)
+        } else if data.starts_with(GZIP) || data.first() == Some(&MVT_LAYER) {
+            Some(TileFormat::Vector)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TileFormat;
+
+    const PNG: &[u8] = &[
+        0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A, 0x00, 0x00, 0x00, 0x0D, b'I', b'H', b'D',
+        b'R',
+    ];
+    /// A tile with a single layer named "water"
+    const PBF: &[u8] = &[
+        0x1A, 0x0A, 0x0A, 0x05, b'w', b'a', b't', b'e', b'r', 0x78, 0x02,
+    ];
+
+    #[test]
+    fn test_sniff() {
+        assert_eq!(TileFormat::sniff(PNG), Some(TileFormat::Raster));
+        assert_eq!(TileFormat::sniff(PBF), Some(TileFormat::Vector));
+
+        // Unknown data is processed as declared by the source
+        assert_eq!(TileFormat::sniff(b"<html>"), None);
+        assert_eq!(TileFormat::sniff(&[]), None);
+    }
+}
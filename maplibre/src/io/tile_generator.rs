@@ -0,0 +1,105 @@
+//! Generates tiles on demand instead of fetching them, e.g. from database queries or by rendering
+//! them on the fly.
+
+use std::future::Future;
+
+use async_trait::async_trait;
+
+use crate::{
+    coords::WorldTileCoords,
+    io::{source_client::SourceFetchError, source_type::SourceType},
+};
+
+/// Produces the bytes of the tile at `coords`. The tile is processed like a fetched tile, so
+/// the [`TileTransform`](crate::io::tile_transform::TileTransform) and the maximum tile size
+/// apply to it. The `source_type` allows to generate only the tiles of some sources.
+///
+/// Closures of the form `|coords| async move { ... }` are generators for all sources.
+#[cfg_attr(not(feature = "thread-safe-futures"), async_trait(?Send))]
+#[cfg_attr(feature = "thread-safe-futures", async_trait)]
+pub trait TileGenerator: Send + Sync + 'static {
+    async fn generate(
+        &self,
+        coords: WorldTileCoords,
+        source_type: &SourceType,
+    ) -> Result<Vec<u8>, SourceFetchError>;
+}
+
+#[cfg(feature = "thread-safe-futures")]
+#[async_trait]
+impl<F, Fut> TileGenerator for F
+where
+    F: Fn(WorldTileCoords) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<Vec<u8>, SourceFetchError>> + Send + 'static,
+{
+    async fn generate(
+        &self,
+        coords: WorldTileCoords,
+        _source_type: &SourceType,
+    ) -> Result<Vec<u8>, SourceFetchError> {
+        self(coords).await
+    }
+}
+
+#[cfg(not(feature = "thread-safe-futures"))]
+#[async_trait(?Send)]
+impl<F, Fut> TileGenerator for F
+where
+    F: Fn(WorldTileCoords) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<Vec<u8>, SourceFetchError>> + 'static,
+{
+    async fn generate(
+        &self,
+        coords: WorldTileCoords,
+        _source_type: &SourceType,
+    ) -> Result<Vec<u8>, SourceFetchError> {
+        self(coords).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(all(feature = "headless", feature = "thread-safe-futures"))]
+    #[tokio::test]
+    async fn test_tile_generator() {
+        use std::{
+            sync::{
+                atomic::{AtomicUsize, Ordering},
+                Arc,
+            },
+            time::Duration,
+        };
+
+        use crate::{
+            coords::WorldTileCoords,
+            headless::tests::{kernel_builder, water_map, water_tile},
+            io::source_client::SourceFetchError,
+        };
+
+        // The HTTP client of the kernel is never used, so the tiles can only come from the
+        // generator
+        let generated = Arc::new(AtomicUsize::new(0));
+        let counter = generated.clone();
+        let mut map = water_map(kernel_builder(64).with_tile_generator(
+            move |_coords: WorldTileCoords| {
+                counter.fetch_add(1, Ordering::SeqCst);
+                async { Ok::<_, SourceFetchError>(water_tile()) }
+            },
+        ))
+        .await;
+
+        // The request system of the map fetches the tiles in view through the kernel
+        let mut pixel = [0; 4];
+        for _ in 0..100 {
+            pixel = map.render().unwrap().get_pixel(32, 32).0;
+            if pixel[0] > 200 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        assert!(generated.load(Ordering::SeqCst) > 0);
+        let [red, green, blue, _] = pixel;
+        assert!(red > 200 && green < 50 && blue < 50, "{red} {green} {blue}");
+    }
+}
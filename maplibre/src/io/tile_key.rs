@@ -0,0 +1,57 @@
+//! Derives the keys which identify tiles within caches.
+
+use crate::{coords::WorldTileCoords, io::source_type::SourceType};
+
+/// Derives the key of a tile which is used by caches. A custom key allows to match the layout of
+/// an existing cache or to invalidate all cached tiles by adding a version prefix.
+pub trait TileKey: Send + Sync + 'static {
+    fn key(&self, coords: &WorldTileCoords, source_type: &SourceType) -> String;
+}
+
+/// The key of the root tile, whose quad key is empty.
+pub const ROOT_TILE_KEY: &str = "root";
+
+/// Uses the quad key of the tile as key. The root tile uses [`ROOT_TILE_KEY`] and tiles which have
+/// no quad key use `z/x/y`, such that every tile has a non-empty key.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct QuadKeyTileKey;
+
+impl TileKey for QuadKeyTileKey {
+    fn key(&self, coords: &WorldTileCoords, _source_type: &SourceType) -> String {
+        match coords.build_quad_key().map(|quad_key| quad_key.to_string()) {
+            Some(quad_key) if quad_key.is_empty() => ROOT_TILE_KEY.to_string(),
+            Some(quad_key) => quad_key,
+            None => format!("{}/{}/{}", coords.z, coords.x, coords.y),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{QuadKeyTileKey, TileKey, ROOT_TILE_KEY};
+    use crate::{
+        coords::{WorldTileCoords, ZoomLevel},
+        io::source_type::{SourceType, TessellateSource},
+    };
+
+    /// Prefixes the quad key with a version, such that tiles of previous versions are not used
+    struct VersionedTileKey(u32);
+
+    impl TileKey for VersionedTileKey {
+        fn key(&self, coords: &WorldTileCoords, source_type: &SourceType) -> String {
+            format!("v{}/{}", self.0, QuadKeyTileKey.key(coords, source_type))
+        }
+    }
+
+    #[test]
+    fn test_tile_key() {
+        let source = SourceType::Tessellate(TessellateSource::default());
+        let coords = WorldTileCoords::from((3, 5, ZoomLevel::new(3)));
+
+        assert_eq!(QuadKeyTileKey.key(&coords, &source), "213");
+        assert_eq!(VersionedTileKey(2).key(&coords, &source), "v2/213");
+
+        let root = WorldTileCoords::from((0, 0, ZoomLevel::default()));
+        assert_eq!(QuadKeyTileKey.key(&root, &source), ROOT_TILE_KEY);
+    }
+}
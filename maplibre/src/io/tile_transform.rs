@@ -0,0 +1,81 @@
+//! Transforms fetched tiles before they are processed, e.g. to unwrap custom containers or to
+//! decrypt them.
+
+use crate::io::{source_client::SourceFetchError, source_type::SourceType};
+
+/// Transforms the bytes of a tile after it has been fetched from a source and before it is
+/// processed by the pipeline. The `source_type` allows to transform only the tiles of some
+/// sources. The `key` identifies the tile like in caches, see
+/// [`TileKey`](crate::io::tile_key::TileKey).
+pub trait TileTransform: Send + Sync + 'static {
+    fn transform(
+        &self,
+        source_type: &SourceType,
+        key: &str,
+        data: Vec<u8>,
+    ) -> Result<Vec<u8>, SourceFetchError>;
+}
+
+impl<F> TileTransform for F
+where
+    F: Fn(&SourceType, &str, Vec<u8>) -> Result<Vec<u8>, SourceFetchError> + Send + Sync + 'static,
+{
+    fn transform(
+        &self,
+        source_type: &SourceType,
+        key: &str,
+        data: Vec<u8>,
+    ) -> Result<Vec<u8>, SourceFetchError> {
+        self(source_type, key, data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        coords::{WorldTileCoords, ZoomLevel},
+        io::{
+            source_client::{tests::MockHttpClient, HttpSourceClient, SourceFetchError},
+            source_type::{RasterSource, SourceType, TessellateSource},
+        },
+    };
+
+    /// The XOR key of the tile with the cache `key`
+    fn xor_key(key: &str) -> u8 {
+        key.bytes().fold(0x5A, |xor_key, byte| xor_key ^ byte)
+    }
+
+    /// Serves tiles which are "encrypted" by XOR-ing each byte with the [`xor_key`] of the tile
+    fn encrypted_http_client() -> MockHttpClient {
+        MockHttpClient::new(|_url, key| {
+            Ok(b"tile".iter().map(|byte| byte ^ xor_key(key)).collect())
+        })
+    }
+
+    fn decrypt(
+        source_type: &SourceType,
+        key: &str,
+        data: Vec<u8>,
+    ) -> Result<Vec<u8>, SourceFetchError> {
+        Ok(match source_type {
+            SourceType::Tessellate(_) => data.into_iter().map(|byte| byte ^ xor_key(key)).collect(),
+            SourceType::Raster(_) => data,
+        })
+    }
+
+    #[tokio::test]
+    async fn test_transform() {
+        let client = HttpSourceClient::new(encrypted_http_client()).with_tile_transform(decrypt);
+        let coords = WorldTileCoords::from((3, 5, ZoomLevel::new(3)));
+
+        let vector = SourceType::Tessellate(TessellateSource::default());
+        assert_eq!(client.fetch(&coords, &vector).await.unwrap(), b"tile");
+
+        // Only the tiles of vector sources are encrypted
+        let raster = SourceType::Raster(RasterSource::default());
+        assert_ne!(client.fetch(&coords, &raster).await.unwrap(), b"tile");
+
+        let plain = HttpSourceClient::new(encrypted_http_client());
+        assert_ne!(plain.fetch(&coords, &vector).await.unwrap(), b"tile");
+    }
+}
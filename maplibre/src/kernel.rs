@@ -1,6 +1,17 @@
+use std::{sync::Arc, time::Duration};
+
 use crate::{
     environment::Environment,
-    io::source_client::{HttpSourceClient, SourceClient},
+    io::{
+        apc::AsyncProcedureCall,
+        io_stats::{IoStats, IoStatsCallback},
+        request_observer::RequestObserver,
+        scheduler::Scheduler,
+        source_client::{HttpSourceClient, SourceClient},
+        tile_generator::TileGenerator,
+        tile_key::TileKey,
+        tile_transform::TileTransform,
+    },
 };
 
 /// Holds references to core constructs of maplibre. Based on the compile-time initialization
@@ -34,14 +45,39 @@ impl<E: Environment> Kernel<E> {
     pub fn source_client(&self) -> &SourceClient<E::HttpClient> {
         &self.source_client
     }
+
+    /// The statistics of all tiles which have been requested through the source client.
+    pub fn io_stats(&self) -> IoStats {
+        self.source_client.io_stats()
+    }
+}
+
+impl<E: Environment> Drop for Kernel<E> {
+    fn drop(&mut self) {
+        // Maps which are created and dropped repeatedly would otherwise leave their work running
+        self.apc.shutdown();
+        self.scheduler.shutdown();
+    }
 }
 
 /// A convenient builder for [Kernels](Kernel).
+///
+/// The options of the source client, like the tile key or the tile transform, are passed to the
+/// procedures of the [`AsyncProcedureCall`]. Procedures which run in web workers of the
+/// single-threaded web platform can not receive them and fetch tiles without these options. A
+/// warning is logged when such options are set.
 pub struct KernelBuilder<E: Environment> {
     map_window_config: Option<E::MapWindowConfig>,
     apc: Option<E::AsyncProcedureCall>,
     scheduler: Option<E::Scheduler>,
     http_client: Option<E::HttpClient>,
+    tile_key: Option<Arc<dyn TileKey>>,
+    tile_transform: Option<Arc<dyn TileTransform>>,
+    tile_generator: Option<Arc<dyn TileGenerator>>,
+    request_observer: Option<Arc<dyn RequestObserver>>,
+    max_tile_size: Option<usize>,
+    io_stats_callback: Option<(Duration, IoStatsCallback)>,
+    message_bound: Option<usize>,
 }
 
 impl<E: Environment> Default for KernelBuilder<E> {
@@ -56,6 +92,13 @@ impl<E: Environment> KernelBuilder<E> {
             scheduler: None,
             apc: None,
             http_client: None,
+            tile_key: None,
+            tile_transform: None,
+            tile_generator: None,
+            request_observer: None,
+            max_tile_size: None,
+            io_stats_callback: None,
+            message_bound: None,
             map_window_config: None,
         }
     }
@@ -80,11 +123,115 @@ impl<E: Environment> KernelBuilder<E> {
         self
     }
 
+    /// Derives the cache keys of tiles with the `tile_key`, see [`TileKey`]. Not supported by
+    /// procedures which run in web workers, see [`KernelBuilder`].
+    pub fn with_tile_key(mut self, tile_key: impl TileKey) -> Self {
+        self.tile_key = Some(Arc::new(tile_key));
+        self
+    }
+
+    /// Transforms fetched tiles with the `tile_transform`, see [`TileTransform`]. Not supported
+    /// by procedures which run in web workers, see [`KernelBuilder`].
+    pub fn with_tile_transform(mut self, tile_transform: impl TileTransform) -> Self {
+        self.tile_transform = Some(Arc::new(tile_transform));
+        self
+    }
+
+    /// Produces tiles with the `tile_generator` instead of fetching them, see [`TileGenerator`].
+    /// Not supported by procedures which run in web workers, see [`KernelBuilder`].
+    pub fn with_tile_generator(mut self, tile_generator: impl TileGenerator) -> Self {
+        self.tile_generator = Some(Arc::new(tile_generator));
+        self
+    }
+
+    /// Notifies the `request_observer` about every requested tile, see [`RequestObserver`]. Not
+    /// supported by procedures which run in web workers, see [`KernelBuilder`].
+    pub fn with_request_observer(mut self, request_observer: impl RequestObserver) -> Self {
+        self.request_observer = Some(Arc::new(request_observer));
+        self
+    }
+
+    /// Rejects fetched tiles which are larger than `max_tile_size` bytes, see
+    /// [`HttpSourceClient::with_max_tile_size`]. Not supported by procedures which run in web
+    /// workers, see [`KernelBuilder`].
+    pub fn with_max_tile_size(mut self, max_tile_size: usize) -> Self {
+        self.max_tile_size = Some(max_tile_size);
+        self
+    }
+
+    /// Calls the `callback` with the statistics of the requested tiles at most once per
+    /// `interval`, see [`HttpSourceClient::with_io_stats_callback`]. Not supported by procedures
+    /// which run in web workers, see [`KernelBuilder`].
+    pub fn with_io_stats_callback(
+        mut self,
+        interval: Duration,
+        callback: impl Fn(IoStats) + Send + Sync + 'static,
+    ) -> Self {
+        self.io_stats_callback = Some((interval, Box::new(callback)));
+        self
+    }
+
+    /// Bounds the messages which the procedures of the APC send back to the map, see
+    /// [`AsyncProcedureCall::set_message_bound`].
+    pub fn with_message_bound(mut self, bound: usize) -> Self {
+        self.message_bound = Some(bound);
+        self
+    }
+
     pub fn build(self) -> Kernel<E> {
+        let source_options = [
+            ("tile key", self.tile_key.is_some()),
+            ("tile transform", self.tile_transform.is_some()),
+            ("tile generator", self.tile_generator.is_some()),
+            ("request observer", self.request_observer.is_some()),
+            ("max tile size", self.max_tile_size.is_some()),
+            ("IO stats callback", self.io_stats_callback.is_some()),
+        ];
+
+        let mut http_source_client = HttpSourceClient::new(self.http_client.unwrap()); // TODO: Remove unwrap
+        if let Some(tile_key) = self.tile_key {
+            http_source_client = http_source_client.with_shared_tile_key(tile_key);
+        }
+        if let Some(tile_transform) = self.tile_transform {
+            http_source_client = http_source_client.with_shared_tile_transform(tile_transform);
+        }
+        if let Some(tile_generator) = self.tile_generator {
+            http_source_client = http_source_client.with_shared_tile_generator(tile_generator);
+        }
+        if let Some(request_observer) = self.request_observer {
+            http_source_client = http_source_client.with_shared_request_observer(request_observer);
+        }
+        if let Some(max_tile_size) = self.max_tile_size {
+            http_source_client = http_source_client.with_max_tile_size(max_tile_size);
+        }
+        if let Some((interval, callback)) = self.io_stats_callback {
+            http_source_client = http_source_client.with_io_stats_callback(interval, callback);
+        }
+
+        let source_client = SourceClient::new(http_source_client);
+        // Procedures fetch tiles with the same options and statistics as the kernel
+        let mut apc = self.apc.unwrap(); // TODO: Remove unwrap
+        if !apc.set_source_client(source_client.clone()) {
+            let ignored = source_options
+                .iter()
+                .filter(|(_, is_set)| *is_set)
+                .map(|(option, _)| *option)
+                .collect::<Vec<_>>();
+            if !ignored.is_empty() {
+                log::warn!(
+                    "procedures can not use the source client, ignoring its {}",
+                    ignored.join(", ")
+                );
+            }
+        }
+        if let Some(bound) = self.message_bound {
+            apc.set_message_bound(bound);
+        }
+
         Kernel {
             scheduler: self.scheduler.unwrap(), // TODO: Remove unwrap
-            apc: self.apc.unwrap(),             // TODO: Remove unwrap
-            source_client: SourceClient::new(HttpSourceClient::new(self.http_client.unwrap())), // TODO: Remove unwrap
+            apc,
+            source_client,
             map_window_config: self.map_window_config.unwrap(), // TODO: Remove unwrap
         }
     }
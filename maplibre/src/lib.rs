@@ -28,10 +28,13 @@ pub(crate) mod tessellation;
 
 pub mod context;
 pub mod coords;
+#[cfg(feature = "globe")]
+pub mod globe;
 #[cfg(feature = "headless")]
 pub mod headless;
 pub mod io;
 pub mod platform;
+pub mod projection;
 // TODO: Exposed because of camera
 pub mod render;
 pub mod style;
@@ -55,5 +58,6 @@ pub mod view_state;
 
 // Plugins
 pub mod debug;
+pub mod overlay;
 pub mod raster;
 pub mod vector;
@@ -1,5 +1,6 @@
 use std::rc::Rc;
 
+use cgmath::Matrix4;
 use thiserror::Error;
 
 use crate::{
@@ -12,8 +13,10 @@ use crate::{
         builder::{
             InitializationResult, InitializedRenderer, RendererBuilder, UninitializedRenderer,
         },
+        device_lost::DeviceLostReason,
         error::RenderError,
         graph::RenderGraphError,
+        Renderer,
     },
     schedule::{Schedule, Stage},
     style::Style,
@@ -31,6 +34,9 @@ pub enum MapError {
     RenderGraphInit(RenderGraphError),
     #[error("initializing device failed")]
     DeviceInit(RenderError),
+    /// The device has been lost while rendering, see [`Map::recover_device`]
+    #[error("render device lost: {0:?}")]
+    DeviceLost(DeviceLostReason),
 }
 
 pub enum CurrentMapContext {
@@ -83,8 +89,7 @@ where
         match &mut self.map_context {
             CurrentMapContext::Ready(_) => Err(MapError::RendererAlreadySet),
             CurrentMapContext::Pending {
-                style,
-                renderer_builder,
+                renderer_builder, ..
             } => {
                 let init_result = renderer_builder
                     .clone() // Cloning because we want to be able to build multiple times maybe
@@ -93,48 +98,57 @@ where
                     .await
                     .map_err(MapError::DeviceInit)?;
 
-                let window_size = self.window.size();
-
-                let center = style.center.unwrap_or_default();
-                let initial_zoom = style.zoom.map(Zoom::new).unwrap_or_default();
-                let view_state = ViewState::new(
-                    window_size,
-                    WorldCoords::from_lat_lon(LatLon::new(center[0], center[1]), initial_zoom),
-                    initial_zoom,
-                    cgmath::Deg::<f64>(style.pitch.unwrap_or_default()),
-                    cgmath::Deg(110.0),
-                );
-
-                let mut world = World::default();
-
                 match init_result {
-                    InitializationResult::Initialized(InitializedRenderer {
-                        mut renderer, ..
-                    }) => {
-                        for plugin in &self.plugins {
-                            plugin.build(
-                                &mut self.schedule,
-                                self.kernel.clone(),
-                                &mut world,
-                                &mut renderer.render_graph,
-                            );
-                        }
-
-                        self.map_context = CurrentMapContext::Ready(MapContext {
-                            world,
-                            view_state,
-                            style: std::mem::take(style),
-                            renderer,
-                        });
+                    InitializationResult::Initialized(InitializedRenderer { renderer, .. }) => {
+                        self.initialize_with_renderer(renderer)
                     }
-                    InitializationResult::Uninitialized(UninitializedRenderer { .. }) => {}
+                    InitializationResult::Uninitialized(UninitializedRenderer { .. }) => Ok(()),
                     _ => panic!("Rendering context gone"),
-                };
-                Ok(())
+                }
             }
         }
     }
 
+    /// Uses an existing `renderer` instead of creating one for the window. This allows to render
+    /// the map with the device of an application which already uses wgpu, see
+    /// [`Renderer::from_device`].
+    pub fn initialize_with_renderer(&mut self, mut renderer: Renderer) -> Result<(), MapError> {
+        let CurrentMapContext::Pending { style, .. } = &mut self.map_context else {
+            return Err(MapError::RendererAlreadySet);
+        };
+
+        let window_size = self.window.size();
+
+        let center = style.center.unwrap_or_default();
+        let initial_zoom = style.zoom.map(Zoom::new).unwrap_or_default();
+        let view_state = ViewState::new(
+            window_size,
+            WorldCoords::from_lat_lon(LatLon::new(center[0], center[1]), initial_zoom),
+            initial_zoom,
+            cgmath::Deg::<f64>(style.pitch.unwrap_or_default()),
+            cgmath::Deg(110.0),
+        );
+
+        let mut world = World::default();
+
+        for plugin in &self.plugins {
+            plugin.build(
+                &mut self.schedule,
+                self.kernel.clone(),
+                &mut world,
+                &mut renderer.render_graph,
+            );
+        }
+
+        self.map_context = CurrentMapContext::Ready(MapContext {
+            world,
+            view_state,
+            style: std::mem::take(style),
+            renderer,
+        });
+        Ok(())
+    }
+
     pub fn window_mut(&mut self) -> &mut <E::MapWindowConfig as MapWindowConfig>::MapWindow {
         &mut self.window
     }
@@ -154,12 +168,68 @@ where
         match &mut self.map_context {
             CurrentMapContext::Ready(map_context) => {
                 self.schedule.run(map_context);
-                Ok(())
+                map_context.view_state.notify_bearing_changed();
+
+                match map_context.renderer.device_lost.take() {
+                    Some(reason) => Err(MapError::DeviceLost(reason)),
+                    None => Ok(()),
+                }
             }
             CurrentMapContext::Pending { .. } => Err(MapError::RendererAlreadySet),
         }
     }
 
+    /// Renders a single frame with the `view_projection` instead of the camera, e.g. to embed the
+    /// map into an AR/VR scene, see [`ViewState::set_view_projection`]. Frames rendered with
+    /// [`Map::run_schedule`] use the camera.
+    pub fn render_with_view_projection(
+        &mut self,
+        view_projection: Matrix4<f64>,
+    ) -> Result<(), MapError> {
+        self.context_mut()?
+            .view_state
+            .set_view_projection(Some(view_projection));
+
+        let result = self.run_schedule();
+
+        if let Ok(map_context) = self.context_mut() {
+            map_context.view_state.set_view_projection(None);
+        }
+
+        result
+    }
+
+    /// Tries once to create the device and the surface again after the device has been lost.
+    /// The plugins build their systems and render graph again, which resets the resources of the
+    /// lost device. The rest of the world, e.g. overlays, feature data, pinned tiles and request
+    /// settings, is kept and loaded tiles are uploaded again. If the device can not be created,
+    /// the device lost callback of the renderer is invoked.
+    pub async fn recover_device(&mut self, reason: DeviceLostReason) -> Result<(), MapError> {
+        let CurrentMapContext::Ready(map_context) = &mut self.map_context else {
+            return Err(MapError::RendererAlreadySet);
+        };
+
+        log::warn!("render device lost ({reason:?}), creating it again");
+
+        if let Err(e) = map_context.renderer.recreate(&self.window).await {
+            map_context.renderer.device_lost.notify(reason);
+            return Err(MapError::DeviceInit(e));
+        }
+
+        self.schedule = Schedule::default();
+
+        for plugin in &self.plugins {
+            plugin.build(
+                &mut self.schedule,
+                self.kernel.clone(),
+                &mut map_context.world,
+                &mut map_context.renderer.render_graph,
+            );
+        }
+
+        Ok(())
+    }
+
     pub fn context(&self) -> Result<&MapContext, MapError> {
         match &self.map_context {
             CurrentMapContext::Ready(map_context) => Ok(map_context),
@@ -0,0 +1,265 @@
+//! Arrows indicate the direction of the lines of an overlay. They are repeated along the lines
+//! with a fixed spacing on the screen and point along the line.
+
+use csscolorparser::Color;
+use geozero::{error::GeozeroError, FeatureProcessor, GeomProcessor, PropertyProcessor};
+use lyon::tessellation::VertexBuffers;
+
+use crate::{
+    coords::{WorldTileCoords, ZoomLevel, EXTENT},
+    render::ShaderVertex,
+    tessellation::{IndexDataType, OverAlignedVertexBuffer},
+};
+
+/// Describes the arrows which are drawn along the lines of an overlay.
+#[derive(Clone, Debug)]
+pub struct LineArrows {
+    pub color: Color,
+    /// The distance between two arrows along a line in pixels
+    pub spacing: f32,
+    /// The length and width of an arrow in pixels
+    pub size: f32,
+}
+
+impl Default for LineArrows {
+    fn default() -> Self {
+        Self {
+            color: Color::new(1.0, 1.0, 1.0, 1.0),
+            spacing: 64.0,
+            size: 10.0,
+        }
+    }
+}
+
+/// Collects the coordinates of all line strings of a geometry. Rings of polygons are skipped.
+#[derive(Default)]
+pub(crate) struct LineCollector {
+    pub lines: Vec<Vec<[f64; 2]>>,
+    in_line: bool,
+    polygon_depth: usize,
+}
+
+impl GeomProcessor for LineCollector {
+    fn xy(&mut self, x: f64, y: f64, _idx: usize) -> Result<(), GeozeroError> {
+        if self.in_line {
+            if let Some(line) = self.lines.last_mut() {
+                line.push([x, y]);
+            }
+        }
+        Ok(())
+    }
+
+    fn linestring_begin(
+        &mut self,
+        _tagged: bool,
+        size: usize,
+        _idx: usize,
+    ) -> Result<(), GeozeroError> {
+        if self.polygon_depth == 0 {
+            self.in_line = true;
+            self.lines.push(Vec::with_capacity(size));
+        }
+        Ok(())
+    }
+
+    fn linestring_end(&mut self, _tagged: bool, _idx: usize) -> Result<(), GeozeroError> {
+        self.in_line = false;
+        Ok(())
+    }
+
+    fn polygon_begin(
+        &mut self,
+        _tagged: bool,
+        _size: usize,
+        _idx: usize,
+    ) -> Result<(), GeozeroError> {
+        self.polygon_depth += 1;
+        Ok(())
+    }
+
+    fn polygon_end(&mut self, _tagged: bool, _idx: usize) -> Result<(), GeozeroError> {
+        self.polygon_depth -= 1;
+        Ok(())
+    }
+}
+
+impl PropertyProcessor for LineCollector {}
+
+impl FeatureProcessor for LineCollector {}
+
+/// A line of an overlay within the tile `0/0/0`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct OverlayLine {
+    pub points: Vec<[f64; 2]>,
+    /// The distance of each point from the start of the line
+    pub distances: Vec<f64>,
+}
+
+impl OverlayLine {
+    pub fn new(mut points: Vec<[f64; 2]>) -> Self {
+        // Segments without a length have no direction
+        points.dedup();
+
+        let mut length = 0.0;
+        let mut distances = vec![length; points.len().min(1)];
+        for segment in points.windows(2) {
+            let ([x0, y0], [x1, y1]) = (segment[0], segment[1]);
+            length += (x1 - x0).hypot(y1 - y0);
+            distances.push(length);
+        }
+
+        Self { points, distances }
+    }
+
+    pub fn length(&self) -> f64 {
+        self.distances.last().copied().unwrap_or(0.0)
+    }
+
+    /// The point at `distance` along the line, together with the direction of the segment on
+    /// which it lies as unit vector.
+    pub(crate) fn point_at(&self, distance: f64) -> ([f64; 2], [f64; 2]) {
+        let end = self
+            .distances
+            .partition_point(|d| *d < distance)
+            .clamp(1, self.points.len() - 1);
+        let ([x0, y0], [x1, y1]) = (self.points[end - 1], self.points[end]);
+        let length = self.distances[end] - self.distances[end - 1];
+
+        let (dx, dy) = ((x1 - x0) / length, (y1 - y0) / length);
+        let along = distance - self.distances[end - 1];
+        ([x0 + dx * along, y0 + dy * along], [dx, dy])
+    }
+}
+
+/// The tile at the `zoom_level` which contains the start of the `lines`. Arrows are placed
+/// relative to it, see [`place_arrows`].
+pub(crate) fn arrow_origin(lines: &[OverlayLine], zoom_level: ZoomLevel) -> WorldTileCoords {
+    let [x, y] = lines
+        .iter()
+        .find_map(|line| line.points.first())
+        .copied()
+        .unwrap_or_default();
+    let scale = 2f64.powi(u8::from(zoom_level) as i32) / EXTENT;
+
+    WorldTileCoords::from((
+        (x * scale).floor() as i32,
+        (y * scale).floor() as i32,
+        zoom_level,
+    ))
+}
+
+/// Places arrows along the `lines`, starting half of the `spacing` after the start of each line.
+/// Each arrow is a triangle with the length and width `size` whose tip points along the line. The
+/// `spacing` and the `size` are in the units of the tile `0/0/0`, like the lines.
+///
+/// The vertices are relative to the tile at `origin`. Within the tile `0/0/0`, `f32` can not
+/// tell apart the vertices of an arrow at high zoom levels.
+pub(crate) fn place_arrows(
+    lines: &[OverlayLine],
+    spacing: f64,
+    size: f64,
+    origin: WorldTileCoords,
+) -> OverAlignedVertexBuffer<ShaderVertex, IndexDataType> {
+    let mut buffer = VertexBuffers::<ShaderVertex, IndexDataType>::new();
+
+    if spacing <= 0.0 || size <= 0.0 {
+        return buffer.into();
+    }
+
+    let scale = 2f64.powi(u8::from(origin.z) as i32);
+    let (origin_x, origin_y) = (origin.x as f64 * EXTENT, origin.y as f64 * EXTENT);
+    let vertex = |x: f64, y: f64| {
+        ShaderVertex::new(
+            [(x * scale - origin_x) as f32, (y * scale - origin_y) as f32],
+            [0.0, 0.0],
+        )
+    };
+
+    let half = size / 2.0;
+    for line in lines.iter().filter(|line| line.points.len() >= 2) {
+        let mut distance = spacing / 2.0;
+
+        while distance < line.length() {
+            let ([x, y], [dx, dy]) = line.point_at(distance);
+
+            let first = buffer.vertices.len() as IndexDataType;
+            buffer.vertices.extend([
+                vertex(x + dx * half, y + dy * half),
+                vertex(x - dx * half - dy * half, y - dy * half + dx * half),
+                vertex(x - dx * half + dy * half, y - dy * half - dx * half),
+            ]);
+            buffer.indices.extend([first, first + 1, first + 2]);
+
+            distance += spacing;
+        }
+    }
+
+    buffer.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{arrow_origin, place_arrows, OverlayLine};
+    use crate::coords::{WorldTileCoords, ZoomLevel, EXTENT};
+
+    #[test]
+    fn test_place_arrows() {
+        // An L-shaped line with a length of 100
+        let lines = vec![OverlayLine::new(vec![
+            [0.0, 0.0],
+            [50.0, 0.0],
+            [50.0, 0.0],
+            [50.0, 50.0],
+        ])];
+        assert_eq!(lines[0].distances, vec![0.0, 50.0, 100.0]);
+
+        let origin = WorldTileCoords::from((0, 0, ZoomLevel::default()));
+        let arrows = place_arrows(&lines, 20.0, 4.0, origin);
+
+        assert_eq!(arrows.usable_indices, 5 * 3);
+
+        // The tips of the arrows follow the direction of the line
+        let tips = arrows
+            .buffer
+            .vertices
+            .iter()
+            .step_by(3)
+            .map(|vertex| vertex.position)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            tips,
+            vec![
+                [12.0, 0.0],
+                [32.0, 0.0],
+                [50.0, 2.0],
+                [50.0, 22.0],
+                [50.0, 42.0]
+            ]
+        );
+    }
+
+    #[test]
+    fn test_place_arrows_at_high_zoom() {
+        // A line of 11 pixels at zoom level 20 near the center of the world
+        let zoom_level = ZoomLevel::from(20);
+        let pixel = EXTENT / (512.0 * 2f64.powi(20));
+        let center = EXTENT / 2.0 + 0.123;
+        let lines = vec![OverlayLine::new(vec![
+            [center, center],
+            [center + 11.0 * pixel, center],
+        ])];
+
+        let origin = arrow_origin(&lines, zoom_level);
+        assert_eq!(origin.z, zoom_level);
+        let arrows = place_arrows(&lines, 4.0 * pixel, 2.0 * pixel, origin);
+        assert_eq!(arrows.usable_indices, 3 * 3);
+
+        // The arrows are 2 pixels long, which are 16 units of the tiles at the zoom level
+        let vertices = &arrows.buffer.vertices;
+        let length = vertices[0].position[0] - vertices[1].position[0];
+        assert!((length - 16.0).abs() < 1e-2, "{length}");
+        let spacing = vertices[3].position[0] - vertices[0].position[0];
+        assert!((spacing - 32.0).abs() < 1e-2, "{spacing}");
+        assert!(vertices[0].position[0] >= 0.0 && vertices[0].position[0] < EXTENT as f32);
+    }
+}
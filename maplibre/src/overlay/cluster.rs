@@ -0,0 +1,247 @@
+//! Clustering groups nearby points into a single marker with a count, such that dense points like
+//! points of interest stay readable. The clusters are recomputed whenever the zoom level changes.
+
+use std::collections::{BTreeMap, HashMap};
+
+use crate::{
+    context::MapContext,
+    coords::{LatLon, WorldCoords, Zoom, ZoomLevel},
+    overlay::marker::{Marker, MarkerStyle, Markers},
+    projection::Projection,
+    tcs::world::World,
+};
+
+/// A group of nearby points.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cluster {
+    /// The mean position of the points of the cluster
+    pub position: LatLon,
+    /// The amount of points in the cluster
+    pub point_count: usize,
+}
+
+/// Groups the `points` on a grid whose cells are `radius` pixels large at the `zoom_level`. The
+/// points within the same cell form a cluster. Like [`TILE_SIZE`](crate::coords::TILE_SIZE),
+/// pixels correspond to world coordinates at the zoom level.
+pub fn cluster_points(
+    points: &[LatLon],
+    zoom_level: ZoomLevel,
+    radius: f64,
+    projection: &dyn Projection,
+) -> Vec<Cluster> {
+    let zoom = Zoom::new(f64::from(u8::from(zoom_level)));
+    let radius = radius.max(f64::EPSILON);
+
+    // Sums of the world coordinates and the amount of points in each cell
+    let mut cells: BTreeMap<(i64, i64), (f64, f64, usize)> = BTreeMap::new();
+    for point in points {
+        let world = projection.project(*point, zoom);
+        let cell = (
+            (world.x / radius).floor() as i64,
+            (world.y / radius).floor() as i64,
+        );
+
+        let (x, y, count) = cells.entry(cell).or_default();
+        *x += world.x;
+        *y += world.y;
+        *count += 1;
+    }
+
+    cells
+        .into_values()
+        .map(|(x, y, count)| {
+            let center = WorldCoords::at_ground(x / count as f64, y / count as f64);
+            Cluster {
+                position: projection.unproject(center, zoom),
+                point_count: count,
+            }
+        })
+        .collect()
+}
+
+/// Points which are drawn as clustered [markers](Marker).
+struct ClusteredPoints {
+    points: Vec<LatLon>,
+    radius: f64,
+    style: MarkerStyle,
+    /// The zoom level at which the `clusters` have been computed
+    zoom_level: Option<ZoomLevel>,
+    clusters: Vec<Cluster>,
+}
+
+/// All clustered points of the map. The clusters of the points with the id `id` are drawn as
+/// markers with the ids `id/0`, `id/1` and so on.
+#[derive(Default)]
+pub struct PointClusters {
+    sources: HashMap<String, ClusteredPoints>,
+}
+
+impl PointClusters {
+    /// The clusters of the points with the `id` at the current zoom level.
+    pub fn clusters(&self, id: &str) -> Option<&[Cluster]> {
+        self.sources
+            .get(id)
+            .map(|source| source.clusters.as_slice())
+    }
+
+    /// Recomputes the clusters of points whose clusters have been computed for a different zoom
+    /// level and replaces their markers.
+    pub fn update(
+        &mut self,
+        zoom_level: ZoomLevel,
+        projection: &dyn Projection,
+        markers: &mut Markers,
+    ) {
+        for (id, source) in &mut self.sources {
+            if source.zoom_level == Some(zoom_level) {
+                continue;
+            }
+
+            remove_markers(id, source.clusters.len(), markers);
+
+            source.clusters = cluster_points(&source.points, zoom_level, source.radius, projection);
+            source.zoom_level = Some(zoom_level);
+
+            for (i, cluster) in source.clusters.iter().enumerate() {
+                markers.insert(Marker {
+                    id: format!("{id}/{i}"),
+                    position: cluster.position,
+                    style: source.style.clone(),
+                });
+            }
+        }
+    }
+}
+
+fn remove_markers(id: &str, clusters: usize, markers: &mut Markers) {
+    for i in 0..clusters {
+        markers.remove(&format!("{id}/{i}"));
+    }
+}
+
+impl World {
+    /// Draws the `points` as markers with the `style`, whereby points within `radius` pixels of
+    /// each other are clustered into a single marker. Existing clustered points with the same `id`
+    /// are replaced. See [`cluster_points`].
+    pub fn add_clustered_points(
+        &mut self,
+        id: &str,
+        points: Vec<LatLon>,
+        radius: f64,
+        style: MarkerStyle,
+    ) {
+        self.remove_clustered_points(id);
+
+        self.resources
+            .get_or_init_mut::<PointClusters>()
+            .sources
+            .insert(
+                id.to_string(),
+                ClusteredPoints {
+                    points,
+                    radius,
+                    style,
+                    zoom_level: None,
+                    clusters: Vec::new(),
+                },
+            );
+    }
+
+    /// Removes the clustered points with the `id` and their markers. Returns whether clustered
+    /// points have been removed.
+    pub fn remove_clustered_points(&mut self, id: &str) -> bool {
+        let Some(source) = self
+            .resources
+            .get_mut::<PointClusters>()
+            .and_then(|clusters| clusters.sources.remove(id)) else { return false; };
+
+        if let Some(markers) = self.resources.get_mut::<Markers>() {
+            remove_markers(id, source.clusters.len(), markers);
+        }
+        true
+    }
+
+    /// The clusters of the points with the `id` at the current zoom level.
+    pub fn clusters(&self, id: &str) -> Option<&[Cluster]> {
+        self.resources
+            .get::<PointClusters>()
+            .and_then(|clusters| clusters.clusters(id))
+    }
+}
+
+pub fn cluster_system(
+    MapContext {
+        world, view_state, ..
+    }: &mut MapContext,
+) {
+    let Some((clusters, markers)) = world
+        .resources
+        .query_mut::<(&mut PointClusters, &mut Markers)>() else { return; };
+
+    clusters.update(view_state.visible_level(), view_state.projection(), markers);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{cluster_points, PointClusters};
+    use crate::{
+        coords::{LatLon, ZoomLevel},
+        overlay::marker::{MarkerStyle, Markers},
+        projection::WebMercator,
+        tcs::world::World,
+    };
+
+    fn points() -> Vec<LatLon> {
+        vec![
+            // Three points in the center of Munich
+            LatLon::new(48.1372, 11.5755),
+            LatLon::new(48.1374, 11.5761),
+            LatLon::new(48.1370, 11.5758),
+            // Berlin
+            LatLon::new(52.5200, 13.4050),
+        ]
+    }
+
+    #[test]
+    fn test_cluster_points() {
+        let clusters = cluster_points(&points(), ZoomLevel::from(10), 50.0, &WebMercator);
+        let mut counts = clusters
+            .iter()
+            .map(|cluster| cluster.point_count)
+            .collect::<Vec<_>>();
+        counts.sort();
+        assert_eq!(counts, vec![1, 3]);
+
+        let munich = clusters
+            .iter()
+            .find(|cluster| cluster.point_count == 3)
+            .unwrap();
+        assert!((munich.position.latitude - 48.1372).abs() < 1e-3);
+        assert!((munich.position.longitude - 11.5758).abs() < 1e-3);
+
+        // At a high zoom level the points in Munich are further apart than the radius
+        let clusters = cluster_points(&points(), ZoomLevel::from(20), 50.0, &WebMercator);
+        assert_eq!(clusters.len(), 4);
+        assert!(clusters.iter().all(|cluster| cluster.point_count == 1));
+    }
+
+    #[test]
+    fn test_clusters_as_markers() {
+        let mut world = World::default();
+        world.add_clustered_points("pois", points(), 50.0, MarkerStyle::default());
+
+        let mut markers = Markers::default();
+        let clusters = world.resources.get_mut::<PointClusters>().unwrap();
+        clusters.update(ZoomLevel::from(10), &WebMercator, &mut markers);
+        assert_eq!(markers.len(), 2);
+
+        clusters.update(ZoomLevel::from(20), &WebMercator, &mut markers);
+        assert_eq!(markers.len(), 4);
+        assert!(markers.get("pois/3").is_some());
+
+        clusters.update(ZoomLevel::from(10), &WebMercator, &mut markers);
+        assert_eq!(markers.len(), 2);
+        assert!(markers.get("pois/2").is_none());
+        assert_eq!(world.clusters("pois").unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,338 @@
+//! Collision detection for point labels. Labels are placed in the order of their rank, whereby a
+//! label is skipped if its box on the screen overlaps a label which has already been placed. The
+//! placement is recomputed every frame, because the boxes move with the camera.
+
+use std::collections::HashMap;
+
+use crate::{context::MapContext, coords::LatLon, tcs::world::World, view_state::ViewState};
+
+/// A label which is anchored at a geographic location. Its box on the screen is centered on the
+/// position.
+#[derive(Clone, Debug)]
+pub struct PointLabel {
+    pub id: String,
+    pub position: LatLon,
+    /// The width of the label in pixels
+    pub width: f64,
+    /// The height of the label in pixels
+    pub height: f64,
+    /// Labels with a lower rank are placed first. Labels with the same rank are placed in the
+    /// order in which they have been added.
+    pub rank: i32,
+}
+
+/// Configures how labels collide with each other.
+#[derive(Clone, Debug)]
+pub struct LabelCollision {
+    /// Whether overlapping labels are skipped. If disabled, all labels on the screen are placed.
+    pub enabled: bool,
+    /// The space in pixels which is kept free around every placed label
+    pub padding: f64,
+}
+
+impl Default for LabelCollision {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            padding: 2.0,
+        }
+    }
+}
+
+/// An axis-aligned box in window coordinates.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ScreenBox {
+    pub min_x: f64,
+    pub min_y: f64,
+    pub max_x: f64,
+    pub max_y: f64,
+}
+
+impl ScreenBox {
+    pub fn intersects(&self, other: &ScreenBox) -> bool {
+        self.min_x < other.max_x
+            && other.min_x < self.max_x
+            && self.min_y < other.max_y
+            && other.min_y < self.max_y
+    }
+}
+
+/// A grid over the screen which stores the boxes of the placed labels. Every cell knows the boxes
+/// which overlap it, such that only nearby boxes are tested for collisions.
+pub struct CollisionGrid {
+    cell_size: f64,
+    cells: HashMap<(i64, i64), Vec<usize>>,
+    boxes: Vec<ScreenBox>,
+}
+
+impl CollisionGrid {
+    /// The size of the cells in pixels, if not specified otherwise
+    const DEFAULT_CELL_SIZE: f64 = 64.0;
+
+    pub fn new(cell_size: f64) -> Self {
+        Self {
+            cell_size: cell_size.max(1.0),
+            cells: HashMap::new(),
+            boxes: Vec::new(),
+        }
+    }
+
+    /// Whether the `bbox` overlaps any box of the grid.
+    pub fn collides(&self, bbox: &ScreenBox) -> bool {
+        self.covered_cells(bbox).any(|cell| {
+            self.cells.get(&cell).map_or(false, |indices| {
+                indices.iter().any(|i| self.boxes[*i].intersects(bbox))
+            })
+        })
+    }
+
+    pub fn insert(&mut self, bbox: ScreenBox) {
+        let index = self.boxes.len();
+        for cell in self.covered_cells(&bbox) {
+            self.cells.entry(cell).or_default().push(index);
+        }
+        self.boxes.push(bbox);
+    }
+
+    /// Inserts the `bbox` if it does not overlap any box of the grid. Returns whether the box has
+    /// been inserted.
+    pub fn insert_if_free(&mut self, bbox: ScreenBox) -> bool {
+        if self.collides(&bbox) {
+            return false;
+        }
+        self.insert(bbox);
+        true
+    }
+
+    fn covered_cells(&self, bbox: &ScreenBox) -> impl Iterator<Item = (i64, i64)> {
+        let min_x = (bbox.min_x / self.cell_size).floor() as i64;
+        let min_y = (bbox.min_y / self.cell_size).floor() as i64;
+        let max_x = (bbox.max_x / self.cell_size).floor() as i64;
+        let max_y = (bbox.max_y / self.cell_size).floor() as i64;
+
+        (min_x..=max_x).flat_map(move |x| (min_y..=max_y).map(move |y| (x, y)))
+    }
+}
+
+impl Default for CollisionGrid {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_CELL_SIZE)
+    }
+}
+
+/// All point labels of the map together with the labels which have been placed for the current
+/// camera.
+#[derive(Default)]
+pub struct PointLabels {
+    labels: Vec<PointLabel>,
+    collision: LabelCollision,
+    /// The ids of the placed labels in the order in which they have been placed
+    placed: Vec<String>,
+}
+
+impl PointLabels {
+    /// Adds the `label`. An existing label with the same id is replaced.
+    pub fn insert(&mut self, label: PointLabel) {
+        self.remove(&label.id);
+        self.labels.push(label);
+    }
+
+    pub fn remove(&mut self, id: &str) -> Option<PointLabel> {
+        let index = self.labels.iter().position(|label| label.id == id)?;
+        Some(self.labels.remove(index))
+    }
+
+    pub fn get(&self, id: &str) -> Option<&PointLabel> {
+        self.labels.iter().find(|label| label.id == id)
+    }
+
+    /// The ids of the labels which have been placed for the current camera.
+    pub fn placed(&self) -> &[String] {
+        &self.placed
+    }
+
+    /// Places the labels in the order of their rank. Labels outside of the window or behind the
+    /// camera are not placed.
+    pub fn place(&mut self, view_state: &ViewState) {
+        let (width, height) = view_state.camera().size();
+        let window = ScreenBox {
+            min_x: 0.0,
+            min_y: 0.0,
+            max_x: width,
+            max_y: height,
+        };
+
+        let mut order = (0..self.labels.len()).collect::<Vec<_>>();
+        order.sort_by_key(|i| self.labels[*i].rank);
+
+        let mut grid = CollisionGrid::default();
+        self.placed.clear();
+
+        for i in order {
+            let label = &self.labels[i];
+            let Some((x, y)) = view_state.lat_lon_to_screen(label.position) else { continue; };
+
+            let half_width = label.width / 2.0;
+            let half_height = label.height / 2.0;
+            let bbox = ScreenBox {
+                min_x: x - half_width,
+                min_y: y - half_height,
+                max_x: x + half_width,
+                max_y: y + half_height,
+            };
+
+            if !bbox.intersects(&window) {
+                continue;
+            }
+
+            if self.collision.enabled {
+                let padding = self.collision.padding;
+                let padded = ScreenBox {
+                    min_x: bbox.min_x - padding,
+                    min_y: bbox.min_y - padding,
+                    max_x: bbox.max_x + padding,
+                    max_y: bbox.max_y + padding,
+                };
+                if !grid.insert_if_free(padded) {
+                    continue;
+                }
+            }
+
+            self.placed.push(label.id.clone());
+        }
+    }
+}
+
+impl World {
+    /// Adds a point label. An existing label with the same id is replaced. The label is only
+    /// placed if it does not collide with a label of a lower rank. See [`PointLabels::place`].
+    pub fn add_point_label(&mut self, label: PointLabel) {
+        self.resources
+            .get_or_init_mut::<PointLabels>()
+            .insert(label);
+    }
+
+    /// Removes the point label with the `id`. Returns whether a label has been removed.
+    pub fn remove_point_label(&mut self, id: &str) -> bool {
+        self.resources
+            .get_mut::<PointLabels>()
+            .and_then(|labels| labels.remove(id))
+            .is_some()
+    }
+
+    /// Configures how point labels collide with each other.
+    pub fn set_label_collision(&mut self, collision: LabelCollision) {
+        self.resources.get_or_init_mut::<PointLabels>().collision = collision;
+    }
+
+    /// The ids of the point labels which have been placed in the last frame.
+    pub fn placed_labels(&self) -> &[String] {
+        self.resources
+            .get::<PointLabels>()
+            .map(PointLabels::placed)
+            .unwrap_or(&[])
+    }
+}
+
+pub fn label_placement_system(
+    MapContext {
+        world, view_state, ..
+    }: &mut MapContext,
+) {
+    let Some(labels) = world.resources.get_mut::<PointLabels>() else { return; };
+
+    labels.place(view_state);
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::Deg;
+
+    use super::{CollisionGrid, LabelCollision, PointLabel, PointLabels, ScreenBox};
+    use crate::{
+        coords::{LatLon, WorldCoords, Zoom},
+        tcs::world::World,
+        view_state::ViewState,
+        window::WindowSize,
+    };
+
+    fn view_state(center: LatLon) -> ViewState {
+        let zoom = Zoom::new(10.0);
+        ViewState::new(
+            WindowSize::new(800, 600).unwrap(),
+            WorldCoords::from_lat_lon(center, zoom),
+            zoom,
+            Deg(0.0),
+            Deg(110.0),
+        )
+    }
+
+    fn label(id: &str, position: LatLon, rank: i32) -> PointLabel {
+        PointLabel {
+            id: id.to_string(),
+            position,
+            width: 80.0,
+            height: 20.0,
+            rank,
+        }
+    }
+
+    #[test]
+    fn test_collision_grid() {
+        let mut grid = CollisionGrid::new(10.0);
+        let bbox = ScreenBox {
+            min_x: 5.0,
+            min_y: 5.0,
+            max_x: 25.0,
+            max_y: 15.0,
+        };
+        assert!(grid.insert_if_free(bbox));
+        assert!(!grid.insert_if_free(ScreenBox {
+            min_x: 20.0,
+            max_x: 30.0,
+            ..bbox
+        }));
+        // Touching boxes do not overlap
+        assert!(grid.insert_if_free(ScreenBox {
+            min_x: 25.0,
+            max_x: 35.0,
+            ..bbox
+        }));
+    }
+
+    #[test]
+    fn test_close_labels_collide() {
+        let munich = LatLon::new(48.137154, 11.576124);
+        let nearby = LatLon::new(48.137254, 11.576324);
+        let view_state = view_state(munich);
+
+        let mut world = World::default();
+        world.add_point_label(label("nearby", nearby, 1));
+        world.add_point_label(label("munich", munich, 0));
+
+        let labels = world.resources.get_mut::<PointLabels>().unwrap();
+        labels.place(&view_state);
+        assert_eq!(world.placed_labels(), ["munich".to_string()]);
+
+        world.set_label_collision(LabelCollision {
+            enabled: false,
+            ..LabelCollision::default()
+        });
+        let labels = world.resources.get_mut::<PointLabels>().unwrap();
+        labels.place(&view_state);
+        assert_eq!(world.placed_labels().len(), 2);
+    }
+
+    #[test]
+    fn test_labels_outside_of_window() {
+        let munich = LatLon::new(48.137154, 11.576124);
+        let berlin = LatLon::new(52.520008, 13.404954);
+
+        let mut labels = PointLabels::default();
+        labels.insert(label("munich", munich, 0));
+        labels.insert(label("berlin", berlin, 0));
+        labels.place(&view_state(munich));
+
+        assert_eq!(labels.placed(), ["munich".to_string()]);
+    }
+}
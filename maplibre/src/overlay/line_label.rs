@@ -0,0 +1,516 @@
+//! Labels along lines, like the names of roads and rivers. The glyphs of a label follow the line
+//! and are rotated along the segment on which they are placed. Labels are flipped such that they
+//! are never upside down. Like for [point labels](super::collision), the placement is recomputed
+//! every frame, because the line moves with the camera.
+//!
+//! Each line has a single label. Besides the labels which are added to the [`World`], the line
+//! features of line layers with a `text-field` are labeled. The glyphs are drawn from the
+//! [`GlyphAtlas`], see [`World::set_glyph_atlas`]. No label is placed until the atlas is set.
+
+use std::{
+    collections::{HashMap, HashSet},
+    f64::consts::{FRAC_PI_4, PI, TAU},
+};
+
+use cgmath::Vector4;
+use csscolorparser::Color;
+
+use crate::{
+    context::MapContext,
+    coords::{LatLon, WorldCoords},
+    io::geometry_index::{ExactGeometry, GeometryIndex},
+    overlay::arrow::OverlayLine,
+    render::{color_space::ColorSpace, shaders::ShaderGlyph},
+    style::{
+        glyph::{GlyphAtlas, GLYPH_BORDER, GLYPH_SIZE},
+        layer::LayerPaint,
+        Style,
+    },
+    tcs::world::World,
+    view_state::ViewState,
+};
+
+/// The maximum change of the angle between two adjacent glyphs. Labels are not placed across
+/// sharper bends, as their glyphs would overlap.
+pub const MAX_GLYPH_ANGLE_CHANGE: f64 = FRAC_PI_4;
+
+/// The font size in pixels of labels whose layer has no `text-size`.
+pub const DEFAULT_TEXT_SIZE: f64 = 16.0;
+
+/// The distance in pixels at [`GLYPH_SIZE`] by which the baseline of the glyphs lies below the
+/// line, such that the glyphs are centered vertically on the line.
+const BASELINE_OFFSET: f64 = 7.0;
+
+/// A label which follows a line.
+#[derive(Clone, Debug)]
+pub struct LineLabel {
+    pub id: String,
+    /// The text of the label, see [`format_text_field`]
+    pub text: String,
+    pub line: Vec<LatLon>,
+    /// The font size in pixels
+    pub size: f64,
+    pub color: Color,
+    /// The position of the center of the label along the line, from 0 at the start to 1 at the
+    /// end. Labels are moved towards the center if they would exceed the line.
+    pub position: f64,
+}
+
+impl Default for LineLabel {
+    fn default() -> Self {
+        Self {
+            id: String::new(),
+            text: String::new(),
+            line: Vec::new(),
+            size: DEFAULT_TEXT_SIZE,
+            color: Color::new(0.0, 0.0, 0.0, 1.0),
+            position: 0.5,
+        }
+    }
+}
+
+/// A glyph of a label which has been placed on the screen.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PlacedGlyph {
+    pub character: char,
+    /// The center of the glyph in window coordinates
+    pub x: f64,
+    pub y: f64,
+    /// The rotation of the glyph in radians, clockwise from the x-axis of the window. Labels are
+    /// flipped as a whole, so the angle is within -90° and 90°, unless the line turns back within
+    /// the label.
+    pub angle: f64,
+}
+
+/// Replaces the `{property}` tokens of the `text_field` of a style layer by the `properties` of a
+/// feature. Tokens of properties which the feature does not have are removed.
+pub fn format_text_field(text_field: &str, properties: &HashMap<String, String>) -> String {
+    let mut text = String::with_capacity(text_field.len());
+    let mut rest = text_field;
+
+    while let Some(start) = rest.find('{') {
+        let Some(length) = rest[start..].find('}') else { break; };
+        text.push_str(&rest[..start]);
+        if let Some(value) = properties.get(&rest[start + 1..start + length]) {
+            text.push_str(value);
+        }
+        rest = &rest[start + length + 1..];
+    }
+
+    text.push_str(rest);
+    text
+}
+
+/// Holds the glyph atlas of the style, from which the glyphs of labels are drawn.
+#[derive(Default)]
+pub struct Glyphs {
+    atlas: Option<GlyphAtlas>,
+    /// Whether the atlas changed since it has been uploaded the last time.
+    changed: bool,
+}
+
+impl Glyphs {
+    pub fn set(&mut self, atlas: GlyphAtlas) {
+        self.atlas = Some(atlas);
+        self.changed = true;
+    }
+
+    pub fn atlas(&self) -> Option<&GlyphAtlas> {
+        self.atlas.as_ref()
+    }
+
+    /// Returns whether the atlas changed since the last call and resets the flag.
+    pub(crate) fn take_changed(&mut self) -> bool {
+        std::mem::take(&mut self.changed)
+    }
+}
+
+/// All line labels of the map together with their glyphs which have been placed for the current
+/// camera.
+#[derive(Default)]
+pub struct LineLabels {
+    labels: Vec<LineLabel>,
+    /// The labels of the line features of the style, which are collected every frame
+    feature_labels: Vec<LineLabel>,
+    placed: HashMap<String, Vec<PlacedGlyph>>,
+}
+
+impl LineLabels {
+    /// Adds the `label`. An existing label with the same id is replaced.
+    pub fn insert(&mut self, label: LineLabel) {
+        self.remove(&label.id);
+        self.labels.push(label);
+    }
+
+    pub fn remove(&mut self, id: &str) -> Option<LineLabel> {
+        let index = self.labels.iter().position(|label| label.id == id)?;
+        self.placed.remove(id);
+        Some(self.labels.remove(index))
+    }
+
+    pub fn get(&self, id: &str) -> Option<&LineLabel> {
+        self.iter().find(|label| label.id == id)
+    }
+
+    /// The added labels followed by the labels of the line features of the style.
+    pub fn iter(&self) -> impl Iterator<Item = &LineLabel> + '_ {
+        self.labels.iter().chain(&self.feature_labels)
+    }
+
+    /// The glyphs of the label with the `id`. Returns `None` if the label has not been placed for
+    /// the current camera.
+    pub fn placed(&self, id: &str) -> Option<&[PlacedGlyph]> {
+        self.placed.get(id).map(Vec::as_slice)
+    }
+
+    /// Places the glyphs of all labels along their lines on the screen with the advances of the
+    /// `atlas`. Characters which are missing from the atlas are skipped. Labels whose line is
+    /// partly behind the camera, which do not fit onto their line or which are outside of the
+    /// window are not placed.
+    pub fn place(&mut self, view_state: &ViewState, atlas: Option<&GlyphAtlas>) {
+        self.placed.clear();
+
+        let Some(atlas) = atlas else { return; };
+
+        let (width, height) = view_state.camera().size();
+        let is_in_window = |glyph: &PlacedGlyph| {
+            (0.0..=width).contains(&glyph.x) && (0.0..=height).contains(&glyph.y)
+        };
+
+        let mut placed = HashMap::new();
+        for label in self.iter() {
+            let Some(points) = label
+                .line
+                .iter()
+                .map(|position| view_state.lat_lon_to_screen(*position).map(|(x, y)| [x, y]))
+                .collect::<Option<Vec<_>>>() else { continue; };
+
+            let scale = label.size / GLYPH_SIZE;
+            let advances = label
+                .text
+                .chars()
+                .filter_map(|character| {
+                    let glyph = atlas.glyph(character)?;
+                    Some((character, glyph.advance as f64 * scale))
+                })
+                .collect::<Vec<_>>();
+
+            let Some(glyphs) =
+                place_along(&OverlayLine::new(points), &advances, label.position) else {
+                continue;
+            };
+
+            if glyphs.iter().any(is_in_window) {
+                placed.insert(label.id.clone(), glyphs);
+            }
+        }
+        self.placed = placed;
+    }
+
+    /// Computes the instances of the placed glyphs which are uploaded to the GPU. The glyphs are
+    /// drawn from the `atlas` at the `z_index`. Their colors are converted into the `color_space`
+    /// of the renderer.
+    pub(crate) fn instances(
+        &self,
+        view_state: &ViewState,
+        atlas: &GlyphAtlas,
+        z_index: f32,
+        color_space: ColorSpace,
+    ) -> Vec<ShaderGlyph> {
+        let view_proj = view_state.view_projection();
+        let (width, height) = view_state.camera().size();
+        let pixel = [2.0 / width as f32, 2.0 / height as f32];
+
+        let mut instances = Vec::new();
+        for label in self.iter() {
+            let Some(glyphs) = self.placed.get(&label.id) else { continue; };
+            let scale = label.size / GLYPH_SIZE;
+            let color = color_space.shader_color(&label.color);
+
+            for glyph in glyphs {
+                let (Some(metrics), Some(tex_coords)) = (
+                    atlas.glyph(glyph.character),
+                    atlas.texture_bounds(glyph.character),
+                ) else { continue; };
+
+                // Glyphs without an outline, like spaces, only advance the label
+                if metrics.width == 0 {
+                    continue;
+                }
+
+                let Some(world) = view_state.screen_to_world(glyph.x, glyph.y) else { continue; };
+                let anchor = view_proj.project(Vector4::new(world.x, world.y, 0.0, 1.0));
+                let Some(anchor) = anchor.cast::<f32>() else { continue; };
+
+                // The bitmap relative to the center of the advance on the baseline, y points down
+                let min_x =
+                    -(metrics.advance as f64) / 2.0 + (metrics.left - GLYPH_BORDER as i32) as f64;
+                let min_y = BASELINE_OFFSET - (metrics.top + GLYPH_BORDER as i32) as f64;
+                let quad = [
+                    min_x * scale,
+                    min_y * scale,
+                    (min_x + metrics.width as f64) * scale,
+                    (min_y + metrics.height as f64) * scale,
+                ]
+                .map(|value| value as f32);
+
+                instances.push(ShaderGlyph::new(
+                    anchor.into(),
+                    color,
+                    quad,
+                    tex_coords,
+                    [glyph.angle.cos() as f32, glyph.angle.sin() as f32],
+                    pixel,
+                    z_index,
+                ));
+            }
+        }
+        instances
+    }
+}
+
+/// Places the `glyphs` along the `line`, centered at `position` along the line. Each glyph is
+/// given with its advance. The text is flipped if the line runs from the right to the left at the
+/// label, such that it reads from the left to the right. Returns `None` if the text does not fit
+/// onto the line or the line bends too sharply, see [`MAX_GLYPH_ANGLE_CHANGE`].
+pub(crate) fn place_along(
+    line: &OverlayLine,
+    glyphs: &[(char, f64)],
+    position: f64,
+) -> Option<Vec<PlacedGlyph>> {
+    let length = line.length();
+    let half = glyphs.iter().map(|(_, advance)| advance).sum::<f64>() / 2.0;
+    if glyphs.is_empty() || half <= 0.0 || line.points.len() < 2 || half * 2.0 > length {
+        return None;
+    }
+
+    let center = (position.clamp(0.0, 1.0) * length).clamp(half, length - half);
+    let ([start_x, _], _) = line.point_at(center - half);
+    let ([end_x, _], _) = line.point_at(center + half);
+    let flip = end_x < start_x;
+
+    let mut placed = Vec::with_capacity(glyphs.len());
+    let mut offset = 0.0;
+    for (character, advance) in glyphs {
+        let along = offset + advance / 2.0;
+        offset += advance;
+        let distance = if flip {
+            center + half - along
+        } else {
+            center - half + along
+        };
+
+        let ([x, y], [dx, dy]) = line.point_at(distance);
+        let mut angle = dy.atan2(dx);
+        if flip {
+            angle += PI;
+            if angle > PI {
+                angle -= TAU;
+            }
+        }
+
+        if let Some(previous) = placed.last().map(|glyph: &PlacedGlyph| glyph.angle) {
+            let change = (angle - previous).abs();
+            if change.min(TAU - change) > MAX_GLYPH_ANGLE_CHANGE {
+                return None;
+            }
+        }
+
+        placed.push(PlacedGlyph {
+            character: *character,
+            x,
+            y,
+            angle,
+        });
+    }
+
+    Some(placed)
+}
+
+/// The labels of the line features of the loaded tiles which are visible in the `view_state`.
+/// Every line layer of the `style` with a `text-field` labels the features of its source layer.
+///
+/// The id of a label is the id of the layer followed by the id of the feature, such that features
+/// which span multiple tiles are labeled once. Features without an id are labeled per tile.
+fn feature_labels(
+    geometry_index: &GeometryIndex,
+    style: &Style,
+    view_state: &ViewState,
+) -> Vec<LineLabel> {
+    let Some(view_region) = view_state.create_view_region() else { return Vec::new(); };
+    let zoom = view_state.zoom();
+
+    let mut seen = HashSet::new();
+    let mut labels = Vec::new();
+    for layer in style
+        .layers
+        .iter()
+        .filter(|layer| matches!(layer.paint, Some(LayerPaint::Line(_))))
+        .filter(|layer| layer.is_visible_at(view_region.zoom_level()))
+    {
+        let (Some(layout), Some(source_layer)) = (&layer.layout, &layer.source_layer) else {
+            continue;
+        };
+        let Some(text_field) = &layout.text_field else { continue; };
+
+        for coords in view_region.iter() {
+            let transform = coords.transform_for_zoom(zoom);
+
+            for (index, (_, geometry)) in geometry_index
+                .layer_features(&coords)
+                .filter(|(layer_name, _)| *layer_name == source_layer.as_str())
+                .enumerate()
+            {
+                let ExactGeometry::LineString(line) = &geometry.exact else { continue; };
+
+                let id = if geometry.feature_id == 0 {
+                    format!("{}/{coords}/{index}", layer.id)
+                } else {
+                    format!("{}/{}", layer.id, geometry.feature_id)
+                };
+                let text = format_text_field(text_field, &geometry.properties);
+                if text.is_empty() || !seen.insert(id.clone()) {
+                    continue;
+                }
+
+                let line = line
+                    .points()
+                    .map(|point| {
+                        let world = transform * Vector4::new(point.x(), point.y(), 0.0, 1.0);
+                        WorldCoords::from((world.x, world.y)).to_lat_lon(zoom)
+                    })
+                    .collect();
+
+                labels.push(LineLabel {
+                    id,
+                    text,
+                    line,
+                    size: layout.text_size.unwrap_or(DEFAULT_TEXT_SIZE),
+                    ..LineLabel::default()
+                });
+            }
+        }
+    }
+    labels
+}
+
+impl World {
+    /// Adds a label along a line. An existing label with the same id is replaced.
+    pub fn add_line_label(&mut self, label: LineLabel) {
+        self.resources.get_or_init_mut::<LineLabels>().insert(label);
+    }
+
+    /// Removes the line label with the `id`. Returns whether a label has been removed.
+    pub fn remove_line_label(&mut self, id: &str) -> bool {
+        self.resources
+            .get_mut::<LineLabels>()
+            .and_then(|labels| labels.remove(id))
+            .is_some()
+    }
+
+    /// The glyphs of the line label with the `id` which have been placed in the last frame. The
+    /// labels of line features have the id of their layer followed by the id of the feature, e.g.
+    /// `roads/42`.
+    pub fn placed_line_label(&self, id: &str) -> Option<&[PlacedGlyph]> {
+        self.resources.get::<LineLabels>()?.placed(id)
+    }
+
+    /// Sets the glyph atlas from which line labels are drawn, e.g. built with
+    /// [`GlyphAtlas::from_ranges`]. The atlas is uploaded again in the next frame.
+    pub fn set_glyph_atlas(&mut self, atlas: GlyphAtlas) {
+        self.resources.get_or_init_mut::<Glyphs>().set(atlas);
+    }
+}
+
+pub fn line_label_placement_system(
+    MapContext {
+        world,
+        style,
+        view_state,
+        ..
+    }: &mut MapContext,
+) {
+    let Some((labels, glyphs)) = world
+        .resources
+        .query_mut::<(&mut LineLabels, &Glyphs)>() else { return; };
+
+    labels.feature_labels = feature_labels(&world.tiles.geometry_index, style, view_state);
+    labels.place(view_state, glyphs.atlas());
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, f64::consts::FRAC_PI_2};
+
+    use super::{format_text_field, place_along};
+    use crate::overlay::arrow::OverlayLine;
+
+    /// Glyphs of the `text` which all have an advance of 10
+    fn advances(text: &str) -> Vec<(char, f64)> {
+        text.chars().map(|character| (character, 10.0)).collect()
+    }
+
+    #[test]
+    fn test_format_text_field() {
+        let properties = HashMap::from([
+            ("name".to_string(), "Isar".to_string()),
+            ("ref".to_string(), "B 11".to_string()),
+        ]);
+
+        assert_eq!(format_text_field("{name}", &properties), "Isar");
+        assert_eq!(
+            format_text_field("{name} ({ref})", &properties),
+            "Isar (B 11)"
+        );
+        assert_eq!(format_text_field("{name:de}", &properties), "");
+        assert_eq!(format_text_field("{name", &properties), "{name");
+    }
+
+    #[test]
+    fn test_place_along() {
+        // A line with a length of 100 from the right to the left
+        let line = OverlayLine::new(vec![[100.0, 10.0], [50.0, 10.0], [0.0, 10.0]]);
+
+        let glyphs = place_along(&line, &advances("abcd"), 0.5).unwrap();
+
+        // The label is flipped, such that it reads from the left to the right
+        let characters = glyphs
+            .iter()
+            .map(|glyph| glyph.character)
+            .collect::<String>();
+        assert_eq!(characters, "abcd");
+        for (glyph, x) in glyphs.iter().zip([35.0, 45.0, 55.0, 65.0]) {
+            assert!((glyph.x - x).abs() < 1e-9, "{glyphs:?}");
+            assert_eq!(glyph.y, 10.0);
+            assert!(glyph.angle.abs() < 1e-9, "{glyphs:?}");
+        }
+
+        // Labels at the end of the line are moved onto the line
+        let placed = place_along(&line, &advances("abcd"), 1.0).unwrap();
+        assert!((placed[0].x - 5.0).abs() < 1e-9);
+
+        // Glyphs are placed by their own advance
+        let placed = place_along(&line, &[('i', 4.0), ('w', 16.0)], 0.5).unwrap();
+        assert!((placed[0].x - 42.0).abs() < 1e-9, "{placed:?}");
+        assert!((placed[1].x - 52.0).abs() < 1e-9, "{placed:?}");
+
+        // Labels which are longer than the line are not placed
+        assert!(place_along(&line, &advances(&"a".repeat(11)), 0.5).is_none());
+        assert!(place_along(&OverlayLine::new(vec![[0.0, 0.0]]), &advances("a"), 0.5).is_none());
+    }
+
+    #[test]
+    fn test_place_along_bend() {
+        // A slight bend downwards on the screen
+        let line = OverlayLine::new(vec![[0.0, 0.0], [50.0, 0.0], [100.0, 20.0]]);
+        let glyphs = place_along(&line, &advances("abcd"), 0.5).unwrap();
+
+        assert_eq!(glyphs[1].angle, 0.0);
+        assert!(glyphs[2].angle > 0.0 && glyphs[2].angle < FRAC_PI_2);
+        assert!(glyphs[2].y > 0.0);
+
+        // A sharp bend
+        let line = OverlayLine::new(vec![[0.0, 0.0], [50.0, 0.0], [50.0, 50.0]]);
+        assert!(place_along(&line, &advances("abcd"), 0.5).is_none());
+    }
+}
@@ -0,0 +1,211 @@
+//! Markers are points at a geographic location which are drawn as circles with a fixed size on
+//! the screen.
+
+use cgmath::Vector4;
+use csscolorparser::Color;
+
+use crate::{
+    coords::LatLon,
+    render::{color_space::ColorSpace, shaders::ShaderMarker},
+    tcs::world::World,
+    view_state::ViewState,
+};
+
+/// Describes how a marker is drawn.
+#[derive(Clone, Debug)]
+pub struct MarkerStyle {
+    pub color: Color,
+    /// The diameter of the marker in pixels
+    pub size: f32,
+}
+
+impl Default for MarkerStyle {
+    fn default() -> Self {
+        Self {
+            color: Color::new(0.0, 0.0, 1.0, 1.0),
+            size: 12.0,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Marker {
+    pub id: String,
+    pub position: LatLon,
+    pub style: MarkerStyle,
+}
+
+/// All markers of the map in the order in which they are drawn.
+#[derive(Default)]
+pub struct Markers {
+    markers: Vec<Marker>,
+}
+
+impl Markers {
+    /// Adds the `marker` on top of all other markers. An existing marker with the same id is
+    /// replaced.
+    pub fn insert(&mut self, marker: Marker) {
+        self.remove(&marker.id);
+        self.markers.push(marker);
+    }
+
+    pub fn remove(&mut self, id: &str) -> Option<Marker> {
+        let index = self.markers.iter().position(|marker| marker.id == id)?;
+        Some(self.markers.remove(index))
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Marker> {
+        self.markers.iter().find(|marker| marker.id == id)
+    }
+
+    pub fn get_mut(&mut self, id: &str) -> Option<&mut Marker> {
+        self.markers.iter_mut().find(|marker| marker.id == id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Marker> + '_ {
+        self.markers.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.markers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.markers.is_empty()
+    }
+
+    /// Returns the topmost marker which covers the window coordinates `x` and `y`. The origin of
+    /// the window coordinates is in the top-left corner and y points down.
+    pub fn pick(&self, view_state: &ViewState, x: f64, y: f64) -> Option<&Marker> {
+        self.markers.iter().rev().find(|marker| {
+            let radius = marker.style.size as f64 / 2.0;
+            view_state
+                .lat_lon_to_screen(marker.position)
+                .map_or(false, |(marker_x, marker_y)| {
+                    (marker_x - x).powi(2) + (marker_y - y).powi(2) <= radius.powi(2)
+                })
+        })
+    }
+
+    /// Computes the instances which are uploaded to the GPU. Markers behind the camera are
+    /// skipped. The markers are placed above the `z_index`. Their colors are converted into the
+    /// `color_space` of the renderer.
+    pub(crate) fn instances(
+        &self,
+        view_state: &ViewState,
+        z_index: f32,
+        color_space: ColorSpace,
+    ) -> Vec<ShaderMarker> {
+        let view_proj = view_state.view_projection();
+        let (width, height) = view_state.camera().size();
+
+        self.markers
+            .iter()
+            .enumerate()
+            .filter_map(|(i, marker)| {
+                let world = view_state
+                    .projection()
+                    .project(marker.position, view_state.zoom());
+                let center = view_proj.project(Vector4::new(world.x, world.y, 0.0, 1.0));
+
+                if center.w <= 0.0 {
+                    return None;
+                }
+
+                Some(ShaderMarker::new(
+                    center.cast::<f32>()?.into(),
+                    color_space.shader_color(&marker.style.color),
+                    [
+                        marker.style.size / width as f32,
+                        marker.style.size / height as f32,
+                    ],
+                    z_index + i as f32,
+                ))
+            })
+            .collect()
+    }
+}
+
+impl World {
+    /// Adds a marker at `position`. An existing marker with the same `id` is replaced.
+    pub fn add_marker(&mut self, id: &str, position: LatLon, style: MarkerStyle) {
+        self.resources.get_or_init_mut::<Markers>().insert(Marker {
+            id: id.to_string(),
+            position,
+            style,
+        });
+    }
+
+    /// Moves the marker with the `id` to `position`. Returns whether the marker exists.
+    pub fn set_marker_position(&mut self, id: &str, position: LatLon) -> bool {
+        self.resources
+            .get_mut::<Markers>()
+            .and_then(|markers| markers.get_mut(id))
+            .map(|marker| marker.position = position)
+            .is_some()
+    }
+
+    /// Removes the marker with the `id`. Returns whether a marker has been removed.
+    pub fn remove_marker(&mut self, id: &str) -> bool {
+        self.resources
+            .get_mut::<Markers>()
+            .and_then(|markers| markers.remove(id))
+            .is_some()
+    }
+
+    /// Returns the id of the topmost marker at the window coordinates `x` and `y`. See
+    /// [`Markers::pick`].
+    pub fn pick_marker(&self, view_state: &ViewState, x: f64, y: f64) -> Option<&str> {
+        self.resources
+            .get::<Markers>()
+            .and_then(|markers| markers.pick(view_state, x, y))
+            .map(|marker| marker.id.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::Deg;
+
+    use super::MarkerStyle;
+    use crate::{
+        coords::{LatLon, WorldCoords, Zoom},
+        tcs::world::World,
+        view_state::ViewState,
+        window::WindowSize,
+    };
+
+    #[test]
+    fn test_pick_marker() {
+        let munich = LatLon::new(48.137154, 11.576124);
+        let zoom = Zoom::new(10.0);
+        let view_state = ViewState::new(
+            WindowSize::new(800, 600).unwrap(),
+            WorldCoords::from_lat_lon(munich, zoom),
+            zoom,
+            Deg(0.0),
+            Deg(110.0),
+        );
+
+        let mut world = World::default();
+        world.add_marker("munich", munich, MarkerStyle::default());
+
+        let (x, y) = view_state.lat_lon_to_screen(munich).unwrap();
+        assert_eq!(world.pick_marker(&view_state, x, y), Some("munich"));
+        assert_eq!(world.pick_marker(&view_state, x + 5.0, y), Some("munich"));
+        assert_eq!(world.pick_marker(&view_state, x + 10.0, y), None);
+
+        // Markers on top are picked first
+        world.add_marker("top", munich, MarkerStyle::default());
+        assert_eq!(world.pick_marker(&view_state, x, y), Some("top"));
+
+        let berlin = LatLon::new(52.520008, 13.404954);
+        assert!(world.set_marker_position("top", berlin));
+        assert_eq!(world.pick_marker(&view_state, x, y), Some("munich"));
+
+        assert!(world.remove_marker("munich"));
+        assert!(!world.remove_marker("munich"));
+        assert_eq!(world.pick_marker(&view_state, x, y), None);
+        assert!(!world.set_marker_position("munich", berlin));
+    }
+}
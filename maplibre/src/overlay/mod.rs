@@ -0,0 +1,407 @@
+//! Overlays are drawn on top of the map without going through the tile system. They are useful to
+//! draw routes, highlighted regions or search results.
+//!
+//! The geometry of an overlay is tessellated once and is stored relative to the tile `0/0/0`.
+//! [Markers](marker::Marker) are drawn on top of all overlays. Dense points can be
+//! [clustered](cluster) into markers. [Arrows](arrow::LineArrows) can indicate the direction of
+//! lines. Point labels are placed with [collision detection](collision), such that they do not
+//! overlap. [Line labels](line_label) follow the curve of a line and are drawn with the glyphs
+//! of the glyph atlas.
+//!
+//! A [focus region](World::set_focus_region) dims everything outside of it and is drawn on top of
+//! all overlays.
+
+use std::rc::Rc;
+
+use csscolorparser::Color;
+use geo::orient::{Direction, Orient};
+use geo_types::{Geometry, LineString, Polygon};
+use geozero::{error::GeozeroError, GeozeroGeometry};
+use thiserror::Error;
+
+use crate::{
+    coords::{LatLon, EXTENT},
+    environment::Environment,
+    kernel::Kernel,
+    overlay::{
+        arrow::{LineArrows, LineCollector, OverlayLine},
+        cluster::{cluster_system, PointClusters},
+        collision::{label_placement_system, PointLabels},
+        line_label::{line_label_placement_system, Glyphs, LineLabels},
+        marker::Markers,
+        queue_system::queue_system,
+        resource::OverlayResources,
+        resource_system::resource_system,
+        upload_system::upload_system,
+    },
+    plugin::Plugin,
+    projection::{Projection, WebMercator},
+    render::{eventually::Eventually, graph::RenderGraph, RenderStageLabel, ShaderVertex},
+    schedule::Schedule,
+    tcs::world::World,
+    tessellation::{zero_tessellator::ZeroTessellator, IndexDataType, OverAlignedVertexBuffer},
+};
+
+pub mod arrow;
+pub mod cluster;
+pub mod collision;
+pub mod line_label;
+pub mod marker;
+mod queue_system;
+mod render_commands;
+mod resource;
+mod resource_system;
+mod upload_system;
+
+#[derive(Error, Debug)]
+pub enum OverlayError {
+    /// The GeoJSON could not be read or tessellated
+    #[error("reading GeoJSON failed")]
+    GeoJson(#[from] GeozeroError),
+    /// The GeoJSON does not contain any geometry which can be drawn
+    #[error("GeoJSON contains no drawable geometry")]
+    Empty,
+}
+
+/// Describes how an overlay is drawn.
+#[derive(Clone, Debug)]
+pub struct OverlayPaint {
+    pub color: Color,
+    /// The width of lines in pixels
+    pub width: f32,
+    /// Arrows which are drawn along lines in the direction of the line
+    pub arrows: Option<LineArrows>,
+}
+
+impl Default for OverlayPaint {
+    fn default() -> Self {
+        Self {
+            color: Color::new(1.0, 0.0, 0.0, 1.0),
+            width: 3.0,
+            arrows: None,
+        }
+    }
+}
+
+pub struct Overlay {
+    pub id: String,
+    pub paint: OverlayPaint,
+    pub geometry: OverAlignedVertexBuffer<ShaderVertex, IndexDataType>,
+    /// The lines of the geometry, along which arrows are placed
+    pub lines: Vec<OverlayLine>,
+    /// Changes whenever the overlay is replaced, such that it is uploaded again
+    version: u64,
+}
+
+/// The id of the overlay which dims the map outside of the focus region.
+const FOCUS_REGION_ID: &str = "focus-region";
+
+/// The latitude at which the Web Mercator projection ends in the north and the south.
+const MAX_LATITUDE: f64 = 85.0511287798066;
+
+/// All overlays of the map in the order in which they are drawn.
+pub struct Overlays {
+    overlays: Vec<Overlay>,
+    /// Dims the map outside of the focus region, drawn on top of all other overlays
+    focus_region: Option<Overlay>,
+    /// The color with which the map is dimmed outside of the focus region
+    focus_color: Color,
+    next_version: u64,
+}
+
+impl Default for Overlays {
+    fn default() -> Self {
+        Self {
+            overlays: Vec::new(),
+            focus_region: None,
+            focus_color: Color::new(0.0, 0.0, 0.0, 0.5),
+            next_version: 0,
+        }
+    }
+}
+
+impl Overlays {
+    /// Adds the `overlay` on top of all other overlays. An existing overlay with the same id is
+    /// replaced.
+    pub fn insert(&mut self, mut overlay: Overlay) {
+        self.remove(&overlay.id);
+        overlay.version = self.next_version();
+        self.overlays.push(overlay);
+    }
+
+    fn next_version(&mut self) -> u64 {
+        let version = self.next_version;
+        self.next_version += 1;
+        version
+    }
+
+    pub fn remove(&mut self, id: &str) -> Option<Overlay> {
+        let index = self.overlays.iter().position(|overlay| overlay.id == id)?;
+        Some(self.overlays.remove(index))
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Overlay> {
+        self.iter().find(|overlay| overlay.id == id)
+    }
+
+    /// All overlays in the order in which they are drawn, followed by the focus region.
+    pub fn iter(&self) -> impl Iterator<Item = &Overlay> + '_ {
+        self.overlays.iter().chain(self.focus_region.iter())
+    }
+
+    /// The overlay which covers everything outside of the focus region.
+    pub fn focus_region(&self) -> Option<&Overlay> {
+        self.focus_region.as_ref()
+    }
+
+    fn set_focus_region(
+        &mut self,
+        geometry: Option<OverAlignedVertexBuffer<ShaderVertex, IndexDataType>>,
+    ) {
+        let version = self.next_version();
+        self.focus_region = geometry.map(|geometry| Overlay {
+            id: FOCUS_REGION_ID.to_string(),
+            paint: OverlayPaint {
+                color: self.focus_color.clone(),
+                ..OverlayPaint::default()
+            },
+            geometry,
+            lines: Vec::new(),
+            version,
+        });
+    }
+
+    fn set_focus_color(&mut self, color: Color) {
+        let version = self.next_version();
+        if let Some(focus_region) = &mut self.focus_region {
+            focus_region.paint.color = color.clone();
+            focus_region.version = version;
+        }
+        self.focus_color = color;
+    }
+}
+
+impl World {
+    /// Tessellates the `geojson` and draws it on top of the map. The coordinates of the GeoJSON
+    /// are expected to be longitude and latitude. An existing overlay with the same `id` is
+    /// replaced.
+    pub fn add_geojson_overlay(
+        &mut self,
+        id: &str,
+        geojson: &str,
+        paint: OverlayPaint,
+    ) -> Result<(), OverlayError> {
+        let geometry = tessellate_geojson(geojson)?;
+
+        let mut lines = LineCollector::default();
+        geozero::geojson::read_geojson(geojson.as_bytes(), &mut lines)?;
+        let lines = lines
+            .lines
+            .into_iter()
+            .map(|line| OverlayLine::new(line.into_iter().map(project_coordinate).collect()))
+            .collect();
+
+        self.resources
+            .get_or_init_mut::<Overlays>()
+            .insert(Overlay {
+                id: id.to_string(),
+                paint,
+                geometry,
+                lines,
+                version: 0,
+            });
+
+        Ok(())
+    }
+
+    /// Dims everything outside of the `region` with the focus color, e.g. to highlight the region
+    /// which is currently relevant. The coordinates of the region are expected to be longitude and
+    /// latitude. Only the exterior ring of the region is considered. Passing `None` removes the
+    /// focus region.
+    pub fn set_focus_region(&mut self, region: Option<Polygon<f64>>) -> Result<(), OverlayError> {
+        let geometry = region.map(tessellate_focus_mask).transpose()?;
+
+        self.resources
+            .get_or_init_mut::<Overlays>()
+            .set_focus_region(geometry);
+
+        Ok(())
+    }
+
+    /// Sets the `color` with which the map is dimmed outside of the focus region. The alpha of the
+    /// color determines how strongly the map is dimmed.
+    pub fn set_focus_color(&mut self, color: Color) {
+        self.resources
+            .get_or_init_mut::<Overlays>()
+            .set_focus_color(color);
+    }
+
+    /// Removes the overlay with the `id`. Returns whether an overlay has been removed.
+    pub fn remove_overlay(&mut self, id: &str) -> bool {
+        self.resources
+            .get_mut::<Overlays>()
+            .and_then(|overlays| overlays.remove(id))
+            .is_some()
+    }
+}
+
+/// Tessellates the `geojson` and projects the resulting vertices into the tile `0/0/0`.
+fn tessellate_geojson(
+    geojson: &str,
+) -> Result<OverAlignedVertexBuffer<ShaderVertex, IndexDataType>, OverlayError> {
+    let mut tessellator = ZeroTessellator::<IndexDataType>::default();
+    geozero::geojson::read_geojson(geojson.as_bytes(), &mut tessellator)?;
+
+    project_into_world(tessellator)
+}
+
+/// Tessellates the whole world except for the `region` and projects the resulting vertices into
+/// the tile `0/0/0`.
+fn tessellate_focus_mask(
+    region: Polygon<f64>,
+) -> Result<OverAlignedVertexBuffer<ShaderVertex, IndexDataType>, OverlayError> {
+    let world = LineString::from(vec![
+        (-180.0, -MAX_LATITUDE),
+        (180.0, -MAX_LATITUDE),
+        (180.0, MAX_LATITUDE),
+        (-180.0, MAX_LATITUDE),
+        (-180.0, -MAX_LATITUDE),
+    ]);
+
+    // The region is cut out of the world as a hole, which requires the opposite winding order
+    // with the non-zero fill rule
+    let (exterior, _) = region.into_inner();
+    let mask = Polygon::new(world, vec![exterior]).orient(Direction::Default);
+
+    let mut tessellator = ZeroTessellator::<IndexDataType>::default();
+    Geometry::Polygon(mask).process_geom(&mut tessellator)?;
+
+    project_into_world(tessellator)
+}
+
+/// Projects the vertices of the `tessellator`, which are in longitude and latitude, into the tile
+/// `0/0/0`.
+fn project_into_world(
+    mut tessellator: ZeroTessellator<IndexDataType>,
+) -> Result<OverAlignedVertexBuffer<ShaderVertex, IndexDataType>, OverlayError> {
+    if tessellator.buffer.indices.is_empty() {
+        return Err(OverlayError::Empty);
+    }
+
+    // The geometry is tessellated in longitude and latitude. Web Mercator does not change the
+    // order of coordinates, so the triangles stay valid when projecting the vertices.
+    for vertex in &mut tessellator.buffer.vertices {
+        let [x, y] = project_coordinate([vertex.position[0] as f64, vertex.position[1] as f64]);
+        vertex.position = [x as f32, y as f32];
+    }
+
+    Ok(tessellator.buffer.into())
+}
+
+/// Projects a longitude and latitude into the tile `0/0/0`.
+fn project_coordinate([longitude, latitude]: [f64; 2]) -> [f64; 2] {
+    let (x, y) = WebMercator.project_unit(LatLon::new(latitude, longitude));
+    [x * EXTENT, y * EXTENT]
+}
+
+/// Draws the [`Overlays`], [`LineLabels`] and [`Markers`] of the [`World`].
+#[derive(Default)]
+pub struct OverlayPlugin;
+
+impl<E: Environment> Plugin<E> for OverlayPlugin {
+    fn build(
+        &self,
+        schedule: &mut Schedule,
+        _kernel: Rc<Kernel<E>>,
+        world: &mut World,
+        _graph: &mut RenderGraph,
+    ) {
+        world
+            .resources
+            .insert(Eventually::<OverlayResources>::Uninitialized);
+        world.resources.get_or_init_mut::<Overlays>();
+        world.resources.get_or_init_mut::<Markers>();
+        world.resources.get_or_init_mut::<PointClusters>();
+        world.resources.get_or_init_mut::<PointLabels>();
+        world.resources.get_or_init_mut::<LineLabels>();
+        world.resources.get_or_init_mut::<Glyphs>();
+
+        schedule.add_system_to_stage(RenderStageLabel::Extract, cluster_system);
+        schedule.add_system_to_stage(RenderStageLabel::Extract, label_placement_system);
+        // Line labels are placed right before their glyphs are uploaded, also in headless maps
+        // which have no extract stage
+        schedule.add_system_to_stage(RenderStageLabel::Prepare, line_label_placement_system);
+        schedule.add_system_to_stage(RenderStageLabel::Prepare, resource_system);
+        schedule.add_system_to_stage(RenderStageLabel::Queue, upload_system);
+        schedule.add_system_to_stage(RenderStageLabel::Queue, queue_system);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{project_coordinate, OverlayError, OverlayPaint, Overlays};
+    use crate::{coords::EXTENT, tcs::world::World};
+
+    const ROUTE: &str = r#"{
+        "type": "LineString",
+        "coordinates": [[11.57, 48.13], [13.40, 52.52]]
+    }"#;
+
+    #[test]
+    fn test_add_and_remove_overlay() {
+        let mut world = World::default();
+
+        world
+            .add_geojson_overlay("route", ROUTE, OverlayPaint::default())
+            .unwrap();
+
+        let overlays = world.resources.get::<Overlays>().unwrap();
+        let route = overlays.get("route").unwrap();
+        assert!(route.geometry.usable_indices > 0);
+
+        // Vertices are projected into the tile 0/0/0
+        for vertex in &route.geometry.buffer.vertices {
+            assert!(vertex.position[0] > EXTENT as f32 / 2.0);
+            assert!(vertex.position[1] < EXTENT as f32 / 2.0);
+        }
+
+        assert!(world.remove_overlay("route"));
+        assert!(!world.remove_overlay("route"));
+        assert!(world
+            .resources
+            .get::<Overlays>()
+            .unwrap()
+            .get("route")
+            .is_none());
+    }
+
+    #[test]
+    fn test_project_coordinate() {
+        // Coordinates are in degrees
+        let [x, y] = project_coordinate([90.0, 0.0]);
+        assert!((x - 0.75 * EXTENT).abs() < 1e-9);
+        assert!((y - 0.5 * EXTENT).abs() < 1e-9);
+
+        let [x, y] = project_coordinate([-180.0, 85.051129]);
+        assert!(x.abs() < 1e-9);
+        assert!(y.abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_invalid_overlay() {
+        let mut world = World::default();
+
+        assert!(matches!(
+            world.add_geojson_overlay("invalid", "{", OverlayPaint::default()),
+            Err(OverlayError::GeoJson(_))
+        ));
+        assert!(matches!(
+            world.add_geojson_overlay(
+                "point",
+                r#"{"type": "Point", "coordinates": [11.57, 48.13]}"#,
+                OverlayPaint::default()
+            ),
+            Err(OverlayError::Empty)
+        ));
+    }
+}
@@ -0,0 +1,90 @@
+//! Queues [PhaseItems](crate::render::render_phase::PhaseItem) for rendering.
+
+use crate::{
+    context::MapContext,
+    coords::{WorldTileCoords, ZoomLevel},
+    overlay::{
+        line_label::{Glyphs, LineLabels},
+        marker::Markers,
+        render_commands::{DrawLineLabels, DrawMarkers, DrawOverlays},
+        Overlays,
+    },
+    render::{
+        render_phase::{DrawState, LayerItem, RenderPhase},
+        tile_view_pattern::TileShape,
+    },
+    tcs::tiles::Tile,
+};
+
+pub fn queue_system(
+    MapContext {
+        world, view_state, ..
+    }: &mut MapContext,
+) {
+    let coords = WorldTileCoords::from((0, 0, ZoomLevel::default()));
+
+    let mut items = world
+        .resources
+        .get::<Overlays>()
+        .map(|overlays| {
+            overlays
+                .iter()
+                // Overlays without geometry have no buffers
+                .filter(|overlay| overlay.geometry.usable_indices > 0)
+                .map(|overlay| LayerItem {
+                    draw_function: Box::new(DrawState::<LayerItem, DrawOverlays>::new()),
+                    // Overlays are drawn after all layers of the style
+                    index: u32::MAX,
+                    style_layer: overlay.id.clone(),
+                    tile: Tile { coords },
+                    source_shape: TileShape::new(coords, view_state),
+                    indices: None,
+                })
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+
+    // All glyphs of line labels are drawn at once, once the glyph atlas is available
+    let has_line_labels = world
+        .resources
+        .get::<LineLabels>()
+        .map_or(false, |labels| labels.iter().next().is_some());
+    let has_glyphs = world
+        .resources
+        .get::<Glyphs>()
+        .map_or(false, |glyphs| glyphs.atlas().is_some());
+    if has_line_labels && has_glyphs {
+        items.push(LayerItem {
+            draw_function: Box::new(DrawState::<LayerItem, DrawLineLabels>::new()),
+            index: u32::MAX,
+            style_layer: "line-labels".to_string(),
+            tile: Tile { coords },
+            source_shape: TileShape::new(coords, view_state),
+            indices: None,
+        });
+    }
+
+    // All markers are drawn at once
+    if world
+        .resources
+        .get::<Markers>()
+        .map_or(false, |markers| !markers.is_empty())
+    {
+        items.push(LayerItem {
+            draw_function: Box::new(DrawState::<LayerItem, DrawMarkers>::new()),
+            index: u32::MAX,
+            style_layer: "markers".to_string(),
+            tile: Tile { coords },
+            source_shape: TileShape::new(coords, view_state),
+            indices: None,
+        });
+    }
+
+    let Some(layer_item_phase) = world
+        .resources
+        .get_mut::<RenderPhase<LayerItem>>() else { return; };
+
+    for item in items {
+        layer_item_phase.add(item);
+    }
+}
@@ -0,0 +1,155 @@
+use crate::{
+    overlay::resource::OverlayResources,
+    render::{
+        eventually::{Eventually, Eventually::Initialized},
+        render_phase::{LayerItem, PhaseItem, RenderCommand, RenderCommandResult},
+        resource::TrackedRenderPass,
+        INDEX_FORMAT,
+    },
+    tcs::world::World,
+};
+
+pub struct SetOverlayPipeline;
+impl<P: PhaseItem> RenderCommand<P> for SetOverlayPipeline {
+    fn render<'w>(
+        world: &'w World,
+        _item: &P,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        let Some(Initialized(overlay_resources)) = world
+            .resources
+            .get::<Eventually<OverlayResources>>() else { return RenderCommandResult::Failure; };
+
+        pass.set_render_pipeline(overlay_resources.pipeline());
+        RenderCommandResult::Success
+    }
+}
+
+pub struct DrawOverlay;
+impl RenderCommand<LayerItem> for DrawOverlay {
+    fn render<'w>(
+        world: &'w World,
+        item: &LayerItem,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        let Some(Initialized(overlay_resources)) = world
+            .resources
+            .get::<Eventually<OverlayResources>>() else { return RenderCommandResult::Failure; };
+
+        let Some(buffers) = overlay_resources
+            .buffers
+            .get(&item.style_layer) else { return RenderCommandResult::Failure; };
+
+        pass.set_index_buffer(&buffers.indices, .., INDEX_FORMAT);
+        pass.set_vertex_buffer(0, &buffers.vertices, ..);
+        pass.set_vertex_buffer(1, &buffers.tile_metadata, ..);
+        pass.set_vertex_buffer(2, &buffers.layer_metadata, ..);
+        pass.set_vertex_buffer(3, &buffers.feature_metadata, ..);
+
+        pass.draw_indexed(0..buffers.usable_indices, 0, 0..1);
+
+        // The arrows are drawn on top of the lines of the overlay
+        if let Some(arrows) = overlay_resources
+            .arrows
+            .get(&item.style_layer)
+            .and_then(|arrows| arrows.buffers.as_ref())
+        {
+            pass.set_index_buffer(&arrows.indices, .., INDEX_FORMAT);
+            pass.set_vertex_buffer(0, &arrows.vertices, ..);
+            pass.set_vertex_buffer(1, &arrows.tile_metadata, ..);
+            pass.set_vertex_buffer(2, &arrows.layer_metadata, ..);
+            pass.set_vertex_buffer(3, &arrows.feature_metadata, ..);
+
+            pass.draw_indexed(0..arrows.usable_indices, 0, 0..1);
+        }
+
+        RenderCommandResult::Success
+    }
+}
+
+pub struct SetMarkerPipeline;
+impl<P: PhaseItem> RenderCommand<P> for SetMarkerPipeline {
+    fn render<'w>(
+        world: &'w World,
+        _item: &P,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        let Some(Initialized(overlay_resources)) = world
+            .resources
+            .get::<Eventually<OverlayResources>>() else { return RenderCommandResult::Failure; };
+
+        pass.set_render_pipeline(overlay_resources.marker_pipeline());
+        RenderCommandResult::Success
+    }
+}
+
+pub struct DrawMarker;
+impl<P: PhaseItem> RenderCommand<P> for DrawMarker {
+    fn render<'w>(
+        world: &'w World,
+        _item: &P,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        let Some(Initialized(overlay_resources)) = world
+            .resources
+            .get::<Eventually<OverlayResources>>() else { return RenderCommandResult::Failure; };
+
+        let Some(markers) = &overlay_resources.markers else { return RenderCommandResult::Failure; };
+
+        pass.set_vertex_buffer(0, &markers.instances, ..);
+
+        const MARKER_VERTICES: u32 = 6;
+        pass.draw(0..MARKER_VERTICES, 0..markers.count);
+
+        RenderCommandResult::Success
+    }
+}
+
+pub struct SetGlyphPipeline;
+impl<P: PhaseItem> RenderCommand<P> for SetGlyphPipeline {
+    fn render<'w>(
+        world: &'w World,
+        _item: &P,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        let Some(Initialized(overlay_resources)) = world
+            .resources
+            .get::<Eventually<OverlayResources>>() else { return RenderCommandResult::Failure; };
+
+        let Some(glyph_texture) = &overlay_resources.glyph_texture else {
+            return RenderCommandResult::Failure;
+        };
+
+        pass.set_render_pipeline(overlay_resources.glyph_pipeline());
+        pass.set_bind_group(0, glyph_texture.bind_group(), &[]);
+        RenderCommandResult::Success
+    }
+}
+
+pub struct DrawGlyphs;
+impl<P: PhaseItem> RenderCommand<P> for DrawGlyphs {
+    fn render<'w>(
+        world: &'w World,
+        _item: &P,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        let Some(Initialized(overlay_resources)) = world
+            .resources
+            .get::<Eventually<OverlayResources>>() else { return RenderCommandResult::Failure; };
+
+        let Some(glyphs) = &overlay_resources.glyphs else { return RenderCommandResult::Failure; };
+
+        pass.set_vertex_buffer(0, &glyphs.instances, ..);
+
+        const GLYPH_VERTICES: u32 = 6;
+        pass.draw(0..GLYPH_VERTICES, 0..glyphs.count);
+
+        RenderCommandResult::Success
+    }
+}
+
+pub type DrawOverlays = (SetOverlayPipeline, DrawOverlay);
+
+pub type DrawLineLabels = (SetGlyphPipeline, DrawGlyphs);
+
+pub type DrawMarkers = (SetMarkerPipeline, DrawMarker);
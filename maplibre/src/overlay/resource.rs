@@ -0,0 +1,312 @@
+use std::{collections::HashMap, mem::size_of};
+
+use crate::{
+    coords::WorldTileCoords,
+    render::{
+        resource::Texture,
+        settings::Msaa,
+        shaders::{
+            ShaderFeatureStyle, ShaderGlyph, ShaderLayerMetadata, ShaderMarker,
+            ShaderRasterMetadata, ShaderTileMetadata,
+        },
+        ShaderVertex,
+    },
+    style::glyph::GlyphAtlas,
+};
+
+/// The GPU buffers of a single overlay.
+pub struct OverlayBuffers {
+    /// The version of the overlay which has been uploaded
+    pub version: u64,
+    pub vertices: wgpu::Buffer,
+    pub indices: wgpu::Buffer,
+    pub usable_indices: u32,
+    pub tile_metadata: wgpu::Buffer,
+    pub layer_metadata: wgpu::Buffer,
+    pub feature_metadata: wgpu::Buffer,
+    /// The metadata which has been written into `tile_metadata` and `layer_metadata`
+    written_metadata: Option<(ShaderTileMetadata, ShaderLayerMetadata)>,
+}
+
+impl OverlayBuffers {
+    pub fn new(
+        device: &wgpu::Device,
+        version: u64,
+        vertex_bytes: u64,
+        index_bytes: u64,
+        usable_indices: u32,
+    ) -> Self {
+        let create_buffer = |label: &str, size: u64, usage: wgpu::BufferUsages| {
+            device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(label),
+                size,
+                usage: usage | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            })
+        };
+
+        let vertex_count = vertex_bytes / size_of::<ShaderVertex>() as u64;
+
+        Self {
+            version,
+            vertices: create_buffer(
+                "overlay vertex buffer",
+                vertex_bytes,
+                wgpu::BufferUsages::VERTEX,
+            ),
+            indices: create_buffer(
+                "overlay indices buffer",
+                index_bytes,
+                wgpu::BufferUsages::INDEX,
+            ),
+            usable_indices,
+            tile_metadata: create_buffer(
+                "overlay tile metadata buffer",
+                size_of::<ShaderTileMetadata>() as u64,
+                wgpu::BufferUsages::VERTEX,
+            ),
+            layer_metadata: create_buffer(
+                "overlay layer metadata buffer",
+                size_of::<ShaderLayerMetadata>() as u64,
+                wgpu::BufferUsages::VERTEX,
+            ),
+            feature_metadata: create_buffer(
+                "overlay feature metadata buffer",
+                vertex_count * size_of::<ShaderFeatureStyle>() as u64,
+                wgpu::BufferUsages::VERTEX,
+            ),
+            written_metadata: None,
+        }
+    }
+
+    /// Whether the vertex and index buffers can hold `vertex_bytes` and `index_bytes`.
+    pub fn fits(&self, vertex_bytes: u64, index_bytes: u64) -> bool {
+        self.vertices.size() >= vertex_bytes && self.indices.size() >= index_bytes
+    }
+
+    /// Writes the tile and layer metadata, unless the same metadata has been written before.
+    pub fn write_metadata(
+        &mut self,
+        queue: &wgpu::Queue,
+        tile_metadata: ShaderTileMetadata,
+        layer_metadata: ShaderLayerMetadata,
+    ) {
+        if self.written_metadata == Some((tile_metadata, layer_metadata)) {
+            return;
+        }
+
+        queue.write_buffer(
+            &self.tile_metadata,
+            0,
+            bytemuck::cast_slice(&[tile_metadata]),
+        );
+        queue.write_buffer(
+            &self.layer_metadata,
+            0,
+            bytemuck::cast_slice(&[layer_metadata]),
+        );
+        self.written_metadata = Some((tile_metadata, layer_metadata));
+    }
+}
+
+/// The GPU buffers of the arrows along the lines of a single overlay.
+pub struct ArrowBuffers {
+    /// The version of the overlay for which the arrows have been placed
+    pub version: u64,
+    /// The size of the world in pixels for which the arrows have been placed
+    pub world_size: f64,
+    /// The tile to which the vertices of the arrows are relative
+    pub origin: WorldTileCoords,
+    /// The buffers of the arrows, which are only created if at least one arrow has been placed
+    pub buffers: Option<OverlayBuffers>,
+}
+
+/// The instance buffer of all markers.
+pub struct MarkerBuffer {
+    pub instances: wgpu::Buffer,
+    /// The amount of markers which fit into the buffer
+    pub capacity: usize,
+    /// The amount of markers which have been uploaded
+    pub count: u32,
+}
+
+impl MarkerBuffer {
+    pub fn new(device: &wgpu::Device, capacity: usize) -> Self {
+        Self {
+            instances: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("marker instance buffer"),
+                size: (capacity * size_of::<ShaderMarker>()) as u64,
+                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }),
+            capacity,
+            count: 0,
+        }
+    }
+}
+
+/// The instance buffer of the glyphs of all line labels.
+pub struct GlyphBuffer {
+    pub instances: wgpu::Buffer,
+    /// The amount of glyphs which fit into the buffer
+    pub capacity: usize,
+    /// The amount of glyphs which have been uploaded
+    pub count: u32,
+}
+
+impl GlyphBuffer {
+    pub fn new(device: &wgpu::Device, capacity: usize) -> Self {
+        Self {
+            instances: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("glyph instance buffer"),
+                size: (capacity * size_of::<ShaderGlyph>()) as u64,
+                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }),
+            capacity,
+            count: 0,
+        }
+    }
+}
+
+/// The glyph atlas on the GPU, bound such that it can be sampled by the glyph pipeline.
+pub struct GlyphTexture {
+    _texture: Texture,
+    _metadata: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+impl GlyphTexture {
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        layout: &wgpu::BindGroupLayout,
+        atlas: &GlyphAtlas,
+    ) -> Self {
+        let (width, height) = (atlas.width(), atlas.height());
+
+        // The distance fields are not colors, so they are not converted into the color space
+        let texture = Texture::new(
+            Some("glyph texture"),
+            device,
+            wgpu::TextureFormat::R8Unorm,
+            width,
+            height,
+            Msaa { samples: 1 },
+            wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        );
+
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                aspect: wgpu::TextureAspect::All,
+                texture: &texture.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+            },
+            atlas.pixels(),
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(width),
+                rows_per_image: Some(height),
+            },
+            texture.size,
+        );
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let metadata = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("glyph metadata buffer"),
+            size: size_of::<ShaderRasterMetadata>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        queue.write_buffer(
+            &metadata,
+            0,
+            bytemuck::cast_slice(&[ShaderRasterMetadata::new(1.0)]),
+        );
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: metadata.as_entire_binding(),
+                },
+            ],
+            label: Some("glyph bind group"),
+        });
+
+        Self {
+            _texture: texture,
+            _metadata: metadata,
+            bind_group,
+        }
+    }
+
+    pub fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+}
+
+/// Holds the pipelines and the buffers of all overlays, markers and line labels which have been
+/// uploaded.
+pub struct OverlayResources {
+    pipeline: wgpu::RenderPipeline,
+    marker_pipeline: wgpu::RenderPipeline,
+    glyph_pipeline: wgpu::RenderPipeline,
+    /// The buffers of the overlays with any geometry
+    pub buffers: HashMap<String, OverlayBuffers>,
+    /// The arrows of the overlays with the same id
+    pub arrows: HashMap<String, ArrowBuffers>,
+    pub markers: Option<MarkerBuffer>,
+    pub glyphs: Option<GlyphBuffer>,
+    /// The glyph atlas, which is uploaded once it is set
+    pub glyph_texture: Option<GlyphTexture>,
+}
+
+impl OverlayResources {
+    pub fn new(
+        pipeline: wgpu::RenderPipeline,
+        marker_pipeline: wgpu::RenderPipeline,
+        glyph_pipeline: wgpu::RenderPipeline,
+    ) -> Self {
+        Self {
+            pipeline,
+            marker_pipeline,
+            glyph_pipeline,
+            buffers: Default::default(),
+            arrows: Default::default(),
+            markers: None,
+            glyphs: None,
+            glyph_texture: None,
+        }
+    }
+
+    pub fn pipeline(&self) -> &wgpu::RenderPipeline {
+        &self.pipeline
+    }
+
+    pub fn marker_pipeline(&self) -> &wgpu::RenderPipeline {
+        &self.marker_pipeline
+    }
+
+    pub fn glyph_pipeline(&self) -> &wgpu::RenderPipeline {
+        &self.glyph_pipeline
+    }
+}
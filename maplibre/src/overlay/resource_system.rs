@@ -0,0 +1,96 @@
+//! Prepares GPU-owned resources by initializing them if they are uninitialized or out-of-date.
+use crate::{
+    context::MapContext,
+    overlay::resource::OverlayResources,
+    render::{
+        eventually::Eventually,
+        resource::{RenderPipeline, TilePipeline},
+        shaders,
+        shaders::Shader,
+        RenderResources, Renderer,
+    },
+};
+
+pub fn resource_system(
+    MapContext {
+        world,
+        renderer:
+            Renderer {
+                device,
+                resources: RenderResources { surface, .. },
+                settings,
+                ..
+            },
+        ..
+    }: &mut MapContext,
+) {
+    let Some(overlay_resources) = world
+        .resources
+        .query_mut::<&mut Eventually<OverlayResources>>() else { return; };
+
+    overlay_resources.initialize(|| {
+        let tile_shader = shaders::VectorTileShader {
+            format: surface.surface_format(),
+            blend_mode: None,
+            pattern: false,
+        };
+
+        // Overlays are not clipped by tile masks, so the stencil test always passes
+        let pipeline = TilePipeline::new(
+            "overlay_pipeline".into(),
+            *settings,
+            tile_shader.describe_vertex(),
+            tile_shader.describe_fragment(),
+            true,
+            false,
+            true,
+            false,
+            surface.is_multisampling_supported(settings.msaa),
+            false,
+        )
+        .describe_render_pipeline()
+        .initialize(device);
+
+        let marker_shader = shaders::MarkerShader {
+            format: surface.surface_format(),
+        };
+
+        let marker_pipeline = TilePipeline::new(
+            "marker_pipeline".into(),
+            *settings,
+            marker_shader.describe_vertex(),
+            marker_shader.describe_fragment(),
+            true,
+            false,
+            true,
+            false,
+            surface.is_multisampling_supported(settings.msaa),
+            false,
+        )
+        .describe_render_pipeline()
+        .initialize(device);
+
+        let glyph_shader = shaders::GlyphShader {
+            format: surface.surface_format(),
+        };
+
+        // The glyphs of a label overlap each other, so they ignore the depth of earlier glyphs
+        let glyph_pipeline = TilePipeline::new(
+            "glyph_pipeline".into(),
+            *settings,
+            glyph_shader.describe_vertex(),
+            glyph_shader.describe_fragment(),
+            true,
+            false,
+            true,
+            false,
+            surface.is_multisampling_supported(settings.msaa),
+            true,
+        )
+        .with_ignored_depth(true)
+        .describe_render_pipeline()
+        .initialize(device);
+
+        OverlayResources::new(pipeline, marker_pipeline, glyph_pipeline)
+    });
+}
@@ -0,0 +1,273 @@
+//! Uploads data to the GPU which is needed for rendering.
+
+use std::iter;
+
+use csscolorparser::Color;
+
+use crate::{
+    context::MapContext,
+    coords::{WorldTileCoords, ZoomLevel, EXTENT},
+    overlay::{
+        arrow::{arrow_origin, place_arrows},
+        line_label::{Glyphs, LineLabels},
+        marker::Markers,
+        resource::{
+            ArrowBuffers, GlyphBuffer, GlyphTexture, MarkerBuffer, OverlayBuffers, OverlayResources,
+        },
+        Overlays,
+    },
+    projection::Projection,
+    render::{
+        color_space::ColorSpace,
+        eventually::{Eventually, Eventually::Initialized},
+        shaders::{ShaderFeatureStyle, ShaderLayerMetadata, ShaderTileMetadata},
+        RenderResources, Renderer, ShaderVertex,
+    },
+    tessellation::{IndexDataType, OverAlignedVertexBuffer},
+};
+
+pub fn upload_system(
+    MapContext {
+        world,
+        style,
+        view_state,
+        renderer:
+            Renderer {
+                device,
+                queue,
+                resources: RenderResources { surface, .. },
+                ..
+            },
+        ..
+    }: &mut MapContext,
+) {
+    let Some((
+        Initialized(overlay_resources),
+        overlays,
+        markers,
+        line_labels,
+        glyphs,
+    )) = world.resources.query_mut::<(
+        &mut Eventually<OverlayResources>,
+        &Overlays,
+        &Markers,
+        &LineLabels,
+        &mut Glyphs,
+    )>() else { return; };
+    let color_space = surface.color_space();
+
+    overlay_resources
+        .buffers
+        .retain(|id, _| overlays.get(id).is_some());
+    overlay_resources.arrows.retain(|id, _| {
+        overlays
+            .get(id)
+            .map_or(false, |overlay| overlay.paint.arrows.is_some())
+    });
+
+    // All overlays are placed within the tile 0/0/0
+    let coords = WorldTileCoords::from((0, 0, ZoomLevel::default()));
+    let transform = view_state
+        .view_projection()
+        .to_model_view_projection(view_state.tile_transform(coords))
+        .downcast()
+        .into();
+    let world_size = view_state.projection().world_size(view_state.zoom());
+
+    for (i, overlay) in overlays.iter().enumerate() {
+        let needs_upload = overlay_resources
+            .buffers
+            .get(&overlay.id)
+            .map_or(overlay.geometry.usable_indices > 0, |buffers| {
+                buffers.version != overlay.version
+            });
+
+        if needs_upload {
+            log::debug!("Uploading overlay {}", overlay.id);
+            let buffers = upload_geometry(
+                device,
+                queue,
+                overlay_resources.buffers.remove(&overlay.id),
+                overlay.version,
+                &overlay.geometry,
+                &overlay.paint.color,
+                color_space,
+            );
+            if let Some(buffers) = buffers {
+                overlay_resources
+                    .buffers
+                    .insert(overlay.id.clone(), buffers);
+            }
+        }
+
+        // Overlays without geometry have no buffers and are not drawn
+        let Some(buffers) = overlay_resources.buffers.get_mut(&overlay.id) else { continue; };
+
+        // Overlays are drawn on top of all layers of the style
+        let z_index = (style.layers.len() + 1 + i) as f32;
+
+        // The shader extrudes lines by three times the zoom factor in units of the tile
+        let zoom_factor = overlay.paint.width as f64 * EXTENT / (3.0 * world_size);
+        buffers.write_metadata(
+            queue,
+            ShaderTileMetadata::new(transform, zoom_factor as f32),
+            ShaderLayerMetadata::new(z_index),
+        );
+
+        let Some(arrows) = &overlay.paint.arrows else { continue; };
+
+        // Arrows have a fixed size on the screen, so they are placed again when zooming
+        let needs_placement = overlay_resources
+            .arrows
+            .get(&overlay.id)
+            .map_or(true, |arrow_buffers| {
+                arrow_buffers.version != overlay.version
+                    || arrow_buffers.world_size != world_size
+            });
+
+        if needs_placement {
+            let origin = arrow_origin(&overlay.lines, view_state.visible_level());
+            let pixel = EXTENT / world_size;
+            let geometry = place_arrows(
+                &overlay.lines,
+                arrows.spacing as f64 * pixel,
+                arrows.size as f64 * pixel,
+                origin,
+            );
+
+            let buffers = upload_geometry(
+                device,
+                queue,
+                overlay_resources
+                    .arrows
+                    .remove(&overlay.id)
+                    .and_then(|arrow_buffers| arrow_buffers.buffers),
+                overlay.version,
+                &geometry,
+                &arrows.color,
+                color_space,
+            );
+            overlay_resources.arrows.insert(
+                overlay.id.clone(),
+                ArrowBuffers {
+                    version: overlay.version,
+                    world_size,
+                    origin,
+                    buffers,
+                },
+            );
+        }
+
+        let Some(ArrowBuffers {
+            origin,
+            buffers: Some(buffers),
+            ..
+        }) = overlay_resources.arrows.get_mut(&overlay.id) else { continue; };
+
+        // Arrows are placed between the overlay and the next one
+        let arrow_transform = view_state
+            .view_projection()
+            .to_model_view_projection(view_state.tile_transform(*origin))
+            .downcast()
+            .into();
+        buffers.write_metadata(
+            queue,
+            ShaderTileMetadata::new(arrow_transform, 0.0),
+            ShaderLayerMetadata::new(z_index + 0.5),
+        );
+    }
+
+    // Line labels are drawn on top of all overlays
+    let z_index = (style.layers.len() + 1 + overlays.iter().count()) as f32;
+
+    if glyphs.take_changed() {
+        overlay_resources.glyph_texture = None;
+    }
+
+    if let Some(atlas) = glyphs.atlas() {
+        if overlay_resources.glyph_texture.is_none() {
+            let layout = overlay_resources.glyph_pipeline().get_bind_group_layout(0);
+            overlay_resources.glyph_texture =
+                Some(GlyphTexture::new(device, queue, &layout, atlas));
+        }
+
+        let instances = line_labels.instances(view_state, atlas, z_index, color_space);
+
+        let glyph_buffer = match &mut overlay_resources.glyphs {
+            Some(glyph_buffer) if glyph_buffer.capacity >= instances.len() => glyph_buffer,
+            glyph_buffer => glyph_buffer.insert(GlyphBuffer::new(
+                device,
+                instances.len().next_power_of_two(),
+            )),
+        };
+
+        queue.write_buffer(&glyph_buffer.instances, 0, bytemuck::cast_slice(&instances));
+        glyph_buffer.count = instances.len() as u32;
+    }
+
+    // Markers are drawn on top of all overlays and line labels
+    let instances = markers.instances(view_state, z_index + 1.0, color_space);
+
+    let marker_buffer = match &mut overlay_resources.markers {
+        Some(marker_buffer) if marker_buffer.capacity >= instances.len() => marker_buffer,
+        marker_buffer => marker_buffer.insert(MarkerBuffer::new(
+            device,
+            instances.len().next_power_of_two(),
+        )),
+    };
+
+    queue.write_buffer(
+        &marker_buffer.instances,
+        0,
+        bytemuck::cast_slice(&instances),
+    );
+    marker_buffer.count = instances.len() as u32;
+}
+
+/// Uploads the `geometry` with a single `color` into the `buffers` of an overlay. The buffers are
+/// only created again if the geometry does not fit into them. Empty geometry has no buffers.
+fn upload_geometry(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    buffers: Option<OverlayBuffers>,
+    version: u64,
+    geometry: &OverAlignedVertexBuffer<ShaderVertex, IndexDataType>,
+    color: &Color,
+    color_space: ColorSpace,
+) -> Option<OverlayBuffers> {
+    if geometry.usable_indices == 0 {
+        return None;
+    }
+
+    let vertices = bytemuck::cast_slice(&geometry.buffer.vertices);
+    let indices = bytemuck::cast_slice(&geometry.buffer.indices);
+
+    let (vertex_bytes, index_bytes) = (vertices.len() as u64, indices.len() as u64);
+    let mut buffers = match buffers {
+        Some(buffers) if buffers.fits(vertex_bytes, index_bytes) => buffers,
+        _ => OverlayBuffers::new(
+            device,
+            version,
+            vertex_bytes.next_power_of_two(),
+            index_bytes.next_power_of_two(),
+            0,
+        ),
+    };
+    buffers.version = version;
+    buffers.usable_indices = geometry.usable_indices;
+
+    let color = color_space.shader_color(color);
+    let feature_metadata = iter::repeat(ShaderFeatureStyle { color, data: 0.0 })
+        .take(geometry.buffer.vertices.len())
+        .collect::<Vec<_>>();
+
+    queue.write_buffer(&buffers.vertices, 0, vertices);
+    queue.write_buffer(&buffers.indices, 0, indices);
+    queue.write_buffer(
+        &buffers.feature_metadata,
+        0,
+        bytemuck::cast_slice(&feature_metadata),
+    );
+
+    Some(buffers)
+}
@@ -21,6 +21,12 @@ pub mod scheduler {
     pub use super::noweb::scheduler::*;
 }
 
+/// Disk cache of tiles for non-web targets.
+pub mod tile_cache {
+    #[cfg(not(target_arch = "wasm32"))]
+    pub use super::noweb::tile_cache::*;
+}
+
 pub mod trace {
     #[cfg(not(target_arch = "wasm32"))]
     pub use super::noweb::trace::*;
@@ -1,13 +1,32 @@
 use async_trait::async_trait;
-use reqwest::{Client, StatusCode};
+use reqwest::{
+    header::{HeaderMap, LOCATION},
+    redirect, Client, StatusCode,
+};
 use reqwest_middleware::ClientWithMiddleware;
 use reqwest_middleware_cache::{managers::CACacheManager, Cache, CacheMode};
 
-use crate::io::source_client::{HttpClient, SourceFetchError};
+use crate::{
+    io::{
+        redirect::{follow_redirects, FetchResponse, RedirectPolicy},
+        source_client::{HttpClient, SourceFetchError, TileTooLargeError},
+    },
+    platform::tile_cache::{CacheCompression, TileCache},
+};
+
+/// The header in which the cache middleware reports whether a response has been served from the
+/// cache
+const CACHE_STATUS_HEADER: &str = "x-cache";
 
 #[derive(Clone)]
 pub struct ReqwestHttpClient {
     client: ClientWithMiddleware,
+    /// Fetches tiles without the cache middleware, as they are cached by the `tile_cache`
+    tile_client: ClientWithMiddleware,
+    redirect_policy: RedirectPolicy,
+    /// Whether responses are cached, see [`ReqwestHttpClient::new`]
+    has_cache: bool,
+    tile_cache: Option<TileCache>,
 }
 
 impl From<reqwest::Error> for SourceFetchError {
@@ -23,10 +42,26 @@ impl From<reqwest_middleware::Error> for SourceFetchError {
 }
 
 impl ReqwestHttpClient {
-    /// cache_path: Under which path should we cache requests.
+    /// cache_path: Under which path should we cache requests. Tiles are stored by their key, see
+    /// [`crate::io::tile_key::TileKey`], in the [`TileCache`] within the `tiles` directory of the
+    /// path. Other responses are cached according to their HTTP headers.
     // TODO: Use Into<Path> instead of String
     pub fn new(cache_path: Option<String>) -> Self {
-        let mut builder = reqwest_middleware::ClientBuilder::new(Client::new());
+        // Redirects are followed explicitly, see `RedirectPolicy`
+        let client = Client::builder()
+            .redirect(redirect::Policy::none())
+            .build()
+            .expect("failed to build HTTP client");
+        let tile_client = reqwest_middleware::ClientBuilder::new(client.clone()).build();
+        let mut builder = reqwest_middleware::ClientBuilder::new(client);
+        let has_cache = cache_path.is_some();
+
+        let tile_cache = cache_path.as_ref().map(|cache_path| {
+            TileCache::new(
+                std::path::Path::new(cache_path).join("tiles"),
+                CacheCompression::default(),
+            )
+        });
 
         if let Some(cache_path) = cache_path {
             builder = builder.with(Cache {
@@ -37,26 +72,234 @@ impl ReqwestHttpClient {
 
         Self {
             client: builder.build(),
+            tile_client,
+            redirect_policy: RedirectPolicy::default(),
+            has_cache,
+            tile_cache,
         }
     }
-}
 
-#[cfg_attr(not(feature = "thread-safe-futures"), async_trait(?Send))]
-#[cfg_attr(feature = "thread-safe-futures", async_trait)]
-impl HttpClient for ReqwestHttpClient {
-    async fn fetch(&self, url: &str) -> Result<Vec<u8>, SourceFetchError> {
-        let response = self.client.get(url).send().await?;
+    pub fn with_redirect_policy(mut self, redirect_policy: RedirectPolicy) -> Self {
+        self.redirect_policy = redirect_policy;
+        self
+    }
+
+    /// Stores the cached tiles with the `compression`. This has no effect without a cache path.
+    pub fn with_cache_compression(mut self, compression: CacheCompression) -> Self {
+        self.tile_cache = self
+            .tile_cache
+            .map(|tile_cache| tile_cache.with_compression(compression));
+        self
+    }
+
+    /// Requests the `url` once. Reading the body stops as soon as it exceeds `max_size` bytes.
+    /// Returns the body and whether it has been served from the cache, if known.
+    async fn fetch_once(
+        &self,
+        client: &ClientWithMiddleware,
+        url: String,
+        max_size: usize,
+    ) -> Result<FetchResponse<(Vec<u8>, Option<bool>)>, SourceFetchError> {
+        let response = client.get(&url).send().await?;
+
+        let is_redirect = matches!(
+            response.status(),
+            StatusCode::MOVED_PERMANENTLY
+                | StatusCode::FOUND
+                | StatusCode::SEE_OTHER
+                | StatusCode::TEMPORARY_REDIRECT
+                | StatusCode::PERMANENT_REDIRECT
+        );
+        if is_redirect {
+            if let Some(location) = response
+                .headers()
+                .get(LOCATION)
+                .and_then(|location| location.to_str().ok())
+            {
+                return Ok(FetchResponse::Redirect(location.to_string()));
+            }
+        }
+
         match response.error_for_status() {
-            Ok(response) => {
+            Ok(mut response) => {
                 if response.status() == StatusCode::NOT_MODIFIED {
                     log::info!("Using data from cache");
                 }
+                let cache_hit = if self.has_cache {
+                    is_cache_hit(response.status(), response.headers())
+                } else {
+                    None
+                };
 
-                let body = response.bytes().await?;
+                // The announced length is checked before anything is read
+                if let Some(length) = response.content_length() {
+                    let size = usize::try_from(length).unwrap_or(usize::MAX);
+                    if size > max_size {
+                        return Err(TileTooLargeError { size, max_size }.into());
+                    }
+                }
+
+                let mut body = Vec::new();
+                while let Some(chunk) = response.chunk().await? {
+                    body.extend_from_slice(&chunk);
+                    if body.len() > max_size {
+                        return Err(TileTooLargeError {
+                            size: body.len(),
+                            max_size,
+                        }
+                        .into());
+                    }
+                }
 
-                Ok(Vec::from(body.as_ref()))
+                Ok(FetchResponse::Data((body, cache_hit)))
             }
             Err(e) => Err(SourceFetchError(Box::new(e))),
         }
     }
 }
+
+#[cfg_attr(not(feature = "thread-safe-futures"), async_trait(?Send))]
+#[cfg_attr(feature = "thread-safe-futures", async_trait)]
+impl HttpClient for ReqwestHttpClient {
+    async fn fetch(&self, url: &str) -> Result<Vec<u8>, SourceFetchError> {
+        let (data, _cache_hit) = follow_redirects(url, self.redirect_policy, |url| {
+            self.fetch_once(&self.client, url, usize::MAX)
+        })
+        .await?;
+        Ok(data)
+    }
+
+    async fn fetch_tile(&self, url: &str, key: &str) -> Result<Vec<u8>, SourceFetchError> {
+        let (data, _cache_hit) = self.fetch_cached_tile(url, key, usize::MAX).await?;
+        Ok(data)
+    }
+
+    async fn fetch_cached_tile(
+        &self,
+        url: &str,
+        key: &str,
+        max_size: usize,
+    ) -> Result<(Vec<u8>, Option<bool>), SourceFetchError> {
+        let Some(tile_cache) = &self.tile_cache else {
+            return follow_redirects(url, self.redirect_policy, |url| {
+                self.fetch_once(&self.client, url, max_size)
+            })
+            .await;
+        };
+
+        if let Some(data) = tile_cache.get(key).await {
+            if data.len() > max_size {
+                return Err(TileTooLargeError {
+                    size: data.len(),
+                    max_size,
+                }
+                .into());
+            }
+            return Ok((data, Some(true)));
+        }
+
+        let (data, _cache_hit) = follow_redirects(url, self.redirect_policy, |url| {
+            self.fetch_once(&self.tile_client, url, max_size)
+        })
+        .await?;
+        tile_cache.put(key, &data).await;
+        Ok((data, Some(false)))
+    }
+}
+
+/// Whether a response with the `status` and `headers` has been served from the cache. Revalidated
+/// responses count as hits. Returns `None` if the cache did not report its status.
+fn is_cache_hit(status: StatusCode, headers: &HeaderMap) -> Option<bool> {
+    if status == StatusCode::NOT_MODIFIED {
+        return Some(true);
+    }
+
+    let cache_status = headers.get(CACHE_STATUS_HEADER)?.to_str().ok()?;
+    Some(cache_status.trim().eq_ignore_ascii_case("hit"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        net::TcpListener,
+    };
+
+    use reqwest::{
+        header::{HeaderMap, HeaderValue},
+        StatusCode,
+    };
+
+    use super::{is_cache_hit, ReqwestHttpClient, CACHE_STATUS_HEADER};
+    use crate::{io::source_client::HttpClient, platform::tile_cache::tests::TempCache};
+
+    /// Serves `requests` requests, each with the number of the request as body. Returns the url of
+    /// the server.
+    fn serve_request_numbers(requests: usize) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/0/0/0.pbf", listener.local_addr().unwrap());
+
+        std::thread::spawn(move || {
+            for number in 1..=requests {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut request = Vec::new();
+                let mut buffer = [0; 1024];
+                while !request.ends_with(b"\r\n\r\n") {
+                    let read = stream.read(&mut buffer).unwrap();
+                    request.extend_from_slice(&buffer[..read]);
+                }
+
+                let body = number.to_string();
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+
+        url
+    }
+
+    #[tokio::test]
+    async fn test_tile_cache_key() {
+        let dir = TempCache::new("http-client-tile-key");
+        let url = serve_request_numbers(2);
+        let client = ReqwestHttpClient::new(Some(dir.0.to_str().unwrap().to_string()));
+
+        let fetch = |key| client.fetch_cached_tile(&url, key, usize::MAX);
+        assert_eq!(
+            fetch("v1/root").await.unwrap(),
+            (b"1".to_vec(), Some(false))
+        );
+        assert_eq!(fetch("v1/root").await.unwrap(), (b"1".to_vec(), Some(true)));
+
+        // The same url is fetched again for a different key
+        assert_eq!(
+            fetch("v2/root").await.unwrap(),
+            (b"2".to_vec(), Some(false))
+        );
+        assert_eq!(fetch("v1/root").await.unwrap(), (b"1".to_vec(), Some(true)));
+
+        let tiles = dir.0.join("tiles");
+        assert_eq!(cacache::read(&tiles, "v1/root").await.unwrap(), b"1");
+        assert_eq!(cacache::read(&tiles, "v2/root").await.unwrap(), b"2");
+    }
+
+    #[test]
+    fn test_is_cache_hit() {
+        let headers = |cache_status: &'static str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(CACHE_STATUS_HEADER, HeaderValue::from_static(cache_status));
+            headers
+        };
+
+        assert_eq!(is_cache_hit(StatusCode::OK, &headers("HIT")), Some(true));
+        assert_eq!(is_cache_hit(StatusCode::OK, &headers("MISS")), Some(false));
+        assert_eq!(is_cache_hit(StatusCode::OK, &HeaderMap::new()), None);
+        assert_eq!(
+            is_cache_hit(StatusCode::NOT_MODIFIED, &HeaderMap::new()),
+            Some(true)
+        );
+    }
+}
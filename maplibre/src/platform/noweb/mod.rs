@@ -13,6 +13,7 @@ use crate::{
 
 pub mod http_client;
 pub mod scheduler;
+pub mod tile_cache;
 pub mod trace;
 
 pub fn run_multithreaded<F: Future>(future: F) -> F::Output {
@@ -35,16 +36,22 @@ pub fn run_multithreaded<F: Future>(future: F) -> F::Output {
         .block_on(future)
 }
 
-pub struct ReqwestOffscreenKernelEnvironment;
+pub struct ReqwestOffscreenKernelEnvironment {
+    source_client: SourceClient<ReqwestHttpClient>,
+}
 
 impl OffscreenKernelEnvironment for ReqwestOffscreenKernelEnvironment {
     type HttpClient = ReqwestHttpClient;
 
-    fn create() -> Self {
-        ReqwestOffscreenKernelEnvironment
+    fn create(source_client: Option<SourceClient<Self::HttpClient>>) -> Self {
+        ReqwestOffscreenKernelEnvironment {
+            source_client: source_client.unwrap_or_else(|| {
+                SourceClient::new(HttpSourceClient::new(ReqwestHttpClient::new(None)))
+            }),
+        }
     }
 
     fn source_client(&self) -> SourceClient<Self::HttpClient> {
-        SourceClient::new(HttpSourceClient::new(ReqwestHttpClient::new(None)))
+        self.source_client.clone()
     }
 }
@@ -1,13 +1,47 @@
-use std::future::Future;
+use std::{
+    future::Future,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+};
+
+use tokio::task::JoinHandle;
 
 use crate::io::scheduler::{ScheduleError, Scheduler};
 
 /// Multi-threading with Tokio.
-pub struct TokioScheduler;
+pub struct TokioScheduler {
+    is_shut_down: AtomicBool,
+    /// The tasks which might still be running
+    tasks: Mutex<Vec<JoinHandle<()>>>,
+}
 
 impl TokioScheduler {
     pub fn new() -> Self {
-        Self {}
+        Self {
+            is_shut_down: AtomicBool::new(false),
+            tasks: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Stops accepting new work and waits until the scheduled tasks have finished. Resources
+    /// which are held by the tasks, like HTTP connections, are released once this returns. Work
+    /// which is scheduled afterwards fails with [`ScheduleError::ShutDown`]. Unlike
+    /// [`Scheduler::shutdown`], the current work is not cancelled.
+    pub async fn shutdown_gracefully(&self) {
+        self.is_shut_down.store(true, Ordering::SeqCst);
+
+        let tasks = std::mem::take(&mut *self.tasks.lock().expect("tasks lock is poisoned"));
+        for task in tasks {
+            if let Err(e) = task.await {
+                log::error!("scheduled task failed during shutdown: {e}");
+            }
+        }
+    }
+
+    pub fn is_shut_down(&self) -> bool {
+        self.is_shut_down.load(Ordering::SeqCst)
     }
 }
 
@@ -20,7 +54,13 @@ impl Scheduler for TokioScheduler {
     where
         T: Future<Output = ()> + Send + 'static,
     {
-        tokio::task::spawn((future_factory)());
+        if self.is_shut_down() {
+            return Err(ScheduleError::ShutDown);
+        }
+
+        let mut tasks = self.tasks.lock().expect("tasks lock is poisoned");
+        tasks.retain(|task| !task.is_finished());
+        tasks.push(tokio::task::spawn((future_factory)()));
         Ok(())
     }
 
@@ -33,8 +73,21 @@ impl Scheduler for TokioScheduler {
     where
         T: Future<Output = ()> + 'static,
     {
+        if self.is_shut_down() {
+            return Err(ScheduleError::ShutDown);
+        }
+
         Ok(())
     }
+
+    fn shutdown(&self) {
+        self.is_shut_down.store(true, Ordering::SeqCst);
+
+        let tasks = std::mem::take(&mut *self.tasks.lock().expect("tasks lock is poisoned"));
+        for task in tasks {
+            task.abort();
+        }
+    }
 }
 
 impl Default for TokioScheduler {
@@ -42,3 +95,70 @@ impl Default for TokioScheduler {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "thread-safe-futures")]
+    use std::{
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    use super::TokioScheduler;
+    use crate::io::scheduler::{ScheduleError, Scheduler};
+
+    #[tokio::test]
+    async fn test_shutdown() {
+        let scheduler = TokioScheduler::new();
+
+        // Work which never finishes on its own and holds a resource. It is tracked like the
+        // work of `schedule`, which only spawns tasks with thread-safe futures.
+        let (resource, released) = tokio::sync::oneshot::channel::<()>();
+        scheduler
+            .tasks
+            .lock()
+            .unwrap()
+            .push(tokio::task::spawn(async move {
+                let _resource = resource;
+                std::future::pending::<()>().await;
+            }));
+
+        Scheduler::shutdown(&scheduler);
+
+        // The resource is released once the work is cancelled
+        assert!(released.await.is_err());
+        assert!(matches!(
+            scheduler.schedule(|| async {}),
+            Err(ScheduleError::ShutDown)
+        ));
+    }
+
+    #[cfg(feature = "thread-safe-futures")]
+    #[tokio::test]
+    async fn test_shutdown_gracefully() {
+        let scheduler = TokioScheduler::new();
+        let finished = Arc::new(AtomicBool::new(false));
+
+        let task_finished = finished.clone();
+        scheduler
+            .schedule(move || async move {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                task_finished.store(true, Ordering::SeqCst);
+            })
+            .unwrap();
+
+        // The current work is finished before the shutdown returns
+        tokio::time::timeout(Duration::from_secs(1), scheduler.shutdown_gracefully())
+            .await
+            .expect("shutdown did not return");
+        assert!(finished.load(Ordering::SeqCst));
+
+        assert!(matches!(
+            scheduler.schedule(|| async {}),
+            Err(ScheduleError::ShutDown)
+        ));
+    }
+}
@@ -0,0 +1,151 @@
+//! Stores fetched tiles on disk.
+
+use std::{
+    io::{self, Read, Write},
+    path::PathBuf,
+};
+
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+
+/// The magic bytes at the start of gzip data
+const GZIP: &[u8] = &[0x1F, 0x8B];
+
+/// How the entries of the [`TileCache`] are stored. Vector tiles compress well, so gzip
+/// considerably reduces the size of offline regions.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CacheCompression {
+    /// Entries are stored as they are fetched.
+    #[default]
+    None,
+    /// Entries are compressed with gzip.
+    Gzip,
+}
+
+impl CacheCompression {
+    /// Encodes the `data` of an entry before it is written to the cache.
+    fn compress(&self, data: &[u8]) -> io::Result<Vec<u8>> {
+        match self {
+            CacheCompression::None => Ok(data.to_vec()),
+            CacheCompression::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(data)?;
+                encoder.finish()
+            }
+        }
+    }
+
+    /// Decodes an entry which has been read from the cache. Compressed entries are detected by
+    /// their content, such that entries which have been written with a different compression
+    /// remain readable.
+    fn decompress(data: Vec<u8>) -> io::Result<Vec<u8>> {
+        if !data.starts_with(GZIP) {
+            return Ok(data);
+        }
+
+        let mut decompressed = Vec::new();
+        GzDecoder::new(data.as_slice()).read_to_end(&mut decompressed)?;
+        Ok(decompressed)
+    }
+}
+
+/// A disk cache of tiles, which is backed by cacache. Tiles are stored without an expiry, so
+/// outdated tiles are invalidated by changing their key.
+#[derive(Clone)]
+pub struct TileCache {
+    path: PathBuf,
+    compression: CacheCompression,
+}
+
+impl TileCache {
+    pub fn new(path: impl Into<PathBuf>, compression: CacheCompression) -> Self {
+        Self {
+            path: path.into(),
+            compression,
+        }
+    }
+
+    pub fn with_compression(mut self, compression: CacheCompression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Reads the tile which is stored under the `key`. Returns `None` if the tile is not cached
+    /// or can not be read.
+    pub async fn get(&self, key: &str) -> Option<Vec<u8>> {
+        let data = match cacache::read(&self.path, key).await {
+            Ok(data) => data,
+            Err(cacache::Error::EntryNotFound(..)) => return None,
+            Err(e) => {
+                log::warn!("failed to read tile {key} from cache: {e}");
+                return None;
+            }
+        };
+
+        CacheCompression::decompress(data)
+            .map_err(|e| log::warn!("failed to decompress tile {key} from cache: {e}"))
+            .ok()
+    }
+
+    /// Stores the `data` of a tile under the `key`. Failures are logged, as the tile can still be
+    /// used.
+    pub async fn put(&self, key: &str, data: &[u8]) {
+        let result = match self.compression.compress(data) {
+            Ok(compressed) => cacache::write(&self.path, key, compressed)
+                .await
+                .map(|_| ())
+                .map_err(|e| e.to_string()),
+            Err(e) => Err(e.to_string()),
+        };
+
+        if let Err(e) = result {
+            log::warn!("failed to write tile {key} to cache: {e}");
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::path::PathBuf;
+
+    use super::{CacheCompression, TileCache};
+
+    /// A cache directory which is removed afterwards
+    pub struct TempCache(pub PathBuf);
+
+    impl TempCache {
+        pub fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("maplibre-{name}-{}", std::process::id()));
+            let _ = std::fs::remove_dir_all(&path);
+            Self(path)
+        }
+    }
+
+    impl Drop for TempCache {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_round_trip() {
+        let dir = TempCache::new("tile-cache-round-trip");
+
+        // Field 3 (layers) of a vector tile, followed by repetitive data
+        let tile = [&[0x1A, 0x80, 0x01][..], &[7; 128]].concat();
+
+        let cache = TileCache::new(&dir.0, CacheCompression::Gzip);
+        assert_eq!(cache.get("213").await, None);
+        cache.put("213", &tile).await;
+
+        let stored = cacache::read(&dir.0, "213").await.unwrap();
+        assert!(stored.len() < tile.len());
+        assert_eq!(cache.get("213").await, Some(tile.clone()));
+
+        // Uncompressed entries are stored as they are and stay readable with any compression
+        let cache = TileCache::new(&dir.0, CacheCompression::None);
+        cache.put("0", &tile).await;
+        assert_eq!(cacache::read(&dir.0, "0").await.unwrap(), tile);
+        let cache = TileCache::new(&dir.0, CacheCompression::Gzip);
+        assert_eq!(cache.get("0").await, Some(tile));
+    }
+}
@@ -0,0 +1,293 @@
+//! Map projections which convert between [`LatLon`] and [`WorldCoords`].
+
+use std::f64::consts::PI;
+
+use serde::{Deserialize, Serialize};
+
+use crate::coords::{LatLon, WorldCoords, WorldTileCoords, Zoom, EXTENT, TILE_SIZE};
+
+/// A projection of the earth onto the square tile grid.
+///
+/// Implementations only need to map geographic coordinates to normalized coordinates within the
+/// unit square. The origin of the unit square is in the upper-left corner, like the origin of
+/// [`WorldCoords`].
+pub trait Projection {
+    /// Projects `lat_lon` to normalized coordinates within `0.0..=1.0`.
+    fn project_unit(&self, lat_lon: LatLon) -> (f64, f64);
+
+    /// Inverse of [`Projection::project_unit`].
+    fn unproject_unit(&self, x: f64, y: f64) -> LatLon;
+
+    /// The size of the whole world in [`WorldCoords`] at the `zoom`.
+    fn world_size(&self, zoom: Zoom) -> f64 {
+        TILE_SIZE * Zoom::new(0.0).scale_delta(&zoom)
+    }
+
+    fn project(&self, lat_lon: LatLon, zoom: Zoom) -> WorldCoords {
+        let world_size = self.world_size(zoom);
+        let (x, y) = self.project_unit(lat_lon);
+        WorldCoords::at_ground(x * world_size, y * world_size)
+    }
+
+    fn unproject(&self, world: WorldCoords, zoom: Zoom) -> LatLon {
+        let world_size = self.world_size(zoom);
+        self.unproject_unit(world.x / world_size, world.y / world_size)
+    }
+}
+
+/// The Web Mercator projection (EPSG:3857) which is used by the tile grid of most tile servers.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct WebMercator;
+
+impl Projection for WebMercator {
+    fn project_unit(&self, lat_lon: LatLon) -> (f64, f64) {
+        let x = (lat_lon.longitude + 180.0) / 360.0;
+
+        let lat_rad = lat_lon.latitude.to_radians();
+        let merc_n = f64::ln(f64::tan((PI / 4.0) + (lat_rad / 2.0)));
+        let y = 0.5 - merc_n / (2.0 * PI);
+
+        (x, y)
+    }
+
+    fn unproject_unit(&self, x: f64, y: f64) -> LatLon {
+        let longitude = x * 360.0 - 180.0;
+
+        let merc_n = (0.5 - y) * (2.0 * PI);
+        let latitude = merc_n.sinh().atan().to_degrees();
+
+        LatLon {
+            latitude,
+            longitude,
+        }
+    }
+}
+
+/// The equirectangular projection (plate carrée) which maps longitude and latitude linearly to
+/// `x` and `y`. The whole globe fits into the square tile grid, which means that distances along
+/// `y` are stretched twice as much as along `x`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Equirectangular;
+
+impl Projection for Equirectangular {
+    fn project_unit(&self, lat_lon: LatLon) -> (f64, f64) {
+        let x = (lat_lon.longitude + 180.0) / 360.0;
+        let y = (90.0 - lat_lon.latitude) / 180.0;
+        (x, y)
+    }
+
+    fn unproject_unit(&self, x: f64, y: f64) -> LatLon {
+        LatLon {
+            latitude: 90.0 - y * 180.0,
+            longitude: x * 360.0 - 180.0,
+        }
+    }
+}
+
+/// The latitude at which [`WebMercator`] reaches the top of the tile grid.
+const WEB_MERCATOR_MAX_LATITUDE: f64 = 85.0511287798066;
+
+/// The coordinate reference system of the tiles of a source. Tiles are always addressed with the
+/// square tile grid, but their geometry is projected with the projection of the CRS.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SourceCrs {
+    /// Web Mercator, which is also used to render the map
+    #[default]
+    #[serde(rename = "EPSG:3857")]
+    WebMercator,
+    /// Longitude and latitude in degrees, which are laid out as the [`Equirectangular`] projection
+    #[serde(rename = "EPSG:4326")]
+    Wgs84,
+}
+
+impl SourceCrs {
+    /// Reprojects the `position` within the tile at `from` of this CRS to the position within the
+    /// [`WebMercator`] tile at `to`. Both tiles are at the same zoom level. Positions are in
+    /// [`EXTENT`] units, like the geometry of vector tiles, and can be outside of `to`.
+    ///
+    /// As both projections map longitude linearly, only the `y` coordinate changes. Latitudes
+    /// beyond the range of Web Mercator are clamped to the edge of the tile grid.
+    pub fn reproject_to_web_mercator(
+        &self,
+        position: [f64; 2],
+        from: WorldTileCoords,
+        to: WorldTileCoords,
+    ) -> [f64; 2] {
+        let tiles = f64::from(1u32 << u8::from(from.z));
+        let unit_x = (from.x as f64 + position[0] / EXTENT) / tiles;
+        let unit_y = (from.y as f64 + position[1] / EXTENT) / tiles;
+
+        let (x, y) = match self {
+            SourceCrs::WebMercator => (unit_x, unit_y),
+            SourceCrs::Wgs84 => {
+                let mut lat_lon = Equirectangular.unproject_unit(unit_x, unit_y);
+                lat_lon.latitude = lat_lon
+                    .latitude
+                    .clamp(-WEB_MERCATOR_MAX_LATITUDE, WEB_MERCATOR_MAX_LATITUDE);
+                WebMercator.project_unit(lat_lon)
+            }
+        };
+
+        [
+            (x * tiles - to.x as f64) * EXTENT,
+            (y * tiles - to.y as f64) * EXTENT,
+        ]
+    }
+
+    /// The tiles of this CRS which cover the [`WebMercator`] tile at `coords`, from north to
+    /// south. Both tile grids have the same columns, but their rows span different latitudes, so
+    /// the geometry of a Web Mercator tile can come from several tiles of this CRS.
+    pub fn covering_tiles(&self, coords: WorldTileCoords) -> Vec<WorldTileCoords> {
+        let projection = match self {
+            SourceCrs::WebMercator => return vec![coords],
+            SourceCrs::Wgs84 => Equirectangular,
+        };
+
+        let tiles = f64::from(1u32 << u8::from(coords.z));
+        let row = |y: f64| {
+            let (_, unit_y) = projection.project_unit(WebMercator.unproject_unit(0.0, y / tiles));
+            unit_y * tiles
+        };
+
+        let first = row(coords.y as f64).floor() as i32;
+        // The southern edge of the tile belongs to the next row, unless it is within the row
+        let last = ((row(coords.y as f64 + 1.0) - 1e-9).ceil() as i32 - 1).max(first);
+
+        (first..=last)
+            .map(|y| WorldTileCoords::from((coords.x, y, coords.z)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Equirectangular, Projection, SourceCrs, WebMercator};
+    use crate::coords::{LatLon, WorldTileCoords, Zoom, ZoomLevel};
+
+    fn assert_round_trip(projection: &dyn Projection) {
+        let zoom = Zoom::new(5.5);
+
+        for lat_lon in [
+            LatLon::new(48.137154, 11.576124),
+            LatLon::new(-33.8688, 151.2093),
+            LatLon::new(0.0, 0.0),
+        ] {
+            let round_trip = projection.unproject(projection.project(lat_lon, zoom), zoom);
+
+            assert!((round_trip.latitude - lat_lon.latitude).abs() < 1e-9);
+            assert!((round_trip.longitude - lat_lon.longitude).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        assert_round_trip(&WebMercator);
+        assert_round_trip(&Equirectangular);
+    }
+
+    #[test]
+    fn test_web_mercator() {
+        let zoom = Zoom::new(0.0);
+
+        let center = WebMercator.project(LatLon::new(0.0, 0.0), zoom);
+        assert!((center.x - 256.0).abs() < 1e-9);
+        assert!((center.y - 256.0).abs() < 1e-9);
+
+        // The maximum latitude of Web Mercator is projected to the top of the tile grid
+        let north_west = WebMercator.project(LatLon::new(85.0511287798066, -180.0), zoom);
+        assert!(north_west.x.abs() < 1e-9);
+        assert!(north_west.y.abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_equirectangular() {
+        let zoom = Zoom::new(0.0);
+
+        let center = Equirectangular.project(LatLon::new(0.0, 0.0), zoom);
+        assert_eq!((center.x, center.y), (256.0, 256.0));
+
+        let north_west = Equirectangular.project(LatLon::new(90.0, -180.0), zoom);
+        assert_eq!((north_west.x, north_west.y), (0.0, 0.0));
+    }
+
+    #[test]
+    fn test_reproject_wgs84() {
+        let world = WorldTileCoords::from((0, 0, ZoomLevel::default()));
+        let reproject =
+            |position, coords| SourceCrs::Wgs84.reproject_to_web_mercator(position, coords, coords);
+
+        // The longitude of 90°E is at three quarters of the width in both projections, while the
+        // latitude of 45°N moves from a quarter of the height to ln(tan(67.5°)) / 2π above the
+        // center
+        let [x, y] = reproject([3072.0, 1024.0], world);
+        assert!((x - 3072.0).abs() < 1e-9);
+        assert!((y - 1473.4).abs() < 0.1);
+
+        // The equator is in the center of both projections
+        let [_, y] = reproject([0.0, 2048.0], world);
+        assert!((y - 2048.0).abs() < 1e-9);
+
+        // The poles are clamped to the edge of the tile grid
+        let [_, y] = reproject([0.0, 0.0], world);
+        assert!(y.abs() < 1e-6);
+
+        // Positions are relative to the tile, here the tile south-east of the center at zoom 1
+        let tile = WorldTileCoords::from((1, 1, ZoomLevel::from(1)));
+        let [x, y] = reproject([2048.0, 0.0], tile);
+        assert!((x - 2048.0).abs() < 1e-9);
+        assert!(y.abs() < 1e-9);
+
+        assert_eq!(
+            SourceCrs::WebMercator.reproject_to_web_mercator([1.0, 2.0], world, world),
+            [1.0, 2.0]
+        );
+    }
+
+    #[test]
+    fn test_reproject_into_neighbor() {
+        // 45°N is at the top of the second row of tiles at zoom 2 in EPSG:4326. The first row of
+        // Web Mercator only spans the latitudes from 85°N to 66.5°N, so it is below that tile.
+        let from = WorldTileCoords::from((1, 1, ZoomLevel::from(2)));
+        let to = WorldTileCoords::from((1, 0, ZoomLevel::from(2)));
+        let [x, y] = SourceCrs::Wgs84.reproject_to_web_mercator([1024.0, 0.0], from, to);
+        assert!((x - 1024.0).abs() < 1e-9);
+        assert!(y > 4096.0, "{y}");
+
+        // The second row of Web Mercator contains the position
+        let below = WorldTileCoords::from((1, 1, ZoomLevel::from(2)));
+        let [_, y_below] = SourceCrs::Wgs84.reproject_to_web_mercator([1024.0, 0.0], from, below);
+        assert!((y - y_below - 4096.0).abs() < 1e-9);
+        assert!((0.0..4096.0).contains(&y_below), "{y_below}");
+
+        // Positions in Web Mercator are moved to the other tile
+        let [_, y] = SourceCrs::WebMercator.reproject_to_web_mercator([0.0, 1.0], from, to);
+        assert_eq!(y, 4097.0);
+    }
+
+    #[test]
+    fn test_covering_tiles() {
+        let tile = |x, y, z| WorldTileCoords::from((x, y, ZoomLevel::from(z)));
+
+        assert_eq!(
+            SourceCrs::WebMercator.covering_tiles(tile(1, 1, 2)),
+            vec![tile(1, 1, 2)]
+        );
+
+        // The whole Web Mercator world lies within the single tile of EPSG:4326
+        assert_eq!(
+            SourceCrs::Wgs84.covering_tiles(tile(0, 0, 0)),
+            vec![tile(0, 0, 0)]
+        );
+
+        // The second row of Web Mercator spans from 66.5°N to the equator, which is covered by the
+        // first two rows of EPSG:4326 at zoom 2
+        assert_eq!(
+            SourceCrs::Wgs84.covering_tiles(tile(2, 1, 2)),
+            vec![tile(2, 0, 2), tile(2, 1, 2)]
+        );
+        assert_eq!(
+            SourceCrs::Wgs84.covering_tiles(tile(2, 0, 2)),
+            vec![tile(2, 0, 2)]
+        );
+    }
+}
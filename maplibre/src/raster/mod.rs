@@ -9,12 +9,16 @@ use crate::{
     plugin::Plugin,
     raster::{
         populate_world_system::PopulateWorldSystem, queue_system::queue_system,
-        request_system::RequestSystem, resource::RasterResources, resource_system::resource_system,
+        request_system::RequestSystem, resource_system::resource_system,
         upload_system::upload_system,
     },
     render::{eventually::Eventually, tile_view_pattern::ViewTileSources, RenderStageLabel},
     schedule::Schedule,
-    tcs::{system::SystemContainer, tiles::TileComponent, world::World},
+    tcs::{
+        system::SystemContainer,
+        tiles::{TileComponent, TileState},
+        world::World,
+    },
 };
 
 mod populate_world_system;
@@ -24,9 +28,16 @@ mod render_commands;
 mod request_system;
 mod resource;
 mod resource_system;
+mod timeline;
 mod transferables;
 mod upload_system;
 
+pub(crate) use populate_world_system::insert_raster_tile;
+pub(crate) use resource::RasterResources;
+pub use process_raster::{
+    process_raster_tile, ProcessRasterContext, ProcessRasterError, RasterTileRequest,
+};
+pub use timeline::{FrameRequest, RasterTimeline};
 pub use transferables::{
     DefaultRasterTransferables, LayerRaster, LayerRasterMissing, RasterTransferables,
 };
@@ -91,6 +102,40 @@ pub enum RasterLayerData {
 #[derive(Default)]
 pub struct RasterLayersDataComponent {
     pub layers: Vec<RasterLayerData>,
+    /// The timestamp of the requested frame, see [`RasterTimeline`]
+    pub time: Option<String>,
 }
 
-impl TileComponent for RasterLayersDataComponent {}
+impl TileComponent for RasterLayersDataComponent {
+    fn tile_state(&self) -> Option<TileState> {
+        let available = self
+            .layers
+            .iter()
+            .filter(|layer| matches!(layer, RasterLayerData::Available(_)))
+            .count();
+
+        Some(if self.layers.is_empty() {
+            TileState::Loading
+        } else if available == self.layers.len() {
+            TileState::Loaded
+        } else if available == 0 {
+            TileState::Unavailable
+        } else {
+            TileState::Partial
+        })
+    }
+
+    fn byte_size(&self) -> usize {
+        let host_bytes: usize = self
+            .layers
+            .iter()
+            .map(|layer| match layer {
+                RasterLayerData::Available(data) => data.image.as_raw().len(),
+                RasterLayerData::Missing(_) => 0,
+            })
+            .sum();
+
+        // The images are uploaded to the GPU as textures of the same size
+        2 * host_bytes
+    }
+}
@@ -2,14 +2,18 @@ use std::{borrow::Cow, marker::PhantomData, rc::Rc};
 
 use crate::{
     context::MapContext,
+    coords::WorldTileCoords,
     environment::Environment,
-    io::apc::{AsyncProcedureCall, Message},
+    io::apc::{AsyncProcedureCall, BufferedContext, Message},
     kernel::Kernel,
     raster::{
+        process_raster::{
+            process_raster_tile, ProcessRasterContext, ProcessRasterError, RasterTileRequest,
+        },
         transferables::{LayerRaster, LayerRasterMissing, RasterTransferables},
-        RasterLayerData, RasterLayersDataComponent,
+        DefaultRasterTransferables, RasterLayerData, RasterLayersDataComponent,
     },
-    tcs::system::System,
+    tcs::{system::System, world::World},
 };
 
 pub struct PopulateWorldSystem<E: Environment, T> {
@@ -36,26 +40,65 @@ impl<E: Environment, T: RasterTransferables> System for PopulateWorldSystem<E, T
             message.has_tag(T::LayerRaster::message_tag())
                 || message.has_tag(T::LayerRasterMissing::message_tag())
         }) {
-            let message: Message = message;
-            if message.has_tag(T::LayerRaster::message_tag()) {
-                let message = message.into_transferable::<T::LayerRaster>();
-                let Some(component) = world
-                        .tiles
-                        .query_mut::<&mut RasterLayersDataComponent>(message.coords()) else { continue; };
-
-                component
-                    .layers
-                    .push(RasterLayerData::Available(message.to_layer()));
-            } else if message.has_tag(T::LayerRaster::message_tag()) {
-                let message = message.into_transferable::<T::LayerRasterMissing>();
-                let Some(component) = world
-                    .tiles
-                    .query_mut::<&mut RasterLayersDataComponent>(message.coords()) else { continue; };
-
-                component
-                    .layers
-                    .push(RasterLayerData::Missing(message.to_layer()));
-            }
+            populate_world::<T>(world, message);
         }
     }
 }
+
+/// Stores the raster layer which is contained in the `message` in the tile of the `world`.
+pub(crate) fn populate_world<T: RasterTransferables>(world: &mut World, message: Message) {
+    if message.has_tag(T::LayerRaster::message_tag()) {
+        let message = message.into_transferable::<T::LayerRaster>();
+        let Some(component) = world
+            .tiles
+            .query_mut::<&mut RasterLayersDataComponent>(message.coords()) else { return; };
+
+        component
+            .layers
+            .push(RasterLayerData::Available(message.to_layer()));
+    } else if message.has_tag(T::LayerRasterMissing::message_tag()) {
+        let message = message.into_transferable::<T::LayerRasterMissing>();
+        let Some(component) = world
+            .tiles
+            .query_mut::<&mut RasterLayersDataComponent>(message.coords()) else { return; };
+
+        component
+            .layers
+            .push(RasterLayerData::Missing(message.to_layer()));
+    }
+}
+
+/// Decodes the raster tile `data` and stores it in the tile at `coords` of the `world`, as if the
+/// tile had been fetched. The layers of a tile which is already loaded are replaced, but a texture
+/// which has already been uploaded for the tile stays bound.
+///
+/// The `coords` have to be within the bounds of the world, see [`World::insert_tile`].
+pub(crate) fn insert_raster_tile(
+    world: &mut World,
+    coords: WorldTileCoords,
+    data: &[u8],
+) -> Result<(), ProcessRasterError> {
+    match world
+        .tiles
+        .query_mut::<&mut RasterLayersDataComponent>(coords)
+    {
+        Some(component) => component.layers.clear(),
+        None => {
+            world
+                .tiles
+                .spawn_mut(coords)
+                .expect("tile coordinates are checked by World::insert_tile")
+                .insert(RasterLayersDataComponent::default());
+        }
+    }
+
+    let mut context =
+        ProcessRasterContext::<DefaultRasterTransferables, _>::new(BufferedContext::default());
+    process_raster_tile(data, RasterTileRequest { coords }, &mut context)?;
+
+    for message in context.take_context().into_messages() {
+        populate_world::<DefaultRasterTransferables>(world, message);
+    }
+
+    Ok(())
+}
@@ -1,19 +1,30 @@
-use std::marker::PhantomData;
+use std::{io::Cursor, marker::PhantomData};
 
-use image::RgbaImage;
+use image::{
+    codecs::{jpeg::JpegDecoder, png::PngDecoder, webp::WebPDecoder},
+    error::{ImageFormatHint, UnsupportedError, UnsupportedErrorKind},
+    io::Limits,
+    ColorType, DynamicImage, ImageDecoder, ImageError, ImageFormat, RgbaImage,
+};
 use thiserror::Error;
 
 use crate::{
     coords::WorldTileCoords,
-    io::apc::Context,
+    io::apc::{Context, SendError},
     raster::transferables::{LayerRaster, RasterTransferables},
 };
 
 #[derive(Error, Debug)]
 pub enum ProcessRasterError {
+    /// Sending of results failed
+    #[error("sending data back through context failed")]
+    SendError(#[from] SendError),
+    /// The image data could not be decoded
+    #[error("decoding raster image failed")]
+    Decode(#[from] image::ImageError),
     /// Error during processing of the pipeline
     #[error("processing data in pipeline failed")]
-    Processing(Box<dyn std::error::Error>),
+    Processing(#[source] Box<dyn std::error::Error>),
 }
 
 pub struct RasterTileRequest {
@@ -26,13 +37,42 @@ pub fn process_raster_tile<T: RasterTransferables, C: Context>(
     context: &mut ProcessRasterContext<T, C>,
 ) -> Result<(), ProcessRasterError> {
     let coords = &tile_request.coords;
-    let img = image::load_from_memory(data).unwrap();
-    let rgba = img.to_rgba8();
+    let rgba = decode_rgba(data)?;
 
     context.layer_raster_finished(coords, "raster".to_string(), rgba)?;
 
     Ok(())
 }
+
+/// Decodes the image `data` without copying it. The format is detected once from the first bytes.
+fn decode_rgba(data: &[u8]) -> Result<RgbaImage, ImageError> {
+    match image::guess_format(data)? {
+        ImageFormat::Png => decode_with(PngDecoder::new(Cursor::new(data))?),
+        ImageFormat::Jpeg => decode_with(JpegDecoder::new(Cursor::new(data))?),
+        ImageFormat::WebP => decode_with(WebPDecoder::new(Cursor::new(data))?),
+        format => Err(ImageError::Unsupported(
+            UnsupportedError::from_format_and_kind(
+                ImageFormatHint::Exact(format),
+                UnsupportedErrorKind::Format(ImageFormatHint::Exact(format)),
+            ),
+        )),
+    }
+}
+
+/// Images which are stored as RGBA are decoded directly into a pre-sized buffer, such that only a
+/// single copy of the image is held. Other images are converted after decoding.
+fn decode_with<'a, D: ImageDecoder<'a>>(mut decoder: D) -> Result<RgbaImage, ImageError> {
+    decoder.set_limits(Limits::default())?;
+
+    if decoder.color_type() != ColorType::Rgba8 {
+        return Ok(DynamicImage::from_decoder(decoder)?.into_rgba8());
+    }
+
+    let (width, height) = decoder.dimensions();
+    let mut image = RgbaImage::new(width, height);
+    decoder.read_image(&mut image)?;
+    Ok(image)
+}
 pub struct ProcessRasterContext<T: RasterTransferables, C: Context> {
     context: C,
     phantom_t: PhantomData<T>,
@@ -48,6 +88,10 @@ impl<T: RasterTransferables, C: Context> ProcessRasterContext<T, C> {
 }
 
 impl<T: RasterTransferables, C: Context> ProcessRasterContext<T, C> {
+    pub fn take_context(self) -> C {
+        self.context
+    }
+
     fn layer_raster_finished(
         &mut self,
         coords: &WorldTileCoords,
@@ -56,31 +100,53 @@ impl<T: RasterTransferables, C: Context> ProcessRasterContext<T, C> {
     ) -> Result<(), ProcessRasterError> {
         self.context
             .send(T::LayerRaster::build_from(*coords, layer_name, image_data))
-            .map_err(|e| ProcessRasterError::Processing(Box::new(e)))
+            .map_err(ProcessRasterError::SendError)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::process_raster_tile;
+    use std::error::Error;
+
+    use image::{
+        codecs::png::{CompressionType, FilterType, PngEncoder},
+        ColorType, ImageEncoder, Rgba, RgbaImage,
+    };
+
+    use super::{decode_rgba, process_raster_tile};
     use crate::{
         coords::ZoomLevel,
         io::apc::tests::DummyContext,
         raster::{
-            process_raster::{ProcessRasterContext, RasterTileRequest},
+            process_raster::{ProcessRasterContext, ProcessRasterError, RasterTileRequest},
             DefaultRasterTransferables,
         },
     };
 
-    #[test] // TODO: Add proper tile byte array
-    #[ignore]
-    fn test() {
-        let _output = process_raster_tile(
+    #[test]
+    fn test_decode_error() {
+        let error = process_raster_tile(
             &[0],
             RasterTileRequest {
                 coords: (0, 0, ZoomLevel::default()).into(),
             },
             &mut ProcessRasterContext::<DefaultRasterTransferables, _>::new(DummyContext),
-        );
+        )
+        .unwrap_err();
+
+        assert!(matches!(error, ProcessRasterError::Decode(_)));
+        assert!(error.source().unwrap().is::<image::ImageError>());
+    }
+
+    #[test]
+    fn test_decode_rgba() {
+        let image = RgbaImage::from_fn(16, 16, |x, y| Rgba([x as u8, y as u8, 0, 255]));
+
+        let mut png = Vec::new();
+        PngEncoder::new_with_quality(&mut png, CompressionType::Fast, FilterType::NoFilter)
+            .write_image(&image, 16, 16, ColorType::Rgba8)
+            .unwrap();
+
+        assert_eq!(decode_rgba(&png).unwrap(), image);
     }
 }
@@ -9,16 +9,22 @@ use crate::{
         render_phase::{DrawState, LayerItem, RenderPhase, TileMaskItem},
         tile_view_pattern::WgpuTileViewPattern,
     },
+    style::layer::LayerPaint,
     tcs::tiles::Tile,
 };
 
-pub fn queue_system(MapContext { world, .. }: &mut MapContext) {
+pub fn queue_system(MapContext { world, style, .. }: &mut MapContext) {
     let Some((
         Initialized(tile_view_pattern),
     )) = world.resources.query::<(
         &Eventually<WgpuTileViewPattern>,
     )>() else { return; };
 
+    // Raster tiles are drawn at the position of the raster layer within the style
+    let Some(raster_layer) = style.layers.iter().find(|layer| {
+        matches!(layer.paint, Some(LayerPaint::Raster(_))) && !layer.is_hidden()
+    }) else { return; };
+
     let mut items = Vec::new();
 
     for view_tile in tile_view_pattern.iter() {
@@ -31,12 +37,13 @@ pub fn queue_system(MapContext { world, .. }: &mut MapContext) {
             items.push((
                 LayerItem {
                     draw_function: Box::new(DrawState::<LayerItem, DrawRasterTiles>::new()),
-                    index: 0,
-                    style_layer: "raster".to_string(),
+                    index: raster_layer.index,
+                    style_layer: raster_layer.id.clone(),
                     tile: Tile {
                         coords: source_shape.coords(),
                     },
                     source_shape: source_shape.clone(),
+                    indices: None,
                 },
                 // FIXME tsc: Tile masks are currently drawn twice by each plugin
                 TileMaskItem {
@@ -2,25 +2,49 @@
 
 use std::{borrow::Cow, collections::HashSet, marker::PhantomData, rc::Rc};
 
+use instant::Instant;
+
 use crate::{
     context::MapContext,
+    coords::{WorldTileCoords, ZoomLevel},
     environment::{Environment, OffscreenKernelEnvironment},
     io::{
         apc::{AsyncProcedureCall, AsyncProcedureFuture, Context, Input, ProcedureError},
+        request_log::{RequestKind, RequestLog, RequestPriority, RequestReplay},
+        request_settings::{RequestBudget, RequestSettings},
         source_type::{RasterSource, SourceType},
+        tile_format::TileFormat,
     },
     kernel::Kernel,
     raster::{
-        process_raster::{process_raster_tile, ProcessRasterContext, RasterTileRequest},
+        process_raster::{
+            process_raster_tile, ProcessRasterContext, ProcessRasterError, RasterTileRequest,
+        },
         transferables::{LayerRasterMissing, RasterTransferables},
-        RasterLayersDataComponent,
+        FrameRequest, RasterLayersDataComponent, RasterTimeline,
+    },
+    style::{
+        layer::LayerPaint,
+        source::{Source, VectorSource},
+        Style,
     },
-    style::layer::LayerPaint,
-    tcs::system::System,
+    tcs::{system::System, world::World},
 };
 
 pub struct RequestSystem<E: Environment, T: RasterTransferables> {
     kernel: Rc<Kernel<E>>,
+    /// The time at which the tiles of a changed view have been requested the last time
+    last_request: Option<Instant>,
+    /// Whether tiles of the view have been deferred to the next frame, see
+    /// [`RequestSettings::max_requests_per_frame`]
+    has_deferred: bool,
+    /// The timestamp of the frame of the [`RasterTimeline`] which has been requested last
+    requested_time: Option<String>,
+    /// Whether tiles of the view are still loading a previous frame of the [`RasterTimeline`]
+    has_stale_frames: bool,
+    /// The ids of the layers which have been hidden in the last frame, see
+    /// [`Style::hidden_layers`]
+    hidden_layers: HashSet<String>,
     phantom_t: PhantomData<T>,
 }
 
@@ -28,6 +52,11 @@ impl<E: Environment, T: RasterTransferables> RequestSystem<E, T> {
     pub fn new(kernel: &Rc<Kernel<E>>) -> Self {
         Self {
             kernel: kernel.clone(),
+            last_request: None,
+            has_deferred: false,
+            requested_time: None,
+            has_stale_frames: false,
+            hidden_layers: HashSet::new(),
             phantom_t: Default::default(),
         }
     }
@@ -47,59 +76,379 @@ impl<E: Environment, T: RasterTransferables> System for RequestSystem<E, T> {
             ..
         }: &mut MapContext,
     ) {
-        let _tiles = &mut world.tiles;
-        let view_region = view_state.create_view_region();
+        let now = Instant::now();
+
+        let Some(settings) =
+            RequestSettings::for_view_request(world, view_state, self.last_request, now) else {
+            // The references are not updated, such that the change of the camera is still
+            // detected once the animation has finished, the coalesce window has elapsed or the
+            // world is unfrozen
+            return;
+        };
 
         if view_state.did_camera_change() || view_state.did_zoom_change() {
+            self.last_request = Some(now);
+        }
+
+        // No tiles have been requested while all raster layers were hidden. All raster layers are
+        // drawn from the same tiles, so tiles which have been loaded are not requested again.
+        let hidden_layers = style.hidden_layers();
+        let did_show_layers = style.layers.iter().any(|layer| {
+            matches!(layer.paint, Some(LayerPaint::Raster(_)))
+                && self.hidden_layers.contains(&layer.id)
+                && !hidden_layers.contains(&layer.id)
+        });
+        self.hidden_layers = hidden_layers;
+
+        let frame = world
+            .resources
+            .get::<RasterTimeline>()
+            .and_then(RasterTimeline::frame_request);
+        let time = frame.as_ref().map(|frame| frame.time.clone());
+        let did_time_change = time != self.requested_time;
+
+        // Larger tiles are requested from lower zoom levels and drawn in place of their children
+        let source = requested_source(style, view_state.visible_level());
+        let view_region = view_state
+            .create_view_region_for_tile_size(raster_source(style, source.as_deref()).tile_size);
+
+        // Tiles are not requested if no raster layer would be drawn at this zoom level
+        if (view_state.did_camera_change()
+            || view_state.did_zoom_change()
+            || self.has_deferred
+            || did_time_change
+            || self.has_stale_frames
+            || did_show_layers)
+            && has_visible_raster_layers(style, view_state.visible_level())
+        {
             if let Some(view_region) = &view_region {
                 // TODO: We also need to request tiles from layers above if we are over the maximum zoom level
 
+                let mut budget = RequestBudget::new(&settings);
+                self.requested_time = time.clone();
+                self.has_stale_frames = false;
+
+                // Tiles which are still in view are used, even if they are not requested again
                 for coords in view_region.iter() {
+                    world.tiles.mark_used(coords);
+                }
+
+                for coords in view_region.iter_center_first() {
                     if coords.build_quad_key().is_none() {
                         continue;
                     }
 
                     // TODO: Make tesselation depend on style? So maybe we need to request even if it exists
-                    if world
-                        .tiles
-                        .query::<&RasterLayersDataComponent>(coords)
-                        .is_some()
-                    {
+                    let component = world.tiles.query::<&RasterLayersDataComponent>(coords);
+                    match frame_state(component, time.as_deref()) {
+                        FrameState::Current | FrameState::Loading => continue,
+                        FrameState::LoadingStale => {
+                            self.has_stale_frames = true;
+                            continue;
+                        }
+                        FrameState::Stale | FrameState::Missing => {}
+                    }
+
+                    if !budget.take() {
                         continue;
                     }
 
-                    world
+                    match world
                         .tiles
-                        .spawn_mut(coords)
-                        .unwrap()
-                        .insert(RasterLayersDataComponent::default());
+                        .query_mut::<&mut RasterLayersDataComponent>(coords)
+                    {
+                        // The tile keeps showing the bound texture of the previous frame
+                        Some(component) => {
+                            component.layers.clear();
+                            component.time = time.clone();
+                        }
+                        None => {
+                            world.tiles.spawn_mut(coords).unwrap().insert(
+                                RasterLayersDataComponent {
+                                    layers: Vec::new(),
+                                    time: time.clone(),
+                                },
+                            );
+                        }
+                    }
 
                     tracing::event!(tracing::Level::ERROR, %coords, "tile request started: {coords}");
                     log::info!("tile request started: {coords}");
+                    record_request(world, style, coords, source.clone(), RequestPriority::View);
 
-                    self.kernel
-                        .apc()
-                        .call(
-                            Input::TileRequest {
-                                coords,
-                                style: style.clone(), // TODO: Avoid cloning whole style
-                            },
-                            fetch_raster_apc::<
-                                E::OffscreenKernelEnvironment,
-                                T,
-                                <E::AsyncProcedureCall as AsyncProcedureCall<
-                                    E::OffscreenKernelEnvironment,
-                                >>::Context,
-                            >,
-                        )
-                        .unwrap(); // TODO: Remove unwrap
+                    self.request_tile(
+                        coords,
+                        style,
+                        source.clone(),
+                        None,
+                        settings.pixel_ratio,
+                        frame.clone(),
+                    );
                 }
+
+                // Deferred tiles are requested in the next frames if they are still in view
+                self.has_deferred = budget.has_deferred();
+
+                // Only tiles which are not in view are evicted, unless the view alone exceeds the
+                // budget
+                world.tiles.evict_to_budget();
             }
         }
 
+        self.request_pinned_tiles(world, style, &settings, frame.clone());
+        self.request_replayed_tiles(world, style, &settings, frame, now);
+
         view_state.update_references();
     }
 }
+
+impl<E: Environment, T: RasterTransferables> RequestSystem<E, T> {
+    /// Requests the pinned tiles which have not been requested yet, regardless of the current
+    /// view, if a raster layer is visible at their zoom level.
+    fn request_pinned_tiles(
+        &self,
+        world: &mut World,
+        style: &Style,
+        settings: &RequestSettings,
+        frame: Option<FrameRequest>,
+    ) {
+        let missing: Vec<WorldTileCoords> = world
+            .tiles
+            .pinned_coords()
+            .filter(|coords| {
+                has_visible_raster_layers(style, coords.z)
+                    && world
+                        .tiles
+                        .query::<&RasterLayersDataComponent>(**coords)
+                        .is_none()
+            })
+            .copied()
+            .collect();
+
+        for coords in missing {
+            world
+                .tiles
+                .spawn_mut(coords)
+                .unwrap()
+                .insert(RasterLayersDataComponent {
+                    layers: Vec::new(),
+                    time: frame.as_ref().map(|frame| frame.time.clone()),
+                });
+
+            let source = requested_source(style, coords.z);
+            log::info!("pinned tile request started: {coords}");
+            record_request(
+                world,
+                style,
+                coords,
+                source.clone(),
+                RequestPriority::Pinned,
+            );
+
+            self.request_tile(
+                coords,
+                style,
+                source,
+                None,
+                settings.pixel_ratio,
+                frame.clone(),
+            );
+        }
+    }
+
+    /// Issues the raster requests of a replay which are due at `now`, see
+    /// [`World::replay_requests`](crate::tcs::world::World::replay_requests). Each tile is
+    /// requested from the recorded source and source layers, in the current `frame`.
+    fn request_replayed_tiles(
+        &self,
+        world: &mut World,
+        style: &Style,
+        settings: &RequestSettings,
+        frame: Option<FrameRequest>,
+        now: Instant,
+    ) {
+        let Some(replay) = world.resources.get_mut::<RequestReplay>() else { return; };
+
+        for request in replay.take_due(RequestKind::Raster, now) {
+            let coords = request.coords;
+            if world
+                .tiles
+                .query::<&RasterLayersDataComponent>(coords)
+                .is_some()
+            {
+                log::debug!("replayed tile at {coords} is already loaded or in flight");
+                continue;
+            }
+
+            world
+                .tiles
+                .spawn_mut(coords)
+                .unwrap()
+                .insert(RasterLayersDataComponent {
+                    layers: Vec::new(),
+                    time: frame.as_ref().map(|frame| frame.time.clone()),
+                });
+
+            log::info!("replayed tile request started: {coords}");
+            record_request(
+                world,
+                style,
+                coords,
+                request.source.clone(),
+                request.priority,
+            );
+
+            self.request_tile(
+                coords,
+                style,
+                request.source,
+                Some(request.layers.into_iter().collect()),
+                settings.pixel_ratio,
+                frame.clone(),
+            );
+        }
+    }
+
+    /// Requests the `layers` of the raster tile at `coords` from the `source`, or all raster
+    /// layers of the `style` if `None`.
+    fn request_tile(
+        &self,
+        coords: WorldTileCoords,
+        style: &Style,
+        source: Option<String>,
+        layers: Option<HashSet<String>>,
+        pixel_ratio: f64,
+        frame: Option<FrameRequest>,
+    ) {
+        self.kernel
+            .apc()
+            .call(
+                Input::TileRequest {
+                    coords,
+                    style: style.clone(), // TODO: Avoid cloning whole style
+                    source,
+                    layers,
+                    pixel_ratio,
+                    index: false,
+                    deadline: None,
+                    frame,
+                },
+                fetch_raster_apc::<
+                    E::OffscreenKernelEnvironment,
+                    T,
+                    <E::AsyncProcedureCall as AsyncProcedureCall<E::OffscreenKernelEnvironment>>::Context,
+                >,
+            )
+            .unwrap(); // TODO: Remove unwrap
+    }
+}
+
+/// Records the request of the raster tile at `coords` from the `source` if the [`RequestLog`] is
+/// recording.
+fn record_request(
+    world: &mut World,
+    style: &Style,
+    coords: WorldTileCoords,
+    source: Option<String>,
+    priority: RequestPriority,
+) {
+    let Some(log) = world.resources.get_mut::<RequestLog>() else { return; };
+    if !log.is_recording() {
+        return;
+    }
+
+    log.record(
+        RequestKind::Raster,
+        coords,
+        raster_source_layers(style),
+        source,
+        priority,
+    );
+}
+
+/// The state of the frame of a tile with the `component` compared to the frame at `time`.
+#[derive(Debug, PartialEq, Eq)]
+enum FrameState {
+    /// The tile has not been requested
+    Missing,
+    /// The tile shows the frame at `time`
+    Current,
+    /// The tile shows another frame and needs to be requested again
+    Stale,
+    /// The tile is loading the frame at `time`
+    Loading,
+    /// The tile is still loading another frame. Only a single request per tile is in flight,
+    /// such that a frame which arrives late can not replace the frame at `time`.
+    LoadingStale,
+}
+
+fn frame_state(component: Option<&RasterLayersDataComponent>, time: Option<&str>) -> FrameState {
+    let Some(component) = component else { return FrameState::Missing; };
+
+    let is_time = component.time.as_deref() == time;
+    match (is_time, component.layers.is_empty()) {
+        (true, true) => FrameState::Loading,
+        (true, false) => FrameState::Current,
+        (false, true) => FrameState::LoadingStale,
+        (false, false) => FrameState::Stale,
+    }
+}
+
+/// Whether any raster layer is visible at the `zoom_level`.
+fn has_visible_raster_layers(style: &Style, zoom_level: ZoomLevel) -> bool {
+    style.layers.iter().any(|layer| {
+        matches!(layer.paint, Some(LayerPaint::Raster(_))) && layer.is_visible_at(zoom_level)
+    })
+}
+
+/// The raster source from which the tiles of the `style` are requested. All raster layers are
+/// drawn from the same tiles, so this is the source of the first raster layer which is visible at
+/// the `zoom_level`. Returns `None` if no raster layer uses a raster source of the style.
+fn requested_source(style: &Style, zoom_level: ZoomLevel) -> Option<String> {
+    style
+        .layers
+        .iter()
+        .filter(|layer| {
+            matches!(layer.paint, Some(LayerPaint::Raster(_))) && layer.is_visible_at(zoom_level)
+        })
+        .filter_map(|layer| layer.source.as_ref())
+        .find(|source| matches!(style.sources.get(*source), Some(Source::Raster(_))))
+        .cloned()
+}
+
+/// The URL and the tile size of the raster `source` in the `style`. Sources without tiles are
+/// requested from [`RasterSource::default`].
+fn raster_source(style: &Style, source: Option<&str>) -> RasterSource {
+    let Some(Source::Raster(properties)) =
+        source.and_then(|source| style.sources.get(source)) else {
+        return RasterSource::default();
+    };
+
+    let raster_source = match &properties.tiles {
+        Some(tiles) => RasterSource::from_template(tiles),
+        None => RasterSource::default(),
+    };
+    match properties.tile_size {
+        Some(tile_size) => raster_source.with_tile_size(tile_size),
+        None => raster_source,
+    }
+}
+
+/// The source layers of the raster layers of the `style`.
+fn raster_source_layers(style: &Style) -> HashSet<String> {
+    style
+        .layers
+        .iter()
+        .filter_map(|layer| {
+            if matches!(layer.paint, Some(LayerPaint::Raster(_))) {
+                layer.source_layer.clone()
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
 pub fn fetch_raster_apc<
     K: OffscreenKernelEnvironment,
     T: RasterTransferables,
@@ -110,36 +459,59 @@ pub fn fetch_raster_apc<
     kernel: K,
 ) -> AsyncProcedureFuture {
     Box::pin(async move {
-        let Input::TileRequest {coords, style} = input else {
+        let Input::TileRequest {coords, style, source, layers, pixel_ratio, frame, ..} = input else {
             return Err(ProcedureError::IncompatibleInput)
         };
 
-        let raster_layers: HashSet<String> = style
-            .layers
-            .iter()
-            .filter_map(|layer| {
-                if matches!(layer.paint, Some(LayerPaint::Raster(_))) {
-                    layer.source_layer.clone()
-                } else {
-                    None
-                }
-            })
-            .collect();
+        let raster_layers = layers.unwrap_or_else(|| raster_source_layers(&style));
 
         let client = kernel.source_client();
 
         if !raster_layers.is_empty() {
             let context = context.clone();
-            let source = SourceType::Raster(RasterSource::default());
+            let raster_source =
+                raster_source(&style, source.as_deref()).with_pixel_ratio(pixel_ratio);
+            let source = SourceType::Raster(match &frame {
+                Some(frame) => raster_source.clone().with_time(&frame.time),
+                None => raster_source.clone(),
+            });
 
             match client.fetch(&coords, &source).await {
+                // The source is misconfigured and serves vector tiles
+                Ok(data) if TileFormat::sniff(&data) == Some(TileFormat::Vector) => {
+                    log::warn!("tile at {coords} is a vector tile and can not be decoded as image");
+
+                    context
+                        .send(<T as RasterTransferables>::LayerRasterMissing::build_from(
+                            coords,
+                        ))
+                        .map_err(ProcedureError::Send)?;
+                }
                 Ok(data) => {
                     let data = data.into_boxed_slice();
 
-                    let mut process_context = ProcessRasterContext::<T, C>::new(context);
+                    let mut process_context = ProcessRasterContext::<T, C>::new(context.clone());
 
-                    process_raster_tile(&data, RasterTileRequest { coords }, &mut process_context)
-                        .map_err(|e| ProcedureError::Execution(Box::new(e)))?;
+                    match process_raster_tile(
+                        &data,
+                        RasterTileRequest { coords },
+                        &mut process_context,
+                    ) {
+                        Ok(()) => {}
+                        Err(ProcessRasterError::SendError(e)) => {
+                            return Err(ProcedureError::Send(e))
+                        }
+                        // The tile is finished, such that it does not keep loading
+                        Err(e) => {
+                            log::error!("tile at {coords} can not be decoded: {e:?}");
+
+                            context
+                                .send(<T as RasterTransferables>::LayerRasterMissing::build_from(
+                                    coords,
+                                ))
+                                .map_err(ProcedureError::Send)?;
+                        }
+                    }
                 }
                 Err(e) => {
                     log::error!("{e:?}");
@@ -151,8 +523,231 @@ pub fn fetch_raster_apc<
                         .map_err(ProcedureError::Send)?;
                 }
             }
+
+            // The neighboring frames are only fetched, such that the HTTP client has cached them
+            // once they are selected
+            for time in frame.into_iter().flat_map(|frame| frame.prefetch) {
+                let source = raster_source.clone().with_time(time);
+                if let Err(e) = client.fetch(&coords, &SourceType::Raster(source)).await {
+                    log::debug!("prefetching frame of tile at {coords} failed: {e:?}");
+                }
+            }
         }
 
         Ok(())
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{frame_state, has_visible_raster_layers, raster_source, FrameState};
+    use crate::{
+        coords::{WorldTileCoords, ZoomLevel},
+        io::source_type::{RasterSource, DEFAULT_TILE_SIZE},
+        raster::{
+            AvailableRasterLayerData, RasterLayerData, RasterLayersDataComponent, RasterTimeline,
+        },
+        style::{
+            layer::{LayerPaint, StyleLayer},
+            raster::RasterLayer,
+            source::{Source, VectorSource},
+            StyleBuilder,
+        },
+    };
+
+    #[test]
+    fn test_skip_source_outside_zoom_range() {
+        let style = StyleBuilder::new()
+            .add_source("satellite", Source::Raster(VectorSource::default()))
+            .add_layer(StyleLayer {
+                id: "satellite".to_string(),
+                minzoom: Some(12),
+                paint: Some(LayerPaint::Raster(RasterLayer::default())),
+                source: Some("satellite".to_string()),
+                source_layer: Some("raster".to_string()),
+                ..StyleLayer::default()
+            })
+            .build()
+            .unwrap();
+
+        assert!(!has_visible_raster_layers(&style, ZoomLevel::from(3)));
+        assert!(has_visible_raster_layers(&style, ZoomLevel::from(12)));
+    }
+
+    #[test]
+    fn test_switch_frame() {
+        let coords = WorldTileCoords::from((1, 2, ZoomLevel::from(3)));
+        let mut timeline = RasterTimeline::default();
+        timeline.set_timestamps(vec!["0900".to_string(), "0910".to_string()]);
+
+        let time = timeline.current();
+        assert_eq!(frame_state(None, time), FrameState::Missing);
+
+        let mut component = RasterLayersDataComponent {
+            layers: Vec::new(),
+            time: time.map(str::to_string),
+        };
+        assert_eq!(frame_state(Some(&component), time), FrameState::Loading);
+
+        component
+            .layers
+            .push(RasterLayerData::Available(AvailableRasterLayerData {
+                coords,
+                source_layer: "raster".to_string(),
+                image: Default::default(),
+            }));
+        assert_eq!(frame_state(Some(&component), time), FrameState::Current);
+
+        // Switching the frame requests the tile again with the new timestamp
+        timeline.set_index(1);
+        let time = timeline.current();
+        assert_eq!(frame_state(Some(&component), time), FrameState::Stale);
+
+        let frame = timeline.frame_request().unwrap();
+        let source = RasterSource::new("https://example.com/{time}", "png", "key");
+        assert_eq!(
+            source.with_time(frame.time).format(&coords),
+            "https://example.com/0910/3/1/2.png?key=key"
+        );
+        assert_eq!(frame.prefetch, vec!["0900".to_string()]);
+
+        // Tiles which are still loading the previous frame are requested once it has arrived
+        component.layers.clear();
+        assert_eq!(
+            frame_state(Some(&component), time),
+            FrameState::LoadingStale
+        );
+    }
+
+    #[test]
+    fn test_source_tile_size() {
+        let style = StyleBuilder::new()
+            .add_source(
+                "satellite",
+                serde_json::from_str(
+                    r#"{"type": "raster", "tiles": "https://example.com", "tileSize": 512}"#,
+                )
+                .unwrap(),
+            )
+            .add_source("terrain", Source::Raster(VectorSource::default()))
+            .build()
+            .unwrap();
+
+        assert_eq!(raster_source(&style, Some("satellite")).tile_size, 512);
+        assert_eq!(
+            raster_source(&style, Some("terrain")).tile_size,
+            DEFAULT_TILE_SIZE
+        );
+        assert_eq!(raster_source(&style, None).tile_size, DEFAULT_TILE_SIZE);
+    }
+
+    #[cfg(all(feature = "headless", feature = "thread-safe-futures"))]
+    #[tokio::test]
+    async fn test_raster_time() {
+        use std::{
+            sync::{Arc, Mutex},
+            time::Duration,
+        };
+
+        use crate::{
+            headless::{
+                map::HeadlessMap,
+                tests::{kernel_builder, requesting_map},
+            },
+            io::source_client::SourceFetchError,
+            raster::{DefaultRasterTransferables, RasterPlugin},
+        };
+
+        /// Records the URLs of all requested tiles
+        #[derive(Clone, Default)]
+        struct RecordedUrls(Arc<Mutex<Vec<String>>>);
+
+        impl RecordedUrls {
+            fn has_requested(&self, pattern: &str) -> bool {
+                self.0
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .any(|url| url.contains(pattern))
+            }
+        }
+
+        let style = StyleBuilder::new()
+            .add_source(
+                "radar",
+                serde_json::from_value::<Source>(serde_json::json!({
+                    "type": "raster",
+                    "tiles": "https://radar.example.com/{time}/{z}/{x}/{y}.png"
+                }))
+                .unwrap(),
+            )
+            .add_layer(StyleLayer {
+                id: "radar".to_string(),
+                paint: Some(LayerPaint::Raster(RasterLayer::default())),
+                source: Some("radar".to_string()),
+                source_layer: Some("raster".to_string()),
+                ..StyleLayer::default()
+            })
+            .build()
+            .unwrap();
+
+        // The URLs are only recorded, all tiles fail to load
+        let urls = RecordedUrls::default();
+        let recorder = urls.clone();
+        let kernel_builder = kernel_builder(64)
+            .with_request_observer(move |url: &str, _coords: &WorldTileCoords| {
+                recorder.0.lock().unwrap().push(url.to_string());
+            })
+            .with_tile_generator(|_coords: WorldTileCoords| async {
+                Err::<Vec<u8>, _>(SourceFetchError("the radar is not available".into()))
+            });
+        let mut map = requesting_map(
+            kernel_builder,
+            style,
+            Box::new(RasterPlugin::<DefaultRasterTransferables>::default()),
+        )
+        .await;
+
+        // Renders until a tile whose URL contains the `pattern` has been requested
+        async fn render_until_requested(
+            map: &mut HeadlessMap,
+            urls: &RecordedUrls,
+            pattern: &str,
+        ) -> bool {
+            for _ in 0..100 {
+                map.render().unwrap();
+                if urls.has_requested(pattern) {
+                    return true;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            false
+        }
+
+        // A single frame has no neighbours which would be prefetched
+        map.world_mut()
+            .set_raster_timestamps(vec!["0900".to_string()]);
+        assert!(render_until_requested(&mut map, &urls, "https://radar.example.com/0900/").await);
+        assert!(!urls.has_requested("0910"));
+
+        // Switching the frame requests the tiles in view with the new timestamp
+        map.world_mut()
+            .set_raster_timestamps(vec!["0900".to_string(), "0910".to_string()]);
+        map.world_mut().set_raster_time(1);
+        assert!(render_until_requested(&mut map, &urls, "https://radar.example.com/0910/").await);
+
+        // Pinned tiles are requested in the current frame, even if they are not in view
+        map.world_mut()
+            .tiles
+            .pin(&WorldTileCoords::from((0, 0, ZoomLevel::from(1))));
+        assert!(render_until_requested(&mut map, &urls, "/0910/1/0/0.png").await);
+
+        // All tiles are requested from the source of the style
+        assert!(urls
+            .0
+            .lock()
+            .unwrap()
+            .iter()
+            .all(|url| url.starts_with("https://radar.example.com/")));
+    }
+}
@@ -1,42 +1,64 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, mem::size_of, time::Duration};
+
+use instant::Instant;
 
 use crate::{
     coords::WorldTileCoords,
-    render::{resource::Texture, settings::Msaa, tile_view_pattern::HasTile},
+    render::{
+        resource::Texture, settings::Msaa, shaders::ShaderRasterMetadata,
+        tile_view_pattern::HasTile,
+    },
+    style::raster::RasterResampling,
     tcs::world::World,
 };
 
+/// A raster tile which is bound to the pipeline.
+struct BoundTexture {
+    bind_group: wgpu::BindGroup,
+    metadata: wgpu::Buffer,
+    bound_at: Instant,
+    /// The timestamp of the frame of the texture, see
+    /// [`RasterTimeline`](crate::raster::RasterTimeline)
+    time: Option<String>,
+}
+
 /// Holds the resources necessary for the raster tiles such as the
-/// * sampler
+/// * samplers
 /// * texture
 /// * pipeline
 /// * bindgroups
 pub struct RasterResources {
-    sampler: wgpu::Sampler,
+    linear_sampler: wgpu::Sampler,
+    nearest_sampler: wgpu::Sampler,
     msaa: Msaa,
     pipeline: wgpu::RenderPipeline,
-    bound_textures: HashMap<WorldTileCoords, wgpu::BindGroup>,
+    bound_textures: HashMap<WorldTileCoords, BoundTexture>,
 }
 
 impl RasterResources {
     pub fn new(msaa: Msaa, device: &wgpu::Device, pipeline: wgpu::RenderPipeline) -> Self {
-        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
-            address_mode_u: wgpu::AddressMode::ClampToEdge,
-            address_mode_v: wgpu::AddressMode::ClampToEdge,
-            address_mode_w: wgpu::AddressMode::ClampToEdge,
-            mag_filter: wgpu::FilterMode::Linear,
-            min_filter: wgpu::FilterMode::Linear,
-            mipmap_filter: wgpu::FilterMode::Linear,
-            ..Default::default()
-        });
         Self {
-            sampler,
+            linear_sampler: Self::create_sampler(device, &RasterResampling::Linear),
+            nearest_sampler: Self::create_sampler(device, &RasterResampling::Nearest),
             msaa,
             pipeline,
             bound_textures: Default::default(),
         }
     }
 
+    fn create_sampler(device: &wgpu::Device, resampling: &RasterResampling) -> wgpu::Sampler {
+        let filter = filter_mode(resampling);
+        device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: filter,
+            min_filter: filter,
+            mipmap_filter: filter,
+            ..Default::default()
+        })
+    }
+
     pub fn create_texture(
         &mut self,
         label: wgpu::Label,
@@ -50,35 +72,100 @@ impl RasterResources {
     }
 
     pub fn get_bound_texture(&self, coords: &WorldTileCoords) -> Option<&wgpu::BindGroup> {
-        self.bound_textures.get(coords)
+        self.bound_textures
+            .get(coords)
+            .map(|bound_texture| &bound_texture.bind_group)
+    }
+
+    /// Whether the frame at `time` is bound for the tile at `coords`.
+    pub fn is_bound(&self, coords: &WorldTileCoords, time: Option<&str>) -> bool {
+        self.bound_textures
+            .get(coords)
+            .map_or(false, |bound_texture| bound_texture.time.as_deref() == time)
     }
 
-    /// Creates a bind group for each fetched raster tile and store it inside a hashmap.
+    /// Creates a bind group for each fetched raster tile and store it inside a hashmap. A texture
+    /// which replaces another frame of the same tile is not faded in again.
     pub fn bind_texture(
         &mut self,
         device: &wgpu::Device,
         coords: &WorldTileCoords,
         texture: Texture,
+        resampling: &RasterResampling,
+        time: Option<String>,
     ) {
+        let sampler = match resampling {
+            RasterResampling::Linear => &self.linear_sampler,
+            RasterResampling::Nearest => &self.nearest_sampler,
+        };
+
+        let metadata = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("raster metadata buffer"),
+            size: size_of::<ShaderRasterMetadata>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &self.pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: metadata.as_entire_binding(),
+                },
+            ],
+            label: None,
+        });
+
+        let bound_at = self
+            .bound_textures
+            .get(coords)
+            .map_or_else(Instant::now, |bound_texture| bound_texture.bound_at);
+
         self.bound_textures.insert(
             *coords,
-            device.create_bind_group(&wgpu::BindGroupDescriptor {
-                layout: &self.pipeline.get_bind_group_layout(0),
-                entries: &[
-                    wgpu::BindGroupEntry {
-                        binding: 0,
-                        resource: wgpu::BindingResource::TextureView(&texture.view),
-                    },
-                    wgpu::BindGroupEntry {
-                        binding: 1,
-                        resource: wgpu::BindingResource::Sampler(&self.sampler),
-                    },
-                ],
-                label: None,
-            }),
+            BoundTexture {
+                bind_group,
+                metadata,
+                bound_at,
+                time,
+            },
         );
     }
 
+    /// Writes the opacity of each bound raster tile. Tiles which have been bound recently are
+    /// faded in over the `fade_duration`.
+    pub fn update_opacity(
+        &self,
+        queue: &wgpu::Queue,
+        opacity: f32,
+        fade_duration: Duration,
+        now: Instant,
+    ) {
+        for bound_texture in self.bound_textures.values() {
+            let elapsed = now.saturating_duration_since(bound_texture.bound_at);
+            let metadata = ShaderRasterMetadata::new(fade_opacity(opacity, fade_duration, elapsed));
+            queue.write_buffer(
+                &bound_texture.metadata,
+                0,
+                bytemuck::cast_slice(&[metadata]),
+            );
+        }
+    }
+
+    /// Releases the texture of the tile at `coords`.
+    pub fn remove_tile(&mut self, coords: &WorldTileCoords) {
+        self.bound_textures.remove(coords);
+    }
+
     pub fn pipeline(&self) -> &wgpu::RenderPipeline {
         &self.pipeline
     }
@@ -89,3 +176,53 @@ impl HasTile for RasterResources {
         self.bound_textures.contains_key(&coords)
     }
 }
+
+fn filter_mode(resampling: &RasterResampling) -> wgpu::FilterMode {
+    match resampling {
+        RasterResampling::Linear => wgpu::FilterMode::Linear,
+        RasterResampling::Nearest => wgpu::FilterMode::Nearest,
+    }
+}
+
+/// The opacity of a raster tile which is fading in since `elapsed`.
+fn fade_opacity(opacity: f32, fade_duration: Duration, elapsed: Duration) -> f32 {
+    if fade_duration.is_zero() {
+        return opacity;
+    }
+
+    let progress = elapsed.as_secs_f32() / fade_duration.as_secs_f32();
+    opacity * progress.min(1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{fade_opacity, filter_mode};
+    use crate::style::raster::RasterResampling;
+
+    #[test]
+    fn test_filter_mode() {
+        assert_eq!(
+            filter_mode(&RasterResampling::Linear),
+            wgpu::FilterMode::Linear
+        );
+        assert_eq!(
+            filter_mode(&RasterResampling::Nearest),
+            wgpu::FilterMode::Nearest
+        );
+    }
+
+    #[test]
+    fn test_fade_opacity() {
+        let fade_duration = Duration::from_millis(300);
+
+        assert_eq!(fade_opacity(0.5, Duration::ZERO, Duration::ZERO), 0.5);
+        assert_eq!(fade_opacity(0.5, fade_duration, Duration::ZERO), 0.0);
+        assert!((fade_opacity(1.0, fade_duration, Duration::from_millis(150)) - 0.5).abs() < 1e-6);
+        assert_eq!(
+            fade_opacity(0.5, fade_duration, Duration::from_millis(600)),
+            0.5
+        );
+    }
+}
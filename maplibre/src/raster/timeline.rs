@@ -0,0 +1,90 @@
+//! Raster sources with a time dimension, e.g. weather radar, provide a frame for each timestamp.
+//! The selected frame is displayed, while its neighbors are fetched ahead for smooth playback.
+
+use serde::{Deserialize, Serialize};
+
+/// The timestamps of the frames of the raster source and the selected frame.
+#[derive(Default, Debug)]
+pub struct RasterTimeline {
+    timestamps: Vec<String>,
+    index: usize,
+}
+
+impl RasterTimeline {
+    /// Replaces the frames of the raster source and selects the first one.
+    pub fn set_timestamps(&mut self, timestamps: Vec<String>) {
+        self.timestamps = timestamps;
+        self.index = 0;
+    }
+
+    /// Selects the frame at `index`. Indices beyond the last frame select the last frame.
+    pub fn set_index(&mut self, index: usize) {
+        self.index = index.min(self.timestamps.len().saturating_sub(1));
+    }
+
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// The timestamp of the selected frame, if the raster source has a time dimension.
+    pub fn current(&self) -> Option<&str> {
+        self.timestamps.get(self.index).map(String::as_str)
+    }
+
+    /// The request for the selected frame, which also fetches the previous and the next frame.
+    /// Playback usually loops, so the neighbors of the first and the last frame wrap around.
+    pub fn frame_request(&self) -> Option<FrameRequest> {
+        let time = self.current()?.to_string();
+
+        let count = self.timestamps.len();
+        let mut prefetch = Vec::new();
+        for neighbor in [self.index + 1, self.index + count - 1] {
+            let neighbor = &self.timestamps[neighbor % count];
+            if *neighbor != time && !prefetch.contains(neighbor) {
+                prefetch.push(neighbor.clone());
+            }
+        }
+
+        Some(FrameRequest { time, prefetch })
+    }
+}
+
+/// Requests the frame at `time` of a raster tile.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FrameRequest {
+    pub time: String,
+    /// The frames which are fetched after the requested frame without being displayed, such that
+    /// they are cached once they are selected
+    pub prefetch: Vec<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FrameRequest, RasterTimeline};
+
+    #[test]
+    fn test_frame_request() {
+        let mut timeline = RasterTimeline::default();
+        assert_eq!(timeline.frame_request(), None);
+
+        timeline.set_timestamps(vec!["t0".into(), "t1".into(), "t2".into()]);
+        assert_eq!(
+            timeline.frame_request(),
+            Some(FrameRequest {
+                time: "t0".into(),
+                prefetch: vec!["t1".into(), "t2".into()],
+            })
+        );
+
+        timeline.set_index(5);
+        assert_eq!(timeline.current(), Some("t2"));
+        assert_eq!(
+            timeline.frame_request().unwrap().prefetch,
+            vec!["t0".to_string(), "t1".to_string()]
+        );
+
+        // A single frame has no neighbors
+        timeline.set_timestamps(vec!["t0".into()]);
+        assert!(timeline.frame_request().unwrap().prefetch.is_empty());
+    }
+}
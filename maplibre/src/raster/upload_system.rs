@@ -1,4 +1,8 @@
 //! Uploads data to the GPU which is needed for rendering.
+use std::time::Duration;
+
+use instant::Instant;
+
 use crate::{
     context::MapContext,
     coords::ViewRegion,
@@ -7,10 +11,15 @@ use crate::{
         RasterLayersDataComponent,
     },
     render::{
+        color_space::ColorSpace,
         eventually::{Eventually, Eventually::Initialized},
-        Renderer,
+        RenderResources, Renderer,
+    },
+    style::{
+        layer::LayerPaint,
+        raster::{RasterLayer, RasterResampling},
+        Style,
     },
-    style::Style,
     tcs::tiles::Tiles,
 };
 
@@ -19,7 +28,13 @@ pub fn upload_system(
         world,
         style,
         view_state,
-        renderer: Renderer { device, queue, .. },
+        renderer:
+            Renderer {
+                device,
+                queue,
+                resources: RenderResources { surface, .. },
+                ..
+            },
         ..
     }: &mut MapContext,
 ) {
@@ -36,8 +51,43 @@ pub fn upload_system(
             &world.tiles,
             style,
             view_region,
+            surface.color_space(),
         );
     }
+
+    update_opacity(raster_resources, queue, style, view_state.reduced_motion());
+}
+
+/// Applies the `raster-opacity` and `raster-fade-duration` of the raster layer to the bound tiles.
+/// With `reduced_motion` tiles are not faded in.
+fn update_opacity(
+    raster_resources: &RasterResources,
+    queue: &wgpu::Queue,
+    style: &Style,
+    reduced_motion: bool,
+) {
+    let Some(raster_layer) = style
+        .layers
+        .iter()
+        .find_map(|layer| match &layer.paint {
+            Some(LayerPaint::Raster(raster_layer)) => Some(raster_layer),
+            _ => None,
+        }) else { return; };
+
+    raster_resources.update_opacity(
+        queue,
+        raster_layer.raster_opacity.unwrap_or(1.0),
+        fade_duration(raster_layer, reduced_motion),
+        Instant::now(),
+    );
+}
+
+fn fade_duration(raster_layer: &RasterLayer, reduced_motion: bool) -> Duration {
+    if reduced_motion {
+        return Duration::ZERO;
+    }
+
+    Duration::from_millis(raster_layer.raster_fade_duration.unwrap_or(0) as u64)
 }
 
 #[tracing::instrument(skip_all)]
@@ -48,15 +98,17 @@ fn upload_raster_layer(
     tiles: &Tiles,
     style: &Style,
     view_region: &ViewRegion,
+    color_space: ColorSpace,
 ) {
     for coords in view_region.iter() {
-        if raster_resources.get_bound_texture(&coords).is_some() {
-            continue;
-        }
-
         let Some(raster_layers) =
             tiles.query::<&RasterLayersDataComponent>(coords) else { continue; };
 
+        // The previous frame stays bound until the requested frame is available
+        if raster_resources.is_bound(&coords, raster_layers.time.as_deref()) {
+            continue;
+        }
+
         for style_layer in &style.layers {
             let style_source_layer = style_layer.source_layer.as_ref().unwrap(); // FIXME: Remove unwrap
 
@@ -77,7 +129,7 @@ fn upload_raster_layer(
             let texture = raster_resources.create_texture(
                 None,
                 device,
-                wgpu::TextureFormat::Rgba8UnormSrgb,
+                color_space.image_format(),
                 width,
                 height,
                 wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
@@ -99,7 +151,44 @@ fn upload_raster_layer(
                 texture.size,
             );
 
-            raster_resources.bind_texture(device, coords, texture);
+            let resampling = match &style_layer.paint {
+                Some(LayerPaint::Raster(RasterLayer {
+                    raster_resampling: Some(resampling),
+                    ..
+                })) => resampling,
+                _ => &RasterResampling::Linear,
+            };
+
+            raster_resources.bind_texture(
+                device,
+                coords,
+                texture,
+                resampling,
+                raster_layers.time.clone(),
+            );
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::fade_duration;
+    use crate::style::raster::RasterLayer;
+
+    #[test]
+    fn test_fade_duration() {
+        let raster_layer = RasterLayer {
+            raster_fade_duration: Some(300),
+            ..RasterLayer::default()
+        };
+
+        assert_eq!(
+            fade_duration(&raster_layer, false),
+            Duration::from_millis(300)
+        );
+        // Tiles appear at full opacity immediately
+        assert_eq!(fade_duration(&raster_layer, true), Duration::ZERO);
+    }
+}
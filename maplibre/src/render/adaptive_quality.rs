@@ -0,0 +1,169 @@
+//! Reduces the quality of rendering while frames take longer than a target time, e.g. on weak
+//! hardware, and restores it once frames are fast again.
+
+use std::time::Duration;
+
+/// The quality of rendering at a step of [`AdaptiveQuality`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct QualityLevel {
+    /// Multiplier of the render scale which has been configured with
+    /// [`Renderer::set_render_scale`](crate::render::Renderer::set_render_scale)
+    pub render_scale: f32,
+    /// The amount of tiles around the view which are requested, see
+    /// [`ViewState::set_view_region_padding`](crate::view_state::ViewState::set_view_region_padding)
+    pub view_region_padding: i32,
+}
+
+/// The steps of [`AdaptiveQuality`], starting with the full quality.
+pub const QUALITY_LEVELS: [QualityLevel; 3] = [
+    QualityLevel {
+        render_scale: 1.0,
+        view_region_padding: 1,
+    },
+    QualityLevel {
+        render_scale: 0.75,
+        view_region_padding: 0,
+    },
+    QualityLevel {
+        render_scale: 0.5,
+        view_region_padding: 0,
+    },
+];
+
+/// The amount of consecutive slow frames after which the quality is reduced
+const DEGRADE_AFTER_FRAMES: u32 = 10;
+/// The amount of consecutive fast frames after which the quality is restored. Restoring is slower
+/// than reducing, such that the quality does not oscillate.
+const RESTORE_AFTER_FRAMES: u32 = 60;
+/// Frames are fast if they take less than this fraction of the target frame time. Frames between
+/// this fraction and the target neither reduce nor restore the quality.
+const RESTORE_RATIO: f64 = 0.7;
+
+/// A feedback loop between the time which frames take and the [`QualityLevel`] of rendering.
+///
+/// The quality is reduced by one step after several frames in a row took longer than the
+/// `target_frame_time`. It is restored by one step after many frames in a row took clearly less
+/// than the target. Disabled by default, as it makes rendering depend on the timing of frames.
+#[derive(Clone, Debug)]
+pub struct AdaptiveQuality {
+    pub enabled: bool,
+    pub target_frame_time: Duration,
+    /// The index of the current level within [`QUALITY_LEVELS`]
+    level: usize,
+    slow_frames: u32,
+    fast_frames: u32,
+}
+
+impl Default for AdaptiveQuality {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            target_frame_time: Duration::from_millis(33),
+            level: 0,
+            slow_frames: 0,
+            fast_frames: 0,
+        }
+    }
+}
+
+impl AdaptiveQuality {
+    /// Enables the adaptation of the quality with the given `target_frame_time`.
+    pub fn new(target_frame_time: Duration) -> Self {
+        Self {
+            enabled: true,
+            target_frame_time,
+            ..Self::default()
+        }
+    }
+
+    /// The current quality. The full quality is used while the adaptation is disabled.
+    pub fn level(&self) -> QualityLevel {
+        if self.enabled {
+            QUALITY_LEVELS[self.level]
+        } else {
+            QUALITY_LEVELS[0]
+        }
+    }
+
+    /// Whether the quality is currently reduced.
+    pub fn is_reduced(&self) -> bool {
+        self.level() != QUALITY_LEVELS[0]
+    }
+
+    /// Records the time which the last frame took. Returns whether the [`QualityLevel`] changed.
+    pub fn record_frame(&mut self, frame_time: Duration) -> bool {
+        if !self.enabled {
+            return false;
+        }
+
+        if frame_time > self.target_frame_time {
+            self.slow_frames += 1;
+            self.fast_frames = 0;
+        } else if frame_time < self.target_frame_time.mul_f64(RESTORE_RATIO) {
+            self.fast_frames += 1;
+            self.slow_frames = 0;
+        } else {
+            self.slow_frames = 0;
+            self.fast_frames = 0;
+        }
+
+        if self.slow_frames >= DEGRADE_AFTER_FRAMES && self.level + 1 < QUALITY_LEVELS.len() {
+            self.level += 1;
+            self.slow_frames = 0;
+            log::info!("reducing the rendering quality to {:?}", self.level());
+            true
+        } else if self.fast_frames >= RESTORE_AFTER_FRAMES && self.level > 0 {
+            self.level -= 1;
+            self.fast_frames = 0;
+            log::info!("restoring the rendering quality to {:?}", self.level());
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{AdaptiveQuality, QUALITY_LEVELS};
+
+    #[test]
+    fn test_quality_drops_and_recovers() {
+        let mut quality = AdaptiveQuality::new(Duration::from_millis(20));
+        let slow = Duration::from_millis(40);
+        let fast = Duration::from_millis(10);
+
+        // A few slow frames are tolerated
+        for _ in 0..5 {
+            assert!(!quality.record_frame(slow));
+        }
+        quality.record_frame(fast);
+        assert!(!quality.is_reduced());
+
+        let changes = (0..100).filter(|_| quality.record_frame(slow)).count();
+        assert_eq!(changes, QUALITY_LEVELS.len() - 1);
+        assert_eq!(quality.level(), QUALITY_LEVELS[QUALITY_LEVELS.len() - 1]);
+
+        // Frames which are just below the target do not restore the quality
+        for _ in 0..100 {
+            assert!(!quality.record_frame(Duration::from_millis(18)));
+        }
+
+        let changes = (0..1000).filter(|_| quality.record_frame(fast)).count();
+        assert_eq!(changes, QUALITY_LEVELS.len() - 1);
+        assert!(!quality.is_reduced());
+    }
+
+    #[test]
+    fn test_disabled() {
+        let mut quality = AdaptiveQuality {
+            level: 2,
+            ..AdaptiveQuality::default()
+        };
+
+        assert!(!quality.record_frame(Duration::from_secs(1)));
+        assert_eq!(quality.level(), QUALITY_LEVELS[0]);
+    }
+}
@@ -46,6 +46,16 @@ impl ViewProjection {
             .cast::<f32>()
             .expect("Unable to cast view projection to f32")
     }
+
+    pub fn matrix(&self) -> Matrix4<f64> {
+        self.0
+    }
+}
+
+impl From<Matrix4<f64>> for ViewProjection {
+    fn from(matrix: Matrix4<f64>) -> Self {
+        ViewProjection(matrix)
+    }
 }
 
 pub struct InvertedViewProjection(Matrix4<f64>);
@@ -74,6 +84,9 @@ pub struct Camera {
     position: Point3<f64>, // The z axis never changes, the zoom is used instead
     yaw: Rad<f64>,
     pitch: Rad<f64>,
+    /// The compass direction the top of the view points to, clockwise from north. At a bearing
+    /// of 90° east is up.
+    bearing: Rad<f64>,
 
     width: f64,
     height: f64,
@@ -86,6 +99,7 @@ impl SignificantlyDifferent for Camera {
         self.position.abs_diff_ne(&other.position, epsilon)
             || self.yaw.abs_diff_ne(&other.yaw, epsilon)
             || self.pitch.abs_diff_ne(&other.pitch, epsilon)
+            || self.bearing.abs_diff_ne(&other.bearing, epsilon)
     }
 }
 
@@ -101,6 +115,7 @@ impl Camera {
             position: position.into(),
             yaw: yaw.into(),
             pitch: pitch.into(),
+            bearing: Rad(0.0),
             width: width as f64,
             height: height as f64,
         }
@@ -112,11 +127,18 @@ impl Camera {
     }
 
     fn calc_matrix(&self) -> Matrix4<f64> {
+        // Rotate the world around the vertical axis through the camera, such that the direction of
+        // the bearing ends up at the top of the view.
+        let axis = Vector3::new(self.position.x, self.position.y, 0.0);
+        let bearing = Matrix4::from_translation(axis)
+            * Matrix4::from_angle_z(-self.bearing)
+            * Matrix4::from_translation(-axis);
+
         Matrix4::look_to_rh(
             self.position,
             Vector3::new(self.yaw.cos(), self.pitch.sin(), self.yaw.sin()).normalize(),
             Vector3::unit_y(),
-        )
+        ) * bearing
     }
 
     #[tracing::instrument(skip_all)]
@@ -262,6 +284,23 @@ impl Camera {
         }
     }
 
+    /// Projects `world` coordinates to window coordinates. Returns `None` if the coordinates are
+    /// behind the camera.
+    pub fn world_to_window(
+        &self,
+        world: &Vector3<f64>,
+        view_proj: &ViewProjection,
+    ) -> Option<Vector2<f64>> {
+        let clip = view_proj.project(world.extend(1.0));
+
+        if clip.w <= 0.0 {
+            return None;
+        }
+
+        let window = self.clip_to_window(&clip);
+        Some(Vector2::new(window.x, window.y))
+    }
+
     /// Calculates an [`Aabb2`] bounding box which contains at least the visible area on the `z=0`
     /// plane. One can think of it as being the bounding box of the geometry which forms the
     /// intersection between the viewing frustum and the `z=0` plane.
@@ -351,6 +390,11 @@ impl Camera {
         ))
     }
 
+    /// The size of the window in pixels.
+    pub fn size(&self) -> (f64, f64) {
+        (self.width, self.height)
+    }
+
     pub fn position(&self) -> Point3<f64> {
         self.position
     }
@@ -367,6 +411,20 @@ impl Camera {
         self.pitch
     }
 
+    pub fn bearing(&self) -> Rad<f64> {
+        self.bearing
+    }
+
+    /// Sets the bearing, which is normalized to the range `[0, 2π)`.
+    pub fn set_bearing<B: Into<Rad<f64>>>(&mut self, bearing: B) {
+        self.bearing = bearing.into().normalize();
+    }
+
+    /// Turns the direction of view clockwise by `delta`.
+    pub fn rotate_bearing<B: Into<Rad<f64>>>(&mut self, delta: B) {
+        self.set_bearing(self.bearing + delta.into());
+    }
+
     pub fn tilt<P: Into<Rad<f64>>>(&mut self, delta: P) {
         let new_pitch = self.pitch + delta.into();
 
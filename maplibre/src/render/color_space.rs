@@ -0,0 +1,101 @@
+//! Colors of the style are defined in sRGB. Whether the shaders need to output sRGB or linear
+//! colors depends on the format of the surface:
+//!
+//! * Surfaces with an sRGB format like `Bgra8UnormSrgb` encode the output of the shaders to sRGB
+//!   when writing it. The pipelines work in [linear](ColorSpace::Linear) space, so colors are
+//!   converted to linear before they are uploaded and blending happens in linear space.
+//! * Surfaces with a plain format like `Rgba8Unorm` store the output as is. The pipelines work in
+//!   [sRGB](ColorSpace::Srgb) space, so colors are uploaded unchanged and blending happens in sRGB
+//!   space, like in browsers.
+//!
+//! Images like raster tiles and sprites are uploaded in a texture format which yields colors in
+//! the same space when sampled, see [`ColorSpace::image_format`].
+
+use cint::{Alpha, EncodedSrgb};
+use csscolorparser::Color;
+
+use crate::render::shaders::Vec4f32;
+
+/// The color space in which the pipelines of the renderer work.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ColorSpace {
+    /// Colors are output in sRGB and stored as is
+    Srgb,
+    /// Colors are output in linear space and encoded to sRGB by the surface
+    Linear,
+}
+
+impl ColorSpace {
+    /// The color space of the pipelines which render into the surface `format`.
+    pub fn of_format(format: wgpu::TextureFormat) -> Self {
+        if format.is_srgb() {
+            ColorSpace::Linear
+        } else {
+            ColorSpace::Srgb
+        }
+    }
+
+    /// Converts the sRGB `color` of the style into the color which the shaders output. The alpha
+    /// is never converted.
+    pub fn shader_color(self, color: &Color) -> Vec4f32 {
+        let color: Alpha<EncodedSrgb<f32>> = color.clone().into();
+        self.convert(color)
+    }
+
+    /// Like [`ColorSpace::shader_color`], but for colors which are already encoded.
+    pub fn convert(self, color: Alpha<EncodedSrgb<f32>>) -> Vec4f32 {
+        let [r, g, b, a]: Vec4f32 = color.into();
+        match self {
+            ColorSpace::Srgb => [r, g, b, a],
+            ColorSpace::Linear => [srgb_to_linear(r), srgb_to_linear(g), srgb_to_linear(b), a],
+        }
+    }
+
+    /// The format of textures which hold sRGB images, such that sampling them yields colors in
+    /// this color space.
+    pub fn image_format(self) -> wgpu::TextureFormat {
+        match self {
+            ColorSpace::Srgb => wgpu::TextureFormat::Rgba8Unorm,
+            ColorSpace::Linear => wgpu::TextureFormat::Rgba8UnormSrgb,
+        }
+    }
+}
+
+/// Decodes an sRGB encoded channel, see <https://en.wikipedia.org/wiki/SRGB#Transfer_function_(%22gamma%22)>.
+fn srgb_to_linear(channel: f32) -> f32 {
+    if channel <= 0.04045 {
+        channel / 12.92
+    } else {
+        ((channel + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use csscolorparser::Color;
+
+    use super::ColorSpace;
+
+    #[test]
+    fn test_shader_color() {
+        let color = Color::from_rgba8(128, 64, 255, 128);
+
+        let srgb = ColorSpace::Srgb.shader_color(&color);
+        assert_eq!(srgb, [128.0 / 255.0, 64.0 / 255.0, 1.0, 128.0 / 255.0]);
+
+        let linear = ColorSpace::Linear.shader_color(&color);
+        assert!((linear[0] - 0.2158605).abs() < 1e-5);
+        assert!((linear[1] - 0.0512695).abs() < 1e-5);
+        assert_eq!(linear[2], 1.0);
+        assert_eq!(linear[3], srgb[3]);
+
+        assert_eq!(
+            ColorSpace::of_format(wgpu::TextureFormat::Bgra8UnormSrgb),
+            ColorSpace::Linear
+        );
+        assert_eq!(
+            ColorSpace::of_format(wgpu::TextureFormat::Rgba8Unorm),
+            ColorSpace::Srgb
+        );
+    }
+}
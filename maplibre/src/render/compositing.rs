@@ -0,0 +1,162 @@
+//! Layers can be rendered into an offscreen texture instead of the render target. After all other
+//! layers have been drawn, the offscreen texture is composited onto the map with a shader, which
+//! can apply an effect like a blur or a glow.
+
+use std::collections::HashSet;
+
+use crate::render::{
+    eventually::HasChanged,
+    resource::{RenderPipelineDescriptor, Texture},
+    settings::Msaa,
+    shaders::{CompositeShader, Shader},
+};
+
+/// Shader which composites the offscreen texture without changing it.
+pub const PASS_THROUGH_EFFECT: &str = include_str!("shaders/downsample.fragment.wgsl");
+
+/// Designates the style layers which are rendered offscreen and the effect which is applied while
+/// compositing them, see [`Renderer::set_offscreen_pass`](crate::render::Renderer::set_offscreen_pass).
+#[derive(Clone, Debug)]
+pub struct OffscreenPass {
+    /// The ids of the layers which are rendered offscreen. The composited layers are drawn on top
+    /// of all other layers.
+    pub layers: HashSet<String>,
+    /// The WGSL source of the fragment shader which composites the offscreen texture. Its `main`
+    /// entry point receives the texture coordinates at location 0, while the texture and its
+    /// sampler are bound to the bindings 0 and 1 of group 0. The colors of the texture are
+    /// premultiplied with their alpha and the shader must return premultiplied colors as well.
+    pub effect: &'static str,
+}
+
+impl OffscreenPass {
+    /// Renders the `layers` offscreen and composites them with the [`PASS_THROUGH_EFFECT`].
+    pub fn new<S: Into<String>>(layers: impl IntoIterator<Item = S>) -> Self {
+        Self {
+            layers: layers.into_iter().map(Into::into).collect(),
+            effect: PASS_THROUGH_EFFECT,
+        }
+    }
+
+    /// Composites the offscreen texture with the fragment shader `effect`, see
+    /// [`OffscreenPass::effect`].
+    pub fn with_effect(mut self, effect: &'static str) -> Self {
+        self.effect = effect;
+        self
+    }
+
+    pub fn contains(&self, layer: &str) -> bool {
+        self.layers.contains(layer)
+    }
+}
+
+/// Pipeline which blends the offscreen texture over the render target with the effect shader.
+pub struct CompositePipeline {
+    pipeline: wgpu::RenderPipeline,
+    sampler: wgpu::Sampler,
+}
+
+impl CompositePipeline {
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat, effect: &'static str) -> Self {
+        let shader = CompositeShader { format, effect };
+
+        let pipeline = RenderPipelineDescriptor {
+            label: Some("composite_pipeline".into()),
+            layout: Some(vec![vec![
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ]]),
+            vertex: shader.describe_vertex(),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            fragment: shader.describe_fragment(),
+        }
+        .initialize(device);
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("composite sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        Self { pipeline, sampler }
+    }
+
+    pub fn pipeline(&self) -> &wgpu::RenderPipeline {
+        &self.pipeline
+    }
+}
+
+/// The offscreen texture of the layers together with the bind group which is used while
+/// compositing.
+pub struct OffscreenTexture {
+    pub texture: Texture,
+    pub bind_group: wgpu::BindGroup,
+}
+
+impl OffscreenTexture {
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+        composite_pipeline: &CompositePipeline,
+    ) -> Self {
+        let texture = Texture::new(
+            Some("offscreen layer texture"),
+            device,
+            format,
+            width,
+            height,
+            Msaa { samples: 1 },
+            wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        );
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("offscreen layer bind group"),
+            layout: &composite_pipeline.pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&composite_pipeline.sampler),
+                },
+            ],
+        });
+
+        Self {
+            texture,
+            bind_group,
+        }
+    }
+}
+
+impl HasChanged for OffscreenTexture {
+    type Criteria = (u32, u32);
+
+    fn has_changed(&self, criteria: &Self::Criteria) -> bool {
+        self.texture.has_changed(criteria)
+    }
+}
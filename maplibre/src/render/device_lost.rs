@@ -0,0 +1,72 @@
+//! Detects the loss of the render device, after which nothing can be rendered anymore until the
+//! device and the surface are created again.
+
+use std::sync::{Arc, Mutex};
+
+/// The reason why the render device can no longer be used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceLostReason {
+    /// The device or the surface ran out of memory
+    OutOfMemory,
+    /// The surface could not be acquired anymore, e.g. because the GPU driver has been reset
+    Lost,
+}
+
+/// Invoked if the device has been lost and could not be created again.
+pub type DeviceLostCallback = Box<dyn Fn(DeviceLostReason)>;
+
+/// Tracks whether the device has been lost and notifies the embedding application if the device
+/// could not be recovered.
+#[derive(Default)]
+pub struct DeviceLost {
+    /// Shared with the error handler of the device, which is invoked by wgpu
+    reason: Arc<Mutex<Option<DeviceLostReason>>>,
+    callback: Option<DeviceLostCallback>,
+}
+
+impl DeviceLost {
+    /// Reports out-of-memory errors of the `device` which are not caught by an error scope as a
+    /// lost device. All other errors panic like with the default error handler of wgpu.
+    pub(crate) fn watch(&self, device: &wgpu::Device) {
+        let reason = self.reason.clone();
+        device.on_uncaptured_error(Box::new(move |error| match error {
+            wgpu::Error::OutOfMemory { .. } => {
+                log::error!("device ran out of memory: {error}");
+                report(&reason, DeviceLostReason::OutOfMemory);
+            }
+            error => panic!("wgpu error: {error}"),
+        }));
+    }
+
+    /// Marks the device as lost. Only the first reason is kept until it is taken.
+    pub(crate) fn report(&self, reason: DeviceLostReason) {
+        report(&self.reason, reason);
+    }
+
+    /// Returns why the device has been lost since the last call, if it has been lost.
+    pub fn take(&self) -> Option<DeviceLostReason> {
+        self.reason
+            .lock()
+            .expect("device lost state is poisoned")
+            .take()
+    }
+
+    pub fn set_callback(&mut self, callback: DeviceLostCallback) {
+        self.callback = Some(callback);
+    }
+
+    /// Invokes the callback because the device could not be recovered.
+    pub fn notify(&self, reason: DeviceLostReason) {
+        log::error!("render device lost: {reason:?}");
+        if let Some(callback) = &self.callback {
+            callback(reason);
+        }
+    }
+}
+
+fn report(state: &Mutex<Option<DeviceLostReason>>, reason: DeviceLostReason) {
+    state
+        .lock()
+        .expect("device lost state is poisoned")
+        .get_or_insert(reason);
+}
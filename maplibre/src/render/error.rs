@@ -12,6 +12,14 @@ pub enum RenderError {
     Graph(#[from] RenderGraphError),
     #[error("error while requesting device")]
     RequestDevice(#[from] wgpu::RequestDeviceError),
+    /// The device is owned by the embedding application and can not be recreated by the renderer
+    #[error("the device of the embedding application can not be recreated")]
+    ExternalDevice,
+    #[error("render scale {render_scale} exceeds the maximum texture dimension of {max_texture_dimension_2d}")]
+    InvalidRenderScale {
+        render_scale: f32,
+        max_texture_dimension_2d: u32,
+    },
 }
 
 impl RenderError {
@@ -0,0 +1,107 @@
+//! Measures how long the GPU takes to execute the commands of a frame with timestamp queries.
+//! Timestamp queries require the [`wgpu::Features::TIMESTAMP_QUERY`] feature, which is not
+//! supported by every adapter.
+
+use std::{sync::mpsc, time::Duration};
+
+/// The amount of timestamps which are written per frame, one before and one after the commands
+const TIMESTAMP_COUNT: u32 = 2;
+
+/// The size of the resolved timestamps in bytes
+const TIMESTAMPS_SIZE: wgpu::BufferAddress = TIMESTAMP_COUNT as u64 * wgpu::QUERY_SIZE as u64;
+
+pub struct GpuTimer {
+    query_set: wgpu::QuerySet,
+    /// The buffer into which the timestamps are resolved
+    resolve_buffer: wgpu::Buffer,
+    /// The buffer into which the resolved timestamps are copied, such that they can be read
+    readback_buffer: wgpu::Buffer,
+    /// The nanoseconds per tick of a timestamp
+    period: f32,
+}
+
+impl GpuTimer {
+    /// Creates the queries and buffers of the timer. Returns `None` if the `device` does not
+    /// support timestamp queries.
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Option<Self> {
+        // Reading the timestamps blocks until the frame has been rendered, which is not
+        // possible in the browser
+        if cfg!(target_arch = "wasm32")
+            || !device.features().contains(wgpu::Features::TIMESTAMP_QUERY)
+        {
+            return None;
+        }
+
+        let query_set = device.create_query_set(&wgpu::QuerySetDescriptor {
+            label: Some("gpu_timer_queries"),
+            ty: wgpu::QueryType::Timestamp,
+            count: TIMESTAMP_COUNT,
+        });
+        let resolve_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("gpu_timer_resolve_buffer"),
+            size: TIMESTAMPS_SIZE,
+            usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("gpu_timer_readback_buffer"),
+            size: TIMESTAMPS_SIZE,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Some(Self {
+            query_set,
+            resolve_buffer,
+            readback_buffer,
+            period: queue.get_timestamp_period(),
+        })
+    }
+
+    /// Writes the timestamp before the commands of the frame.
+    pub fn begin(&self, encoder: &mut wgpu::CommandEncoder) {
+        encoder.write_timestamp(&self.query_set, 0);
+    }
+
+    /// Writes the timestamp after the commands of the frame and copies both timestamps into the
+    /// readback buffer.
+    pub fn end(&self, encoder: &mut wgpu::CommandEncoder) {
+        encoder.write_timestamp(&self.query_set, 1);
+        encoder.resolve_query_set(&self.query_set, 0..TIMESTAMP_COUNT, &self.resolve_buffer, 0);
+        encoder.copy_buffer_to_buffer(
+            &self.resolve_buffer,
+            0,
+            &self.readback_buffer,
+            0,
+            TIMESTAMPS_SIZE,
+        );
+    }
+
+    /// Waits until the submitted frame has been rendered and returns the time between the
+    /// timestamps. Returns `None` if the timestamps could not be read.
+    pub fn read(&self, device: &wgpu::Device) -> Option<Duration> {
+        let slice = self.readback_buffer.slice(..);
+
+        let (sender, receiver) = mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        device.poll(wgpu::Maintain::Wait);
+
+        if let Err(e) = receiver.try_recv().ok()? {
+            log::warn!("reading the GPU timestamps failed: {e}");
+            return None;
+        }
+
+        let ticks = {
+            let data = slice.get_mapped_range();
+            let timestamps: &[u64] = bytemuck::cast_slice(&data);
+            timestamps[1].saturating_sub(timestamps[0])
+        };
+        self.readback_buffer.unmap();
+
+        Some(Duration::from_nanos(
+            (ticks as f64 * self.period as f64) as u64,
+        ))
+    }
+}
@@ -11,10 +11,12 @@ use thiserror::Error;
 
 use crate::{
     render::{
+        eventually::Eventually::Initialized,
         graph::{
             Edge, NodeId, NodeRunError, NodeState, RenderContext, RenderGraph, RenderGraphContext,
             SlotLabel, SlotType, SlotValue,
         },
+        stats::RenderStats,
         RenderResources,
     },
     tcs::world::World,
@@ -62,12 +64,31 @@ impl RenderGraphRunner {
             command_encoder,
         };
 
+        let gpu_timer = match &state.gpu_timer {
+            Initialized(Some(gpu_timer)) => Some(gpu_timer),
+            _ => None,
+        };
+
+        if let Some(gpu_timer) = gpu_timer {
+            gpu_timer.begin(&mut render_context.command_encoder);
+        }
         Self::run_graph(graph, None, &mut render_context, state, world, &[])?;
+        if let Some(gpu_timer) = gpu_timer {
+            gpu_timer.end(&mut render_context.command_encoder);
+        }
+
         {
             #[cfg(feature = "trace")]
             let _span = tracing::info_span!("submit_graph_commands").entered();
             queue.submit(vec![render_context.command_encoder.finish()]);
         }
+
+        if let Some(gpu_timer) = gpu_timer {
+            state.record_stats(RenderStats {
+                gpu_time: gpu_timer.read(device),
+                ..RenderStats::default()
+            });
+        }
         Ok(())
     }
 
@@ -1,6 +1,8 @@
 //! The main render pass for this application.
 //!
-//! Right now there is only one render graph. A use case for multiple render passes would be
+//! Right now there is only one render graph. Layers of an
+//! [`OffscreenPass`](crate::render::compositing::OffscreenPass) are drawn in a second pass and
+//! composited afterwards. Another use case for multiple render passes would be
 //! [shadows](https://www.raywenderlich.com/books/metal-by-tutorials/v2.0/chapters/14-multipass-deferred-rendering).
 
 use std::ops::Deref;
@@ -10,7 +12,8 @@ use crate::{
         draw_graph,
         graph::{Node, NodeRunError, RenderContext, RenderGraphContext, SlotInfo},
         render_phase::{LayerItem, RenderPhase, TileMaskItem},
-        resource::TrackedRenderPass,
+        resource::{Texture, TrackedRenderPass},
+        viewport::Viewport,
         Eventually::Initialized,
         RenderResources,
     },
@@ -49,65 +52,204 @@ impl Node for MainPassNode {
             return Ok(());
         };
 
-        let color_attachment = if let Some(texture) = multisampling_texture {
-            wgpu::RenderPassColorAttachment {
-                view: &texture.view,
-                ops: wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(wgpu::Color::WHITE),
-                    store: true,
-                },
-                resolve_target: Some(render_target.deref()),
-            }
-        } else {
-            wgpu::RenderPassColorAttachment {
-                view: render_target.deref(),
-                ops: wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(wgpu::Color::WHITE),
-                    store: true,
-                },
-                resolve_target: None,
+        // If supersampling is enabled, we render to the offscreen target and downsample it to the
+        // render target afterwards.
+        let supersampling = match (&state.supersampling_texture, &state.downsample_pipeline) {
+            (Initialized(Some(texture)), Initialized(pipeline)) => Some((texture, pipeline)),
+            _ => None,
+        };
+        let target_view: &wgpu::TextureView = match supersampling {
+            Some((texture, _)) => texture.texture.view.deref(),
+            None => render_target.deref(),
+        };
+
+        // Layers of the offscreen pass are drawn into the offscreen texture and composited
+        // afterwards
+        let offscreen = match (
+            &state.offscreen_pass,
+            &state.offscreen_texture,
+            &state.composite_pipeline,
+        ) {
+            (Some(pass), Initialized(Some(texture)), Initialized(pipeline)) => {
+                Some((pass, texture, pipeline))
             }
+            _ => None,
         };
 
-        let render_pass =
-            render_context
-                .command_encoder
-                .begin_render_pass(&wgpu::RenderPassDescriptor {
-                    label: Some("main_pass"),
-                    color_attachments: &[Some(color_attachment)],
-                    depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                        view: &depth_texture.view,
-                        depth_ops: Some(wgpu::Operations {
-                            load: wgpu::LoadOp::Clear(0.0),
-                            store: true,
-                        }),
-                        stencil_ops: Some(wgpu::Operations {
-                            load: wgpu::LoadOp::Clear(0),
-                            store: true,
-                        }),
-                    }),
-                });
+        draw_layers(
+            render_context,
+            state,
+            world,
+            "main_pass",
+            color_attachment(multisampling_texture, target_view, wgpu::Color::WHITE),
+            &depth_texture.view,
+            |layer| offscreen.map_or(true, |(pass, _, _)| !pass.contains(layer)),
+        );
 
-        let mut tracked_pass = TrackedRenderPass::new(render_pass);
+        if let Some((pass, texture, pipeline)) = offscreen {
+            draw_layers(
+                render_context,
+                state,
+                world,
+                "offscreen_pass",
+                color_attachment(
+                    multisampling_texture,
+                    &texture.texture.view,
+                    wgpu::Color::TRANSPARENT,
+                ),
+                &depth_texture.view,
+                |layer| pass.contains(layer),
+            );
 
-        if let Some(mask_items) = world.resources.get::<RenderPhase<TileMaskItem>>() {
-            log::trace!("RenderPhase<TileMaskItem>::size() = {}", mask_items.size());
-            for item in mask_items {
-                item.draw_function.draw(&mut tracked_pass, world, item);
-            }
+            let mut composite_pass =
+                render_context
+                    .command_encoder
+                    .begin_render_pass(&wgpu::RenderPassDescriptor {
+                        label: Some("composite_pass"),
+                        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                            view: target_view,
+                            ops: wgpu::Operations {
+                                load: wgpu::LoadOp::Load,
+                                store: true,
+                            },
+                            resolve_target: None,
+                        })],
+                        depth_stencil_attachment: None,
+                    });
+
+            composite_pass.set_pipeline(pipeline.pipeline());
+            composite_pass.set_bind_group(0, &texture.bind_group, &[]);
+            composite_pass.draw(0..3, 0..1);
         }
 
-        if let Some(layer_items) = world.resources.get::<RenderPhase<LayerItem>>() {
-            log::trace!("RenderPhase<LayerItem>::size() = {}", layer_items.size());
-            for item in layer_items {
-                item.draw_function.draw(&mut tracked_pass, world, item);
+        if let Some((texture, pipeline)) = supersampling {
+            let viewport = state.clamped_viewport();
+
+            let mut downsample_pass =
+                render_context
+                    .command_encoder
+                    .begin_render_pass(&wgpu::RenderPassDescriptor {
+                        label: Some("downsample_pass"),
+                        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                            view: render_target.deref(),
+                            ops: wgpu::Operations {
+                                // Pixels outside of the viewport are kept
+                                load: if viewport.is_some() {
+                                    wgpu::LoadOp::Load
+                                } else {
+                                    wgpu::LoadOp::Clear(wgpu::Color::WHITE)
+                                },
+                                store: true,
+                            },
+                            resolve_target: None,
+                        })],
+                        depth_stencil_attachment: None,
+                    });
+
+            if let Some(Viewport {
+                x,
+                y,
+                width,
+                height,
+            }) = viewport
+            {
+                downsample_pass.set_viewport(
+                    x as f32,
+                    y as f32,
+                    width as f32,
+                    height as f32,
+                    0.0,
+                    1.0,
+                );
+                downsample_pass.set_scissor_rect(x, y, width, height);
             }
+
+            downsample_pass.set_pipeline(pipeline.pipeline());
+            downsample_pass.set_bind_group(0, &texture.bind_group, &[]);
+            downsample_pass.draw(0..3, 0..1);
         }
 
         Ok(())
     }
 }
 
+/// The attachment which is cleared with `clear_color` and into which is drawn directly, or through
+/// the `multisampling_texture` if multisampling is enabled.
+fn color_attachment<'a>(
+    multisampling_texture: &'a Option<Texture>,
+    target_view: &'a wgpu::TextureView,
+    clear_color: wgpu::Color,
+) -> wgpu::RenderPassColorAttachment<'a> {
+    let ops = wgpu::Operations {
+        load: wgpu::LoadOp::Clear(clear_color),
+        store: true,
+    };
+
+    match multisampling_texture {
+        Some(texture) => wgpu::RenderPassColorAttachment {
+            view: &texture.view,
+            ops,
+            resolve_target: Some(target_view),
+        },
+        None => wgpu::RenderPassColorAttachment {
+            view: target_view,
+            ops,
+            resolve_target: None,
+        },
+    }
+}
+
+/// Draws the tile masks and the layers for which `include_layer` returns `true` in a render pass
+/// which is labeled `label`.
+fn draw_layers(
+    render_context: &mut RenderContext,
+    state: &RenderResources,
+    world: &World,
+    label: &str,
+    color_attachment: wgpu::RenderPassColorAttachment,
+    depth_view: &wgpu::TextureView,
+    include_layer: impl Fn(&str) -> bool,
+) {
+    let render_pass =
+        render_context
+            .command_encoder
+            .begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some(label),
+                color_attachments: &[Some(color_attachment)],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: depth_view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(0.0),
+                        store: true,
+                    }),
+                    stencil_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(0),
+                        store: true,
+                    }),
+                }),
+            });
+
+    let mut tracked_pass = TrackedRenderPass::new(render_pass);
+
+    if let Some(mask_items) = world.resources.get::<RenderPhase<TileMaskItem>>() {
+        log::trace!("RenderPhase<TileMaskItem>::size() = {}", mask_items.size());
+        for item in mask_items {
+            item.draw_function.draw(&mut tracked_pass, world, item);
+        }
+    }
+
+    if let Some(layer_items) = world.resources.get::<RenderPhase<LayerItem>>() {
+        log::trace!("RenderPhase<LayerItem>::size() = {}", layer_items.size());
+        for item in layer_items {
+            if include_layer(&item.style_layer) {
+                item.draw_function.draw(&mut tracked_pass, world, item);
+            }
+        }
+    }
+
+    state.record_stats(tracked_pass.stats());
+}
+
 pub struct MainPassDriverNode;
 
 impl Node for MainPassDriverNode {
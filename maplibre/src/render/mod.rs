@@ -18,31 +18,40 @@
 //! We appreciate the design and implementation work which as gone into it.
 //!
 
-use std::{ops::Deref, rc::Rc, sync::Arc};
+use std::{cell::Cell, ops::Deref, rc::Rc, sync::Arc};
 
 use crate::{
     environment::Environment,
     kernel::Kernel,
     plugin::Plugin,
     render::{
+        adaptive_quality::AdaptiveQuality,
+        color_space::ColorSpace,
+        compositing::{CompositePipeline, OffscreenPass, OffscreenTexture},
+        device_lost::{DeviceLost, DeviceLostCallback},
         error::RenderError,
         eventually::Eventually,
+        gpu_timer::GpuTimer,
         graph::{EmptyNode, RenderGraph},
         main_pass::{MainPassDriverNode, MainPassNode},
         resource::{Head, Surface, Texture, TextureView},
-        settings::{RendererSettings, WgpuSettings},
+        settings::{AdapterInfo, Limits, RendererSettings, WgpuSettings},
+        stats::RenderStats,
+        supersampling::{DownsamplePipeline, SupersamplingTexture},
         systems::{
-            cleanup_system::cleanup_system, resource_system::ResourceSystem,
+            adaptive_quality_system::AdaptiveQualitySystem, cleanup_system::cleanup_system,
+            eviction_system::eviction_system, resource_system::ResourceSystem,
             sort_phase_system::sort_phase_system,
             tile_view_pattern_system::tile_view_pattern_system,
         },
+        viewport::Viewport,
     },
     schedule::{Schedule, StageLabel},
     tcs::{
         system::{stage::SystemStage, SystemContainer},
         world::World,
     },
-    window::{HeadedMapWindow, MapWindow},
+    window::{HeadedMapWindow, MapWindow, WindowSize},
 };
 
 pub mod graph;
@@ -53,16 +62,24 @@ mod systems;
 mod graph_runner;
 mod main_pass;
 pub mod shaders; // TODO: Make private
+pub mod supersampling;
 
 // Public API
+pub mod adaptive_quality;
 pub mod builder;
 pub mod camera;
+pub mod color_space;
+pub mod compositing;
+pub mod device_lost;
 pub mod error;
 pub mod eventually;
+pub mod gpu_timer;
 pub mod render_commands;
 pub mod render_phase;
 pub mod settings;
+pub mod stats;
 pub mod tile_view_pattern;
+pub mod viewport;
 
 pub use shaders::ShaderVertex;
 
@@ -112,6 +129,25 @@ pub struct RenderResources {
     pub render_target: Eventually<TextureView>,
     pub depth_texture: Eventually<Texture>,
     pub multisampling_texture: Eventually<Option<Texture>>,
+    /// Multiplier of the surface resolution at which the map is rendered.
+    pub render_scale: f32,
+    /// Offscreen target which is used if the `render_scale` is not `1.0` or a viewport is set.
+    pub supersampling_texture: Eventually<Option<SupersamplingTexture>>,
+    pub downsample_pipeline: Eventually<DownsamplePipeline>,
+    /// The layers which are rendered into the `offscreen_texture`, see
+    /// [`Renderer::set_offscreen_pass`]
+    pub offscreen_pass: Option<OffscreenPass>,
+    pub offscreen_texture: Eventually<Option<OffscreenTexture>>,
+    pub composite_pipeline: Eventually<CompositePipeline>,
+    /// The rectangle of the surface into which the map is rendered. The whole surface is used if
+    /// it is `None`.
+    pub viewport: Option<Viewport>,
+    /// Measures the GPU time of every frame if
+    /// [`RendererSettings::gpu_timing`] is enabled. `None` if timestamp queries are not supported.
+    pub gpu_timer: Eventually<Option<GpuTimer>>,
+    /// The statistics of the last frame. The render graph only has shared access to the
+    /// resources, so the render passes accumulate their statistics in place.
+    stats: Cell<RenderStats>,
 }
 
 impl RenderResources {
@@ -120,10 +156,47 @@ impl RenderResources {
             render_target: Default::default(),
             depth_texture: Default::default(),
             multisampling_texture: Default::default(),
+            render_scale: 1.0,
+            supersampling_texture: Default::default(),
+            downsample_pipeline: Default::default(),
+            offscreen_pass: None,
+            offscreen_texture: Default::default(),
+            composite_pipeline: Default::default(),
+            viewport: None,
+            gpu_timer: Default::default(),
+            stats: Cell::default(),
             surface,
         }
     }
 
+    /// The statistics of the last rendered frame.
+    pub fn stats(&self) -> RenderStats {
+        self.stats.get()
+    }
+
+    pub(crate) fn record_stats(&self, stats: RenderStats) {
+        let mut total = self.stats.get();
+        total += stats;
+        self.stats.set(total);
+    }
+
+    pub(crate) fn reset_stats(&self) {
+        self.stats.set(RenderStats::default());
+    }
+
+    /// The viewport restricted to the surface.
+    pub fn clamped_viewport(&self) -> Option<Viewport> {
+        self.viewport
+            .and_then(|viewport| viewport.clamp(self.surface.size()))
+    }
+
+    /// The size of the area into which the map is rendered.
+    pub fn render_area_size(&self) -> WindowSize {
+        self.clamped_viewport()
+            .and_then(|viewport| viewport.size())
+            .unwrap_or_else(|| self.surface.size())
+    }
+
     pub fn recreate_surface<MW>(
         &mut self,
         window: &MW,
@@ -141,16 +214,20 @@ impl RenderResources {
 }
 
 pub struct Renderer {
-    pub instance: wgpu::Instance,
+    /// The instance is `None` if the device is owned by the embedding application.
+    pub instance: Option<wgpu::Instance>,
     pub device: Arc<wgpu::Device>, // TODO: Arc is needed for headless rendering. Is there a simpler solution?
-    pub queue: wgpu::Queue,
-    pub adapter: wgpu::Adapter,
+    pub queue: Arc<wgpu::Queue>,
+    /// The adapter is `None` if the device is owned by the embedding application.
+    pub adapter: Option<wgpu::Adapter>,
 
     pub wgpu_settings: WgpuSettings,
     pub settings: RendererSettings,
 
     pub resources: RenderResources,
     pub render_graph: RenderGraph,
+    /// Whether the device has been lost while rendering
+    pub device_lost: DeviceLost,
 }
 
 impl Renderer {
@@ -186,18 +263,22 @@ impl Renderer {
 
         match surface.head() {
             Head::Headed(window) => window.configure(&device),
-            Head::Headless(_) => {}
+            Head::Headless(_) | Head::External(_) => {}
         }
 
+        let device_lost = DeviceLost::default();
+        device_lost.watch(&device);
+
         Ok(Self {
-            instance,
+            instance: Some(instance),
             device: Arc::new(device),
-            queue,
-            adapter,
+            queue: Arc::new(queue),
+            adapter: Some(adapter),
             wgpu_settings,
             settings,
             resources: RenderResources::new(surface),
             render_graph: Default::default(),
+            device_lost,
         })
     }
 
@@ -227,22 +308,177 @@ impl Renderer {
 
         let surface = Surface::from_image(&device, window, &settings);
 
+        let device_lost = DeviceLost::default();
+        device_lost.watch(&device);
+
         Ok(Self {
-            instance,
+            instance: Some(instance),
             device: Arc::new(device),
-            queue,
-            adapter,
+            queue: Arc::new(queue),
+            adapter: Some(adapter),
             wgpu_settings,
             settings,
             resources: RenderResources::new(surface),
             render_graph: Default::default(),
+            device_lost,
         })
     }
 
+    /// Creates a renderer which uses the `device` and `queue` of an existing wgpu application and
+    /// renders into its `target` texture. The texture must have been created with
+    /// [`wgpu::TextureUsages::RENDER_ATTACHMENT`].
+    ///
+    /// The embedding application is responsible for presenting or sampling the `target` after
+    /// each frame.
+    pub fn from_device(
+        device: Arc<wgpu::Device>,
+        queue: Arc<wgpu::Queue>,
+        target: Arc<wgpu::Texture>,
+        wgpu_settings: WgpuSettings,
+        settings: RendererSettings,
+    ) -> Self {
+        Self {
+            instance: None,
+            device,
+            queue,
+            adapter: None,
+            wgpu_settings,
+            settings,
+            resources: RenderResources::new(Surface::from_texture(target)),
+            render_graph: Default::default(),
+            device_lost: Default::default(),
+        }
+    }
+
+    /// Requests a new device and creates the surface of the `window` again after the device has
+    /// been lost. The render graph is cleared, such that it can be built again together with all
+    /// resources which have been created with the lost device.
+    ///
+    /// Renderers which use the device of the embedding application can not be recreated.
+    pub async fn recreate<MW>(&mut self, window: &MW) -> Result<(), RenderError>
+    where
+        MW: MapWindow + HeadedMapWindow,
+    {
+        if self.instance.is_none() {
+            return Err(RenderError::ExternalDevice);
+        }
+
+        let renderer = Self::initialize(window, self.wgpu_settings.clone(), self.settings).await?;
+        self.replace_device(renderer);
+        Ok(())
+    }
+
+    /// Like [`Renderer::recreate`], but for renderers which render into an image.
+    pub async fn recreate_headless<MW>(&mut self, window: &MW) -> Result<(), RenderError>
+    where
+        MW: MapWindow,
+    {
+        if self.instance.is_none() {
+            return Err(RenderError::ExternalDevice);
+        }
+
+        let renderer =
+            Self::initialize_headless(window, self.wgpu_settings.clone(), self.settings).await?;
+        self.replace_device(renderer);
+        Ok(())
+    }
+
+    /// Takes over the device and the surface of the `renderer`. The viewport, the render scale, the
+    /// offscreen pass and the device lost callback are kept.
+    fn replace_device(&mut self, renderer: Renderer) {
+        let Renderer {
+            instance,
+            device,
+            queue,
+            adapter,
+            mut resources,
+            ..
+        } = renderer;
+
+        resources.viewport = self.resources.viewport;
+        resources.render_scale = self.resources.render_scale;
+        resources.offscreen_pass = self.resources.offscreen_pass.take();
+
+        self.instance = instance;
+        self.device = device;
+        self.queue = queue;
+        self.adapter = adapter;
+        self.resources = resources;
+        self.render_graph = RenderGraph::default();
+
+        // Errors of the new device are reported to the existing state
+        self.device_lost.take();
+        self.device_lost.watch(&self.device);
+    }
+
+    /// Invoked if the device has been lost and could not be created again, such that the
+    /// embedding application can react, e.g. by showing an error or by creating a new map.
+    pub fn set_device_lost_callback(&mut self, callback: DeviceLostCallback) {
+        self.device_lost.set_callback(callback);
+    }
+
+    /// Replaces the texture into which a renderer created by [`Renderer::from_device`] renders,
+    /// for example after the embedding application has been resized.
+    pub fn set_render_target(&mut self, target: Arc<wgpu::Texture>) {
+        self.resources.surface = Surface::from_texture(target);
+        self.resources.render_target = Eventually::Uninitialized;
+    }
+
     pub fn resize_surface(&mut self, width: u32, height: u32) {
         self.resources.surface.resize(width, height)
     }
 
+    /// Renders the map into the `viewport` of the surface instead of the whole surface. Pixels
+    /// outside of the viewport are left untouched. The map is rendered offscreen and copied into
+    /// the viewport afterwards.
+    ///
+    /// The camera needs to be resized to the viewport, see
+    /// [`MapContext::set_viewport`](crate::context::MapContext::set_viewport).
+    pub fn set_viewport(&mut self, viewport: Option<Viewport>) {
+        self.resources.viewport = viewport;
+        // Whether rendering offscreen is needed may change without a change of the size
+        self.resources.supersampling_texture = Eventually::Uninitialized;
+    }
+
+    pub fn viewport(&self) -> Option<Viewport> {
+        self.resources.viewport
+    }
+
+    /// Renders the map at a multiple of the surface resolution into an offscreen target, which is
+    /// then downsampled to the surface. For example a scale of `2.0` results in 4x supersampling.
+    /// Each pixel of the surface averages the texels it covers, up to 8x8 texels at scales of 8
+    /// and above. Headless maps can capture the offscreen target instead, see
+    /// `HeadlessMap::set_full_resolution`.
+    ///
+    /// The scaled resolution must not exceed the `max_texture_dimension_2d` limit of the device.
+    pub fn set_render_scale(&mut self, render_scale: f32) -> Result<(), RenderError> {
+        supersampling::scaled_size(
+            self.resources.surface.size(),
+            render_scale,
+            self.device.limits().max_texture_dimension_2d,
+        )?;
+        self.resources.render_scale = render_scale;
+        Ok(())
+    }
+
+    pub fn render_scale(&self) -> f32 {
+        self.resources.render_scale
+    }
+
+    /// Renders the layers of the `offscreen_pass` into an offscreen texture, which is composited
+    /// onto the map after all other layers have been drawn, see [`OffscreenPass`]. `None` draws
+    /// all layers directly.
+    pub fn set_offscreen_pass(&mut self, offscreen_pass: Option<OffscreenPass>) {
+        self.resources.offscreen_pass = offscreen_pass;
+        // The effect shader of the pipeline may have changed
+        self.resources.composite_pipeline = Eventually::Uninitialized;
+        self.resources.offscreen_texture = Eventually::Uninitialized;
+    }
+
+    pub fn offscreen_pass(&self) -> Option<&OffscreenPass> {
+        self.resources.offscreen_pass.as_ref()
+    }
+
     /// Requests a device
     async fn request_device(
         instance: &wgpu::Instance,
@@ -255,6 +491,13 @@ impl Renderer {
             .ok_or(wgpu::RequestDeviceError)?;
 
         let adapter_info = adapter.get_info();
+        log::info!(
+            "using adapter {} ({:?}, driver {} {})",
+            adapter_info.name,
+            adapter_info.backend,
+            adapter_info.driver,
+            adapter_info.driver_info
+        );
 
         #[cfg(not(target_arch = "wasm32"))]
         let trace_path = if settings.record_trace {
@@ -398,8 +641,8 @@ impl Renderer {
         Ok((adapter, device, queue))
     }
 
-    pub fn instance(&self) -> &wgpu::Instance {
-        &self.instance
+    pub fn instance(&self) -> Option<&wgpu::Instance> {
+        self.instance.as_ref()
     }
     pub fn device(&self) -> &wgpu::Device {
         &self.device
@@ -407,12 +650,31 @@ impl Renderer {
     pub fn queue(&self) -> &wgpu::Queue {
         &self.queue
     }
+    /// Information about the adapter, e.g. its name, backend and driver. Returns `None` if the
+    /// device is owned by the embedding application, see [`Renderer::from_device`].
+    pub fn adapter_info(&self) -> Option<AdapterInfo> {
+        self.adapter.as_ref().map(wgpu::Adapter::get_info)
+    }
+    /// The limits of the device, e.g. the maximum texture and buffer sizes. These can be lower
+    /// than the limits of the adapter, see [`WgpuSettings::constrained_limits`].
+    pub fn device_limits(&self) -> Limits {
+        self.device.limits()
+    }
     pub fn state(&self) -> &RenderResources {
         &self.resources
     }
+    /// The statistics of the last rendered frame, see [`RenderStats`].
+    pub fn stats(&self) -> RenderStats {
+        self.resources.stats()
+    }
     pub fn surface(&self) -> &Surface {
         &self.resources.surface
     }
+    /// Whether the pipelines work in linear or sRGB space, which depends on the format of the
+    /// surface. See [`ColorSpace`].
+    pub fn color_space(&self) -> ColorSpace {
+        self.resources.surface.color_space()
+    }
 }
 
 #[cfg(test)]
@@ -571,6 +833,7 @@ impl<E: Environment> Plugin<E> for RenderPlugin {
         resources.init::<ViewTileSources>();
         // masks
         resources.insert(Eventually::<MaskPipeline>::Uninitialized);
+        resources.init::<AdaptiveQuality>();
 
         schedule.add_stage(RenderStageLabel::Extract, SystemStage::default());
         schedule.add_stage(
@@ -593,7 +856,10 @@ impl<E: Environment> Plugin<E> for RenderPlugin {
         );
         schedule.add_stage(
             RenderStageLabel::Cleanup,
-            SystemStage::default().with_system(cleanup_system),
+            SystemStage::default()
+                .with_system(cleanup_system)
+                .with_system(eviction_system)
+                .with_system(SystemContainer::new(AdaptiveQualitySystem::default())),
         );
     }
 }
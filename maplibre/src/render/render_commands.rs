@@ -48,7 +48,8 @@ impl RenderCommand<TileMaskItem> for DrawMask {
         pass.set_vertex_buffer(
             0,
             // Mask is of the requested shape
-            tile_view_pattern.buffer().slice(tile_view_pattern_buffer),
+            tile_view_pattern.buffer(),
+            tile_view_pattern_buffer,
         );
         const TILE_MASK_SHADER_VERTICES: u32 = 6;
         pass.draw(0..TILE_MASK_SHADER_VERTICES, 0..1);
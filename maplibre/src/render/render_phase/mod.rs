@@ -1,8 +1,10 @@
 //! Describes the concept of a [`RenderPhase`] and [`PhaseItem`]
 
+use std::ops::Range;
+
 pub use draw::*;
 
-use crate::{render::tile_view_pattern::TileShape, tcs::tiles::Tile};
+use crate::{coords::WorldTileCoords, render::tile_view_pattern::TileShape, tcs::tiles::Tile};
 
 mod draw;
 
@@ -48,19 +50,24 @@ impl<I: PhaseItem> RenderPhase<I> {
 
 pub struct LayerItem {
     pub draw_function: Box<dyn Draw<LayerItem>>,
+    /// The index of the style layer, see [`StyleLayer::index`](crate::style::layer::StyleLayer::index)
     pub index: u32,
 
     pub style_layer: String,
 
     pub tile: Tile,
     pub source_shape: TileShape, // FIXME tcs: TileShape contains buffer ranges. This is bad, move them to a component?
+    /// The range of the indices of the layer which is drawn, e.g. the indices of a single
+    /// feature. All indices are drawn if `None`.
+    pub indices: Option<Range<u32>>,
 }
 
 impl PhaseItem for LayerItem {
-    type SortKey = u32;
+    type SortKey = (u32, WorldTileCoords);
 
+    /// Layers are drawn in the order of the style, such that later layers are drawn on top.
     fn sort_key(&self) -> Self::SortKey {
-        self.index
+        (self.index, self.tile.coords)
     }
 
     fn draw_function(&self) -> &dyn Draw<LayerItem> {
@@ -84,3 +91,82 @@ impl PhaseItem for TileMaskItem {
         self.draw_function.as_ref()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use cgmath::Deg;
+
+    use crate::{
+        coords::{WorldCoords, WorldTileCoords, Zoom},
+        render::{
+            render_phase::{Draw, LayerItem, RenderPhase},
+            resource::TrackedRenderPass,
+            tile_view_pattern::TileShape,
+        },
+        style::Style,
+        tcs::{tiles::Tile, world::World},
+        view_state::ViewState,
+        window::WindowSize,
+    };
+
+    struct NoDraw;
+
+    impl Draw<LayerItem> for NoDraw {
+        fn draw<'w>(
+            &self,
+            _pass: &mut TrackedRenderPass<'w>,
+            _world: &'w World,
+            _item: &LayerItem,
+        ) {
+        }
+    }
+
+    #[test]
+    fn test_layer_order() {
+        let style: Style = serde_json::from_value(serde_json::json!({
+            "version": 8,
+            "name": "Two Layers",
+            "metadata": {},
+            "sources": {},
+            "layers": [
+                { "id": "water", "type": "fill", "paint": {} },
+                { "id": "roads", "type": "line", "paint": {} }
+            ]
+        }))
+        .unwrap();
+
+        let view_state = ViewState::new(
+            WindowSize::new(800, 600).unwrap(),
+            WorldCoords::default(),
+            Zoom::new(1.0),
+            Deg(0.0),
+            Deg(110.0),
+        );
+
+        let mut phase = RenderPhase::<LayerItem>::default();
+        // Queue the items in reverse order
+        for layer in style.layers.iter().rev() {
+            for coords in [(1, 0), (0, 0)] {
+                let coords = WorldTileCoords::from((coords.0, coords.1, 1.into()));
+                phase.add(LayerItem {
+                    draw_function: Box::new(NoDraw),
+                    index: layer.index,
+                    style_layer: layer.id.clone(),
+                    tile: Tile { coords },
+                    source_shape: TileShape::new(coords, &view_state),
+                    indices: None,
+                });
+            }
+        }
+
+        phase.sort();
+
+        assert_eq!(
+            phase
+                .into_iter()
+                .map(|item| (item.style_layer.as_str(), item.tile.coords.x))
+                .collect::<Vec<_>>(),
+            vec![("water", 0), ("water", 1), ("roads", 0), ("roads", 1)]
+        );
+    }
+}
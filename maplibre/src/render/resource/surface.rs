@@ -1,5 +1,6 @@
-//! Utilities for handling surfaces which can be either headless or headed. A headed surface has
-//! a handle to a window. A headless surface renders to a texture.
+//! Utilities for handling surfaces which can be either headless, headed or external. A headed
+//! surface has a handle to a window. A headless surface renders to a texture. An external surface
+//! renders to a texture which is owned by the embedding application.
 
 use std::{mem::size_of, sync::Arc};
 
@@ -7,6 +8,7 @@ use wgpu::TextureFormatFeatures;
 
 use crate::{
     render::{
+        color_space::ColorSpace,
         error::RenderError,
         eventually::HasChanged,
         resource::texture::TextureView,
@@ -23,7 +25,7 @@ pub struct BufferDimensions {
 }
 
 impl BufferDimensions {
-    fn new(size: WindowSize) -> Self {
+    pub(crate) fn new(size: WindowSize) -> Self {
         let bytes_per_pixel = size_of::<u32>() as u32;
         let unpadded_bytes_per_row = size.width() * bytes_per_pixel;
 
@@ -145,6 +147,18 @@ impl BufferedTextureHead {
         Ok(())
     }
 
+    /// Copies the unpadded rows of the `padded_buffer` into an image.
+    pub fn to_image(&self, padded_buffer: &wgpu::BufferView<'_>) -> Option<image::RgbaImage> {
+        let dimensions = &self.buffer_dimensions;
+        let data = padded_buffer
+            .chunks(dimensions.padded_bytes_per_row as usize)
+            .flat_map(|chunk| &chunk[..dimensions.unpadded_bytes_per_row as usize])
+            .copied()
+            .collect();
+
+        image::RgbaImage::from_raw(dimensions.width, dimensions.height, data)
+    }
+
     pub fn copy_texture(&self) -> wgpu::ImageCopyTexture<'_> {
         self.texture.as_image_copy()
     }
@@ -158,9 +172,21 @@ impl BufferedTextureHead {
     }
 }
 
+/// A texture which is owned by the application that embeds the map.
+pub struct ExternalHead {
+    texture: Arc<wgpu::Texture>,
+}
+
+impl ExternalHead {
+    pub fn texture(&self) -> &Arc<wgpu::Texture> {
+        &self.texture
+    }
+}
+
 pub enum Head {
     Headed(WindowHead),
     Headless(Arc<BufferedTextureHead>),
+    External(ExternalHead),
 }
 
 pub struct Surface {
@@ -257,35 +283,57 @@ impl Surface {
         }
     }
 
+    /// Renders into the `texture` which is owned by the embedding application. The texture must
+    /// have been created with [`wgpu::TextureUsages::RENDER_ATTACHMENT`].
+    pub fn from_texture(texture: Arc<wgpu::Texture>) -> Self {
+        let size = WindowSize::new(texture.width(), texture.height())
+            .expect("Invalid size of the external texture");
+
+        Self {
+            size,
+            head: Head::External(ExternalHead { texture }),
+        }
+    }
+
     pub fn surface_format(&self) -> wgpu::TextureFormat {
         match &self.head {
             Head::Headed(headed) => headed.texture_format,
             Head::Headless(headless) => headless.texture_format,
+            Head::External(external) => external.texture.format(),
         }
     }
 
+    /// The color space in which the pipelines work that render into this surface.
+    pub fn color_space(&self) -> ColorSpace {
+        ColorSpace::of_format(self.surface_format())
+    }
+
+    /// Acquires the texture into which the next frame is rendered. Outdated or lost surfaces are
+    /// configured again once.
     #[tracing::instrument(name = "create_view", skip_all)]
-    pub fn create_view(&self, device: &wgpu::Device) -> TextureView {
+    pub fn create_view(&self, device: &wgpu::Device) -> Result<TextureView, wgpu::SurfaceError> {
         match &self.head {
             Head::Headed(window) => {
                 let WindowHead { surface, .. } = window;
                 let frame = match surface.get_current_texture() {
                     Ok(view) => view,
-                    Err(wgpu::SurfaceError::Outdated) => {
-                        log::warn!("surface outdated");
+                    Err(e @ (wgpu::SurfaceError::Outdated | wgpu::SurfaceError::Lost)) => {
+                        log::warn!("surface outdated or lost: {e}");
                         window.configure(device);
-                        surface
-                            .get_current_texture()
-                            .expect("Error reconfiguring surface")
+                        surface.get_current_texture()?
                     }
-                    err => err.expect("Failed to acquire next swap chain texture!"),
+                    Err(e) => return Err(e),
                 };
-                frame.into()
+                Ok(frame.into())
             }
-            Head::Headless(arc) => arc
+            Head::Headless(arc) => Ok(arc
+                .texture
+                .create_view(&wgpu::TextureViewDescriptor::default())
+                .into()),
+            Head::External(external) => Ok(external
                 .texture
                 .create_view(&wgpu::TextureViewDescriptor::default())
-                .into(),
+                .into()),
         }
     }
 
@@ -304,7 +352,7 @@ impl Surface {
                     window.resize_and_configure(self.size.width(), self.size.height(), device);
                 }
             }
-            Head::Headless(_) => {}
+            Head::Headless(_) | Head::External(_) => {}
         }
     }
 
@@ -322,7 +370,7 @@ impl Surface {
                     window_head.recreate_surface(window, instance)?;
                 }
             }
-            Head::Headless(_) => {}
+            Head::Headless(_) | Head::External(_) => {}
         }
         Ok(())
     }
@@ -357,6 +405,7 @@ impl Surface {
                 is_supported
             }
             Head::Headless(_) => false, // TODO: support multisampling on headless
+            Head::External(_) => false, // The adapter is unknown, so the support can not be checked
         }
     }
 }
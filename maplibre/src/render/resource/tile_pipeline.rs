@@ -7,6 +7,24 @@ use crate::render::{
     settings::RendererSettings,
 };
 
+/// The bit of the stencil which marks the pixels drawn by [`StencilMode::Once`]. The reference
+/// values of the tile masks stay below it.
+pub const STENCIL_MARK: u32 = 0x80;
+
+/// How a pipeline which draws within the tile masks treats the stencil.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum StencilMode {
+    /// Fragments are drawn within the mask of their tile.
+    #[default]
+    Masked,
+    /// Fragments are drawn within the mask of their tile and mark their pixel with
+    /// [`STENCIL_MARK`], such that overlapping fragments are drawn only once.
+    Once,
+    /// Nothing is drawn, but the marks of [`StencilMode::Once`] are removed again. The stencil
+    /// reference includes [`STENCIL_MARK`].
+    Unmark,
+}
+
 pub struct TilePipeline {
     name: Cow<'static, str>,
     /// Is the depth stencil used?
@@ -18,6 +36,11 @@ pub struct TilePipeline {
     wireframe: bool,
     msaa: bool,
     raster: bool,
+    stencil_mode: StencilMode,
+    /// Whether fragments are drawn regardless of the depth which has been written before
+    ignore_depth: bool,
+    /// Whether fragments are drawn on top of fragments with the same depth
+    equal_depth: bool,
     settings: RendererSettings,
 
     vertex_state: VertexState,
@@ -45,11 +68,35 @@ impl TilePipeline {
             wireframe,
             msaa: multisampling,
             raster,
+            stencil_mode: StencilMode::default(),
+            ignore_depth: false,
+            equal_depth: false,
             settings,
             vertex_state,
             fragment_state,
         }
     }
+
+    /// Sets how the stencil is treated if it is not updated, see [`StencilMode`].
+    pub fn with_stencil_mode(mut self, stencil_mode: StencilMode) -> Self {
+        self.stencil_mode = stencil_mode;
+        self
+    }
+
+    /// Draws fragments regardless of the depth which has been written by earlier draws. The depth
+    /// of the fragments is still written.
+    pub fn with_ignored_depth(mut self, ignore_depth: bool) -> Self {
+        self.ignore_depth = ignore_depth;
+        self
+    }
+
+    /// Draws fragments on top of fragments with the same depth, such that overlapping features of
+    /// a layer are blended with each other. Otherwise, only the first fragment of a layer is drawn
+    /// on each pixel.
+    pub fn with_equal_depth(mut self, equal_depth: bool) -> Self {
+        self.equal_depth = equal_depth;
+        self
+    }
 }
 
 impl RenderPipeline for TilePipeline {
@@ -70,9 +117,27 @@ impl RenderPipeline for TilePipeline {
                 },
                 fail_op: wgpu::StencilOperation::Keep,
                 depth_fail_op: wgpu::StencilOperation::Keep,
-                pass_op: wgpu::StencilOperation::Keep,
+                pass_op: match self.stencil_mode {
+                    StencilMode::Masked => wgpu::StencilOperation::Keep,
+                    // Flips the mark, which is limited by the write mask
+                    StencilMode::Once | StencilMode::Unmark => wgpu::StencilOperation::Invert,
+                },
             }
         };
+        let write_mask = match self.stencil_mode {
+            StencilMode::Masked => 0xff,
+            StencilMode::Once | StencilMode::Unmark => STENCIL_MARK,
+        };
+
+        // The marks are removed regardless of the depth, which has been written already
+        let ignore_depth = self.ignore_depth || self.stencil_mode == StencilMode::Unmark;
+
+        let mut fragment = self.fragment_state;
+        if self.stencil_mode == StencilMode::Unmark {
+            for target in fragment.targets.iter_mut().flatten() {
+                target.write_mask = wgpu::ColorWrites::empty();
+            }
+        }
 
         RenderPipelineDescriptor {
             label: Some(self.name),
@@ -94,12 +159,22 @@ impl RenderPipeline for TilePipeline {
                         ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
                         count: None,
                     },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
                 ]])
             } else {
                 None
             },
             vertex: self.vertex_state,
-            fragment: self.fragment_state,
+            fragment,
             primitive: wgpu::PrimitiveState {
                 topology: wgpu::PrimitiveTopology::TriangleList,
                 polygon_mode: if self.update_stencil {
@@ -120,13 +195,20 @@ impl RenderPipeline for TilePipeline {
             } else {
                 Some(wgpu::DepthStencilState {
                     format: self.settings.depth_texture_format,
-                    depth_write_enabled: !self.update_stencil,
-                    depth_compare: wgpu::CompareFunction::Greater,
+                    depth_write_enabled: !self.update_stencil
+                        && self.stencil_mode != StencilMode::Unmark,
+                    depth_compare: if ignore_depth {
+                        wgpu::CompareFunction::Always
+                    } else if self.equal_depth {
+                        wgpu::CompareFunction::GreaterEqual
+                    } else {
+                        wgpu::CompareFunction::Greater
+                    },
                     stencil: wgpu::StencilState {
                         front: stencil_state,
                         back: stencil_state,
                         read_mask: 0xff, // Applied to stencil values being read from the stencil buffer
-                        write_mask: 0xff, // Applied to fragment stencil values before being written to  the stencil buffer
+                        write_mask, // Applied to fragment stencil values before being written to  the stencil buffer
                     },
                     bias: wgpu::DepthBiasState::default(),
                 })
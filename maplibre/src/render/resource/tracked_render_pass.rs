@@ -1,20 +1,31 @@
 //! A render pass which allows tracking, for example using a tracing framework.
 
-use std::ops::Range;
+use std::ops::{Bound, Range, RangeBounds};
 
 use log::trace;
 
+use crate::render::stats::RenderStats;
+
 /// A [`RenderPass`], which tracks the current pipeline state to ensure all draw calls are valid.
 /// It is used to set the current [`RenderPipeline`], [`BindGroups`](BindGroup) and buffers.
 /// After all requirements are specified, draw calls can be issued.
 pub struct TrackedRenderPass<'a> {
     pass: wgpu::RenderPass<'a>,
+    stats: RenderStats,
 }
 
 impl<'a> TrackedRenderPass<'a> {
     /// Tracks the supplied render pass.
     pub fn new(pass: wgpu::RenderPass<'a>) -> Self {
-        Self { pass }
+        Self {
+            pass,
+            stats: RenderStats::default(),
+        }
+    }
+
+    /// The statistics of the commands which have been recorded so far.
+    pub fn stats(&self) -> RenderStats {
+        self.stats
     }
 
     /// Sets the active [`RenderPipeline`].
@@ -40,24 +51,34 @@ impl<'a> TrackedRenderPass<'a> {
     /// Assign a vertex buffer to a slot.
     ///
     /// Subsequent calls to [`TrackedRenderPass::draw`] and [`TrackedRenderPass::draw_indexed`]
-    /// will use the buffer referenced by `buffer_slice` as one of the source vertex buffer(s).
+    /// will use the `bounds` of the `buffer` as one of the source vertex buffer(s).
     ///
     /// The `slot_index` refers to the index of the matching descriptor in
     /// [`VertexState::buffers`](crate::render_resource::VertexState::buffers).
-    pub fn set_vertex_buffer(&mut self, slot_index: usize, buffer_slice: wgpu::BufferSlice<'a>) {
-        self.pass.set_vertex_buffer(slot_index as u32, buffer_slice);
+    pub fn set_vertex_buffer<S: RangeBounds<wgpu::BufferAddress>>(
+        &mut self,
+        slot_index: usize,
+        buffer: &'a wgpu::Buffer,
+        bounds: S,
+    ) {
+        self.stats.buffer_bytes += slice_size(buffer, &bounds);
+        self.pass
+            .set_vertex_buffer(slot_index as u32, buffer.slice(bounds));
     }
 
     /// Sets the active index buffer.
     ///
-    /// Subsequent calls to [`TrackedRenderPass::draw_indexed`] will use the buffer referenced by
-    /// `buffer_slice` as the source index buffer.
-    pub fn set_index_buffer(
+    /// Subsequent calls to [`TrackedRenderPass::draw_indexed`] will use the `bounds` of the
+    /// `buffer` as the source index buffer.
+    pub fn set_index_buffer<S: RangeBounds<wgpu::BufferAddress>>(
         &mut self,
-        buffer_slice: wgpu::BufferSlice<'a>,
+        buffer: &'a wgpu::Buffer,
+        bounds: S,
         index_format: wgpu::IndexFormat,
     ) {
-        self.pass.set_index_buffer(buffer_slice, index_format);
+        self.stats.buffer_bytes += slice_size(buffer, &bounds);
+        self.pass
+            .set_index_buffer(buffer.slice(bounds), index_format);
     }
 
     /// Draws primitives from the active vertex buffer(s).
@@ -65,6 +86,7 @@ impl<'a> TrackedRenderPass<'a> {
     /// The active vertex buffer(s) can be set with [`TrackedRenderPass::set_vertex_buffer`].
     pub fn draw(&mut self, vertices: Range<u32>, instances: Range<u32>) {
         trace!("draw: {vertices:?} {instances:?}");
+        self.record_draw(vertices.len(), instances.len());
         self.pass.draw(vertices, instances);
     }
 
@@ -74,6 +96,7 @@ impl<'a> TrackedRenderPass<'a> {
     /// active vertex buffer(s) can be set with [`TrackedRenderPass::set_vertex_buffer`].
     pub fn draw_indexed(&mut self, indices: Range<u32>, base_vertex: i32, instances: Range<u32>) {
         trace!("draw indexed: {indices:?} {base_vertex} {instances:?}");
+        self.record_draw(indices.len(), instances.len());
         self.pass.draw_indexed(indices, base_vertex, instances);
     }
 
@@ -95,6 +118,8 @@ impl<'a> TrackedRenderPass<'a> {
     /// ```
     pub fn draw_indirect(&mut self, indirect_buffer: &'a wgpu::Buffer, indirect_offset: u64) {
         trace!("draw indirect: {indirect_buffer:?} {indirect_offset}");
+        // The amount of triangles is only known on the GPU
+        self.stats.draw_calls += 1;
         self.pass.draw_indirect(indirect_buffer, indirect_offset);
     }
 
@@ -123,6 +148,7 @@ impl<'a> TrackedRenderPass<'a> {
         indirect_offset: u64,
     ) {
         trace!("draw indexed indirect: {indirect_buffer:?} {indirect_offset}");
+        self.stats.draw_calls += 1;
         self.pass
             .draw_indexed_indirect(indirect_buffer, indirect_offset);
     }
@@ -225,4 +251,24 @@ impl<'a> TrackedRenderPass<'a> {
         trace!("set blend constant: {color:?}");
         self.pass.set_blend_constant(color);
     }
+
+    fn record_draw(&mut self, vertices: usize, instances: usize) {
+        self.stats.draw_calls += 1;
+        self.stats.triangles += (vertices / 3 * instances) as u64;
+    }
+}
+
+/// The size in bytes of the `bounds` within the `buffer`.
+fn slice_size<S: RangeBounds<wgpu::BufferAddress>>(buffer: &wgpu::Buffer, bounds: &S) -> u64 {
+    let start = match bounds.start_bound() {
+        Bound::Included(&start) => start,
+        Bound::Excluded(&start) => start + 1,
+        Bound::Unbounded => 0,
+    };
+    let end = match bounds.end_bound() {
+        Bound::Included(&end) => end + 1,
+        Bound::Excluded(&end) => end,
+        Bound::Unbounded => buffer.size(),
+    };
+    end.saturating_sub(start)
 }
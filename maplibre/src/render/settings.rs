@@ -3,11 +3,11 @@
 use std::borrow::Cow;
 
 use wgpu::PresentMode;
-pub use wgpu::{Backends, Features, Limits, PowerPreference, TextureFormat};
+pub use wgpu::{AdapterInfo, Backend, Backends, Features, Limits, PowerPreference, TextureFormat};
 
-/// Provides configuration for renderer initialization. Use [`Device::features`](crate::renderer::Device::features),
-/// [`Device::limits`](crate::renderer::Device::limits), and the [`WgpuAdapterInfo`](crate::render_resource::WgpuAdapterInfo)
-/// resource to get runtime information about the actual adapter, backend, features, and limits.
+/// Provides configuration for renderer initialization. Use [`Renderer::adapter_info`](crate::render::Renderer::adapter_info),
+/// [`Renderer::device_limits`](crate::render::Renderer::device_limits) and [`wgpu::Device::features`]
+/// to get runtime information about the actual adapter, backend, features, and limits.
 #[derive(Clone)]
 pub struct WgpuSettings {
     pub device_label: Option<Cow<'static, str>>,
@@ -104,11 +104,22 @@ impl Default for Msaa {
 #[derive(Clone, Copy)]
 pub struct RendererSettings {
     pub msaa: Msaa,
-    /// Explicitly set a texture format or let the renderer automatically choose one
+    /// Explicitly set a texture format or let the renderer automatically choose one. sRGB formats
+    /// make the renderer work in linear space, see
+    /// [`ColorSpace`](crate::render::color_space::ColorSpace).
     pub texture_format: Option<TextureFormat>,
     pub depth_texture_format: TextureFormat,
     /// Present mode for surfaces if a surface is used.
     pub present_mode: PresentMode,
+    /// Limits how many bytes of tile geometry are uploaded to the GPU per frame. The remaining
+    /// tiles are uploaded during the following frames. At least one tile is uploaded per frame.
+    /// `None` uploads all tiles at once.
+    pub max_upload_bytes_per_frame: Option<u64>,
+    /// Measures how long the GPU takes to render each frame with timestamp queries, see
+    /// [`RenderStats::gpu_time`](crate::render::stats::RenderStats::gpu_time). Waiting for the
+    /// timestamps stalls the CPU until the frame has been rendered, so this is meant for
+    /// profiling. Ignored if the device does not support [`Features::TIMESTAMP_QUERY`].
+    pub gpu_timing: bool,
 }
 
 impl Default for RendererSettings {
@@ -119,6 +130,8 @@ impl Default for RendererSettings {
 
             depth_texture_format: TextureFormat::Depth24PlusStencil8,
             present_mode: PresentMode::AutoVsync,
+            max_upload_bytes_per_frame: Some(8 * 1024 * 1024),
+            gpu_timing: false,
         }
     }
 }
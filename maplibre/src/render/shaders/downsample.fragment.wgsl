@@ -0,0 +1,36 @@
+struct VertexOutput {
+    @location(0) tex_coords: vec2<f32>,
+    @builtin(position) position: vec4<f32>,
+};
+
+@group(0) @binding(0)
+var t_diffuse: texture_2d<f32>;
+@group(0) @binding(1)
+var s_diffuse: sampler;
+
+// Limits the taps per axis, i.e. render scales above 8 are not fully averaged.
+const MAX_TAPS: f32 = 8.0;
+
+// Averages all texels which are covered by a pixel of the render target (box filter). A single
+// bilinear sample only averages 2x2 texels, which aliases at render scales above 2.
+@fragment
+fn main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // The area of the offscreen target which is covered by the pixel, in texture coordinates
+    let footprint = fwidth(in.tex_coords);
+    let texels = footprint * vec2<f32>(textureDimensions(t_diffuse));
+
+    // One tap per covered texel, which are placed at the centers of the texels at integer scales
+    let taps = vec2<i32>(clamp(ceil(texels), vec2<f32>(1.0), vec2<f32>(MAX_TAPS)));
+    let step = footprint / vec2<f32>(taps);
+    let origin = in.tex_coords - footprint * 0.5 + step * 0.5;
+
+    var color = vec4<f32>(0.0);
+    for (var y = 0; y < taps.y; y += 1) {
+        for (var x = 0; x < taps.x; x += 1) {
+            let tex_coords = origin + step * vec2<f32>(f32(x), f32(y));
+            color += textureSampleLevel(t_diffuse, s_diffuse, tex_coords, 0.0);
+        }
+    }
+
+    return color / f32(taps.x * taps.y);
+}
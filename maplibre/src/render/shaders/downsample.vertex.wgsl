@@ -0,0 +1,18 @@
+struct VertexOutput {
+    @location(0) tex_coords: vec2<f32>,
+    @builtin(position) position: vec4<f32>,
+};
+
+// Emits a single triangle which covers the whole render target.
+@vertex
+fn main(
+    @builtin(vertex_index) vertex_idx: u32,
+) -> VertexOutput {
+    let x = f32((vertex_idx << 1u) & 2u);
+    let y = f32(vertex_idx & 2u);
+
+    let tex_coords = vec2<f32>(x, y);
+    let position = vec4<f32>(x * 2.0 - 1.0, 1.0 - y * 2.0, 0.0, 1.0);
+
+    return VertexOutput(tex_coords, position);
+}
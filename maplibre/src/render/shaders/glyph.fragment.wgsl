@@ -0,0 +1,27 @@
+struct RasterMetadata {
+    opacity: f32,
+};
+
+@group(0) @binding(0)
+var t_glyphs: texture_2d<f32>;
+@group(0) @binding(1)
+var s_glyphs: sampler;
+@group(0) @binding(2)
+var<uniform> metadata: RasterMetadata;
+
+struct Output {
+    @location(0) out_color: vec4<f32>,
+};
+
+@fragment
+fn main(@location(0) v_color: vec4<f32>, @location(1) v_tex_coords: vec2<f32>) -> Output {
+    let sdf = textureSample(t_glyphs, s_glyphs, v_tex_coords).r;
+    // The outline of the glyph lies at this distance within the signed distance field
+    let edge = 0.75;
+
+    // The outline is smoothed across about a pixel on the screen
+    let gamma = max(fwidth(sdf), 0.01);
+    let alpha = smoothstep(edge - gamma, edge + gamma, sdf);
+
+    return Output(vec4<f32>(v_color.rgb, v_color.a * alpha * metadata.opacity));
+}
@@ -0,0 +1,42 @@
+struct VertexOutput {
+    @location(0) v_color: vec4<f32>,
+    @location(1) v_tex_coords: vec2<f32>,
+    @builtin(position) position: vec4<f32>,
+};
+
+@vertex
+fn main(
+    @location(0) anchor: vec4<f32>,
+    @location(1) color: vec4<f32>,
+    @location(2) quad: vec4<f32>,
+    @location(3) tex_coords: vec4<f32>,
+    @location(4) rotation: vec2<f32>,
+    @location(5) pixel: vec2<f32>,
+    @location(6) z_index: f32,
+
+    @builtin(vertex_index) vertex_idx: u32,
+) -> VertexOutput {
+    var CORNERS: array<vec2<f32>, 6> = array<vec2<f32>, 6>(
+        vec2<f32>(0.0, 0.0),
+        vec2<f32>(0.0, 1.0),
+        vec2<f32>(1.0, 0.0),
+        vec2<f32>(1.0, 0.0),
+        vec2<f32>(0.0, 1.0),
+        vec2<f32>(1.0, 1.0),
+    );
+    let corner = CORNERS[vertex_idx];
+
+    // The corner of the bitmap in pixels, rotated along the line. The y-axis of the window points
+    // down, unlike the one of normalized device coordinates.
+    let offset = mix(quad.xy, quad.zw, corner);
+    let rotated = vec2<f32>(
+        offset.x * rotation.x - offset.y * rotation.y,
+        offset.x * rotation.y + offset.y * rotation.x,
+    );
+
+    // The offset is scaled by w such that the glyph keeps its size on the screen
+    var position = anchor + vec4<f32>(rotated * vec2<f32>(pixel.x, -pixel.y) * anchor.w, 0.0, 0.0);
+    position.z = z_index;
+
+    return VertexOutput(color, mix(tex_coords.xy, tex_coords.zw, corner), position);
+}
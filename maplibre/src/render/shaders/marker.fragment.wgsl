@@ -0,0 +1,13 @@
+struct Output {
+    @location(0) out_color: vec4<f32>,
+};
+
+@fragment
+fn main(@location(0) v_color: vec4<f32>, @location(1) v_offset: vec2<f32>) -> Output {
+    // Markers are drawn as circles within their quad
+    if (length(v_offset) > 1.0) {
+        discard;
+    }
+
+    return Output(v_color);
+}
@@ -0,0 +1,31 @@
+struct VertexOutput {
+    @location(0) v_color: vec4<f32>,
+    @location(1) v_offset: vec2<f32>,
+    @builtin(position) position: vec4<f32>,
+};
+
+@vertex
+fn main(
+    @location(11) center: vec4<f32>,
+    @location(12) color: vec4<f32>,
+    @location(13) size: vec2<f32>,
+    @location(14) z_index: f32,
+
+    @builtin(vertex_index) vertex_idx: u32,
+) -> VertexOutput {
+    var CORNERS: array<vec2<f32>, 6> = array<vec2<f32>, 6>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(-1.0, 1.0),
+        vec2<f32>(1.0, -1.0),
+        vec2<f32>(1.0, -1.0),
+        vec2<f32>(-1.0, 1.0),
+        vec2<f32>(1.0, 1.0),
+    );
+    let corner = CORNERS[vertex_idx];
+
+    // The offset is scaled by w such that the marker keeps its size on the screen
+    var position = center + vec4<f32>(corner * size * center.w, 0.0, 0.0);
+    position.z = z_index;
+
+    return VertexOutput(color, corner, position);
+}
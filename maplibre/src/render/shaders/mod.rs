@@ -6,6 +6,7 @@ use cgmath::SquareMatrix;
 use crate::{
     coords::WorldCoords,
     render::resource::{FragmentState, VertexBufferLayout, VertexState},
+    style::layer::BlendMode,
 };
 
 pub type Vec2f32 = [f32; 2];
@@ -92,14 +93,150 @@ impl Shader for TileMaskShader {
     }
 }
 
+/// Fills whole tiles with a color, e.g. to visualize the loading state of tiles.
+pub struct TileStatusShader {
+    pub format: wgpu::TextureFormat,
+}
+
+impl Shader for TileStatusShader {
+    fn describe_vertex(&self) -> VertexState {
+        VertexState {
+            source: include_str!("tile_status.vertex.wgsl"),
+            entry_point: "main",
+            buffers: vec![VertexBufferLayout {
+                array_stride: std::mem::size_of::<ShaderTileStatus>() as u64,
+                step_mode: wgpu::VertexStepMode::Instance,
+                attributes: vec![
+                    // translate
+                    wgpu::VertexAttribute {
+                        offset: 0,
+                        format: wgpu::VertexFormat::Float32x4,
+                        shader_location: 4,
+                    },
+                    wgpu::VertexAttribute {
+                        offset: 1 * wgpu::VertexFormat::Float32x4.size(),
+                        format: wgpu::VertexFormat::Float32x4,
+                        shader_location: 5,
+                    },
+                    wgpu::VertexAttribute {
+                        offset: 2 * wgpu::VertexFormat::Float32x4.size(),
+                        format: wgpu::VertexFormat::Float32x4,
+                        shader_location: 6,
+                    },
+                    wgpu::VertexAttribute {
+                        offset: 3 * wgpu::VertexFormat::Float32x4.size(),
+                        format: wgpu::VertexFormat::Float32x4,
+                        shader_location: 7,
+                    },
+                    // color
+                    wgpu::VertexAttribute {
+                        offset: 4 * wgpu::VertexFormat::Float32x4.size(),
+                        format: wgpu::VertexFormat::Float32x4,
+                        shader_location: 8,
+                    },
+                ],
+            }],
+        }
+    }
+
+    fn describe_fragment(&self) -> FragmentState {
+        FragmentState {
+            source: include_str!("basic.fragment.wgsl"),
+            entry_point: "main",
+            targets: vec![Some(wgpu::ColorTargetState {
+                format: self.format,
+                blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }
+    }
+}
+
+/// The blend state which combines the colors of a layer with the colors below it according to
+/// the `blend_mode`.
+pub fn blend_state(blend_mode: BlendMode) -> wgpu::BlendState {
+    let color = match blend_mode {
+        BlendMode::Normal => return wgpu::BlendState::ALPHA_BLENDING,
+        BlendMode::Multiply => wgpu::BlendComponent {
+            src_factor: wgpu::BlendFactor::Dst,
+            dst_factor: wgpu::BlendFactor::Zero,
+            operation: wgpu::BlendOperation::Add,
+        },
+        BlendMode::Screen => wgpu::BlendComponent {
+            src_factor: wgpu::BlendFactor::One,
+            dst_factor: wgpu::BlendFactor::OneMinusSrc,
+            operation: wgpu::BlendOperation::Add,
+        },
+        BlendMode::Add => wgpu::BlendComponent {
+            src_factor: wgpu::BlendFactor::SrcAlpha,
+            dst_factor: wgpu::BlendFactor::One,
+            operation: wgpu::BlendOperation::Add,
+        },
+    };
+
+    wgpu::BlendState {
+        color,
+        alpha: wgpu::BlendComponent::OVER,
+    }
+}
+
 pub struct VectorTileShader {
     pub format: wgpu::TextureFormat,
+    /// The blend mode of the layer. Without a blend mode, the colors below are replaced.
+    pub blend_mode: Option<BlendMode>,
+    /// Whether the features are filled with a pattern from the sprite texture instead of a color
+    pub pattern: bool,
+}
+
+impl VectorTileShader {
+    fn layer_metadata_attributes(&self) -> Vec<wgpu::VertexAttribute> {
+        let mut attributes = vec![
+            // z_index
+            wgpu::VertexAttribute {
+                offset: 0,
+                format: wgpu::VertexFormat::Float32,
+                shader_location: 10,
+            },
+        ];
+
+        if self.pattern {
+            let offset = wgpu::VertexFormat::Float32.size();
+            attributes.extend([
+                // pattern bounds
+                wgpu::VertexAttribute {
+                    offset,
+                    format: wgpu::VertexFormat::Float32x4,
+                    shader_location: 12,
+                },
+                // pattern origin
+                wgpu::VertexAttribute {
+                    offset: offset + wgpu::VertexFormat::Float32x4.size(),
+                    format: wgpu::VertexFormat::Float32x2,
+                    shader_location: 13,
+                },
+                // pattern scale
+                wgpu::VertexAttribute {
+                    offset: offset
+                        + wgpu::VertexFormat::Float32x4.size()
+                        + wgpu::VertexFormat::Float32x2.size(),
+                    format: wgpu::VertexFormat::Float32x2,
+                    shader_location: 14,
+                },
+            ]);
+        }
+
+        attributes
+    }
 }
 
 impl Shader for VectorTileShader {
     fn describe_vertex(&self) -> VertexState {
         VertexState {
-            source: include_str!("tile.vertex.wgsl"),
+            source: if self.pattern {
+                include_str!("tile_pattern.vertex.wgsl")
+            } else {
+                include_str!("tile.vertex.wgsl")
+            },
             entry_point: "main",
             buffers: vec![
                 // vertex data
@@ -159,14 +296,7 @@ impl Shader for VectorTileShader {
                 VertexBufferLayout {
                     array_stride: std::mem::size_of::<ShaderLayerMetadata>() as u64,
                     step_mode: wgpu::VertexStepMode::Instance,
-                    attributes: vec![
-                        // z_index
-                        wgpu::VertexAttribute {
-                            offset: 0,
-                            format: wgpu::VertexFormat::Float32,
-                            shader_location: 10,
-                        },
-                    ],
+                    attributes: self.layer_metadata_attributes(),
                 },
                 // features
                 VertexBufferLayout {
@@ -179,6 +309,12 @@ impl Shader for VectorTileShader {
                             format: wgpu::VertexFormat::Float32x4,
                             shader_location: 8,
                         },
+                        // data
+                        wgpu::VertexAttribute {
+                            offset: wgpu::VertexFormat::Float32x4.size(),
+                            format: wgpu::VertexFormat::Float32,
+                            shader_location: 11,
+                        },
                     ],
                 },
             ],
@@ -187,7 +323,37 @@ impl Shader for VectorTileShader {
 
     fn describe_fragment(&self) -> FragmentState {
         FragmentState {
-            source: include_str!("basic.fragment.wgsl"),
+            source: if self.pattern {
+                include_str!("tile_pattern.fragment.wgsl")
+            } else {
+                include_str!("basic.fragment.wgsl")
+            },
+            entry_point: "main",
+            targets: vec![Some(wgpu::ColorTargetState {
+                format: self.format,
+                blend: self.blend_mode.map(blend_state),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }
+    }
+}
+
+pub struct DownsampleShader {
+    pub format: wgpu::TextureFormat,
+}
+
+impl Shader for DownsampleShader {
+    fn describe_vertex(&self) -> VertexState {
+        VertexState {
+            source: include_str!("downsample.vertex.wgsl"),
+            entry_point: "main",
+            buffers: vec![],
+        }
+    }
+
+    fn describe_fragment(&self) -> FragmentState {
+        FragmentState {
+            source: include_str!("downsample.fragment.wgsl"),
             entry_point: "main",
             targets: vec![Some(wgpu::ColorTargetState {
                 format: self.format,
@@ -198,6 +364,36 @@ impl Shader for VectorTileShader {
     }
 }
 
+/// Blends a texture over the render target with an `effect` fragment shader, see
+/// [`OffscreenPass`](crate::render::compositing::OffscreenPass).
+pub struct CompositeShader {
+    pub format: wgpu::TextureFormat,
+    pub effect: &'static str,
+}
+
+impl Shader for CompositeShader {
+    fn describe_vertex(&self) -> VertexState {
+        VertexState {
+            source: include_str!("downsample.vertex.wgsl"),
+            entry_point: "main",
+            buffers: vec![],
+        }
+    }
+
+    fn describe_fragment(&self) -> FragmentState {
+        FragmentState {
+            source: self.effect,
+            entry_point: "main",
+            targets: vec![Some(wgpu::ColorTargetState {
+                format: self.format,
+                // Layers are drawn onto a transparent texture, so its colors are premultiplied
+                blend: Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }
+    }
+}
+
 #[repr(C)]
 #[derive(Copy, Clone, Pod, Zeroable)]
 pub struct ShaderCamera {
@@ -260,22 +456,48 @@ impl Default for ShaderVertex {
 #[derive(Debug, Copy, Clone, Pod, Zeroable)]
 pub struct ShaderFeatureStyle {
     pub color: Vec4f32,
+    /// Custom data which the application attached to the feature, see
+    /// [`FeatureData`](crate::vector::FeatureData).
+    pub data: f32,
 }
 
 #[repr(C)]
-#[derive(Copy, Clone, Pod, Zeroable)]
+#[derive(Copy, Clone, PartialEq, Pod, Zeroable)]
 pub struct ShaderLayerMetadata {
     pub z_index: f32,
+    /// The bounds of the pattern within the sprite texture, see [`ShaderPattern`]
+    pub pattern: ShaderPattern,
 }
 
 impl ShaderLayerMetadata {
     pub fn new(z_index: f32) -> Self {
-        Self { z_index }
+        Self {
+            z_index,
+            pattern: ShaderPattern::default(),
+        }
+    }
+
+    pub fn with_pattern(z_index: f32, pattern: ShaderPattern) -> Self {
+        Self { z_index, pattern }
     }
 }
 
+/// Describes how a sprite icon is repeated across the features of a tile. The pattern coordinates
+/// of a vertex are `origin + position * scale`, of which the fractional part is mapped into the
+/// `bounds` of the icon within the sprite texture.
 #[repr(C)]
-#[derive(Copy, Clone, Pod, Zeroable)]
+#[derive(Copy, Clone, Default, PartialEq, Pod, Zeroable)]
+pub struct ShaderPattern {
+    /// The min x, min y, max x and max y texture coordinates of the icon
+    pub bounds: Vec4f32,
+    /// The pattern coordinates of the origin of the tile, reduced to the range from 0 to 1
+    pub origin: Vec2f32,
+    /// The amount of pattern repetitions per tile unit
+    pub scale: Vec2f32,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, PartialEq, Pod, Zeroable)]
 pub struct ShaderTileMetadata {
     pub transform: Mat4x4f32,
     pub zoom_factor: f32,
@@ -290,6 +512,19 @@ impl ShaderTileMetadata {
     }
 }
 
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable)]
+pub struct ShaderTileStatus {
+    pub transform: Mat4x4f32,
+    pub color: Vec4f32,
+}
+
+impl ShaderTileStatus {
+    pub fn new(transform: Mat4x4f32, color: Vec4f32) -> Self {
+        Self { transform, color }
+    }
+}
+
 #[repr(C)]
 #[derive(Copy, Clone, Pod, Zeroable)]
 pub struct ShaderTextureVertex {
@@ -312,6 +547,22 @@ impl Default for ShaderTextureVertex {
     }
 }
 
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable)]
+pub struct ShaderRasterMetadata {
+    pub opacity: f32,
+    padding: [f32; 3],
+}
+
+impl ShaderRasterMetadata {
+    pub fn new(opacity: f32) -> Self {
+        Self {
+            opacity,
+            padding: [0.0; 3],
+        }
+    }
+}
+
 pub struct RasterTileShader {
     pub format: wgpu::TextureFormat,
 }
@@ -360,12 +611,207 @@ impl Shader for RasterTileShader {
                 VertexBufferLayout {
                     array_stride: std::mem::size_of::<ShaderLayerMetadata>() as u64,
                     step_mode: wgpu::VertexStepMode::Instance,
+                    attributes: self.layer_metadata_attributes(),
+                },
+            ],
+        }
+    }
+
+    fn describe_fragment(&self) -> FragmentState {
+        FragmentState {
+            source: include_str!("tile_raster.fragment.wgsl"),
+            entry_point: "main",
+            targets: vec![Some(wgpu::ColorTargetState {
+                format: self.format,
+                blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable)]
+pub struct ShaderMarker {
+    /// The center of the marker in clip space
+    pub center: Vec4f32,
+    pub color: Vec4f32,
+    /// Half of the width and height of the marker in normalized device coordinates
+    pub size: Vec2f32,
+    pub z_index: f32,
+    padding: f32,
+}
+
+impl ShaderMarker {
+    pub fn new(center: Vec4f32, color: Vec4f32, size: Vec2f32, z_index: f32) -> Self {
+        Self {
+            center,
+            color,
+            size,
+            z_index,
+            padding: 0.0,
+        }
+    }
+}
+
+pub struct MarkerShader {
+    pub format: wgpu::TextureFormat,
+}
+
+impl Shader for MarkerShader {
+    fn describe_vertex(&self) -> VertexState {
+        VertexState {
+            source: include_str!("marker.vertex.wgsl"),
+            entry_point: "main",
+            buffers: vec![
+                // markers
+                VertexBufferLayout {
+                    array_stride: std::mem::size_of::<ShaderMarker>() as u64,
+                    step_mode: wgpu::VertexStepMode::Instance,
                     attributes: vec![
+                        // center
+                        wgpu::VertexAttribute {
+                            offset: 0,
+                            format: wgpu::VertexFormat::Float32x4,
+                            shader_location: 11,
+                        },
+                        // color
+                        wgpu::VertexAttribute {
+                            offset: 1 * wgpu::VertexFormat::Float32x4.size(),
+                            format: wgpu::VertexFormat::Float32x4,
+                            shader_location: 12,
+                        },
+                        // size
+                        wgpu::VertexAttribute {
+                            offset: 2 * wgpu::VertexFormat::Float32x4.size(),
+                            format: wgpu::VertexFormat::Float32x2,
+                            shader_location: 13,
+                        },
                         // z_index
+                        wgpu::VertexAttribute {
+                            offset: 2 * wgpu::VertexFormat::Float32x4.size()
+                                + wgpu::VertexFormat::Float32x2.size(),
+                            format: wgpu::VertexFormat::Float32,
+                            shader_location: 14,
+                        },
+                    ],
+                },
+            ],
+        }
+    }
+
+    fn describe_fragment(&self) -> FragmentState {
+        FragmentState {
+            source: include_str!("marker.fragment.wgsl"),
+            entry_point: "main",
+            targets: vec![Some(wgpu::ColorTargetState {
+                format: self.format,
+                blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable)]
+pub struct ShaderGlyph {
+    /// The center of the glyph on the line in clip space
+    pub anchor: Vec4f32,
+    pub color: Vec4f32,
+    /// The bounds of the bitmap relative to the anchor in pixels, ordered as min x, min y, max x
+    /// and max y. The y-axis points down.
+    pub quad: Vec4f32,
+    /// The bounds of the bitmap within the glyph atlas in texture coordinates
+    pub tex_coords: Vec4f32,
+    /// The cosine and sine of the clockwise rotation of the glyph on the screen
+    pub rotation: Vec2f32,
+    /// The size of a pixel in normalized device coordinates
+    pub pixel: Vec2f32,
+    pub z_index: f32,
+    padding: [f32; 3],
+}
+
+impl ShaderGlyph {
+    pub fn new(
+        anchor: Vec4f32,
+        color: Vec4f32,
+        quad: Vec4f32,
+        tex_coords: Vec4f32,
+        rotation: Vec2f32,
+        pixel: Vec2f32,
+        z_index: f32,
+    ) -> Self {
+        Self {
+            anchor,
+            color,
+            quad,
+            tex_coords,
+            rotation,
+            pixel,
+            z_index,
+            padding: [0.0; 3],
+        }
+    }
+}
+
+pub struct GlyphShader {
+    pub format: wgpu::TextureFormat,
+}
+
+impl Shader for GlyphShader {
+    fn describe_vertex(&self) -> VertexState {
+        VertexState {
+            source: include_str!("glyph.vertex.wgsl"),
+            entry_point: "main",
+            buffers: vec![
+                // glyphs
+                VertexBufferLayout {
+                    array_stride: std::mem::size_of::<ShaderGlyph>() as u64,
+                    step_mode: wgpu::VertexStepMode::Instance,
+                    attributes: vec![
+                        // anchor
                         wgpu::VertexAttribute {
                             offset: 0,
+                            format: wgpu::VertexFormat::Float32x4,
+                            shader_location: 0,
+                        },
+                        // color
+                        wgpu::VertexAttribute {
+                            offset: 1 * wgpu::VertexFormat::Float32x4.size(),
+                            format: wgpu::VertexFormat::Float32x4,
+                            shader_location: 1,
+                        },
+                        // quad
+                        wgpu::VertexAttribute {
+                            offset: 2 * wgpu::VertexFormat::Float32x4.size(),
+                            format: wgpu::VertexFormat::Float32x4,
+                            shader_location: 2,
+                        },
+                        // tex_coords
+                        wgpu::VertexAttribute {
+                            offset: 3 * wgpu::VertexFormat::Float32x4.size(),
+                            format: wgpu::VertexFormat::Float32x4,
+                            shader_location: 3,
+                        },
+                        // rotation
+                        wgpu::VertexAttribute {
+                            offset: 4 * wgpu::VertexFormat::Float32x4.size(),
+                            format: wgpu::VertexFormat::Float32x2,
+                            shader_location: 4,
+                        },
+                        // pixel
+                        wgpu::VertexAttribute {
+                            offset: 4 * wgpu::VertexFormat::Float32x4.size()
+                                + wgpu::VertexFormat::Float32x2.size(),
+                            format: wgpu::VertexFormat::Float32x2,
+                            shader_location: 5,
+                        },
+                        // z_index
+                        wgpu::VertexAttribute {
+                            offset: 5 * wgpu::VertexFormat::Float32x4.size(),
                             format: wgpu::VertexFormat::Float32,
-                            shader_location: 10,
+                            shader_location: 6,
                         },
                     ],
                 },
@@ -375,14 +821,11 @@ impl Shader for RasterTileShader {
 
     fn describe_fragment(&self) -> FragmentState {
         FragmentState {
-            source: include_str!("tile_raster.fragment.wgsl"),
+            source: include_str!("glyph.fragment.wgsl"),
             entry_point: "main",
             targets: vec![Some(wgpu::ColorTargetState {
                 format: self.format,
-                blend: Some(wgpu::BlendState {
-                    color: wgpu::BlendComponent::REPLACE,
-                    alpha: wgpu::BlendComponent::REPLACE,
-                }),
+                blend: Some(wgpu::BlendState::ALPHA_BLENDING),
                 write_mask: wgpu::ColorWrites::ALL,
             })],
         }
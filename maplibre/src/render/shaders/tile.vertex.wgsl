@@ -25,6 +25,7 @@ fn main(
     @location(8) color: vec4<f32>,
     @location(9) zoom_factor: f32,
     @location(10) z_index: f32,
+    @location(11) data: f32,
     @builtin(instance_index) instance_idx: u32 // instance_index is used when we have multiple instances of the same "object"
 ) -> VertexOutput {
     let z = 0.0;
@@ -39,5 +40,10 @@ fn main(
     // FIXME: how to fix z-fighting?
     final_position.z = z_index;
 
-    return VertexOutput(color, final_position);
+    // The data of the application darkens the feature, such that features can be shaded by values
+    // which are not part of the tiles
+    let shade = 1.0 - clamp(data, 0.0, 1.0);
+    let shaded_color = vec4<f32>(color.rgb * shade, color.a);
+
+    return VertexOutput(shaded_color, final_position);
 }
@@ -0,0 +1,32 @@
+struct VertexOutput {
+    @location(0) pattern_coords: vec2<f32>,
+    @location(1) pattern_bounds: vec4<f32>,
+    @builtin(position) position: vec4<f32>,
+};
+
+struct PatternMetadata {
+    opacity: f32,
+};
+
+@group(0) @binding(0)
+var t_sprite: texture_2d<f32>;
+@group(0) @binding(1)
+var s_sprite: sampler;
+@group(0) @binding(2)
+var<uniform> metadata: PatternMetadata;
+
+@fragment
+fn main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let bounds = in.pattern_bounds;
+
+    // The icon of the pattern is not part of the sprite
+    if (bounds.z <= bounds.x) {
+        discard;
+    }
+
+    let tex_coords = mix(bounds.xy, bounds.zw, fract(in.pattern_coords));
+
+    // The level is fixed because fract() makes the derivatives of the coordinates discontinuous
+    let color = textureSampleLevel(t_sprite, s_sprite, tex_coords, 0.0);
+    return vec4<f32>(color.rgb, color.a * metadata.opacity);
+}
@@ -0,0 +1,34 @@
+struct VertexOutput {
+    @location(0) pattern_coords: vec2<f32>,
+    @location(1) pattern_bounds: vec4<f32>,
+    @builtin(position) position: vec4<f32>,
+};
+
+@vertex
+fn main(
+    @location(0) position: vec2<f32>,
+    @location(1) normal: vec2<f32>,
+    @location(4) translate1: vec4<f32>,
+    @location(5) translate2: vec4<f32>,
+    @location(6) translate3: vec4<f32>,
+    @location(7) translate4: vec4<f32>,
+    @location(8) color: vec4<f32>,
+    @location(9) zoom_factor: f32,
+    @location(10) z_index: f32,
+    @location(11) data: f32,
+    @location(12) pattern_bounds: vec4<f32>,
+    @location(13) pattern_origin: vec2<f32>,
+    @location(14) pattern_scale: vec2<f32>,
+) -> VertexOutput {
+    let z = 0.0;
+    let width = 3.0 * zoom_factor;
+
+    var final_position = mat4x4<f32>(translate1, translate2, translate3, translate4) * vec4<f32>(position + normal * width, z, 1.0);
+    final_position.z = z_index;
+
+    // The pattern coordinates depend on the position within the world, such that the pattern
+    // continues across tiles and stays in place while panning
+    let pattern_coords = pattern_origin + position * pattern_scale;
+
+    return VertexOutput(pattern_coords, pattern_bounds, final_position);
+}
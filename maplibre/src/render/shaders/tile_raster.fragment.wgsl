@@ -3,12 +3,19 @@ struct VertexOutput {
     @builtin(position) position: vec4<f32>,
 };
 
+struct RasterMetadata {
+    opacity: f32,
+};
+
 @group(0) @binding(0)
 var t_diffuse: texture_2d<f32>;
 @group(0) @binding(1)
 var s_diffuse: sampler;
+@group(0) @binding(2)
+var<uniform> metadata: RasterMetadata;
 
 @fragment
 fn main(in: VertexOutput) -> @location(0) vec4<f32> {
-    return textureSample(t_diffuse, s_diffuse, in.tex_coords);
+    let color = textureSample(t_diffuse, s_diffuse, in.tex_coords);
+    return vec4<f32>(color.rgb, color.a * metadata.opacity);
 }
@@ -0,0 +1,32 @@
+struct VertexOutput {
+    @location(0) v_color: vec4<f32>,
+    @builtin(position) position: vec4<f32>,
+};
+
+var<private> EXTENT: f32 = 4096.0;
+
+@vertex
+fn main(
+    @location(4) translate1: vec4<f32>,
+    @location(5) translate2: vec4<f32>,
+    @location(6) translate3: vec4<f32>,
+    @location(7) translate4: vec4<f32>,
+    @location(8) color: vec4<f32>,
+    @builtin(vertex_index) vertex_idx: u32,
+) -> VertexOutput {
+    var VERTICES: array<vec2<f32>, 6> = array<vec2<f32>, 6>(
+        vec2<f32>(0.0, 0.0),
+        vec2<f32>(0.0, EXTENT),
+        vec2<f32>(EXTENT, 0.0),
+        vec2<f32>(EXTENT, 0.0),
+        vec2<f32>(0.0, EXTENT),
+        vec2<f32>(EXTENT, EXTENT),
+    );
+    let vertex = VERTICES[vertex_idx];
+
+    var final_position = mat4x4<f32>(translate1, translate2, translate3, translate4) * vec4<f32>(vertex, 0.0, 1.0);
+    // The debug pass has no depth buffer, so any depth within the clip volume works
+    final_position.z = 0.0;
+
+    return VertexOutput(color, final_position);
+}
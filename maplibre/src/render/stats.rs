@@ -0,0 +1,29 @@
+//! Statistics about the commands which are submitted to render a frame.
+
+use std::{ops::AddAssign, time::Duration};
+
+/// The work which has been submitted to the GPU to render a frame.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RenderStats {
+    pub draw_calls: u32,
+    /// The triangles of all draw calls, assuming that triangle lists are drawn
+    pub triangles: u64,
+    /// The size of the vertex and index buffer slices which have been bound
+    pub buffer_bytes: u64,
+    /// How long the GPU took to execute the commands of the frame. Only measured if
+    /// [`RendererSettings::gpu_timing`](crate::render::settings::RendererSettings::gpu_timing) is
+    /// enabled and the device supports timestamp queries.
+    pub gpu_time: Option<Duration>,
+}
+
+impl AddAssign for RenderStats {
+    fn add_assign(&mut self, other: Self) {
+        self.draw_calls += other.draw_calls;
+        self.triangles += other.triangles;
+        self.buffer_bytes += other.buffer_bytes;
+        self.gpu_time = match (self.gpu_time, other.gpu_time) {
+            (Some(a), Some(b)) => Some(a + b),
+            (a, b) => a.or(b),
+        };
+    }
+}
@@ -0,0 +1,231 @@
+//! Supersampling (SSAA) renders the map at a multiple of the surface resolution into an offscreen
+//! target. The offscreen target is afterwards downsampled to the surface by the main pass, which
+//! averages all texels that are covered by a pixel of the surface.
+
+use crate::{
+    render::{
+        error::RenderError,
+        eventually::HasChanged,
+        resource::{RenderPipelineDescriptor, Texture},
+        settings::Msaa,
+        shaders::{DownsampleShader, Shader},
+    },
+    window::WindowSize,
+};
+
+/// Calculates the size of the offscreen render target for the given `render_scale`.
+///
+/// Fails if the scale is not a positive number or if the scaled size exceeds the
+/// `max_texture_dimension_2d` limit of the device.
+pub fn scaled_size(
+    size: WindowSize,
+    render_scale: f32,
+    max_texture_dimension_2d: u32,
+) -> Result<(u32, u32), RenderError> {
+    let invalid = RenderError::InvalidRenderScale {
+        render_scale,
+        max_texture_dimension_2d,
+    };
+
+    if !render_scale.is_finite() || render_scale <= 0.0 {
+        return Err(invalid);
+    }
+
+    let width = (size.width() as f64 * render_scale as f64).round() as u64;
+    let height = (size.height() as f64 * render_scale as f64).round() as u64;
+
+    if width == 0
+        || height == 0
+        || width > max_texture_dimension_2d as u64
+        || height > max_texture_dimension_2d as u64
+    {
+        return Err(invalid);
+    }
+
+    Ok((width as u32, height as u32))
+}
+
+/// Pipeline which samples the offscreen target and writes it to the surface.
+pub struct DownsamplePipeline {
+    pipeline: wgpu::RenderPipeline,
+    sampler: wgpu::Sampler,
+}
+
+impl DownsamplePipeline {
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat) -> Self {
+        let shader = DownsampleShader { format };
+
+        let pipeline = RenderPipelineDescriptor {
+            label: Some("downsample_pipeline".into()),
+            layout: Some(vec![vec![
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ]]),
+            vertex: shader.describe_vertex(),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            fragment: shader.describe_fragment(),
+        }
+        .initialize(device);
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("downsample sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        Self { pipeline, sampler }
+    }
+
+    pub fn pipeline(&self) -> &wgpu::RenderPipeline {
+        &self.pipeline
+    }
+}
+
+/// The offscreen render target together with the bind group which is used while downsampling.
+pub struct SupersamplingTexture {
+    pub texture: Texture,
+    pub bind_group: wgpu::BindGroup,
+}
+
+impl SupersamplingTexture {
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+        downsample_pipeline: &DownsamplePipeline,
+    ) -> Self {
+        let texture = Texture::new(
+            Some("supersampling texture"),
+            device,
+            format,
+            width,
+            height,
+            Msaa { samples: 1 },
+            wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_SRC,
+        );
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("supersampling bind group"),
+            layout: &downsample_pipeline.pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&downsample_pipeline.sampler),
+                },
+            ],
+        });
+
+        Self {
+            texture,
+            bind_group,
+        }
+    }
+}
+
+#[cfg(feature = "headless")]
+impl SupersamplingTexture {
+    /// Reads the offscreen target at the render resolution, i.e. before it is downsampled to the
+    /// surface.
+    pub fn read_image(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> Option<image::RgbaImage> {
+        use crate::render::resource::BufferDimensions;
+
+        let size = self.texture.size;
+        let dimensions = BufferDimensions::new(WindowSize::new(size.width, size.height)?);
+
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("supersampling buffer"),
+            size: (dimensions.padded_bytes_per_row * dimensions.height) as u64,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("supersampling copy encoder"),
+        });
+        encoder.copy_texture_to_buffer(
+            self.texture.texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(dimensions.padded_bytes_per_row),
+                    rows_per_image: None,
+                },
+            },
+            size,
+        );
+        queue.submit(Some(encoder.finish()));
+
+        let buffer_slice = buffer.slice(..);
+        buffer_slice.map_async(wgpu::MapMode::Read, |_| ());
+        device.poll(wgpu::Maintain::Wait);
+
+        // The rows are copied without their padding
+        let data = buffer_slice
+            .get_mapped_range()
+            .chunks(dimensions.padded_bytes_per_row as usize)
+            .flat_map(|chunk| &chunk[..dimensions.unpadded_bytes_per_row as usize])
+            .copied()
+            .collect();
+        buffer.unmap();
+
+        image::RgbaImage::from_raw(dimensions.width, dimensions.height, data)
+    }
+}
+
+impl HasChanged for SupersamplingTexture {
+    type Criteria = (u32, u32);
+
+    fn has_changed(&self, criteria: &Self::Criteria) -> bool {
+        self.texture.has_changed(criteria)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::scaled_size;
+    use crate::window::WindowSize;
+
+    #[test]
+    fn test_scaled_size() {
+        let size = WindowSize::new(800, 600).unwrap();
+
+        assert_eq!(scaled_size(size, 1.0, 8192).unwrap(), (800, 600));
+        assert_eq!(scaled_size(size, 2.0, 8192).unwrap(), (1600, 1200));
+        assert!(scaled_size(size, 0.0, 8192).is_err());
+        assert!(scaled_size(size, f32::NAN, 8192).is_err());
+        assert!(scaled_size(size, 16.0, 8192).is_err());
+    }
+}
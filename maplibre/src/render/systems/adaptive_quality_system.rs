@@ -0,0 +1,66 @@
+//! Applies the [`AdaptiveQuality`] to the renderer and the view.
+
+use std::borrow::Cow;
+
+use instant::Instant;
+
+use crate::{
+    context::MapContext,
+    render::adaptive_quality::{AdaptiveQuality, QUALITY_LEVELS},
+    tcs::system::System,
+};
+
+/// Measures the time between frames and adapts the render scale and the view region padding to
+/// the [`AdaptiveQuality`].
+#[derive(Default)]
+pub struct AdaptiveQualitySystem {
+    last_frame: Option<Instant>,
+    /// The render scale which has been configured before the quality was reduced
+    base_render_scale: Option<f32>,
+}
+
+impl System for AdaptiveQualitySystem {
+    fn name(&self) -> Cow<'static, str> {
+        "adaptive_quality".into()
+    }
+
+    fn run(
+        &mut self,
+        MapContext {
+            world,
+            view_state,
+            renderer,
+            ..
+        }: &mut MapContext,
+    ) {
+        let now = Instant::now();
+        let last_frame = self.last_frame.replace(now);
+
+        let Some(quality) = world.resources.get_mut::<AdaptiveQuality>() else { return; };
+
+        let changed = match last_frame {
+            Some(last_frame) => quality.record_frame(now.saturating_duration_since(last_frame)),
+            None => false,
+        };
+        // The quality is restored immediately if the adaptation is disabled while it is reduced
+        let restore = !quality.enabled && self.base_render_scale.is_some();
+
+        if !changed && !restore {
+            return;
+        }
+
+        let level = quality.level();
+        let base_render_scale = *self
+            .base_render_scale
+            .get_or_insert_with(|| renderer.render_scale());
+
+        if let Err(e) = renderer.set_render_scale(base_render_scale * level.render_scale) {
+            log::warn!("adapting the render scale failed: {e}");
+        }
+        view_state.set_view_region_padding(level.view_region_padding);
+
+        if level == QUALITY_LEVELS[0] {
+            self.base_render_scale = None;
+        }
+    }
+}
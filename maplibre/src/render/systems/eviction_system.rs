@@ -0,0 +1,34 @@
+//! Releases the GPU resources of evicted tiles, see
+//! [`Tiles::evict`](crate::tcs::tiles::Tiles::evict).
+
+use crate::{
+    context::MapContext,
+    raster::RasterResources,
+    render::eventually::{Eventually, Eventually::Initialized},
+    vector::VectorBufferPool,
+};
+
+pub fn eviction_system(MapContext { world, .. }: &mut MapContext) {
+    // The draw calls of this frame have been submitted, so the resources of evicted tiles are no
+    // longer used
+    let evicted = world.tiles.take_evicted();
+    if evicted.is_empty() {
+        return;
+    }
+
+    if let Some(Initialized(buffer_pool)) =
+        world.resources.get_mut::<Eventually<VectorBufferPool>>()
+    {
+        for coords in &evicted {
+            buffer_pool.remove_tile(*coords);
+        }
+    }
+
+    if let Some(Initialized(raster_resources)) =
+        world.resources.get_mut::<Eventually<RasterResources>>()
+    {
+        for coords in &evicted {
+            raster_resources.remove_tile(coords);
+        }
+    }
+}
@@ -35,6 +35,7 @@ impl System for GraphRunnerSystem {
         }: &mut MapContext,
     ) {
         render_graph.update(state);
+        state.reset_stats();
 
         if let Err(e) = RenderGraphRunner::run(render_graph, device, queue, state, world) {
             error!("Error running render graph:");
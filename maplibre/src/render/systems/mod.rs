@@ -1,6 +1,8 @@
 //! Rendering specific systems
 
+pub mod adaptive_quality_system;
 pub mod cleanup_system;
+pub mod eviction_system;
 pub mod graph_runner_system;
 pub mod resource_system;
 pub mod sort_phase_system;
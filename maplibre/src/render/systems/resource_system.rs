@@ -5,11 +5,16 @@ use std::{borrow::Cow, mem};
 use crate::{
     context::MapContext,
     render::{
-        eventually::Eventually,
+        compositing::{CompositePipeline, OffscreenTexture},
+        device_lost::DeviceLostReason,
+        eventually::{Eventually, Eventually::Initialized},
+        gpu_timer::GpuTimer,
         resource::{BackingBufferDescriptor, RenderPipeline, Texture, TilePipeline},
         settings::Msaa,
         shaders,
         shaders::{Shader, ShaderTileMetadata},
+        supersampling,
+        supersampling::{DownsamplePipeline, SupersamplingTexture},
         tile_view_pattern::{TileViewPattern, WgpuTileViewPattern, DEFAULT_TILE_VIEW_PATTERN_SIZE},
         MaskPipeline, Renderer,
     },
@@ -31,7 +36,9 @@ impl System for ResourceSystem {
                 Renderer {
                     settings,
                     device,
+                    queue,
                     resources: state,
+                    device_lost,
                     ..
                 },
             world,
@@ -46,15 +53,87 @@ impl System for ResourceSystem {
             &mut Eventually<MaskPipeline>,
         )>() else { return; };
 
-        let surface = &mut state.surface;
+        let size = state.render_area_size();
+        // The map is rendered offscreen and copied into the viewport, such that the rest of the
+        // surface is left untouched
+        let has_viewport = state.clamped_viewport().is_some();
 
-        let size = surface.size();
+        let surface = &mut state.surface;
 
         surface.reconfigure(device);
 
-        state
-            .render_target
-            .initialize(|| surface.create_view(device));
+        if let Eventually::Uninitialized = state.render_target {
+            match surface.create_view(device) {
+                Ok(view) => state.render_target = Initialized(view),
+                Err(wgpu::SurfaceError::OutOfMemory) => {
+                    device_lost.report(DeviceLostReason::OutOfMemory)
+                }
+                Err(wgpu::SurfaceError::Lost) => device_lost.report(DeviceLostReason::Lost),
+                // Nothing is rendered in this frame
+                Err(e) => log::warn!("acquiring the surface texture failed: {e}"),
+            }
+        }
+
+        let render_size = supersampling::scaled_size(
+            size,
+            state.render_scale,
+            device.limits().max_texture_dimension_2d,
+        )
+        .unwrap_or_else(|e| {
+            log::warn!("falling back to the surface resolution: {e}");
+            (size.width(), size.height())
+        });
+        let (render_width, render_height) = render_size;
+
+        let is_offscreen = has_viewport || render_size != (size.width(), size.height());
+
+        if is_offscreen {
+            state
+                .downsample_pipeline
+                .initialize(|| DownsamplePipeline::new(device, surface.surface_format()));
+        }
+
+        state.supersampling_texture.reinitialize(
+            || match &state.downsample_pipeline {
+                Initialized(downsample_pipeline) if is_offscreen => {
+                    Some(SupersamplingTexture::new(
+                        device,
+                        surface.surface_format(),
+                        render_width,
+                        render_height,
+                        downsample_pipeline,
+                    ))
+                }
+                _ => None,
+            },
+            &render_size,
+        );
+
+        if settings.gpu_timing {
+            state
+                .gpu_timer
+                .initialize(|| GpuTimer::new(device, queue));
+        }
+
+        if let Some(offscreen_pass) = &state.offscreen_pass {
+            state.composite_pipeline.initialize(|| {
+                CompositePipeline::new(device, surface.surface_format(), offscreen_pass.effect)
+            });
+        }
+
+        state.offscreen_texture.reinitialize(
+            || match (&state.offscreen_pass, &state.composite_pipeline) {
+                (Some(_), Initialized(composite_pipeline)) => Some(OffscreenTexture::new(
+                    device,
+                    surface.surface_format(),
+                    render_width,
+                    render_height,
+                    composite_pipeline,
+                )),
+                _ => None,
+            },
+            &render_size,
+        );
 
         state.depth_texture.reinitialize(
             || {
@@ -62,8 +141,8 @@ impl System for ResourceSystem {
                     Some("depth texture"),
                     device,
                     settings.depth_texture_format,
-                    size.width(),
-                    size.height(),
+                    render_width,
+                    render_height,
                     if surface.is_multisampling_supported(settings.msaa) {
                         settings.msaa
                     } else {
@@ -72,7 +151,7 @@ impl System for ResourceSystem {
                     wgpu::TextureUsages::RENDER_ATTACHMENT,
                 )
             },
-            &(size.width(), size.height()),
+            &render_size,
         );
 
         state.multisampling_texture.reinitialize(
@@ -84,8 +163,8 @@ impl System for ResourceSystem {
                         Some("multisampling texture"),
                         device,
                         surface.surface_format(),
-                        size.width(),
-                        size.height(),
+                        render_width,
+                        render_height,
                         settings.msaa,
                         wgpu::TextureUsages::RENDER_ATTACHMENT,
                     ))
@@ -93,7 +172,7 @@ impl System for ResourceSystem {
                     None
                 }
             },
-            &(size.width(), size.height()),
+            &render_size,
         );
 
         tile_view_pattern.initialize(|| {
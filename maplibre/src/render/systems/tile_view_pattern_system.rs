@@ -23,10 +23,8 @@ pub fn tile_view_pattern_system(
     let view_region = view_state.create_view_region();
 
     if let Some(view_region) = &view_region {
-        let zoom = view_state.zoom();
-
         let view_tiles =
-            tile_view_pattern.generate_pattern(view_region, view_tile_sources, zoom, world);
+            tile_view_pattern.generate_pattern(view_region, view_tile_sources, view_state, world);
 
         // TODO: Can we &mut borrow initially somehow instead of here?
         let Some(Initialized(tile_view_pattern)) = world
@@ -8,9 +8,10 @@ use cgmath::Matrix4;
 pub use pattern::{TileViewPattern, DEFAULT_TILE_VIEW_PATTERN_SIZE};
 
 use crate::{
-    coords::{WorldTileCoords, Zoom},
+    coords::WorldTileCoords,
     render::shaders::ShaderTileMetadata,
     tcs::{resources::ResourceQuery, world::World},
+    view_state::ViewState,
 };
 
 pub type WgpuTileViewPattern = TileViewPattern<wgpu::Queue, wgpu::Buffer>;
@@ -76,11 +77,11 @@ pub struct TileShape {
 }
 
 impl TileShape {
-    fn new(coords: WorldTileCoords, zoom: Zoom) -> Self {
+    pub(crate) fn new(coords: WorldTileCoords, view_state: &ViewState) -> Self {
         Self {
             coords,
-            zoom_factor: zoom.scale_to_tile(&coords),
-            transform: coords.transform_for_zoom(zoom),
+            zoom_factor: view_state.zoom().scale_to_tile(&coords),
+            transform: view_state.tile_transform(coords),
             buffer_range: None,
         }
     }
@@ -1,7 +1,7 @@
 use std::marker::PhantomData;
 
 use crate::{
-    coords::{ViewRegion, Zoom},
+    coords::ViewRegion,
     render::{
         camera::ViewProjection,
         resource::{BackingBufferDescriptor, Queue},
@@ -9,6 +9,7 @@ use crate::{
         tile_view_pattern::{HasTile, SourceShapes, TileShape, ViewTile},
     },
     tcs::world::World,
+    view_state::ViewState,
 };
 
 pub const DEFAULT_TILE_VIEW_PATTERN_SIZE: wgpu::BufferAddress = 32 * 4;
@@ -53,7 +54,7 @@ impl<Q: Queue<B>, B> TileViewPattern<Q, B> {
         &self,
         view_region: &ViewRegion,
         container: &T,
-        zoom: Zoom,
+        view_state: &ViewState,
         world: &World,
     ) -> Vec<ViewTile> {
         let mut view_tiles = Vec::with_capacity(self.view_tiles.len());
@@ -65,11 +66,11 @@ impl<Q: Queue<B>, B> TileViewPattern<Q, B> {
 
             let source_shapes = {
                 if container.has_tile(coords, world) {
-                    SourceShapes::SourceEqTarget(TileShape::new(coords, zoom))
+                    SourceShapes::SourceEqTarget(TileShape::new(coords, view_state))
                 } else if let Some(parent_coords) = container.get_available_parent(coords, world) {
                     log::debug!("Could not find data at {coords}. Falling back to {parent_coords}");
 
-                    SourceShapes::Parent(TileShape::new(parent_coords, zoom))
+                    SourceShapes::Parent(TileShape::new(parent_coords, view_state))
                 } else if let Some(children_coords) =
                     container.get_available_children(coords, world, CHILDREN_SEARCH_DEPTH)
                 {
@@ -80,7 +81,7 @@ impl<Q: Queue<B>, B> TileViewPattern<Q, B> {
                     SourceShapes::Children(
                         children_coords
                             .iter()
-                            .map(|child_coord| TileShape::new(*child_coord, zoom))
+                            .map(|child_coord| TileShape::new(*child_coord, view_state))
                             .collect(),
                     )
                 } else {
@@ -0,0 +1,80 @@
+//! Restricts rendering to a rectangle of the surface, e.g. for split-screen views or a minimap.
+
+use crate::window::WindowSize;
+
+/// A rectangle of the surface in pixels into which the map is rendered. The origin is in the
+/// top-left corner of the surface.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Viewport {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Viewport {
+    pub fn new(x: u32, y: u32, width: u32, height: u32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// Restricts the viewport to the `surface`. Returns `None` if no pixel of the viewport is on
+    /// the surface.
+    pub fn clamp(&self, surface: WindowSize) -> Option<Viewport> {
+        let width = self.width.min(surface.width().saturating_sub(self.x));
+        let height = self.height.min(surface.height().saturating_sub(self.y));
+
+        WindowSize::new(width, height)?;
+
+        Some(Viewport::new(self.x, self.y, width, height))
+    }
+
+    pub fn size(&self) -> Option<WindowSize> {
+        WindowSize::new(self.width, self.height)
+    }
+
+    /// Maps window coordinates to coordinates relative to the viewport.
+    pub fn window_to_viewport(&self, x: f64, y: f64) -> (f64, f64) {
+        (x - self.x as f64, y - self.y as f64)
+    }
+
+    /// Whether the window coordinates are within the viewport.
+    pub fn contains(&self, x: f64, y: f64) -> bool {
+        let (x, y) = self.window_to_viewport(x, y);
+        x >= 0.0 && y >= 0.0 && x < self.width as f64 && y < self.height as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Viewport;
+    use crate::window::WindowSize;
+
+    #[test]
+    fn test_clamp() {
+        let surface = WindowSize::new(800, 600).unwrap();
+
+        let left_half = Viewport::new(0, 0, 400, 600);
+        assert_eq!(left_half.clamp(surface), Some(left_half));
+        assert_eq!(
+            Viewport::new(600, 500, 400, 200).clamp(surface),
+            Some(Viewport::new(600, 500, 200, 100))
+        );
+        assert_eq!(Viewport::new(800, 0, 100, 100).clamp(surface), None);
+        assert_eq!(Viewport::new(0, 0, 0, 100).clamp(surface), None);
+    }
+
+    #[test]
+    fn test_window_to_viewport() {
+        let right_half = Viewport::new(400, 0, 400, 600);
+
+        assert_eq!(right_half.window_to_viewport(600.0, 300.0), (200.0, 300.0));
+        assert!(right_half.contains(400.0, 0.0));
+        assert!(!right_half.contains(399.0, 0.0));
+        assert!(!right_half.contains(800.0, 300.0));
+    }
+}
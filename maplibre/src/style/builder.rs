@@ -0,0 +1,211 @@
+//! Builds styles in code, e.g. for tests or maps whose style is not loaded from JSON.
+
+use std::collections::{HashMap, HashSet};
+
+use csscolorparser::Color;
+use thiserror::Error;
+
+use crate::{
+    coords::LatLon,
+    style::{
+        layer::{FillPaint, LayerPaint, LinePaint, StyleLayer},
+        source::Source,
+        Style,
+    },
+};
+
+#[derive(Error, Debug)]
+pub enum StyleBuilderError {
+    #[error("layer {layer} references no source")]
+    NoSource { layer: String },
+    #[error("layer {layer} references the unknown source {source_id}")]
+    UnknownSource { layer: String, source_id: String },
+    #[error("the layer id {0} is used by multiple layers")]
+    DuplicateLayer(String),
+}
+
+/// Builds a [`Style`] from sources and layers. Layers are drawn in the order in which they are
+/// added.
+///
+/// The layers which are added with [`add_fill_layer`](StyleBuilder::add_fill_layer) and
+/// [`add_line_layer`](StyleBuilder::add_line_layer) reference the source which has been added
+/// last.
+#[derive(Default)]
+pub struct StyleBuilder {
+    name: Option<String>,
+    sources: HashMap<String, Source>,
+    last_source: Option<String>,
+    layers: Vec<StyleLayer>,
+    center: Option<LatLon>,
+    zoom: Option<f64>,
+    pitch: Option<f64>,
+}
+
+impl StyleBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = Some(name.to_string());
+        self
+    }
+
+    pub fn with_center(mut self, center: LatLon) -> Self {
+        self.center = Some(center);
+        self
+    }
+
+    pub fn with_zoom(mut self, zoom: f64) -> Self {
+        self.zoom = Some(zoom);
+        self
+    }
+
+    pub fn with_pitch(mut self, pitch: f64) -> Self {
+        self.pitch = Some(pitch);
+        self
+    }
+
+    /// Adds the `source` with the `id`. A previous source with the same `id` is replaced.
+    pub fn add_source(mut self, id: &str, source: Source) -> Self {
+        self.sources.insert(id.to_string(), source);
+        self.last_source = Some(id.to_string());
+        self
+    }
+
+    /// Adds the `layer` on top of the previous layers.
+    pub fn add_layer(mut self, layer: StyleLayer) -> Self {
+        self.layers.push(layer);
+        self
+    }
+
+    /// Adds a layer which fills the polygons of the `source_layer` with the `color`.
+    pub fn add_fill_layer(self, id: &str, source_layer: &str, color: Color) -> Self {
+        let paint = LayerPaint::Fill(FillPaint {
+            fill_color: Some(color),
+            fill_pattern: None,
+        });
+        self.add_paint_layer(id, source_layer, paint)
+    }
+
+    /// Adds a layer which draws the lines of the `source_layer` with the `color`.
+    pub fn add_line_layer(self, id: &str, source_layer: &str, color: Color) -> Self {
+        let paint = LayerPaint::Line(LinePaint {
+            line_color: Some(color),
+        });
+        self.add_paint_layer(id, source_layer, paint)
+    }
+
+    fn add_paint_layer(self, id: &str, source_layer: &str, paint: LayerPaint) -> Self {
+        let source = self.last_source.clone();
+        self.add_layer(StyleLayer {
+            id: id.to_string(),
+            paint: Some(paint),
+            source,
+            source_layer: Some(source_layer.to_string()),
+            ..StyleLayer::default()
+        })
+    }
+
+    /// Builds the style. Fails if a layer does not reference a source which has been added or if
+    /// multiple layers have the same id.
+    pub fn build(self) -> Result<Style, StyleBuilderError> {
+        let mut ids = HashSet::new();
+
+        for layer in &self.layers {
+            if !ids.insert(layer.id.as_str()) {
+                return Err(StyleBuilderError::DuplicateLayer(layer.id.clone()));
+            }
+
+            let Some(source) = &layer.source else {
+                return Err(StyleBuilderError::NoSource {
+                    layer: layer.id.clone(),
+                });
+            };
+
+            if !self.sources.contains_key(source) {
+                return Err(StyleBuilderError::UnknownSource {
+                    layer: layer.id.clone(),
+                    source_id: source.clone(),
+                });
+            }
+        }
+
+        let default = Style::default();
+        Ok(Style {
+            name: self.name.unwrap_or(default.name),
+            sources: self.sources,
+            layers: self
+                .layers
+                .into_iter()
+                .enumerate()
+                .map(|(index, layer)| StyleLayer {
+                    index: index as u32,
+                    ..layer
+                })
+                .collect(),
+            center: self
+                .center
+                .map(|center| [center.latitude, center.longitude])
+                .or(default.center),
+            zoom: self.zoom.or(default.zoom),
+            pitch: self.pitch.or(default.pitch),
+            ..default
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, str::FromStr};
+
+    use csscolorparser::Color;
+
+    use super::{StyleBuilder, StyleBuilderError};
+    use crate::{
+        coords::ZoomLevel,
+        style::source::{Source, VectorSource},
+        vector::requested_source_layers,
+    };
+
+    #[test]
+    fn test_build_style() {
+        let style = StyleBuilder::new()
+            .add_source("openmaptiles", Source::Vector(VectorSource::default()))
+            .add_fill_layer("water", "water", Color::from_str("#aad3df").unwrap())
+            .add_line_layer(
+                "road",
+                "transportation",
+                Color::from_str("#ffffff").unwrap(),
+            )
+            .build()
+            .unwrap();
+
+        assert_eq!(style.layers.len(), 2);
+        assert_eq!(style.layers[1].index, 1);
+        assert_eq!(style.layers[1].source.as_deref(), Some("openmaptiles"));
+
+        assert_eq!(
+            requested_source_layers(&style, ZoomLevel::from(10))[&Some("openmaptiles".to_string())],
+            HashSet::from(["water".to_string(), "transportation".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_unknown_source() {
+        let no_source = StyleBuilder::new()
+            .add_fill_layer("water", "water", Color::from_str("#aad3df").unwrap())
+            .build();
+        assert!(matches!(no_source, Err(StyleBuilderError::NoSource { .. })));
+
+        let duplicate = StyleBuilder::new()
+            .add_source("openmaptiles", Source::Vector(VectorSource::default()))
+            .add_fill_layer("water", "water", Color::from_str("#aad3df").unwrap())
+            .add_fill_layer("water", "ocean", Color::from_str("#aad3df").unwrap())
+            .build();
+        assert!(matches!(
+            duplicate,
+            Err(StyleBuilderError::DuplicateLayer(id)) if id == "water"
+        ));
+    }
+}
@@ -0,0 +1,477 @@
+//! Glyphs of the fonts which are referenced by styles, e.g. by `text-field`. Glyphs are served as
+//! signed distance fields in ranges of consecutive code points, see
+//! [`glyphs_url`].
+
+use std::{cmp::Reverse, collections::HashMap};
+
+use thiserror::Error;
+
+/// The font size in pixels at which the signed distance fields of the glyphs are rendered.
+pub const GLYPH_SIZE: f64 = 24.0;
+
+/// The border in pixels around the outline of every glyph, within which its distance field fades
+/// out.
+pub const GLYPH_BORDER: u32 = 3;
+
+/// The width of the atlas image in pixels, unless a glyph is wider. Glyphs are packed into rows
+/// of this width.
+const ATLAS_WIDTH: u32 = 256;
+
+/// The space in pixels which is kept free between the glyphs of the atlas, such that glyphs do
+/// not bleed into each other when sampled linearly.
+const ATLAS_PADDING: u32 = 1;
+
+/// The amount of glyphs within a single glyph range. Glyphs are fetched in ranges of consecutive
+/// code points, e.g. `0-255`.
+pub const GLYPH_RANGE_SIZE: u32 = 256;
+
+/// Resolves the URL of the glyph range which contains the `code_point` from the `glyphs` template
+/// of a style. The template contains the place holders `{fontstack}` and `{range}`.
+pub fn glyphs_url(glyphs: &str, font_stack: &[&str], code_point: u32) -> String {
+    let start = code_point / GLYPH_RANGE_SIZE * GLYPH_RANGE_SIZE;
+    let end = start + GLYPH_RANGE_SIZE - 1;
+
+    glyphs
+        .replace("{fontstack}", &font_stack.join(",").replace(' ', "%20"))
+        .replace("{range}", &format!("{start}-{end}"))
+}
+
+#[derive(Error, Debug)]
+pub enum GlyphError {
+    #[error("the glyph range is truncated")]
+    Truncated,
+    #[error("the glyph range contains the unsupported wire type {0}")]
+    WireType(u64),
+    #[error("the bitmap of the glyph {id} has {actual} bytes instead of {expected}")]
+    BitmapSize {
+        id: u32,
+        expected: usize,
+        actual: usize,
+    },
+}
+
+/// A glyph of a glyph range, as specified by the glyph protocol buffer of MapLibre.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GlyphBitmap {
+    /// The code point of the glyph
+    pub id: u32,
+    /// The signed distance field of the outline, surrounded by a [`GLYPH_BORDER`] on every side.
+    /// It is empty for glyphs without an outline, like spaces.
+    pub bitmap: Vec<u8>,
+    /// The width of the outline without the border
+    pub width: u32,
+    /// The height of the outline without the border
+    pub height: u32,
+    /// The offset of the outline to the right of the pen position
+    pub left: i32,
+    /// The offset of the top of the outline above the baseline
+    pub top: i32,
+    /// The distance by which the pen moves after the glyph
+    pub advance: u32,
+}
+
+impl GlyphBitmap {
+    /// The width and height of the bitmap including the border, or zero if there is no bitmap.
+    fn bitmap_size(&self) -> (u32, u32) {
+        if self.bitmap.is_empty() {
+            return (0, 0);
+        }
+        (
+            self.width + 2 * GLYPH_BORDER,
+            self.height + 2 * GLYPH_BORDER,
+        )
+    }
+}
+
+/// Decodes the glyphs of a glyph range. The glyphs of all font stacks of the range are returned.
+pub fn parse_glyphs(data: &[u8]) -> Result<Vec<GlyphBitmap>, GlyphError> {
+    let mut glyphs = Vec::new();
+
+    for field in Fields(data) {
+        // The font stacks of the range
+        let (1, Value::Bytes(stack)) = field? else { continue; };
+
+        for field in Fields(stack) {
+            // The glyphs of the font stack
+            let (3, Value::Bytes(glyph)) = field? else { continue; };
+            glyphs.push(parse_glyph(glyph)?);
+        }
+    }
+
+    Ok(glyphs)
+}
+
+fn parse_glyph(data: &[u8]) -> Result<GlyphBitmap, GlyphError> {
+    let mut glyph = GlyphBitmap::default();
+
+    for field in Fields(data) {
+        match field? {
+            (1, Value::Varint(id)) => glyph.id = id as u32,
+            (2, Value::Bytes(bitmap)) => glyph.bitmap = bitmap.to_vec(),
+            (3, Value::Varint(width)) => glyph.width = width as u32,
+            (4, Value::Varint(height)) => glyph.height = height as u32,
+            (5, Value::Varint(left)) => glyph.left = decode_zigzag(left),
+            (6, Value::Varint(top)) => glyph.top = decode_zigzag(top),
+            (7, Value::Varint(advance)) => glyph.advance = advance as u32,
+            _ => {}
+        }
+    }
+
+    let (width, height) = glyph.bitmap_size();
+    let expected = width as usize * height as usize;
+    if glyph.bitmap.len() != expected {
+        return Err(GlyphError::BitmapSize {
+            id: glyph.id,
+            expected,
+            actual: glyph.bitmap.len(),
+        });
+    }
+
+    Ok(glyph)
+}
+
+/// The value of a field of a protocol buffer.
+enum Value<'a> {
+    Varint(u64),
+    Bytes(&'a [u8]),
+    /// A fixed-size value, which is not used by glyph ranges
+    Fixed,
+}
+
+/// Iterates the fields of a protocol buffer message along with their field numbers.
+struct Fields<'a>(&'a [u8]);
+
+impl<'a> Fields<'a> {
+    fn varint(&mut self) -> Result<u64, GlyphError> {
+        let mut value = 0;
+        for shift in (0..64).step_by(7) {
+            let (&byte, rest) = self.0.split_first().ok_or(GlyphError::Truncated)?;
+            self.0 = rest;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(GlyphError::Truncated)
+    }
+
+    fn take(&mut self, length: usize) -> Result<&'a [u8], GlyphError> {
+        if self.0.len() < length {
+            return Err(GlyphError::Truncated);
+        }
+        let (bytes, rest) = self.0.split_at(length);
+        self.0 = rest;
+        Ok(bytes)
+    }
+
+    fn field(&mut self) -> Result<(u64, Value<'a>), GlyphError> {
+        let key = self.varint()?;
+        let value = match key & 0x7 {
+            0 => Value::Varint(self.varint()?),
+            1 => {
+                self.take(8)?;
+                Value::Fixed
+            }
+            2 => {
+                let length = self.varint()? as usize;
+                Value::Bytes(self.take(length)?)
+            }
+            5 => {
+                self.take(4)?;
+                Value::Fixed
+            }
+            wire_type => return Err(GlyphError::WireType(wire_type)),
+        };
+        Ok((key >> 3, value))
+    }
+}
+
+impl<'a> Iterator for Fields<'a> {
+    type Item = Result<(u64, Value<'a>), GlyphError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.0.is_empty() {
+            return None;
+        }
+
+        let field = self.field();
+        if field.is_err() {
+            // Nothing can be read after a malformed field
+            self.0 = &[];
+        }
+        Some(field)
+    }
+}
+
+fn decode_zigzag(value: u64) -> i32 {
+    ((value >> 1) as i64 ^ -((value & 1) as i64)) as i32
+}
+
+/// The position of a glyph within the [`GlyphAtlas`] together with its metrics.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GlyphMetrics {
+    pub x: u32,
+    pub y: u32,
+    /// The width of the bitmap including the border, zero for glyphs without an outline
+    pub width: u32,
+    /// The height of the bitmap including the border, zero for glyphs without an outline
+    pub height: u32,
+    pub left: i32,
+    pub top: i32,
+    pub advance: u32,
+}
+
+/// The signed distance fields of glyphs packed into a single image, from which labels are drawn.
+#[derive(Debug, Clone)]
+pub struct GlyphAtlas {
+    width: u32,
+    height: u32,
+    /// The distance fields with one byte per pixel
+    pixels: Vec<u8>,
+    glyphs: HashMap<char, GlyphMetrics>,
+}
+
+impl GlyphAtlas {
+    /// Packs the `glyphs` into rows of the atlas. Glyphs with the same code point replace the
+    /// earlier ones, glyphs whose id is not a valid character are skipped.
+    pub fn new(glyphs: impl IntoIterator<Item = GlyphBitmap>) -> Self {
+        let glyphs = glyphs
+            .into_iter()
+            .filter_map(|glyph| Some((char::from_u32(glyph.id)?, glyph)))
+            .collect::<HashMap<_, _>>();
+
+        // Taller glyphs are packed first, such that the rows are filled evenly
+        let mut order = glyphs.iter().collect::<Vec<_>>();
+        order.sort_by_key(|(character, glyph)| (Reverse(glyph.bitmap_size().1), **character));
+
+        // The atlas is widened for glyphs which do not fit into a row
+        let atlas_width = order
+            .iter()
+            .map(|(_, glyph)| glyph.bitmap_size().0 + 2 * ATLAS_PADDING)
+            .fold(ATLAS_WIDTH, u32::max);
+
+        let (mut x, mut y, mut row_height) = (ATLAS_PADDING, ATLAS_PADDING, 0);
+        let mut positions = HashMap::with_capacity(order.len());
+        for (character, glyph) in &order {
+            let (width, height) = glyph.bitmap_size();
+            if x + width + ATLAS_PADDING > atlas_width {
+                x = ATLAS_PADDING;
+                y += row_height + ATLAS_PADDING;
+                row_height = 0;
+            }
+            positions.insert(**character, (x, y));
+            x += width + ATLAS_PADDING;
+            row_height = row_height.max(height);
+        }
+
+        let height = y + row_height + ATLAS_PADDING;
+        let mut pixels = vec![0; (atlas_width * height) as usize];
+        let mut metrics = HashMap::with_capacity(glyphs.len());
+        for (character, glyph) in glyphs {
+            let (x, y) = positions[&character];
+            let (width, height) = glyph.bitmap_size();
+
+            for (row, line) in glyph.bitmap.chunks_exact(width.max(1) as usize).enumerate() {
+                let start = ((y + row as u32) * atlas_width + x) as usize;
+                pixels[start..start + line.len()].copy_from_slice(line);
+            }
+
+            metrics.insert(
+                character,
+                GlyphMetrics {
+                    x,
+                    y,
+                    width,
+                    height,
+                    left: glyph.left,
+                    top: glyph.top,
+                    advance: glyph.advance,
+                },
+            );
+        }
+
+        Self {
+            width: atlas_width,
+            height,
+            pixels,
+            glyphs: metrics,
+        }
+    }
+
+    /// Decodes the glyph `ranges`, e.g. fetched from [`glyphs_url`], and packs their glyphs into
+    /// an atlas.
+    pub fn from_ranges(ranges: &[&[u8]]) -> Result<Self, GlyphError> {
+        let mut glyphs = Vec::new();
+        for range in ranges {
+            glyphs.extend(parse_glyphs(range)?);
+        }
+        Ok(Self::new(glyphs))
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    pub fn glyph(&self, character: char) -> Option<&GlyphMetrics> {
+        self.glyphs.get(&character)
+    }
+
+    /// The bounds of the bitmap of the glyph of the `character` in texture coordinates, ordered as
+    /// min x, min y, max x and max y.
+    pub fn texture_bounds(&self, character: char) -> Option<[f32; 4]> {
+        let glyph = self.glyph(character)?;
+        let (width, height) = (self.width as f32, self.height as f32);
+        Some([
+            glyph.x as f32 / width,
+            glyph.y as f32 / height,
+            (glyph.x + glyph.width) as f32 / width,
+            (glyph.y + glyph.height) as f32 / height,
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{glyphs_url, parse_glyphs, GlyphAtlas, GlyphBitmap, GlyphError, GLYPH_BORDER};
+
+    fn varint(buffer: &mut Vec<u8>, mut value: u64) {
+        while value >= 0x80 {
+            buffer.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        buffer.push(value as u8);
+    }
+
+    fn bytes(buffer: &mut Vec<u8>, field: u64, bytes: &[u8]) {
+        varint(buffer, field << 3 | 2);
+        varint(buffer, bytes.len() as u64);
+        buffer.extend_from_slice(bytes);
+    }
+
+    fn number(buffer: &mut Vec<u8>, field: u64, value: u64) {
+        varint(buffer, field << 3);
+        varint(buffer, value);
+    }
+
+    /// Encodes the `glyph` like a glyph range with a single font stack
+    fn encode(glyph: &GlyphBitmap) -> Vec<u8> {
+        let zigzag = |value: i32| ((value << 1) ^ (value >> 31)) as u32 as u64;
+
+        let mut encoded = Vec::new();
+        number(&mut encoded, 1, glyph.id as u64);
+        if !glyph.bitmap.is_empty() {
+            bytes(&mut encoded, 2, &glyph.bitmap);
+        }
+        number(&mut encoded, 3, glyph.width as u64);
+        number(&mut encoded, 4, glyph.height as u64);
+        number(&mut encoded, 5, zigzag(glyph.left));
+        number(&mut encoded, 6, zigzag(glyph.top));
+        number(&mut encoded, 7, glyph.advance as u64);
+
+        let mut stack = Vec::new();
+        bytes(&mut stack, 1, b"Open Sans Regular");
+        bytes(&mut stack, 2, b"0-255");
+        bytes(&mut stack, 3, &encoded);
+
+        let mut range = Vec::new();
+        bytes(&mut range, 1, &stack);
+        range
+    }
+
+    fn glyph(character: char, width: u32, height: u32) -> GlyphBitmap {
+        let size = (width + 2 * GLYPH_BORDER) * (height + 2 * GLYPH_BORDER);
+        GlyphBitmap {
+            id: character as u32,
+            bitmap: (0..size).map(|i| i as u8).collect(),
+            width,
+            height,
+            left: 1,
+            top: -7,
+            advance: width + 2,
+        }
+    }
+
+    #[test]
+    fn test_parse_glyphs() {
+        let a = glyph('a', 10, 12);
+        assert_eq!(parse_glyphs(&encode(&a)).unwrap(), vec![a.clone()]);
+
+        // Spaces have no bitmap
+        let space = GlyphBitmap {
+            id: ' ' as u32,
+            advance: 6,
+            ..GlyphBitmap::default()
+        };
+        assert_eq!(parse_glyphs(&encode(&space)).unwrap(), vec![space]);
+
+        let range = encode(&a);
+        assert!(matches!(
+            parse_glyphs(&range[..range.len() - 1]),
+            Err(GlyphError::Truncated)
+        ));
+
+        let mut wrong_size = a;
+        wrong_size.width = 11;
+        assert!(matches!(
+            parse_glyphs(&encode(&wrong_size)),
+            Err(GlyphError::BitmapSize { id: 97, .. })
+        ));
+    }
+
+    #[test]
+    fn test_glyph_atlas() {
+        let a = glyph('a', 10, 12);
+        let b = glyph('b', 240, 2);
+        let atlas = GlyphAtlas::from_ranges(&[&encode(&a), &encode(&b)]).unwrap();
+
+        // The wide glyph does not fit next to the taller one
+        let metrics = atlas.glyph('a').unwrap();
+        assert_eq!((metrics.x, metrics.y), (1, 1));
+        assert_eq!((metrics.width, metrics.height), (16, 18));
+        assert_eq!((metrics.left, metrics.top, metrics.advance), (1, -7, 12));
+        let metrics = atlas.glyph('b').unwrap();
+        assert_eq!((metrics.x, metrics.y), (1, 20));
+
+        // The bitmap is copied row by row
+        let row = |y: u32| &atlas.pixels()[(y * atlas.width() + 1) as usize..][..16];
+        assert_eq!(row(1), &a.bitmap[..16]);
+        assert_eq!(row(2), &a.bitmap[16..32]);
+
+        let [min_x, min_y, max_x, max_y] = atlas.texture_bounds('a').unwrap();
+        assert_eq!((min_x, max_x), (1.0 / 256.0, 17.0 / 256.0));
+        assert_eq!(
+            (min_y, max_y),
+            (1.0 / atlas.height() as f32, 19.0 / atlas.height() as f32)
+        );
+        assert!(atlas.glyph('c').is_none());
+
+        // Glyphs which are wider than a row widen the atlas
+        let atlas = GlyphAtlas::new([glyph('c', 300, 2)]);
+        assert_eq!(atlas.width(), 308);
+        assert_eq!(atlas.texture_bounds('c').unwrap()[2], 307.0 / 308.0);
+    }
+
+    #[test]
+    fn test_glyphs_url() {
+        let glyphs = "https://example.com/fonts/{fontstack}/{range}.pbf";
+        let font_stack = ["Open Sans Regular", "Arial Unicode MS Regular"];
+
+        // 中 lies within the range 19968-20223
+        assert_eq!(
+            glyphs_url(glyphs, &font_stack, '中' as u32),
+            "https://example.com/fonts/Open%20Sans%20Regular,Arial%20Unicode%20MS%20Regular/19968-20223.pbf"
+        );
+        assert_eq!(
+            glyphs_url(glyphs, &font_stack, 20000),
+            glyphs_url(glyphs, &font_stack, '中' as u32)
+        );
+    }
+}
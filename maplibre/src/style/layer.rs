@@ -6,7 +6,7 @@ use cint::{Alpha, EncodedSrgb};
 use csscolorparser::Color;
 use serde::{Deserialize, Serialize};
 
-use crate::style::raster::RasterLayer;
+use crate::{coords::ZoomLevel, style::raster::RasterLayer};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct BackgroundPaint {
@@ -21,6 +21,10 @@ pub struct FillPaint {
     #[serde(rename = "fill-color")]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub fill_color: Option<Color>,
+    /// The name of the sprite icon which is repeated across the fill.
+    #[serde(rename = "fill-pattern")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fill_pattern: Option<String>,
     // TODO a lot
 }
 
@@ -32,6 +36,9 @@ pub struct LinePaint {
     // TODO a lot
 }
 
+/// The layer types which are supported by the renderer.
+pub const SUPPORTED_LAYER_TYPES: [&str; 4] = ["background", "line", "fill", "raster"];
+
 /// The different types of paints.
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(tag = "type", content = "paint")]
@@ -58,16 +65,112 @@ impl LayerPaint {
             LayerPaint::Raster(_) => None,
         }
     }
+
+    /// The name of the sprite icon which is repeated across the features of the layer.
+    pub fn get_pattern(&self) -> Option<&str> {
+        match self {
+            LayerPaint::Fill(paint) => paint.fill_pattern.as_deref(),
+            _ => None,
+        }
+    }
+}
+
+/// The shape of the ends of lines.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LineCap {
+    /// The line ends exactly at its end point.
+    #[default]
+    #[serde(rename = "butt")]
+    Butt,
+    /// The line is extended by a half circle beyond its end point.
+    #[serde(rename = "round")]
+    Round,
+    /// The line is extended by a half square beyond its end point.
+    #[serde(rename = "square")]
+    Square,
+}
+
+/// The shape of the corners where two segments of a line meet.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LineJoin {
+    /// The outer edges of the segments are extended until they meet.
+    #[default]
+    #[serde(rename = "miter")]
+    Miter,
+    /// The corner is rounded off by a circular arc.
+    #[serde(rename = "round")]
+    Round,
+    /// The corner is cut off.
+    #[serde(rename = "bevel")]
+    Bevel,
+}
+
+/// Whether a layer is drawn. Hidden layers are neither requested nor drawn, until they are shown,
+/// see [`Style::set_layer_visibility`](crate::style::Style::set_layer_visibility).
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Visibility {
+    #[default]
+    #[serde(rename = "visible")]
+    Visible,
+    #[serde(rename = "none")]
+    None,
+}
+
+/// The layout properties of a layer. Properties which do not apply to the type of the layer are
+/// ignored.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct LayerLayout {
+    #[serde(rename = "line-cap")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line_cap: Option<LineCap>,
+    #[serde(rename = "line-join")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line_join: Option<LineJoin>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub visibility: Option<Visibility>,
+    /// The text with which the features are labeled. `{property}` is replaced by the property of
+    /// the feature, see [`format_text_field`](crate::overlay::line_label::format_text_field).
+    /// Line layers place the label along the line.
+    #[serde(rename = "text-field")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text_field: Option<String>,
+    /// The font size of the labels in pixels
+    #[serde(rename = "text-size")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text_size: Option<f64>,
+    // TODO a lot
+}
+
+/// How the colors of a layer are combined with the colors below it. Layers without a blend mode
+/// replace the colors below them. Blend modes are not part of the MapLibre style specification.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum BlendMode {
+    /// The layer is drawn on top according to its alpha.
+    #[default]
+    #[serde(rename = "normal")]
+    Normal,
+    /// The colors are multiplied, which darkens the colors below.
+    #[serde(rename = "multiply")]
+    Multiply,
+    /// The inverted colors are multiplied, which lightens the colors below.
+    #[serde(rename = "screen")]
+    Screen,
+    /// The colors are added according to the alpha of the layer.
+    #[serde(rename = "add")]
+    Add,
 }
 
 /// Stores all the styles for a specific layer.
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct StyleLayer {
+    /// The position of the layer within [`Style::layers`](crate::style::Style::layers). It is
+    /// assigned while deserializing the style.
     #[serde(skip)]
-    pub index: u32, // FIXME: How is this initialized?
+    pub index: u32,
     pub id: String,
     // TODO filter
-    // TODO layout
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub layout: Option<LayerLayout>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub maxzoom: Option<u8>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -81,6 +184,39 @@ pub struct StyleLayer {
     pub source: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub source_layer: Option<String>,
+    #[serde(rename = "blend-mode")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blend_mode: Option<BlendMode>,
+    /// Whether overlapping features of the layer are blended only once per pixel, such that
+    /// semi-transparent fills of a layer with a [`BlendMode`] do not darken where they overlap.
+    /// This is not part of the MapLibre style specification.
+    #[serde(rename = "single-blend")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub single_blend: Option<bool>,
+    /// The type of the layer if the renderer does not support it, e.g. `heatmap`. Such layers are
+    /// skipped. It is assigned while deserializing the style.
+    #[serde(skip)]
+    pub unsupported_type: Option<String>,
+}
+
+impl StyleLayer {
+    /// Whether the layer is hidden by its `visibility` layout property.
+    pub fn is_hidden(&self) -> bool {
+        self.layout.as_ref().and_then(|layout| layout.visibility) == Some(Visibility::None)
+    }
+
+    pub fn set_visibility(&mut self, visibility: Visibility) {
+        self.layout.get_or_insert_with(LayerLayout::default).visibility = Some(visibility);
+    }
+
+    /// Whether the layer is visible at the `zoom_level`. The `minzoom` is inclusive and the
+    /// `maxzoom` is exclusive. Hidden layers are never visible.
+    pub fn is_visible_at(&self, zoom_level: ZoomLevel) -> bool {
+        let zoom_level = u8::from(zoom_level);
+        !self.is_hidden()
+            && self.minzoom.map_or(true, |minzoom| zoom_level >= minzoom)
+            && self.maxzoom.map_or(true, |maxzoom| zoom_level < maxzoom)
+    }
 }
 
 impl Default for StyleLayer {
@@ -91,9 +227,13 @@ impl Default for StyleLayer {
             maxzoom: None,
             minzoom: None,
             metadata: None,
+            layout: None,
             paint: None,
             source: None,
             source_layer: Some("does not exist".to_string()),
+            blend_mode: None,
+            single_blend: None,
+            unsupported_type: None,
         }
     }
 }
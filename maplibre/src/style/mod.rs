@@ -1,9 +1,13 @@
 //! Vector tile format styling.
 
+pub use builder::*;
 pub use cint::*;
 pub use style::*;
 
+mod builder;
+pub mod glyph;
 pub mod layer;
 pub mod raster;
 pub mod source;
+pub mod sprite;
 mod style;
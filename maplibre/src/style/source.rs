@@ -2,6 +2,12 @@
 
 use serde::{Deserialize, Serialize};
 
+use crate::{
+    coords::{LatLon, ZoomLevel},
+    projection::SourceCrs,
+    style::layer::{LineCap, LineJoin},
+};
+
 /// String url to a tile.
 pub type TileUrl = String;
 
@@ -24,7 +30,7 @@ impl Default for TileAddressingScheme {
 }
 
 /// Source properties for tiles or rasters.
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct VectorSource {
     /// String which contains attribution information for the used tiles.
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -47,6 +53,89 @@ pub struct VectorSource {
     pub tiles: Option<TileUrl>,
     // url: Option<TileJSONUrl>,
     // TODO volatile
+    /// Interval in milliseconds after which tiles in view are requested again. This is useful
+    /// for live data. A value of 0 disables refreshing.
+    #[serde(rename = "refresh-interval")]
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub refresh_interval: Option<u64>,
+    /// The coordinate reference system of the tiles, e.g. `EPSG:4326`. Their geometry is
+    /// reprojected to Web Mercator during tessellation. Defaults to `EPSG:3857`.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub crs: Option<SourceCrs>,
+    /// The maximum amount of adjacent tiles which are fetched with one request. Batches are only
+    /// fetched with one request if the [`HttpClient`](crate::io::source_client::HttpClient)
+    /// supports it, see
+    /// [`HttpClient::fetch_batch_response`](crate::io::source_client::HttpClient::fetch_batch_response).
+    #[serde(rename = "batch-size")]
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub batch_size: Option<u32>,
+    /// How the layers of the source are tessellated, e.g. more coarsely than the layers of other
+    /// sources. Only applies to vector sources.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tessellation: Option<TessellationSettings>,
+    /// The size of the tiles in pixels at a pixel ratio of 1. Larger tiles are requested from
+    /// lower zoom levels. Only applies to raster sources. Defaults to
+    /// [`DEFAULT_TILE_SIZE`](crate::io::source_type::DEFAULT_TILE_SIZE).
+    #[serde(rename = "tileSize")]
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tile_size: Option<u32>,
+}
+
+/// The tessellation of the layers of a vector source. Properties which are not set keep the
+/// defaults of the renderer.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct TessellationSettings {
+    /// The maximum distance in tile units by which lines and polygon rings may deviate from the
+    /// original geometry when they are simplified. Geometries are not simplified if unset.
+    #[serde(rename = "simplification-tolerance")]
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub simplification_tolerance: Option<f64>,
+    /// The size of the grid in tile units to which coordinates are snapped.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub precision: Option<f64>,
+    /// Replaces the `line-cap` of the line layers of the source.
+    #[serde(rename = "line-cap")]
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line_cap: Option<LineCap>,
+    /// Replaces the `line-join` of the line layers of the source.
+    #[serde(rename = "line-join")]
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line_join: Option<LineJoin>,
+}
+
+impl VectorSource {
+    /// Whether tiles of the source can be visible at the `zoom_level` within the geographic
+    /// `bounds`, which are given by their south-west and north-east corners. Tiles are overzoomed
+    /// beyond the `maxzoom`, so only the `minzoom` hides the source.
+    pub fn is_visible(&self, zoom_level: ZoomLevel, bounds: (LatLon, LatLon)) -> bool {
+        if self
+            .minzoom
+            .map_or(false, |minzoom| u8::from(zoom_level) < minzoom)
+        {
+            return false;
+        }
+
+        let Some((west, south, east, north)) = self.bounds else { return true; };
+        let (south_west, north_east) = bounds;
+        if south > north_east.latitude || north < south_west.latitude {
+            return false;
+        }
+
+        // The visible longitudes are not wrapped, so copies of the source bounds in the adjacent
+        // worlds are visible as well
+        [-360.0, 0.0, 360.0].iter().any(|offset| {
+            west + offset <= north_east.longitude && east + offset >= south_west.longitude
+        })
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -57,3 +146,12 @@ pub enum Source {
     #[serde(rename = "raster")]
     Raster(VectorSource), // FIXME: Does it make sense that a raster have a VectorSource?
 }
+
+impl Source {
+    /// The properties of the source, regardless of its type.
+    pub fn properties(&self) -> &VectorSource {
+        match self {
+            Source::Vector(source) | Source::Raster(source) => source,
+        }
+    }
+}
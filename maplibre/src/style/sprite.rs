@@ -0,0 +1,162 @@
+//! Sprite sheets which hold the images which are referenced by styles, e.g. by `fill-pattern`.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum SpriteError {
+    #[error("the sprite index is invalid: {0}")]
+    Index(#[from] serde_json::Error),
+    #[error("the sprite image has {actual} bytes instead of {expected}")]
+    ImageSize { expected: usize, actual: usize },
+    #[error("the icon {0} lies outside of the sprite image")]
+    IconOutOfBounds(String),
+}
+
+/// The position of an icon within the sprite image, as specified by the sprite index.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct SpriteIcon {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    #[serde(rename = "pixelRatio")]
+    #[serde(default = "default_pixel_ratio")]
+    pub pixel_ratio: f32,
+}
+
+fn default_pixel_ratio() -> f32 {
+    1.0
+}
+
+impl SpriteIcon {
+    /// The size of the icon in logical pixels.
+    pub fn logical_size(&self) -> (f32, f32) {
+        (
+            self.width as f32 / self.pixel_ratio,
+            self.height as f32 / self.pixel_ratio,
+        )
+    }
+}
+
+/// Resolves the URLs of the index and the image of the `sprite` of a style. Sprites for high
+/// resolution screens are requested if the `pixel_ratio` is larger than 1.
+pub fn sprite_urls(sprite: &str, pixel_ratio: f64) -> (String, String) {
+    let suffix = if pixel_ratio > 1.0 { "@2x" } else { "" };
+    (
+        format!("{sprite}{suffix}.json"),
+        format!("{sprite}{suffix}.png"),
+    )
+}
+
+/// A sprite image together with the icons which it contains.
+#[derive(Debug, Clone)]
+pub struct SpriteAtlas {
+    width: u32,
+    height: u32,
+    /// The RGBA pixels of the sprite image
+    pixels: Vec<u8>,
+    icons: HashMap<String, SpriteIcon>,
+}
+
+impl SpriteAtlas {
+    /// Creates an atlas from the RGBA `pixels` of the sprite image and its `index`, which is the
+    /// JSON document of the sprite which maps icon names to their position.
+    pub fn new(width: u32, height: u32, pixels: Vec<u8>, index: &str) -> Result<Self, SpriteError> {
+        let icons: HashMap<String, SpriteIcon> = serde_json::from_str(index)?;
+
+        let expected = width as usize * height as usize * 4;
+        if pixels.len() != expected {
+            return Err(SpriteError::ImageSize {
+                expected,
+                actual: pixels.len(),
+            });
+        }
+
+        if let Some((name, _)) = icons.iter().find(|(_, icon)| {
+            icon.x.saturating_add(icon.width) > width || icon.y.saturating_add(icon.height) > height
+        }) {
+            return Err(SpriteError::IconOutOfBounds(name.clone()));
+        }
+
+        Ok(Self {
+            width,
+            height,
+            pixels,
+            icons,
+        })
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    pub fn icon(&self, name: &str) -> Option<&SpriteIcon> {
+        self.icons.get(name)
+    }
+
+    /// The bounds of the icon `name` in texture coordinates, ordered as min x, min y, max x and
+    /// max y.
+    pub fn texture_bounds(&self, name: &str) -> Option<[f32; 4]> {
+        let icon = self.icon(name)?;
+        let (width, height) = (self.width as f32, self.height as f32);
+        Some([
+            icon.x as f32 / width,
+            icon.y as f32 / height,
+            (icon.x + icon.width) as f32 / width,
+            (icon.y + icon.height) as f32 / height,
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{sprite_urls, SpriteAtlas, SpriteError};
+
+    #[test]
+    fn test_sprite_atlas() {
+        let index = r#"{
+            "dots": {"x": 0, "y": 0, "width": 2, "height": 2, "pixelRatio": 2},
+            "stripes": {"x": 2, "y": 0, "width": 2, "height": 4}
+        }"#;
+
+        let atlas = SpriteAtlas::new(4, 4, vec![0; 4 * 4 * 4], index).unwrap();
+
+        assert_eq!(atlas.icon("dots").unwrap().logical_size(), (1.0, 1.0));
+        assert_eq!(atlas.icon("stripes").unwrap().pixel_ratio, 1.0);
+        assert_eq!(atlas.texture_bounds("stripes"), Some([0.5, 0.0, 1.0, 1.0]));
+        assert_eq!(atlas.texture_bounds("missing"), None);
+
+        assert!(matches!(
+            SpriteAtlas::new(2, 2, vec![0; 2 * 2 * 4], index),
+            Err(SpriteError::IconOutOfBounds(name)) if name == "stripes"
+        ));
+        assert!(matches!(
+            SpriteAtlas::new(4, 4, vec![0; 3], index),
+            Err(SpriteError::ImageSize { .. })
+        ));
+    }
+
+    #[test]
+    fn test_sprite_urls() {
+        let sprite = "https://example.com/sprites/bright";
+        assert_eq!(
+            sprite_urls(sprite, 1.0),
+            (format!("{sprite}.json"), format!("{sprite}.png"))
+        );
+        assert_eq!(
+            sprite_urls(sprite, 2.0),
+            (format!("{sprite}@2x.json"), format!("{sprite}@2x.png"))
+        );
+    }
+}
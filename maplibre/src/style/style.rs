@@ -1,14 +1,22 @@
 //! Default vector tile styles configuration.
 
-use std::{collections::HashMap, str::FromStr};
+use std::{
+    collections::{HashMap, HashSet},
+    str::FromStr,
+};
 
 use csscolorparser::Color;
-use serde::{Deserialize, Serialize};
+use serde::{de::Error, Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+use thiserror::Error;
 
-use crate::style::{
-    layer::{FillPaint, LayerPaint, LinePaint, StyleLayer},
-    raster::RasterLayer,
-    source::Source,
+use crate::{
+    style::{
+        layer::{FillPaint, LayerPaint, LinePaint, StyleLayer, Visibility, SUPPORTED_LAYER_TYPES},
+        raster::RasterLayer,
+        source::Source,
+    },
+    view_state::ViewState,
 };
 
 /// Stores the style for a multi-layered map.
@@ -18,10 +26,206 @@ pub struct Style {
     pub name: String,
     pub metadata: HashMap<String, String>,
     pub sources: HashMap<String, Source>,
+    #[serde(deserialize_with = "deserialize_layers")]
     pub layers: Vec<StyleLayer>,
     pub center: Option<[f64; 2]>, // TODO: Use LatLon type here
     pub zoom: Option<f64>,
     pub pitch: Option<f64>,
+    /// The URL of the sprite without the file extension, see
+    /// [`sprite_urls`](crate::style::sprite::sprite_urls).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sprite: Option<String>,
+    /// The URL template of the glyph ranges, see
+    /// [`glyphs_url`](crate::style::glyph::glyphs_url).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub glyphs: Option<String>,
+}
+
+/// Deserializes the layers and assigns each layer its index within the style. Layers with a higher
+/// index are drawn on top of layers with a lower index.
+///
+/// Layers with a type which is not supported are kept, but marked as unsupported. A single warning
+/// is logged for each of them.
+fn deserialize_layers<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<StyleLayer>, D::Error> {
+    let mut values = Vec::<Value>::deserialize(deserializer)?;
+    resolve_refs(&mut values).map_err(D::Error::custom)?;
+
+    let mut layers = Vec::with_capacity(values.len());
+    for (index, value) in values.into_iter().enumerate() {
+        let layer_type = value
+            .get("type")
+            .and_then(|layer_type| layer_type.as_str())
+            .map(|layer_type| layer_type.to_string());
+
+        let mut layer = StyleLayer::deserialize(value).map_err(D::Error::custom)?;
+        layer.index = index as u32;
+
+        if let Some(layer_type) =
+            layer_type.filter(|layer_type| !SUPPORTED_LAYER_TYPES.contains(&layer_type.as_str()))
+        {
+            log::warn!(
+                "layer {} is skipped, because layers of type {layer_type} are not supported",
+                layer.id
+            );
+            layer.unsupported_type = Some(layer_type);
+        }
+
+        layers.push(layer);
+    }
+    Ok(layers)
+}
+
+/// The properties which a layer inherits from the layer referenced by its deprecated `ref`
+/// property.
+const REF_PROPERTIES: [&str; 7] = [
+    "type",
+    "source",
+    "source-layer",
+    "minzoom",
+    "maxzoom",
+    "filter",
+    "layout",
+];
+
+#[derive(Error, Debug)]
+pub enum LayerRefError {
+    #[error("layer {layer} references the unknown layer {reference}")]
+    UnknownLayer { layer: String, reference: String },
+    #[error("the references of layer {0} form a cycle")]
+    Cycle(String),
+}
+
+/// Resolves the deprecated `ref` property of legacy styles by copying the [`REF_PROPERTIES`] of
+/// the referenced layers.
+fn resolve_refs(layers: &mut [Value]) -> Result<(), LayerRefError> {
+    let ids: HashMap<String, usize> = layers
+        .iter()
+        .enumerate()
+        .filter_map(|(index, layer)| Some((layer.get("id")?.as_str()?.to_string(), index)))
+        .collect();
+
+    for index in 0..layers.len() {
+        resolve_ref(layers, &ids, index, &mut Vec::new())?;
+    }
+    Ok(())
+}
+
+fn resolve_ref(
+    layers: &mut [Value],
+    ids: &HashMap<String, usize>,
+    index: usize,
+    visiting: &mut Vec<usize>,
+) -> Result<(), LayerRefError> {
+    let Some(reference) = layers[index].get("ref").and_then(Value::as_str) else { return Ok(()); };
+    let reference = reference.to_string();
+
+    let layer_id = || {
+        layers[index]
+            .get("id")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string()
+    };
+
+    if visiting.contains(&index) {
+        return Err(LayerRefError::Cycle(layer_id()));
+    }
+    visiting.push(index);
+
+    let Some(&referenced) = ids.get(&reference) else {
+        return Err(LayerRefError::UnknownLayer {
+            layer: layer_id(),
+            reference,
+        });
+    };
+    resolve_ref(layers, ids, referenced, visiting)?;
+
+    let inherited: Vec<(String, Value)> = REF_PROPERTIES
+        .iter()
+        .filter_map(|property| {
+            let value = layers[referenced].get(property)?;
+            Some((property.to_string(), value.clone()))
+        })
+        .collect();
+
+    if let Some(layer) = layers[index].as_object_mut() {
+        layer.remove("ref");
+        layer.extend(inherited);
+    }
+    Ok(())
+}
+
+impl Style {
+    /// The ids of the layers which are skipped, because the renderer does not support their type.
+    pub fn unsupported_layers(&self) -> Vec<&str> {
+        self.layers
+            .iter()
+            .filter(|layer| layer.unsupported_type.is_some())
+            .map(|layer| layer.id.as_str())
+            .collect()
+    }
+
+    /// Shows or hides the layer with the `id`. Tiles are requested again once a hidden layer is
+    /// shown, such that its data is loaded. Returns whether the style has a layer with the `id`.
+    pub fn set_layer_visibility(&mut self, id: &str, visibility: Visibility) -> bool {
+        self.layers
+            .iter_mut()
+            .find(|layer| layer.id == id)
+            .map(|layer| layer.set_visibility(visibility))
+            .is_some()
+    }
+
+    /// The ids of the layers which are hidden by their `visibility` layout property.
+    pub fn hidden_layers(&self) -> HashSet<String> {
+        self.layers
+            .iter()
+            .filter(|layer| layer.is_hidden())
+            .map(|layer| layer.id.clone())
+            .collect()
+    }
+
+    /// The attributions of the sources which are visible in the `view_state`, which apps need to
+    /// display. A source is visible if a layer which is visible at the current zoom level draws
+    /// it and its bounds and `minzoom` admit the view. Layers without a source draw all sources.
+    ///
+    /// The attributions are deduplicated and ordered by the first layer which draws them.
+    pub fn attributions(&self, view_state: &ViewState) -> Vec<String> {
+        let Some(bounds) = view_state.visible_bounds() else { return Vec::new(); };
+        let zoom_level = view_state.visible_level();
+
+        let mut source_names: Vec<&String> = Vec::new();
+        for layer in self
+            .layers
+            .iter()
+            .filter(|layer| layer.is_visible_at(zoom_level))
+        {
+            match &layer.source {
+                Some(source) => source_names.push(source),
+                None => {
+                    // Sources are sorted, as the order of the map is not stable
+                    let mut all_sources = self.sources.keys().collect::<Vec<_>>();
+                    all_sources.sort();
+                    source_names.extend(all_sources);
+                }
+            }
+        }
+
+        let mut attributions: Vec<String> = Vec::new();
+        for attribution in source_names
+            .into_iter()
+            .filter_map(|name| self.sources.get(name))
+            .map(Source::properties)
+            .filter(|source| source.is_visible(zoom_level, bounds))
+            .filter_map(|source| source.attribution.as_ref())
+        {
+            if !attributions.contains(attribution) {
+                attributions.push(attribution.clone());
+            }
+        }
+        attributions
+    }
 }
 
 impl Default for Style {
@@ -34,6 +238,8 @@ impl Default for Style {
             center: Some([46.5197, 6.6323]),
             pitch: Some(0.0),
             zoom: Some(13.0),
+            sprite: None,
+            glyphs: None,
             layers: vec![
                 StyleLayer {
                     index: 0,
@@ -41,11 +247,16 @@ impl Default for Style {
                     maxzoom: None,
                     minzoom: None,
                     metadata: None,
+                    layout: None,
                     paint: Some(LayerPaint::Fill(FillPaint {
                         fill_color: Some(Color::from_str("#c8facc").unwrap()),
+                        fill_pattern: None,
                     })),
                     source: None,
                     source_layer: Some("park".to_string()),
+                    blend_mode: None,
+                    single_blend: None,
+                    unsupported_type: None,
                 },
                 StyleLayer {
                     index: 1,
@@ -53,11 +264,16 @@ impl Default for Style {
                     maxzoom: None,
                     minzoom: None,
                     metadata: None,
+                    layout: None,
                     paint: Some(LayerPaint::Fill(FillPaint {
                         fill_color: Some(Color::from_str("#e0dfdf").unwrap()),
+                        fill_pattern: None,
                     })),
                     source: None,
                     source_layer: Some("landuse".to_string()),
+                    blend_mode: None,
+                    single_blend: None,
+                    unsupported_type: None,
                 },
                 StyleLayer {
                     index: 2,
@@ -65,11 +281,16 @@ impl Default for Style {
                     maxzoom: None,
                     minzoom: None,
                     metadata: None,
+                    layout: None,
                     paint: Some(LayerPaint::Fill(FillPaint {
                         fill_color: Some(Color::from_str("#aedfa3").unwrap()),
+                        fill_pattern: None,
                     })),
                     source: None,
                     source_layer: Some("landcover".to_string()),
+                    blend_mode: None,
+                    single_blend: None,
+                    unsupported_type: None,
                 },
                 StyleLayer {
                     index: 3,
@@ -77,11 +298,15 @@ impl Default for Style {
                     maxzoom: None,
                     minzoom: None,
                     metadata: None,
+                    layout: None,
                     paint: Some(LayerPaint::Line(LinePaint {
                         line_color: Some(Color::from_str("#ffffff").unwrap()),
                     })),
                     source: None,
                     source_layer: Some("transportation".to_string()),
+                    blend_mode: None,
+                    single_blend: None,
+                    unsupported_type: None,
                 },
                 StyleLayer {
                     index: 4,
@@ -89,23 +314,33 @@ impl Default for Style {
                     maxzoom: None,
                     minzoom: None,
                     metadata: None,
+                    layout: None,
                     paint: Some(LayerPaint::Fill(FillPaint {
                         fill_color: Some(Color::from_str("#d9d0c9").unwrap()),
+                        fill_pattern: None,
                     })),
                     source: None,
                     source_layer: Some("building".to_string()),
+                    blend_mode: None,
+                    single_blend: None,
+                    unsupported_type: None,
                 },
                 StyleLayer {
-                    index: 4,
+                    index: 5,
                     id: "water".to_string(),
                     maxzoom: None,
                     minzoom: None,
                     metadata: None,
+                    layout: None,
                     paint: Some(LayerPaint::Fill(FillPaint {
                         fill_color: Some(Color::from_str("#aad3df").unwrap()),
+                        fill_pattern: None,
                     })),
                     source: None,
                     source_layer: Some("water".to_string()),
+                    blend_mode: None,
+                    single_blend: None,
+                    unsupported_type: None,
                 },
                 StyleLayer {
                     index: 6,
@@ -113,11 +348,16 @@ impl Default for Style {
                     maxzoom: None,
                     minzoom: None,
                     metadata: None,
+                    layout: None,
                     paint: Some(LayerPaint::Fill(FillPaint {
                         fill_color: Some(Color::from_str("#aad3df").unwrap()),
+                        fill_pattern: None,
                     })),
                     source: None,
                     source_layer: Some("waterway".to_string()),
+                    blend_mode: None,
+                    single_blend: None,
+                    unsupported_type: None,
                 },
                 StyleLayer {
                     index: 7,
@@ -125,11 +365,15 @@ impl Default for Style {
                     maxzoom: None,
                     minzoom: None,
                     metadata: None,
+                    layout: None,
                     paint: Some(LayerPaint::Line(LinePaint {
                         line_color: Some(Color::from_str("black").unwrap()),
                     })),
                     source: None,
                     source_layer: Some("boundary".to_string()),
+                    blend_mode: None,
+                    single_blend: None,
+                    unsupported_type: None,
                 },
                 StyleLayer {
                     index: 8,
@@ -137,9 +381,13 @@ impl Default for Style {
                     maxzoom: None,
                     minzoom: None,
                     metadata: None,
+                    layout: None,
                     paint: Some(LayerPaint::Raster(RasterLayer::default())),
                     source: None,
                     source_layer: Some("raster".to_string()),
+                    blend_mode: None,
+                    single_blend: None,
+                    unsupported_type: None,
                 },
             ],
         }
@@ -203,6 +451,170 @@ mod tests {
         }
         "##;
 
-        let _style: Style = serde_json::from_str(style_json_str).unwrap();
+        let style: Style = serde_json::from_str(style_json_str).unwrap();
+
+        assert_eq!(
+            style
+                .layers
+                .iter()
+                .map(|layer| (layer.id.as_str(), layer.index))
+                .collect::<Vec<_>>(),
+            vec![
+                ("background", 0),
+                ("transportation", 1),
+                ("boundary", 2),
+                ("building", 3)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unsupported_layers() {
+        let style: Style = serde_json::from_value(serde_json::json!({
+            "version": 8,
+            "name": "Test Style",
+            "metadata": {},
+            "sources": {},
+            "layers": [
+                {
+                    "id": "water",
+                    "type": "fill",
+                    "source-layer": "water",
+                    "paint": {"fill-color": "#aad3df"}
+                },
+                {
+                    "id": "poi-heat",
+                    "type": "heatmap",
+                    "source-layer": "poi",
+                    "paint": {"heatmap-radius": 10}
+                },
+                {
+                    "id": "sky",
+                    "type": "sky"
+                }
+            ]
+        }))
+        .unwrap();
+
+        assert_eq!(style.unsupported_layers(), vec!["poi-heat", "sky"]);
+        assert_eq!(style.layers[1].unsupported_type.as_deref(), Some("heatmap"));
+        assert_eq!(style.layers[2].index, 2);
+        assert!(style.layers[0].unsupported_type.is_none());
+    }
+
+    #[test]
+    fn test_ref_layers() {
+        let layers = |layers: serde_json::Value| {
+            serde_json::from_value::<Style>(serde_json::json!({
+                "version": 8,
+                "name": "Legacy Style",
+                "metadata": {},
+                "sources": {},
+                "layers": layers
+            }))
+        };
+
+        let style = layers(serde_json::json!([
+            {
+                "id": "road",
+                "type": "line",
+                "source": "openmaptiles",
+                "source-layer": "transportation",
+                "minzoom": 5,
+                "paint": {"line-color": "#ffffff"}
+            },
+            {
+                "id": "road-casing",
+                "ref": "road",
+                "paint": {"line-color": "#cccccc"}
+            }
+        ]))
+        .unwrap();
+
+        let casing = &style.layers[1];
+        assert_eq!(casing.source_layer.as_deref(), Some("transportation"));
+        assert_eq!(casing.source.as_deref(), Some("openmaptiles"));
+        assert_eq!(casing.minzoom, Some(5));
+        let Some(LayerPaint::Line(paint)) = &casing.paint else { panic!("expected a line layer") };
+        assert_eq!(paint.line_color, Some(Color::from_str("#cccccc").unwrap()));
+
+        let cycle = layers(serde_json::json!([
+            {"id": "a", "ref": "b"},
+            {"id": "b", "ref": "a"}
+        ]));
+        assert!(cycle.unwrap_err().to_string().contains("cycle"));
+
+        let unknown = layers(serde_json::json!([{"id": "a", "ref": "missing"}]));
+        assert!(unknown.unwrap_err().to_string().contains("unknown layer"));
+    }
+
+    #[test]
+    fn test_attributions() {
+        use cgmath::Deg;
+
+        use crate::{
+            coords::{LatLon, WorldCoords, Zoom},
+            window::WindowSize,
+        };
+
+        let layer = |id: &str, source: &str| {
+            serde_json::json!({
+                "id": id,
+                "type": "fill",
+                "source": source,
+                "source-layer": id,
+                "paint": {"fill-color": "#000000"}
+            })
+        };
+        let style: Style = serde_json::from_value(serde_json::json!({
+            "version": 8,
+            "name": "Test Style",
+            "metadata": {},
+            "sources": {
+                "imagery": {
+                    "type": "raster",
+                    "attribution": "© Imagery",
+                    "bounds": [10.0, 40.0, 20.0, 50.0]
+                },
+                "streets": {"type": "vector", "attribution": "© OpenStreetMap"},
+                "labels": {"type": "vector", "attribution": "© OpenStreetMap"},
+                "buildings": {"type": "vector", "attribution": "© Buildings", "minzoom": 14},
+                "unattributed": {"type": "vector"}
+            },
+            "layers": [
+                layer("satellite", "imagery"),
+                layer("water", "streets"),
+                layer("places", "labels"),
+                layer("buildings", "buildings"),
+                layer("debug", "unattributed")
+            ]
+        }))
+        .unwrap();
+
+        let view_state = |lat_lon: LatLon, zoom: f64| {
+            let zoom = Zoom::new(zoom);
+            ViewState::new(
+                WindowSize::new(800, 600).unwrap(),
+                WorldCoords::from_lat_lon(lat_lon, zoom),
+                zoom,
+                Deg(0.0),
+                Deg(110.0),
+            )
+        };
+
+        // The imagery covers the view and the buildings are not visible yet
+        assert_eq!(
+            style.attributions(&view_state(LatLon::new(45.0, 15.0), 5.0)),
+            vec!["© Imagery", "© OpenStreetMap"]
+        );
+        assert_eq!(
+            style.attributions(&view_state(LatLon::new(45.0, 15.0), 15.0)),
+            vec!["© Imagery", "© OpenStreetMap", "© Buildings"]
+        );
+        // The imagery is out of view
+        assert_eq!(
+            style.attributions(&view_state(LatLon::new(0.0, -100.0), 5.0)),
+            vec!["© OpenStreetMap"]
+        );
     }
 }
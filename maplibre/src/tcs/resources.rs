@@ -31,7 +31,13 @@ impl Resources {
         }
     }
 
+    /// Inserts the `resource`. A previous resource of the same type is dropped.
     pub fn insert<R: Resource>(&mut self, resource: R) {
+        if let Some(index) = self.index.get(&TypeId::of::<R>()) {
+            self.resources[*index] = UnsafeCell::new(Box::new(resource));
+            return;
+        }
+
         let index = self.resources.len();
         self.resources.push(UnsafeCell::new(Box::new(resource)));
         self.index.insert(TypeId::of::<R>(), index);
@@ -9,7 +9,7 @@ use downcast_rs::{impl_downcast, Downcast};
 
 use crate::{
     coords::{Quadkey, WorldTileCoords},
-    io::geometry_index::GeometryIndex,
+    io::geometry_index::{GeometryIndex, IndexedGeometry},
 };
 
 #[derive(Copy, Clone, Debug)]
@@ -17,16 +17,85 @@ pub struct Tile {
     pub coords: WorldTileCoords,
 }
 
+/// The loading state of a tile.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum TileState {
+    /// The tile has been requested, but not all of its data has arrived yet.
+    Loading,
+    /// All requested data of the tile is available.
+    Loaded,
+    /// Some of the requested data of the tile is available and some is missing.
+    Partial,
+    /// None of the requested data of the tile is available.
+    Unavailable,
+}
+
+impl TileState {
+    /// Combines the states of two components of the same tile.
+    fn combine(self, other: TileState) -> TileState {
+        match (self, other) {
+            (TileState::Loading, _) | (_, TileState::Loading) => TileState::Loading,
+            (TileState::Loaded, TileState::Loaded) => TileState::Loaded,
+            (TileState::Unavailable, TileState::Unavailable) => TileState::Unavailable,
+            _ => TileState::Partial,
+        }
+    }
+}
+
 /// A component is data associated with an [`Entity`](crate::tcs::entity::Entity). Each entity can have
 /// multiple different types of components, but only one of them per type.
-pub trait TileComponent: Downcast + 'static {}
+pub trait TileComponent: Downcast + 'static {
+    /// The loading state of this component. Components which do not load any data return `None`.
+    fn tile_state(&self) -> Option<TileState> {
+        None
+    }
+
+    /// Whether some of the data of this component is unavailable because loading it failed, as
+    /// opposed to the data not existing.
+    fn has_errors(&self) -> bool {
+        false
+    }
+
+    /// The approximate amount of bytes which the data of this component occupies in host and GPU
+    /// memory.
+    fn byte_size(&self) -> usize {
+        0
+    }
+
+    /// Called before the tile of this component is evicted or cleared, e.g. to return buffers to
+    /// a pool.
+    fn release(&mut self) {}
+}
 impl_downcast!(TileComponent);
 
+/// Which tiles related to a used tile are retained together with it. Related tiles are marked as
+/// used whenever the tile is used, such that they are not evicted before the tile. This keeps
+/// geometry around which can be displayed while zooming.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct RetentionPolicy {
+    /// The amount of zoom levels above the tile whose tiles are retained
+    pub keep_parents: u8,
+    /// Whether the stored children of the tile are retained
+    pub keep_children: bool,
+}
+
 #[derive(Default)]
 pub struct Tiles {
     pub tiles: BTreeMap<Quadkey, Tile>,
     pub components: BTreeMap<Quadkey, Vec<UnsafeCell<Box<dyn TileComponent>>>>,
     pub geometry_index: GeometryIndex,
+    /// The maximum amount of bytes which the components of all tiles may occupy
+    memory_budget: Option<usize>,
+    retention_policy: RetentionPolicy,
+    /// Tiles which are never evicted by [`Tiles::evict_to_budget`], see [`Tiles::pin`]
+    pinned: BTreeMap<Quadkey, WorldTileCoords>,
+    /// Holds for each tile the value of `use_counter` when it has been used the last time
+    last_used: BTreeMap<Quadkey, u64>,
+    use_counter: u64,
+    /// Coordinates of the evicted tiles whose GPU resources have not been released yet
+    evicted: Vec<WorldTileCoords>,
+    /// Incremented whenever tiles are removed, see [`Tiles::removal_count`]
+    removal_count: u64,
 }
 
 impl Tiles {
@@ -53,6 +122,46 @@ impl Tiles {
         }
     }
 
+    /// Coordinates of all tiles which are stored, regardless of their [`TileState`].
+    pub fn loaded_coords(&self) -> Vec<WorldTileCoords> {
+        self.tiles.values().map(|tile| tile.coords).collect()
+    }
+
+    /// The combined [`TileState`] of all components of the tile at `coords`. Returns `None` if the
+    /// tile does not exist.
+    pub fn tile_state(&self, coords: &WorldTileCoords) -> Option<TileState> {
+        let components = self.components.get(&coords.build_quad_key()?)?;
+
+        Some(
+            components
+                .iter()
+                // SAFETY: Tiles is borrowed immutably, so no component is borrowed mutably.
+                .filter_map(|component| unsafe { component.get().as_ref().unwrap().tile_state() })
+                .reduce(TileState::combine)
+                .unwrap_or(TileState::Loading),
+        )
+    }
+
+    /// Whether data of any stored tile, in view or not, is still expected to arrive.
+    pub fn has_loading_tiles(&self) -> bool {
+        self.tiles
+            .values()
+            .any(|tile| self.tile_state(&tile.coords) == Some(TileState::Loading))
+    }
+
+    /// Whether loading some of the data of the tile at `coords` failed. See
+    /// [`TileComponent::has_errors`].
+    pub fn has_errors(&self, coords: &WorldTileCoords) -> bool {
+        let Some(components) = coords
+            .build_quad_key()
+            .and_then(|key| self.components.get(&key)) else { return false; };
+
+        components
+            .iter()
+            // SAFETY: Tiles is borrowed immutably, so no component is borrowed mutably.
+            .any(|component| unsafe { component.get().as_ref().unwrap().has_errors() })
+    }
+
     pub fn spawn_mut(&mut self, coords: WorldTileCoords) -> Option<TileSpawnResult> {
         if let Some(key) = coords.build_quad_key() {
             if let Some(tile) = self.tiles.get(&key) {
@@ -62,6 +171,7 @@ impl Tiles {
                 let tile = Tile { coords };
                 self.tiles.insert(key, tile);
                 self.components.insert(key, Vec::new());
+                self.mark_used(coords);
                 Some(TileSpawnResult { tiles: self, tile })
             }
         } else {
@@ -69,9 +179,210 @@ impl Tiles {
         }
     }
 
+    /// The decoded features of the loaded vector tile at `coords`, e.g. to export them. See
+    /// [`GeometryIndex::features`].
+    pub fn features(
+        &self,
+        coords: &WorldTileCoords,
+    ) -> impl Iterator<Item = &IndexedGeometry<f64>> + '_ {
+        self.geometry_index.features(coords)
+    }
+
     pub fn clear(&mut self) {
         self.tiles.clear();
-        self.components.clear();
+        for components in std::mem::take(&mut self.components).into_values() {
+            release_components(components);
+        }
+        self.last_used.clear();
+        self.removal_count += 1;
+    }
+
+    /// Evicts the tile at `coords` together with its components and indexed geometries. The tile
+    /// is requested again once it is in view. Returns whether the tile existed.
+    ///
+    /// The GPU resources of the tile are released once the current frame has been rendered, see
+    /// [`Tiles::take_evicted`].
+    pub fn evict(&mut self, coords: &WorldTileCoords) -> bool {
+        coords
+            .build_quad_key()
+            .map_or(false, |key| self.evict_key(&key).is_some())
+    }
+
+    /// Evicts all tiles, see [`Tiles::evict`]. Returns the coordinates of the evicted tiles.
+    pub fn evict_all(&mut self) -> Vec<WorldTileCoords> {
+        let keys: Vec<Quadkey> = self.tiles.keys().copied().collect();
+        keys.iter().filter_map(|key| self.evict_key(key)).collect()
+    }
+
+    fn evict_key(&mut self, key: &Quadkey) -> Option<WorldTileCoords> {
+        let tile = self.tiles.remove(key)?;
+        if let Some(components) = self.components.remove(key) {
+            release_components(components);
+        }
+        self.last_used.remove(key);
+        self.geometry_index.remove_tile(&tile.coords);
+        self.evicted.push(tile.coords);
+        self.removal_count += 1;
+        Some(tile.coords)
+    }
+
+    /// Changes whenever tiles are cleared or evicted. Systems which only look at tiles they have
+    /// not seen before compare this count to detect that tiles they have seen are gone.
+    pub fn removal_count(&self) -> u64 {
+        self.removal_count
+    }
+
+    /// Returns the coordinates of the tiles which have been evicted since the last call, such that
+    /// their GPU resources can be released. This happens once a frame has been rendered, so
+    /// resources are never released while a frame uses them.
+    pub(crate) fn take_evicted(&mut self) -> Vec<WorldTileCoords> {
+        std::mem::take(&mut self.evicted)
+    }
+
+    /// Limits the approximate memory which the data of all tiles occupies to `bytes`. Tiles over
+    /// the budget are evicted by [`Tiles::evict_to_budget`].
+    pub fn set_memory_budget(&mut self, bytes: Option<usize>) {
+        self.memory_budget = bytes;
+    }
+
+    pub fn memory_budget(&self) -> Option<usize> {
+        self.memory_budget
+    }
+
+    /// The approximate amount of bytes which the components of all tiles occupy.
+    pub fn memory_usage(&self) -> usize {
+        self.components
+            .keys()
+            .map(|key| self.tile_memory_usage(key))
+            .sum()
+    }
+
+    fn tile_memory_usage(&self, key: &Quadkey) -> usize {
+        self.components.get(key).map_or(0, |components| {
+            components
+                .iter()
+                // SAFETY: Tiles is borrowed immutably, so no component is borrowed mutably.
+                .map(|component| unsafe { component.get().as_ref().unwrap().byte_size() })
+                .sum()
+        })
+    }
+
+    /// Sets which tiles are retained together with a used tile, see [`RetentionPolicy`].
+    pub fn set_retention_policy(&mut self, policy: RetentionPolicy) {
+        self.retention_policy = policy;
+    }
+
+    pub fn retention_policy(&self) -> RetentionPolicy {
+        self.retention_policy
+    }
+
+    /// Marks the tile at `coords` as used. Tiles which have been used less recently are evicted
+    /// first. The related tiles of the [`RetentionPolicy`] are marked as used as well.
+    pub fn mark_used(&mut self, coords: WorldTileCoords) {
+        let Some(key) = coords.build_quad_key() else { return; };
+        if !self.tiles.contains_key(&key) {
+            return;
+        }
+
+        self.use_counter += 1;
+        self.last_used.insert(key, self.use_counter);
+
+        let mut parent = coords;
+        for _ in 0..self.retention_policy.keep_parents {
+            let Some(next) = parent.get_parent() else { break; };
+            parent = next;
+            self.retain(parent);
+        }
+
+        if self.retention_policy.keep_children {
+            for child in coords.get_children() {
+                self.retain(child);
+            }
+        }
+    }
+
+    /// Whether the tile at `coords` has been marked as used since it has been stored, see
+    /// [`Tiles::mark_used`].
+    pub fn is_used(&self, coords: &WorldTileCoords) -> bool {
+        coords
+            .build_quad_key()
+            .map_or(false, |key| self.last_used.contains_key(&key))
+    }
+
+    /// Marks the tile at `coords` as used at the same time as the last used tile, if it exists.
+    fn retain(&mut self, coords: WorldTileCoords) {
+        let Some(key) = coords.build_quad_key() else { return; };
+        if self.tiles.contains_key(&key) {
+            self.last_used.insert(key, self.use_counter);
+        }
+    }
+
+    /// Pins the tile at `coords`, such that it is never evicted by [`Tiles::evict_to_budget`] and
+    /// its memory does not count towards the budget. Pinned tiles are requested eagerly, even if
+    /// they are not in view. Returns whether the tile has not been pinned before.
+    pub fn pin(&mut self, coords: &WorldTileCoords) -> bool {
+        coords
+            .build_quad_key()
+            .map_or(false, |key| self.pinned.insert(key, *coords).is_none())
+    }
+
+    /// Unpins the tile at `coords`, such that it is evicted like any other tile. Returns whether
+    /// the tile has been pinned.
+    pub fn unpin(&mut self, coords: &WorldTileCoords) -> bool {
+        coords
+            .build_quad_key()
+            .map_or(false, |key| self.pinned.remove(&key).is_some())
+    }
+
+    pub fn is_pinned(&self, coords: &WorldTileCoords) -> bool {
+        coords
+            .build_quad_key()
+            .map_or(false, |key| self.pinned.contains_key(&key))
+    }
+
+    /// Coordinates of all pinned tiles, whether they are stored or not.
+    pub fn pinned_coords(&self) -> impl Iterator<Item = &WorldTileCoords> + '_ {
+        self.pinned.values()
+    }
+
+    /// Evicts the least recently used tiles until the memory usage is within the budget. Pinned
+    /// tiles are neither evicted nor counted. Returns the coordinates of the evicted tiles.
+    pub fn evict_to_budget(&mut self) -> Vec<WorldTileCoords> {
+        let Some(budget) = self.memory_budget else { return Vec::new(); };
+
+        let mut usages: Vec<(u64, Quadkey, usize)> = self
+            .tiles
+            .keys()
+            .filter(|key| !self.pinned.contains_key(key))
+            .map(|key| {
+                let last_used = self.last_used.get(key).copied().unwrap_or_default();
+                (last_used, *key, self.tile_memory_usage(key))
+            })
+            .collect();
+        let mut usage: usize = usages.iter().map(|(_, _, bytes)| bytes).sum();
+
+        usages.sort_unstable_by_key(|(last_used, _, _)| *last_used);
+
+        let mut evicted = Vec::new();
+        for (_, key, bytes) in usages {
+            if usage <= budget {
+                break;
+            }
+
+            if let Some(coords) = self.evict_key(&key) {
+                evicted.push(coords);
+            }
+            usage -= bytes;
+        }
+
+        if !evicted.is_empty() {
+            log::debug!(
+                "evicted {} tiles to stay within the memory budget of {budget} bytes",
+                evicted.len()
+            );
+        }
+
+        evicted
     }
 }
 
@@ -324,3 +635,254 @@ impl<
         }
     }
 }
+
+/// Releases the `components` of a tile which is removed, see [`TileComponent::release`].
+fn release_components(components: Vec<UnsafeCell<Box<dyn TileComponent>>>) {
+    for component in components {
+        component.into_inner().release();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, rc::Rc};
+
+    use crate::{
+        coords::{WorldTileCoords, ZoomLevel},
+        tcs::tiles::{RetentionPolicy, TileComponent, TileState, Tiles},
+    };
+
+    struct StateComponent(TileState);
+
+    impl TileComponent for StateComponent {
+        fn tile_state(&self) -> Option<TileState> {
+            Some(self.0)
+        }
+    }
+
+    struct OtherStateComponent(TileState);
+
+    impl TileComponent for OtherStateComponent {
+        fn tile_state(&self) -> Option<TileState> {
+            Some(self.0)
+        }
+    }
+
+    /// Counts how often the components of the tiles have been released
+    struct ReleasedComponent(Rc<Cell<usize>>);
+
+    impl TileComponent for ReleasedComponent {
+        fn release(&mut self) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    #[test]
+    fn test_loaded_coords_and_states() {
+        let mut tiles = Tiles::default();
+
+        let loaded = WorldTileCoords::from((0, 0, ZoomLevel::new(1)));
+        let partial = WorldTileCoords::from((1, 0, ZoomLevel::new(1)));
+        let loading = WorldTileCoords::from((0, 1, ZoomLevel::new(1)));
+        let absent = WorldTileCoords::from((1, 1, ZoomLevel::new(1)));
+
+        tiles
+            .spawn_mut(loaded)
+            .unwrap()
+            .insert(StateComponent(TileState::Loaded))
+            .insert(OtherStateComponent(TileState::Loaded));
+        tiles
+            .spawn_mut(partial)
+            .unwrap()
+            .insert(StateComponent(TileState::Loaded))
+            .insert(OtherStateComponent(TileState::Unavailable));
+        tiles.spawn_mut(loading).unwrap();
+
+        let mut coords = tiles.loaded_coords();
+        coords.sort_by_key(|coords| (coords.x, coords.y));
+        assert_eq!(coords, vec![loaded, loading, partial]);
+
+        assert_eq!(tiles.tile_state(&loaded), Some(TileState::Loaded));
+        assert_eq!(tiles.tile_state(&partial), Some(TileState::Partial));
+        assert_eq!(tiles.tile_state(&loading), Some(TileState::Loading));
+        assert_eq!(tiles.tile_state(&absent), None);
+        assert!(tiles.has_loading_tiles());
+
+        tiles.query_mut::<&mut StateComponent>(partial).unwrap().0 = TileState::Loading;
+        tiles.evict(&loading);
+        assert!(tiles.has_loading_tiles());
+
+        tiles.evict(&partial);
+        assert!(!tiles.has_loading_tiles());
+    }
+
+    /// Occupies a known amount of memory
+    struct SizedComponent(usize);
+
+    impl TileComponent for SizedComponent {
+        fn byte_size(&self) -> usize {
+            self.0
+        }
+    }
+
+    #[test]
+    fn test_memory_budget() {
+        let mut tiles = Tiles::default();
+        tiles.set_memory_budget(Some(1000));
+
+        let coords: Vec<_> = (0..4)
+            .map(|x| WorldTileCoords::from((x, 0, ZoomLevel::new(2))))
+            .collect();
+
+        for (coords, bytes) in coords.iter().zip([400, 300, 200, 500]) {
+            tiles
+                .spawn_mut(*coords)
+                .unwrap()
+                .insert(SizedComponent(bytes));
+        }
+        assert_eq!(tiles.memory_usage(), 1400);
+
+        // The first tile is used again, so the second tile is the least recently used
+        tiles.mark_used(coords[0]);
+
+        assert_eq!(tiles.evict_to_budget(), vec![coords[1], coords[2]]);
+        assert_eq!(tiles.memory_usage(), 900);
+        assert!(tiles.exists(coords[0]));
+        assert!(!tiles.exists(coords[1]));
+
+        // Without a budget nothing is evicted
+        tiles.set_memory_budget(None);
+        tiles
+            .spawn_mut(coords[1])
+            .unwrap()
+            .insert(SizedComponent(800));
+        assert!(tiles.evict_to_budget().is_empty());
+        assert_eq!(tiles.memory_usage(), 1700);
+    }
+
+    #[test]
+    fn test_retention_policy() {
+        let mut tiles = Tiles::default();
+        tiles.set_memory_budget(Some(1000));
+        tiles.set_retention_policy(RetentionPolicy {
+            keep_parents: 1,
+            keep_children: false,
+        });
+
+        let parent = WorldTileCoords::from((0, 0, ZoomLevel::new(1)));
+        let other = WorldTileCoords::from((1, 0, ZoomLevel::new(1)));
+        let child = WorldTileCoords::from((0, 0, ZoomLevel::new(2)));
+
+        for coords in [parent, other, child] {
+            tiles
+                .spawn_mut(coords)
+                .unwrap()
+                .insert(SizedComponent(400));
+        }
+
+        // The parent has been loaded before the other tile, but is retained with its child
+        assert_eq!(tiles.evict_to_budget(), vec![other]);
+        assert!(tiles.exists(parent));
+        assert!(tiles.exists(child));
+
+        // Without a policy the parent is the least recently used tile
+        tiles.set_retention_policy(RetentionPolicy::default());
+        tiles
+            .spawn_mut(other)
+            .unwrap()
+            .insert(SizedComponent(400));
+        tiles.mark_used(child);
+        assert_eq!(tiles.evict_to_budget(), vec![parent]);
+
+        // Children are retained together with their parent
+        tiles.set_retention_policy(RetentionPolicy {
+            keep_parents: 0,
+            keep_children: true,
+        });
+        tiles
+            .spawn_mut(parent)
+            .unwrap()
+            .insert(SizedComponent(400));
+        assert_eq!(tiles.evict_to_budget(), vec![other]);
+        assert!(tiles.exists(child));
+    }
+
+    #[test]
+    fn test_pinned_tiles() {
+        let mut tiles = Tiles::default();
+        tiles.set_memory_budget(Some(1000));
+
+        let home = WorldTileCoords::from((0, 0, ZoomLevel::new(3)));
+        assert!(tiles.pin(&home));
+        assert!(!tiles.pin(&home));
+        assert_eq!(tiles.pinned_coords().collect::<Vec<_>>(), vec![&home]);
+
+        tiles
+            .spawn_mut(home)
+            .unwrap()
+            .insert(SizedComponent(600));
+
+        // The pinned tile is the least recently used tile and does not count towards the budget
+        for x in 1..8 {
+            let coords = WorldTileCoords::from((x, 0, ZoomLevel::new(3)));
+            tiles
+                .spawn_mut(coords)
+                .unwrap()
+                .insert(SizedComponent(300));
+            tiles.evict_to_budget();
+            assert!(tiles.exists(home));
+        }
+        assert_eq!(tiles.memory_usage(), 600 + 3 * 300);
+
+        assert!(tiles.unpin(&home));
+        assert!(!tiles.is_pinned(&home));
+        assert_eq!(tiles.evict_to_budget(), vec![home]);
+    }
+
+    #[test]
+    fn test_evict() {
+        let mut tiles = Tiles::default();
+
+        let first = WorldTileCoords::from((0, 0, ZoomLevel::new(1)));
+        let second = WorldTileCoords::from((1, 0, ZoomLevel::new(1)));
+
+        for coords in [first, second] {
+            tiles
+                .spawn_mut(coords)
+                .unwrap()
+                .insert(StateComponent(TileState::Loaded));
+        }
+
+        assert!(tiles.evict(&first));
+        assert!(!tiles.evict(&first));
+        assert_eq!(tiles.loaded_coords(), vec![second]);
+        assert_eq!(tiles.tile_state(&first), None);
+
+        // The tile is requested again, as it does not exist anymore
+        assert!(!tiles.exists(first));
+        assert!(tiles.spawn_mut(first).is_some());
+        assert_eq!(tiles.tile_state(&first), Some(TileState::Loading));
+
+        let mut evicted = tiles.evict_all();
+        evicted.sort_by_key(|coords| coords.x);
+        assert_eq!(evicted, vec![first, second]);
+        assert!(tiles.loaded_coords().is_empty());
+
+        // The GPU resources of every eviction are released once
+        assert_eq!(tiles.take_evicted(), vec![first, first, second]);
+        assert!(tiles.take_evicted().is_empty());
+
+        // The components of evicted and cleared tiles are released, e.g. to recycle their buffers
+        let released = Rc::new(Cell::new(0));
+        for coords in [first, second] {
+            tiles
+                .spawn_mut(coords)
+                .unwrap()
+                .insert(ReleasedComponent(released.clone()));
+        }
+        assert!(tiles.evict(&first));
+        assert_eq!(released.get(), 1);
+        tiles.clear();
+        assert_eq!(released.get(), 2);
+    }
+}
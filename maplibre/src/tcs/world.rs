@@ -1,11 +1,539 @@
-use std::default::Default;
+use std::{collections::HashSet, default::Default};
 
-use crate::tcs::{resources::Resources, tiles::Tiles};
+use cgmath::{Point2, Vector4};
+use geo_types::{LineString, Point, Polygon};
+use rstar::Envelope;
+use thiserror::Error;
+
+use crate::{
+    coords::{LatLon, WorldCoords, WorldTileCoords, Zoom, EXTENT, TILE_SIZE},
+    io::{geometry_index::IndexedGeometry, source_type::SourceType},
+    raster::{insert_raster_tile, ProcessRasterError, RasterTimeline},
+    style::{sprite::SpriteAtlas, Style},
+    tcs::{resources::Resources, tiles::Tiles},
+    util::math::{bounds_from_points, Aabb2},
+    vector::{insert_vector_tile, FeatureData, FrontFeatures, ProcessVectorError, Sprite},
+    view_state::ViewState,
+};
+
+#[derive(Error, Debug)]
+pub enum InsertTileError {
+    #[error("processing vector tile failed")]
+    Vector(#[from] ProcessVectorError),
+    #[error("processing raster tile failed")]
+    Raster(#[from] ProcessRasterError),
+    /// The coordinates are outside of the world
+    #[error("tile coordinates {0} are out of bounds")]
+    OutOfBounds(WorldTileCoords),
+}
 
-#[derive(Default)]
 pub struct World {
     pub resources: Resources,
     pub tiles: Tiles,
+    frozen: bool,
+    /// Whether the features of tiles are indexed, such that they can be queried
+    interactive: bool,
+}
+
+impl Default for World {
+    fn default() -> Self {
+        Self {
+            resources: Resources::default(),
+            tiles: Tiles::default(),
+            frozen: false,
+            interactive: true,
+        }
+    }
 }
 
-impl World {}
+impl World {
+    /// Freezes the map, such that no tiles are requested and camera animations do not advance. The
+    /// current state is still rendered. This isolates rendering from loading, e.g. for benchmarks
+    /// and deterministic screenshots.
+    pub fn freeze(&mut self) {
+        self.frozen = true;
+    }
+
+    /// Resumes requesting tiles and advancing animations. Changes of the camera while the map was
+    /// frozen are requested in the next frame.
+    pub fn unfreeze(&mut self) {
+        self.frozen = false;
+    }
+
+    pub fn is_frozen(&self) -> bool {
+        self.frozen
+    }
+
+    /// Sets whether features can be queried, e.g. by [`World::feature_screen_bounds`]. Maps which
+    /// only display tiles can disable this, such that the geometries of tiles which are requested
+    /// from now on are not indexed. This saves the time and memory which indexing requires.
+    pub fn set_interactive(&mut self, interactive: bool) {
+        self.interactive = interactive;
+    }
+
+    pub fn is_interactive(&self) -> bool {
+        self.interactive
+    }
+
+    /// Attaches `value` to the feature with the id `feature_id` of the style source `source`. The
+    /// value is available to the vertex shader of the feature, see [`FeatureData`].
+    pub fn set_feature_data(&mut self, source: &str, feature_id: u64, value: f32) {
+        self.resources
+            .get_or_init_mut::<FeatureData>()
+            .set(source, feature_id, value);
+    }
+
+    /// Draws the feature with the id `feature_id` of the style source `source` on top of all
+    /// layers, e.g. to highlight it once it is selected. See [`FrontFeatures`].
+    pub fn bring_feature_to_front(&mut self, source: &str, feature_id: u64) {
+        self.resources
+            .get_or_init_mut::<FrontFeatures>()
+            .insert(source, feature_id);
+    }
+
+    /// Draws the feature only within its layer again. Returns whether the feature has been
+    /// brought to the front before.
+    pub fn restore_feature_order(&mut self, source: &str, feature_id: u64) -> bool {
+        self.resources
+            .get_mut::<FrontFeatures>()
+            .map_or(false, |features| features.remove(source, feature_id))
+    }
+
+    /// Sets the timestamps of the frames of the raster source and displays the first frame. The
+    /// timestamp of the displayed frame replaces `{time}` in the URL of the raster tiles.
+    pub fn set_raster_timestamps(&mut self, timestamps: Vec<String>) {
+        self.resources
+            .get_or_init_mut::<RasterTimeline>()
+            .set_timestamps(timestamps);
+    }
+
+    /// Displays the frame at `index` of the timestamps of the raster source. The tiles in view
+    /// keep showing the previous frame until the selected frame has been loaded.
+    pub fn set_raster_time(&mut self, index: usize) {
+        self.resources
+            .get_or_init_mut::<RasterTimeline>()
+            .set_index(index);
+    }
+
+    /// Processes the tile `data` of the `source`, which is already in memory, e.g. from a bundled
+    /// asset, and stores it at `coords` as if it had been fetched. Vector tiles are tessellated
+    /// with all of their layers, like the tiles of the vector source `source_id` of the `style`,
+    /// raster tiles are decoded. The tile is rendered once it is in view, without requesting it.
+    pub fn insert_tile(
+        &mut self,
+        style: &Style,
+        source_id: Option<&str>,
+        coords: WorldTileCoords,
+        source: &SourceType,
+        data: &[u8],
+    ) -> Result<(), InsertTileError> {
+        if coords.build_quad_key().is_none() {
+            return Err(InsertTileError::OutOfBounds(coords));
+        }
+
+        match source {
+            SourceType::Tessellate(_) => insert_vector_tile(self, style, source_id, coords, data)?,
+            SourceType::Raster(_) => insert_raster_tile(self, coords, data)?,
+        }
+        Ok(())
+    }
+
+    /// Replaces the sprite, from which the `fill-pattern`s of the style are sampled.
+    pub fn set_sprite_atlas(&mut self, atlas: SpriteAtlas) {
+        self.resources.get_or_init_mut::<Sprite>().set(atlas);
+    }
+
+    /// The bounding box in window coordinates of the feature with the id `feature_id` of the style
+    /// source `source`, e.g. to anchor a popup to the feature. Only the loaded tiles which are
+    /// visible in the `view_state` are considered. The bounds of features which span multiple
+    /// tiles are combined.
+    ///
+    /// Returns `None` if the feature is not part of a loaded and visible tile.
+    pub fn feature_screen_bounds(
+        &self,
+        style: &Style,
+        view_state: &ViewState,
+        source: &str,
+        feature_id: u64,
+    ) -> Option<Aabb2<f64>> {
+        let source_layers = style
+            .layers
+            .iter()
+            .filter(|layer| layer.source.as_deref() == Some(source))
+            .filter_map(|layer| layer.source_layer.as_deref())
+            .collect::<HashSet<_>>();
+
+        let view_region = view_state.create_view_region()?;
+        let zoom = view_state.zoom();
+
+        let mut corners = Vec::new();
+        for coords in view_region.iter() {
+            let transform = coords.transform_for_zoom(zoom);
+
+            for (_, geometry) in self
+                .tiles
+                .geometry_index
+                .layer_features(&coords)
+                .filter(|(layer_name, _)| source_layers.contains(layer_name))
+                .filter(|(_, geometry)| geometry.feature_id == feature_id)
+            {
+                let (lower, upper) = (geometry.bounds.lower(), geometry.bounds.upper());
+                for (x, y) in [
+                    (lower.x(), lower.y()),
+                    (upper.x(), lower.y()),
+                    (lower.x(), upper.y()),
+                    (upper.x(), upper.y()),
+                ] {
+                    let world = transform * Vector4::new(x, y, 0.0, 1.0);
+                    corners.push(WorldCoords::from((world.x, world.y)));
+                }
+            }
+        }
+
+        // Corners behind the camera of a pitched view are skipped
+        let (min, max) = bounds_from_points(
+            corners
+                .into_iter()
+                .filter_map(|world| view_state.world_to_screen(world))
+                .map(|(x, y)| [x, y]),
+        )?;
+        Some(Aabb2::new(Point2::from(min), Point2::from(max)))
+    }
+
+    /// The features of the loaded tiles which are visible in the `view_state` and whose geometry
+    /// intersects the `polygon`, e.g. for lasso selection, along with the name of the layer which
+    /// contains them. The polygon may be concave and is closed implicitly.
+    ///
+    /// Features which span multiple tiles are returned once per layer, unless they have no id.
+    pub fn query_features_in_polygon(
+        &self,
+        view_state: &ViewState,
+        polygon: &[LatLon],
+    ) -> Vec<(&str, &IndexedGeometry<f64>)> {
+        if polygon.len() < 3 {
+            return Vec::new();
+        }
+
+        let Some(view_region) = view_state.create_view_region() else { return Vec::new(); };
+        let zoom = view_state.zoom();
+        let polygon = polygon
+            .iter()
+            .map(|lat_lon| WorldCoords::from_lat_lon(*lat_lon, zoom))
+            .collect::<Vec<_>>();
+
+        let mut seen = HashSet::new();
+        let mut features = Vec::new();
+        for coords in view_region.iter() {
+            let selection = Polygon::new(
+                LineString::from(
+                    polygon
+                        .iter()
+                        .map(|world| to_tile_local(*world, coords, zoom))
+                        .collect::<Vec<_>>(),
+                ),
+                vec![],
+            );
+
+            for (layer_name, geometry) in self
+                .tiles
+                .geometry_index
+                .layer_features(&coords)
+                .filter(|(_, geometry)| geometry.intersects_polygon(&selection))
+            {
+                if geometry.feature_id != 0 && !seen.insert((layer_name, geometry.feature_id)) {
+                    continue;
+                }
+                features.push((layer_name, geometry));
+            }
+        }
+        features
+    }
+
+    /// The feature of the loaded tiles which are visible in the `view_state` whose geometry is
+    /// closest to `position`, e.g. to snap a position to the nearest road. Only features within
+    /// `max_distance` and of layers for which `layer_filter` returns `true` are considered. Unlike
+    /// picking, the position does not need to be inside of the feature.
+    ///
+    /// Returns the name of the layer, the feature and its distance. Distances are in pixels at the
+    /// zoom of the `view_state`, like world coordinates.
+    pub fn nearest_feature(
+        &self,
+        view_state: &ViewState,
+        position: LatLon,
+        max_distance: f64,
+        layer_filter: impl Fn(&str) -> bool,
+    ) -> Option<(&str, &IndexedGeometry<f64>, f64)> {
+        let view_region = view_state.create_view_region()?;
+        let zoom = view_state.zoom();
+        let world = WorldCoords::from_lat_lon(position, zoom);
+
+        let mut nearest: Option<(&str, &IndexedGeometry<f64>, f64)> = None;
+        for coords in view_region.iter() {
+            let point = Point::from(to_tile_local(world, coords, zoom));
+            // The amount of tile coordinates per world coordinate
+            let tile_scale = zoom.scale_to_zoom_level(coords.z) / TILE_SIZE * EXTENT;
+            let max_tile_distance =
+                nearest.map_or(max_distance, |(_, _, distance)| distance) * tile_scale;
+
+            for (layer_name, geometry) in self
+                .tiles
+                .geometry_index
+                .layer_features(&coords)
+                .filter(|(layer_name, _)| layer_filter(layer_name))
+            {
+                // Geometries whose bounds are too far away can not be closer
+                if geometry.bounds.distance_2(&point) > max_tile_distance.powi(2) {
+                    continue;
+                }
+
+                let distance = geometry.distance_to_point(&point) / tile_scale;
+                if distance <= nearest.map_or(max_distance, |(_, _, distance)| distance) {
+                    nearest = Some((layer_name, geometry, distance));
+                }
+            }
+        }
+        nearest
+    }
+}
+
+/// Converts the `world` coordinates to the coordinates of the tile at `coords`, which range from 0
+/// to [`EXTENT`] within the tile.
+fn to_tile_local(world: WorldCoords, coords: WorldTileCoords, zoom: Zoom) -> (f64, f64) {
+    let scale = zoom.scale_to_zoom_level(coords.z) / TILE_SIZE;
+    (
+        (world.x * scale - coords.x as f64) * EXTENT,
+        (world.y * scale - coords.y as f64) * EXTENT,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::Deg;
+    use geo_types::{LineString, Point, Polygon};
+    use rstar::AABB;
+
+    use super::World;
+    use crate::{
+        coords::{LatLon, WorldCoords, WorldTileCoords, Zoom, ZoomLevel},
+        io::geometry_index::{ExactGeometry, IndexedGeometry, TileIndex},
+        style::{layer::StyleLayer, Style},
+        view_state::ViewState,
+        window::WindowSize,
+    };
+
+    /// An indexed square with the given corners in tile coordinates
+    fn square(feature_id: u64, min: (f64, f64), max: (f64, f64)) -> IndexedGeometry<f64> {
+        IndexedGeometry {
+            bounds: AABB::from_corners(Point::from(min), Point::from(max)),
+            exact: ExactGeometry::Polygon(Polygon::new(
+                LineString::from(vec![min, (max.0, min.1), max, (min.0, max.1), min]),
+                vec![],
+            )),
+            properties: Default::default(),
+            feature_id,
+        }
+    }
+
+    #[test]
+    fn test_feature_screen_bounds() {
+        let mut world = World::default();
+        let z = ZoomLevel::new(1);
+
+        // The feature 7 spans the bottom halves of the two upper tiles
+        for (x, min_x, max_x) in [(0, 2048.0, 4096.0), (1, 0.0, 2048.0)] {
+            world.tiles.geometry_index.index_layer(
+                &WorldTileCoords::from((x, 0, z)),
+                "water".to_string(),
+                TileIndex::Linear {
+                    list: vec![square(7, (min_x, 2048.0), (max_x, 4096.0))],
+                },
+            );
+        }
+
+        let style = Style {
+            layers: vec![StyleLayer {
+                source: Some("openmaptiles".to_string()),
+                source_layer: Some("water".to_string()),
+                ..StyleLayer::default()
+            }],
+            ..Style::default()
+        };
+
+        // The center of the world is in the center of the window
+        let zoom = Zoom::new(1.0);
+        let view_state = ViewState::new(
+            WindowSize::new(800, 600).unwrap(),
+            WorldCoords::at_ground(512.0, 512.0),
+            zoom,
+            Deg(0.0),
+            Deg(110.0),
+        );
+
+        let bounds = world
+            .feature_screen_bounds(&style, &view_state, "openmaptiles", 7)
+            .unwrap();
+
+        // The feature covers the world coordinates from (256, 256) to (768, 512)
+        let min = view_state
+            .world_to_screen(WorldCoords::at_ground(256.0, 256.0))
+            .unwrap();
+        let max = view_state
+            .world_to_screen(WorldCoords::at_ground(768.0, 512.0))
+            .unwrap();
+        assert!((max.1 - 300.0).abs() < 1e-3);
+
+        for (actual, expected) in [
+            (bounds.min.x, min.0),
+            (bounds.min.y, min.1),
+            (bounds.max.x, max.0),
+            (bounds.max.y, max.1),
+        ] {
+            assert!((actual - expected).abs() < 1e-3, "{bounds:?}");
+        }
+
+        assert!(world
+            .feature_screen_bounds(&style, &view_state, "openmaptiles", 8)
+            .is_none());
+        assert!(world
+            .feature_screen_bounds(&style, &view_state, "other", 7)
+            .is_none());
+    }
+
+    #[test]
+    fn test_query_features_in_polygon() {
+        let mut world = World::default();
+        let z = ZoomLevel::new(1);
+        let left = WorldTileCoords::from((0, 0, z));
+        let right = WorldTileCoords::from((1, 0, z));
+
+        // At zoom 1, tile coordinates are 8 times the world coordinates, offset by 512 in the
+        // right tile
+        let line = |feature_id, from: (f64, f64), to: (f64, f64)| IndexedGeometry {
+            bounds: AABB::from_corners(Point::from(from), Point::from(to)),
+            exact: ExactGeometry::LineString(LineString::from(vec![from, to])),
+            properties: Default::default(),
+            feature_id,
+        };
+        world.tiles.geometry_index.index_layer(
+            &left,
+            "water".to_string(),
+            TileIndex::Linear {
+                list: vec![
+                    // Inside, continued in the right tile
+                    square(1, (3840.0, 960.0), (4096.0, 1280.0)),
+                    // Across the left edge of the selection
+                    square(3, (2800.0, 2000.0), (3360.0, 2240.0)),
+                    // Outside of the selection
+                    square(4, (800.0, 800.0), (1200.0, 1200.0)),
+                    // Across the upper edge of the selection
+                    line(6, (3600.0, 400.0), (3600.0, 1200.0)),
+                ],
+            },
+        );
+        world.tiles.geometry_index.index_layer(
+            &right,
+            "water".to_string(),
+            TileIndex::Linear {
+                list: vec![
+                    square(1, (0.0, 960.0), (384.0, 1280.0)),
+                    // In the notch of the selection, within its bounds
+                    square(2, (224.0, 1920.0), (544.0, 2240.0)),
+                    // Through the notch of the selection
+                    line(5, (64.0, 2000.0), (1504.0, 2000.0)),
+                ],
+            },
+        );
+
+        let zoom = Zoom::new(1.0);
+        let view_state = ViewState::new(
+            WindowSize::new(800, 600).unwrap(),
+            WorldCoords::at_ground(512.0, 512.0),
+            zoom,
+            Deg(0.0),
+            Deg(110.0),
+        );
+
+        // An L-shaped selection in world coordinates, whose lower right quarter is cut out
+        let selection = [
+            (400.0, 100.0),
+            (600.0, 100.0),
+            (600.0, 200.0),
+            (500.0, 200.0),
+            (500.0, 300.0),
+            (400.0, 300.0),
+        ]
+        .map(|(x, y)| WorldCoords::at_ground(x, y).to_lat_lon(zoom));
+
+        let mut feature_ids = world
+            .query_features_in_polygon(&view_state, &selection)
+            .into_iter()
+            .map(|(layer_name, geometry)| {
+                assert_eq!(layer_name, "water");
+                geometry.feature_id
+            })
+            .collect::<Vec<_>>();
+        feature_ids.sort_unstable();
+        assert_eq!(feature_ids, vec![1, 3, 6]);
+
+        let degenerate: [LatLon; 2] = [selection[0], selection[1]];
+        assert!(world
+            .query_features_in_polygon(&view_state, &degenerate)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_nearest_feature() {
+        let mut world = World::default();
+        let coords = WorldTileCoords::from((0, 0, ZoomLevel::new(1)));
+
+        // Horizontal lines across the tile. At zoom 1, tile coordinates are 8 times the world
+        // coordinates.
+        let line = |feature_id, y: f64| IndexedGeometry {
+            bounds: AABB::from_corners(Point::new(0.0, y), Point::new(4096.0, y)),
+            exact: ExactGeometry::LineString(LineString::from(vec![(0.0, y), (4096.0, y)])),
+            properties: Default::default(),
+            feature_id,
+        };
+        world.tiles.geometry_index.index_layer(
+            &coords,
+            "roads".to_string(),
+            TileIndex::Linear {
+                list: vec![line(1, 800.0), line(2, 2000.0)],
+            },
+        );
+        world.tiles.geometry_index.index_layer(
+            &coords,
+            "rivers".to_string(),
+            TileIndex::Linear {
+                list: vec![line(3, 1000.0)],
+            },
+        );
+
+        let zoom = Zoom::new(1.0);
+        let view_state = ViewState::new(
+            WindowSize::new(800, 600).unwrap(),
+            WorldCoords::at_ground(512.0, 512.0),
+            zoom,
+            Deg(0.0),
+            Deg(110.0),
+        );
+        // The tile coordinates (2048, 1200)
+        let position = WorldCoords::at_ground(256.0, 150.0).to_lat_lon(zoom);
+
+        let (layer_name, geometry, distance) = world
+            .nearest_feature(&view_state, position, 100.0, |_| true)
+            .unwrap();
+        assert_eq!((layer_name, geometry.feature_id), ("rivers", 3));
+        assert!((distance - 25.0).abs() < 1e-6, "{distance}");
+
+        let (layer_name, geometry, distance) = world
+            .nearest_feature(&view_state, position, 100.0, |layer| layer == "roads")
+            .unwrap();
+        assert_eq!((layer_name, geometry.feature_id), ("roads", 1));
+        assert!((distance - 50.0).abs() < 1e-6, "{distance}");
+
+        assert!(world
+            .nearest_feature(&view_state, position, 40.0, |layer| layer == "roads")
+            .is_none());
+    }
+}
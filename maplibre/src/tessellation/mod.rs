@@ -5,8 +5,13 @@ use lyon::tessellation::{
     FillVertex, FillVertexConstructor, StrokeVertex, StrokeVertexConstructor, VertexBuffers,
 };
 
-use crate::render::ShaderVertex;
+use crate::{
+    render::ShaderVertex,
+    style::layer::{LineCap, LineJoin},
+};
 
+pub mod pool;
+pub mod tessellator;
 pub mod zero_tessellator;
 
 const DEFAULT_TOLERANCE: f32 = 0.02;
@@ -14,6 +19,41 @@ const DEFAULT_TOLERANCE: f32 = 0.02;
 /// Vertex buffers index data type.
 pub type IndexDataType = u32; // Must match INDEX_FORMAT
 
+/// The smallest [`IndexFormat`](wgpu::IndexFormat) which can address `vertices` vertices. 16 bit
+/// indices halve the size of the index buffers of simple layers.
+pub fn index_format(vertices: usize) -> wgpu::IndexFormat {
+    if vertices <= u16::MAX as usize {
+        wgpu::IndexFormat::Uint16
+    } else {
+        wgpu::IndexFormat::Uint32
+    }
+}
+
+/// The size of a single index with the `format` in bytes.
+pub fn index_size(format: wgpu::IndexFormat) -> wgpu::BufferAddress {
+    match format {
+        wgpu::IndexFormat::Uint16 => 2,
+        wgpu::IndexFormat::Uint32 => 4,
+    }
+}
+
+/// Packs the `indices` into the `format`. The packed bytes are padded to fulfill the
+/// `wgpu::COPY_BUFFER_ALIGNMENT`.
+pub fn pack_indices<I: Copy + Into<u32>>(indices: &[I], format: wgpu::IndexFormat) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(indices.len() * index_size(format) as usize);
+    for &index in indices {
+        let index: u32 = index.into();
+        match format {
+            wgpu::IndexFormat::Uint16 => bytes.extend_from_slice(&(index as u16).to_ne_bytes()),
+            wgpu::IndexFormat::Uint32 => bytes.extend_from_slice(&index.to_ne_bytes()),
+        }
+    }
+
+    let align = wgpu::COPY_BUFFER_ALIGNMENT as usize;
+    bytes.resize((bytes.len() + align - 1) / align * align, 0);
+    bytes
+}
+
 /// Constructor for Fill and Stroke vertices.
 pub struct VertexConstructor {}
 
@@ -32,6 +72,26 @@ impl StrokeVertexConstructor<ShaderVertex> for VertexConstructor {
     }
 }
 
+impl From<LineCap> for lyon::tessellation::LineCap {
+    fn from(line_cap: LineCap) -> Self {
+        match line_cap {
+            LineCap::Butt => Self::Butt,
+            LineCap::Round => Self::Round,
+            LineCap::Square => Self::Square,
+        }
+    }
+}
+
+impl From<LineJoin> for lyon::tessellation::LineJoin {
+    fn from(line_join: LineJoin) -> Self {
+        match line_join {
+            LineJoin::Miter => Self::Miter,
+            LineJoin::Round => Self::Round,
+            LineJoin::Bevel => Self::Bevel,
+        }
+    }
+}
+
 /// Vertex buffer which includes additional padding to fulfill the `wgpu::COPY_BUFFER_ALIGNMENT`.
 #[derive(Clone)]
 pub struct OverAlignedVertexBuffer<V, I> {
@@ -47,6 +107,11 @@ impl<V, I> OverAlignedVertexBuffer<V, I> {
         }
     }
 
+    /// The format in which the indices of this buffer are uploaded, see [`index_format`].
+    pub fn index_format(&self) -> wgpu::IndexFormat {
+        index_format(self.buffer.vertices.len())
+    }
+
     pub fn from_iters<IV, II>(vertices: IV, indices: II, usable_indices: u32) -> Self
     where
         IV: IntoIterator<Item = V>,
@@ -110,3 +175,42 @@ impl<V: Pod, I: Pod> Align<V, I> for VertexBuffers<V, I> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use lyon::tessellation::VertexBuffers;
+
+    use super::{pack_indices, OverAlignedVertexBuffer};
+    use crate::render::ShaderVertex;
+
+    fn layer(vertices: usize) -> OverAlignedVertexBuffer<ShaderVertex, u32> {
+        let mut buffer = VertexBuffers::new();
+        buffer.vertices = vec![ShaderVertex::default(); vertices];
+        buffer.indices = vec![0, 1, vertices as u32 - 1];
+        buffer.into()
+    }
+
+    #[test]
+    fn test_index_format() {
+        let small = layer(100);
+        assert_eq!(small.index_format(), wgpu::IndexFormat::Uint16);
+        // Three 16 bit indices are padded to 8 bytes
+        assert_eq!(
+            pack_indices(&small.buffer.indices[..3], small.index_format()),
+            [0u16, 1, 99, 0]
+                .iter()
+                .flat_map(|index| index.to_ne_bytes())
+                .collect::<Vec<_>>()
+        );
+
+        let large = layer(100_000);
+        assert_eq!(large.index_format(), wgpu::IndexFormat::Uint32);
+        assert_eq!(
+            pack_indices(&large.buffer.indices[..3], large.index_format()),
+            [0u32, 1, 99_999]
+                .iter()
+                .flat_map(|index| index.to_ne_bytes())
+                .collect::<Vec<_>>()
+        );
+    }
+}
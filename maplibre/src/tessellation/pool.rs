@@ -0,0 +1,132 @@
+//! Reuses the vertex buffers of tessellated tiles to reduce allocations while panning.
+
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Mutex,
+};
+
+use lyon::tessellation::VertexBuffers;
+
+use crate::{render::ShaderVertex, tessellation::IndexDataType};
+
+/// The maximum amount of buffers which are kept in the [`VERTEX_BUFFER_POOL`].
+pub const MAX_POOLED_BUFFERS: usize = 64;
+
+/// The pool which is used by the tessellation of vector tiles. Buffers are returned once the data
+/// of a tile is replaced.
+pub static VERTEX_BUFFER_POOL: VertexBufferPool = VertexBufferPool::new(MAX_POOLED_BUFFERS);
+
+/// Counts how often buffers have been taken from a [`VertexBufferPool`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PoolStats {
+    /// Buffers which had to be allocated, because the pool was empty
+    pub allocated: usize,
+    /// Buffers which have been reused
+    pub reused: usize,
+}
+
+/// A pool of vertex buffers which can be shared between the threads which tessellate tiles.
+pub struct VertexBufferPool {
+    buffers: Mutex<Vec<VertexBuffers<ShaderVertex, IndexDataType>>>,
+    max_pooled: usize,
+    allocated: AtomicUsize,
+    reused: AtomicUsize,
+}
+
+impl VertexBufferPool {
+    pub const fn new(max_pooled: usize) -> Self {
+        Self {
+            buffers: Mutex::new(Vec::new()),
+            max_pooled,
+            allocated: AtomicUsize::new(0),
+            reused: AtomicUsize::new(0),
+        }
+    }
+
+    /// Takes an empty buffer from the pool or allocates a new one.
+    pub fn take(&self) -> VertexBuffers<ShaderVertex, IndexDataType> {
+        let buffer = self.buffers.lock().ok().and_then(|mut buffers| buffers.pop());
+
+        match buffer {
+            Some(buffer) => {
+                self.reused.fetch_add(1, Ordering::Relaxed);
+                buffer
+            }
+            None => {
+                self.allocated.fetch_add(1, Ordering::Relaxed);
+                VertexBuffers::new()
+            }
+        }
+    }
+
+    /// Returns the `buffer` to the pool. The buffer is cleared, but keeps its capacity. If the pool
+    /// is full, the buffer is dropped.
+    pub fn give(&self, mut buffer: VertexBuffers<ShaderVertex, IndexDataType>) {
+        buffer.vertices.clear();
+        buffer.indices.clear();
+
+        let Ok(mut buffers) = self.buffers.lock() else { return; };
+        if buffers.len() < self.max_pooled {
+            buffers.push(buffer);
+        }
+    }
+
+    pub fn stats(&self) -> PoolStats {
+        PoolStats {
+            allocated: self.allocated.load(Ordering::Relaxed),
+            reused: self.reused.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PoolStats, VertexBufferPool};
+    use crate::render::ShaderVertex;
+
+    /// Simulates tessellating `tiles` tiles of which the previous ones are replaced.
+    fn tessellate_tiles(pool: &VertexBufferPool, tiles: usize) {
+        let mut loaded = Vec::new();
+
+        for tile in 0..tiles {
+            let mut buffer = pool.take();
+            assert!(buffer.vertices.is_empty() && buffer.indices.is_empty());
+
+            buffer
+                .vertices
+                .extend((0..100).map(|_| ShaderVertex::new([0.0, 0.0], [0.0, 0.0])));
+            buffer.indices.extend(0..300);
+            loaded.push(buffer);
+
+            // Only two tiles are kept, older tiles are evicted
+            if tile >= 2 {
+                pool.give(loaded.remove(0));
+            }
+        }
+    }
+
+    #[test]
+    fn test_reuse_buffers() {
+        let pool = VertexBufferPool::new(8);
+        tessellate_tiles(&pool, 100);
+
+        assert_eq!(
+            pool.stats(),
+            PoolStats {
+                allocated: 3,
+                reused: 97
+            }
+        );
+
+        // Without pooling every tile needs an allocation
+        let pool = VertexBufferPool::new(0);
+        tessellate_tiles(&pool, 100);
+        assert_eq!(
+            pool.stats(),
+            PoolStats {
+                allocated: 100,
+                reused: 0
+            }
+        );
+    }
+}
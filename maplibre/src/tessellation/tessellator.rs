@@ -0,0 +1,485 @@
+//! Makes the tessellation of source-layers pluggable, e.g. for extruded contours or custom line
+//! joins.
+
+use std::{collections::HashMap, sync::Arc};
+
+use geozero::{mvt::tile, GeozeroDatasource};
+use lyon::tessellation::VertexBuffers;
+
+use crate::{
+    coords::WorldTileCoords,
+    render::ShaderVertex,
+    style::{
+        layer::{LayerLayout, LayerPaint, LineCap, LineJoin},
+        source::{Source, TessellationSettings, VectorSource},
+        Style,
+    },
+    tessellation::{pool::VERTEX_BUFFER_POOL, zero_tessellator::ZeroTessellator, IndexDataType},
+};
+
+/// The vertices and indices of a tessellated layer along with the amount of indices of each
+/// feature.
+pub type TessellatedLayer = (VertexBuffers<ShaderVertex, IndexDataType>, Vec<u32>);
+
+/// Tessellates the features of a layer of a vector tile.
+pub trait Tessellator: Send + Sync + 'static {
+    fn tessellate(
+        &self,
+        layer: &mut tile::Layer,
+        coords: WorldTileCoords,
+    ) -> geozero::error::Result<TessellatedLayer>;
+}
+
+/// Tessellates layers with the [`ZeroTessellator`]. The buffers are taken from the
+/// [`VERTEX_BUFFER_POOL`].
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct DefaultTessellator {
+    pub line_cap: LineCap,
+    pub line_join: LineJoin,
+    /// The size of the grid in tile units to which coordinates are snapped, see
+    /// [`ZeroTessellator::with_precision`]. Coordinates are not snapped if `None`.
+    pub precision: Option<f64>,
+    /// The tolerance in tile units with which lines and polygon rings are simplified, see
+    /// [`ZeroTessellator::with_simplification`]. Geometries are not simplified if `None`.
+    pub simplification_tolerance: Option<f64>,
+}
+
+impl DefaultTessellator {
+    /// Tessellates line strings according to the `line-cap` and `line-join` of the `layout`.
+    pub fn from_layout(layout: &LayerLayout) -> Self {
+        Self {
+            line_cap: layout.line_cap.unwrap_or_default(),
+            line_join: layout.line_join.unwrap_or_default(),
+            ..Self::default()
+        }
+    }
+
+    /// Replaces the properties which are set in the `settings` of a source.
+    pub fn with_settings(self, settings: &TessellationSettings) -> Self {
+        Self {
+            line_cap: settings.line_cap.unwrap_or(self.line_cap),
+            line_join: settings.line_join.unwrap_or(self.line_join),
+            precision: settings.precision.or(self.precision),
+            simplification_tolerance: settings
+                .simplification_tolerance
+                .or(self.simplification_tolerance),
+        }
+    }
+}
+
+impl Tessellator for DefaultTessellator {
+    fn tessellate(
+        &self,
+        layer: &mut tile::Layer,
+        _coords: WorldTileCoords,
+    ) -> geozero::error::Result<TessellatedLayer> {
+        let mut tessellator =
+            ZeroTessellator::<IndexDataType>::with_buffer(VERTEX_BUFFER_POOL.take())
+                .with_line_style(self.line_cap, self.line_join);
+        if let Some(precision) = self.precision {
+            tessellator = tessellator.with_precision(precision);
+        }
+        if let Some(tolerance) = self.simplification_tolerance {
+            tessellator = tessellator.with_simplification(tolerance);
+        }
+
+        if let Err(e) = layer.process(&mut tessellator) {
+            VERTEX_BUFFER_POOL.give(tessellator.buffer);
+            return Err(e);
+        }
+
+        Ok((tessellator.buffer, tessellator.feature_indices))
+    }
+}
+
+/// Chooses the [`Tessellator`] per source-layer. Source-layers without a registered tessellator
+/// are tessellated by the [`DefaultTessellator`].
+#[derive(Clone, Default)]
+pub struct Tessellators {
+    by_source_layer: HashMap<String, Arc<dyn Tessellator>>,
+    /// Tessellates the source-layers without a registered tessellator or line layout
+    default: DefaultTessellator,
+    /// The [`DefaultTessellator`]s of the source-layers of each vector source which are
+    /// configured by the style, either by the layout of their line layers or by the settings of
+    /// their source. The default source is `None`.
+    configured: HashMap<Option<String>, HashMap<String, DefaultTessellator>>,
+}
+
+impl Tessellators {
+    pub fn with(mut self, source_layer: impl Into<String>, tessellator: impl Tessellator) -> Self {
+        self.by_source_layer
+            .insert(source_layer.into(), Arc::new(tessellator));
+        self
+    }
+
+    /// Tessellates the line strings of the source-layers of the line layers of the `style`
+    /// according to their `line-cap` and `line-join`. A source-layer is tessellated once for all
+    /// layers of its source, so the layout of the first line layer of each source-layer is used.
+    pub fn with_line_layouts(mut self, style: &Style) -> Self {
+        for layer in &style.layers {
+            let (Some(LayerPaint::Line(_)), Some(source_layer), Some(layout)) =
+                (&layer.paint, &layer.source_layer, &layer.layout) else { continue; };
+
+            let precision = self.default.precision;
+            self.configured
+                .entry(layer.source.clone())
+                .or_default()
+                .entry(source_layer.clone())
+                .or_insert_with(|| DefaultTessellator {
+                    precision,
+                    ..DefaultTessellator::from_layout(layout)
+                });
+        }
+        self
+    }
+
+    /// Tessellates the source-layers of the layers of the `style` according to the
+    /// [`TessellationSettings`] of their vector source. The settings replace the line layouts,
+    /// so this is applied after [`Tessellators::with_line_layouts`].
+    pub fn with_source_settings(mut self, style: &Style) -> Self {
+        for layer in &style.layers {
+            let (Some(source), Some(source_layer)) = (&layer.source, &layer.source_layer) else { continue; };
+            let Some(Source::Vector(VectorSource {
+                tessellation: Some(settings),
+                ..
+            })) = style.sources.get(source) else { continue; };
+
+            let default = self.default;
+            let tessellator = self
+                .configured
+                .entry(Some(source.clone()))
+                .or_default()
+                .entry(source_layer.clone())
+                .or_insert(default);
+            // Applying the settings of the source again for another layer changes nothing
+            *tessellator = tessellator.with_settings(settings);
+        }
+        self
+    }
+
+    /// Snaps the coordinates of all source-layers which are tessellated by a
+    /// [`DefaultTessellator`] to a grid of the size `precision` in tile units.
+    pub fn with_precision(mut self, precision: f64) -> Self {
+        self.default.precision = Some(precision);
+        for tessellator in self.configured.values_mut().flat_map(HashMap::values_mut) {
+            tessellator.precision = Some(precision);
+        }
+        self
+    }
+
+    /// The tessellator of the `source_layer` of the vector `source`, `None` for the default
+    /// source.
+    pub fn get(&self, source: Option<&str>, source_layer: &str) -> &dyn Tessellator {
+        if let Some(tessellator) = self.by_source_layer.get(source_layer) {
+            return tessellator.as_ref();
+        }
+
+        match self
+            .configured
+            .get(&source.map(str::to_string))
+            .and_then(|source_layers| source_layers.get(source_layer))
+        {
+            Some(tessellator) => tessellator,
+            None => &self.default,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use geozero::mvt::tile;
+    use lyon::tessellation::VertexBuffers;
+
+    use super::{DefaultTessellator, TessellatedLayer, Tessellator, Tessellators};
+    use crate::{
+        coords::WorldTileCoords,
+        render::ShaderVertex,
+        style::{
+            layer::{LayerLayout, LayerPaint, LineCap, LineJoin, LinePaint, StyleLayer},
+            source::{Source, TessellationSettings, VectorSource},
+            Style,
+        },
+    };
+
+    /// Replaces every layer by a single triangle
+    struct TriangleTessellator;
+
+    impl Tessellator for TriangleTessellator {
+        fn tessellate(
+            &self,
+            _layer: &mut tile::Layer,
+            _coords: WorldTileCoords,
+        ) -> geozero::error::Result<TessellatedLayer> {
+            let mut buffer = VertexBuffers::new();
+            buffer.vertices.extend([
+                ShaderVertex::new([0.0, 0.0], [0.0, 0.0]),
+                ShaderVertex::new([4096.0, 0.0], [0.0, 0.0]),
+                ShaderVertex::new([0.0, 4096.0], [0.0, 0.0]),
+            ]);
+            buffer.indices.extend([0, 1, 2]);
+            Ok((buffer, vec![3]))
+        }
+    }
+
+    fn square(name: &str) -> tile::Layer {
+        tile::Layer {
+            version: 2,
+            name: name.to_string(),
+            features: vec![tile::Feature {
+                id: Some(1),
+                tags: vec![],
+                r#type: Some(tile::GeomType::Polygon as i32),
+                geometry: vec![9, 0, 0, 26, 20, 0, 0, 20, 19, 0, 15],
+            }],
+            keys: vec![],
+            values: vec![],
+            extent: Some(4096),
+        }
+    }
+
+    #[test]
+    fn test_custom_tessellator() {
+        let tessellators = Tessellators::default().with("contour", TriangleTessellator);
+        let coords = WorldTileCoords::default();
+
+        let (buffer, feature_indices) = tessellators
+            .get(None, "contour")
+            .tessellate(&mut square("contour"), coords)
+            .unwrap();
+        assert_eq!(buffer.vertices.len(), 3);
+        assert_eq!(buffer.indices, vec![0, 1, 2]);
+        assert_eq!(feature_indices, vec![3]);
+
+        // Other source-layers use the default tessellator
+        let (buffer, feature_indices) = tessellators
+            .get(None, "water")
+            .tessellate(&mut square("water"), coords)
+            .unwrap();
+        assert!(!buffer.indices.is_empty());
+        assert_eq!(feature_indices, vec![buffer.indices.len() as u32]);
+    }
+
+    /// A line with a right angle: (0, 0) -> (10, 0) -> (10, 10)
+    fn bend(name: &str) -> tile::Layer {
+        tile::Layer {
+            version: 2,
+            name: name.to_string(),
+            features: vec![tile::Feature {
+                id: Some(1),
+                tags: vec![],
+                r#type: Some(tile::GeomType::Linestring as i32),
+                geometry: vec![9, 0, 0, 18, 20, 0, 0, 20],
+            }],
+            keys: vec![],
+            values: vec![],
+            extent: Some(4096),
+        }
+    }
+
+    /// Two squares whose edges at x = 99 and x = 101 almost meet:
+    /// (0, 0) -> (101, 101) and (99, 0) -> (200, 101)
+    fn neighbors() -> tile::Layer {
+        let feature = |id, geometry| tile::Feature {
+            id: Some(id),
+            tags: vec![],
+            r#type: Some(tile::GeomType::Polygon as i32),
+            geometry,
+        };
+        tile::Layer {
+            version: 2,
+            name: "landuse".to_string(),
+            features: vec![
+                feature(1, vec![9, 0, 0, 26, 202, 0, 0, 202, 201, 0, 15]),
+                feature(2, vec![9, 198, 0, 26, 202, 0, 0, 202, 201, 0, 15]),
+            ],
+            keys: vec![],
+            values: vec![],
+            extent: Some(4096),
+        }
+    }
+
+    /// The distinct vertex positions of each feature
+    fn feature_positions(tessellator: &dyn Tessellator) -> Vec<Vec<[f32; 2]>> {
+        let (buffer, feature_indices) = tessellator
+            .tessellate(&mut neighbors(), WorldTileCoords::default())
+            .unwrap();
+
+        let mut start = 0;
+        feature_indices
+            .iter()
+            .map(|count| {
+                let end = start + *count as usize;
+                let mut positions = buffer.indices[start..end]
+                    .iter()
+                    .map(|index| buffer.vertices[*index as usize].position)
+                    .collect::<Vec<_>>();
+                start = end;
+
+                positions.sort_by(|a, b| a.partial_cmp(b).unwrap());
+                positions.dedup();
+                positions
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_precision() {
+        let positions = feature_positions(&DefaultTessellator::default());
+        assert_eq!(positions[0].last(), Some(&[101.0, 101.0]));
+        assert_eq!(positions[1].first(), Some(&[99.0, 0.0]));
+
+        // Both edges are snapped to x = 100, so the features share their vertices exactly
+        let tessellators = Tessellators::default().with_precision(4.0);
+        let positions = feature_positions(tessellators.get(None, "landuse"));
+        assert_eq!(
+            positions[0],
+            vec![[0.0, 0.0], [0.0, 100.0], [100.0, 0.0], [100.0, 100.0]]
+        );
+        assert_eq!(
+            positions[1],
+            vec![[100.0, 0.0], [100.0, 100.0], [200.0, 0.0], [200.0, 100.0]]
+        );
+        let edge = |positions: &[[f32; 2]]| {
+            positions
+                .iter()
+                .filter(|[x, _]| *x == 100.0)
+                .copied()
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(edge(&positions[0]), edge(&positions[1]));
+    }
+
+    fn vertex_count(tessellator: &dyn Tessellator) -> usize {
+        let (buffer, _) = tessellator
+            .tessellate(&mut bend("roads"), WorldTileCoords::default())
+            .unwrap();
+        buffer.vertices.len()
+    }
+
+    #[test]
+    fn test_line_joins_and_caps() {
+        let tessellator = |line_cap, line_join| DefaultTessellator {
+            line_cap,
+            line_join,
+            ..DefaultTessellator::default()
+        };
+
+        let miter = vertex_count(&tessellator(LineCap::Butt, LineJoin::Miter));
+        let round = vertex_count(&tessellator(LineCap::Butt, LineJoin::Round));
+        // The round join approximates an arc, while the miter join is a single point on each side
+        assert!(round > miter);
+
+        let round_caps = vertex_count(&tessellator(LineCap::Round, LineJoin::Miter));
+        assert!(round_caps > miter);
+    }
+
+    #[test]
+    fn test_line_layouts() {
+        let line = |source_layer: &str, layout: Option<LayerLayout>| StyleLayer {
+            paint: Some(LayerPaint::Line(LinePaint { line_color: None })),
+            source_layer: Some(source_layer.to_string()),
+            layout,
+            ..StyleLayer::default()
+        };
+        let style = Style {
+            layers: vec![
+                line(
+                    "roads",
+                    Some(LayerLayout {
+                        line_cap: Some(LineCap::Round),
+                        line_join: Some(LineJoin::Round),
+                        ..LayerLayout::default()
+                    }),
+                ),
+                line("rivers", None),
+            ],
+            ..Style::default()
+        };
+
+        let tessellators = Tessellators::default().with_line_layouts(&style);
+        let round = DefaultTessellator {
+            line_cap: LineCap::Round,
+            line_join: LineJoin::Round,
+            ..DefaultTessellator::default()
+        };
+
+        assert_eq!(
+            vertex_count(tessellators.get(None, "roads")),
+            vertex_count(&round)
+        );
+        assert_eq!(
+            vertex_count(tessellators.get(None, "rivers")),
+            vertex_count(&DefaultTessellator::default())
+        );
+    }
+
+    /// A line which zigzags with an amplitude of 4 along the x-axis
+    fn zigzag(name: &str) -> tile::Layer {
+        let mut geometry = vec![9, 0, 0, 2 | (19 << 3)];
+        for i in 1..20 {
+            geometry.extend([16, if i % 2 == 1 { 8 } else { 7 }]);
+        }
+
+        tile::Layer {
+            version: 2,
+            name: name.to_string(),
+            features: vec![tile::Feature {
+                id: Some(1),
+                tags: vec![],
+                r#type: Some(tile::GeomType::Linestring as i32),
+                geometry,
+            }],
+            keys: vec![],
+            values: vec![],
+            extent: Some(4096),
+        }
+    }
+
+    #[test]
+    fn test_source_settings() {
+        let source = |simplification_tolerance| {
+            Source::Vector(VectorSource {
+                tessellation: Some(TessellationSettings {
+                    simplification_tolerance: Some(simplification_tolerance),
+                    ..TessellationSettings::default()
+                }),
+                ..VectorSource::default()
+            })
+        };
+        let line = |source: &str, source_layer: &str| StyleLayer {
+            paint: Some(LayerPaint::Line(LinePaint { line_color: None })),
+            source: Some(source.to_string()),
+            source_layer: Some(source_layer.to_string()),
+            ..StyleLayer::default()
+        };
+        let style = Style {
+            sources: HashMap::from([
+                ("detailed".to_string(), source(1.0)),
+                ("coarse".to_string(), source(10.0)),
+            ]),
+            // Both sources have a source-layer with the same name
+            layers: vec![line("detailed", "roads"), line("coarse", "roads")],
+            ..Style::default()
+        };
+
+        let tessellators = Tessellators::default().with_source_settings(&style);
+        let vertex_count = |source: Option<&str>, source_layer: &str| {
+            let (buffer, _) = tessellators
+                .get(source, source_layer)
+                .tessellate(&mut zigzag(source_layer), WorldTileCoords::default())
+                .unwrap();
+            buffer.vertices.len()
+        };
+
+        // The zigzag deviates further from a straight line than the detailed tolerance
+        let detailed = vertex_count(Some("detailed"), "roads");
+        assert_eq!(detailed, vertex_count(Some("detailed"), "rivers"));
+        assert_eq!(detailed, vertex_count(None, "roads"));
+
+        // The coarse tolerance simplifies the zigzag to a straight line
+        let coarse = vertex_count(Some("coarse"), "roads");
+        assert!(coarse < detailed, "{coarse} {detailed}");
+    }
+}
@@ -2,6 +2,8 @@
 
 use std::cell::RefCell;
 
+use geo::Simplify;
+use geo_types::LineString;
 use geozero::{FeatureProcessor, GeomProcessor, PropertyProcessor};
 use lyon::{
     geom,
@@ -15,6 +17,7 @@ use lyon::{
 
 use crate::{
     render::ShaderVertex,
+    style::layer::{LineCap, LineJoin},
     tessellation::{VertexConstructor, DEFAULT_TOLERANCE},
 };
 
@@ -23,13 +26,24 @@ type GeoResult<T> = geozero::error::Result<T>;
 /// Build tessellations with vectors.
 pub struct ZeroTessellator<I: std::ops::Add + From<lyon::tessellation::VertexId> + MaxIndex> {
     path_builder: RefCell<Builder>,
-    path_open: bool,
+    /// The snapped points of the open path. They are added to the `path_builder` once the path
+    /// ends, such that the path can be simplified as a whole.
+    path_points: Vec<geom::Point<f32>>,
     is_point: bool,
 
     pub buffer: VertexBuffers<ShaderVertex, I>,
 
     pub feature_indices: Vec<u32>,
     current_index: usize,
+
+    /// The options which are used to tessellate line strings, e.g. their caps and joins
+    stroke_options: StrokeOptions,
+    /// The size of the grid to which coordinates are snapped, see
+    /// [`ZeroTessellator::with_precision`]
+    precision: Option<f64>,
+    /// The maximum distance in tile units by which simplified paths may deviate from the original
+    /// paths, see [`ZeroTessellator::with_simplification`]
+    simplification_tolerance: Option<f32>,
 }
 
 impl<I: std::ops::Add + From<lyon::tessellation::VertexId> + MaxIndex> Default
@@ -41,13 +55,62 @@ impl<I: std::ops::Add + From<lyon::tessellation::VertexId> + MaxIndex> Default
             buffer: VertexBuffers::new(),
             feature_indices: Vec::new(),
             current_index: 0,
-            path_open: false,
+            path_points: Vec::new(),
             is_point: false,
+            stroke_options: StrokeOptions::tolerance(DEFAULT_TOLERANCE),
+            precision: None,
+            simplification_tolerance: None,
         }
     }
 }
 
 impl<I: std::ops::Add + From<lyon::tessellation::VertexId> + MaxIndex> ZeroTessellator<I> {
+    /// Tessellates into the existing `buffer`, e.g. one which is taken from a
+    /// [`VertexBufferPool`](crate::tessellation::pool::VertexBufferPool).
+    pub fn with_buffer(buffer: VertexBuffers<ShaderVertex, I>) -> Self {
+        Self {
+            buffer,
+            ..Self::default()
+        }
+    }
+
+    /// Tessellates line strings with the given `line_cap` and `line_join`.
+    pub fn with_line_style(mut self, line_cap: LineCap, line_join: LineJoin) -> Self {
+        self.stroke_options = self
+            .stroke_options
+            .with_line_cap(line_cap.into())
+            .with_line_join(line_join.into());
+        self
+    }
+
+    /// Snaps all coordinates to a grid of the size `precision` in tile units before they are
+    /// tessellated. Edges which are shared by adjacent features or tiles, but whose coordinates
+    /// differ slightly, then meet exactly, which avoids cracks and z-fighting.
+    pub fn with_precision(mut self, precision: f64) -> Self {
+        self.precision = Some(precision).filter(|precision| *precision > 0.0);
+        self
+    }
+
+    /// Simplifies lines and polygon rings with the Ramer–Douglas–Peucker algorithm, such that they
+    /// deviate at most by `tolerance` in tile units from the original geometry. This reduces the
+    /// amount of vertices of detailed geometries which are displayed at a coarse scale.
+    pub fn with_simplification(mut self, tolerance: f64) -> Self {
+        self.simplification_tolerance = Some(tolerance as f32).filter(|tolerance| *tolerance > 0.0);
+        self
+    }
+
+    /// Rounds the coordinates to the nearest multiple of the precision in `f64`, such that equal
+    /// input coordinates always result in equal `f32` vertices.
+    fn snap(&self, x: f64, y: f64) -> geom::Point<f32> {
+        match self.precision {
+            Some(precision) => geom::point(
+                ((x / precision).round() * precision) as f32,
+                ((y / precision).round() * precision) as f32,
+            ),
+            None => geom::point(x as f32, y as f32),
+        }
+    }
+
     fn update_feature_indices(&mut self) {
         let next_index = self.buffer.indices.len();
         let indices = (next_index - self.current_index) as u32;
@@ -61,17 +124,33 @@ impl<I: std::ops::Add + From<lyon::tessellation::VertexId> + MaxIndex> ZeroTesse
         StrokeTessellator::new()
             .tessellate_path(
                 &path_builder.build(),
-                &StrokeOptions::tolerance(DEFAULT_TOLERANCE),
+                &self.stroke_options,
                 &mut BuffersBuilder::new(&mut self.buffer, VertexConstructor {}),
             )
             .unwrap(); // TODO: Remove unwrap
     }
 
     fn end(&mut self, close: bool) {
-        if self.path_open {
-            self.path_builder.borrow_mut().end(close);
-            self.path_open = false;
+        let mut points = std::mem::take(&mut self.path_points);
+
+        if let Some(tolerance) = self.simplification_tolerance {
+            let line = LineString::from_iter(points.iter().map(|point| (point.x, point.y)));
+            points = line
+                .simplify(&tolerance)
+                .into_inner()
+                .into_iter()
+                .map(|coord| geom::point(coord.x, coord.y))
+                .collect();
         }
+
+        let Some((first, rest)) = points.split_first() else { return; };
+
+        let mut path_builder = self.path_builder.borrow_mut();
+        path_builder.begin(*first);
+        for point in rest {
+            path_builder.line_to(*point);
+        }
+        path_builder.end(close);
     }
 
     fn tessellate_fill(&mut self) {
@@ -93,17 +172,13 @@ impl<I: std::ops::Add + From<lyon::tessellation::VertexId> + MaxIndex> GeomProce
     fn xy(&mut self, x: f64, y: f64, _idx: usize) -> GeoResult<()> {
         // log::info!("xy");
 
+        let point = self.snap(x, y);
+
         if self.is_point {
             // log::info!("point");
-        } else if !self.path_open {
-            self.path_builder
-                .borrow_mut()
-                .begin(geom::point(x as f32, y as f32));
-            self.path_open = true;
-        } else {
-            self.path_builder
-                .borrow_mut()
-                .line_to(geom::point(x as f32, y as f32));
+        } else if self.path_points.last() != Some(&point) {
+            // Snapping can collapse consecutive coordinates
+            self.path_points.push(point);
         }
         Ok(())
     }
@@ -0,0 +1,92 @@
+use std::time::Duration;
+
+/// The maximum amount of samples which are kept by a [`TimingHistogram`].
+const MAX_SAMPLES: usize = 1024;
+
+/// Collects durations in order to summarize them. Only the most recent samples are kept, so the
+/// memory usage is bounded.
+#[derive(Debug, Clone, Default)]
+pub struct TimingHistogram {
+    samples: Vec<Duration>,
+    count: u64,
+}
+
+impl TimingHistogram {
+    pub const fn new() -> Self {
+        Self {
+            samples: Vec::new(),
+            count: 0,
+        }
+    }
+
+    pub fn record(&mut self, duration: Duration) {
+        // Once full, the oldest sample is overwritten
+        if self.samples.len() < MAX_SAMPLES {
+            self.samples.push(duration);
+        } else {
+            self.samples[(self.count % MAX_SAMPLES as u64) as usize] = duration;
+        }
+        self.count += 1;
+    }
+
+    pub fn reset(&mut self) {
+        self.samples.clear();
+        self.count = 0;
+    }
+
+    pub fn summary(&self) -> TimingSummary {
+        let mut sorted = self.samples.clone();
+        sorted.sort();
+
+        let percentile = |p: f64| {
+            if sorted.is_empty() {
+                return Duration::ZERO;
+            }
+            let index = ((sorted.len() - 1) as f64 * p).round() as usize;
+            sorted[index]
+        };
+
+        TimingSummary {
+            count: self.count,
+            p50: percentile(0.5),
+            p95: percentile(0.95),
+            max: sorted.last().copied().unwrap_or_default(),
+        }
+    }
+}
+
+/// A summary of the durations in a [`TimingHistogram`]. The percentiles are calculated from the
+/// most recent samples, while `count` includes all recorded samples.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TimingSummary {
+    pub count: u64,
+    pub p50: Duration,
+    pub p95: Duration,
+    pub max: Duration,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{TimingHistogram, TimingSummary};
+
+    #[test]
+    fn test_summary() {
+        let mut histogram = TimingHistogram::new();
+        assert_eq!(histogram.summary(), TimingSummary::default());
+
+        for millis in 1..=100 {
+            histogram.record(Duration::from_millis(millis));
+        }
+
+        let summary = histogram.summary();
+        assert_eq!(summary.count, 100);
+        assert_eq!(summary.p50, Duration::from_millis(51));
+        assert_eq!(summary.p95, Duration::from_millis(95));
+        assert_eq!(summary.max, Duration::from_millis(100));
+
+        histogram.reset();
+        assert_eq!(histogram.summary(), TimingSummary::default());
+    }
+}
@@ -6,6 +6,7 @@ pub use fps_meter::FPSMeter;
 
 mod fps_meter;
 pub mod grid;
+pub mod histogram;
 pub mod label;
 pub mod math;
 
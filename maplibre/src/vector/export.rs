@@ -0,0 +1,142 @@
+//! Exports the tessellated geometry of tiles, such that meshes can be inspected in external tools.
+
+use std::fmt::Write;
+
+use crate::{
+    coords::WorldTileCoords,
+    tcs::world::World,
+    vector::{VectorLayerData, VectorLayersDataComponent},
+};
+
+impl World {
+    /// Serializes the tessellated geometry of the vector tile at `coords` in the Wavefront OBJ
+    /// format. Returns `None` if the tile has no vector data.
+    ///
+    /// Each available layer becomes an object and each of its features a group, which is named
+    /// after the id of the feature. Positions are in tile coordinates with a `z` of zero. Lines
+    /// are extruded in the vertex shader along the exported normals, so their triangles collapse
+    /// onto the center lines.
+    pub fn export_tile_obj(&self, coords: WorldTileCoords) -> Option<Vec<u8>> {
+        let component = self.tiles.query::<&VectorLayersDataComponent>(coords)?;
+        Some(write_obj(coords, &component.layers).into_bytes())
+    }
+}
+
+fn write_obj(coords: WorldTileCoords, layers: &[VectorLayerData]) -> String {
+    let mut obj = String::new();
+    // Writing into a String does not fail
+    let _ = writeln!(obj, "# tile {coords}");
+
+    // Indices of OBJ files start at 1 and are shared by all objects
+    let mut vertex_offset = 1;
+    for layer in layers {
+        let VectorLayerData::Available(layer) = layer else { continue; };
+        let vertices = &layer.buffer.buffer.vertices;
+        // Skip the indices which pad the buffer
+        let indices = &layer.buffer.buffer.indices[..layer.buffer.usable_indices as usize];
+
+        let _ = writeln!(obj, "o {}", layer.source_layer);
+        for vertex in vertices {
+            let [x, y] = vertex.position;
+            let [normal_x, normal_y] = vertex.normal;
+            let _ = writeln!(obj, "v {x} {y} 0");
+            let _ = writeln!(obj, "vn {normal_x} {normal_y} 0");
+        }
+
+        let mut triangles = indices
+            .chunks_exact(3)
+            .map(|triangle| [0, 1, 2].map(|i| triangle[i] + vertex_offset));
+        for (feature_id, index_count) in layer.feature_ids.iter().zip(&layer.feature_indices) {
+            let _ = writeln!(obj, "g feature_{feature_id}");
+            for [a, b, c] in triangles.by_ref().take(*index_count as usize / 3) {
+                let _ = writeln!(obj, "f {a}//{a} {b}//{b} {c}//{c}");
+            }
+        }
+
+        vertex_offset += vertices.len() as u32;
+    }
+
+    obj
+}
+
+#[cfg(test)]
+mod tests {
+    use geozero::mvt::{tile, Message, Tile};
+
+    use crate::{
+        coords::{WorldTileCoords, ZoomLevel},
+        io::source_type::{SourceType, TessellateSource},
+        style::Style,
+        tcs::world::World,
+        vector::{VectorLayerData, VectorLayersDataComponent},
+    };
+
+    #[test]
+    fn test_export_tile_obj() {
+        let layer = tile::Layer {
+            version: 2,
+            name: "water".to_string(),
+            features: vec![tile::Feature {
+                id: Some(7),
+                tags: vec![],
+                r#type: Some(tile::GeomType::Polygon as i32),
+                // A square covering the whole extent of 4096
+                geometry: vec![9, 0, 0, 26, 8192, 0, 0, 8192, 8191, 0, 15],
+            }],
+            keys: vec![],
+            values: vec![],
+            extent: Some(4096),
+        };
+        let data = Tile {
+            layers: vec![layer],
+        }
+        .encode_to_vec();
+
+        let mut world = World::default();
+        let coords = WorldTileCoords::from((0, 0, ZoomLevel::default()));
+        assert!(world.export_tile_obj(coords).is_none());
+
+        world
+            .insert_tile(
+                &Style::default(),
+                None,
+                coords,
+                &SourceType::Tessellate(TessellateSource::default()),
+                &data,
+            )
+            .unwrap();
+
+        let obj = String::from_utf8(world.export_tile_obj(coords).unwrap()).unwrap();
+        let lines = obj.lines().collect::<Vec<_>>();
+        let count = |prefix: &str| lines.iter().filter(|line| line.starts_with(prefix)).count();
+
+        let component = world
+            .tiles
+            .query::<&VectorLayersDataComponent>(coords)
+            .unwrap();
+        let VectorLayerData::Available(layer) = &component.layers[0] else { panic!() };
+
+        assert_eq!(count("o water"), 1);
+        assert_eq!(count("g feature_7"), 1);
+        assert_eq!(count("v "), layer.buffer.buffer.vertices.len());
+        assert_eq!(count("f "), layer.buffer.usable_indices as usize / 3);
+        assert!(count("f ") >= 2);
+
+        // Every face refers to an exported vertex
+        let vertex_count = count("v ");
+        for face in lines.iter().filter(|line| line.starts_with("f ")) {
+            for vertex in face.split_whitespace().skip(1) {
+                let index = vertex.split("//").next().unwrap().parse::<usize>().unwrap();
+                assert!((1..=vertex_count).contains(&index), "{face}");
+            }
+        }
+
+        // The square spans the whole extent
+        let xs = lines
+            .iter()
+            .filter_map(|line| line.strip_prefix("v "))
+            .map(|vertex| vertex.split(' ').next().unwrap().parse::<f32>().unwrap())
+            .collect::<Vec<_>>();
+        assert!(xs.contains(&0.0) && xs.contains(&4096.0), "{xs:?}");
+    }
+}
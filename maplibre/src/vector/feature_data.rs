@@ -0,0 +1,53 @@
+//! Custom numeric data which the application attaches to features at runtime.
+
+use std::collections::HashMap;
+
+use geozero::mvt::tile::Layer;
+
+/// Numeric values which are attached to features by the application, e.g. to color features by
+/// data which is not part of the tiles. The values are passed to the vertex shader of the features,
+/// which darkens each feature by its value, from `0.0` which keeps the color of the style to `1.0`
+/// which draws the feature black.
+///
+/// Values are identified by the name of the style source and the id of the feature. Features
+/// without a value receive `0.0`.
+#[derive(Default)]
+pub struct FeatureData {
+    values: HashMap<String, HashMap<u64, f32>>,
+    /// Whether values changed since the features have been uploaded the last time.
+    changed: bool,
+}
+
+impl FeatureData {
+    pub fn set(&mut self, source: &str, feature_id: u64, value: f32) {
+        self.values
+            .entry(source.to_string())
+            .or_default()
+            .insert(feature_id, value);
+        self.changed = true;
+    }
+
+    pub fn remove(&mut self, source: &str, feature_id: u64) -> Option<f32> {
+        let value = self.values.get_mut(source)?.remove(&feature_id);
+        self.changed |= value.is_some();
+        value
+    }
+
+    pub fn get(&self, source: &str, feature_id: u64) -> Option<f32> {
+        self.values.get(source)?.get(&feature_id).copied()
+    }
+
+    /// Returns whether values changed since the last call and resets the flag.
+    pub(crate) fn take_changed(&mut self) -> bool {
+        std::mem::take(&mut self.changed)
+    }
+}
+
+/// The ids of the features of `layer` in order. Features without an id have the id `0`.
+pub(crate) fn feature_ids(layer: &Layer) -> Vec<u64> {
+    layer
+        .features
+        .iter()
+        .map(|feature| feature.id.unwrap_or_default())
+        .collect()
+}
@@ -0,0 +1,98 @@
+//! Features which are drawn on top of everything else, e.g. to highlight a selected feature.
+
+use std::{
+    collections::{HashMap, HashSet},
+    ops::Range,
+};
+
+/// Features which are drawn again after all layers of the style, such that they are not hidden by
+/// adjacent features of their layer or by other layers. Only overlays and markers are drawn above
+/// them.
+///
+/// Features are identified by the name of the style source and their id, like in
+/// [`FeatureData`](crate::vector::FeatureData). Features without an id can not be brought to the
+/// front.
+#[derive(Default)]
+pub struct FrontFeatures {
+    features: HashMap<String, HashSet<u64>>,
+}
+
+impl FrontFeatures {
+    /// Returns whether the feature has not been in front before.
+    pub fn insert(&mut self, source: &str, feature_id: u64) -> bool {
+        self.features
+            .entry(source.to_string())
+            .or_default()
+            .insert(feature_id)
+    }
+
+    /// Returns whether the feature has been in front.
+    pub fn remove(&mut self, source: &str, feature_id: u64) -> bool {
+        self.features
+            .get_mut(source)
+            .map_or(false, |features| features.remove(&feature_id))
+    }
+
+    pub fn contains(&self, source: &str, feature_id: u64) -> bool {
+        self.features
+            .get(source)
+            .map_or(false, |features| features.contains(&feature_id))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.features.values().all(HashSet::is_empty)
+    }
+
+    /// The ranges of the indices of the features in front within a layer of the `source`. The
+    /// features of the layer have the ids `feature_ids` and consist of `feature_indices` indices
+    /// each, see [`AvailableVectorLayerData`](crate::vector::AvailableVectorLayerData).
+    pub(crate) fn index_ranges(
+        &self,
+        source: &str,
+        feature_ids: &[u64],
+        feature_indices: &[u32],
+    ) -> Vec<Range<u32>> {
+        let Some(features) = self.features.get(source) else { return Vec::new(); };
+
+        let mut ranges = Vec::new();
+        let mut start = 0;
+        for (feature_id, indices) in feature_ids.iter().zip(feature_indices) {
+            let end = start + indices;
+            if *feature_id != 0 && start < end && features.contains(feature_id) {
+                ranges.push(start..end);
+            }
+            start = end;
+        }
+        ranges
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FrontFeatures;
+
+    #[test]
+    fn test_index_ranges() {
+        let mut front = FrontFeatures::default();
+        assert!(front.insert("openmaptiles", 7));
+        assert!(!front.insert("openmaptiles", 7));
+        assert!(front.insert("openmaptiles", 0));
+
+        let feature_ids = [3, 7, 0, 7];
+        let feature_indices = [6, 3, 12, 9];
+
+        assert_eq!(
+            front.index_ranges("openmaptiles", &feature_ids, &feature_indices),
+            vec![6..9, 21..30]
+        );
+        assert!(front
+            .index_ranges("other", &feature_ids, &feature_indices)
+            .is_empty());
+
+        assert!(front.remove("openmaptiles", 7));
+        assert!(!front.contains("openmaptiles", 7));
+        assert!(front
+            .index_ranges("openmaptiles", &feature_ids, &feature_indices)
+            .is_empty());
+    }
+}
@@ -0,0 +1,62 @@
+//! Timing metrics of the vector tile processing.
+//!
+//! The metrics are collected per process. On the web, tiles are processed within web workers,
+//! which means that the metrics have to be read from within the worker.
+
+use std::{sync::Mutex, time::Duration};
+
+use crate::util::histogram::{TimingHistogram, TimingSummary};
+
+static METRICS: Mutex<ProcessingTimings> = Mutex::new(ProcessingTimings::new());
+
+struct ProcessingTimings {
+    decode: TimingHistogram,
+    tessellate: TimingHistogram,
+    total: TimingHistogram,
+}
+
+impl ProcessingTimings {
+    const fn new() -> Self {
+        Self {
+            decode: TimingHistogram::new(),
+            tessellate: TimingHistogram::new(),
+            total: TimingHistogram::new(),
+        }
+    }
+}
+
+/// A snapshot of the timings of processed vector tiles.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ProcessingMetrics {
+    /// Time spent decoding a tile
+    pub decode: TimingSummary,
+    /// Time spent tessellating all layers of a tile
+    pub tessellate: TimingSummary,
+    /// Time spent processing a tile
+    pub total: TimingSummary,
+}
+
+/// Returns a snapshot of the timings of all vector tiles which have been processed since the last
+/// [`reset_metrics`].
+pub fn metrics() -> ProcessingMetrics {
+    let timings = METRICS.lock().unwrap_or_else(|e| e.into_inner());
+    ProcessingMetrics {
+        decode: timings.decode.summary(),
+        tessellate: timings.tessellate.summary(),
+        total: timings.total.summary(),
+    }
+}
+
+pub fn reset_metrics() {
+    let mut timings = METRICS.lock().unwrap_or_else(|e| e.into_inner());
+    timings.decode.reset();
+    timings.tessellate.reset();
+    timings.total.reset();
+}
+
+pub(crate) fn record_tile(decode: Duration, tessellate: Duration, total: Duration) {
+    let mut timings = METRICS.lock().unwrap_or_else(|e| e.into_inner());
+    timings.decode.record(decode);
+    timings.tessellate.record(tessellate);
+    timings.total.record(total);
+}
@@ -1,4 +1,6 @@
-use std::{marker::PhantomData, ops::Deref, rc::Rc};
+use std::{collections::HashMap, marker::PhantomData, mem, ops::Deref, rc::Rc};
+
+use instant::Instant;
 
 use crate::{
     coords::WorldTileCoords,
@@ -7,31 +9,53 @@ use crate::{
     plugin::Plugin,
     render::{
         eventually::Eventually,
+        resource::StencilMode,
         shaders::{ShaderFeatureStyle, ShaderLayerMetadata},
         tile_view_pattern::{HasTile, ViewTileSources},
         RenderStageLabel, ShaderVertex,
     },
     schedule::Schedule,
-    tcs::{system::SystemContainer, tiles::TileComponent, world::World},
-    tessellation::{IndexDataType, OverAlignedVertexBuffer},
+    style::layer::{BlendMode, StyleLayer},
+    tcs::{
+        system::SystemContainer,
+        tiles::{TileComponent, TileState},
+        world::World,
+    },
+    tessellation::{pool::VERTEX_BUFFER_POOL, IndexDataType, OverAlignedVertexBuffer},
     vector::{
-        populate_world_system::PopulateWorldSystem, queue_system::queue_system,
-        request_system::RequestSystem, resource::BufferPool, resource_system::resource_system,
-        upload_system::upload_system,
+        pattern::SpriteTexture, populate_world_system::PopulateWorldSystem,
+        queue_system::queue_system, request_system::RequestSystem, resource::BufferPool,
+        resource_system::resource_system, upload_system::upload_system,
     },
 };
 
+mod export;
+mod feature_data;
+mod front_features;
+pub mod metrics;
+mod mvt_version;
+mod pattern;
 mod populate_world_system;
 mod process_vector;
 mod queue_system;
 mod render_commands;
+mod reproject;
 mod request_system;
 mod resource;
 mod resource_system;
 mod transferables;
 mod upload_system;
 
+pub(crate) use feature_data::feature_ids;
+pub use feature_data::FeatureData;
+pub use front_features::FrontFeatures;
+pub use mvt_version::ExtentScale;
+pub use pattern::Sprite;
 pub use process_vector::*;
+pub(crate) use populate_world_system::insert_vector_tile;
+pub(crate) use request_system::{
+    fetch_covering_tiles, requested_source_layers, source_crs, vector_source_type,
+};
 pub use transferables::{
     DefaultVectorTransferables, LayerIndexed, LayerMissing, LayerTessellated, TileTessellated,
     VectorTransferables,
@@ -39,9 +63,61 @@ pub use transferables::{
 
 use crate::render::graph::RenderGraph;
 
-struct VectorPipeline(wgpu::RenderPipeline);
+/// Identifies the variant of the vector pipeline which draws a layer.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+struct VectorPipelineKey {
+    blend_mode: Option<BlendMode>,
+    /// Whether the features are filled with a pattern of the [`Sprite`]
+    pattern: bool,
+    /// Whether overlapping features are blended once, see [`StyleLayer::single_blend`]
+    stencil_mode: StencilMode,
+    /// Whether the features are drawn on top of all layers, see [`FrontFeatures`]
+    front: bool,
+}
+
+impl VectorPipelineKey {
+    fn of(style_layer: &StyleLayer) -> Self {
+        Self {
+            blend_mode: style_layer.blend_mode,
+            pattern: style_layer
+                .paint
+                .as_ref()
+                .and_then(|paint| paint.get_pattern())
+                .is_some(),
+            stencil_mode: if style_layer.single_blend.unwrap_or(false) {
+                StencilMode::Once
+            } else {
+                StencilMode::Masked
+            },
+            front: false,
+        }
+    }
+
+    /// The key of the pipeline which draws features of a layer in front of all layers. The depth
+    /// of the layers is ignored, as the features keep the depth of their layer.
+    fn front(self) -> Self {
+        Self {
+            stencil_mode: StencilMode::Masked,
+            front: true,
+            ..self
+        }
+    }
+
+    /// The key of the pipeline which removes the marks of the pipeline of this key from the
+    /// stencil. The geometry of a layer is drawn with it after it has been drawn with
+    /// [`StencilMode::Once`].
+    fn unmark(self) -> Self {
+        Self {
+            stencil_mode: StencilMode::Unmark,
+            ..self
+        }
+    }
+}
+
+/// The pipelines of the variants which are used by the style.
+struct VectorPipeline(HashMap<VectorPipelineKey, wgpu::RenderPipeline>);
 impl Deref for VectorPipeline {
-    type Target = wgpu::RenderPipeline;
+    type Target = HashMap<VectorPipelineKey, wgpu::RenderPipeline>;
 
     fn deref(&self) -> &Self::Target {
         &self.0
@@ -75,7 +151,12 @@ impl HasTile for VectorTilesDone {
             .tiles
             .query::<&VectorLayersDataComponent>(coords) else { return false; };
 
+        // Tiles which are still loading are drawn as soon as their first layers are available
         vector_layers_indices.done
+            || vector_layers_indices
+                .layers
+                .iter()
+                .any(|layer| matches!(layer, VectorLayerData::Available(_)))
     }
 }
 
@@ -91,6 +172,10 @@ impl<E: Environment, T: VectorTransferables> Plugin<E> for VectorPlugin<T> {
 
         resources.insert(Eventually::<VectorBufferPool>::Uninitialized);
         resources.insert(Eventually::<VectorPipeline>::Uninitialized);
+        resources.get_or_init_mut::<FeatureData>();
+        resources.get_or_init_mut::<FrontFeatures>();
+        resources.get_or_init_mut::<Sprite>();
+        resources.insert(Eventually::<SpriteTexture>::Uninitialized);
 
         resources
             .get_or_init_mut::<ViewTileSources>()
@@ -114,15 +199,65 @@ impl<E: Environment, T: VectorTransferables> Plugin<E> for VectorPlugin<T> {
 
 pub struct AvailableVectorLayerData {
     pub coords: WorldTileCoords,
+    /// The vector source of the layer, `None` for the default source
+    pub source: Option<String>,
     pub source_layer: String,
     pub buffer: OverAlignedVertexBuffer<ShaderVertex, IndexDataType>,
     /// Holds for each feature the count of indices.
     pub feature_indices: Vec<u32>,
+    /// Holds for each feature its id. Features without an id have the id `0`.
+    pub feature_ids: Vec<u64>,
+}
+
+/// The reason why a requested layer is not available for a tile.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum LayerMissingReason {
+    /// The tile does not contain the requested layer.
+    Missing,
+    /// The layer exists in the tile, but tessellating it failed.
+    TessellationFailed,
+    /// The layer exists in the tile, but has no features.
+    Empty,
+    /// The tile could not be fetched from its source.
+    FetchFailed,
+    /// The tile exceeds the maximum tile size and has not been decoded.
+    TooLarge,
+    /// The tile has been fetched, but is not a valid vector tile.
+    DecodeFailed,
+    /// The deadline of the request passed before the layer has been tessellated. The tile is
+    /// evicted, such that it is requested again once it is in view.
+    DeadlineExceeded,
+}
+
+impl LayerMissingReason {
+    /// Whether requesting the layer again could yield a different result.
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            LayerMissingReason::TessellationFailed
+                | LayerMissingReason::FetchFailed
+                | LayerMissingReason::DeadlineExceeded
+        )
+    }
+
+    /// Whether the layer is missing because loading it failed, rather than because it does not
+    /// exist.
+    pub fn is_error(&self) -> bool {
+        !matches!(
+            self,
+            LayerMissingReason::Missing
+                | LayerMissingReason::Empty
+                | LayerMissingReason::DeadlineExceeded
+        )
+    }
 }
 
 pub struct MissingVectorLayerData {
     pub coords: WorldTileCoords,
+    /// The vector source of the layer, `None` for the default source
+    pub source: Option<String>,
     pub source_layer: String,
+    pub reason: LayerMissingReason,
 }
 
 pub enum VectorLayerData {
@@ -130,10 +265,161 @@ pub enum VectorLayerData {
     Missing(MissingVectorLayerData),
 }
 
+impl VectorLayerData {
+    pub fn source(&self) -> Option<&str> {
+        match self {
+            VectorLayerData::Available(data) => data.source.as_deref(),
+            VectorLayerData::Missing(data) => data.source.as_deref(),
+        }
+    }
+
+    pub fn source_layer(&self) -> &str {
+        match self {
+            VectorLayerData::Available(data) => &data.source_layer,
+            VectorLayerData::Missing(data) => &data.source_layer,
+        }
+    }
+
+    /// Whether this is the data of the `source_layer` of the `source`. Source-layers of different
+    /// sources can have the same name.
+    pub fn is_layer(&self, source: Option<&str>, source_layer: &str) -> bool {
+        self.source() == source && self.source_layer() == source_layer
+    }
+
+    /// Whether the layer is missing because loading it failed, see
+    /// [`LayerMissingReason::is_error`].
+    pub fn is_error(&self) -> bool {
+        match self {
+            VectorLayerData::Missing(data) => data.reason.is_error(),
+            VectorLayerData::Available(_) => false,
+        }
+    }
+}
+
 #[derive(Default)]
 pub struct VectorLayersDataComponent {
     pub done: bool,
     pub layers: Vec<VectorLayerData>,
+    /// Time at which the layers of each vector source of this tile have been requested the last
+    /// time. Sources are refreshed independently of each other.
+    pub requested_at: HashMap<Option<String>, Instant>,
+    /// Layers of a refresh which is in progress. They replace the layers of the refreshed sources
+    /// once the refresh is done, such that the old layers are rendered until then.
+    pub pending_layers: Option<Vec<VectorLayerData>>,
+    /// Whether `layers` have been replaced by a refresh and need to be uploaded again.
+    pub needs_upload: bool,
+    /// The amount of requests of the tile which have not been finished, one for each vector
+    /// source. The tile is done once the last request has finished.
+    pub pending_requests: usize,
+}
+
+impl VectorLayersDataComponent {
+    /// Adds a layer to the tile. If a refresh is in progress, the layer is added to the pending
+    /// layers.
+    pub fn push_layer(&mut self, layer: VectorLayerData) {
+        if let Some(pending_layers) = &mut self.pending_layers {
+            pending_layers.push(layer);
+        } else {
+            self.layers.push(layer);
+        }
+    }
+
+    /// Whether the `source_layer` of the `source` of this tile has been processed, regardless of
+    /// whether it is available or missing. All layers are resolved once the tile is done.
+    pub fn is_layer_resolved(&self, source: Option<&str>, source_layer: &str) -> bool {
+        self.done
+            || self
+                .layers
+                .iter()
+                .any(|layer| layer.is_layer(source, source_layer))
+    }
+}
+
+impl TileComponent for VectorLayersDataComponent {
+    fn tile_state(&self) -> Option<TileState> {
+        if !self.done {
+            return Some(TileState::Loading);
+        }
+
+        let available = self
+            .layers
+            .iter()
+            .filter(|layer| matches!(layer, VectorLayerData::Available(_)))
+            .count();
+
+        Some(if available == self.layers.len() {
+            TileState::Loaded
+        } else if available == 0 {
+            TileState::Unavailable
+        } else {
+            TileState::Partial
+        })
+    }
+
+    fn has_errors(&self) -> bool {
+        self.layers.iter().any(VectorLayerData::is_error)
+    }
+
+    fn byte_size(&self) -> usize {
+        let host_bytes: usize = self
+            .layers
+            .iter()
+            .chain(self.pending_layers.iter().flatten())
+            .map(|layer| match layer {
+                VectorLayerData::Available(data) => {
+                    data.buffer.buffer.vertices.len() * mem::size_of::<ShaderVertex>()
+                        + data.buffer.buffer.indices.len() * mem::size_of::<IndexDataType>()
+                        + data.feature_indices.len() * mem::size_of::<u32>()
+                        + data.feature_ids.len() * mem::size_of::<u64>()
+                }
+                VectorLayerData::Missing(_) => 0,
+            })
+            .sum();
+
+        // The buffers are uploaded to the GPU in the same size
+        2 * host_bytes
+    }
+
+    fn release(&mut self) {
+        recycle_layers(mem::take(&mut self.layers));
+        recycle_layers(self.pending_layers.take().unwrap_or_default());
+    }
 }
 
-impl TileComponent for VectorLayersDataComponent {}
+/// Returns the buffers of `layers` which have been replaced or evicted to the pool, such that the
+/// tessellation of the next tiles can reuse them.
+pub(crate) fn recycle_layers(layers: Vec<VectorLayerData>) {
+    for layer in layers {
+        if let VectorLayerData::Available(data) = layer {
+            VERTEX_BUFFER_POOL.give(data.buffer.buffer);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        LayerMissingReason, MissingVectorLayerData, VectorLayerData, VectorLayersDataComponent,
+    };
+    use crate::coords::ZoomLevel;
+
+    #[test]
+    fn test_layer_resolved() {
+        let mut component = VectorLayersDataComponent::default();
+        assert!(!component.is_layer_resolved(None, "water"));
+
+        component.push_layer(VectorLayerData::Missing(MissingVectorLayerData {
+            coords: (0, 0, ZoomLevel::default()).into(),
+            source: None,
+            source_layer: "water".to_string(),
+            reason: LayerMissingReason::Empty,
+        }));
+        assert!(component.is_layer_resolved(None, "water"));
+        assert!(!component.is_layer_resolved(None, "roads"));
+        // Another source can have a source-layer with the same name
+        assert!(!component.is_layer_resolved(Some("lakes"), "water"));
+
+        component.done = true;
+        assert!(component.is_layer_resolved(None, "roads"));
+    }
+}
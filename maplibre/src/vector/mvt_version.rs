@@ -0,0 +1,367 @@
+//! Normalizes layers of vector tiles which are encoded with version 1 of the
+//! [MVT specification](https://github.com/mapbox/vector-tile-spec), such that they can be
+//! processed like layers of version 2.
+//!
+//! Version 1 does not specify the winding order of polygon rings. Version 2 requires exterior rings
+//! to have a positive area and interior rings to have a negative area. Like other renderers, the
+//! first ring of a polygon of version 1 is considered to be an exterior ring and all rings with the
+//! same winding order start a new polygon. The extent of a layer defaults to 4096 in both versions,
+//! but tessellation assumes [`EXTENT`], so the coordinates of layers with other extents are scaled.
+
+use geozero::mvt::tile;
+
+use crate::coords::EXTENT;
+
+const MOVE_TO: u32 = 1;
+const LINE_TO: u32 = 2;
+const CLOSE_PATH: u32 = 7;
+
+/// The extent of layers which do not specify it.
+const DEFAULT_EXTENT: u32 = 4096;
+
+/// A decoded command of a feature geometry with absolute coordinates.
+#[derive(Clone, Debug, PartialEq)]
+enum Command {
+    MoveTo(Vec<(i64, i64)>),
+    LineTo(Vec<(i64, i64)>),
+    ClosePath,
+}
+
+/// Rewrites the geometries of the `layer` such that they follow version 2 of the specification and
+/// use the [`EXTENT`] of the tessellation. Geometries which can not be decoded are left untouched.
+pub(crate) fn normalize_layer(layer: &mut tile::Layer) {
+    let scale = ExtentScale::new(layer.extent.unwrap_or(DEFAULT_EXTENT));
+
+    let classify_rings = match layer.version {
+        1 => true,
+        2 => false,
+        version => {
+            log::warn!(
+                "layer {} has unknown version {version}, processing it as version 2",
+                layer.name
+            );
+            false
+        }
+    };
+
+    if scale.is_none() && !classify_rings {
+        return;
+    }
+
+    for feature in &mut layer.features {
+        let Some(mut commands) = decode(&feature.geometry) else {
+            log::warn!("geometry of a feature in layer {} is invalid", layer.name);
+            continue;
+        };
+
+        if let Some(scale) = scale {
+            for command in &mut commands {
+                if let Command::MoveTo(points) | Command::LineTo(points) = command {
+                    for point in points {
+                        *point = scale.apply(*point);
+                    }
+                }
+            }
+        }
+
+        if classify_rings && feature.r#type == Some(tile::GeomType::Polygon as i32) {
+            orient_rings(&mut commands);
+        }
+
+        feature.geometry = encode(&commands);
+    }
+
+    if scale.is_some() {
+        layer.extent = Some(EXTENT as u32);
+    }
+    layer.version = 2;
+}
+
+/// Replaces every point of the geometries of the `layer` by the result of `map`, e.g. to reproject
+/// them. Geometries which can not be decoded are left untouched.
+pub(crate) fn map_points(layer: &mut tile::Layer, map: impl Fn((i64, i64)) -> (i64, i64)) {
+    for feature in &mut layer.features {
+        let Some(mut commands) = decode(&feature.geometry) else {
+            log::warn!("geometry of a feature in layer {} is invalid", layer.name);
+            continue;
+        };
+
+        for command in &mut commands {
+            if let Command::MoveTo(points) | Command::LineTo(points) = command {
+                for point in points {
+                    *point = map(*point);
+                }
+            }
+        }
+
+        feature.geometry = encode(&commands);
+    }
+}
+
+/// Maps the coordinates of a layer with another extent to [`EXTENT`]. Coordinates are rounded to
+/// the nearest integer, with halves rounded away from zero.
+///
+/// Most extents are powers of two, so the integer paths apply to them. They avoid converting each
+/// coordinate to floating point and produce exactly the same coordinates as [`ExtentScale::Float`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExtentScale {
+    /// The extent divides [`EXTENT`], so coordinates are multiplied by an integer factor.
+    Multiply(i64),
+    /// The extent is [`EXTENT`] times a power of two, so coordinates are divided by shifting.
+    Shift(u32),
+    /// Coordinates of any other extent are scaled in floating point.
+    Float(f64),
+}
+
+impl ExtentScale {
+    /// The scale for layers of the `extent`. Returns `None` if the coordinates are already in
+    /// [`EXTENT`] units or the extent is invalid.
+    pub fn new(extent: u32) -> Option<Self> {
+        let target = EXTENT as u32;
+        if extent == target || extent == 0 {
+            return None;
+        }
+
+        Some(if target % extent == 0 {
+            ExtentScale::Multiply(i64::from(target / extent))
+        } else if extent % target == 0 && (extent / target).is_power_of_two() {
+            ExtentScale::Shift((extent / target).trailing_zeros())
+        } else {
+            ExtentScale::float(extent)
+        })
+    }
+
+    /// The floating point scale for layers of the `extent`, regardless of whether an integer path
+    /// applies.
+    pub fn float(extent: u32) -> Self {
+        ExtentScale::Float(EXTENT / f64::from(extent))
+    }
+
+    pub fn apply(&self, (x, y): (i64, i64)) -> (i64, i64) {
+        match *self {
+            ExtentScale::Multiply(factor) => (x * factor, y * factor),
+            ExtentScale::Shift(shift) => {
+                let half = 1 << (shift - 1);
+                let divide = |value: i64| {
+                    if value >= 0 {
+                        (value + half) >> shift
+                    } else {
+                        -((-value + half) >> shift)
+                    }
+                };
+                (divide(x), divide(y))
+            }
+            ExtentScale::Float(scale) => (
+                (x as f64 * scale).round() as i64,
+                (y as f64 * scale).round() as i64,
+            ),
+        }
+    }
+}
+
+/// Reverses all rings of a polygon of version 1 if its first ring is not wound like an exterior
+/// ring of version 2. Rings are reversed such that their first point stays the same.
+fn orient_rings(commands: &mut [Command]) {
+    let is_ring = |ring: &[Command]| {
+        matches!(
+            ring,
+            [Command::MoveTo(start), Command::LineTo(_), Command::ClosePath] if start.len() == 1
+        )
+    };
+
+    if commands.len() % 3 != 0 || !commands.chunks(3).all(is_ring) {
+        return;
+    }
+
+    let ring_area = |ring: &[Command]| match ring {
+        [Command::MoveTo(start), Command::LineTo(points), _] => signed_area(start[0], points),
+        _ => 0,
+    };
+
+    let Some(first_area) = commands
+        .chunks(3)
+        .map(ring_area)
+        .find(|area| *area != 0) else { return; };
+
+    if first_area > 0 {
+        return;
+    }
+
+    for ring in commands.chunks_mut(3) {
+        if let Command::LineTo(points) = &mut ring[1] {
+            points.reverse();
+        }
+    }
+}
+
+/// Twice the area of the ring which starts at `start`, positive for rings which are wound clockwise
+/// in tile coordinates.
+fn signed_area(start: (i64, i64), points: &[(i64, i64)]) -> i64 {
+    let ring: Vec<_> = std::iter::once(start)
+        .chain(points.iter().copied())
+        .collect();
+    ring.iter()
+        .zip(ring.iter().cycle().skip(1))
+        .map(|((x0, y0), (x1, y1))| x0 * y1 - x1 * y0)
+        .sum()
+}
+
+fn decode(geometry: &[u32]) -> Option<Vec<Command>> {
+    let mut commands = Vec::new();
+    let mut cursor = (0i64, 0i64);
+    let mut values = geometry.iter();
+
+    while let Some(command) = values.next() {
+        let (id, count) = (command & 0x7, command >> 3);
+
+        let mut read_points = || {
+            (0..count)
+                .map(|_| {
+                    let dx = zigzag_decode(*values.next()?);
+                    let dy = zigzag_decode(*values.next()?);
+                    cursor = (cursor.0 + dx, cursor.1 + dy);
+                    Some(cursor)
+                })
+                .collect::<Option<Vec<_>>>()
+        };
+
+        commands.push(match id {
+            MOVE_TO => Command::MoveTo(read_points()?),
+            LINE_TO => Command::LineTo(read_points()?),
+            CLOSE_PATH => Command::ClosePath,
+            _ => return None,
+        });
+    }
+
+    Some(commands)
+}
+
+fn encode(commands: &[Command]) -> Vec<u32> {
+    let mut geometry = Vec::new();
+    let mut cursor = (0i64, 0i64);
+
+    for command in commands {
+        let (id, points) = match command {
+            Command::MoveTo(points) => (MOVE_TO, points.as_slice()),
+            Command::LineTo(points) => (LINE_TO, points.as_slice()),
+            Command::ClosePath => (CLOSE_PATH, [].as_slice()),
+        };
+
+        let count = if id == CLOSE_PATH { 1 } else { points.len() };
+        geometry.push(id | (count as u32) << 3);
+
+        for point in points {
+            geometry.push(zigzag_encode(point.0 - cursor.0));
+            geometry.push(zigzag_encode(point.1 - cursor.1));
+            cursor = *point;
+        }
+    }
+
+    geometry
+}
+
+fn zigzag_decode(value: u32) -> i64 {
+    i64::from(value >> 1) ^ -i64::from(value & 1)
+}
+
+fn zigzag_encode(value: i64) -> u32 {
+    ((value << 1) ^ (value >> 63)) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use geozero::mvt::tile;
+
+    use super::{normalize_layer, ExtentScale};
+    use crate::{
+        coords::WorldTileCoords, render::ShaderVertex, tessellation::tessellator::Tessellators,
+    };
+
+    fn polygon_layer(version: u32, extent: u32, geometry: Vec<u32>) -> tile::Layer {
+        tile::Layer {
+            version,
+            name: "water".to_string(),
+            features: vec![tile::Feature {
+                id: Some(1),
+                tags: vec![],
+                r#type: Some(tile::GeomType::Polygon as i32),
+                geometry,
+            }],
+            keys: vec![],
+            values: vec![],
+            extent: Some(extent),
+        }
+    }
+
+    #[test]
+    fn test_v1_layer_matches_v2() {
+        // A square covering (0,0)-(10,10), wound clockwise as required by version 2, with a
+        // counter-clockwise hole covering (2,2)-(4,4)
+        let v2 = polygon_layer(
+            2,
+            4096,
+            vec![
+                9, 0, 0, 26, 20, 0, 0, 20, 19, 0, 15, 9, 4, 15, 26, 0, 4, 4, 0, 0, 3, 15,
+            ],
+        );
+
+        // The same polygon in version 1 with an extent of 8192, wound the other way around
+        let mut v1 = polygon_layer(
+            1,
+            8192,
+            vec![
+                9, 0, 0, 26, 0, 40, 40, 0, 0, 39, 15, 9, 31, 8, 26, 8, 0, 0, 8, 7, 0, 15,
+            ],
+        );
+
+        normalize_layer(&mut v1);
+        assert_eq!(v1, v2);
+
+        // Layers of version 2 are not changed
+        let mut unchanged = v2.clone();
+        normalize_layer(&mut unchanged);
+        assert_eq!(unchanged, v2);
+
+        let tessellators = Tessellators::default();
+        let (v1_buffer, _) = tessellators
+            .get("water")
+            .tessellate(&mut v1, WorldTileCoords::default())
+            .unwrap();
+        let (v2_buffer, _) = tessellators
+            .get("water")
+            .tessellate(&mut unchanged, WorldTileCoords::default())
+            .unwrap();
+        let positions = |vertices: &[ShaderVertex]| {
+            vertices
+                .iter()
+                .map(|vertex| vertex.position)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            positions(&v1_buffer.vertices),
+            positions(&v2_buffer.vertices)
+        );
+        assert_eq!(v1_buffer.indices, v2_buffer.indices);
+    }
+
+    #[test]
+    fn test_integer_extent_scale_matches_float() {
+        for extent in [256, 512, 1024, 2048, 8192, 16384, 32768] {
+            let scale = ExtentScale::new(extent).unwrap();
+            assert!(!matches!(scale, ExtentScale::Float(_)));
+
+            let float = ExtentScale::float(extent);
+            for x in -20000..20000 {
+                assert_eq!(
+                    scale.apply((x, -x)),
+                    float.apply((x, -x)),
+                    "extent {extent}"
+                );
+            }
+        }
+
+        assert_eq!(ExtentScale::new(4096), None);
+        assert_eq!(ExtentScale::new(3000), Some(ExtentScale::float(3000)));
+        assert_eq!(ExtentScale::new(12288), Some(ExtentScale::float(12288)));
+    }
+}
@@ -0,0 +1,199 @@
+//! Fills features with a pattern which is repeated from an icon of the sprite.
+
+use std::mem::size_of;
+
+use crate::{
+    coords::{WorldTileCoords, EXTENT, TILE_SIZE},
+    render::{
+        color_space::ColorSpace,
+        resource::Texture,
+        settings::Msaa,
+        shaders::{ShaderPattern, ShaderRasterMetadata},
+    },
+    style::sprite::SpriteAtlas,
+};
+
+/// Holds the sprite atlas of the style, from which the patterns of layers are sampled.
+#[derive(Default)]
+pub struct Sprite {
+    atlas: Option<SpriteAtlas>,
+    /// Whether the atlas changed since it has been uploaded the last time.
+    changed: bool,
+}
+
+impl Sprite {
+    pub fn set(&mut self, atlas: SpriteAtlas) {
+        self.atlas = Some(atlas);
+        self.changed = true;
+    }
+
+    pub fn atlas(&self) -> Option<&SpriteAtlas> {
+        self.atlas.as_ref()
+    }
+
+    /// Returns whether the atlas changed since the last call and resets the flag.
+    pub(crate) fn take_changed(&mut self) -> bool {
+        std::mem::take(&mut self.changed)
+    }
+}
+
+/// The sprite atlas on the GPU, bound such that it can be sampled by the pattern pipelines.
+pub struct SpriteTexture {
+    _texture: Texture,
+    _metadata: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+impl SpriteTexture {
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        layout: &wgpu::BindGroupLayout,
+        atlas: &SpriteAtlas,
+        color_space: ColorSpace,
+    ) -> Self {
+        let (width, height) = (atlas.width(), atlas.height());
+
+        let texture = Texture::new(
+            Some("sprite texture"),
+            device,
+            color_space.image_format(),
+            width,
+            height,
+            Msaa { samples: 1 },
+            wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        );
+
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                aspect: wgpu::TextureAspect::All,
+                texture: &texture.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+            },
+            atlas.pixels(),
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * width),
+                rows_per_image: Some(height),
+            },
+            texture.size,
+        );
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let metadata = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("sprite metadata buffer"),
+            size: size_of::<ShaderRasterMetadata>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        queue.write_buffer(
+            &metadata,
+            0,
+            bytemuck::cast_slice(&[ShaderRasterMetadata::new(1.0)]),
+        );
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: metadata.as_entire_binding(),
+                },
+            ],
+            label: Some("sprite bind group"),
+        });
+
+        Self {
+            _texture: texture,
+            _metadata: metadata,
+            bind_group,
+        }
+    }
+
+    pub fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+}
+
+/// Describes how the icon `name` of the `atlas` is repeated across the tile at `coords`. The
+/// pattern is sized in logical pixels of the zoom level of the tile and continues across tiles.
+pub(crate) fn pattern(
+    atlas: &SpriteAtlas,
+    name: &str,
+    coords: WorldTileCoords,
+) -> Option<ShaderPattern> {
+    let bounds = atlas.texture_bounds(name)?;
+    let (width, height) = atlas.icon(name)?.logical_size();
+
+    if width <= 0.0 || height <= 0.0 {
+        return None;
+    }
+
+    let (width, height) = (f64::from(width), f64::from(height));
+
+    // The origin is computed in double precision, such that patterns stay in place at high zoom
+    // levels
+    Some(ShaderPattern {
+        bounds,
+        origin: [
+            (f64::from(coords.x) * TILE_SIZE / width).rem_euclid(1.0) as f32,
+            (f64::from(coords.y) * TILE_SIZE / height).rem_euclid(1.0) as f32,
+        ],
+        scale: [
+            (TILE_SIZE / EXTENT / width) as f32,
+            (TILE_SIZE / EXTENT / height) as f32,
+        ],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::pattern;
+    use crate::{
+        coords::{WorldTileCoords, ZoomLevel},
+        style::sprite::SpriteAtlas,
+    };
+
+    #[test]
+    fn test_pattern_continues_across_tiles() {
+        let index = r#"{"hatch": {"x": 0, "y": 0, "width": 6, "height": 8, "pixelRatio": 2}}"#;
+        let atlas = SpriteAtlas::new(8, 8, vec![0; 8 * 8 * 4], index).unwrap();
+
+        let zoom_level = ZoomLevel::new(2);
+        let first = pattern(&atlas, "hatch", (0, 0, zoom_level).into()).unwrap();
+        let second = pattern(&atlas, "hatch", (1, 1, zoom_level).into()).unwrap();
+
+        assert_eq!(first.bounds, [0.0, 0.0, 0.75, 1.0]);
+        assert_eq!(first.origin, [0.0, 0.0]);
+
+        // The pattern is 3x4 logical pixels large
+        let scale = [(512.0 / 4096.0 / 3.0f64) as f32, 512.0 / 4096.0 / 4.0];
+        assert_eq!(first.scale, scale);
+
+        // The pattern coordinates at the right edge of the first tile equal the ones at the left
+        // edge of the second tile
+        for axis in 0..2 {
+            let edge = (first.origin[axis] + 4096.0 * first.scale[axis]).fract();
+            assert!((edge - second.origin[axis]).abs() < 1e-4);
+        }
+
+        assert!(pattern(&atlas, "missing", WorldTileCoords::default()).is_none());
+    }
+}
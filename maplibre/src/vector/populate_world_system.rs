@@ -1,12 +1,25 @@
 use std::{borrow::Cow, marker::PhantomData, rc::Rc};
 
+use geozero::mvt::{Message as _, Tile};
+
 use crate::{
     context::MapContext,
+    coords::WorldTileCoords,
     environment::Environment,
-    io::apc::{AsyncProcedureCall, Message},
+    io::apc::{AsyncProcedureCall, BufferedContext, Message},
     kernel::Kernel,
-    tcs::system::System,
-    vector::{transferables::*, VectorLayerData, VectorLayersDataComponent},
+    style::Style,
+    tcs::{system::System, world::World},
+    tessellation::tessellator::Tessellators,
+    vector::{
+        process_vector::{
+            process_vector_tiles, ProcessVectorContext, ProcessVectorError, VectorTileRequest,
+        },
+        recycle_layers, source_crs,
+        transferables::*,
+        DefaultVectorTransferables, LayerMissingReason, MissingVectorLayerData, VectorLayerData,
+        VectorLayersDataComponent,
+    },
 };
 
 pub struct PopulateWorldSystem<E: Environment, T> {
@@ -35,44 +48,256 @@ impl<E: Environment, T: VectorTransferables> System for PopulateWorldSystem<E, T
                 || message.has_tag(T::LayerTessellated::message_tag())
                 || message.has_tag(T::LayerIndexed::message_tag())
         }) {
-            let message: Message = message;
-            if message.has_tag(T::TileTessellated::message_tag()) {
-                let message = message.into_transferable::<T::TileTessellated>();
-                let Some(component) = world
-                       .tiles
-                       .query_mut::<&mut VectorLayersDataComponent>(message.coords()) else { continue; };
-
-                component.done = true;
-            } else if message.has_tag(T::LayerMissing::message_tag()) {
-                let message = message.into_transferable::<T::LayerMissing>();
-                let Some(component) = world
-                        .tiles
-                        .query_mut::<&mut VectorLayersDataComponent>(message.coords()) else { continue; };
-
-                component
-                    .layers
-                    .push(VectorLayerData::Missing(message.to_layer()));
-            } else if message.has_tag(T::LayerTessellated::message_tag()) {
-                let message = message.into_transferable::<T::LayerTessellated>();
-                // FIXME: Handle points!
-                /*if message.is_empty() {
-                    continue;
-                }*/
-
-                let Some(component) = world
-                        .tiles
-                        .query_mut::<&mut VectorLayersDataComponent>(message.coords()) else { continue; };
-
-                component
-                    .layers
-                    .push(VectorLayerData::Available(message.to_layer()));
-            } else if message.has_tag(T::LayerIndexed::message_tag()) {
-                let message = message.into_transferable::<T::LayerIndexed>();
-                world
-                    .tiles
-                    .geometry_index
-                    .index_tile(&message.coords(), message.to_tile_index());
+            populate_world::<T>(world, message);
+        }
+    }
+}
+
+/// Stores the result of processing a vector tile, which is contained in the `message`, in the
+/// tile of the `world`.
+pub(crate) fn populate_world<T: VectorTransferables>(world: &mut World, message: Message) {
+    if message.has_tag(T::TileTessellated::message_tag()) {
+        let message = message.into_transferable::<T::TileTessellated>();
+        let Some(component) = world
+            .tiles
+            .query_mut::<&mut VectorLayersDataComponent>(message.coords()) else { return; };
+
+        // The layers of each vector source are requested separately
+        component.pending_requests = component.pending_requests.saturating_sub(1);
+        if component.pending_requests > 0 {
+            return;
+        }
+
+        if let Some(pending_layers) = component.pending_layers.take() {
+            // A failed refresh keeps the previous layers, which are refreshed again after the
+            // next interval
+            if pending_layers.iter().any(VectorLayerData::is_error) {
+                recycle_layers(pending_layers);
+            } else {
+                // Only the layers of the refreshed sources are replaced
+                let (replaced, mut kept): (Vec<_>, Vec<_>) = std::mem::take(&mut component.layers)
+                    .into_iter()
+                    .partition(|layer| {
+                        pending_layers
+                            .iter()
+                            .any(|pending| pending.is_layer(layer.source(), layer.source_layer()))
+                    });
+                kept.extend(pending_layers);
+                component.layers = kept;
+                recycle_layers(replaced);
+                component.needs_upload = true;
             }
         }
+
+        component.done = true;
+    } else if message.has_tag(T::LayerMissing::message_tag()) {
+        let message = message.into_transferable::<T::LayerMissing>();
+
+        // Tiles whose deadline passed are requested again once they are in view
+        if message.reason() == LayerMissingReason::DeadlineExceeded {
+            world.tiles.evict(&message.coords());
+            return;
+        }
+
+        let Some(component) = world
+            .tiles
+            .query_mut::<&mut VectorLayersDataComponent>(message.coords()) else { return; };
+
+        component.push_layer(VectorLayerData::Missing(message.to_layer()));
+    } else if message.has_tag(T::LayerTessellated::message_tag()) {
+        let message = message.into_transferable::<T::LayerTessellated>();
+
+        let Some(component) = world
+            .tiles
+            .query_mut::<&mut VectorLayersDataComponent>(message.coords()) else { return; };
+
+        // FIXME: Handle points!
+        // Empty layers would result in zero-sized allocations on the GPU
+        if message.is_empty() {
+            let layer = message.to_layer();
+            component.push_layer(VectorLayerData::Missing(MissingVectorLayerData {
+                coords: layer.coords,
+                source: layer.source,
+                source_layer: layer.source_layer,
+                reason: LayerMissingReason::Empty,
+            }));
+            return;
+        }
+
+        component.push_layer(VectorLayerData::Available(message.to_layer()));
+    } else if message.has_tag(T::LayerIndexed::message_tag()) {
+        let message = message.into_transferable::<T::LayerIndexed>();
+        let coords = message.coords();
+        let layer_name = message.layer_name().to_owned();
+        world
+            .tiles
+            .geometry_index
+            .index_layer(&coords, layer_name, message.to_tile_index());
+    }
+}
+
+/// Tessellates all layers of the vector tile `data` of the `source` and stores them in the tile at
+/// `coords` of the `world`, as if the tile had been fetched. The layers are tessellated with the
+/// line layouts and the settings of the `source` in the `style`. The `data` of a source which is
+/// not in Web Mercator is the tile at `coords` in the grid of the source and is reprojected, see
+/// [`SourceCrs`](crate::projection::SourceCrs). A tile which is already loaded is replaced.
+///
+/// The `coords` have to be within the bounds of the world, see [`World::insert_tile`].
+pub(crate) fn insert_vector_tile(
+    world: &mut World,
+    style: &Style,
+    source: Option<&str>,
+    coords: WorldTileCoords,
+    data: &[u8],
+) -> Result<(), ProcessVectorError> {
+    let tile = Tile::decode(data).map_err(|e| ProcessVectorError::Decode(Box::new(e)))?;
+    let layers = tile.layers.into_iter().map(|layer| layer.name).collect();
+
+    match world
+        .tiles
+        .query_mut::<&mut VectorLayersDataComponent>(coords)
+    {
+        // All layers are replaced, such that they are uploaded again
+        Some(component) => {
+            recycle_layers(std::mem::take(&mut component.layers));
+            *component = VectorLayersDataComponent {
+                needs_upload: true,
+                ..VectorLayersDataComponent::default()
+            };
+        }
+        None => {
+            world
+                .tiles
+                .spawn_mut(coords)
+                .expect("tile coordinates are checked by World::insert_tile")
+                .insert(VectorLayersDataComponent::default());
+        }
+    }
+
+    let mut context =
+        ProcessVectorContext::<DefaultVectorTransferables, _>::new(BufferedContext::default());
+    process_vector_tiles(
+        &[(coords, data)],
+        source_crs(style, source),
+        VectorTileRequest {
+            coords,
+            source: source.map(str::to_string),
+            layers,
+            tessellators: Tessellators::default()
+                .with_line_layouts(style)
+                .with_source_settings(style),
+            index: world.is_interactive(),
+            deadline: None,
+        },
+        &mut context,
+    )?;
+
+    for message in context.take_context().into_messages() {
+        populate_world::<DefaultVectorTransferables>(world, message);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::populate_world;
+    use crate::{
+        coords::{WorldTileCoords, ZoomLevel},
+        io::apc::IntoMessage,
+        tcs::world::World,
+        vector::{
+            transferables::{
+                DefaultLayerMissing, DefaultTileTessellated, LayerMissing, TileTessellated,
+            },
+            DefaultVectorTransferables, LayerMissingReason, MissingVectorLayerData,
+            VectorLayerData, VectorLayersDataComponent,
+        },
+    };
+
+    #[test]
+    fn test_refresh_replaces_refreshed_sources() {
+        let coords = WorldTileCoords::from((0, 0, ZoomLevel::from(3)));
+        let live = Some("live".to_string());
+        let missing = |source: Option<String>, source_layer: &str, reason| {
+            VectorLayerData::Missing(MissingVectorLayerData {
+                coords,
+                source,
+                source_layer: source_layer.to_string(),
+                reason,
+            })
+        };
+
+        let mut world = World::default();
+        world
+            .tiles
+            .spawn_mut(coords)
+            .unwrap()
+            .insert(VectorLayersDataComponent {
+                done: true,
+                layers: vec![
+                    missing(None, "water", LayerMissingReason::Missing),
+                    missing(live.clone(), "traffic", LayerMissingReason::Missing),
+                ],
+                ..VectorLayersDataComponent::default()
+            });
+
+        let refresh = |world: &mut World, reason| {
+            let component = world
+                .tiles
+                .query_mut::<&mut VectorLayersDataComponent>(coords)
+                .unwrap();
+            component.pending_layers = Some(Vec::new());
+            component.pending_requests = 1;
+            component.needs_upload = false;
+
+            let message = DefaultLayerMissing::build_from(
+                coords,
+                live.clone(),
+                "traffic".to_string(),
+                reason,
+            );
+            populate_world::<DefaultVectorTransferables>(world, IntoMessage::into(message));
+            populate_world::<DefaultVectorTransferables>(
+                world,
+                IntoMessage::into(DefaultTileTessellated::build_from(coords)),
+            );
+        };
+        let reasons = |component: &VectorLayersDataComponent| {
+            component
+                .layers
+                .iter()
+                .map(|layer| match layer {
+                    VectorLayerData::Missing(data) => (data.source_layer.clone(), data.reason),
+                    VectorLayerData::Available(_) => unreachable!(),
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // Only the layers of the refreshed source are replaced
+        refresh(&mut world, LayerMissingReason::Empty);
+        let component = world
+            .tiles
+            .query::<&VectorLayersDataComponent>(coords)
+            .unwrap();
+        assert!(component.done && component.needs_upload);
+        assert!(component.pending_layers.is_none());
+        assert_eq!(
+            reasons(component),
+            vec![
+                ("water".to_string(), LayerMissingReason::Missing),
+                ("traffic".to_string(), LayerMissingReason::Empty),
+            ]
+        );
+
+        // A failed refresh keeps the previous layers and can be refreshed again
+        refresh(&mut world, LayerMissingReason::FetchFailed);
+        let component = world
+            .tiles
+            .query::<&VectorLayersDataComponent>(coords)
+            .unwrap();
+        assert!(component.done && !component.needs_upload);
+        assert!(component.pending_layers.is_none());
+        assert_eq!(reasons(component)[1].1, LayerMissingReason::Empty);
     }
 }
@@ -1,9 +1,10 @@
-use std::{collections::HashSet, marker::PhantomData};
+use std::{collections::HashSet, marker::PhantomData, time::Duration};
 
 use geozero::{
-    mvt::{tile, Message},
+    mvt::{tile, Message, Tile},
     GeozeroDatasource,
 };
+use instant::Instant;
 use thiserror::Error;
 
 use crate::{
@@ -11,11 +12,21 @@ use crate::{
     io::{
         apc::{Context, SendError},
         geometry_index::{IndexProcessor, IndexedGeometry, TileIndex},
+        request_settings::Deadline,
     },
+    projection::SourceCrs,
     render::ShaderVertex,
-    tessellation::{zero_tessellator::ZeroTessellator, IndexDataType, OverAlignedVertexBuffer},
-    vector::transferables::{
-        LayerIndexed, LayerMissing, LayerTessellated, TileTessellated, VectorTransferables,
+    tessellation::{
+        pool::VERTEX_BUFFER_POOL, tessellator::Tessellators, IndexDataType, OverAlignedVertexBuffer,
+    },
+    vector::{
+        feature_ids, metrics,
+        mvt_version::normalize_layer,
+        reproject::reproject_tiles,
+        transferables::{
+            LayerIndexed, LayerMissing, LayerTessellated, TileTessellated, VectorTransferables,
+        },
+        LayerMissingReason,
     },
 };
 
@@ -23,16 +34,35 @@ use crate::{
 pub enum ProcessVectorError {
     /// Sending of results failed
     #[error("sending data back through context failed")]
-    SendError(SendError),
+    SendError(#[from] SendError),
+    /// The tile data could not be decoded
+    #[error("decoding tile failed")]
+    Decode(#[source] Box<dyn std::error::Error>),
     /// Error during processing of the pipeline
     #[error("processing data in pipeline failed")]
-    Processing(Box<dyn std::error::Error>),
+    Processing(#[source] Box<dyn std::error::Error>),
 }
 
 /// A request for a tile at the given coordinates and in the given layers.
 pub struct VectorTileRequest {
     pub coords: WorldTileCoords,
+    /// The vector source of the `layers`. Layers without a source are requested from the default
+    /// source, which is `None`.
+    pub source: Option<String>,
     pub layers: HashSet<String>,
+    /// The tessellators which are used for the source-layers
+    pub tessellators: Tessellators,
+    /// Whether the geometries of all layers are indexed, such that their features can be queried
+    pub index: bool,
+    /// The tile is dropped if the deadline passes before all layers have been tessellated
+    pub deadline: Option<Deadline>,
+}
+
+impl VectorTileRequest {
+    fn has_deadline_passed(&self) -> bool {
+        self.deadline
+            .map_or(false, |deadline| deadline.has_passed())
+    }
 }
 
 pub fn process_vector_tile<T: VectorTransferables, C: Context>(
@@ -40,33 +70,125 @@ pub fn process_vector_tile<T: VectorTransferables, C: Context>(
     tile_request: VectorTileRequest,
     context: &mut ProcessVectorContext<T, C>,
 ) -> Result<(), ProcessVectorError> {
+    let started_at = Instant::now();
+
     // Decode
 
-    let mut tile = geozero::mvt::Tile::decode(data).expect("failed to load tile");
+    let tile = Tile::decode(data).map_err(|e| ProcessVectorError::Decode(Box::new(e)))?;
+
+    process_tile(tile, started_at, tile_request, context)
+}
+
+/// Processes the `tiles` of a source in the `crs`, which cover the Web Mercator tile of the
+/// `tile_request`, see [`SourceCrs::covering_tiles`]. The tiles are given with their coordinates
+/// in the tile grid of the source. Their geometry is reprojected to Web Mercator and the layers of
+/// all tiles are processed like the layers of a single tile.
+pub fn process_vector_tiles<T: VectorTransferables, C: Context>(
+    tiles: &[(WorldTileCoords, &[u8])],
+    crs: SourceCrs,
+    tile_request: VectorTileRequest,
+    context: &mut ProcessVectorContext<T, C>,
+) -> Result<(), ProcessVectorError> {
+    let started_at = Instant::now();
+
+    let tiles = tiles
+        .iter()
+        .map(|(coords, data)| {
+            let tile = Tile::decode(*data).map_err(|e| ProcessVectorError::Decode(Box::new(e)))?;
+            Ok((*coords, tile))
+        })
+        .collect::<Result<Vec<_>, ProcessVectorError>>()?;
+    let tile = reproject_tiles(crs, tile_request.coords, tiles);
+
+    process_tile(tile, started_at, tile_request, context)
+}
+
+/// Indexes and tessellates the layers of the decoded `tile`. The time until the processing
+/// started is recorded as the time it took to decode the tile.
+fn process_tile<T: VectorTransferables, C: Context>(
+    mut tile: Tile,
+    started_at: Instant,
+    tile_request: VectorTileRequest,
+    context: &mut ProcessVectorContext<T, C>,
+) -> Result<(), ProcessVectorError> {
+    let decode_time = started_at.elapsed();
+    let mut tessellate_time = Duration::ZERO;
 
     // Available
 
     let coords = &tile_request.coords;
+    let source = tile_request.source.as_deref();
 
     for layer in &mut tile.layers {
+        // The remaining layers are not tessellated if the tile is not useful anymore
+        if tile_request.has_deadline_passed() {
+            tracing::info!("deadline of tile at {coords} passed");
+            return context.deadline_exceeded(coords, source, &tile_request.layers);
+        }
+
+        normalize_layer(layer);
+
+        // Every layer is indexed and sent right away, such that its features can be picked before
+        // the remaining layers are processed
+        if tile_request.index {
+            let mut index = IndexProcessor::new().with_feature_ids(feature_ids(layer));
+            if let Err(e) = layer.process(&mut index) {
+                tracing::error!("layer {} at {coords} indexing failed {e:?}", layer.name);
+            }
+            context.layer_indexing_finished(coords, &layer.name, index.get_geometries())?;
+        }
+
         let cloned_layer = layer.clone();
         let layer_name: &str = &cloned_layer.name;
         if !tile_request.layers.contains(layer_name) {
             continue;
         }
 
-        let mut tessellator = ZeroTessellator::<IndexDataType>::default();
-        if let Err(e) = layer.process(&mut tessellator) {
-            context.layer_missing(coords, layer_name)?;
+        if layer.features.is_empty() {
+            context.layer_missing(coords, source, layer_name, LayerMissingReason::Empty)?;
 
-            tracing::error!("layer {layer_name} at {coords} tesselation failed {e:?}");
-        } else {
-            context.layer_tesselation_finished(
-                coords,
-                tessellator.buffer.into(),
-                tessellator.feature_indices,
-                cloned_layer,
-            )?;
+            tracing::info!("layer {layer_name} at {coords} has no features");
+            continue;
+        }
+
+        let tessellate_started_at = Instant::now();
+        let result = tile_request
+            .tessellators
+            .get(source, layer_name)
+            .tessellate(layer, *coords);
+        tessellate_time += tessellate_started_at.elapsed();
+
+        match result {
+            Err(e) => {
+                context.layer_missing(
+                    coords,
+                    source,
+                    layer_name,
+                    LayerMissingReason::TessellationFailed,
+                )?;
+
+                tracing::error!("layer {layer_name} at {coords} tesselation failed {e:?}");
+            }
+            Ok((buffer, feature_indices)) => {
+                let buffer: OverAlignedVertexBuffer<ShaderVertex, IndexDataType> = buffer.into();
+
+                // Layers can consist of features which do not produce any geometry, e.g. points
+                if buffer.usable_indices == 0 {
+                    VERTEX_BUFFER_POOL.give(buffer.buffer);
+                    context.layer_missing(coords, source, layer_name, LayerMissingReason::Empty)?;
+
+                    tracing::info!("layer {layer_name} at {coords} has no geometry");
+                    continue;
+                }
+
+                context.layer_tesselation_finished(
+                    coords,
+                    source,
+                    buffer,
+                    feature_indices,
+                    cloned_layer,
+                )?;
+            }
         }
     }
 
@@ -81,25 +203,17 @@ pub fn process_vector_tile<T: VectorTransferables, C: Context>(
         .collect::<HashSet<_>>();
 
     for missing_layer in tile_request.layers.difference(&available_layers) {
-        context.layer_missing(coords, missing_layer)?;
-        tracing::info!("requested layer {missing_layer} at {coords} not found in tile");
+        context.layer_missing(coords, source, missing_layer, LayerMissingReason::Missing)?;
+        tracing::debug!("requested layer {missing_layer} at {coords} not found in tile");
     }
 
-    // Indexing
-
-    let mut index = IndexProcessor::new();
-
-    for layer in &mut tile.layers {
-        layer.process(&mut index).unwrap();
-    }
-
-    context.layer_indexing_finished(&tile_request.coords, index.get_geometries())?;
-
     // End
 
     tracing::info!("tile tessellated at {coords} finished");
     context.tile_finished(coords)?;
 
+    metrics::record_tile(decode_time, tessellate_time, started_at.elapsed());
+
     Ok(())
 }
 
@@ -125,22 +239,49 @@ impl<T: VectorTransferables, C: Context> ProcessVectorContext<T, C> {
     fn tile_finished(&mut self, coords: &WorldTileCoords) -> Result<(), ProcessVectorError> {
         self.context
             .send(T::TileTessellated::build_from(*coords))
-            .map_err(|e| ProcessVectorError::Processing(Box::new(e)))
+            .map_err(ProcessVectorError::SendError)
     }
 
     fn layer_missing(
         &mut self,
         coords: &WorldTileCoords,
+        source: Option<&str>,
         layer_name: &str,
+        reason: LayerMissingReason,
     ) -> Result<(), ProcessVectorError> {
         self.context
-            .send(T::LayerMissing::build_from(*coords, layer_name.to_owned()))
-            .map_err(|e| ProcessVectorError::Processing(Box::new(e)))
+            .send(T::LayerMissing::build_from(
+                *coords,
+                source.map(str::to_owned),
+                layer_name.to_owned(),
+                reason,
+            ))
+            .map_err(ProcessVectorError::SendError)
+    }
+
+    /// Drops the tile at `coords` by reporting its requested `layers` as missing, see
+    /// [`LayerMissingReason::DeadlineExceeded`].
+    fn deadline_exceeded(
+        &mut self,
+        coords: &WorldTileCoords,
+        source: Option<&str>,
+        layers: &HashSet<String>,
+    ) -> Result<(), ProcessVectorError> {
+        for layer_name in layers {
+            self.layer_missing(
+                coords,
+                source,
+                layer_name,
+                LayerMissingReason::DeadlineExceeded,
+            )?;
+        }
+        Ok(())
     }
 
     fn layer_tesselation_finished(
         &mut self,
         coords: &WorldTileCoords,
+        source: Option<&str>,
         buffer: OverAlignedVertexBuffer<ShaderVertex, IndexDataType>,
         feature_indices: Vec<u32>,
         layer_data: tile::Layer,
@@ -148,49 +289,417 @@ impl<T: VectorTransferables, C: Context> ProcessVectorContext<T, C> {
         self.context
             .send(T::LayerTessellated::build_from(
                 *coords,
+                source.map(str::to_owned),
                 buffer,
                 feature_indices,
                 layer_data,
             ))
-            .map_err(|e| ProcessVectorError::Processing(Box::new(e)))
+            .map_err(ProcessVectorError::SendError)
     }
 
     fn layer_indexing_finished(
         &mut self,
         coords: &WorldTileCoords,
+        layer_name: &str,
         geometries: Vec<IndexedGeometry<f64>>,
     ) -> Result<(), ProcessVectorError> {
         self.context
             .send(T::LayerIndexed::build_from(
                 *coords,
+                layer_name.to_owned(),
                 TileIndex::Linear { list: geometries },
             ))
-            .map_err(|e| ProcessVectorError::Processing(Box::new(e)))
+            .map_err(ProcessVectorError::SendError)
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::{cell::RefCell, collections::HashSet, error::Error, time::Duration};
+
+    use geozero::mvt::{tile, Message as _, Tile};
+
     use super::ProcessVectorContext;
     use crate::{
-        coords::ZoomLevel,
-        io::apc::tests::DummyContext,
+        coords::{WorldCoords, WorldTileCoords, Zoom, ZoomLevel},
+        io::{
+            apc::{tests::DummyContext, Context, IntoMessage, Message, ProcedureError, SendError},
+            geometry_index::GeometryIndex,
+            request_settings::Deadline,
+        },
+        projection::SourceCrs,
         vector::{
-            process_vector::{process_vector_tile, VectorTileRequest},
-            DefaultVectorTransferables,
+            metrics,
+            process_vector::{
+                process_vector_tile, process_vector_tiles, ProcessVectorError, VectorTileRequest,
+            },
+            transferables::{
+                DefaultLayerIndexed, DefaultLayerMissing, DefaultTileTessellated, LayerIndexed,
+                LayerMissing, TileTessellated,
+            },
+            DefaultVectorTransferables, LayerMissingReason,
         },
     };
 
-    #[test] // TODO: Add proper tile byte array
-    #[ignore]
-    fn test() {
-        let _output = process_vector_tile(
+    #[derive(Default)]
+    struct RecordingContext {
+        messages: RefCell<Vec<Message>>,
+    }
+
+    impl Context for RecordingContext {
+        fn send<T: IntoMessage>(&self, message: T) -> Result<(), SendError> {
+            self.messages.borrow_mut().push(message.into());
+            Ok(())
+        }
+    }
+
+    fn layer(name: &str, geometry: Option<Vec<u32>>) -> tile::Layer {
+        typed_layer(name, tile::GeomType::Polygon, geometry)
+    }
+
+    fn typed_layer(
+        name: &str,
+        geometry_type: tile::GeomType,
+        geometry: Option<Vec<u32>>,
+    ) -> tile::Layer {
+        tile::Layer {
+            version: 2,
+            name: name.to_string(),
+            features: geometry
+                .map(|geometry| {
+                    vec![tile::Feature {
+                        id: Some(1),
+                        tags: vec![],
+                        r#type: Some(geometry_type as i32),
+                        geometry,
+                    }]
+                })
+                .unwrap_or_default(),
+            keys: vec![],
+            values: vec![],
+            extent: Some(4096),
+        }
+    }
+
+    fn missing_reasons(
+        layers: Vec<tile::Layer>,
+        requested: &[&str],
+    ) -> Vec<(String, LayerMissingReason)> {
+        let data = Tile { layers }.encode_to_vec();
+        let mut context =
+            ProcessVectorContext::<DefaultVectorTransferables, _>::new(RecordingContext::default());
+
+        process_vector_tile(
+            &data,
+            VectorTileRequest {
+                coords: WorldTileCoords::from((0, 0, ZoomLevel::default())),
+                source: None,
+                layers: requested
+                    .iter()
+                    .map(|name| name.to_string())
+                    .collect::<HashSet<_>>(),
+                tessellators: Default::default(),
+                index: true,
+                deadline: None,
+            },
+            &mut context,
+        )
+        .unwrap();
+
+        context
+            .take_context()
+            .messages
+            .into_inner()
+            .into_iter()
+            .filter(|message| message.has_tag(DefaultLayerMissing::message_tag()))
+            .map(|message| {
+                let missing = message.into_transferable::<DefaultLayerMissing>();
+                (missing.layer_name().to_string(), missing.reason())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_decode_error() {
+        let error = process_vector_tile(
             &[0],
             VectorTileRequest {
                 coords: (0, 0, ZoomLevel::default()).into(),
+                source: None,
                 layers: Default::default(),
+                tessellators: Default::default(),
+                index: true,
+                deadline: None,
             },
             &mut ProcessVectorContext::<DefaultVectorTransferables, _>::new(DummyContext),
+        )
+        .unwrap_err();
+
+        let decode_error = Tile::decode(&[0u8][..]).unwrap_err().to_string();
+
+        assert!(matches!(error, ProcessVectorError::Decode(_)));
+        assert_eq!(error.source().unwrap().to_string(), decode_error);
+
+        let error = ProcedureError::Execution(Box::new(error));
+        let process_error = error.source().unwrap();
+        assert!(process_error.is::<ProcessVectorError>());
+        assert_eq!(process_error.source().unwrap().to_string(), decode_error);
+    }
+
+    #[test]
+    fn test_metrics() {
+        let square = vec![9, 0, 0, 26, 20, 0, 0, 20, 19, 0, 15];
+        let before = metrics::metrics();
+
+        for _ in 0..3 {
+            missing_reasons(vec![layer("water", Some(square.clone()))], &["water"]);
+        }
+
+        // Other tests process tiles concurrently, so the counts can only be compared loosely
+        let after = metrics::metrics();
+        assert!(after.decode.count >= before.decode.count + 3);
+        assert!(after.tessellate.count >= before.tessellate.count + 3);
+        assert!(after.total.count >= before.total.count + 3);
+        assert!(after.total.max >= after.tessellate.p50);
+    }
+
+    #[test]
+    fn test_layer_missing() {
+        let square = vec![9, 0, 0, 26, 20, 0, 0, 20, 19, 0, 15];
+
+        assert_eq!(
+            missing_reasons(vec![layer("water", Some(square))], &["water", "roads"]),
+            vec![("roads".to_string(), LayerMissingReason::Missing)]
         );
     }
+
+    #[test]
+    fn test_layer_empty() {
+        assert_eq!(
+            missing_reasons(vec![layer("water", None)], &["water"]),
+            vec![("water".to_string(), LayerMissingReason::Empty)]
+        );
+    }
+
+    #[test]
+    fn test_layer_without_geometry() {
+        let point = vec![9, 20, 20];
+
+        assert_eq!(
+            missing_reasons(
+                vec![typed_layer("pois", tile::GeomType::Point, Some(point))],
+                &["pois"]
+            ),
+            vec![("pois".to_string(), LayerMissingReason::Empty)]
+        );
+    }
+
+    #[test]
+    fn test_layer_tessellation_failed() {
+        // Command id 7 is not defined by the MVT specification
+        let invalid = vec![(1 << 3) | 7, 0, 0];
+
+        assert_eq!(
+            missing_reasons(vec![layer("water", Some(invalid))], &["water"]),
+            vec![("water".to_string(), LayerMissingReason::TessellationFailed)]
+        );
+    }
+
+    #[test]
+    fn test_layers_are_indexed_incrementally() {
+        let square = vec![9, 0, 0, 26, 20, 0, 0, 20, 19, 0, 15];
+        let layers = vec![
+            layer("water", Some(square.clone())),
+            layer("park", Some(square)),
+        ];
+        let data = Tile { layers }.encode_to_vec();
+        let coords = WorldTileCoords::from((0, 0, ZoomLevel::default()));
+
+        let mut context =
+            ProcessVectorContext::<DefaultVectorTransferables, _>::new(RecordingContext::default());
+        process_vector_tile(
+            &data,
+            VectorTileRequest {
+                coords,
+                source: None,
+                layers: HashSet::from(["water".to_string(), "park".to_string()]),
+                tessellators: Default::default(),
+                index: true,
+                deadline: None,
+            },
+            &mut context,
+        )
+        .unwrap();
+        let mut messages = context.take_context().messages.into_inner();
+
+        // The first layer is indexed before the tile is finished
+        let first = messages
+            .iter()
+            .position(|message| message.has_tag(DefaultLayerIndexed::message_tag()))
+            .unwrap();
+        let finished = messages
+            .iter()
+            .position(|message| message.has_tag(DefaultTileTessellated::message_tag()))
+            .unwrap();
+        assert!(first < finished);
+
+        let first = messages
+            .remove(first)
+            .into_transferable::<DefaultLayerIndexed>();
+        assert_eq!(first.layer_name(), "water");
+
+        let mut index = GeometryIndex::new();
+        index.index_layer(
+            &first.coords(),
+            first.layer_name().to_owned(),
+            first.to_tile_index(),
+        );
+
+        // A point within the square, which covers the top left corner of the tile
+        let point = WorldCoords::at_ground(0.5, 0.5);
+        let query = |index: &GeometryIndex| {
+            index
+                .query_point(&point, ZoomLevel::default(), Zoom::default())
+                .map_or(0, |features| features.len())
+        };
+        assert_eq!(query(&index), 1);
+
+        for message in messages {
+            if message.has_tag(DefaultLayerIndexed::message_tag()) {
+                let indexed = message.into_transferable::<DefaultLayerIndexed>();
+                index.index_layer(
+                    &indexed.coords(),
+                    indexed.layer_name().to_owned(),
+                    indexed.to_tile_index(),
+                );
+            }
+        }
+        assert_eq!(query(&index), 2);
+    }
+
+    #[test]
+    fn test_indexing_disabled() {
+        let square = vec![9, 0, 0, 26, 20, 0, 0, 20, 19, 0, 15];
+        let data = Tile {
+            layers: vec![layer("water", Some(square))],
+        }
+        .encode_to_vec();
+
+        let mut context =
+            ProcessVectorContext::<DefaultVectorTransferables, _>::new(RecordingContext::default());
+        process_vector_tile(
+            &data,
+            VectorTileRequest {
+                coords: WorldTileCoords::from((0, 0, ZoomLevel::default())),
+                source: None,
+                layers: HashSet::from(["water".to_string()]),
+                tessellators: Default::default(),
+                index: false,
+                deadline: None,
+            },
+            &mut context,
+        )
+        .unwrap();
+        let messages = context.take_context().messages.into_inner();
+
+        assert!(messages
+            .iter()
+            .any(|message| message.has_tag(DefaultTileTessellated::message_tag())));
+        assert!(!messages
+            .iter()
+            .any(|message| message.has_tag(DefaultLayerIndexed::message_tag())));
+    }
+
+    #[test]
+    fn test_deadline_passed() {
+        let square = vec![9, 0, 0, 26, 20, 0, 0, 20, 19, 0, 15];
+        let data = Tile {
+            layers: vec![layer("water", Some(square))],
+        }
+        .encode_to_vec();
+
+        let mut context =
+            ProcessVectorContext::<DefaultVectorTransferables, _>::new(RecordingContext::default());
+        process_vector_tile(
+            &data,
+            VectorTileRequest {
+                coords: WorldTileCoords::from((0, 0, ZoomLevel::default())),
+                source: None,
+                layers: HashSet::from(["water".to_string()]),
+                tessellators: Default::default(),
+                index: true,
+                deadline: Some(Deadline::after(Duration::ZERO)),
+            },
+            &mut context,
+        )
+        .unwrap();
+        let messages = context.take_context().messages.into_inner();
+
+        // The tile is neither indexed nor tessellated
+        assert_eq!(messages.len(), 1);
+        let missing = messages
+            .into_iter()
+            .next()
+            .unwrap()
+            .into_transferable::<DefaultLayerMissing>();
+        assert_eq!(missing.layer_name(), "water");
+        assert_eq!(missing.reason(), LayerMissingReason::DeadlineExceeded);
+    }
+
+    #[test]
+    fn test_reprojected_tile_is_indexed() {
+        // A square at 67.5°N in EPSG:4326
+        let square = vec![9, 0, 2048, 26, 20, 0, 0, 20, 19, 0, 15];
+        let data = Tile {
+            layers: vec![layer("water", Some(square))],
+        }
+        .encode_to_vec();
+        let coords = WorldTileCoords::from((0, 0, ZoomLevel::from(1)));
+
+        let mut context =
+            ProcessVectorContext::<DefaultVectorTransferables, _>::new(RecordingContext::default());
+        process_vector_tiles(
+            &[(coords, data.as_slice())],
+            SourceCrs::Wgs84,
+            VectorTileRequest {
+                coords,
+                source: None,
+                layers: HashSet::from(["water".to_string()]),
+                tessellators: Default::default(),
+                index: true,
+                deadline: None,
+            },
+            &mut context,
+        )
+        .unwrap();
+
+        let index = context
+            .take_context()
+            .messages
+            .into_inner()
+            .into_iter()
+            .find(|message| message.has_tag(DefaultLayerIndexed::message_tag()))
+            .unwrap()
+            .into_transferable::<DefaultLayerIndexed>()
+            .to_tile_index();
+        let geometry = index.geometries().next().unwrap();
+
+        // The square is indexed where it is drawn, which is further south in the tile, as Web
+        // Mercator stretches high latitudes
+        let [_, y] = SourceCrs::Wgs84.reproject_to_web_mercator([0.0, 1024.0], coords, coords);
+        assert!(y > 1024.0 + 100.0, "{y}");
+        assert!((geometry.bounds.lower().y() - y).abs() < 1.0);
+    }
+
+    #[test]
+    fn test_reason_is_retryable() {
+        assert!(!LayerMissingReason::Missing.is_retryable());
+        assert!(!LayerMissingReason::Empty.is_retryable());
+        assert!(LayerMissingReason::TessellationFailed.is_retryable());
+        assert!(LayerMissingReason::FetchFailed.is_retryable());
+        assert!(!LayerMissingReason::TooLarge.is_retryable());
+        assert!(!LayerMissingReason::DecodeFailed.is_retryable());
+        assert!(LayerMissingReason::DecodeFailed.is_error());
+        assert!(LayerMissingReason::DeadlineExceeded.is_retryable());
+    }
 }
@@ -1,4 +1,6 @@
 //! Queues [PhaseItems](crate::render::render_phase::PhaseItem) for rendering.
+use std::collections::HashSet;
+
 use crate::{
     context::MapContext,
     render::{
@@ -8,24 +10,32 @@ use crate::{
         tile_view_pattern::WgpuTileViewPattern,
     },
     tcs::tiles::Tile,
-    vector::{render_commands::DrawVectorTiles, VectorBufferPool},
+    vector::{
+        render_commands::DrawVectorTiles, FrontFeatures, VectorBufferPool, VectorLayerData,
+        VectorLayersDataComponent,
+    },
 };
 
-pub fn queue_system(MapContext { world, .. }: &mut MapContext) {
+pub fn queue_system(MapContext { world, style, .. }: &mut MapContext) {
     let Some((
         Initialized(tile_view_pattern),
         Initialized(buffer_pool),
         mask_phase,
-        layer_item_phase
+        layer_item_phase,
+        front_features
     )) = world.resources.query_mut::<(
         &mut Eventually<WgpuTileViewPattern>,
         &mut Eventually<VectorBufferPool>,
         &mut RenderPhase<TileMaskItem>,
         &mut RenderPhase<LayerItem>,
+        &FrontFeatures,
     )>() else { return; };
 
     let buffer_pool_index = buffer_pool.index();
 
+    // The data of hidden layers stays uploaded, such that they are drawn again once shown
+    let hidden_layers = style.hidden_layers();
+
     for view_tile in tile_view_pattern.iter() {
         let coords = &view_tile.coords();
         tracing::trace!("Drawing tile at {coords}");
@@ -39,7 +49,16 @@ pub fn queue_system(MapContext { world, .. }: &mut MapContext) {
             });
 
             if let Some(layer_entries) = buffer_pool_index.get_layers(source_shape.coords()) {
-                for layer_entry in layer_entries {
+                let mut queued_layers = HashSet::new();
+
+                // Newer entries replace older entries of the same layer, e.g. after a refresh
+                for layer_entry in layer_entries.iter().rev() {
+                    if !queued_layers.insert(&layer_entry.style_layer.id)
+                        || hidden_layers.contains(&layer_entry.style_layer.id)
+                    {
+                        continue;
+                    }
+
                     // Draw tile
                     layer_item_phase.add(LayerItem {
                         draw_function: Box::new(DrawState::<LayerItem, DrawVectorTiles>::new()),
@@ -49,7 +68,51 @@ pub fn queue_system(MapContext { world, .. }: &mut MapContext) {
                             coords: layer_entry.coords,
                         },
                         source_shape: source_shape.clone(),
+                        indices: None,
                     });
+
+                    if front_features.is_empty() {
+                        continue;
+                    }
+
+                    let Some(source) = &layer_entry.style_layer.source else { continue; };
+                    let Some(source_layer) = &layer_entry.style_layer.source_layer else { continue; };
+                    let Some(component) = world
+                        .tiles
+                        .query::<&VectorLayersDataComponent>(layer_entry.coords) else { continue; };
+
+                    let Some(data) = component.layers.iter().find_map(|layer| match layer {
+                        VectorLayerData::Available(data)
+                            if layer.is_layer(Some(source), source_layer) =>
+                        {
+                            Some(data)
+                        }
+                        _ => None,
+                    }) else { continue; };
+
+                    let usable_indices = layer_entry.indices_range().end;
+                    for indices in front_features.index_ranges(
+                        source,
+                        &data.feature_ids,
+                        &data.feature_indices,
+                    ) {
+                        if indices.end > usable_indices {
+                            continue;
+                        }
+
+                        // Features in front are drawn after all layers of the style, but below
+                        // overlays and markers
+                        layer_item_phase.add(LayerItem {
+                            draw_function: Box::new(DrawState::<LayerItem, DrawVectorTiles>::new()),
+                            index: u32::MAX - 1,
+                            style_layer: layer_entry.style_layer.id.clone(),
+                            tile: Tile {
+                                coords: layer_entry.coords,
+                            },
+                            source_shape: source_shape.clone(),
+                            indices: Some(indices),
+                        });
+                    }
                 }
             };
         });
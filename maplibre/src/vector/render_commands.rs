@@ -3,27 +3,62 @@
 use crate::{
     render::{
         eventually::{Eventually, Eventually::Initialized},
-        render_phase::{LayerItem, PhaseItem, RenderCommand, RenderCommandResult},
-        resource::TrackedRenderPass,
+        render_phase::{LayerItem, RenderCommand, RenderCommandResult},
+        resource::{StencilMode, TrackedRenderPass, STENCIL_MARK},
         tile_view_pattern::WgpuTileViewPattern,
-        INDEX_FORMAT,
     },
+    style::layer::StyleLayer,
     tcs::world::World,
-    vector::{VectorBufferPool, VectorPipeline},
+    vector::{pattern::SpriteTexture, VectorBufferPool, VectorPipeline, VectorPipelineKey},
 };
 
+/// The key of the pipeline which draws the `item` of the `style_layer`. Items of single features
+/// are features in front, see [`FrontFeatures`](crate::vector::FrontFeatures).
+fn pipeline_key(style_layer: &StyleLayer, item: &LayerItem) -> VectorPipelineKey {
+    let key = VectorPipelineKey::of(style_layer);
+    if item.indices.is_some() {
+        key.front()
+    } else {
+        key
+    }
+}
+
 pub struct SetVectorTilePipeline;
-impl<P: PhaseItem> RenderCommand<P> for SetVectorTilePipeline {
+impl RenderCommand<LayerItem> for SetVectorTilePipeline {
     fn render<'w>(
         world: &'w World,
-        _item: &P,
+        item: &LayerItem,
         pass: &mut TrackedRenderPass<'w>,
     ) -> RenderCommandResult {
-        let Some(Initialized(pipeline)) = world
-            .resources
-            .get::<Eventually<VectorPipeline>>() else { return RenderCommandResult::Failure; };
+        let Some((
+            Initialized(buffer_pool),
+            Initialized(pipelines),
+        )) = world.resources.query::<(
+            &Eventually<VectorBufferPool>,
+            &Eventually<VectorPipeline>
+        )>() else { return RenderCommandResult::Failure; };
+
+        let Some(vector_layers) = buffer_pool.index().get_layers(item.tile.coords) else { return RenderCommandResult::Failure; };
+
+        let Some(entry) = vector_layers
+            .iter()
+            .rev()
+            .find(|entry| entry.style_layer.id == item.style_layer) else { return RenderCommandResult::Failure; };
+
+        let key = pipeline_key(&entry.style_layer, item);
+        let Some(pipeline) = pipelines.get(&key) else { return RenderCommandResult::Failure; };
 
         pass.set_render_pipeline(pipeline);
+
+        // Patterns are not drawn until the sprite is available
+        if key.pattern {
+            let Some(Initialized(sprite_texture)) = world
+                .resources
+                .get::<Eventually<SpriteTexture>>() else { return RenderCommandResult::Failure; };
+
+            pass.set_bind_group(0, sprite_texture.bind_group(), &[]);
+        }
+
         RenderCommandResult::Success
     }
 }
@@ -47,6 +82,7 @@ impl RenderCommand<LayerItem> for DrawVectorTile {
 
         let Some(entry) = vector_layers
             .iter()
+            .rev()
             .find(|entry| entry.style_layer.id == item.style_layer) else { return RenderCommandResult::Failure; };
 
         let source_shape = &item.source_shape;
@@ -61,6 +97,7 @@ impl RenderCommand<LayerItem> for DrawVectorTile {
         );
 
         let index_range = entry.indices_buffer_range();
+        let indices = item.indices.clone().unwrap_or_else(|| entry.indices_range());
 
         if index_range.is_empty() {
             tracing::error!("Tried to draw a vector tile without any vertices");
@@ -69,34 +106,80 @@ impl RenderCommand<LayerItem> for DrawVectorTile {
 
         pass.set_stencil_reference(reference);
 
-        pass.set_index_buffer(buffer_pool.indices().slice(index_range), INDEX_FORMAT);
-        pass.set_vertex_buffer(
-            0,
-            buffer_pool.vertices().slice(entry.vertices_buffer_range()),
-        );
+        pass.set_index_buffer(buffer_pool.indices(), index_range, entry.index_format());
+        pass.set_vertex_buffer(0, buffer_pool.vertices(), entry.vertices_buffer_range());
         let tile_view_pattern_buffer = source_shape
             .buffer_range()
             .expect("tile_view_pattern needs to be uploaded first"); // FIXME tcs
-        pass.set_vertex_buffer(
-            1,
-            tile_view_pattern.buffer().slice(tile_view_pattern_buffer),
-        );
+        pass.set_vertex_buffer(1, tile_view_pattern.buffer(), tile_view_pattern_buffer);
         pass.set_vertex_buffer(
             2,
-            buffer_pool
-                .metadata()
-                .slice(entry.layer_metadata_buffer_range()),
+            buffer_pool.metadata(),
+            entry.layer_metadata_buffer_range(),
         );
         pass.set_vertex_buffer(
             3,
-            buffer_pool
-                .feature_metadata()
-                .slice(entry.feature_metadata_buffer_range()),
+            buffer_pool.feature_metadata(),
+            entry.feature_metadata_buffer_range(),
+        );
+        pass.draw_indexed(indices, 0, 0..1);
+
+        RenderCommandResult::Success
+    }
+}
+
+/// Removes the marks of layers which are blended once from the stencil, such that the following
+/// layers are drawn within the whole mask of the tile. Expects the buffers of [`DrawVectorTile`]
+/// to be bound.
+pub struct UnmarkVectorTile;
+impl RenderCommand<LayerItem> for UnmarkVectorTile {
+    fn render<'w>(
+        world: &'w World,
+        item: &LayerItem,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        let Some((
+            Initialized(buffer_pool),
+            Initialized(pipelines),
+        )) = world.resources.query::<(
+            &Eventually<VectorBufferPool>,
+            &Eventually<VectorPipeline>
+        )>() else { return RenderCommandResult::Failure; };
+
+        let Some(vector_layers) = buffer_pool.index().get_layers(item.tile.coords) else { return RenderCommandResult::Failure; };
+
+        let Some(entry) = vector_layers
+            .iter()
+            .rev()
+            .find(|entry| entry.style_layer.id == item.style_layer) else { return RenderCommandResult::Failure; };
+
+        let key = pipeline_key(&entry.style_layer, item);
+        if key.stencil_mode != StencilMode::Once {
+            return RenderCommandResult::Success;
+        }
+
+        let Some(pipeline) = pipelines.get(&key.unmark()) else { return RenderCommandResult::Failure; };
+        pass.set_render_pipeline(pipeline);
+
+        // The layout of the pipeline is created separately, so the sprite is bound again
+        if key.pattern {
+            let Some(Initialized(sprite_texture)) = world
+                .resources
+                .get::<Eventually<SpriteTexture>>() else { return RenderCommandResult::Failure; };
+
+            pass.set_bind_group(0, sprite_texture.bind_group(), &[]);
+        }
+
+        let reference = item.source_shape.coords().stencil_reference_value_3d() as u32;
+        pass.set_stencil_reference(reference | STENCIL_MARK);
+        pass.draw_indexed(
+            item.indices.clone().unwrap_or_else(|| entry.indices_range()),
+            0,
+            0..1,
         );
-        pass.draw_indexed(entry.indices_range(), 0, 0..1);
 
         RenderCommandResult::Success
     }
 }
 
-pub type DrawVectorTiles = (SetVectorTilePipeline, DrawVectorTile);
+pub type DrawVectorTiles = (SetVectorTilePipeline, DrawVectorTile, UnmarkVectorTile);
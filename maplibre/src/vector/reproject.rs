@@ -0,0 +1,172 @@
+//! Reprojects the vector tiles of sources whose CRS is not Web Mercator, see [`SourceCrs`].
+//!
+//! The geometry of a Web Mercator tile can come from several tiles of the source, see
+//! [`SourceCrs::covering_tiles`]. Their layers are merged into a single tile whose coordinates
+//! are relative to the Web Mercator tile. Geometry beyond the edges of the tile is kept and
+//! clipped by the tile mask, like the buffer of any other tile, while the neighbouring tiles draw
+//! their part of it from the same source tiles.
+//!
+//! The geometry is reprojected before it is tessellated and indexed, such that lines keep their
+//! width and features are picked where they are drawn.
+
+use geozero::mvt::{tile, Tile};
+
+use crate::{
+    coords::WorldTileCoords,
+    projection::SourceCrs,
+    vector::mvt_version::{map_points, normalize_layer},
+};
+
+/// Merges the `tiles` of a source in the `crs`, which are given with their coordinates in the
+/// tile grid of the source, into the Web Mercator tile at `coords`. Layers with the same name are
+/// merged into one layer.
+pub(crate) fn reproject_tiles(
+    crs: SourceCrs,
+    coords: WorldTileCoords,
+    tiles: Vec<(WorldTileCoords, Tile)>,
+) -> Tile {
+    let mut layers: Vec<tile::Layer> = Vec::new();
+
+    for (source_coords, tile) in tiles {
+        for mut layer in tile.layers {
+            if crs != SourceCrs::WebMercator || source_coords != coords {
+                // The reprojection assumes the extent of the tessellation
+                normalize_layer(&mut layer);
+                map_points(&mut layer, |(x, y)| {
+                    let [x, y] =
+                        crs.reproject_to_web_mercator([x as f64, y as f64], source_coords, coords);
+                    (x.round() as i64, y.round() as i64)
+                });
+            }
+
+            match layers.iter_mut().find(|merged| merged.name == layer.name) {
+                Some(merged) => merge_layer(merged, layer),
+                None => layers.push(layer),
+            }
+        }
+    }
+
+    Tile { layers }
+}
+
+/// Appends the features of `layer` to `target`. The tags of the features refer to the keys and
+/// values of their layer, so they are moved behind the keys and values of `target`.
+fn merge_layer(target: &mut tile::Layer, mut layer: tile::Layer) {
+    let key_offset = target.keys.len() as u32;
+    let value_offset = target.values.len() as u32;
+
+    for feature in &mut layer.features {
+        for (i, tag) in feature.tags.iter_mut().enumerate() {
+            *tag += if i % 2 == 0 { key_offset } else { value_offset };
+        }
+    }
+
+    target.keys.append(&mut layer.keys);
+    target.values.append(&mut layer.values);
+    target.features.append(&mut layer.features);
+}
+
+#[cfg(test)]
+mod tests {
+    use geozero::mvt::{tile, Tile};
+
+    use super::reproject_tiles;
+    use crate::{
+        coords::{WorldTileCoords, ZoomLevel},
+        projection::SourceCrs,
+    };
+
+    /// A layer with a single point at (`x`, `y`) whose `class` is `value`
+    fn point_layer(x: u32, y: u32, value: &str) -> tile::Layer {
+        tile::Layer {
+            version: 2,
+            name: "water".to_string(),
+            features: vec![tile::Feature {
+                id: Some(1),
+                tags: vec![0, 0],
+                r#type: Some(tile::GeomType::Point as i32),
+                geometry: vec![9, x << 1, y << 1],
+            }],
+            keys: vec!["class".to_string()],
+            values: vec![tile::Value {
+                string_value: Some(value.to_string()),
+                ..tile::Value::default()
+            }],
+            extent: Some(4096),
+        }
+    }
+
+    /// The position of the point of a `feature` of [`point_layer`]
+    fn position(feature: &tile::Feature) -> (u32, u32) {
+        (feature.geometry[1] >> 1, feature.geometry[2] >> 1)
+    }
+
+    #[test]
+    fn test_merge_covering_tiles() {
+        let tile = |x, y| WorldTileCoords::from((x, y, ZoomLevel::from(2)));
+        let coords = tile(2, 1);
+        assert_eq!(
+            SourceCrs::Wgs84.covering_tiles(coords),
+            vec![tile(2, 0), tile(2, 1)]
+        );
+
+        // A point at 45°N in the first tile and one at 22.5°N in the second tile
+        let tiles = vec![
+            (
+                tile(2, 0),
+                Tile {
+                    layers: vec![point_layer(0, 4096, "lake")],
+                },
+            ),
+            (
+                tile(2, 1),
+                Tile {
+                    layers: vec![point_layer(2048, 2048, "river")],
+                },
+            ),
+        ];
+
+        let merged = reproject_tiles(SourceCrs::Wgs84, coords, tiles);
+        assert_eq!(merged.layers.len(), 1);
+        let layer = &merged.layers[0];
+        assert_eq!(layer.features.len(), 2);
+
+        // The tags of the second feature refer to its own value
+        assert_eq!(layer.features[1].tags, vec![1, 1]);
+        assert_eq!(layer.keys, vec!["class", "class"]);
+        assert_eq!(layer.values[1].string_value.as_deref(), Some("river"));
+
+        // ln(tan(67.5°)) / 2π = 0.1403 and ln(tan(56.25°)) / 2π = 0.0642 above the center of the
+        // world, relative to the second row which starts a quarter of the world above the center
+        let (x, y) = position(&layer.features[0]);
+        assert_eq!(x, 0);
+        assert!(
+            (y as f64 - (0.25 - 0.1403) * 4.0 * 4096.0).abs() < 2.0,
+            "{y}"
+        );
+        let (x, y) = position(&layer.features[1]);
+        assert_eq!(x, 2048);
+        assert!(
+            (y as f64 - (0.25 - 0.0642) * 4.0 * 4096.0).abs() < 2.0,
+            "{y}"
+        );
+    }
+
+    #[test]
+    fn test_web_mercator_is_unchanged() {
+        let coords = WorldTileCoords::from((1, 1, ZoomLevel::from(1)));
+        let layer = point_layer(100, 200, "lake");
+        let merged = reproject_tiles(
+            SourceCrs::WebMercator,
+            coords,
+            vec![(
+                coords,
+                Tile {
+                    layers: vec![layer.clone()],
+                },
+            )],
+        );
+
+        assert_eq!(merged.layers, vec![layer]);
+    }
+}
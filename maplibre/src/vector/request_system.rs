@@ -1,26 +1,59 @@
 //! Requests tiles which are currently in view
 
-use std::{borrow::Cow, collections::HashSet, marker::PhantomData, rc::Rc};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap, HashSet},
+    marker::PhantomData,
+    rc::Rc,
+    time::Duration,
+};
+
+use instant::Instant;
 
 use crate::{
     context::MapContext,
+    coords::{ViewRegion, WorldTileCoords, ZoomLevel},
     environment::{Environment, OffscreenKernelEnvironment},
     io::{
         apc::{AsyncProcedureCall, AsyncProcedureFuture, Context, Input, ProcedureError},
+        preload::{evict_finished, PreloadRegions, PRELOAD_REQUESTS_PER_FRAME},
+        request_log::{RequestKind, RequestLog, RequestPriority, RequestReplay},
+        request_settings::{Deadline, RequestBudget, RequestSettings},
+        source_client::{HttpClient, SourceClient, SourceFetchError},
         source_type::{SourceType, TessellateSource},
+        tile_format::TileFormat,
     },
     kernel::Kernel,
-    style::layer::LayerPaint,
-    tcs::system::System,
+    projection::SourceCrs,
+    style::{
+        layer::{LayerPaint, StyleLayer},
+        source::{Source, VectorSource},
+        Style,
+    },
+    tcs::{system::System, world::World},
     vector::{
-        process_vector::{process_vector_tile, ProcessVectorContext, VectorTileRequest},
-        transferables::{LayerMissing, VectorTransferables},
-        VectorLayersDataComponent,
+        process_vector::{
+            process_vector_tiles, ProcessVectorContext, ProcessVectorError, VectorTileRequest,
+        },
+        transferables::{LayerMissing, TileTessellated, VectorTransferables},
+        LayerMissingReason, VectorLayersDataComponent,
     },
 };
 
 pub struct RequestSystem<E: Environment, T> {
     kernel: Rc<Kernel<E>>,
+    /// The time at which the tiles of a changed view have been requested the last time
+    last_request: Option<Instant>,
+    /// Whether tiles of the view have been deferred to the next frame, see
+    /// [`RequestSettings::max_requests_per_frame`]
+    has_deferred: bool,
+    /// The view region whose tiles have all been requested together with the
+    /// [`Tiles::removal_count`](crate::tcs::tiles::Tiles::removal_count) at that time. When
+    /// panning, only the tiles which are newly exposed compared to this region are requested.
+    last_region: Option<(ViewRegion, u64)>,
+    /// The ids of the layers which have been hidden in the last frame, see
+    /// [`Style::hidden_layers`]
+    hidden_layers: HashSet<String>,
     phantom_t: PhantomData<T>,
 }
 
@@ -28,6 +61,10 @@ impl<E: Environment, T> RequestSystem<E, T> {
     pub fn new(kernel: &Rc<Kernel<E>>) -> Self {
         Self {
             kernel: kernel.clone(),
+            last_request: None,
+            has_deferred: false,
+            last_region: None,
+            hidden_layers: HashSet::new(),
             phantom_t: Default::default(),
         }
     }
@@ -47,14 +84,71 @@ impl<E: Environment, T: VectorTransferables> System for RequestSystem<E, T> {
             ..
         }: &mut MapContext,
     ) {
-        let _tiles = &mut world.tiles;
-        let view_region = view_state.create_view_region();
+        let now = Instant::now();
+
+        let Some(settings) =
+            RequestSettings::for_view_request(world, view_state, self.last_request, now) else {
+            // The references are not updated, such that the change of the camera is still
+            // detected once the animation has finished, the coalesce window has elapsed or the
+            // world is unfrozen
+            return;
+        };
 
         if view_state.did_camera_change() || view_state.did_zoom_change() {
-            if let Some(view_region) = &view_region {
+            self.last_request = Some(now);
+        }
+
+        // Tiles which have been requested while a layer was hidden lack its data
+        let hidden_layers = style.hidden_layers();
+        let shown_layers: HashSet<String> = self
+            .hidden_layers
+            .difference(&hidden_layers)
+            .cloned()
+            .collect();
+        let did_show_layers = !shown_layers.is_empty();
+        self.hidden_layers = hidden_layers;
+
+        let view_region = view_state.create_view_region();
+        let index = world.is_interactive();
+
+        if let Some(view_region) = &view_region {
+            let sources = requested_source_layers(style, view_region.zoom_level());
+
+            // Tiles which are requested below already include the shown layers
+            if did_show_layers {
+                self.request_shown_layers(world, style, view_region, &sources, &shown_layers, now);
+            }
+
+            // Tiles are not requested if none of their layers would be drawn at this zoom level
+            if (view_state.did_camera_change()
+                || view_state.did_zoom_change()
+                || self.has_deferred
+                || did_show_layers)
+                && !sources.is_empty()
+            {
                 // TODO: We also need to request tiles from layers above if we are over the maximum zoom level
 
+                let mut budget = RequestBudget::new(&settings);
+
+                // Tiles which are still in view are used, even if they are not requested again
                 for coords in view_region.iter() {
+                    world.tiles.mark_used(coords);
+                }
+
+                let candidates: Vec<WorldTileCoords> = match &self.last_region {
+                    Some((last_region, removal_count))
+                        if !view_state.did_zoom_change()
+                            && !self.has_deferred
+                            && !did_show_layers
+                            && *removal_count == world.tiles.removal_count() =>
+                    {
+                        view_region.iter_exposed(last_region).collect()
+                    }
+                    _ => view_region.iter_center_first().collect(),
+                };
+
+                let mut requested = Vec::new();
+                for coords in candidates {
                     if coords.build_quad_key().is_none() {
                         continue;
                     }
@@ -68,39 +162,580 @@ impl<E: Environment, T: VectorTransferables> System for RequestSystem<E, T> {
                         continue;
                     }
 
-                    world
-                        .tiles
-                        .spawn_mut(coords)
-                        .unwrap()
-                        .insert(VectorLayersDataComponent::default());
+                    if !budget.take() {
+                        continue;
+                    }
+
+                    insert_requested_tile(world, coords, &sources);
 
                     tracing::event!(tracing::Level::ERROR, %coords, "tile request started: {coords}");
                     log::info!("tile request started: {coords}");
+                    record_request(world, &sources, coords, RequestPriority::View);
 
-                    self.kernel
-                        .apc()
-                        .call(
-                            Input::TileRequest {
-                                coords,
-                                style: style.clone(), // TODO: Avoid cloning whole style
-                            },
-                            fetch_vector_apc::<
-                                E::OffscreenKernelEnvironment,
-                                T,
-                                <E::AsyncProcedureCall as AsyncProcedureCall<
-                                    E::OffscreenKernelEnvironment,
-                                >>::Context,
-                            >,
-                        )
-                        .unwrap(); // TODO: Remove unwrap
+                    requested.push(coords);
+                }
+
+                self.request_tiles(
+                    requested,
+                    style,
+                    &sources,
+                    index,
+                    settings.request_deadline.map(Deadline::after),
+                );
+
+                // Deferred tiles are requested in the next frames if they are still in view
+                self.has_deferred = budget.has_deferred();
+
+                // Only tiles which are not in view are evicted, unless the view alone exceeds the
+                // budget, so the count is taken afterwards
+                world.tiles.evict_to_budget();
+                self.last_region = Some((view_region.clone(), world.tiles.removal_count()));
+            }
+
+            let intervals = refresh_intervals(style);
+            if !intervals.is_empty() {
+                for coords in view_region.iter() {
+                    let Some(component) = world
+                        .tiles
+                        .query_mut::<&mut VectorLayersDataComponent>(coords) else { continue; };
+
+                    // Wait until the tile and its previous refresh have finished
+                    if !component.done || component.pending_layers.is_some() {
+                        continue;
+                    }
+
+                    let due = due_sources(component, &sources, &intervals, now);
+                    if due.is_empty() {
+                        continue;
+                    }
+
+                    for source in due.keys() {
+                        component.requested_at.insert(source.clone(), now);
+                    }
+                    component.pending_layers = Some(Vec::new());
+                    component.pending_requests = due.len();
+
+                    log::info!("tile refresh started: {coords}");
+                    record_request(world, &due, coords, RequestPriority::Refresh);
+
+                    self.request_tile(coords, style, &due, index, None);
                 }
             }
         }
 
+        self.request_pinned_tiles(world, style);
+        self.request_preloaded_tiles(world, style);
+        self.request_replayed_tiles(world, style, now);
+
         view_state.update_references();
     }
 }
 
+impl<E: Environment, T: VectorTransferables> RequestSystem<E, T> {
+    /// Requests the source-layers of the `shown_layers` of the `style` for the tiles in view
+    /// which lack them, because the tiles have been requested while the layers were hidden. The
+    /// other layers of the tiles are kept.
+    fn request_shown_layers(
+        &self,
+        world: &mut World,
+        style: &Style,
+        view_region: &ViewRegion,
+        sources: &SourceLayers,
+        shown_layers: &HashSet<String>,
+        now: Instant,
+    ) {
+        let index = world.is_interactive();
+        let shown = drawn_source_layers(style, sources, shown_layers);
+        if shown.is_empty() {
+            return;
+        }
+
+        for coords in view_region.iter() {
+            let Some(component) = world
+                .tiles
+                .query_mut::<&mut VectorLayersDataComponent>(coords) else { continue; };
+
+            let missing = missing_source_layers(component, &shown);
+            if missing.is_empty() {
+                continue;
+            }
+
+            // The tile is loading until the missing layers have been processed
+            component.done = false;
+            component.pending_requests += missing.len();
+            for source in missing.keys() {
+                component.requested_at.entry(source.clone()).or_insert(now);
+            }
+
+            log::info!("shown layers request started: {coords}");
+            record_request(world, &missing, coords, RequestPriority::View);
+
+            for (source, layers) in missing {
+                self.request_source_layers(coords, style, &source, Some(layers), index, None);
+            }
+        }
+    }
+
+    /// Requests the pinned tiles which have not been requested yet, regardless of the current
+    /// view. Pinned tiles are only missing at startup or after all tiles have been evicted.
+    fn request_pinned_tiles(&self, world: &mut World, style: &Style) {
+        let index = world.is_interactive();
+        let missing: Vec<WorldTileCoords> = world
+            .tiles
+            .pinned_coords()
+            .filter(|coords| {
+                world
+                    .tiles
+                    .query::<&VectorLayersDataComponent>(**coords)
+                    .is_none()
+            })
+            .copied()
+            .collect();
+
+        for coords in missing {
+            let sources = requested_source_layers(style, coords.z);
+            insert_requested_tile(world, coords, &sources);
+
+            log::info!("pinned tile request started: {coords}");
+            record_request(world, &sources, coords, RequestPriority::Pinned);
+
+            self.request_tile(coords, style, &sources, index, None);
+        }
+    }
+
+    /// Requests the tiles of preloaded regions after the tiles in view have been requested.
+    fn request_preloaded_tiles(&self, world: &mut World, style: &Style) {
+        let index = world.is_interactive();
+        let Some(preload_regions) = world.resources.get_mut::<PreloadRegions>() else { return; };
+
+        // Failed tiles are done as well, see `send_failed`
+        let finished = preload_regions.update(|coords| {
+            world
+                .tiles
+                .query::<&VectorLayersDataComponent>(coords)
+                .map_or(true, |component| component.done)
+        });
+        evict_finished(&mut world.tiles, &finished);
+
+        for coords in preload_regions.next_requests(PRELOAD_REQUESTS_PER_FRAME) {
+            // Tiles which are already loaded or in flight complete without another request
+            if world
+                .tiles
+                .query::<&VectorLayersDataComponent>(coords)
+                .is_some()
+            {
+                continue;
+            }
+
+            let sources = requested_source_layers(style, coords.z);
+            insert_requested_tile(world, coords, &sources);
+
+            log::info!("tile preload started: {coords}");
+            record_request(world, &sources, coords, RequestPriority::Preload);
+
+            self.request_tile(coords, style, &sources, index, None);
+        }
+    }
+
+    /// Issues the vector requests of a replay which are due at `now`, see
+    /// [`World::replay_requests`](crate::tcs::world::World::replay_requests). Each tile is
+    /// requested from the recorded sources and source layers, regardless of the current style.
+    fn request_replayed_tiles(&self, world: &mut World, style: &Style, now: Instant) {
+        let index = world.is_interactive();
+        let Some(replay) = world.resources.get_mut::<RequestReplay>() else { return; };
+
+        // The requests of the sources of a tile are recorded one by one
+        let mut tiles: Vec<(WorldTileCoords, SourceLayers, RequestPriority)> = Vec::new();
+        for request in replay.take_due(RequestKind::Vector, now) {
+            let layers = request.layers.into_iter();
+            match tiles
+                .iter_mut()
+                .find(|(coords, ..)| *coords == request.coords)
+            {
+                Some((_, sources, _)) => sources.entry(request.source).or_default().extend(layers),
+                None => tiles.push((
+                    request.coords,
+                    SourceLayers::from([(request.source, layers.collect())]),
+                    request.priority,
+                )),
+            }
+        }
+
+        for (coords, sources, priority) in tiles {
+            if world
+                .tiles
+                .query::<&VectorLayersDataComponent>(coords)
+                .is_some()
+            {
+                log::debug!("replayed tile at {coords} is already loaded or in flight");
+                continue;
+            }
+
+            insert_requested_tile(world, coords, &sources);
+
+            log::info!("replayed tile request started: {coords}");
+            record_request(world, &sources, coords, priority);
+
+            for (source, layers) in sources {
+                self.request_source_layers(coords, style, &source, Some(layers), index, None);
+            }
+        }
+    }
+
+    /// Requests the tiles at `coords` from each of the `sources`. Adjacent tiles are requested
+    /// together if the source declares a batch size, see [`batch_adjacent`].
+    fn request_tiles(
+        &self,
+        coords: Vec<WorldTileCoords>,
+        style: &Style,
+        sources: &SourceLayers,
+        index: bool,
+        deadline: Option<Deadline>,
+    ) {
+        for source in sources.keys() {
+            let Some(batch_size) = batch_size(style, source.as_deref()) else {
+                for coords in &coords {
+                    self.request_source_tile(*coords, style, source, index, deadline);
+                }
+                continue;
+            };
+
+            for batch in batch_adjacent(coords.clone(), batch_size) {
+                if let [coords] = batch[..] {
+                    self.request_source_tile(coords, style, source, index, deadline);
+                    continue;
+                }
+
+                self.call(Input::TileBatchRequest {
+                    coords: batch,
+                    style: style.clone(), // TODO: Avoid cloning whole style
+                    source: source.clone(),
+                    index,
+                    deadline,
+                });
+            }
+        }
+    }
+
+    /// Requests the tile at `coords` from each of the `sources`. Only tiles which are requested
+    /// for the current view have a `deadline`, as refreshed and preloaded tiles are useful
+    /// regardless of the view.
+    fn request_tile(
+        &self,
+        coords: WorldTileCoords,
+        style: &Style,
+        sources: &SourceLayers,
+        index: bool,
+        deadline: Option<Deadline>,
+    ) {
+        for source in sources.keys() {
+            self.request_source_tile(coords, style, source, index, deadline);
+        }
+    }
+
+    fn request_source_tile(
+        &self,
+        coords: WorldTileCoords,
+        style: &Style,
+        source: &Option<String>,
+        index: bool,
+        deadline: Option<Deadline>,
+    ) {
+        self.request_source_layers(coords, style, source, None, index, deadline);
+    }
+
+    /// Requests the `layers` of the `source` of the tile at `coords`, or all of its layers which
+    /// are visible in the `style` if `None`.
+    fn request_source_layers(
+        &self,
+        coords: WorldTileCoords,
+        style: &Style,
+        source: &Option<String>,
+        layers: Option<HashSet<String>>,
+        index: bool,
+        deadline: Option<Deadline>,
+    ) {
+        self.call(Input::TileRequest {
+            coords,
+            style: style.clone(), // TODO: Avoid cloning whole style
+            source: source.clone(),
+            layers,
+            pixel_ratio: 1.0,
+            index,
+            deadline,
+            frame: None,
+        });
+    }
+
+    fn call(&self, input: Input) {
+        self.kernel
+            .apc()
+            .call(
+                input,
+                fetch_vector_apc::<
+                    E::OffscreenKernelEnvironment,
+                    T,
+                    <E::AsyncProcedureCall as AsyncProcedureCall<E::OffscreenKernelEnvironment>>::Context,
+                >,
+            )
+            .unwrap(); // TODO: Remove unwrap
+    }
+}
+
+/// The refresh intervals of the vector sources which are used by the `style`, keyed like
+/// [`SourceLayers`]. Sources which are not refreshed are left out.
+fn refresh_intervals(style: &Style) -> HashMap<Option<String>, Duration> {
+    style
+        .layers
+        .iter()
+        .filter(|layer| is_tessellated(layer))
+        .filter_map(|layer| {
+            let source = layer.source.as_ref()?;
+            match style.sources.get(source)? {
+                Source::Vector(VectorSource {
+                    refresh_interval: Some(interval),
+                    ..
+                }) if *interval > 0 => {
+                    Some((Some(source.clone()), Duration::from_millis(*interval)))
+                }
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// Inserts the component of the tile at `coords`, whose layers are requested from the `sources`.
+/// Tiles without any layers to request are done right away.
+fn insert_requested_tile(world: &mut World, coords: WorldTileCoords, sources: &SourceLayers) {
+    world
+        .tiles
+        .spawn_mut(coords)
+        .unwrap()
+        .insert(VectorLayersDataComponent {
+            done: sources.is_empty(),
+            requested_at: sources
+                .keys()
+                .map(|source| (source.clone(), Instant::now()))
+                .collect(),
+            pending_requests: sources.len(),
+            ..VectorLayersDataComponent::default()
+        });
+}
+
+/// Records the request of the tile at `coords` from each of the `sources` if the [`RequestLog`]
+/// is recording.
+fn record_request(
+    world: &mut World,
+    sources: &SourceLayers,
+    coords: WorldTileCoords,
+    priority: RequestPriority,
+) {
+    let Some(log) = world.resources.get_mut::<RequestLog>() else { return; };
+    if !log.is_recording() {
+        return;
+    }
+
+    for (source, layers) in sources {
+        log.record(
+            RequestKind::Vector,
+            coords,
+            layers.iter().cloned(),
+            source.clone(),
+            priority,
+        );
+    }
+}
+
+/// The vector source with the id `source` in the `style`. Returns `None` for the default source.
+fn vector_source<'a>(style: &'a Style, source: Option<&str>) -> Option<&'a VectorSource> {
+    match style.sources.get(source?)? {
+        Source::Vector(source) => Some(source),
+        _ => None,
+    }
+}
+
+/// The URL of the tiles of the vector `source` in the `style`. Sources without tiles, like the
+/// default source, are requested from [`TessellateSource::default`].
+pub(crate) fn vector_source_type(style: &Style, source: Option<&str>) -> SourceType {
+    let tessellate_source =
+        match vector_source(style, source).and_then(|source| source.tiles.as_ref()) {
+            Some(tiles) => TessellateSource::from_template(tiles),
+            None => TessellateSource::default(),
+        };
+    SourceType::Tessellate(tessellate_source)
+}
+
+/// The CRS of the vector tiles of the `source`. The default source serves Web Mercator tiles.
+pub(crate) fn source_crs(style: &Style, source: Option<&str>) -> SourceCrs {
+    vector_source(style, source)
+        .and_then(|source| source.crs)
+        .unwrap_or_default()
+}
+
+/// The maximum amount of adjacent tiles which are fetched from the `source` with one request, see
+/// [`VectorSource::batch_size`]. Tiles which are reprojected are not batched, because each of them
+/// is assembled from several tiles of the source.
+fn batch_size(style: &Style, source: Option<&str>) -> Option<usize> {
+    if source_crs(style, source) != SourceCrs::WebMercator {
+        return None;
+    }
+
+    vector_source(style, source)
+        .and_then(|source| source.batch_size)
+        .filter(|batch_size| *batch_size > 1)
+        .map(|batch_size| batch_size as usize)
+}
+
+/// Groups the `coords` into batches of at most `batch_size` tiles. The tiles of a batch are
+/// within the same block of tiles, which is as square as the batch size allows. Batches are
+/// ordered by the position of their first tile in `coords`, so tiles which are requested first
+/// stay first.
+fn batch_adjacent(coords: Vec<WorldTileCoords>, batch_size: usize) -> Vec<Vec<WorldTileCoords>> {
+    let width = (batch_size as f64).sqrt().ceil().max(1.0) as i32;
+    let height = (batch_size as i32 / width).max(1);
+
+    let mut batches: Vec<((i32, i32), Vec<WorldTileCoords>)> = Vec::new();
+    for coords in coords {
+        let block = (coords.x.div_euclid(width), coords.y.div_euclid(height));
+        match batches.iter_mut().find(|(key, _)| *key == block) {
+            Some((_, batch)) => batch.push(coords),
+            None => batches.push((block, vec![coords])),
+        }
+    }
+
+    batches.into_iter().map(|(_, batch)| batch).collect()
+}
+
+/// Whether the style `layer` is rendered from tessellated vector data.
+fn is_tessellated(layer: &StyleLayer) -> bool {
+    layer.unsupported_type.is_none()
+        && matches!(
+            layer.paint,
+            Some(LayerPaint::Fill(_)) | Some(LayerPaint::Line(_))
+        )
+}
+
+/// The source layers which are requested from each vector source, see
+/// [`requested_source_layers`]. `None` stands for the default source.
+pub(crate) type SourceLayers = BTreeMap<Option<String>, HashSet<String>>;
+
+/// The source layers which need to be requested from vector tiles at the `zoom_level` for the
+/// `style`, grouped by their vector source. Layers which belong to a source that is not a vector
+/// source, are skipped as the vector tile can never provide them. Layers without a source are
+/// requested from the default source. Layers which are not visible at the `zoom_level` are skipped
+/// as well.
+pub(crate) fn requested_source_layers(style: &Style, zoom_level: ZoomLevel) -> SourceLayers {
+    style
+        .layers
+        .iter()
+        .filter(|layer| is_tessellated(layer))
+        .filter(|layer| layer.is_visible_at(zoom_level))
+        .filter(|layer| match &layer.source {
+            Some(source) => {
+                let is_vector = matches!(style.sources.get(source), Some(Source::Vector(_)));
+                if !is_vector {
+                    log::debug!(
+                        "layer {} is not requested, because {source} is no vector source",
+                        layer.id
+                    );
+                }
+                is_vector
+            }
+            None => true,
+        })
+        .filter_map(|layer| Some((layer.source.clone(), layer.source_layer.clone()?)))
+        .fold(
+            SourceLayers::new(),
+            |mut sources, (source, source_layer)| {
+                sources.entry(source).or_default().insert(source_layer);
+                sources
+            },
+        )
+}
+
+/// The source-layers of the requested `sources` which are drawn by the style `layers` with the
+/// given ids.
+fn drawn_source_layers(
+    style: &Style,
+    sources: &SourceLayers,
+    layers: &HashSet<String>,
+) -> SourceLayers {
+    let mut drawn = SourceLayers::new();
+    for layer in &style.layers {
+        let Some(source_layer) = layer
+            .source_layer
+            .as_ref()
+            .filter(|_| layers.contains(&layer.id)) else { continue; };
+        let is_requested = sources
+            .get(&layer.source)
+            .map_or(false, |source_layers| source_layers.contains(source_layer));
+
+        if is_requested {
+            drawn
+                .entry(layer.source.clone())
+                .or_default()
+                .insert(source_layer.clone());
+        }
+    }
+    drawn
+}
+
+/// The source-layers of the `sources` which the tile of the `component` neither has nor has been
+/// told are missing, including the layers of a refresh which is in progress.
+fn missing_source_layers(
+    component: &VectorLayersDataComponent,
+    sources: &SourceLayers,
+) -> SourceLayers {
+    let layers = component
+        .layers
+        .iter()
+        .chain(component.pending_layers.iter().flatten())
+        .collect::<Vec<_>>();
+
+    sources
+        .iter()
+        .filter_map(|(source, source_layers)| {
+            let missing: HashSet<String> = source_layers
+                .iter()
+                .filter(|source_layer| {
+                    !layers
+                        .iter()
+                        .any(|layer| layer.is_layer(source.as_deref(), source_layer))
+                })
+                .cloned()
+                .collect();
+
+            (!missing.is_empty()).then(|| (source.clone(), missing))
+        })
+        .collect()
+}
+
+fn needs_refresh(requested_at: Instant, now: Instant, interval: Duration) -> bool {
+    now.saturating_duration_since(requested_at) >= interval
+}
+
+/// The `sources` of the tile of the `component` whose refresh interval has elapsed at `now`.
+fn due_sources(
+    component: &VectorLayersDataComponent,
+    sources: &SourceLayers,
+    intervals: &HashMap<Option<String>, Duration>,
+    now: Instant,
+) -> SourceLayers {
+    sources
+        .iter()
+        .filter(|(source, _)| {
+            let Some(interval) = intervals.get(*source) else { return false; };
+            component
+                .requested_at
+                .get(*source)
+                .map_or(false, |requested_at| {
+                    needs_refresh(*requested_at, now, *interval)
+                })
+        })
+        .map(|(source, layers)| (source.clone(), layers.clone()))
+        .collect()
+}
+
 pub fn fetch_vector_apc<
     K: OffscreenKernelEnvironment,
     T: VectorTransferables,
@@ -111,58 +746,950 @@ pub fn fetch_vector_apc<
     kernel: K,
 ) -> AsyncProcedureFuture {
     Box::pin(async move {
-        let Input::TileRequest {coords, style} = input else {
-            return Err(ProcedureError::IncompatibleInput)
+        let (coords, style, source, layers, index, deadline) = match input {
+            Input::TileRequest {
+                coords,
+                style,
+                source,
+                layers,
+                index,
+                deadline,
+                ..
+            } => (vec![coords], style, source, layers, index, deadline),
+            Input::TileBatchRequest {
+                coords,
+                style,
+                source,
+                index,
+                deadline,
+            } => (coords, style, source, None, index, deadline),
         };
 
-        let fill_layers: HashSet<String> = style
-            .layers
-            .iter()
-            .filter_map(|layer| {
-                if matches!(layer.paint, Some(LayerPaint::Fill(_)))
-                    || matches!(layer.paint, Some(LayerPaint::Line(_)))
-                {
-                    layer.source_layer.clone()
-                } else {
-                    None
-                }
-            })
-            .collect();
+        // All tiles of a batch are at the same zoom level
+        let Some(zoom_level) = coords.first().map(|coords| coords.z) else { return Ok(()); };
+        let fill_layers = layers.unwrap_or_else(|| {
+            requested_source_layers(&style, zoom_level)
+                .remove(&source)
+                .unwrap_or_default()
+        });
+
+        // The tiles are finished, such that they do not keep loading
+        if fill_layers.is_empty() {
+            for coords in coords {
+                send_failed::<T, C>(
+                    &context,
+                    coords,
+                    source.as_deref(),
+                    &fill_layers,
+                    LayerMissingReason::Missing,
+                )?;
+            }
+            return Ok(());
+        }
+
+        // The view moved on while the request was waiting to be fetched
+        if deadline.map_or(false, |deadline| deadline.has_passed()) {
+            for coords in coords {
+                log::debug!("deadline of tile at {coords} passed before fetching");
+                send_missing::<T, C>(
+                    &context,
+                    coords,
+                    source.as_deref(),
+                    &fill_layers,
+                    LayerMissingReason::DeadlineExceeded,
+                )?;
+            }
+            return Ok(());
+        }
 
         let client = kernel.source_client();
+        let source_type = vector_source_type(&style, source.as_deref());
+        let crs = source_crs(&style, source.as_deref());
+        let results = match &coords[..] {
+            [coords] => vec![fetch_covering_tiles(&client, &source_type, *coords, crs).await],
+            coords => client
+                .fetch_batch(coords, &source_type)
+                .await
+                .into_iter()
+                .zip(coords)
+                .map(|(result, coords)| result.map(|data| vec![(*coords, data)]))
+                .collect(),
+        };
 
-        if !fill_layers.is_empty() {
-            let context = context.clone();
-            let source = SourceType::Tessellate(TessellateSource::default());
-            match client.fetch(&coords, &source).await {
-                Ok(data) => {
-                    let data = data.into_boxed_slice();
+        let tessellators = kernel
+            .tessellators()
+            .with_line_layouts(&style)
+            .with_source_settings(&style);
 
-                    let mut pipeline_context = ProcessVectorContext::<T, C>::new(context);
-                    process_vector_tile(
-                        &data,
+        // The tiles of a batch are processed like separately fetched tiles
+        let process_tile = |coords: WorldTileCoords,
+                            result: Result<Vec<(WorldTileCoords, Vec<u8>)>, SourceFetchError>|
+         -> Result<(), ProcedureError> {
+            match result {
+                // The source is misconfigured and serves raster tiles
+                Ok(tiles)
+                    if tiles
+                        .iter()
+                        .any(|(_, data)| TileFormat::sniff(data) == Some(TileFormat::Raster)) =>
+                {
+                    log::warn!("tile at {coords} is a raster image and can not be tessellated");
+                    send_failed::<T, C>(
+                        &context,
+                        coords,
+                        source.as_deref(),
+                        &fill_layers,
+                        LayerMissingReason::Missing,
+                    )
+                }
+                Ok(tiles) => {
+                    let tiles = tiles
+                        .iter()
+                        .map(|(coords, data)| (*coords, data.as_slice()))
+                        .collect::<Vec<_>>();
+
+                    let mut pipeline_context = ProcessVectorContext::<T, C>::new(context.clone());
+                    let processed = process_vector_tiles(
+                        &tiles,
+                        crs,
                         VectorTileRequest {
                             coords,
-                            layers: fill_layers,
+                            source: source.clone(),
+                            layers: fill_layers.clone(),
+                            tessellators: tessellators.clone(),
+                            index,
+                            deadline,
                         },
                         &mut pipeline_context,
-                    )
-                    .map_err(|e| ProcedureError::Execution(Box::new(e)))?;
+                    );
+
+                    match processed {
+                        Ok(()) => Ok(()),
+                        Err(ProcessVectorError::SendError(e)) => Err(ProcedureError::Send(e)),
+                        Err(e) => {
+                            log::error!("tile at {coords} can not be processed: {e:?}");
+                            send_failed::<T, C>(
+                                &context,
+                                coords,
+                                source.as_deref(),
+                                &fill_layers,
+                                LayerMissingReason::DecodeFailed,
+                            )
+                        }
+                    }
                 }
                 Err(e) => {
                     log::error!("{e:?}");
-                    for to_load in &fill_layers {
-                        context
-                            .send(<T as VectorTransferables>::LayerMissing::build_from(
-                                coords,
-                                to_load.to_string(),
-                            ))
-                            .map_err(ProcedureError::Send)?;
-                    }
+                    let reason = if e.is_too_large() {
+                        LayerMissingReason::TooLarge
+                    } else {
+                        LayerMissingReason::FetchFailed
+                    };
+                    send_failed::<T, C>(&context, coords, source.as_deref(), &fill_layers, reason)
                 }
             }
+        };
+
+        // A tile which fails does not stop the other tiles of the batch. The first error is
+        // returned once all tiles have been processed.
+        let mut first_error = None;
+        for (coords, result) in coords.into_iter().zip(results) {
+            if let Err(e) = process_tile(coords, result) {
+                log::error!("tile at {coords} of the batch failed: {e:?}");
+                first_error.get_or_insert(e);
+            }
         }
 
-        Ok(())
+        first_error.map_or(Ok(()), Err)
     })
 }
+
+/// Fetches the tiles of a source in the `crs` which cover the Web Mercator tile at `coords`, see
+/// [`SourceCrs::covering_tiles`]. Fails if any of them can not be fetched.
+pub(crate) async fn fetch_covering_tiles<HC: HttpClient>(
+    client: &SourceClient<HC>,
+    source_type: &SourceType,
+    coords: WorldTileCoords,
+    crs: SourceCrs,
+) -> Result<Vec<(WorldTileCoords, Vec<u8>)>, SourceFetchError> {
+    let mut tiles = Vec::new();
+    for coords in crs.covering_tiles(coords) {
+        let data = client.fetch(&coords, source_type).await?;
+        tiles.push((coords, data));
+    }
+    Ok(tiles)
+}
+
+/// Reports the `layers` of the `source` in the tile at `coords` as missing.
+fn send_missing<T: VectorTransferables, C: Context>(
+    context: &C,
+    coords: WorldTileCoords,
+    source: Option<&str>,
+    layers: &HashSet<String>,
+    reason: LayerMissingReason,
+) -> Result<(), ProcedureError> {
+    for to_load in layers {
+        context
+            .send(<T as VectorTransferables>::LayerMissing::build_from(
+                coords,
+                source.map(str::to_owned),
+                to_load.to_string(),
+                reason,
+            ))
+            .map_err(ProcedureError::Send)?;
+    }
+    Ok(())
+}
+
+/// Reports the `layers` of the tile at `coords` as missing and finishes the request, such that the
+/// tile does not stay loading. Used if the tile could not be fetched or decoded.
+fn send_failed<T: VectorTransferables, C: Context>(
+    context: &C,
+    coords: WorldTileCoords,
+    source: Option<&str>,
+    layers: &HashSet<String>,
+    reason: LayerMissingReason,
+) -> Result<(), ProcedureError> {
+    send_missing::<T, C>(context, coords, source, layers, reason)?;
+    context
+        .send(<T as VectorTransferables>::TileTessellated::build_from(
+            coords,
+        ))
+        .map_err(ProcedureError::Send)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::{HashMap, HashSet},
+        time::Duration,
+    };
+
+    use instant::Instant;
+
+    use super::{
+        batch_adjacent, batch_size, drawn_source_layers, due_sources, missing_source_layers,
+        needs_refresh, refresh_intervals, requested_source_layers, source_crs, SourceLayers,
+    };
+    use crate::{
+        coords::{WorldTileCoords, ZoomLevel},
+        projection::SourceCrs,
+        style::{
+            layer::{FillPaint, LayerPaint, LinePaint, StyleLayer, Visibility},
+            raster::RasterLayer,
+            source::{Source, VectorSource},
+            Style, StyleBuilder,
+        },
+        vector::{
+            LayerMissingReason, MissingVectorLayerData, VectorLayerData, VectorLayersDataComponent,
+        },
+    };
+
+    #[test]
+    fn test_needs_refresh() {
+        let interval = Duration::from_millis(500);
+        let requested_at = Instant::now();
+
+        assert!(!needs_refresh(requested_at, requested_at, interval));
+        assert!(!needs_refresh(
+            requested_at,
+            requested_at + Duration::from_millis(499),
+            interval
+        ));
+        assert!(needs_refresh(
+            requested_at,
+            requested_at + Duration::from_millis(500),
+            interval
+        ));
+    }
+
+    #[test]
+    fn test_refresh_intervals() {
+        let mut style = Style::default();
+        assert!(refresh_intervals(&style).is_empty());
+
+        let source = |refresh_interval: u64| {
+            serde_json::from_value::<Source>(serde_json::json!({
+                "type": "vector",
+                "refresh-interval": refresh_interval
+            }))
+            .unwrap()
+        };
+
+        for layer in &mut style.layers {
+            layer.source = Some("live".to_string());
+        }
+
+        style.sources.insert("live".to_string(), source(0));
+        assert!(refresh_intervals(&style).is_empty());
+
+        style.sources.insert("live".to_string(), source(1000));
+        assert_eq!(
+            refresh_intervals(&style),
+            HashMap::from([(Some("live".to_string()), Duration::from_secs(1))])
+        );
+    }
+
+    #[test]
+    fn test_due_sources() {
+        let start = Instant::now();
+        let live = Some("live".to_string());
+        let weather = Some("weather".to_string());
+        let sources = SourceLayers::from([
+            (None, HashSet::from(["water".to_string()])),
+            (live.clone(), HashSet::from(["traffic".to_string()])),
+            (weather.clone(), HashSet::from(["radar".to_string()])),
+        ]);
+        let intervals = HashMap::from([
+            (live.clone(), Duration::from_secs(1)),
+            (weather.clone(), Duration::from_secs(5)),
+        ]);
+        let mut component = VectorLayersDataComponent {
+            done: true,
+            requested_at: sources
+                .keys()
+                .map(|source| (source.clone(), start))
+                .collect(),
+            ..VectorLayersDataComponent::default()
+        };
+        let due = |component: &VectorLayersDataComponent, elapsed: u64| {
+            due_sources(
+                component,
+                &sources,
+                &intervals,
+                start + Duration::from_secs(elapsed),
+            )
+            .into_keys()
+            .collect::<Vec<_>>()
+        };
+
+        assert!(due(&component, 0).is_empty());
+
+        // Each source is refreshed after its own interval, the default source never
+        assert_eq!(due(&component, 1), vec![live.clone()]);
+        component
+            .requested_at
+            .insert(live.clone(), start + Duration::from_secs(1));
+        assert!(due(&component, 1).is_empty());
+        assert_eq!(due(&component, 5), vec![live, weather]);
+    }
+
+    #[test]
+    fn test_source_crs() {
+        let mut style = Style::default();
+        assert_eq!(source_crs(&style, None), SourceCrs::WebMercator);
+
+        let source = |crs: &str| {
+            serde_json::from_value::<Source>(serde_json::json!({
+                "type": "vector",
+                "crs": crs
+            }))
+            .unwrap()
+        };
+        style
+            .sources
+            .insert("grid".to_string(), source("EPSG:4326"));
+        style
+            .sources
+            .insert("tiles".to_string(), source("EPSG:3857"));
+
+        // Each source keeps its own CRS
+        assert_eq!(source_crs(&style, Some("grid")), SourceCrs::Wgs84);
+        assert_eq!(source_crs(&style, Some("tiles")), SourceCrs::WebMercator);
+        assert_eq!(source_crs(&style, None), SourceCrs::WebMercator);
+    }
+
+    #[test]
+    fn test_batch_adjacent() {
+        let mut style = Style::default();
+        assert_eq!(batch_size(&style, None), None);
+
+        let source = serde_json::from_value::<Source>(serde_json::json!({
+            "type": "vector",
+            "batch-size": 4
+        }))
+        .unwrap();
+        style.sources.insert("batched".to_string(), source);
+        assert_eq!(batch_size(&style, Some("batched")), Some(4));
+
+        // Reprojected tiles are not batched
+        let source = serde_json::from_value::<Source>(serde_json::json!({
+            "type": "vector",
+            "batch-size": 4,
+            "crs": "EPSG:4326"
+        }))
+        .unwrap();
+        style.sources.insert("grid".to_string(), source);
+        assert_eq!(batch_size(&style, Some("grid")), None);
+
+        let tile = |x, y| WorldTileCoords::from((x, y, ZoomLevel::from(3)));
+
+        // Batches of four tiles cover blocks of 2x2 tiles
+        assert_eq!(
+            batch_adjacent(vec![tile(2, 2), tile(4, 2), tile(3, 3), tile(2, 1)], 4),
+            vec![
+                vec![tile(2, 2), tile(3, 3)],
+                vec![tile(4, 2)],
+                vec![tile(2, 1)]
+            ]
+        );
+
+        // Batches of two tiles cover pairs of horizontally adjacent tiles
+        assert_eq!(
+            batch_adjacent(vec![tile(0, 0), tile(1, 0), tile(0, 1)], 2),
+            vec![vec![tile(0, 0), tile(1, 0)], vec![tile(0, 1)]]
+        );
+
+        // Batches of six tiles cover blocks of 3x2 tiles
+        assert_eq!(
+            batch_adjacent(vec![tile(0, 0), tile(3, 0), tile(2, 1), tile(0, 2)], 6),
+            vec![
+                vec![tile(0, 0), tile(2, 1)],
+                vec![tile(3, 0)],
+                vec![tile(0, 2)]
+            ]
+        );
+
+        // Blocks of negative coordinates, e.g. of wrapped worlds, do not include the tiles at zero
+        assert_eq!(
+            batch_adjacent(vec![tile(-1, 0), tile(0, 0), tile(-2, 0), tile(-1, -1)], 4),
+            vec![
+                vec![tile(-1, 0), tile(-2, 0)],
+                vec![tile(0, 0)],
+                vec![tile(-1, -1)]
+            ]
+        );
+
+        // Without batching each tile is fetched on its own, in the order of the requests
+        assert_eq!(
+            batch_adjacent(vec![tile(1, 0), tile(0, 0), tile(1, 1)], 1),
+            vec![vec![tile(1, 0)], vec![tile(0, 0)], vec![tile(1, 1)]]
+        );
+    }
+
+    #[test]
+    fn test_requested_source_layers() {
+        let layer = |id: &str, paint: LayerPaint, source: Option<&str>, source_layer: &str| {
+            StyleLayer {
+                id: id.to_string(),
+                paint: Some(paint),
+                source: source.map(|source| source.to_string()),
+                source_layer: Some(source_layer.to_string()),
+                ..StyleLayer::default()
+            }
+        };
+        let fill = || {
+            LayerPaint::Fill(FillPaint {
+                fill_color: None,
+                fill_pattern: None,
+            })
+        };
+        let line = || LayerPaint::Line(LinePaint { line_color: None });
+
+        let mut style = Style {
+            sources: HashMap::from([
+                (
+                    "vector".to_string(),
+                    serde_json::from_value::<Source>(serde_json::json!({ "type": "vector" }))
+                        .unwrap(),
+                ),
+                (
+                    "raster".to_string(),
+                    serde_json::from_value::<Source>(serde_json::json!({ "type": "raster" }))
+                        .unwrap(),
+                ),
+            ]),
+            layers: vec![
+                layer("water", fill(), Some("vector"), "water"),
+                layer("water-outline", line(), Some("vector"), "water"),
+                layer("roads", line(), None, "transportation"),
+                layer("hillshade", fill(), Some("raster"), "hillshade"),
+                layer("unknown", fill(), Some("unknown"), "unknown"),
+                StyleLayer {
+                    unsupported_type: Some("heatmap".to_string()),
+                    ..layer("poi-heat", fill(), Some("vector"), "poi")
+                },
+                layer(
+                    "satellite",
+                    LayerPaint::Raster(RasterLayer::default()),
+                    Some("raster"),
+                    "raster",
+                ),
+            ],
+            ..Style::default()
+        };
+
+        let zoom_level = ZoomLevel::from(14);
+        let sources = requested_source_layers(&style, zoom_level);
+        assert_eq!(sources.len(), 2);
+        assert_eq!(
+            sources[&None],
+            HashSet::from(["transportation".to_string()])
+        );
+        assert_eq!(
+            sources[&Some("vector".to_string())],
+            HashSet::from(["water".to_string()])
+        );
+
+        style.layers.clear();
+        assert!(requested_source_layers(&style, zoom_level).is_empty());
+    }
+
+    #[test]
+    fn test_missing_shown_layers() {
+        let layer = |id: &str, source: Option<&str>, source_layer: &str| StyleLayer {
+            id: id.to_string(),
+            paint: Some(LayerPaint::Line(LinePaint { line_color: None })),
+            source: source.map(|source| source.to_string()),
+            source_layer: Some(source_layer.to_string()),
+            ..StyleLayer::default()
+        };
+        let style = Style {
+            sources: HashMap::from([(
+                "streets".to_string(),
+                serde_json::from_value::<Source>(serde_json::json!({ "type": "vector" })).unwrap(),
+            )]),
+            layers: vec![
+                layer("water", None, "water"),
+                layer("roads", Some("streets"), "roads"),
+                layer("rail", Some("streets"), "rail"),
+            ],
+            ..Style::default()
+        };
+        let sources = requested_source_layers(&style, ZoomLevel::from(14));
+
+        // Only the source-layers of the shown layers are requested
+        let shown = drawn_source_layers(
+            &style,
+            &sources,
+            &HashSet::from(["roads".to_string(), "unknown".to_string()]),
+        );
+        let streets = Some("streets".to_string());
+        assert_eq!(shown.len(), 1);
+        assert_eq!(shown[&streets], HashSet::from(["roads".to_string()]));
+
+        // Tiles which already have the source-layer, e.g. because another layer draws it, are not
+        // requested again
+        let coords = WorldTileCoords::from((0, 0, ZoomLevel::from(14)));
+        let missing = |source: Option<&str>, source_layer: &str| {
+            VectorLayerData::Missing(MissingVectorLayerData {
+                coords,
+                source: source.map(|source| source.to_string()),
+                source_layer: source_layer.to_string(),
+                reason: LayerMissingReason::Missing,
+            })
+        };
+        let mut component = VectorLayersDataComponent {
+            done: true,
+            layers: vec![missing(None, "roads"), missing(Some("streets"), "rail")],
+            ..VectorLayersDataComponent::default()
+        };
+        assert_eq!(missing_source_layers(&component, &shown), shown);
+
+        // The layers of a refresh which is in progress are considered as well
+        component.pending_layers = Some(vec![missing(Some("streets"), "roads")]);
+        assert!(missing_source_layers(&component, &shown).is_empty());
+    }
+
+    #[test]
+    fn test_skip_layers_outside_zoom_range() {
+        let style = Style {
+            layers: vec![
+                StyleLayer {
+                    id: "buildings".to_string(),
+                    minzoom: Some(10),
+                    paint: Some(LayerPaint::Fill(FillPaint {
+                        fill_color: None,
+                        fill_pattern: None,
+                    })),
+                    source_layer: Some("building".to_string()),
+                    ..StyleLayer::default()
+                },
+                StyleLayer {
+                    id: "countries".to_string(),
+                    maxzoom: Some(6),
+                    paint: Some(LayerPaint::Line(LinePaint { line_color: None })),
+                    source_layer: Some("boundary".to_string()),
+                    ..StyleLayer::default()
+                },
+            ],
+            ..Style::default()
+        };
+
+        let layers = |z: u8| {
+            let mut layers = requested_source_layers(&style, ZoomLevel::from(z))
+                .remove(&None)
+                .unwrap_or_default()
+                .into_iter()
+                .collect::<Vec<_>>();
+            layers.sort();
+            layers
+        };
+        assert_eq!(layers(5), vec!["boundary"]);
+        assert!(layers(6).is_empty());
+        assert_eq!(layers(10), vec!["building"]);
+    }
+
+    #[test]
+    fn test_skip_hidden_layers() {
+        let mut style: Style = serde_json::from_value(serde_json::json!({
+            "version": 8,
+            "name": "Hidden Layers",
+            "metadata": {},
+            "sources": {},
+            "layers": [
+                {
+                    "id": "water",
+                    "type": "fill",
+                    "source-layer": "water",
+                    "paint": {"fill-color": "#aad3df"}
+                },
+                {
+                    "id": "buildings",
+                    "type": "fill",
+                    "source-layer": "building",
+                    "layout": {"visibility": "none"},
+                    "paint": {"fill-color": "#d9d0c9"}
+                }
+            ]
+        }))
+        .unwrap();
+
+        let zoom_level = ZoomLevel::from(14);
+        assert!(style.layers[1].is_hidden());
+        assert_eq!(style.hidden_layers().len(), 1);
+        assert_eq!(
+            requested_source_layers(&style, zoom_level)[&None],
+            HashSet::from(["water".to_string()])
+        );
+
+        assert!(style.set_layer_visibility("buildings", Visibility::Visible));
+        assert!(style.hidden_layers().is_empty());
+        assert_eq!(
+            requested_source_layers(&style, zoom_level)[&None],
+            HashSet::from(["building".to_string(), "water".to_string()])
+        );
+
+        assert!(!style.set_layer_visibility("unknown", Visibility::None));
+    }
+
+    #[test]
+    fn test_skip_source_outside_zoom_range() {
+        let style = StyleBuilder::new()
+            .add_source("detail", Source::Vector(VectorSource::default()))
+            .add_layer(StyleLayer {
+                id: "buildings".to_string(),
+                minzoom: Some(12),
+                paint: Some(LayerPaint::Fill(FillPaint {
+                    fill_color: None,
+                    fill_pattern: None,
+                })),
+                source: Some("detail".to_string()),
+                source_layer: Some("building".to_string()),
+                ..StyleLayer::default()
+            })
+            .build()
+            .unwrap();
+
+        assert!(requested_source_layers(&style, ZoomLevel::from(3)).is_empty());
+        assert!(!requested_source_layers(&style, ZoomLevel::from(12)).is_empty());
+    }
+
+    #[cfg(all(feature = "headless", feature = "thread-safe-futures"))]
+    #[tokio::test]
+    async fn test_fetch_with_kernel_source_client() {
+        use std::sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        };
+
+        use geozero::mvt::{Message, Tile};
+
+        use super::fetch_vector_apc;
+        use crate::{
+            headless::{environment::HeadlessEnvironment, window::HeadlessMapWindowConfig},
+            io::{
+                apc::{AsyncProcedureCall, Input, SchedulerAsyncProcedureCall},
+                source_client::SourceFetchError,
+            },
+            kernel::{Kernel, KernelBuilder},
+            platform::{http_client::ReqwestHttpClient, scheduler::TokioScheduler},
+            vector::DefaultVectorTransferables,
+            window::WindowSize,
+        };
+
+        // The tile is only available from the generator of the kernel
+        let generated = Arc::new(AtomicUsize::new(0));
+        let counter = generated.clone();
+        let kernel: Kernel<HeadlessEnvironment> = KernelBuilder::new()
+            .with_map_window_config(HeadlessMapWindowConfig::new(
+                WindowSize::new(64, 64).unwrap(),
+            ))
+            .with_http_client(ReqwestHttpClient::new(None))
+            .with_apc(SchedulerAsyncProcedureCall::new(TokioScheduler::new()))
+            .with_scheduler(TokioScheduler::new())
+            .with_tile_generator(move |_coords: WorldTileCoords| {
+                counter.fetch_add(1, Ordering::SeqCst);
+                async { Ok::<_, SourceFetchError>(Tile::default().encode_to_vec()) }
+            })
+            .build();
+
+        let style = StyleBuilder::new()
+            .add_layer(StyleLayer {
+                id: "water".to_string(),
+                paint: Some(LayerPaint::Fill(FillPaint {
+                    fill_color: None,
+                    fill_pattern: None,
+                })),
+                source_layer: Some("water".to_string()),
+                ..StyleLayer::default()
+            })
+            .build()
+            .unwrap();
+
+        kernel
+            .apc()
+            .call(
+                Input::TileRequest {
+                    coords: WorldTileCoords::from((0, 0, ZoomLevel::default())),
+                    style,
+                    source: None,
+                    layers: None,
+                    pixel_ratio: 1.0,
+                    index: false,
+                    deadline: None,
+                    frame: None,
+                },
+                fetch_vector_apc::<_, DefaultVectorTransferables, _>,
+            )
+            .unwrap();
+
+        // The procedure reports the layers of the tile once it has been processed
+        let mut received = 0;
+        for _ in 0..100 {
+            received += kernel.apc().receive(|_| true).count();
+            if received > 0 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        assert!(received > 0);
+        assert_eq!(generated.load(Ordering::SeqCst), 1);
+        assert_eq!(kernel.io_stats().requested, 1);
+    }
+
+    #[tokio::test]
+    async fn test_batch_continues_after_failed_tile() {
+        use std::sync::{Arc, Mutex};
+
+        use geozero::mvt::{Message as _, Tile};
+
+        use super::fetch_vector_apc;
+        use crate::{
+            environment::OffscreenKernelEnvironment,
+            io::{
+                apc::{Context, Input, IntoMessage, Message, ProcedureError, SendError},
+                source_client::{HttpSourceClient, SourceClient, SourceFetchError},
+            },
+            platform::{http_client::ReqwestHttpClient, ReqwestOffscreenKernelEnvironment},
+            vector::{DefaultVectorTransferables, TileTessellated, VectorTransferables},
+        };
+
+        type Finished = <DefaultVectorTransferables as VectorTransferables>::TileTessellated;
+
+        /// Fails to send the first message and records the tiles which have been finished
+        #[derive(Clone, Default)]
+        struct FlakyContext {
+            finished: Arc<Mutex<Option<Vec<WorldTileCoords>>>>,
+        }
+
+        impl Context for FlakyContext {
+            fn send<T: IntoMessage>(&self, message: T) -> Result<(), SendError> {
+                let mut finished = self.finished.lock().unwrap();
+                if finished.is_none() {
+                    *finished = Some(Vec::new());
+                    return Err(SendError::Transmission);
+                }
+
+                let message: Message = IntoMessage::into(message);
+                if message.has_tag(Finished::message_tag()) {
+                    let coords = message.into_transferable::<Finished>().coords();
+                    finished.as_mut().unwrap().push(coords);
+                }
+                Ok(())
+            }
+        }
+
+        let kernel = ReqwestOffscreenKernelEnvironment::create(Some(SourceClient::new(
+            HttpSourceClient::new(ReqwestHttpClient::new(None)).with_tile_generator(
+                |_coords: WorldTileCoords| async {
+                    Ok::<_, SourceFetchError>(Tile::default().encode_to_vec())
+                },
+            ),
+        )));
+        let style = StyleBuilder::new()
+            .add_layer(StyleLayer {
+                id: "water".to_string(),
+                paint: Some(LayerPaint::Fill(FillPaint {
+                    fill_color: None,
+                    fill_pattern: None,
+                })),
+                source_layer: Some("water".to_string()),
+                ..StyleLayer::default()
+            })
+            .build()
+            .unwrap();
+        let tile = |x| WorldTileCoords::from((x, 0, ZoomLevel::from(1)));
+
+        let context = FlakyContext::default();
+        let result = fetch_vector_apc::<_, DefaultVectorTransferables, _>(
+            Input::TileBatchRequest {
+                coords: vec![tile(0), tile(1)],
+                style,
+                source: None,
+                index: false,
+                deadline: None,
+            },
+            context.clone(),
+            kernel,
+        )
+        .await;
+
+        // The error of the first tile is reported after the second tile has been finished
+        assert!(matches!(result, Err(ProcedureError::Send(_))));
+        let finished = context.finished.lock().unwrap().take().unwrap();
+        assert_eq!(finished, vec![tile(1)]);
+    }
+
+    #[cfg(all(feature = "headless", feature = "thread-safe-futures"))]
+    #[tokio::test]
+    async fn test_max_requests_per_frame() {
+        use cgmath::Vector3;
+
+        use crate::{
+            headless::{
+                map::HeadlessMap,
+                tests::{kernel_builder, water_map, water_tile},
+            },
+            io::{
+                request_log::RequestLog, request_settings::RequestSettings,
+                source_client::SourceFetchError,
+            },
+        };
+
+        let mut map = water_map(kernel_builder(1024).with_tile_generator(
+            |_coords: WorldTileCoords| async { Ok::<_, SourceFetchError>(water_tile()) },
+        ))
+        .await;
+        map.world_mut().resources.insert(RequestSettings {
+            max_requests_per_frame: Some(4),
+            ..RequestSettings::default()
+        });
+        map.world_mut().start_request_log();
+
+        // Renders a frame and returns the tiles which have been requested in it
+        let render = |map: &mut HeadlessMap| {
+            let log = map.world_mut().resources.get::<RequestLog>().unwrap();
+            let before = log.requests().len();
+
+            map.render().unwrap();
+
+            let log = map.world_mut().resources.get::<RequestLog>().unwrap();
+            log.requests()[before..]
+                .iter()
+                .map(|request| request.coords)
+                .collect::<Vec<_>>()
+        };
+
+        // More tiles are in view than are requested in the first frame
+        assert_eq!(render(&mut map).len(), 4);
+
+        // The view moves far away while requests are deferred
+        map.view_state_mut()
+            .camera_mut()
+            .move_relative(Vector3::new(100000.0, 0.0, 0.0));
+
+        let mut requested = Vec::new();
+        for _ in 0..100 {
+            let frame = render(&mut map);
+            assert!(frame.len() <= 4);
+            if frame.is_empty() {
+                break;
+            }
+            requested.extend(frame);
+        }
+
+        // Deferred tiles of the previous view are not requested anymore
+        let view_region = map.view_state_mut().create_view_region().unwrap();
+        assert!(requested
+            .iter()
+            .all(|coords| view_region.is_in_view(coords)));
+        assert!(view_region.iter().all(|coords| requested.contains(&coords)));
+    }
+
+    #[cfg(all(feature = "headless", feature = "thread-safe-futures"))]
+    #[tokio::test]
+    async fn test_failed_tiles_finish() {
+        use crate::{
+            headless::tests::{kernel_builder, water_map},
+            io::{source_client::SourceFetchError, tile_generator::TileGenerator},
+        };
+
+        /// Renders the water style through the request system, which fetches the tiles from the
+        /// `generator`, until no tile is loading anymore. Returns the reasons why the layers of
+        /// the tiles are missing.
+        async fn render_until_finished(generator: impl TileGenerator) -> Vec<LayerMissingReason> {
+            let mut map = water_map(kernel_builder(64).with_tile_generator(generator)).await;
+
+            // The first frame requests the tiles in view
+            map.render().unwrap();
+            for _ in 0..100 {
+                if !map.world_mut().tiles.has_loading_tiles() {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                map.render().unwrap();
+            }
+
+            let tiles = &map.world_mut().tiles;
+            assert!(!tiles.has_loading_tiles());
+
+            let coords = tiles.loaded_coords();
+            assert!(!coords.is_empty());
+            assert!(coords.iter().all(|coords| tiles.has_errors(coords)));
+
+            coords
+                .into_iter()
+                .flat_map(|coords| {
+                    let component = tiles.query::<&VectorLayersDataComponent>(coords).unwrap();
+                    component
+                        .layers
+                        .iter()
+                        .filter_map(|layer| match layer {
+                            VectorLayerData::Missing(data) => Some(data.reason),
+                            VectorLayerData::Available(_) => None,
+                        })
+                        .collect::<Vec<_>>()
+                })
+                .collect()
+        }
+
+        // Tiles which can not be fetched, e.g. while offline
+        let reasons = render_until_finished(|_coords: WorldTileCoords| async {
+            Err::<Vec<u8>, _>(SourceFetchError("the network is unreachable".into()))
+        })
+        .await;
+        assert!(reasons
+            .iter()
+            .all(|reason| *reason == LayerMissingReason::FetchFailed));
+
+        // Tiles which can not be decoded
+        let reasons = render_until_finished(|_coords: WorldTileCoords| async {
+            Ok::<_, SourceFetchError>(b"not a tile".to_vec())
+        })
+        .await;
+        assert!(reasons
+            .iter()
+            .all(|reason| *reason == LayerMissingReason::DecodeFailed));
+    }
+}
@@ -18,7 +18,7 @@ use crate::{
     },
     style::layer::StyleLayer,
     tcs::world::World,
-    tessellation::OverAlignedVertexBuffer,
+    tessellation::{pack_indices, OverAlignedVertexBuffer},
 };
 
 // TODO: Too low values can cause a back-and-forth between unloading and loading layers
@@ -72,7 +72,9 @@ impl<B> BackingBuffer<B> {
     }
 }
 
-impl<V: Pod, I: Pod, TM: Pod, FM: Pod> BufferPool<wgpu::Queue, wgpu::Buffer, V, I, TM, FM> {
+impl<V: Pod, I: Pod + Into<u32>, TM: Pod, FM: Pod>
+    BufferPool<wgpu::Queue, wgpu::Buffer, V, I, TM, FM>
+{
     pub fn from_device(device: &wgpu::Device) -> Self {
         let vertex_buffer_desc = wgpu::BufferDescriptor {
             label: Some("vertex buffer"),
@@ -122,7 +124,7 @@ impl<V: Pod, I: Pod, TM: Pod, FM: Pod> BufferPool<wgpu::Queue, wgpu::Buffer, V,
         )
     }
 }
-impl<Q: Queue<B>, B, V: Pod, I: Pod, TM: Pod, FM: Pod> BufferPool<Q, B, V, I, TM, FM> {
+impl<Q: Queue<B>, B, V: Pod, I: Pod + Into<u32>, TM: Pod, FM: Pod> BufferPool<Q, B, V, I, TM, FM> {
     pub fn new(
         vertices: BackingBufferDescriptor<B>,
         indices: BackingBufferDescriptor<B>,
@@ -163,6 +165,13 @@ impl<Q: Queue<B>, B, V: Pod, I: Pod, TM: Pod, FM: Pod> BufferPool<Q, B, V, I, TM
         self.index.clear()
     }
 
+    /// Releases the space of all layers of the tile at `coords`, such that it can be reused by
+    /// other tiles. The backing buffers are not written to, so commands which have already been
+    /// submitted can still read the previous data.
+    pub fn remove_tile(&mut self, coords: WorldTileCoords) {
+        self.index.remove(coords)
+    }
+
     #[cfg(test)]
     fn available_space(&self, typ: BackingBufferType) -> wgpu::BufferAddress {
         let gap = self.index.find_largest_gap(
@@ -214,11 +223,21 @@ impl<Q: Queue<B>, B, V: Pod, I: Pod, TM: Pod, FM: Pod> BufferPool<Q, B, V, I, TM
         (bytes, aligned_bytes)
     }
 
-    pub fn get_loaded_source_layers_at(&self, coords: WorldTileCoords) -> Option<HashSet<&str>> {
+    /// The source-layers which are loaded for the tile at `coords`, together with their source,
+    /// see [`VectorLayerData::is_layer`](crate::vector::VectorLayerData::is_layer).
+    pub fn get_loaded_source_layers_at(
+        &self,
+        coords: WorldTileCoords,
+    ) -> Option<HashSet<(Option<&str>, &str)>> {
         self.index.get_layers(coords).map(|layers| {
             layers
                 .iter()
-                .map(|entry| entry.style_layer.source_layer.as_ref().unwrap().as_str()) // TODO: Remove unwrap
+                .map(|entry| {
+                    (
+                        entry.style_layer.source.as_deref(),
+                        entry.style_layer.source_layer.as_ref().unwrap().as_str(), // TODO: Remove unwrap
+                    )
+                })
                 .collect()
         })
     }
@@ -228,6 +247,9 @@ impl<Q: Queue<B>, B, V: Pod, I: Pod, TM: Pod, FM: Pod> BufferPool<Q, B, V, I, TM
     /// * `layer_metadata` and
     /// * `feature_metadata` for a layer. This function is able to dynamically evict layers if there
     /// is not enough space available.
+    ///
+    /// The indices of the `geometry` are stored in its
+    /// [`index_format`](OverAlignedVertexBuffer::index_format).
     #[tracing::instrument(skip_all)]
     pub fn allocate_layer_geometry(
         &mut self,
@@ -239,7 +261,6 @@ impl<Q: Queue<B>, B, V: Pod, I: Pod, TM: Pod, FM: Pod> BufferPool<Q, B, V, I, TM
         feature_metadata: &[FM],
     ) {
         let vertices_stride = size_of::<V>() as wgpu::BufferAddress;
-        let indices_stride = size_of::<I>() as wgpu::BufferAddress;
         let layer_metadata_stride = size_of::<TM>() as wgpu::BufferAddress;
         let feature_metadata_stride = size_of::<FM>() as wgpu::BufferAddress;
 
@@ -248,10 +269,10 @@ impl<Q: Queue<B>, B, V: Pod, I: Pod, TM: Pod, FM: Pod> BufferPool<Q, B, V, I, TM
             geometry.buffer.vertices.len() as wgpu::BufferAddress,
             geometry.buffer.vertices.len() as wgpu::BufferAddress,
         );
-        let (indices_bytes, aligned_indices_bytes) = Self::align(
-            indices_stride,
-            geometry.buffer.indices.len() as wgpu::BufferAddress,
-            geometry.usable_indices as wgpu::BufferAddress,
+        let index_format = geometry.index_format();
+        let indices = pack_indices(
+            &geometry.buffer.indices[..geometry.usable_indices as usize],
+            index_format,
         );
         let (layer_metadata_bytes, aligned_layer_metadata_bytes) =
             Self::align(layer_metadata_stride, 1, 1);
@@ -278,10 +299,11 @@ impl<Q: Queue<B>, B, V: Pod, I: Pod, TM: Pod, FM: Pod> BufferPool<Q, B, V, I, TM
                 self.vertices.inner_size,
             ),
             buffer_indices: self.index.make_room(
-                indices_bytes,
+                indices.len() as wgpu::BufferAddress,
                 self.indices.typ,
                 self.indices.inner_size,
             ),
+            index_format,
             usable_indices: geometry.usable_indices,
             buffer_layer_metadata: self.index.make_room(
                 layer_metadata_bytes,
@@ -305,7 +327,7 @@ impl<Q: Queue<B>, B, V: Pod, I: Pod, TM: Pod, FM: Pod> BufferPool<Q, B, V, I, TM
         queue.write_buffer(
             &self.indices.inner,
             maybe_entry.buffer_indices.start,
-            &bytemuck::cast_slice(&geometry.buffer.indices)[0..aligned_indices_bytes as usize],
+            &indices,
         );
 
         queue.write_buffer(
@@ -389,7 +411,9 @@ pub struct IndexEntry {
     buffer_layer_metadata: Range<wgpu::BufferAddress>,
     // Range of bytes within the backing buffer for feature metadata
     buffer_feature_metadata: Range<wgpu::BufferAddress>,
-    // Amount of actually usable indices. Each index has the size/format `IndexDataType`.
+    // Format of the indices within `buffer_indices`
+    index_format: wgpu::IndexFormat,
+    // Amount of actually usable indices. Each index has the size/format `index_format`.
     // Can be lower than size(buffer_indices) / indices_stride because of alignment.
     usable_indices: u32,
 }
@@ -403,6 +427,10 @@ impl IndexEntry {
         self.buffer_indices.clone()
     }
 
+    pub fn index_format(&self) -> wgpu::IndexFormat {
+        self.index_format
+    }
+
     pub fn vertices_buffer_range(&self) -> Range<wgpu::BufferAddress> {
         self.buffer_vertices.clone()
     }
@@ -469,6 +497,14 @@ impl RingIndex {
             .flat_map(|key| self.tree_index.get(key).map(|entry| entry.layers.iter()))
     }
 
+    fn remove(&mut self, coords: WorldTileCoords) {
+        let Some(key) = coords.build_quad_key() else { return; };
+
+        if self.tree_index.remove(&key).is_some() {
+            self.linear_index.retain(|linear_key| *linear_key != key);
+        }
+    }
+
     fn pop_front(&mut self) -> Option<IndexEntry> {
         if let Some(entry) = self
             .linear_index
@@ -718,4 +754,46 @@ mod tests {
         println!("{:?}", pool.index);
         assert_eq!(0, pool.available_space(BackingBufferType::Vertices));
     }
+
+    #[test]
+    fn test_remove_tile() {
+        let mut pool: BufferPool<TestQueue, TestBuffer, TestVertex, u32, u32, u32> =
+            BufferPool::new(
+                BackingBufferDescriptor::new(TestBuffer { size: 128 }, 128),
+                BackingBufferDescriptor::new(TestBuffer { size: 128 }, 128),
+                BackingBufferDescriptor::new(TestBuffer { size: 128 }, 128),
+                BackingBufferDescriptor::new(TestBuffer { size: 128 }, 128),
+            );
+
+        let queue = TestQueue {};
+
+        let mut data48bytes = VertexBuffers::new();
+        data48bytes.vertices.append(&mut create_48byte());
+        data48bytes.indices.append(&mut vec![1, 2, 3, 4]);
+        let data48bytes_aligned = data48bytes.into();
+
+        let first = (0, 0, ZoomLevel::new(1)).into();
+        let second = (1, 0, ZoomLevel::new(1)).into();
+
+        for coords in [first, second] {
+            pool.allocate_layer_geometry(
+                &queue,
+                coords,
+                StyleLayer::default(),
+                &data48bytes_aligned,
+                2,
+                &[],
+            );
+        }
+        assert_eq!(
+            128 - 2 * 48,
+            pool.available_space(BackingBufferType::Vertices)
+        );
+
+        pool.remove_tile(first);
+        assert!(pool.index().get_layers(first).is_none());
+        assert!(pool.index().get_layers(second).is_some());
+        // The space of the first tile at the beginning of the buffer can be reused
+        assert_eq!(48, pool.available_space(BackingBufferType::Vertices));
+    }
 }
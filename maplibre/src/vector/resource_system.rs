@@ -1,22 +1,62 @@
 //! Prepares GPU-owned resources by initializing them if they are uninitialized or out-of-date.
+use std::collections::HashMap;
+
 use crate::{
     context::MapContext,
     render::{
-        eventually::Eventually,
-        resource::{RenderPipeline, TilePipeline},
+        eventually::{Eventually, Eventually::Initialized},
+        resource::{RenderPipeline, StencilMode, Surface, TilePipeline},
+        settings::RendererSettings,
         shaders,
         shaders::Shader,
         RenderResources, Renderer,
     },
-    vector::{resource::BufferPool, VectorBufferPool, VectorPipeline},
+    vector::{
+        pattern::SpriteTexture, resource::BufferPool, FrontFeatures, Sprite, VectorBufferPool,
+        VectorLayersDataComponent, VectorPipeline, VectorPipelineKey,
+    },
 };
 
+fn create_pipeline(
+    device: &wgpu::Device,
+    settings: RendererSettings,
+    surface: &Surface,
+    key: VectorPipelineKey,
+) -> wgpu::RenderPipeline {
+    let tile_shader = shaders::VectorTileShader {
+        format: surface.surface_format(),
+        blend_mode: key.blend_mode,
+        pattern: key.pattern,
+    };
+
+    TilePipeline::new(
+        "vector_pipeline".into(),
+        settings,
+        tile_shader.describe_vertex(),
+        tile_shader.describe_fragment(),
+        true,
+        false,
+        false,
+        false,
+        surface.is_multisampling_supported(settings.msaa),
+        key.pattern,
+    )
+    .with_stencil_mode(key.stencil_mode)
+    .with_ignored_depth(key.front)
+    // Overlapping features of a layer are blended, unless the layer blends them once
+    .with_equal_depth(true)
+    .describe_render_pipeline()
+    .initialize(device)
+}
+
 pub fn resource_system(
     MapContext {
+        style,
         world,
         renderer:
             Renderer {
                 device,
+                queue,
                 resources: RenderResources { surface, .. },
                 settings,
                 ..
@@ -26,34 +66,81 @@ pub fn resource_system(
 ) {
     let Some((
         buffer_pool,
-        vector_pipeline
+        vector_pipeline,
+        sprite,
+        sprite_texture,
+        front_features,
     )) = world.resources.query_mut::<(
         &mut Eventually<VectorBufferPool>,
-        &mut Eventually<VectorPipeline>
+        &mut Eventually<VectorPipeline>,
+        &mut Sprite,
+        &mut Eventually<SpriteTexture>,
+        &FrontFeatures,
     )>() else { return; };
 
     buffer_pool.initialize(|| BufferPool::from_device(device));
 
     vector_pipeline.initialize(|| {
-        let tile_shader = shaders::VectorTileShader {
-            format: surface.surface_format(),
+        let key = VectorPipelineKey {
+            blend_mode: None,
+            pattern: false,
+            stencil_mode: StencilMode::Masked,
+            front: false,
         };
-
-        let pipeline = TilePipeline::new(
-            "vector_pipeline".into(),
-            *settings,
-            tile_shader.describe_vertex(),
-            tile_shader.describe_fragment(),
-            true,
-            false,
-            false,
-            false,
-            surface.is_multisampling_supported(settings.msaa),
-            false,
-        )
-        .describe_render_pipeline()
-        .initialize(device);
-
-        VectorPipeline(pipeline)
+        let mut pipelines = HashMap::new();
+        pipelines.insert(key, create_pipeline(device, *settings, surface, key));
+        VectorPipeline(pipelines)
     });
+
+    let Initialized(vector_pipeline) = vector_pipeline else { return; };
+
+    // Pipelines are created lazily for the variants which are used by the style
+    for layer in &style.layers {
+        let key = VectorPipelineKey::of(layer);
+        let mut keys = if key.stencil_mode == StencilMode::Once {
+            vec![key, key.unmark()]
+        } else {
+            vec![key]
+        };
+        if !front_features.is_empty() {
+            keys.push(key.front());
+        }
+
+        for key in keys {
+            vector_pipeline
+                .0
+                .entry(key)
+                .or_insert_with(|| create_pipeline(device, *settings, surface, key));
+        }
+    }
+
+    // The patterns of all layers are uploaded again, as the icons might have moved
+    if sprite.take_changed() {
+        *sprite_texture = Eventually::Uninitialized;
+
+        for coords in world.tiles.loaded_coords() {
+            if let Some(vector_layers) = world
+                .tiles
+                .query_mut::<&mut VectorLayersDataComponent>(coords)
+            {
+                vector_layers.needs_upload = true;
+            }
+        }
+    }
+
+    let pattern_pipeline = vector_pipeline
+        .iter()
+        .find_map(|(key, pipeline)| key.pattern.then_some(pipeline));
+
+    if let (Some(atlas), Some(pattern_pipeline)) = (sprite.atlas(), pattern_pipeline) {
+        sprite_texture.initialize(|| {
+            SpriteTexture::new(
+                device,
+                queue,
+                &pattern_pipeline.get_bind_group_layout(0),
+                atlas,
+                surface.color_space(),
+            )
+        });
+    }
 }
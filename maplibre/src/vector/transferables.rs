@@ -10,7 +10,7 @@ use crate::{
     },
     render::ShaderVertex,
     tessellation::{IndexDataType, OverAlignedVertexBuffer},
-    vector::{AvailableVectorLayerData, MissingVectorLayerData},
+    vector::{feature_ids, AvailableVectorLayerData, LayerMissingReason, MissingVectorLayerData},
 };
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
@@ -40,14 +40,23 @@ pub trait TileTessellated: IntoMessage + Debug + Send {
 pub trait LayerMissing: IntoMessage + Debug + Send {
     fn message_tag() -> &'static dyn MessageTag;
 
-    fn build_from(coords: WorldTileCoords, layer_name: String) -> Self
+    fn build_from(
+        coords: WorldTileCoords,
+        source: Option<String>,
+        layer_name: String,
+        reason: LayerMissingReason,
+    ) -> Self
     where
         Self: Sized;
 
     fn coords(&self) -> WorldTileCoords;
 
+    fn source(&self) -> Option<&str>;
+
     fn layer_name(&self) -> &str;
 
+    fn reason(&self) -> LayerMissingReason;
+
     fn to_layer(self) -> MissingVectorLayerData;
 }
 
@@ -56,6 +65,7 @@ pub trait LayerTessellated: IntoMessage + Debug + Send {
 
     fn build_from(
         coords: WorldTileCoords,
+        source: Option<String>,
         buffer: OverAlignedVertexBuffer<ShaderVertex, IndexDataType>,
         feature_indices: Vec<u32>,
         layer_data: Layer,
@@ -73,12 +83,14 @@ pub trait LayerTessellated: IntoMessage + Debug + Send {
 pub trait LayerIndexed: IntoMessage + Debug + Send {
     fn message_tag() -> &'static dyn MessageTag;
 
-    fn build_from(coords: WorldTileCoords, index: TileIndex) -> Self
+    fn build_from(coords: WorldTileCoords, layer_name: String, index: TileIndex) -> Self
     where
         Self: Sized;
 
     fn coords(&self) -> WorldTileCoords;
 
+    fn layer_name(&self) -> &str;
+
     fn to_tile_index(self) -> TileIndex;
 }
 
@@ -114,12 +126,14 @@ impl TileTessellated for DefaultTileTessellated {
 
 pub struct DefaultLayerMissing {
     pub coords: WorldTileCoords,
+    pub source: Option<String>,
     pub layer_name: String,
+    pub reason: LayerMissingReason,
 }
 
 impl Debug for DefaultLayerMissing {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "DefaultLayerMissing({})", self.coords)
+        write!(f, "DefaultLayerMissing({}, {:?})", self.coords, self.reason)
     }
 }
 
@@ -134,22 +148,42 @@ impl LayerMissing for DefaultLayerMissing {
         &VectorMessageTag::LayerMissing
     }
 
-    fn build_from(coords: WorldTileCoords, layer_name: String) -> Self {
-        Self { coords, layer_name }
+    fn build_from(
+        coords: WorldTileCoords,
+        source: Option<String>,
+        layer_name: String,
+        reason: LayerMissingReason,
+    ) -> Self {
+        Self {
+            coords,
+            source,
+            layer_name,
+            reason,
+        }
     }
 
     fn coords(&self) -> WorldTileCoords {
         self.coords
     }
 
+    fn source(&self) -> Option<&str> {
+        self.source.as_deref()
+    }
+
     fn layer_name(&self) -> &str {
         &self.layer_name
     }
 
+    fn reason(&self) -> LayerMissingReason {
+        self.reason
+    }
+
     fn to_layer(self) -> MissingVectorLayerData {
         MissingVectorLayerData {
             coords: self.coords,
+            source: self.source,
             source_layer: self.layer_name,
+            reason: self.reason,
         }
     }
 }
@@ -157,6 +191,7 @@ impl LayerMissing for DefaultLayerMissing {
 #[derive(Clone)]
 pub struct DefaultLayerTesselated {
     pub coords: WorldTileCoords,
+    pub source: Option<String>,
     pub buffer: OverAlignedVertexBuffer<ShaderVertex, IndexDataType>,
     /// Holds for each feature the count of indices.
     pub feature_indices: Vec<u32>,
@@ -182,12 +217,14 @@ impl LayerTessellated for DefaultLayerTesselated {
 
     fn build_from(
         coords: WorldTileCoords,
+        source: Option<String>,
         buffer: OverAlignedVertexBuffer<ShaderVertex, IndexDataType>,
         feature_indices: Vec<u32>,
         layer_data: Layer,
     ) -> Self {
         Self {
             coords,
+            source,
             buffer,
             feature_indices,
             layer_data,
@@ -205,6 +242,8 @@ impl LayerTessellated for DefaultLayerTesselated {
     fn to_layer(self) -> AvailableVectorLayerData {
         AvailableVectorLayerData {
             coords: self.coords,
+            feature_ids: feature_ids(&self.layer_data),
+            source: self.source,
             source_layer: self.layer_data.name,
             buffer: self.buffer,
             feature_indices: self.feature_indices,
@@ -214,12 +253,17 @@ impl LayerTessellated for DefaultLayerTesselated {
 
 pub struct DefaultLayerIndexed {
     coords: WorldTileCoords,
+    layer_name: String,
     index: TileIndex,
 }
 
 impl Debug for DefaultLayerIndexed {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "DefaultLayerIndexed({})", self.coords)
+        write!(
+            f,
+            "DefaultLayerIndexed({}, {})",
+            self.coords, self.layer_name
+        )
     }
 }
 
@@ -234,14 +278,22 @@ impl LayerIndexed for DefaultLayerIndexed {
         &VectorMessageTag::LayerIndexed
     }
 
-    fn build_from(coords: WorldTileCoords, index: TileIndex) -> Self {
-        Self { coords, index }
+    fn build_from(coords: WorldTileCoords, layer_name: String, index: TileIndex) -> Self {
+        Self {
+            coords,
+            layer_name,
+            index,
+        }
     }
 
     fn coords(&self) -> WorldTileCoords {
         self.coords
     }
 
+    fn layer_name(&self) -> &str {
+        &self.layer_name
+    }
+
     fn to_tile_index(self) -> TileIndex {
         self.index
     }
@@ -1,19 +1,22 @@
 //! Uploads data to the GPU which is needed for rendering.
 
-use std::iter;
+use std::{iter, mem::size_of};
 
 use crate::{
     context::MapContext,
     coords::ViewRegion,
     render::{
+        color_space::ColorSpace,
         eventually::{Eventually, Eventually::Initialized},
         shaders::{ShaderFeatureStyle, ShaderLayerMetadata, Vec4f32},
-        Renderer,
+        RenderResources, Renderer, ShaderVertex,
     },
-    style::Style,
+    style::{layer::StyleLayer, Style},
     tcs::tiles::Tiles,
+    tessellation::{index_size, IndexDataType, OverAlignedVertexBuffer},
     vector::{
-        AvailableVectorLayerData, VectorBufferPool, VectorLayerData, VectorLayersDataComponent,
+        pattern::pattern, AvailableVectorLayerData, FeatureData, Sprite, VectorBufferPool,
+        VectorLayerData, VectorLayersDataComponent,
     },
 };
 
@@ -22,15 +25,37 @@ pub fn upload_system(
         world,
         style,
         view_state,
-        renderer: Renderer { device, queue, .. },
+        renderer:
+            Renderer {
+                device,
+                queue,
+                settings,
+                resources: RenderResources { surface, .. },
+                ..
+            },
         ..
     }: &mut MapContext,
 ) {
-    let Some(
-        Initialized(buffer_pool)
-    ) = world.resources.query_mut::<
+    let Some((
+        Initialized(buffer_pool),
+        feature_data,
+        sprite,
+    )) = world.resources.query_mut::<(
         &mut Eventually<VectorBufferPool>,
-    >() else { return; };
+        &mut FeatureData,
+        &Sprite,
+    )>() else { return; };
+
+    // Changed feature data only changes the feature metadata of the uploaded layers
+    if feature_data.take_changed() {
+        update_feature_data(
+            buffer_pool,
+            queue,
+            &world.tiles,
+            feature_data,
+            surface.color_space(),
+        );
+    }
 
     let view_region = view_state.create_view_region();
 
@@ -41,7 +66,11 @@ pub fn upload_system(
             queue,
             &mut world.tiles,
             style,
+            feature_data,
+            sprite,
             view_region,
+            settings.max_upload_bytes_per_frame,
+            surface.color_space(),
         );
         // self.update_metadata(state, tile_repository, queue);
     }
@@ -124,15 +153,34 @@ fn upload_tesselated_layer(
     queue: &wgpu::Queue,
     tiles: &mut Tiles,
     style: &Style,
+    feature_data: &FeatureData,
+    sprite: &Sprite,
     view_region: &ViewRegion,
+    max_bytes: Option<u64>,
+    color_space: ColorSpace,
 ) {
+    let mut uploaded_bytes = 0;
+
     // Upload all tessellated layers which are in view
     for coords in view_region.iter() {
-        let Some(vector_layers) = tiles.query_mut::<&VectorLayersDataComponent>(coords) else { continue; };
+        if max_bytes.map_or(false, |max_bytes| uploaded_bytes >= max_bytes) {
+            log::debug!("upload budget of {uploaded_bytes} bytes exhausted, continuing next frame");
+            break;
+        }
+
+        let Some(vector_layers) = tiles.query_mut::<&mut VectorLayersDataComponent>(coords) else { continue; };
 
-        let loaded_layers = buffer_pool
-            .get_loaded_source_layers_at(coords)
-            .unwrap_or_default();
+        // Refreshed layers replace the layers which are already loaded. The commands of the
+        // previous frame have been submitted, so their space can be reused right away.
+        let loaded_layers = if vector_layers.needs_upload {
+            vector_layers.needs_upload = false;
+            buffer_pool.remove_tile(coords);
+            Default::default()
+        } else {
+            buffer_pool
+                .get_loaded_source_layers_at(coords)
+                .unwrap_or_default()
+        };
 
         let available_layers = vector_layers
             .layers
@@ -141,36 +189,70 @@ fn upload_tesselated_layer(
                 VectorLayerData::Available(data) => Some(data),
                 VectorLayerData::Missing(_) => None,
             })
-            .filter(|data| !loaded_layers.contains(data.source_layer.as_str()))
+            .filter(|data| {
+                !loaded_layers.contains(&(data.source.as_deref(), data.source_layer.as_str()))
+            })
             .collect::<Vec<_>>();
 
         for style_layer in &style.layers {
+            if style_layer.unsupported_type.is_some() {
+                continue;
+            }
+
             let source_layer = style_layer.source_layer.as_ref().unwrap(); // TODO: Unwrap
 
+            // Layers of a tile which is still loading are uploaded in the order of the style, such
+            // that later layers are never drawn without the layers below them
+            let source = style_layer.source.as_deref();
+            if !vector_layers.is_layer_resolved(source, source_layer) {
+                break;
+            }
+
             let Some(AvailableVectorLayerData {
                          coords,
                          feature_indices,
+                         feature_ids,
                          buffer,
                          ..
                      }) = available_layers
                 .iter()
-                .find(|layer| source_layer.as_str() == layer.source_layer) else { continue; };
+                .find(|layer| {
+                    layer.source.as_deref() == source && source_layer.as_str() == layer.source_layer
+                }) else { continue; };
 
-            let color: Option<Vec4f32> = style_layer
+            if buffer.usable_indices == 0 {
+                continue;
+            }
+
+            let color = layer_color(style_layer, color_space);
+
+            let pattern_name = style_layer
                 .paint
                 .as_ref()
-                .and_then(|paint| paint.get_color())
-                .map(|color| color.into());
+                .and_then(|paint| paint.get_pattern());
 
-            let feature_metadata = (0..feature_indices.len()) // FIXME: Iterate over actual features
-                .enumerate()
-                .flat_map(|(i, _feature)| {
-                    iter::repeat(ShaderFeatureStyle {
-                        color: color.unwrap(),
-                    })
-                    .take(feature_indices[i] as usize)
-                })
-                .collect::<Vec<_>>();
+            // Patterns whose icon is not part of the sprite are not drawn
+            let layer_metadata = match pattern_name {
+                Some(name) => ShaderLayerMetadata::with_pattern(
+                    style_layer.index as f32,
+                    sprite
+                        .atlas()
+                        .and_then(|atlas| pattern(atlas, name, *coords))
+                        .unwrap_or_default(),
+                ),
+                None => ShaderLayerMetadata::new(style_layer.index as f32),
+            };
+
+            let feature_metadata = feature_metadata(
+                color,
+                feature_indices,
+                feature_ids,
+                style_layer.source.as_deref(),
+                feature_data,
+            );
+
+            uploaded_bytes += geometry_size(buffer)
+                + (feature_metadata.len() * size_of::<ShaderFeatureStyle>()) as u64;
 
             log::debug!("Allocating geometry at {coords}");
             buffer_pool.allocate_layer_geometry(
@@ -178,9 +260,127 @@ fn upload_tesselated_layer(
                 *coords,
                 style_layer.clone(),
                 buffer,
-                ShaderLayerMetadata::new(style_layer.index as f32),
+                layer_metadata,
                 &feature_metadata,
             );
         }
     }
 }
+
+/// Writes the feature metadata of all uploaded layers again, such that they receive the current
+/// values of the `feature_data`. Layers which are uploaded again anyway are skipped.
+fn update_feature_data(
+    buffer_pool: &VectorBufferPool,
+    queue: &wgpu::Queue,
+    tiles: &Tiles,
+    feature_data: &FeatureData,
+    color_space: ColorSpace,
+) {
+    for entries in buffer_pool.index().iter() {
+        for entry in entries {
+            let Some(vector_layers) = tiles.query::<&VectorLayersDataComponent>(entry.coords) else { continue; };
+
+            // The layers of a refresh do not match the uploaded geometry
+            if vector_layers.needs_upload {
+                continue;
+            }
+
+            let source = entry.style_layer.source.as_deref();
+            let Some(source_layer) = entry.style_layer.source_layer.as_deref() else { continue; };
+            let Some(data) = vector_layers.layers.iter().find_map(|layer| match layer {
+                VectorLayerData::Available(data) if layer.is_layer(source, source_layer) => {
+                    Some(data)
+                }
+                _ => None,
+            }) else { continue; };
+
+            let feature_metadata = feature_metadata(
+                layer_color(&entry.style_layer, color_space),
+                &data.feature_indices,
+                &data.feature_ids,
+                source,
+                feature_data,
+            );
+            buffer_pool.update_feature_metadata(queue, entry, &feature_metadata);
+        }
+    }
+}
+
+/// The color of the features of the `style_layer`. Layers which are filled with a pattern do not
+/// need a color.
+fn layer_color(style_layer: &StyleLayer, color_space: ColorSpace) -> Vec4f32 {
+    let color = style_layer
+        .paint
+        .as_ref()
+        .and_then(|paint| paint.get_color())
+        .map(|color| color_space.convert(color));
+
+    if style_layer
+        .paint
+        .as_ref()
+        .and_then(|paint| paint.get_pattern())
+        .is_some()
+    {
+        color.unwrap_or_default()
+    } else {
+        color.unwrap()
+    }
+}
+
+/// The metadata of each index of a layer. The features of the layer are drawn with `color` and
+/// receive the values which are attached to their id in `feature_data`.
+fn feature_metadata(
+    color: Vec4f32,
+    feature_indices: &[u32],
+    feature_ids: &[u64],
+    source: Option<&str>,
+    feature_data: &FeatureData,
+) -> Vec<ShaderFeatureStyle> {
+    feature_indices
+        .iter()
+        .zip(feature_ids)
+        .flat_map(|(indices, feature_id)| {
+            let data = source
+                .and_then(|source| feature_data.get(source, *feature_id))
+                .unwrap_or_default();
+
+            iter::repeat(ShaderFeatureStyle { color, data }).take(*indices as usize)
+        })
+        .collect()
+}
+
+/// The amount of bytes which are uploaded for the geometry in `buffer`.
+fn geometry_size(buffer: &OverAlignedVertexBuffer<ShaderVertex, IndexDataType>) -> u64 {
+    (buffer.buffer.vertices.len() * size_of::<ShaderVertex>()) as u64
+        + u64::from(buffer.usable_indices) * index_size(buffer.index_format())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::feature_metadata;
+    use crate::{tcs::world::World, vector::FeatureData};
+
+    #[test]
+    fn test_feature_data_reaches_feature_metadata() {
+        let mut world = World::default();
+        world.set_feature_data("openmaptiles", 7, 42.0);
+
+        let feature_data = world.resources.get_mut::<FeatureData>().unwrap();
+        assert!(feature_data.take_changed());
+
+        let color = [1.0, 0.0, 0.0, 1.0];
+        let metadata =
+            feature_metadata(color, &[3, 6], &[1, 7], Some("openmaptiles"), feature_data);
+
+        let data = metadata.iter().map(|style| style.data).collect::<Vec<_>>();
+        assert_eq!(
+            data,
+            vec![0.0, 0.0, 0.0, 42.0, 42.0, 42.0, 42.0, 42.0, 42.0]
+        );
+        assert!(metadata.iter().all(|style| style.color == color));
+
+        // Features of other sources do not receive the value
+        let metadata = feature_metadata(color, &[3], &[7], Some("other"), feature_data);
+        assert!(metadata.iter().all(|style| style.data == 0.0));
+    }
+}
@@ -1,21 +1,55 @@
-use std::ops::{Deref, DerefMut};
+use std::{
+    ops::{Deref, DerefMut},
+    time::Duration,
+};
 
-use cgmath::Angle;
+use cgmath::{Angle, Deg, Matrix4, Rad, Vector2, Vector3, Zero};
 
 use crate::{
-    coords::{ViewRegion, WorldCoords, Zoom, ZoomLevel, TILE_SIZE},
+    coords::{LatLon, ViewRegion, WorldCoords, WorldTileCoords, Zoom, ZoomLevel, TILE_SIZE},
+    io::source_type::tile_zoom_offset,
+    projection::{Projection, WebMercator},
     render::camera::{Camera, Perspective, ViewProjection},
-    util::ChangeObserver,
+    util::{math::bounds_from_points, ChangeObserver},
     window::WindowSize,
 };
 
 const VIEW_REGION_PADDING: i32 = 1;
 
+/// The additional amount of tiles which are requested around the camera if the map is rendered
+/// with a custom view projection, whose visible region is unknown.
+const CUSTOM_VIEW_PROJECTION_PADDING: i32 = 2;
+
+/// An animation of the camera towards a target position and zoom. Positions are stored relative
+/// to the size of the world, so they are independent of the zoom.
+struct CameraAnimation {
+    from: (Vector2<f64>, Zoom),
+    to: (Vector2<f64>, Zoom),
+    /// The bearing at the start and the end of the animation. The bearing is not animated if this
+    /// is `None`.
+    bearing: Option<(Rad<f64>, Rad<f64>)>,
+    duration: Duration,
+    elapsed: Duration,
+}
+
+/// Called with the new bearing of the camera, see [`ViewState::on_bearing_changed`].
+pub type BearingChangedCallback = Box<dyn FnMut(Rad<f64>)>;
+
 /// Stores the camera configuration.
 pub struct ViewState {
     zoom: ChangeObserver<Zoom>,
     camera: ChangeObserver<Camera>,
     perspective: Perspective,
+    projection: Box<dyn Projection>,
+    animation: Option<CameraAnimation>,
+    reduced_motion: bool,
+    /// The amount of tiles around the visible tiles which are part of the view region
+    view_region_padding: i32,
+    /// Replaces the view projection of the camera, see [`ViewState::set_view_projection`]
+    custom_view_projection: Option<Matrix4<f64>>,
+    /// The bearing which has been passed to `on_bearing_changed` the last time
+    notified_bearing: Rad<f64>,
+    on_bearing_changed: Option<BearingChangedCallback>,
 }
 
 impl ViewState {
@@ -55,6 +89,13 @@ impl ViewState {
             zoom: ChangeObserver::new(zoom),
             camera: ChangeObserver::new(camera),
             perspective,
+            projection: Box::new(WebMercator),
+            animation: None,
+            reduced_motion: false,
+            view_region_padding: VIEW_REGION_PADDING,
+            custom_view_projection: None,
+            notified_bearing: Rad::zero(),
+            on_bearing_changed: None,
         }
     }
 
@@ -64,23 +105,224 @@ impl ViewState {
     }
 
     pub fn create_view_region(&self) -> Option<ViewRegion> {
-        self.camera
-            .view_region_bounding_box(&self.view_projection().invert())
-            .map(|bounding_box| {
+        self.create_view_region_at(self.visible_level())
+    }
+
+    /// Creates the view region for a source whose tiles are `tile_size` pixels large. Tiles which
+    /// are larger than the [default](crate::io::source_type::DEFAULT_TILE_SIZE) cover more of the
+    /// screen, so they are requested from lower zoom levels.
+    pub fn create_view_region_for_tile_size(&self, tile_size: u32) -> Option<ViewRegion> {
+        let offset = tile_zoom_offset(tile_size).min(self.visible_level().into());
+        self.create_view_region_at(self.visible_level() - offset)
+    }
+
+    fn create_view_region_at(&self, level: ZoomLevel) -> Option<ViewRegion> {
+        #[cfg(feature = "globe")]
+        if crate::globe::globe_factor(self.zoom()) > 0.0 {
+            // The frustum does not intersect the ground plane like the globe. Instead consider the
+            // whole world and only keep the tiles which face the camera.
+            let world_size = WebMercator.world_size(self.zoom());
+            let position = self.camera.position();
+            let center = WorldCoords::at_ground(position.x, position.y);
+
+            return Some(
                 ViewRegion::new(
-                    bounding_box,
-                    VIEW_REGION_PADDING,
+                    crate::util::math::Aabb2::new(
+                        cgmath::Point2::new(0.0, 0.0),
+                        cgmath::Point2::new(world_size, world_size),
+                    ),
+                    0,
                     32,
                     *self.zoom,
-                    self.visible_level(),
+                    level,
                 )
-            })
+                .on_globe(WebMercator.unproject(center, self.zoom())),
+            );
+        }
+
+        let padding = if self.custom_view_projection.is_some() {
+            self.view_region_padding + CUSTOM_VIEW_PROJECTION_PADDING
+        } else {
+            self.view_region_padding
+        };
+
+        self.camera
+            .view_region_bounding_box(&self.camera_view_projection().invert())
+            .map(|bounding_box| ViewRegion::new(bounding_box, padding, 32, *self.zoom, level))
+    }
+
+    /// Sets the amount of tiles around the visible tiles which are requested, such that they are
+    /// available when the camera moves. A padding of `0` only requests the visible tiles.
+    pub fn set_view_region_padding(&mut self, padding: i32) {
+        self.view_region_padding = padding.max(0);
+    }
+
+    pub fn view_region_padding(&self) -> i32 {
+        self.view_region_padding
+    }
+
+    /// The transform of the tile at `coords` into the world.
+    pub fn tile_transform(&self, coords: WorldTileCoords) -> Matrix4<f64> {
+        #[cfg(feature = "globe")]
+        {
+            let position = self.camera.position();
+            crate::globe::tile_transform(
+                coords,
+                self.zoom(),
+                WorldCoords::at_ground(position.x, position.y),
+            )
+        }
+
+        #[cfg(not(feature = "globe"))]
+        coords.transform_for_zoom(self.zoom())
     }
 
+    /// The view projection with which the map is rendered. This is the view projection of the
+    /// camera unless a custom one is set.
     pub fn view_projection(&self) -> ViewProjection {
+        match self.custom_view_projection {
+            Some(view_projection) => view_projection.into(),
+            None => self.camera_view_projection(),
+        }
+    }
+
+    fn camera_view_projection(&self) -> ViewProjection {
         self.camera.calc_view_proj(&self.perspective)
     }
 
+    /// Renders the map with the `view_projection` instead of the camera, e.g. to embed the map
+    /// into an AR/VR scene. The matrix transforms world coordinates into clip space. Tiles are
+    /// still requested around the camera, but with an additional padding. Passing `None` renders
+    /// with the camera again.
+    pub fn set_view_projection(&mut self, view_projection: Option<Matrix4<f64>>) {
+        self.custom_view_projection = view_projection;
+    }
+
+    pub fn custom_view_projection(&self) -> Option<Matrix4<f64>> {
+        self.custom_view_projection
+    }
+
+    /// Converts window coordinates to [`WorldCoords`] on the ground. The origin of the window
+    /// coordinates is in the top-left corner and y points down.
+    ///
+    /// Returns `None` if the ground is not visible at the window coordinates, which can happen if
+    /// the camera is pitched.
+    pub fn screen_to_world(&self, x: f64, y: f64) -> Option<WorldCoords> {
+        let view_proj = self.view_projection();
+        let world = self.camera.window_to_world_at_ground(
+            &Vector2::new(x, y),
+            &view_proj.invert(),
+            false,
+        )?;
+
+        // Rays which point above the horizon intersect with the ground behind the camera
+        if view_proj.project(world.extend(1.0)).w <= 0.0 {
+            return None;
+        }
+
+        Some(WorldCoords::at_ground(world.x, world.y))
+    }
+
+    /// Converts window coordinates to [`LatLon`]. See [`ViewState::screen_to_world`].
+    pub fn screen_to_lat_lon(&self, x: f64, y: f64) -> Option<LatLon> {
+        self.screen_to_world(x, y)
+            .map(|world| self.projection.unproject(world, self.zoom()))
+    }
+
+    /// Converts [`WorldCoords`] on the ground to window coordinates. The origin of the window
+    /// coordinates is in the top-left corner and y points down. Coordinates outside the window
+    /// are returned as well, e.g. negative values for positions left of the window.
+    ///
+    /// Returns `None` if the coordinates are behind the camera, which can happen if the camera is
+    /// pitched.
+    pub fn world_to_screen(&self, world: WorldCoords) -> Option<(f64, f64)> {
+        self.camera
+            .world_to_window(
+                &Vector3::new(world.x, world.y, 0.0),
+                &self.view_projection(),
+            )
+            .map(|window| (window.x, window.y))
+    }
+
+    /// Converts [`LatLon`] to window coordinates. See [`ViewState::world_to_screen`].
+    pub fn lat_lon_to_screen(&self, lat_lon: LatLon) -> Option<(f64, f64)> {
+        self.world_to_screen(self.projection.project(lat_lon, self.zoom()))
+    }
+
+    /// Whether `lat_lon` lies within the window. The copies of the world to the left and right
+    /// are considered as well, such that positions across the antimeridian are visible.
+    pub fn is_visible(&self, lat_lon: LatLon) -> bool {
+        let Some(([min_x, _], [max_x, _])) = self.visible_ground_bounds() else { return false; };
+
+        let (width, height) = self.camera.size();
+        let world = self.projection.project(lat_lon, self.zoom());
+        let world_size = self.projection.world_size(self.zoom());
+
+        // The copies of the world which overlap the visible ground
+        let first = ((min_x - world.x) / world_size).floor() as i64;
+        let last = ((max_x - world.x) / world_size).ceil() as i64;
+
+        (first..=last).any(|copy| {
+            let x = world.x + copy as f64 * world_size;
+            self.world_to_screen(WorldCoords::at_ground(x, world.y))
+                .map_or(false, |(x, y)| {
+                    (0.0..=width).contains(&x) && (0.0..=height).contains(&y)
+                })
+        })
+    }
+
+    /// The south-west and north-east corners of the geographic extent which is visible within
+    /// the window. The bounds enclose the window also if the camera is rotated or pitched. If the
+    /// horizon is visible, the bounds end at the farthest visible ground.
+    ///
+    /// Longitudes are not wrapped, such that the west longitude is always less than the east
+    /// longitude. The east longitude exceeds 180 if the view crosses the antimeridian.
+    pub fn visible_bounds(&self) -> Option<(LatLon, LatLon)> {
+        let (min, max) = self.visible_ground_bounds()?;
+
+        // World y points south
+        let south_west = WorldCoords::at_ground(min[0], max[1]);
+        let north_east = WorldCoords::at_ground(max[0], min[1]);
+
+        Some((
+            self.projection.unproject(south_west, self.zoom()),
+            self.projection.unproject(north_east, self.zoom()),
+        ))
+    }
+
+    /// The bounding box of the ground which is visible within the window. The edges of the window
+    /// are sampled, as the ground can be cut off by the horizon if the camera is pitched.
+    fn visible_ground_bounds(&self) -> Option<([f64; 2], [f64; 2])> {
+        const SAMPLES_PER_EDGE: usize = 16;
+
+        let (width, height) = self.camera.size();
+        let edges = (0..SAMPLES_PER_EDGE).flat_map(|i| {
+            let t = i as f64 / SAMPLES_PER_EDGE as f64;
+            [
+                (t * width, 0.0),
+                (width, t * height),
+                ((1.0 - t) * width, height),
+                (0.0, (1.0 - t) * height),
+            ]
+        });
+
+        bounds_from_points(
+            edges
+                .filter_map(|(x, y)| self.screen_to_world(x, y))
+                .map(|world| [world.x, world.y]),
+        )
+    }
+
+    /// The projection which is used to convert between [`LatLon`] and [`WorldCoords`]. Defaults
+    /// to [`WebMercator`].
+    pub fn projection(&self) -> &dyn Projection {
+        self.projection.as_ref()
+    }
+
+    pub fn set_projection<P: Projection + 'static>(&mut self, projection: P) {
+        self.projection = Box::new(projection);
+    }
+
     pub fn visible_level(&self) -> ZoomLevel {
         self.zoom.level()
     }
@@ -110,8 +352,501 @@ impl ViewState {
         self.camera.did_change(0.05)
     }
 
+    /// Animates the camera to `position` and `zoom` over the `duration`. The animation progresses
+    /// with each call to [`ViewState::advance_animation`]. With reduced motion the camera jumps
+    /// to the target immediately.
+    pub fn animate_to(&mut self, position: WorldCoords, zoom: Zoom, duration: Duration) {
+        let current = self.camera.position();
+        let current_size = self.projection.world_size(self.zoom());
+        let target_size = self.projection.world_size(zoom);
+
+        self.animation = Some(CameraAnimation {
+            from: (
+                Vector2::new(current.x, current.y) / current_size,
+                self.zoom(),
+            ),
+            to: (Vector2::new(position.x, position.y) / target_size, zoom),
+            bearing: None,
+            duration,
+            elapsed: Duration::ZERO,
+        });
+
+        if self.reduced_motion {
+            self.finish_animation();
+        }
+    }
+
+    /// The bearing of the camera, which is the clockwise angle between north and the top of the
+    /// view.
+    pub fn bearing(&self) -> Rad<f64> {
+        self.camera.bearing()
+    }
+
+    /// Animates the bearing of the camera back to north over the `duration`, e.g. when the
+    /// compass of the application is clicked. The camera turns the shorter way. A running
+    /// animation continues towards its target position and zoom.
+    pub fn reset_north(&mut self, duration: Duration) {
+        let current = self.camera.position();
+        let from = (
+            Vector2::new(current.x, current.y) / self.projection.world_size(self.zoom()),
+            self.zoom(),
+        );
+        let to = self
+            .animation
+            .as_ref()
+            .map_or(from, |animation| animation.to);
+
+        let bearing = self.bearing();
+        let north = if bearing > Rad::turn_div_2() {
+            Rad::full_turn()
+        } else {
+            Rad::zero()
+        };
+
+        self.animation = Some(CameraAnimation {
+            from,
+            to,
+            bearing: Some((bearing, north)),
+            duration,
+            elapsed: Duration::ZERO,
+        });
+
+        if self.reduced_motion {
+            self.finish_animation();
+        }
+    }
+
+    /// Calls the `callback` whenever the bearing of the camera changed, e.g. to rotate the compass
+    /// of the application. Changes are detected after each rendered frame, e.g. in
+    /// [`crate::map::Map::run_schedule`].
+    pub fn on_bearing_changed(&mut self, callback: impl FnMut(Rad<f64>) + 'static) {
+        self.on_bearing_changed = Some(Box::new(callback));
+    }
+
+    pub(crate) fn notify_bearing_changed(&mut self) {
+        let bearing = self.bearing();
+        if bearing == self.notified_bearing {
+            return;
+        }
+
+        self.notified_bearing = bearing;
+        if let Some(on_bearing_changed) = &mut self.on_bearing_changed {
+            on_bearing_changed(bearing);
+        }
+    }
+
+    /// Moves the camera to the target of the current animation.
+    fn finish_animation(&mut self) {
+        if let Some(animation) = &mut self.animation {
+            animation.duration = Duration::ZERO;
+        }
+        self.advance_animation(Duration::ZERO);
+    }
+
+    /// Advances the current animation by `dt`.
+    pub fn advance_animation(&mut self, dt: Duration) {
+        let Some(animation) = &mut self.animation else { return; };
+
+        animation.elapsed += dt;
+
+        let t = if animation.duration.is_zero() {
+            1.0
+        } else {
+            (animation.elapsed.as_secs_f64() / animation.duration.as_secs_f64()).min(1.0)
+        };
+
+        let zoom = animation.from.1.lerp(&animation.to.1, t);
+        let position = animation.from.0 + (animation.to.0 - animation.from.0) * t;
+        let bearing = animation.bearing.map(|(from, to)| from + (to - from) * t);
+
+        if t >= 1.0 {
+            self.animation = None;
+        }
+
+        let position = position * self.projection.world_size(zoom);
+        let height = self.camera.position().z;
+
+        *self.zoom = zoom;
+        self.camera
+            .move_to(cgmath::Point3::new(position.x, position.y, height));
+        if let Some(bearing) = bearing {
+            self.camera.set_bearing(bearing);
+        }
+    }
+
+    /// Whether the camera is currently animated.
+    pub fn is_animating(&self) -> bool {
+        self.animation.is_some()
+    }
+
+    /// Disables all animations, such that camera movements and the appearance of tiles happen
+    /// instantly. Embedders can use this to respect the accessibility settings of the operating
+    /// system. A running animation is finished immediately.
+    pub fn set_reduced_motion(&mut self, reduced_motion: bool) {
+        self.reduced_motion = reduced_motion;
+
+        if reduced_motion {
+            self.finish_animation();
+        }
+    }
+
+    pub fn reduced_motion(&self) -> bool {
+        self.reduced_motion
+    }
+
     pub fn update_references(&mut self) {
         self.camera.update_reference();
         self.zoom.update_reference();
     }
+
+    /// Creates a view state from the hash of a URL in the format `#zoom/lat/lng/bearing/pitch`,
+    /// which is used for shareable links. Bearing and pitch are optional. Returns `None` if the
+    /// hash is malformed.
+    pub fn from_hash(hash: &str, window_size: WindowSize) -> Option<ViewState> {
+        let values = hash
+            .strip_prefix('#')
+            .unwrap_or(hash)
+            .split('/')
+            .map(|value| value.parse::<f64>().ok().filter(|value| value.is_finite()))
+            .collect::<Option<Vec<_>>>()?;
+
+        let (zoom, latitude, longitude, bearing, pitch) = match values[..] {
+            [zoom, latitude, longitude] => (zoom, latitude, longitude, 0.0, 0.0),
+            [zoom, latitude, longitude, bearing] => (zoom, latitude, longitude, bearing, 0.0),
+            [zoom, latitude, longitude, bearing, pitch] => {
+                (zoom, latitude, longitude, bearing, pitch)
+            }
+            _ => return None,
+        };
+
+        if zoom < 0.0 || latitude.abs() > 90.0 || longitude.abs() > 180.0 {
+            return None;
+        }
+
+        let zoom = Zoom::new(zoom);
+        let mut view_state = ViewState::new(
+            window_size,
+            WorldCoords::from_lat_lon(LatLon::new(latitude, longitude), zoom),
+            zoom,
+            Deg(pitch),
+            Deg(110.0),
+        );
+        view_state.camera_mut().set_bearing(Deg(bearing));
+        view_state.update_references();
+        Some(view_state)
+    }
+
+    /// Encodes the camera in the format of [`ViewState::from_hash`]. The precision of the center
+    /// depends on the zoom. Bearing and pitch are omitted if they are zero.
+    pub fn to_hash(&self) -> String {
+        let zoom = self.zoom();
+        let position = self.camera.position();
+        let center = self
+            .projection
+            .unproject(WorldCoords::at_ground(position.x, position.y), zoom);
+
+        // Enough decimals to locate the center up to a pixel
+        let level = u8::from(zoom.level()) as f64;
+        let precision = ((level * 2f64.ln() + (512.0 / 360.0 / 0.5f64).ln()) / 10f64.ln())
+            .ceil()
+            .max(0.0) as usize;
+
+        let round = |value: f64, decimals: i32| {
+            let factor = 10f64.powi(decimals);
+            (value * factor).round() / factor
+        };
+
+        // Rounding can reach 360°, which is printed as 0
+        let bearing = round(Deg::from(self.camera.bearing()).0, 1) % 360.0;
+        let pitch = round(Deg::from(self.camera.pitch()).0, 0);
+
+        let mut hash = format!(
+            "#{zoom}/{:.precision$}/{:.precision$}",
+            center.latitude, center.longitude
+        );
+        if bearing != 0.0 || pitch != 0.0 {
+            hash.push_str(&format!("/{bearing}"));
+        }
+        if pitch != 0.0 {
+            hash.push_str(&format!("/{pitch}"));
+        }
+        hash
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc, time::Duration};
+
+    use cgmath::{Deg, Rad};
+
+    use crate::{
+        coords::{LatLon, WorldCoords, Zoom},
+        projection::Equirectangular,
+        view_state::ViewState,
+        window::WindowSize,
+    };
+
+    fn view_state(pitch: f64) -> ViewState {
+        let zoom = Zoom::new(10.0);
+        let mut view_state = ViewState::new(
+            WindowSize::new(800, 600).unwrap(),
+            WorldCoords::from_lat_lon(LatLon::new(48.137154, 11.576124), zoom),
+            zoom,
+            Deg(0.0),
+            Deg(110.0),
+        );
+        view_state.camera_mut().tilt(Deg(pitch));
+        view_state
+    }
+
+    #[test]
+    fn test_screen_to_world_round_trip() {
+        for pitch in [0.0, 10.0, 25.0] {
+            let view_state = view_state(pitch);
+
+            for (x, y) in [(400.0, 300.0), (0.0, 0.0), (800.0, 600.0), (123.0, 456.0)] {
+                let world = view_state.screen_to_world(x, y).unwrap();
+                let window = view_state.world_to_screen(world).unwrap();
+
+                assert!((window.0 - x).abs() < 1e-2, "pitch {pitch}: {window:?}");
+                assert!((window.1 - y).abs() < 1e-2, "pitch {pitch}: {window:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_screen_to_lat_lon() {
+        let view_state = view_state(0.0);
+
+        let center = view_state.screen_to_lat_lon(400.0, 300.0).unwrap();
+
+        assert!((center.latitude - 48.137154).abs() < 1e-3);
+        assert!((center.longitude - 11.576124).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_world_to_screen() {
+        let view_state = view_state(0.0);
+        let position = view_state.camera().position();
+
+        let center = view_state
+            .world_to_screen(WorldCoords::at_ground(position.x, position.y))
+            .unwrap();
+        assert!((center.0 - 400.0).abs() < 1e-6);
+        assert!((center.1 - 300.0).abs() < 1e-6);
+
+        // World y points down like window y
+        let below = view_state
+            .world_to_screen(WorldCoords::at_ground(position.x + 10.0, position.y + 10.0))
+            .unwrap();
+        assert!(below.0 > center.0);
+        assert!(below.1 > center.1);
+
+        let munich = view_state
+            .lat_lon_to_screen(LatLon::new(48.137154, 11.576124))
+            .unwrap();
+        assert!((munich.0 - 400.0).abs() < 1e-6);
+        assert!((munich.1 - 300.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_bearing() {
+        let mut view_state = view_state(0.0);
+        view_state.camera_mut().set_bearing(Deg(90.0));
+        let position = view_state.camera().position();
+
+        // East is up
+        let east = view_state
+            .world_to_screen(WorldCoords::at_ground(position.x + 10.0, position.y))
+            .unwrap();
+        assert!((east.0 - 400.0).abs() < 1e-6);
+        assert!(east.1 < 300.0);
+
+        // The tiles at the corners of the rotated window are requested
+        view_state.camera_mut().set_bearing(Deg(45.0));
+        let view_region = view_state.create_view_region().unwrap();
+        for (x, y) in [(0.0, 0.0), (800.0, 0.0), (800.0, 600.0), (0.0, 600.0)] {
+            let tile = view_state
+                .screen_to_world(x, y)
+                .unwrap()
+                .into_world_tile(view_region.zoom_level(), view_state.zoom());
+            assert!(view_region.is_in_view(&tile), "corner ({x}, {y})");
+        }
+    }
+
+    #[test]
+    fn test_reset_north() {
+        let mut view_state = view_state(0.0);
+        let position = view_state.camera().position();
+
+        let bearings = Rc::new(RefCell::new(Vec::new()));
+        let observed = bearings.clone();
+        view_state
+            .on_bearing_changed(move |bearing| observed.borrow_mut().push(Deg::from(bearing)));
+
+        view_state.camera_mut().set_bearing(Deg(90.0));
+        view_state.reset_north(Duration::from_secs(1));
+        view_state.advance_animation(Duration::from_millis(500));
+        view_state.notify_bearing_changed();
+        assert!((Deg::from(view_state.bearing()).0 - 45.0).abs() < 1e-6);
+
+        view_state.advance_animation(Duration::from_millis(500));
+        view_state.notify_bearing_changed();
+        assert_eq!(view_state.bearing(), Rad(0.0));
+        assert!(!view_state.is_animating());
+        let moved = view_state.camera().position() - position;
+        assert!(moved.x.abs() < 1e-6 && moved.y.abs() < 1e-6);
+
+        // The camera turns the shorter way
+        view_state.camera_mut().set_bearing(Deg(270.0));
+        view_state.reset_north(Duration::from_secs(1));
+        view_state.advance_animation(Duration::from_millis(500));
+        view_state.notify_bearing_changed();
+        assert!((Deg::from(view_state.bearing()).0 - 315.0).abs() < 1e-6);
+
+        let bearings = bearings.borrow();
+        assert_eq!(bearings.len(), 3);
+        assert!((bearings[0].0 - 45.0).abs() < 1e-6);
+        assert_eq!(bearings[1], Deg(0.0));
+        assert!((bearings[2].0 - 315.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_is_visible() {
+        let view_state = view_state(0.0);
+        let at = |x, y| view_state.screen_to_lat_lon(x, y).unwrap();
+
+        // Points just inside and just outside each edge of the window
+        for (inside, outside) in [
+            ((1.0, 300.0), (-1.0, 300.0)),
+            ((799.0, 300.0), (801.0, 300.0)),
+            ((400.0, 1.0), (400.0, -1.0)),
+            ((400.0, 599.0), (400.0, 601.0)),
+        ] {
+            assert!(view_state.is_visible(at(inside.0, inside.1)), "{inside:?}");
+            assert!(
+                !view_state.is_visible(at(outside.0, outside.1)),
+                "{outside:?}"
+            );
+        }
+
+        let (south_west, north_east) = view_state.visible_bounds().unwrap();
+        let top_left = at(0.0, 0.0);
+        let bottom_right = at(800.0, 600.0);
+        assert!((south_west.latitude - bottom_right.latitude).abs() < 1e-9);
+        assert!((south_west.longitude - top_left.longitude).abs() < 1e-9);
+        assert!((north_east.latitude - top_left.latitude).abs() < 1e-9);
+        assert!((north_east.longitude - bottom_right.longitude).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_is_visible_across_antimeridian() {
+        let zoom = Zoom::new(10.0);
+        let view_state = ViewState::new(
+            WindowSize::new(800, 600).unwrap(),
+            WorldCoords::from_lat_lon(LatLon::new(0.0, 179.95), zoom),
+            zoom,
+            Deg(0.0),
+            Deg(110.0),
+        );
+
+        let (south_west, north_east) = view_state.visible_bounds().unwrap();
+        assert!(south_west.longitude < 179.95);
+        assert!(north_east.longitude > 180.0);
+
+        // East of the antimeridian
+        assert!(view_state.is_visible(LatLon::new(0.0, -179.98)));
+        assert!(!view_state.is_visible(LatLon::new(0.0, -170.0)));
+        assert!(!view_state.is_visible(LatLon::new(0.0, 170.0)));
+    }
+
+    #[test]
+    fn test_visible_bounds_pitched() {
+        let flat = view_state(0.0).visible_bounds().unwrap();
+        let pitched = view_state(25.0).visible_bounds().unwrap();
+
+        // More of the ground in the north is visible
+        assert!(pitched.1.latitude > flat.1.latitude);
+        assert!(pitched.0.latitude < pitched.1.latitude);
+    }
+
+    #[test]
+    fn test_projection() {
+        let mut view_state = view_state(0.0);
+        view_state.set_projection(Equirectangular);
+
+        let lat_lon = LatLon::new(10.0, 20.0);
+        let screen = view_state.lat_lon_to_screen(lat_lon).unwrap();
+        let round_trip = view_state.screen_to_lat_lon(screen.0, screen.1).unwrap();
+
+        assert!((round_trip.latitude - lat_lon.latitude).abs() < 1e-6);
+        assert!((round_trip.longitude - lat_lon.longitude).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_world_to_screen_behind_camera() {
+        let view_state = view_state(25.0);
+        let position = view_state.camera().position();
+
+        assert!(view_state
+            .world_to_screen(WorldCoords::at_ground(position.x, position.y - 1.0e6))
+            .is_none());
+    }
+
+    #[test]
+    fn test_reduced_motion() {
+        let mut view_state = view_state(0.0);
+        view_state.set_reduced_motion(true);
+
+        let zoom = Zoom::new(12.0);
+        let berlin = WorldCoords::from_lat_lon(LatLon::new(52.520008, 13.404954), zoom);
+        view_state.animate_to(berlin, zoom, Duration::from_secs(1));
+
+        // The camera jumps to the target without advancing the animation
+        assert!(!view_state.is_animating());
+        assert_eq!(view_state.zoom(), zoom);
+        let position = view_state.camera().position();
+        assert!((position.x - berlin.x).abs() < 1e-6);
+        assert!((position.y - berlin.y).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_hash_round_trip() {
+        let window_size = WindowSize::new(800, 600).unwrap();
+
+        for hash in [
+            "#10/48.1372/11.5761",
+            "#2.5/-33.87/151.21/45",
+            "#14.25/48.13715/11.57612/350.5/20",
+            "#1/10.0/20.0/0/10",
+        ] {
+            let view_state = ViewState::from_hash(hash, window_size).unwrap();
+            assert_eq!(view_state.to_hash(), hash);
+        }
+
+        // The leading # is optional
+        let view_state = ViewState::from_hash("10/48.1372/11.5761", window_size).unwrap();
+        assert_eq!(view_state.to_hash(), "#10/48.1372/11.5761");
+    }
+
+    #[test]
+    fn test_malformed_hash() {
+        let window_size = WindowSize::new(800, 600).unwrap();
+
+        for hash in [
+            "",
+            "#",
+            "#10/48.1",
+            "#10/48.1/11.5/0/0/1",
+            "#ten/48.1/11.5",
+            "#10/91/11.5",
+            "#10/48.1/181",
+            "#-1/48.1/11.5",
+            "#10/NaN/11.5",
+            "#10//11.5",
+        ] {
+            assert!(ViewState::from_hash(hash, window_size).is_none(), "{hash}");
+        }
+    }
 }
@@ -0,0 +1,116 @@
+//! Measures the memory which is needed to decode raster tiles. The measuring allocator replaces the
+//! global allocator of the whole binary, so this test is kept apart from all other tests.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+};
+
+use image::{
+    codecs::png::{CompressionType, FilterType, PngEncoder},
+    ColorType, ImageEncoder, Rgba, RgbaImage,
+};
+use maplibre::{
+    coords::ZoomLevel,
+    io::apc::{Context, IntoMessage, SendError},
+    raster::{
+        process_raster_tile, DefaultRasterTransferables, ProcessRasterContext, RasterTileRequest,
+    },
+};
+
+thread_local! {
+    static ALLOCATED: Cell<isize> = const { Cell::new(0) };
+    static PEAK: Cell<isize> = const { Cell::new(0) };
+}
+
+/// Tracks the peak amount of bytes which are allocated by each thread, such that allocations of
+/// the test harness do not interfere.
+struct PeakAllocator;
+
+impl PeakAllocator {
+    fn track(delta: isize) {
+        let _ = ALLOCATED.try_with(|allocated| {
+            allocated.set(allocated.get() + delta);
+            let _ = PEAK.try_with(|peak| peak.set(peak.get().max(allocated.get())));
+        });
+    }
+
+    /// The peak amount of bytes which are allocated by the current thread while running `f`.
+    fn measure<R>(f: impl FnOnce() -> R) -> (R, usize) {
+        let start = ALLOCATED.with(|allocated| allocated.get());
+        PEAK.with(|peak| peak.set(start));
+        let result = f();
+        let peak = PEAK.with(|peak| peak.get());
+        (result, (peak - start) as usize)
+    }
+}
+
+unsafe impl GlobalAlloc for PeakAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            Self::track(layout.size() as isize);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        Self::track(-(layout.size() as isize));
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc_zeroed(layout);
+        if !ptr.is_null() {
+            Self::track(layout.size() as isize);
+        }
+        ptr
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = System.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() {
+            Self::track(new_size as isize - layout.size() as isize);
+        }
+        new_ptr
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: PeakAllocator = PeakAllocator;
+
+/// Drops the decoded tiles
+struct DummyContext;
+
+impl Context for DummyContext {
+    fn send<T: IntoMessage>(&self, _message: T) -> Result<(), SendError> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_decode_peak_memory() {
+    const SIZE: u32 = 2048;
+    let image = RgbaImage::from_fn(SIZE, SIZE, |x, y| Rgba([x as u8, y as u8, 0, 255]));
+
+    let mut png = Vec::new();
+    PngEncoder::new_with_quality(&mut png, CompressionType::Fast, FilterType::NoFilter)
+        .write_image(&image, SIZE, SIZE, ColorType::Rgba8)
+        .unwrap();
+
+    let mut context = ProcessRasterContext::<DefaultRasterTransferables, _>::new(DummyContext);
+    let (result, peak) = PeakAllocator::measure(|| {
+        process_raster_tile(
+            &png,
+            RasterTileRequest {
+                coords: (0, 0, ZoomLevel::default()).into(),
+            },
+            &mut context,
+        )
+    });
+    result.unwrap();
+
+    // Converting a decoded image to RGBA would hold two full copies of the image
+    let image_bytes = image.as_raw().len();
+    assert!(peak < image_bytes * 3 / 2, "{peak} > {image_bytes}");
+}
@@ -0,0 +1,119 @@
+//! Checks that layers of unsupported types are reported once and not for every tile. The logger
+//! which records the messages is global to the whole binary, so this test is kept apart from all
+//! other tests. The tiles are requested through the kernel, which requires thread-safe futures.
+#![cfg(all(feature = "headless", feature = "thread-safe-futures"))]
+
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use geozero::mvt::{tile, Message, Tile};
+use log::{Log, Metadata, Record};
+use maplibre::{
+    coords::{LatLon, Zoom},
+    headless::render_static_map,
+    io::source_client::{HttpClient, SourceFetchError},
+    style::Style,
+    window::WindowSize,
+};
+
+/// Records the messages of all log records
+struct RecordingLogger {
+    messages: Mutex<Vec<String>>,
+}
+
+impl Log for RecordingLogger {
+    fn enabled(&self, _metadata: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        self.messages
+            .lock()
+            .unwrap()
+            .push(record.args().to_string());
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: RecordingLogger = RecordingLogger {
+    messages: Mutex::new(Vec::new()),
+};
+
+/// Serves a tile which is covered by a single water polygon for all coordinates
+#[derive(Clone)]
+struct WaterHttpClient;
+
+#[cfg_attr(not(feature = "thread-safe-futures"), async_trait(?Send))]
+#[cfg_attr(feature = "thread-safe-futures", async_trait)]
+impl HttpClient for WaterHttpClient {
+    async fn fetch(&self, _url: &str) -> Result<Vec<u8>, SourceFetchError> {
+        let layer = tile::Layer {
+            version: 2,
+            name: "water".to_string(),
+            features: vec![tile::Feature {
+                id: Some(1),
+                tags: vec![],
+                r#type: Some(tile::GeomType::Polygon as i32),
+                // A square covering the whole extent of 4096
+                geometry: vec![9, 0, 0, 26, 8192, 0, 0, 8192, 8191, 0, 15],
+            }],
+            keys: vec![],
+            values: vec![],
+            extent: Some(4096),
+        };
+        Ok(Tile {
+            layers: vec![layer],
+        }
+        .encode_to_vec())
+    }
+}
+
+#[tokio::test]
+async fn test_unsupported_layer_warning() {
+    log::set_logger(&LOGGER).unwrap();
+    log::set_max_level(log::LevelFilter::Trace);
+
+    let style: Style = serde_json::from_value(serde_json::json!({
+        "version": 8,
+        "name": "Test Style",
+        "metadata": {},
+        "sources": {},
+        "layers": [
+            {
+                "id": "water",
+                "type": "fill",
+                "source-layer": "water",
+                "paint": {"fill-color": "#aad3df"}
+            },
+            {
+                "id": "poi-heat",
+                "type": "heatmap",
+                "source-layer": "water",
+                "paint": {"heatmap-radius": 10}
+            }
+        ]
+    }))
+    .unwrap();
+
+    // The view covers several tiles, all of which contain the source-layer of the heatmap
+    render_static_map(
+        style,
+        LatLon::new(48.137154, 11.576124),
+        Zoom::new(10.0),
+        WindowSize::new(512, 512).unwrap(),
+        WaterHttpClient,
+    )
+    .await
+    .unwrap();
+
+    let messages = LOGGER.messages.lock().unwrap();
+    let warnings = messages
+        .iter()
+        .filter(|message| message.contains("poi-heat"))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        warnings,
+        vec!["layer poi-heat is skipped, because layers of type heatmap are not supported"]
+    );
+}
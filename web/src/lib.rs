@@ -47,17 +47,23 @@ pub fn wasm_bindgen_start() {
     enable_tracing();
 }
 
-pub struct WHATWGOffscreenKernelEnvironment;
+pub struct WHATWGOffscreenKernelEnvironment {
+    source_client: SourceClient<WHATWGFetchHttpClient>,
+}
 
 impl OffscreenKernelEnvironment for WHATWGOffscreenKernelEnvironment {
     type HttpClient = WHATWGFetchHttpClient;
 
-    fn create() -> Self {
-        WHATWGOffscreenKernelEnvironment
+    fn create(source_client: Option<SourceClient<Self::HttpClient>>) -> Self {
+        WHATWGOffscreenKernelEnvironment {
+            source_client: source_client.unwrap_or_else(|| {
+                SourceClient::new(HttpSourceClient::new(WHATWGFetchHttpClient::default()))
+            }),
+        }
     }
 
     fn source_client(&self) -> SourceClient<Self::HttpClient> {
-        SourceClient::new(HttpSourceClient::new(WHATWGFetchHttpClient::default()))
+        self.source_client.clone()
     }
 }
 
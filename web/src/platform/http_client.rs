@@ -1,6 +1,6 @@
 use async_trait::async_trait;
 use js_sys::{ArrayBuffer, Uint8Array};
-use maplibre::io::source_client::{HttpClient, SourceFetchError};
+use maplibre::io::source_client::{HttpClient, SourceFetchError, TileTooLargeError};
 use wasm_bindgen::{prelude::*, JsCast};
 use wasm_bindgen_futures::JsFuture;
 use web_sys::{Request, RequestInit, Response, WorkerGlobalScope};
@@ -11,7 +11,7 @@ use crate::error::WebError;
 pub struct WHATWGFetchHttpClient;
 
 impl WHATWGFetchHttpClient {
-    async fn fetch_array_buffer(url: &str) -> Result<JsValue, WebError> {
+    async fn fetch_response(url: &str) -> Result<Response, WebError> {
         let mut opts = RequestInit::new();
         opts.method("GET");
 
@@ -28,14 +28,12 @@ impl WHATWGFetchHttpClient {
         let response: Response = maybe_response
             .dyn_into()
             .map_err(|_e| WebError::TypeError("Unable to cast to Response".into()))?;
+        Ok(response)
+    }
 
+    async fn read_bytes(response: Response) -> Result<Vec<u8>, WebError> {
         // Get ArrayBuffer
         let maybe_array_buffer = JsFuture::from(response.array_buffer()?).await?;
-        Ok(maybe_array_buffer)
-    }
-
-    async fn fetch_bytes(&self, url: &str) -> Result<Vec<u8>, WebError> {
-        let maybe_array_buffer = Self::fetch_array_buffer(url).await?;
 
         let array_buffer: ArrayBuffer = maybe_array_buffer
             .dyn_into()
@@ -48,6 +46,11 @@ impl WHATWGFetchHttpClient {
 
         Ok(output)
     }
+
+    async fn fetch_bytes(&self, url: &str) -> Result<Vec<u8>, WebError> {
+        let response = Self::fetch_response(url).await?;
+        Self::read_bytes(response).await
+    }
 }
 
 impl Clone for WHATWGFetchHttpClient {
@@ -63,4 +66,31 @@ impl HttpClient for WHATWGFetchHttpClient {
             .await
             .map_err(|e| SourceFetchError(Box::new(e)))
     }
+
+    async fn fetch_cached_tile(
+        &self,
+        url: &str,
+        _key: &str,
+        max_size: usize,
+    ) -> Result<(Vec<u8>, Option<bool>), SourceFetchError> {
+        let response = Self::fetch_response(url)
+            .await
+            .map_err(|e| SourceFetchError(Box::new(e)))?;
+
+        // The body is not read if its announced length is too large
+        let length = response
+            .headers()
+            .get("Content-Length")
+            .ok()
+            .flatten()
+            .and_then(|length| length.parse::<usize>().ok());
+        if let Some(size) = length.filter(|size| *size > max_size) {
+            return Err(TileTooLargeError { size, max_size }.into());
+        }
+
+        let data = Self::read_bytes(response)
+            .await
+            .map_err(|e| SourceFetchError(Box::new(e)))?;
+        Ok((data, None))
+    }
 }
@@ -17,7 +17,7 @@ use maplibre::{
     render::ShaderVertex,
     tile::Layer,
     vector::{
-        AvailableVectorLayerData, LayerIndexed, LayerMissing, LayerTessellated,
+        AvailableVectorLayerData, LayerIndexed, LayerMissing, LayerMissingReason, LayerTessellated,
         MissingVectorLayerData, TileTessellated, VectorTransferables,
     },
 };
@@ -57,7 +57,42 @@ pub mod layer_tessellated_generated {
 }
 pub mod layer_missing_generated {
     #![allow(unused, unused_imports, clippy::all)]
+
+    use maplibre::vector::LayerMissingReason;
+
     include!(concat!(env!("OUT_DIR"), "/layer_missing_generated.rs"));
+
+    impl From<LayerMissingReason> for FlatLayerMissingReason {
+        fn from(reason: LayerMissingReason) -> Self {
+            match reason {
+                LayerMissingReason::Missing => FlatLayerMissingReason::Missing,
+                LayerMissingReason::TessellationFailed => {
+                    FlatLayerMissingReason::TessellationFailed
+                }
+                LayerMissingReason::Empty => FlatLayerMissingReason::Empty,
+                LayerMissingReason::FetchFailed => FlatLayerMissingReason::FetchFailed,
+                LayerMissingReason::TooLarge => FlatLayerMissingReason::TooLarge,
+                LayerMissingReason::DecodeFailed => FlatLayerMissingReason::DecodeFailed,
+                LayerMissingReason::DeadlineExceeded => FlatLayerMissingReason::DeadlineExceeded,
+            }
+        }
+    }
+
+    impl From<FlatLayerMissingReason> for LayerMissingReason {
+        fn from(reason: FlatLayerMissingReason) -> Self {
+            match reason {
+                FlatLayerMissingReason::TessellationFailed => {
+                    LayerMissingReason::TessellationFailed
+                }
+                FlatLayerMissingReason::Empty => LayerMissingReason::Empty,
+                FlatLayerMissingReason::FetchFailed => LayerMissingReason::FetchFailed,
+                FlatLayerMissingReason::TooLarge => LayerMissingReason::TooLarge,
+                FlatLayerMissingReason::DecodeFailed => LayerMissingReason::DecodeFailed,
+                FlatLayerMissingReason::DeadlineExceeded => LayerMissingReason::DeadlineExceeded,
+                _ => LayerMissingReason::Missing,
+            }
+        }
+    }
 }
 pub mod tile_tessellated_generated {
     #![allow(unused, unused_imports, clippy::all)]
@@ -125,9 +160,15 @@ impl LayerMissing for FlatBufferTransferable {
         &WebMessageTag::LayerMissing
     }
 
-    fn build_from(coords: WorldTileCoords, layer_name: String) -> Self {
+    fn build_from(
+        coords: WorldTileCoords,
+        source: Option<String>,
+        layer_name: String,
+        reason: LayerMissingReason,
+    ) -> Self {
         let mut inner_builder = FlatBufferBuilder::with_capacity(1024);
         let layer_name = inner_builder.create_string(&layer_name);
+        let source = source.map(|source| inner_builder.create_string(&source));
 
         let mut builder = FlatLayerMissingBuilder::new(&mut inner_builder);
         builder.add_coords(&FlatWorldTileCoords::new(
@@ -136,6 +177,10 @@ impl LayerMissing for FlatBufferTransferable {
             coords.z.into(),
         ));
         builder.add_layer_name(layer_name);
+        builder.add_reason(reason.into());
+        if let Some(source) = source {
+            builder.add_source(source);
+        }
         let root = builder.finish();
 
         inner_builder.finish(root, None);
@@ -152,15 +197,27 @@ impl LayerMissing for FlatBufferTransferable {
         data.coords().unwrap().into()
     }
 
+    fn source(&self) -> Option<&str> {
+        let data = root_as_flat_layer_missing(&self.data[self.start..]).unwrap();
+        data.source()
+    }
+
     fn layer_name(&self) -> &str {
         let data = root_as_flat_layer_missing(&self.data[self.start..]).unwrap();
         data.layer_name().expect("property must be set")
     }
 
+    fn reason(&self) -> LayerMissingReason {
+        let data = root_as_flat_layer_missing(&self.data[self.start..]).unwrap();
+        data.reason().into()
+    }
+
     fn to_layer(self) -> MissingVectorLayerData {
         MissingVectorLayerData {
-            source_layer: self.layer_name().to_owned(),
+            source: LayerMissing::source(&self).map(str::to_owned),
+            source_layer: LayerMissing::layer_name(&self).to_owned(),
             coords: LayerMissing::coords(&self),
+            reason: self.reason(),
         }
     }
 }
@@ -184,6 +241,7 @@ impl LayerTessellated for FlatBufferTransferable {
 
     fn build_from(
         coords: WorldTileCoords,
+        source: Option<String>,
         buffer: OverAlignedVertexBuffer<ShaderVertex, IndexDataType>,
         feature_indices: Vec<u32>,
         layer_data: Layer,
@@ -200,7 +258,15 @@ impl LayerTessellated for FlatBufferTransferable {
         );
         let indices = inner_builder.create_vector(&buffer.buffer.indices);
         let feature_indices = inner_builder.create_vector(&feature_indices);
+        let feature_ids = inner_builder.create_vector(
+            &layer_data
+                .features
+                .iter()
+                .map(|feature| feature.id.unwrap_or_default())
+                .collect::<Vec<_>>(),
+        );
         let layer_name = inner_builder.create_string(&layer_data.name);
+        let source = source.map(|source| inner_builder.create_string(&source));
 
         let mut builder = FlatLayerTessellatedBuilder::new(&mut inner_builder);
 
@@ -213,7 +279,11 @@ impl LayerTessellated for FlatBufferTransferable {
         builder.add_vertices(vertices);
         builder.add_indices(indices);
         builder.add_feature_indices(feature_indices);
+        builder.add_feature_ids(feature_ids);
         builder.add_usable_indices(buffer.usable_indices);
+        if let Some(source) = source {
+            builder.add_source(source);
+        }
         let root = builder.finish();
 
         inner_builder.finish(root, None);
@@ -245,12 +315,15 @@ impl LayerTessellated for FlatBufferTransferable {
 
         let indices = data.indices().unwrap();
         let feature_indices: Vec<u32> = data.feature_indices().unwrap().iter().collect();
+        let feature_ids: Vec<u64> = data.feature_ids().unwrap().iter().collect();
         let usable_indices = data.usable_indices();
         AvailableVectorLayerData {
             coords: LayerTessellated::coords(&self),
+            source: data.source().map(str::to_owned),
             source_layer: data.layer_name().unwrap().to_owned(),
             buffer: OverAlignedVertexBuffer::from_iters(vertices, indices, usable_indices),
             feature_indices,
+            feature_ids,
         }
     }
 }
@@ -260,12 +333,14 @@ impl LayerIndexed for FlatBufferTransferable {
         &WebMessageTag::LayerIndexed
     }
 
-    fn build_from(coords: WorldTileCoords, _index: TileIndex) -> Self {
+    fn build_from(coords: WorldTileCoords, layer_name: String, _index: TileIndex) -> Self {
         let mut inner_builder = FlatBufferBuilder::with_capacity(1024);
+        let layer_name = inner_builder.create_string(&layer_name);
         let mut builder = FlatLayerIndexedBuilder::new(&mut inner_builder);
 
         // TODO index
 
+        builder.add_layer_name(layer_name);
         builder.add_coords(&FlatWorldTileCoords::new(
             coords.x,
             coords.y,
@@ -286,6 +361,11 @@ impl LayerIndexed for FlatBufferTransferable {
         data.coords().unwrap().into()
     }
 
+    fn layer_name(&self) -> &str {
+        let data = root_as_flat_layer_indexed(&self.data[self.start..]).unwrap();
+        data.layer_name().expect("property must be set")
+    }
+
     fn to_tile_index(self) -> TileIndex {
         TileIndex::Linear { list: vec![] } // TODO index
     }
@@ -53,7 +53,8 @@ pub async fn singlethreaded_worker_entry(procedure_ptr: u32, input: String) -> R
         );
     }
 
-    procedure(input, context, UsedOffscreenKernelEnvironment::create()).await?;
+    // The source client of the kernel can not be passed to the worker, see `KernelBuilder`
+    procedure(input, context, UsedOffscreenKernelEnvironment::create(None)).await?;
 
     Ok(())
 }